@@ -0,0 +1,103 @@
+//! Parses every class of a jar, by default the `java.base.jmod` of the JDK or the jar in
+//! `JAUST_BENCH_JAR`: decompressing the jar alone, eager parsing, and the header-only scan
+//! (class, super class and member names and descriptors) of lazy and borrowed parsing.
+//!
+//!     cargo bench --bench parse
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use jaust::class_file::{self, borrowed, ClassFile, Parsing};
+use std::fs;
+use std::io::{Cursor, Read};
+use std::path::PathBuf;
+use zip::ZipArchive;
+
+fn jar_path() -> PathBuf {
+    match std::env::var_os("JAUST_BENCH_JAR") {
+        Some(jar) => PathBuf::from(jar),
+        None => class_file::find_java_home()
+            .expect("No JDK found, set JAVA_HOME or JAUST_BENCH_JAR")
+            .join("jmods/java.base.jmod"),
+    }
+}
+
+/// The bytes of every class file of the jar, a jmod is a jar after a 4 byte header
+fn read_classes(jar: &PathBuf) -> Vec<Vec<u8>> {
+    let mut bytes = fs::read(jar).unwrap();
+    if bytes.starts_with(b"JM") {
+        bytes.drain(..4);
+    }
+    let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+    let mut classes = Vec::new();
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i).unwrap();
+        if !entry.name().ends_with(".class") || entry.name().ends_with("module-info.class") {
+            continue;
+        }
+        let mut bytes = Vec::new();
+        entry.read_to_end(&mut bytes).unwrap();
+        classes.push(bytes);
+    }
+    classes
+}
+
+/// What a scan for class names and signatures looks at
+fn scan_headers(cf: &ClassFile) -> usize {
+    let cp = &cf.constant_pool;
+    let members =
+        cf.methods.methods.iter().map(|m| {
+            cp.utf8(m.name_index).unwrap().len() + cp.utf8(m.descriptor_index).unwrap().len()
+        });
+    let fields =
+        cf.fields.fields.iter().map(|f| {
+            cp.utf8(f.name_index).unwrap().len() + cp.utf8(f.descriptor_index).unwrap().len()
+        });
+    cf.class_name().len()
+        + cf.super_class_name().map_or(0, |s| s.len())
+        + members.chain(fields).sum::<usize>()
+}
+
+fn scan_borrowed_headers(cf: &borrowed::ClassFile) -> usize {
+    let cp = &cf.constant_pool;
+    let members = cf
+        .methods
+        .iter()
+        .chain(&cf.fields)
+        .map(|m| m.name(cp).unwrap().len() + m.descriptor(cp).unwrap().len());
+    cf.class_name().unwrap().len()
+        + cf.super_class_name().unwrap().map_or(0, |s| s.len())
+        + members.sum::<usize>()
+}
+
+fn parse_jar(c: &mut Criterion) {
+    let jar = jar_path();
+    let classes = read_classes(&jar);
+    let total = classes.iter().map(Vec::len).sum::<usize>();
+
+    let mut group = c.benchmark_group(format!("{} classes", classes.len()));
+    group.throughput(Throughput::Bytes(total as u64));
+    group.sample_size(10);
+
+    group.bench_function("decompress", |b| b.iter(|| black_box(read_classes(&jar))));
+    for (name, parsing) in [("eager", Parsing::Eager), ("lazy", Parsing::Lazy)] {
+        group.bench_function(format!("{} parse", name), |b| {
+            b.iter(|| {
+                for bytes in &classes {
+                    let cf = class_file::read_class_bytes_with(bytes.clone(), parsing).unwrap();
+                    black_box(scan_headers(&cf));
+                }
+            })
+        });
+    }
+    group.bench_function("borrowed parse", |b| {
+        b.iter(|| {
+            for bytes in &classes {
+                let cf = borrowed::ClassFile::parse(bytes).unwrap();
+                black_box(scan_borrowed_headers(&cf));
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, parse_jar);
+criterion_main!(benches);
@@ -1,42 +1,211 @@
+use class_file::jaustp_cfg;
 use class_file::jaustp_summary_print;
+use class_file::jaustp_sysinfo;
 use class_file::raw_string;
 use jaust::class_file;
 
-use clap::Parser;
-use jaust::class_file::JavapOptions;
+use clap::{Parser, ValueEnum};
+use jaust::class_file::{JavapOptions, Parsing, Source, Visibility};
+use std::path::Path;
 
 #[derive(Parser)]
 struct Opts {
     #[clap(short, long, help = "Prints all classes and members")]
     pub private: bool,
 
+    #[clap(
+        long,
+        conflicts_with_all = ["private", "protected", "package"],
+        help = "Prints only public classes and members"
+    )]
+    pub public: bool,
+
+    #[clap(
+        long,
+        conflicts_with_all = ["private", "package"],
+        help = "Prints protected and public classes and members"
+    )]
+    pub protected: bool,
+
+    #[clap(
+        long,
+        conflicts_with = "private",
+        help = "Prints package, protected and public classes and members (the default)"
+    )]
+    pub package: bool,
+
     #[clap(short, long, help = "Prints full class_file structure")]
     pub raw: bool,
 
     #[clap(short, long, help = "Prints method bytecodes")]
     pub code: bool,
 
-    /// input file
-    pub file: String,
+    #[clap(long, help = "Prints the values of constant fields")]
+    pub constants: bool,
+
+    #[clap(short = 's', help = "Prints the descriptor of each field and method")]
+    pub descriptors: bool,
+
+    #[clap(
+        long,
+        help = "Prints the path, last modification date, size and SHA-256 checksum of each class"
+    )]
+    pub sysinfo: bool,
+
+    #[clap(
+        short,
+        long,
+        help = "Prints class attributes after the members (InnerClasses)"
+    )]
+    pub verbose: bool,
+
+    #[clap(
+        long = "class-path",
+        alias = "cp",
+        help = "Directories to look up super types in (defaults to the root of the input file)"
+    )]
+    pub class_path: Vec<String>,
+
+    #[clap(
+        long,
+        value_name = "METHOD",
+        help = "Prints the control flow graph of the method in the DOT format"
+    )]
+    pub cfg: Option<String>,
+
+    #[clap(
+        long,
+        value_enum,
+        default_value = "text",
+        help = "Prints javap-style text or a line of JSON per class"
+    )]
+    pub format: Format,
+
+    /// input class files, directories and jars
+    #[clap(required = true)]
+    pub files: Vec<String>,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum Format {
+    Text,
+    Json,
+}
+
+impl Opts {
+    fn visibility(&self) -> Visibility {
+        if self.public {
+            Visibility::Public
+        } else if self.protected {
+            Visibility::Protected
+        } else if self.private {
+            Visibility::Private
+        } else {
+            Visibility::Package
+        }
+    }
 }
 
-impl Into<JavapOptions> for Opts {
+#[allow(clippy::from_over_into)]
+impl Into<JavapOptions> for &Opts {
     fn into(self) -> JavapOptions {
         JavapOptions {
-            private: self.private,
+            visibility: self.visibility(),
             code: self.code,
+            constants: self.constants,
+            verbose: self.verbose,
+            class_path: self.class_path.clone(),
+            descriptors: self.descriptors,
+            sysinfo: self.sysinfo,
         }
     }
 }
 
+/// The directory the class file's package hierarchy starts in,
+/// e.g. `out/com/example/Foo.class` with class `com/example/Foo` -> `out`
+fn class_root(file: &str, class_name: &str) -> Option<String> {
+    let suffix = format!("{}.class", class_name);
+    let path = Path::new(file);
+    let mut root = path.to_path_buf();
+    for _ in Path::new(&suffix).components() {
+        root.pop();
+    }
+    if root.join(&suffix) != path {
+        return None;
+    }
+    Some(root.to_str()?.to_string())
+}
+
 fn main() {
     let ops = Opts::parse();
 
-    let cf = class_file::read_class_file(&ops.file).unwrap();
-    if ops.raw {
-        println!("{}", raw_string(&cf));
-        return;
+    // the class files are parsed in parallel and printed in the order of the inputs, one that
+    // fails is reported without stopping the others
+    let mut failed = false;
+    for parsed in class_file::parse_all(&ops.files, Parsing::Eager) {
+        let cf = match parsed.class_file {
+            Ok(cf) => cf,
+            Err(e) => {
+                eprintln!("{}: {}", parsed.source, e);
+                failed = true;
+                continue;
+            }
+        };
+
+        if ops.raw {
+            println!("{}", raw_string(&cf));
+            continue;
+        }
+
+        if let Some(method) = &ops.cfg {
+            match jaustp_cfg(&cf, method) {
+                Ok(dot) => print!("{}", dot),
+                Err(e) => {
+                    eprintln!("{}: {}", parsed.source, e);
+                    failed = true;
+                }
+            }
+            continue;
+        }
+
+        let mut javap_ops: JavapOptions = (&ops).into();
+        if javap_ops.class_path.is_empty() {
+            if let Source::File(file) = &parsed.source {
+                javap_ops
+                    .class_path
+                    .extend(file.to_str().and_then(|f| class_root(f, &cf.class_name())));
+            }
+        }
+        let printed = match ops.format {
+            Format::Text => {
+                if ops.sysinfo {
+                    match jaustp_sysinfo(&parsed.source) {
+                        Ok(sysinfo) => print!("{}", sysinfo),
+                        Err(e) => {
+                            eprintln!("{}: {}", parsed.source, e);
+                            failed = true;
+                        }
+                    }
+                }
+                jaustp_summary_print(&cf, &javap_ops)
+            }
+            #[cfg(feature = "json")]
+            Format::Json => {
+                class_file::jaustp_json(&cf, &javap_ops).map(|json| println!("{}", json))
+            }
+            #[cfg(not(feature = "json"))]
+            Format::Json => {
+                eprintln!("jaustp was built without the json feature");
+                std::process::exit(1);
+            }
+        };
+        if let Err(e) = printed {
+            eprintln!("{}: {}", parsed.source, e);
+            failed = true;
+        }
     }
 
-    jaustp_summary_print(&cf, &ops.into());
+    if failed {
+        std::process::exit(1);
+    }
 }
@@ -1,6 +1,11 @@
 pub struct AccessFlags(u16);
 
 #[derive(Debug, PartialEq)]
+#[cfg_attr(
+    feature = "json",
+    derive(serde::Serialize),
+    serde(rename_all = "lowercase")
+)]
 pub enum AccessFlag {
     Public,
     Final,
@@ -17,6 +22,11 @@ impl AccessFlags {
         AccessFlags(flags)
     }
 
+    /// The flags as written in the class file
+    pub fn bits(&self) -> u16 {
+        self.0
+    }
+
     pub fn flag_vector(&self) -> Vec<AccessFlag> {
         let mut flags = Vec::new();
         add_flag(&mut flags, self.0, 0x0001, AccessFlag::Public);
@@ -36,3 +46,35 @@ fn add_flag(flags: &mut Vec<AccessFlag>, bit_flags: u16, bit_mask: u16, access_f
         flags.push(access_flag);
     }
 }
+
+/// How accessible a member or inner class has to be to be printed, like the `-public`,
+/// `-protected`, `-package` (the default) and `-private` options of javap. Each level
+/// also shows the members of the levels before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Visibility {
+    Public,
+    Protected,
+    #[default]
+    Package,
+    Private,
+}
+
+impl Visibility {
+    /// The visibility of a member from its access flags
+    pub fn of(public: bool, protected: bool, private: bool) -> Visibility {
+        if public {
+            Visibility::Public
+        } else if protected {
+            Visibility::Protected
+        } else if private {
+            Visibility::Private
+        } else {
+            Visibility::Package
+        }
+    }
+
+    /// Whether members with the visibility `access` are printed
+    pub fn shows(self, access: Visibility) -> bool {
+        access <= self
+    }
+}
@@ -0,0 +1,487 @@
+use super::lexer::{parse_int, Token};
+use crate::class_file::builder::{Constant, Constants, Jump};
+use crate::class_file::bytecode::ByteCode;
+use crate::class_file::constant_pool::{
+    ConstantPool, FieldRef, MemberRef, MethodHandle, MethodHandleReferenceKind, MethodRef,
+};
+use crate::class_file::MethodDescriptor;
+use anyhow::{anyhow, Result};
+
+/// A parsed instruction, a branch still names its label
+#[derive(Debug)]
+pub(super) enum Instruction {
+    /// Written in `len` bytes if the form is given (`iload_1`, `iload 1` or `wide iload 1`)
+    Fixed(ByteCode, Option<u32>),
+    Jump(Jump, String),
+}
+
+/// The tokens of a directive or instruction after its name
+pub(super) struct Operands<'a> {
+    tokens: &'a [Token],
+    position: usize,
+}
+
+impl<'a> Operands<'a> {
+    pub fn new(tokens: &'a [Token]) -> Operands<'a> {
+        Operands {
+            tokens,
+            position: 0,
+        }
+    }
+
+    pub fn peek(&self) -> Option<&'a Token> {
+        self.tokens.get(self.position)
+    }
+
+    pub fn next(&mut self) -> Result<&'a Token> {
+        let token = self.peek().ok_or(anyhow!("Missing operand"))?;
+        self.position += 1;
+        Ok(token)
+    }
+
+    pub fn word(&mut self) -> Result<&'a str> {
+        match self.next()? {
+            Token::Word(word) => Ok(word),
+            Token::Str(s) => Err(anyhow!("Expected a name, not \"{}\"", s)),
+        }
+    }
+
+    /// Consumes `keyword` if it is the next word
+    pub fn keyword(&mut self, keyword: &str) -> bool {
+        let found = self.peek().and_then(Token::word) == Some(keyword);
+        self.position += found as usize;
+        found
+    }
+
+    pub fn expect(&mut self, keyword: &str) -> Result<()> {
+        match self.keyword(keyword) {
+            true => Ok(()),
+            false => Err(anyhow!("Expected {}", keyword)),
+        }
+    }
+
+    pub fn int<T: TryFrom<i64>>(&mut self) -> Result<T> {
+        let word = self.word()?;
+        T::try_from(parse_int(word)?).map_err(|_| anyhow!("{} is out of range", word))
+    }
+
+    /// A string in quotes or a single word
+    pub fn text(&mut self) -> Result<&'a str> {
+        match self.next()? {
+            Token::Str(s) | Token::Word(s) => Ok(s),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.position == self.tokens.len()
+    }
+
+    pub fn end(&self) -> Result<()> {
+        match self.peek() {
+            None => Ok(()),
+            Some(Token::Word(word)) => Err(anyhow!("Unexpected {}", word)),
+            Some(Token::Str(s)) => Err(anyhow!("Unexpected \"{}\"", s)),
+        }
+    }
+}
+
+/// Parses an instruction other than the switches, adding its constants to the pool
+pub(super) fn parse(
+    mnemonic: &str,
+    operands: &mut Operands,
+    constants: &mut Constants,
+) -> Result<Instruction> {
+    if let Some(instruction) = no_operands(mnemonic) {
+        return Ok(Instruction::Fixed(instruction, None));
+    }
+    if let Some((instruction, index)) = short_form(mnemonic) {
+        return Ok(Instruction::Fixed(instruction(index), Some(1)));
+    }
+    if mnemonic == "wide" {
+        let mnemonic = operands.word()?;
+        let index = operands.int()?;
+        let instruction = match (local_variable(mnemonic), mnemonic) {
+            (Some(instruction), _) => Instruction::Fixed(instruction(index), Some(4)),
+            (None, "iinc") => Instruction::Fixed(ByteCode::IInc(index, operands.int()?), Some(6)),
+            _ => return Err(anyhow!("{} has no wide form", mnemonic)),
+        };
+        return Ok(instruction);
+    }
+    if let Some(instruction) = local_variable(mnemonic) {
+        let index: u16 = operands.int()?;
+        let len = if index <= u8::MAX as u16 { 2 } else { 4 };
+        return Ok(Instruction::Fixed(instruction(index), Some(len)));
+    }
+    if let Some(jump) = jump(mnemonic) {
+        return Ok(Instruction::Jump(jump, operands.word()?.to_string()));
+    }
+
+    let cp = &mut constants.cp;
+    let instruction = match mnemonic {
+        "bipush" => ByteCode::BiPush(operands.int()?),
+        "sipush" => ByteCode::SiPush(operands.int()?),
+        "iinc" => {
+            let index: u16 = operands.int()?;
+            let delta: i16 = operands.int()?;
+            let fits = index <= u8::MAX as u16 && i8::try_from(delta).is_ok();
+            let len = if fits { 3 } else { 6 };
+            return Ok(Instruction::Fixed(ByteCode::IInc(index, delta), Some(len)));
+        }
+        "ldc" | "ldc_w" | "ldc2_w" => {
+            let constant = constant(operands)?;
+            let index = constant.add(&mut constants.cp)?;
+            let wide = matches!(constant, Constant::Long(_) | Constant::Double(_));
+            match (mnemonic, wide) {
+                ("ldc2_w", true) => ByteCode::Ldc2W(index),
+                ("ldc2_w", false) => return Err(anyhow!("ldc2_w only loads longs and doubles")),
+                (_, true) => return Err(anyhow!("Longs and doubles are loaded with ldc2_w")),
+                ("ldc_w", false) => ByteCode::LdcW(index),
+                _ => {
+                    ByteCode::Ldc(u8::try_from(index).map_err(|_| {
+                        anyhow!("Constant #{} doesn't fit in ldc, use ldc_w", index)
+                    })?)
+                }
+            }
+        }
+        "getstatic" => ByteCode::GetStatic(field_ref(operands, cp)?),
+        "putstatic" => ByteCode::PutStatic(field_ref(operands, cp)?),
+        "getfield" => ByteCode::GetField(field_ref(operands, cp)?),
+        "putfield" => ByteCode::PutField(field_ref(operands, cp)?),
+        "invokevirtual" => ByteCode::InvokeVirtual(method_ref(operands, cp, false)?.0),
+        "invokespecial" => ByteCode::InvokeSpecial(method_ref(operands, cp, false)?.0),
+        "invokestatic" => ByteCode::InvokeStatic(method_ref(operands, cp, false)?.0),
+        "invokeinterface" => {
+            let (index, method) = method_ref(operands, cp, true)?;
+            let count = match operands.is_empty() {
+                true => MethodDescriptor::parse(&method.descriptor)?.args_size(false) as u8,
+                false => operands.int()?,
+            };
+            ByteCode::InvokeInterface(index, count)
+        }
+        "invokedynamic" => ByteCode::InvokeDynamic(invoke_dynamic(operands, constants)?),
+        "new" => ByteCode::New(cp.add_class(operands.word()?)?),
+        "anewarray" => ByteCode::ANewArray(cp.add_class(operands.word()?)?),
+        "checkcast" => ByteCode::CheckCast(cp.add_class(operands.word()?)?),
+        "instanceof" => ByteCode::InstanceOf(cp.add_class(operands.word()?)?),
+        "multianewarray" => {
+            let class = cp.add_class(operands.word()?)?;
+            ByteCode::MultiANewArray(class, operands.int()?)
+        }
+        "newarray" => {
+            let element = operands.word()?;
+            let types = [
+                "boolean", "char", "float", "double", "byte", "short", "int", "long",
+            ];
+            let atype = types
+                .iter()
+                .position(|t| *t == element)
+                .ok_or(anyhow!("Invalid array type {}", element))?;
+            ByteCode::NewArray(atype as u8 + 4)
+        }
+        "breakpoint" => ByteCode::Generic(0xca),
+        "impdep1" => ByteCode::Generic(0xfe),
+        "impdep2" => ByteCode::Generic(0xff),
+        _ => return Err(anyhow!("Unknown instruction {}", mnemonic)),
+    };
+    Ok(Instruction::Fixed(instruction, None))
+}
+
+fn no_operands(mnemonic: &str) -> Option<ByteCode> {
+    let instruction = match mnemonic {
+        "nop" => ByteCode::Nop,
+        "aconst_null" => ByteCode::AConstNull,
+        "iconst_m1" => ByteCode::IConstn(-1),
+        "iconst_0" => ByteCode::IConstn(0),
+        "iconst_1" => ByteCode::IConstn(1),
+        "iconst_2" => ByteCode::IConstn(2),
+        "iconst_3" => ByteCode::IConstn(3),
+        "iconst_4" => ByteCode::IConstn(4),
+        "iconst_5" => ByteCode::IConstn(5),
+        "lconst_0" => ByteCode::LConstn(0),
+        "lconst_1" => ByteCode::LConstn(1),
+        "fconst_0" => ByteCode::FConstn(0),
+        "fconst_1" => ByteCode::FConstn(1),
+        "fconst_2" => ByteCode::FConstn(2),
+        "dconst_0" => ByteCode::DConstn(0),
+        "dconst_1" => ByteCode::DConstn(1),
+        "iaload" => ByteCode::IALoad,
+        "laload" => ByteCode::LALoad,
+        "faload" => ByteCode::FALoad,
+        "daload" => ByteCode::DALoad,
+        "aaload" => ByteCode::AALoad,
+        "baload" => ByteCode::BALoad,
+        "caload" => ByteCode::CALoad,
+        "saload" => ByteCode::SALoad,
+        "iastore" => ByteCode::IAStore,
+        "lastore" => ByteCode::LAStore,
+        "fastore" => ByteCode::FAStore,
+        "dastore" => ByteCode::DAStore,
+        "aastore" => ByteCode::AAStore,
+        "bastore" => ByteCode::BAStore,
+        "castore" => ByteCode::CAStore,
+        "sastore" => ByteCode::SAStore,
+        "pop" => ByteCode::Pop,
+        "pop2" => ByteCode::Pop2,
+        "dup" => ByteCode::Duplicate,
+        "dup_x1" => ByteCode::DupX1,
+        "dup_x2" => ByteCode::DupX2,
+        "dup2" => ByteCode::Dup2,
+        "dup2_x1" => ByteCode::Dup2X1,
+        "dup2_x2" => ByteCode::Dup2X2,
+        "swap" => ByteCode::Swap,
+        "iadd" => ByteCode::IAdd,
+        "ladd" => ByteCode::LAdd,
+        "fadd" => ByteCode::FAdd,
+        "dadd" => ByteCode::DAdd,
+        "isub" => ByteCode::ISub,
+        "lsub" => ByteCode::LSub,
+        "fsub" => ByteCode::FSub,
+        "dsub" => ByteCode::DSub,
+        "imul" => ByteCode::IMul,
+        "lmul" => ByteCode::LMul,
+        "fmul" => ByteCode::FMul,
+        "dmul" => ByteCode::DMul,
+        "idiv" => ByteCode::IDiv,
+        "ldiv" => ByteCode::LDiv,
+        "fdiv" => ByteCode::FDiv,
+        "ddiv" => ByteCode::DDiv,
+        "irem" => ByteCode::IRem,
+        "lrem" => ByteCode::LRem,
+        "frem" => ByteCode::FRem,
+        "drem" => ByteCode::DRem,
+        "ineg" => ByteCode::INeg,
+        "lneg" => ByteCode::LNeg,
+        "fneg" => ByteCode::FNeg,
+        "dneg" => ByteCode::DNeg,
+        "ishl" => ByteCode::IShl,
+        "lshl" => ByteCode::LShl,
+        "ishr" => ByteCode::IShr,
+        "lshr" => ByteCode::LShr,
+        "iushr" => ByteCode::IUShr,
+        "lushr" => ByteCode::LUShr,
+        "iand" => ByteCode::IAnd,
+        "land" => ByteCode::LAnd,
+        "ior" => ByteCode::IOr,
+        "lor" => ByteCode::LOr,
+        "ixor" => ByteCode::IXor,
+        "lxor" => ByteCode::LXor,
+        "i2l" => ByteCode::I2L,
+        "i2f" => ByteCode::I2F,
+        "i2d" => ByteCode::I2D,
+        "l2i" => ByteCode::L2i,
+        "l2f" => ByteCode::L2F,
+        "l2d" => ByteCode::L2D,
+        "f2i" => ByteCode::F2I,
+        "f2l" => ByteCode::F2L,
+        "f2d" => ByteCode::F2D,
+        "d2i" => ByteCode::D2I,
+        "d2l" => ByteCode::D2L,
+        "d2f" => ByteCode::D2F,
+        "i2b" => ByteCode::I2B,
+        "i2c" => ByteCode::I2C,
+        "i2s" => ByteCode::I2S,
+        "lcmp" => ByteCode::Lcmp,
+        "fcmpl" => ByteCode::FCmpl,
+        "fcmpg" => ByteCode::FCmpg,
+        "dcmpl" => ByteCode::DCmpl,
+        "dcmpg" => ByteCode::DCmpg,
+        "ireturn" => ByteCode::IReturn,
+        "lreturn" => ByteCode::LReturn,
+        "freturn" => ByteCode::FReturn,
+        "dreturn" => ByteCode::DReturn,
+        "areturn" => ByteCode::AReturn,
+        "return" => ByteCode::Return,
+        "arraylength" => ByteCode::ArrayLength,
+        "athrow" => ByteCode::Athrow,
+        "monitorenter" => ByteCode::MonitorEnter,
+        "monitorexit" => ByteCode::MonitorExit,
+        _ => return None,
+    };
+    Some(instruction)
+}
+
+/// Makes an instruction from its local variable index
+type LocalVariableInstruction = fn(u16) -> ByteCode;
+
+/// The instructions that take a local variable index
+fn local_variable(mnemonic: &str) -> Option<LocalVariableInstruction> {
+    let instruction: LocalVariableInstruction = match mnemonic {
+        "iload" => ByteCode::ILoad,
+        "lload" => ByteCode::Lload,
+        "fload" => ByteCode::FLoad,
+        "dload" => ByteCode::DLoad,
+        "aload" => ByteCode::ALoad,
+        "istore" => ByteCode::IStore,
+        "lstore" => ByteCode::LStore,
+        "fstore" => ByteCode::FStore,
+        "dstore" => ByteCode::DStore,
+        "astore" => ByteCode::AStore,
+        "ret" => ByteCode::Ret,
+        _ => return None,
+    };
+    Some(instruction)
+}
+
+/// `iload_0` to `astore_3`
+fn short_form(mnemonic: &str) -> Option<(LocalVariableInstruction, u16)> {
+    let (name, index) = mnemonic.split_once('_')?;
+    let index = match index {
+        "0" => 0,
+        "1" => 1,
+        "2" => 2,
+        "3" => 3,
+        _ => return None,
+    };
+    match name {
+        "ret" => None,
+        name => local_variable(name).map(|instruction| (instruction, index)),
+    }
+}
+
+fn jump(mnemonic: &str) -> Option<Jump> {
+    let jump = match mnemonic {
+        "ifeq" => Jump::Short(ByteCode::Ifeq),
+        "ifne" => Jump::Short(ByteCode::Ifne),
+        "iflt" => Jump::Short(ByteCode::Iflt),
+        "ifge" => Jump::Short(ByteCode::Ifge),
+        "ifgt" => Jump::Short(ByteCode::Ifgt),
+        "ifle" => Jump::Short(ByteCode::Ifle),
+        "if_icmpeq" => Jump::Short(ByteCode::IfICmpEq),
+        "if_icmpne" => Jump::Short(ByteCode::IfICmpNe),
+        "if_icmplt" => Jump::Short(ByteCode::IfICmpLt),
+        "if_icmpge" => Jump::Short(ByteCode::IfICmpGe),
+        "if_icmpgt" => Jump::Short(ByteCode::IfICmpGt),
+        "if_icmple" => Jump::Short(ByteCode::IfICmpLe),
+        "if_acmpeq" => Jump::Short(ByteCode::IfACmpEq),
+        "if_acmpne" => Jump::Short(ByteCode::IfACmpNe),
+        "goto" => Jump::Short(ByteCode::Goto),
+        "jsr" => Jump::Short(ByteCode::Jsr),
+        "ifnull" => Jump::Short(ByteCode::IfNull),
+        "ifnonnull" => Jump::Short(ByteCode::IfNonNull),
+        "goto_w" => Jump::Wide(ByteCode::GotoW),
+        "jsr_w" => Jump::Wide(ByteCode::JsrW),
+        _ => return None,
+    };
+    Some(jump)
+}
+
+/// `Owner.name:descriptor`, the name possibly in quotes like javap prints `"<init>"`
+fn member(word: &str) -> Result<(String, String, String)> {
+    let invalid = || anyhow!("Expected Owner.name:descriptor, not {}", word);
+    let (owner_and_name, descriptor) = word.split_once(':').ok_or_else(invalid)?;
+    let (owner, name) = owner_and_name.rsplit_once('.').ok_or_else(invalid)?;
+    let name = name.trim_matches('"');
+    if owner.is_empty() || name.is_empty() || descriptor.is_empty() {
+        return Err(invalid());
+    }
+    Ok((owner.to_string(), name.to_string(), descriptor.to_string()))
+}
+
+fn field_ref(operands: &mut Operands, cp: &mut ConstantPool) -> Result<u16> {
+    operands.keyword("Field");
+    cp.add_field_ref(&parse_field(operands.word()?)?)
+}
+
+fn parse_field(word: &str) -> Result<FieldRef> {
+    let (owner, name, descriptor) = member(word)?;
+    Ok(FieldRef {
+        owner,
+        name,
+        descriptor,
+    })
+}
+
+/// A Methodref, or an InterfaceMethodref after `InterfaceMethod` or if `is_interface`
+fn method_ref(
+    operands: &mut Operands,
+    cp: &mut ConstantPool,
+    is_interface: bool,
+) -> Result<(u16, MethodRef)> {
+    let method = parse_method(operands, is_interface)?;
+    Ok((cp.add_method_ref(&method)?, method))
+}
+
+fn parse_method(operands: &mut Operands, is_interface: bool) -> Result<MethodRef> {
+    let is_interface = match operands.keyword("InterfaceMethod") {
+        true => true,
+        false => !operands.keyword("Method") && is_interface,
+    };
+    let (owner, name, descriptor) = member(operands.word()?)?;
+    Ok(MethodRef {
+        owner,
+        name,
+        descriptor,
+        is_interface,
+    })
+}
+
+/// `kind Owner.name:descriptor`, e.g. `invokeStatic Example.bootstrap:(...)...`
+fn method_handle(operands: &mut Operands) -> Result<MethodHandle> {
+    let word = operands.word()?;
+    let kind = MethodHandleReferenceKind::from_name(word)
+        .ok_or(anyhow!("Invalid method handle kind {}", word))?;
+    let reference = match kind.is_field() {
+        true => {
+            operands.keyword("Field");
+            MemberRef::Field(parse_field(operands.word()?)?)
+        }
+        false => MemberRef::Method(parse_method(
+            operands,
+            kind == MethodHandleReferenceKind::RefInvokeInterface,
+        )?),
+    };
+    Ok(MethodHandle { kind, reference })
+}
+
+/// A loadable constant as javap prints them: `"string"`, `42`, `1.5f`, `10L`, `2.5d`
+/// (or any number with a `.`), `class Name`, `MethodType descriptor` and
+/// `MethodHandle kind Owner.name:descriptor`
+pub(super) fn constant(operands: &mut Operands) -> Result<Constant> {
+    let word = match operands.next()? {
+        Token::Str(s) => return Ok(Constant::String(s.clone())),
+        Token::Word(word) => word.as_str(),
+    };
+    let number = |digits: &str| digits.replace("Infinity", "inf");
+    let constant = match word {
+        "class" => Constant::Class(operands.word()?.to_string()),
+        "MethodType" => Constant::MethodType(operands.word()?.to_string()),
+        "MethodHandle" => Constant::MethodHandle(method_handle(operands)?),
+        _ if word.ends_with(['f', 'F']) && !is_hex(word) => {
+            let value = number(&word[..word.len() - 1]).parse::<f32>();
+            Constant::Float(value.map_err(|_| anyhow!("Invalid float {}", word))?)
+        }
+        _ if word.ends_with(['l', 'L']) => Constant::Long(parse_int(&word[..word.len() - 1])?),
+        _ if word.ends_with(['d', 'D']) && !is_hex(word) => {
+            let value = number(&word[..word.len() - 1]).parse::<f64>();
+            Constant::Double(value.map_err(|_| anyhow!("Invalid double {}", word))?)
+        }
+        _ if word.contains('.') || word.contains("NaN") || word.contains("Infinity") => {
+            let value = number(word).parse::<f64>();
+            Constant::Double(value.map_err(|_| anyhow!("Invalid double {}", word))?)
+        }
+        _ => {
+            let value = i32::try_from(parse_int(word)?);
+            Constant::Int(value.map_err(|_| anyhow!("{} is out of range", word))?)
+        }
+    };
+    Ok(constant)
+}
+
+fn is_hex(word: &str) -> bool {
+    word.trim_start_matches('-').starts_with("0x")
+}
+
+/// `name:descriptor kind Owner.name:descriptor [arguments]`, the call site with its
+/// bootstrap method handle and constant arguments
+fn invoke_dynamic(operands: &mut Operands, constants: &mut Constants) -> Result<u16> {
+    let word = operands.word()?;
+    let (name, descriptor) = word
+        .split_once(':')
+        .ok_or(anyhow!("Expected name:descriptor, not {}", word))?;
+    let bootstrap = method_handle(operands)?;
+    let mut arguments = Vec::new();
+    while !operands.is_empty() {
+        arguments.push(constant(operands)?.add(&mut constants.cp)?);
+    }
+    constants.add_invoke_dynamic(name, descriptor, &bootstrap, arguments)
+}
@@ -0,0 +1,139 @@
+use anyhow::{anyhow, Result};
+
+/// A word or a quoted string of a line of assembly
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum Token {
+    Word(String),
+    /// A string in double quotes with its escapes resolved
+    Str(String),
+}
+
+/// The tokens of a line that isn't blank or a comment, with its 1 based line number
+#[derive(Debug)]
+pub(super) struct Line {
+    pub number: usize,
+    pub tokens: Vec<Token>,
+}
+
+impl Token {
+    pub fn word(&self) -> Option<&str> {
+        match self {
+            Token::Word(word) => Some(word),
+            Token::Str(_) => None,
+        }
+    }
+}
+
+/// Splits the source into lines of tokens separated by whitespace. A `;` at the start
+/// of a word starts a comment, so descriptors like `Ljava/lang/String;` are not cut.
+pub(super) fn tokenize(source: &str) -> Result<Vec<Line>> {
+    let mut lines = Vec::new();
+    for (i, text) in source.lines().enumerate() {
+        let number = i + 1;
+        let tokens = tokenize_line(text).map_err(|e| anyhow!("line {}: {}", number, e))?;
+        if !tokens.is_empty() {
+            lines.push(Line { number, tokens });
+        }
+    }
+    Ok(lines)
+}
+
+fn tokenize_line(text: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == ';' {
+            break;
+        } else if c == '"' {
+            chars.next();
+            tokens.push(Token::Str(string(&mut chars)?));
+        } else {
+            let mut word = String::new();
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() {
+                    break;
+                }
+                // a quoted member name like java/lang/Object."<init>":()V is one word
+                if c == '"' {
+                    word.push(c);
+                    chars.next();
+                    for c in chars.by_ref() {
+                        word.push(c);
+                        if c == '"' {
+                            break;
+                        }
+                    }
+                    continue;
+                }
+                word.push(c);
+                chars.next();
+            }
+            tokens.push(Token::Word(word));
+        }
+    }
+    Ok(tokens)
+}
+
+/// The rest of a string after the opening quote with the Java escapes resolved,
+/// `\uXXXX` surrogate pairs included
+fn string(chars: &mut impl Iterator<Item = char>) -> Result<String> {
+    let mut units: Vec<u16> = Vec::new();
+    loop {
+        let c = chars.next().ok_or(anyhow!("Unterminated string"))?;
+        let c = match c {
+            '"' => break,
+            '\\' => match chars.next().ok_or(anyhow!("Unterminated string"))? {
+                'n' => '\n',
+                't' => '\t',
+                'r' => '\r',
+                'b' => '\u{8}',
+                'f' => '\u{c}',
+                '0' => '\0',
+                '"' => '"',
+                '\'' => '\'',
+                '\\' => '\\',
+                'u' => {
+                    let hex: String = chars.by_ref().take(4).collect();
+                    let unit = u16::from_str_radix(&hex, 16)
+                        .map_err(|_| anyhow!("Invalid escape \\u{}", hex))?;
+                    units.push(unit);
+                    continue;
+                }
+                c => return Err(anyhow!("Invalid escape \\{}", c)),
+            },
+            c => c,
+        };
+        let mut buf = [0; 2];
+        units.extend_from_slice(c.encode_utf16(&mut buf));
+    }
+    String::from_utf16(&units).map_err(|_| anyhow!("Unpaired surrogate in string"))
+}
+
+/// An int literal, decimal or `0x` hexadecimal, possibly negative
+pub(super) fn parse_int(word: &str) -> Result<i64> {
+    let invalid = || anyhow!("Invalid number {}", word);
+    let (negative, digits) = match word.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, word),
+    };
+    match digits.strip_prefix("0x").or(digits.strip_prefix("0X")) {
+        Some(hex) => {
+            let value = i64::from_str_radix(hex, 16).map_err(|_| invalid())?;
+            Ok(if negative { -value } else { value })
+        }
+        None => word.parse::<i64>().map_err(|_| invalid()),
+    }
+}
+
+/// Parses bytes written in hexadecimal, e.g. `cafe0001`
+pub(super) fn parse_hex(hex: &str) -> Result<Vec<u8>> {
+    if !hex.is_ascii() || !hex.len().is_multiple_of(2) {
+        return Err(anyhow!("Invalid hex {}", hex));
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| anyhow!("Invalid hex {}", hex)))
+        .collect()
+}
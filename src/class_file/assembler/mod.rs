@@ -0,0 +1,617 @@
+//! Assembles class files from a textual format.
+//!
+//! The format follows Jasmin: directives start with a `.`, instructions use the JVMS
+//! mnemonics and branch to labels, and `;` starts a comment. The constant pool,
+//! `max_stack`, `max_locals` and the StackMapTable are built from the instructions.
+//!
+//! ```text
+//! .version 52
+//! .class public Hello
+//! .super java/lang/Object
+//!
+//! .method public static main([Ljava/lang/String;)V
+//!     getstatic java/lang/System.out:Ljava/io/PrintStream;
+//!     ldc "Hello"
+//!     invokevirtual java/io/PrintStream.println:(Ljava/lang/String;)V
+//!     return
+//! .end method
+//! ```
+
+mod instructions;
+mod lexer;
+
+use super::attributes::{
+    Attribute, DeprecatedAttribute, EnclosingMethodAttribute, ExceptionsAttribute,
+    GenericAttribute, InnerClassInfo, InnerClassesAttribute, NestHostAttribute,
+    NestMembersAttribute, PermittedSubclassesAttribute, SignatureAttribute, SyntheticAttribute,
+};
+use super::builder::*;
+use super::constant_pool::ConstantPool;
+use super::{ClassFile, ClassHierarchy};
+use instructions::{Instruction, Operands};
+use lexer::{Line, Token};
+
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+
+const CLASS_FLAGS: &[(&str, u16)] = &[
+    ("public", ACC_PUBLIC),
+    ("final", ACC_FINAL),
+    ("super", ACC_SUPER),
+    ("interface", ACC_INTERFACE),
+    ("abstract", ACC_ABSTRACT),
+    ("synthetic", ACC_SYNTHETIC),
+    ("annotation", ACC_ANNOTATION),
+    ("enum", ACC_ENUM),
+    ("module", ACC_MODULE),
+];
+
+const FIELD_FLAGS: &[(&str, u16)] = &[
+    ("public", ACC_PUBLIC),
+    ("private", ACC_PRIVATE),
+    ("protected", ACC_PROTECTED),
+    ("static", ACC_STATIC),
+    ("final", ACC_FINAL),
+    ("volatile", ACC_VOLATILE),
+    ("transient", ACC_TRANSIENT),
+    ("synthetic", ACC_SYNTHETIC),
+    ("enum", ACC_ENUM),
+];
+
+const METHOD_FLAGS: &[(&str, u16)] = &[
+    ("public", ACC_PUBLIC),
+    ("private", ACC_PRIVATE),
+    ("protected", ACC_PROTECTED),
+    ("static", ACC_STATIC),
+    ("final", ACC_FINAL),
+    ("synchronized", ACC_SYNCHRONIZED),
+    ("bridge", ACC_BRIDGE),
+    ("varargs", ACC_VARARGS),
+    ("native", ACC_NATIVE),
+    ("abstract", ACC_ABSTRACT),
+    ("strict", ACC_STRICT),
+    ("synthetic", ACC_SYNTHETIC),
+];
+
+const INNER_CLASS_FLAGS: &[(&str, u16)] = &[
+    ("public", ACC_PUBLIC),
+    ("private", ACC_PRIVATE),
+    ("protected", ACC_PROTECTED),
+    ("static", ACC_STATIC),
+    ("final", ACC_FINAL),
+    ("interface", ACC_INTERFACE),
+    ("abstract", ACC_ABSTRACT),
+    ("synthetic", ACC_SYNTHETIC),
+    ("annotation", ACC_ANNOTATION),
+    ("enum", ACC_ENUM),
+];
+
+/// Assembles the class described by `source`. `hierarchy` is used to merge the types
+/// of the frames, errors name the line they were found on.
+pub fn assemble(source: &str, hierarchy: &dyn ClassHierarchy) -> Result<ClassFile> {
+    let mut assembler = Assembler {
+        constants: Constants::default(),
+        major_version: 52,
+        minor_version: 0,
+        access_flags: 0,
+        name: None,
+        super_class: None,
+        interfaces: Vec::new(),
+        source_file: None,
+        fields: Vec::new(),
+        methods: Vec::new(),
+        attributes: Vec::new(),
+        inner_classes: Vec::new(),
+        nest_members: Vec::new(),
+        permitted_subclasses: Vec::new(),
+        scope: Scope::Class,
+    };
+    for line in lexer::tokenize(source)? {
+        // the errors of a method name the lines of its instructions
+        if assembler.ends_method(&line) {
+            assembler.end_method()?;
+            continue;
+        }
+        assembler
+            .line(&line)
+            .map_err(|e| anyhow!("line {}: {}", line.number, e))?;
+    }
+    assembler.finish()?.build(hierarchy)
+}
+
+struct Assembler {
+    constants: Constants,
+    major_version: u16,
+    minor_version: u16,
+    access_flags: u16,
+    name: Option<String>,
+    super_class: Option<String>,
+    interfaces: Vec<String>,
+    source_file: Option<String>,
+    fields: Vec<FieldDecl>,
+    methods: Vec<MethodBody>,
+    attributes: Vec<Attribute>,
+    inner_classes: Vec<InnerClassInfo>,
+    nest_members: Vec<u16>,
+    permitted_subclasses: Vec<u16>,
+    scope: Scope,
+}
+
+/// Where the directives of a line go
+enum Scope {
+    Class,
+    /// The last field, until `.end field` or a directive that is not about fields
+    Field,
+    Method(Box<MethodBody>),
+}
+
+/// A `.field` directive and the attributes that follow it
+struct FieldDecl {
+    access_flags: u16,
+    name: String,
+    descriptor: String,
+    constant: Option<Constant>,
+    attributes: Vec<Attribute>,
+}
+
+/// A method between `.method` and `.end method`
+struct MethodBody {
+    line: usize,
+    access_flags: u16,
+    name: String,
+    descriptor: String,
+    attributes: Vec<Attribute>,
+    exceptions: Vec<u16>,
+    code: CodeBuilder,
+    /// The label of each name and whether it was placed
+    labels: HashMap<String, (Label, bool)>,
+    /// Line and name of the labels in the order they are used, to report the ones that
+    /// are never placed
+    uses: Vec<(usize, String)>,
+    switch: Option<Switch>,
+}
+
+/// A `tableswitch` or `lookupswitch` whose cases are on the following lines
+struct Switch {
+    line: usize,
+    table: bool,
+    pairs: Vec<(i32, Label)>,
+    default: Option<Label>,
+}
+
+impl Assembler {
+    fn line(&mut self, line: &Line) -> Result<()> {
+        let first = match &line.tokens[0] {
+            Token::Word(word) => word.as_str(),
+            Token::Str(s) => return Err(anyhow!("Unexpected \"{}\"", s)),
+        };
+        let mut operands = Operands::new(&line.tokens[1..]);
+        let operands = &mut operands;
+
+        if let Scope::Method(body) = &mut self.scope {
+            if first == ".end" {
+                operands.expect("method")?;
+                return operands.end();
+            }
+            return body.line(line.number, first, operands, &mut self.constants);
+        }
+        if let Scope::Field = self.scope {
+            match member_directive(first, operands, &mut self.constants.cp)? {
+                Some(attribute) => {
+                    self.fields.last_mut().unwrap().attributes.push(attribute);
+                    return operands.end();
+                }
+                None if first == ".end" => {
+                    operands.expect("field")?;
+                    self.scope = Scope::Class;
+                    return operands.end();
+                }
+                None => self.scope = Scope::Class,
+            }
+        }
+
+        let cp = &mut self.constants.cp;
+        match first {
+            ".version" => {
+                self.major_version = operands.int()?;
+                if !operands.is_empty() {
+                    self.minor_version = operands.int()?;
+                }
+            }
+            ".class" => {
+                if self.name.is_some() {
+                    return Err(anyhow!("Only one .class per file"));
+                }
+                self.access_flags = flags(operands, CLASS_FLAGS);
+                self.name = Some(operands.word()?.to_string());
+            }
+            ".super" => self.super_class = Some(operands.word()?.to_string()),
+            ".implements" => {
+                while !operands.is_empty() {
+                    self.interfaces.push(operands.word()?.to_string());
+                }
+            }
+            ".source" => self.source_file = Some(operands.text()?.to_string()),
+            ".inner" => {
+                let access_flags = flags(operands, INNER_CLASS_FLAGS);
+                let inner = cp.add_class(operands.word()?)?;
+                let outer = match operands.keyword("outer") {
+                    true => Some(cp.add_class(operands.word()?)?),
+                    false => None,
+                };
+                let name = match operands.keyword("name") {
+                    true => Some(cp.add_utf8(operands.word()?)?),
+                    false => None,
+                };
+                let info = InnerClassInfo::new(inner, outer, name, access_flags);
+                self.inner_classes.push(info);
+            }
+            ".nesthost" => {
+                let name_index = cp.add_utf8("NestHost")?;
+                let host = cp.add_class(operands.word()?)?;
+                let att = NestHostAttribute::new(name_index, host);
+                self.attributes.push(Attribute::NestHostAttribute(att));
+            }
+            ".nestmembers" => {
+                while !operands.is_empty() {
+                    self.nest_members.push(cp.add_class(operands.word()?)?);
+                }
+            }
+            ".permittedsubclasses" => {
+                while !operands.is_empty() {
+                    let class = cp.add_class(operands.word()?)?;
+                    self.permitted_subclasses.push(class);
+                }
+            }
+            ".enclosing" => {
+                let name_index = cp.add_utf8("EnclosingMethod")?;
+                let class = cp.add_class(operands.word()?)?;
+                let method = match operands.is_empty() {
+                    true => 0,
+                    false => {
+                        let word = operands.word()?;
+                        let (name, descriptor) = word
+                            .split_once(':')
+                            .ok_or(anyhow!("Expected name:descriptor, not {}", word))?;
+                        cp.add_name_and_type(name, descriptor)?
+                    }
+                };
+                let att = EnclosingMethodAttribute::new(name_index, class, method);
+                self.attributes
+                    .push(Attribute::EnclosingMethodAttribute(att));
+            }
+            ".field" => self.field(operands)?,
+            ".method" => self.method(line.number, operands)?,
+            _ => match member_directive(first, operands, cp)? {
+                Some(attribute) => self.attributes.push(attribute),
+                None => return Err(anyhow!("Unknown directive {}", first)),
+            },
+        }
+        operands.end()
+    }
+
+    /// `.field flags name descriptor [= constant]`
+    fn field(&mut self, operands: &mut Operands) -> Result<()> {
+        let access_flags = flags(operands, FIELD_FLAGS);
+        let name = operands.word()?.to_string();
+        let descriptor = operands.word()?.to_string();
+        let constant = match operands.keyword("=") {
+            true => Some(instructions::constant(operands)?),
+            false => None,
+        };
+        self.fields.push(FieldDecl {
+            access_flags,
+            name,
+            descriptor,
+            constant,
+            attributes: Vec::new(),
+        });
+        self.scope = Scope::Field;
+        Ok(())
+    }
+
+    /// `.method flags name(descriptor)`
+    fn method(&mut self, line: usize, operands: &mut Operands) -> Result<()> {
+        let access_flags = flags(operands, METHOD_FLAGS);
+        let word = operands.word()?;
+        let split = word
+            .find('(')
+            .ok_or(anyhow!("Expected name(parameters)return, not {}", word))?;
+        let (name, descriptor) = word.split_at(split);
+        self.scope = Scope::Method(Box::new(MethodBody {
+            line,
+            access_flags,
+            name: name.trim_matches('"').to_string(),
+            descriptor: descriptor.to_string(),
+            attributes: Vec::new(),
+            exceptions: Vec::new(),
+            code: CodeBuilder::new(),
+            labels: HashMap::new(),
+            uses: Vec::new(),
+            switch: None,
+        }));
+        Ok(())
+    }
+
+    fn ends_method(&self, line: &Line) -> bool {
+        let end = [Token::Word(".end".into()), Token::Word("method".into())];
+        matches!(self.scope, Scope::Method(_)) && line.tokens == end
+    }
+
+    fn end_method(&mut self) -> Result<()> {
+        let Scope::Method(mut body) = std::mem::replace(&mut self.scope, Scope::Class) else {
+            unreachable!()
+        };
+        if let Some(switch) = &body.switch {
+            return Err(anyhow!("line {}: Missing }} of the switch", switch.line));
+        }
+        for (line, name) in &body.uses {
+            if !body.labels[name].1 {
+                return Err(anyhow!("line {}: Unknown label {}", line, name));
+            }
+        }
+        if !body.exceptions.is_empty() {
+            let name_index = self.constants.cp.add_utf8("Exceptions")?;
+            let exceptions = std::mem::take(&mut body.exceptions);
+            let att = ExceptionsAttribute::new(name_index, exceptions);
+            body.attributes.push(Attribute::Exceptions(att));
+        }
+        self.methods.push(*body);
+        Ok(())
+    }
+
+    /// The class with the attributes that collect the directives of several lines
+    fn finish(mut self) -> Result<ClassBuilder> {
+        if let Scope::Method(body) = &self.scope {
+            return Err(anyhow!("line {}: Missing .end method", body.line));
+        }
+        let name = self.name.ok_or(anyhow!("Missing .class"))?;
+        let cp = &mut self.constants.cp;
+        let mut attributes = self.attributes;
+        if !self.inner_classes.is_empty() {
+            let name_index = cp.add_utf8("InnerClasses")?;
+            let att = InnerClassesAttribute::new(name_index, self.inner_classes);
+            attributes.push(Attribute::InnerClassesAttribute(att));
+        }
+        if !self.nest_members.is_empty() {
+            let name_index = cp.add_utf8("NestMembers")?;
+            let att = NestMembersAttribute::new(name_index, self.nest_members);
+            attributes.push(Attribute::NestMembersAttribute(att));
+        }
+        if !self.permitted_subclasses.is_empty() {
+            let name_index = cp.add_utf8("PermittedSubclasses")?;
+            let att = PermittedSubclassesAttribute::new(name_index, self.permitted_subclasses);
+            attributes.push(Attribute::PermittedSubclassesAttribute(att));
+        }
+
+        let mut builder = ClassBuilder::with_constants(&name, self.constants)
+            .version(self.major_version, self.minor_version)
+            .access_flags(self.access_flags);
+        if let Some(super_class) = &self.super_class {
+            builder = builder.super_class(super_class);
+        }
+        for interface in &self.interfaces {
+            builder = builder.interface(interface);
+        }
+        if let Some(source_file) = &self.source_file {
+            builder = builder.source_file(source_file);
+        }
+        for field in self.fields {
+            builder = builder.field_with(
+                field.access_flags,
+                &field.name,
+                &field.descriptor,
+                field.constant,
+                field.attributes,
+            );
+        }
+        for method in self.methods {
+            builder = builder.method_with(
+                method.access_flags,
+                &method.name,
+                &method.descriptor,
+                method.code,
+                method.attributes,
+            );
+        }
+        for attribute in attributes {
+            builder = builder.attribute(attribute);
+        }
+        Ok(builder)
+    }
+}
+
+/// `.signature`, `.deprecated`, `.synthetic` and `.attribute Name hex`, `None` for
+/// other directives
+fn member_directive(
+    directive: &str,
+    operands: &mut Operands,
+    cp: &mut ConstantPool,
+) -> Result<Option<Attribute>> {
+    let attribute = match directive {
+        ".signature" => {
+            let name_index = cp.add_utf8("Signature")?;
+            let signature = cp.add_utf8(operands.text()?)?;
+            Attribute::SignatureAttribute(SignatureAttribute::new(name_index, signature))
+        }
+        ".deprecated" => {
+            Attribute::Deprecated(DeprecatedAttribute::new(cp.add_utf8("Deprecated")?))
+        }
+        ".synthetic" => {
+            Attribute::SyntheticAttribute(SyntheticAttribute::new(cp.add_utf8("Synthetic")?))
+        }
+        ".attribute" => {
+            let name_index = cp.add_utf8(operands.text()?)?;
+            let mut info = Vec::new();
+            while !operands.is_empty() {
+                info.extend(lexer::parse_hex(operands.word()?)?);
+            }
+            Attribute::GenericAttribute(GenericAttribute::new(name_index, info))
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(attribute))
+}
+
+/// The access flags at the start of a directive
+fn flags(operands: &mut Operands, names: &[(&str, u16)]) -> u16 {
+    let mut flags = 0;
+    while let Some((_, flag)) = names
+        .iter()
+        .find(|(name, _)| operands.peek().and_then(Token::word) == Some(name))
+    {
+        flags |= flag;
+        operands.next().unwrap();
+    }
+    flags
+}
+
+impl MethodBody {
+    fn line(
+        &mut self,
+        line: usize,
+        first: &str,
+        operands: &mut Operands,
+        constants: &mut Constants,
+    ) -> Result<()> {
+        if self.switch.is_some() {
+            return self.switch_case(line, first, operands);
+        }
+        let cp = &mut constants.cp;
+        match first {
+            ".limit" => {
+                let what = operands.word()?;
+                match what {
+                    "stack" => self.code.max_stack(operands.int()?),
+                    "locals" => self.code.max_locals(operands.int()?),
+                    _ => return Err(anyhow!("Expected .limit stack or .limit locals")),
+                };
+            }
+            ".noframes" => {
+                self.code.no_stack_map();
+            }
+            ".throws" => self.exceptions.push(cp.add_class(operands.word()?)?),
+            ".catch" => {
+                let catch_type = match operands.word()? {
+                    "all" => None,
+                    class => Some(class),
+                };
+                operands.expect("from")?;
+                let start = self.label(line, operands.word()?);
+                operands.expect("to")?;
+                let end = self.label(line, operands.word()?);
+                operands.expect("using")?;
+                let handler = self.label(line, operands.word()?);
+                self.code.try_catch(start, end, handler, catch_type);
+            }
+            ".line" => {
+                self.code.line_number(operands.int()?);
+            }
+            ".var" => {
+                let index = operands.int()?;
+                operands.expect("is")?;
+                let name = operands.word()?;
+                let descriptor = operands.word()?;
+                let signature = match operands.keyword("signature") {
+                    true => Some(operands.text()?),
+                    false => None,
+                };
+                operands.expect("from")?;
+                let start = self.label(line, operands.word()?);
+                operands.expect("to")?;
+                let end = self.label(line, operands.word()?);
+                self.code
+                    .local_variable(index, name, descriptor, signature, start, end);
+            }
+            _ if first.starts_with('.') => match member_directive(first, operands, cp)? {
+                Some(attribute) => self.attributes.push(attribute),
+                None => return Err(anyhow!("Unknown directive {}", first)),
+            },
+            _ if first.ends_with(':') => {
+                let name = &first[..first.len() - 1];
+                let code = &mut self.code;
+                let (label, placed) = self
+                    .labels
+                    .entry(name.to_string())
+                    .or_insert_with(|| (code.new_label(), false));
+                if *placed {
+                    return Err(anyhow!("Label {} is defined twice", name));
+                }
+                *placed = true;
+                code.place(*label);
+                // an instruction can follow the label on the same line
+                if let Some(Token::Word(mnemonic)) = operands.peek() {
+                    operands.next()?;
+                    return self.line(line, mnemonic, operands, constants);
+                }
+            }
+            "tableswitch" | "lookupswitch" => {
+                operands.expect("{")?;
+                self.switch = Some(Switch {
+                    line,
+                    table: first == "tableswitch",
+                    pairs: Vec::new(),
+                    default: None,
+                });
+            }
+            mnemonic => match instructions::parse(mnemonic, operands, constants)? {
+                Instruction::Fixed(instruction, len) => {
+                    self.code.instruction(instruction, len);
+                }
+                Instruction::Jump(jump, target) => {
+                    let target = self.label(line, &target);
+                    self.code.jump(jump, target);
+                }
+            },
+        }
+        operands.end()
+    }
+
+    /// The label named `name`, used on `line`
+    fn label(&mut self, line: usize, name: &str) -> Label {
+        self.uses.push((line, name.to_string()));
+        let code = &mut self.code;
+        self.labels
+            .entry(name.to_string())
+            .or_insert_with(|| (code.new_label(), false))
+            .0
+    }
+
+    /// `key: Label`, `default: Label` or the closing `}` of a switch
+    fn switch_case(&mut self, line: usize, first: &str, operands: &mut Operands) -> Result<()> {
+        if first == "}" {
+            operands.end()?;
+            let switch = self.switch.take().unwrap();
+            let default = switch
+                .default
+                .ok_or(anyhow!("Missing default: of the switch"))?;
+            if switch.table {
+                let low = switch.pairs.first().map_or(0, |(key, _)| *key);
+                let consecutive = (low..).zip(&switch.pairs).all(|(i, (key, _))| i == *key);
+                if !consecutive {
+                    return Err(anyhow!("The keys of a tableswitch must be consecutive"));
+                }
+                let targets: Vec<Label> = switch.pairs.iter().map(|(_, label)| *label).collect();
+                self.code.tableswitch(low, &targets, default);
+            } else {
+                self.code.lookupswitch(&switch.pairs, default);
+            }
+            return Ok(());
+        }
+        let key = first.strip_suffix(':').unwrap_or(first);
+        if key == first {
+            operands.expect(":")?;
+        }
+        let label = self.label(line, operands.word()?);
+        let switch = self.switch.as_mut().unwrap();
+        match key {
+            "default" => switch.default = Some(label),
+            key => switch.pairs.push((
+                i32::try_from(lexer::parse_int(key)?)
+                    .map_err(|_| anyhow!("{} is out of range", key))?,
+                label,
+            )),
+        }
+        operands.end()
+    }
+}
@@ -1,17 +1,52 @@
 use super::{
-    BootstrapMethodsAttribute, CodeAttribute, DeprecatedAttribute, ExceptionsAttribute,
-    GenericAttribute, InnerClassesAttribute, LineNumberTableAttribute, MethodParametersAttribute,
-    RecordAttribute, RuntimeVisibleAnnotationsAttribute, SignatureAttribute, SourceFileAttribute,
-    StackMapTableAttribute,
+    BootstrapMethodsAttribute, CodeAttribute, ConstantValue, ConstantValueAttribute,
+    DeprecatedAttribute, EnclosingMethodAttribute, ExceptionsAttribute, GenericAttribute,
+    InnerClassesAttribute, LineNumberTableAttribute, LocalVariableTableAttribute,
+    MethodParametersAttribute, NestHostAttribute, NestMembersAttribute,
+    PermittedSubclassesAttribute, RecordAttribute, RuntimeVisibleAnnotationsAttribute,
+    SignatureAttribute, SourceDebugExtensionAttribute, SourceFileAttribute, StackMapTableAttribute,
+    SyntheticAttribute,
 };
 
-use crate::class_file::{constant_pool::ConstantPool, file_reader::FileReader};
+use crate::class_file::constant_pool::{ConstantPool, Info};
+use crate::class_file::file_reader::FileReader;
+use crate::class_file::file_writer::FileWriter;
+use crate::class_file::Parsing;
 use crate::print_debug as p;
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use std::fmt;
+use std::sync::{Arc, OnceLock};
 
+/// An attribute table, decoded when it is read or, for lazily parsed class files, the
+/// first time its attributes are accessed
 #[derive(Debug)]
 pub struct Attributes {
-    pub attributes: Vec<Attribute>,
+    decoded: OnceLock<Decoded>,
+    raw: Option<RawAttributes>,
+}
+
+#[derive(Debug, Default)]
+struct Decoded {
+    attributes: Vec<Attribute>,
+    /// Attributes whose parsed content doesn't match their `attribute_length`
+    length_mismatches: Vec<LengthMismatch>,
+    /// Why the raw attribute table couldn't be decoded
+    error: Option<String>,
+}
+
+/// The bytes of an attribute table that is not decoded yet, with the constant pool
+/// to look up the attribute names in
+struct RawAttributes {
+    bytes: Arc<[u8]>,
+    cp: ConstantPool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LengthMismatch {
+    pub attribute_name_index: u16,
+    pub attribute_length: u32,
+    /// bytes the attribute's structure actually took up
+    pub parsed_length: u32,
 }
 
 #[derive(Debug)]
@@ -19,6 +54,8 @@ pub enum Attribute {
     Code(CodeAttribute),
     SourceFile(SourceFileAttribute),
     LineNumberTable(LineNumberTableAttribute),
+    LocalVariableTable(LocalVariableTableAttribute),
+    LocalVariableTypeTable(LocalVariableTableAttribute),
     StackMapTable(StackMapTableAttribute),
     Deprecated(DeprecatedAttribute),
     Exceptions(ExceptionsAttribute),
@@ -28,6 +65,13 @@ pub enum Attribute {
     MethodParametersAttribute(MethodParametersAttribute),
     BootstrapMethodsAttribute(BootstrapMethodsAttribute),
     SignatureAttribute(SignatureAttribute),
+    NestHostAttribute(NestHostAttribute),
+    NestMembersAttribute(NestMembersAttribute),
+    PermittedSubclassesAttribute(PermittedSubclassesAttribute),
+    EnclosingMethodAttribute(EnclosingMethodAttribute),
+    ConstantValueAttribute(ConstantValueAttribute),
+    SyntheticAttribute(SyntheticAttribute),
+    SourceDebugExtensionAttribute(SourceDebugExtensionAttribute),
     GenericAttribute(GenericAttribute),
 }
 
@@ -37,9 +81,58 @@ pub struct AttStart {
     pub attribute_length: u32,
 }
 
+/// `attribute_length` of an attribute built in memory, the size of the content it writes
+pub(super) fn written_length(write: impl FnOnce(&mut FileWriter) -> Result<()>) -> u32 {
+    let mut file = FileWriter::new();
+    // an attribute that can't be written fails again when the class file is written
+    let _ = write(&mut file);
+    file.position() as u32
+}
+
 impl Attributes {
+    /// An attribute table built in memory
+    pub fn new(attributes: Vec<Attribute>) -> Attributes {
+        Attributes {
+            decoded: OnceLock::from(Decoded {
+                attributes,
+                ..Default::default()
+            }),
+            raw: None,
+        }
+    }
+
+    /// Reads an attribute table, decoding it now or, for `Parsing::Lazy`, only checking
+    /// the length of every attribute and keeping its bytes for later
+    pub fn read(file: &mut FileReader, cp: &ConstantPool, parsing: Parsing) -> Result<Attributes> {
+        match parsing {
+            Parsing::Eager => Attributes::from(file, cp),
+            Parsing::Lazy => Attributes::skip(file, cp),
+        }
+    }
+
+    fn skip(file: &mut FileReader, cp: &ConstantPool) -> Result<Attributes> {
+        let attributes_count = file.read_u2_to_u16()?;
+        let mut bytes = attributes_count.to_be_bytes().to_vec();
+        for _i in 0..attributes_count {
+            let attribute_name_index = file.read_u2()?;
+            let attribute_length = file.read_u4()?;
+            bytes.extend(attribute_name_index);
+            bytes.extend(attribute_length);
+            file.read_into(&mut bytes, u32::from_be_bytes(attribute_length) as usize)?;
+        }
+        Ok(Attributes {
+            decoded: OnceLock::new(),
+            raw: Some(RawAttributes {
+                bytes: bytes.into(),
+                cp: cp.clone(),
+            }),
+        })
+    }
+
+    /// Reads and decodes an attribute table
     pub fn from(file: &mut FileReader, cp: &ConstantPool) -> Result<Attributes> {
         let mut attributes = Vec::new();
+        let mut length_mismatches = Vec::new();
 
         let attributes_count = file.read_u2_to_u16()?;
         for _i in 0..attributes_count {
@@ -51,7 +144,12 @@ impl Attributes {
                 attribute_length,
             };
 
-            let name = cp.get_to_string(attribute_name_index);
+            let start = file.position();
+            // an invalid name index is reported by the format check, parse it as unknown
+            let name = match cp.try_get(attribute_name_index) {
+                Some(Info::Utf8Info(name)) => name.clone(),
+                _ => String::new(),
+            };
             p!("Attribute name: {}", name);
             match name.as_str() {
                 "Code" => {
@@ -66,6 +164,14 @@ impl Attributes {
                     let att = LineNumberTableAttribute::parse(file, &att_start)?;
                     attributes.push(Attribute::LineNumberTable(att));
                 }
+                "LocalVariableTable" => {
+                    let att = LocalVariableTableAttribute::parse(file, &att_start)?;
+                    attributes.push(Attribute::LocalVariableTable(att));
+                }
+                "LocalVariableTypeTable" => {
+                    let att = LocalVariableTableAttribute::parse(file, &att_start)?;
+                    attributes.push(Attribute::LocalVariableTypeTable(att));
+                }
                 "StackMapTable" => {
                     let att = StackMapTableAttribute::parse(file, &att_start)?;
                     attributes.push(Attribute::StackMapTable(att));
@@ -102,33 +208,167 @@ impl Attributes {
                     let att = SignatureAttribute::parse(file, &att_start)?;
                     attributes.push(Attribute::SignatureAttribute(att));
                 }
+                "NestHost" => {
+                    let att = NestHostAttribute::parse(file, &att_start)?;
+                    attributes.push(Attribute::NestHostAttribute(att));
+                }
+                "NestMembers" => {
+                    let att = NestMembersAttribute::parse(file, &att_start)?;
+                    attributes.push(Attribute::NestMembersAttribute(att));
+                }
+                "PermittedSubclasses" => {
+                    let att = PermittedSubclassesAttribute::parse(file, &att_start)?;
+                    attributes.push(Attribute::PermittedSubclassesAttribute(att));
+                }
+                "EnclosingMethod" => {
+                    let att = EnclosingMethodAttribute::parse(file, &att_start)?;
+                    attributes.push(Attribute::EnclosingMethodAttribute(att));
+                }
+                "ConstantValue" => {
+                    let att = ConstantValueAttribute::parse(file, &att_start)?;
+                    attributes.push(Attribute::ConstantValueAttribute(att));
+                }
+                "Synthetic" => {
+                    let att = SyntheticAttribute::parse(file, &att_start)?;
+                    attributes.push(Attribute::SyntheticAttribute(att));
+                }
+                "SourceDebugExtension" => {
+                    let att = SourceDebugExtensionAttribute::parse(file, &att_start)?;
+                    attributes.push(Attribute::SourceDebugExtensionAttribute(att));
+                }
                 _ => {
                     let att = GenericAttribute::parse(file, &att_start)?;
                     attributes.push(Attribute::GenericAttribute(att));
                 }
             }
+
+            let parsed_length = (file.position() - start) as u32;
+            if parsed_length != attribute_length {
+                length_mismatches.push(LengthMismatch {
+                    attribute_name_index,
+                    attribute_length,
+                    parsed_length,
+                });
+                // continue after the declared end, a too short length can't be recovered from
+                if parsed_length < attribute_length {
+                    file.skip((attribute_length - parsed_length) as usize)?;
+                }
+            }
+        }
+
+        Ok(Attributes {
+            decoded: OnceLock::from(Decoded {
+                attributes,
+                length_mismatches,
+                error: None,
+            }),
+            raw: None,
+        })
+    }
+
+    /// Writes the attribute table, the bytes as read if it was never changed
+    pub fn write(&self, file: &mut FileWriter) -> Result<()> {
+        if let Some(raw) = &self.raw {
+            file.write_bytes(&raw.bytes);
+            return Ok(());
+        }
+        let attributes = self.decode()?;
+        file.write_count(attributes.len(), "attributes")?;
+        for att in attributes {
+            att.write(file)?;
+        }
+        Ok(())
+    }
+
+    fn decoded(&self) -> &Decoded {
+        self.decoded.get_or_init(|| match &self.raw {
+            Some(raw) => raw.decode(),
+            None => Decoded::default(),
+        })
+    }
+
+    /// The attributes, empty if the attribute table of a lazily parsed class file can't
+    /// be decoded, `decode` returns why
+    pub fn attributes(&self) -> &[Attribute] {
+        &self.decoded().attributes
+    }
+
+    /// The attributes to change, an error if they can't be decoded. The table is written
+    /// as it was read until they are decoded and changed.
+    pub fn attributes_mut(&mut self) -> Result<&mut Vec<Attribute>> {
+        self.decode()?;
+        self.raw = None;
+        match self.decoded.get_mut() {
+            Some(decoded) => Ok(&mut decoded.attributes),
+            None => Err(anyhow!("Attributes are not decoded")),
         }
+    }
 
-        Ok(Attributes { attributes })
+    /// The attributes or why they couldn't be decoded
+    pub fn decode(&self) -> Result<&[Attribute]> {
+        let decoded = self.decoded();
+        match &decoded.error {
+            Some(error) => Err(anyhow!("{}", error)),
+            None => Ok(&decoded.attributes),
+        }
+    }
+
+    /// Keeps the attributes `keep` returns true for. Nothing is removed if it fails for
+    /// any of them or if the attributes can't be decoded.
+    pub(crate) fn retain(
+        &mut self,
+        keep: &mut dyn FnMut(&Attribute) -> Result<bool>,
+    ) -> Result<()> {
+        let kept = self
+            .decode()?
+            .iter()
+            .map(&mut *keep)
+            .collect::<Result<Vec<_>>>()?;
+        let mut kept = kept.into_iter();
+        self.attributes_mut()?
+            .retain(|_| kept.next().unwrap_or(true));
+        Ok(())
+    }
+
+    /// Passes the constant pool indices of the attributes, their names included, to `f`,
+    /// which may change them. `cp` is the pool the indices currently point into. Indices
+    /// that may be 0 are passed as they are.
+    pub(crate) fn constant_indices(
+        &mut self,
+        cp: &ConstantPool,
+        f: &mut dyn FnMut(&mut u16),
+    ) -> Result<()> {
+        for att in self.attributes_mut()? {
+            att.constant_indices(cp, f)?;
+        }
+        Ok(())
+    }
+
+    /// Whether the attributes were decoded, always true unless the class file is parsed
+    /// with `Parsing::Lazy`
+    pub fn is_decoded(&self) -> bool {
+        self.decoded.get().is_some()
+    }
+
+    /// Attributes whose parsed content doesn't match their `attribute_length`
+    pub fn length_mismatches(&self) -> &[LengthMismatch] {
+        &self.decoded().length_mismatches
     }
 
     pub fn to_string(&self, cp: &ConstantPool) -> String {
         let mut s = String::new();
 
         s.push_str("Attributes\n");
-        for att in &self.attributes {
+        for att in self.attributes() {
             s.push_str(&att.to_string(cp));
         }
         s
     }
 
     pub fn get_source_file(&self, cp: &ConstantPool) -> Option<String> {
-        for att in &self.attributes {
-            match att {
-                Attribute::SourceFile(att) => {
-                    return Some(att.to_string(cp));
-                }
-                _ => {}
+        for att in self.attributes() {
+            if let Attribute::SourceFile(att) = att {
+                return Some(att.to_string(cp));
             }
         }
         None
@@ -136,21 +376,346 @@ impl Attributes {
 
     pub fn get_checked_exceptions(&self, cp: &ConstantPool) -> Vec<String> {
         let mut exceptions = Vec::new();
-        for att in &self.attributes {
-            match att {
-                Attribute::Exceptions(e) => {
-                    e.exception_index_table
-                        .iter()
-                        .for_each(|e_index| exceptions.push(cp.get_to_string(*e_index)));
-                }
-                _ => {}
+        for att in self.attributes() {
+            if let Attribute::Exceptions(e) = att {
+                e.exception_index_table
+                    .iter()
+                    .for_each(|e_index| exceptions.push(cp.get_to_string(*e_index)));
             }
         }
         exceptions
     }
+
+    /// The generic signature of a class, field or method, e.g. `<T:Ljava/lang/Object;>(TT;)V`
+    pub fn get_signature(&self, cp: &ConstantPool) -> Option<String> {
+        for att in self.attributes() {
+            if let Attribute::SignatureAttribute(att) = att {
+                return Some(cp.get_to_string(att.signature_index));
+            }
+        }
+        None
+    }
+
+    pub fn get_annotations(&self) -> Option<&RuntimeVisibleAnnotationsAttribute> {
+        for att in self.attributes() {
+            if let Attribute::RuntimeVisibleAnnotationsAttribute(att) = att {
+                return Some(att);
+            }
+        }
+        None
+    }
+
+    pub fn get_nest_host(&self, cp: &ConstantPool) -> Option<String> {
+        for att in self.attributes() {
+            if let Attribute::NestHostAttribute(att) = att {
+                return Some(att.host_class(cp));
+            }
+        }
+        None
+    }
+
+    pub fn get_nest_members(&self, cp: &ConstantPool) -> Vec<String> {
+        for att in self.attributes() {
+            if let Attribute::NestMembersAttribute(att) = att {
+                return att.class_names(cp);
+            }
+        }
+        Vec::new()
+    }
+
+    /// Returns the classes listed in `PermittedSubclasses`, `None` if the class is not sealed
+    pub fn get_permitted_subclasses(&self, cp: &ConstantPool) -> Option<Vec<String>> {
+        for att in self.attributes() {
+            if let Attribute::PermittedSubclassesAttribute(att) = att {
+                return Some(att.class_names(cp));
+            }
+        }
+        None
+    }
+
+    /// The value of a constant field, `None` for fields without a `ConstantValue` attribute
+    pub fn get_constant_value(&self, cp: &ConstantPool) -> Option<Result<ConstantValue>> {
+        for att in self.attributes() {
+            if let Attribute::ConstantValueAttribute(att) = att {
+                return Some(att.value(cp));
+            }
+        }
+        None
+    }
+
+    pub fn is_deprecated(&self) -> bool {
+        self.attributes()
+            .iter()
+            .any(|att| matches!(att, Attribute::Deprecated(_)))
+    }
+
+    pub fn is_synthetic(&self) -> bool {
+        self.attributes()
+            .iter()
+            .any(|att| matches!(att, Attribute::SyntheticAttribute(_)))
+    }
+
+    pub fn get_source_debug_extension(&self) -> Option<&SourceDebugExtensionAttribute> {
+        for att in self.attributes() {
+            if let Attribute::SourceDebugExtensionAttribute(att) = att {
+                return Some(att);
+            }
+        }
+        None
+    }
+
+    pub fn get_bootstrap_methods(&self) -> Option<&BootstrapMethodsAttribute> {
+        for att in self.attributes() {
+            if let Attribute::BootstrapMethodsAttribute(att) = att {
+                return Some(att);
+            }
+        }
+        None
+    }
+
+    pub fn get_stack_map_table(&self) -> Option<&StackMapTableAttribute> {
+        for att in self.attributes() {
+            if let Attribute::StackMapTable(att) = att {
+                return Some(att);
+            }
+        }
+        None
+    }
+
+    /// The `LocalVariableTable` of a `Code` attribute, only present with `javac -g`
+    pub fn get_local_variable_table(&self) -> Option<&LocalVariableTableAttribute> {
+        for att in self.attributes() {
+            if let Attribute::LocalVariableTable(att) = att {
+                return Some(att);
+            }
+        }
+        None
+    }
+
+    /// The `LocalVariableTypeTable` of a `Code` attribute, the variables with generic types
+    pub fn get_local_variable_type_table(&self) -> Option<&LocalVariableTableAttribute> {
+        for att in self.attributes() {
+            if let Attribute::LocalVariableTypeTable(att) = att {
+                return Some(att);
+            }
+        }
+        None
+    }
+
+    /// The components of a record class, `None` for other classes
+    pub fn get_record(&self) -> Option<&RecordAttribute> {
+        for att in self.attributes() {
+            if let Attribute::RecordAttribute(att) = att {
+                return Some(att);
+            }
+        }
+        None
+    }
+
+    pub fn get_inner_classes(&self) -> Option<&InnerClassesAttribute> {
+        for att in self.attributes() {
+            if let Attribute::InnerClassesAttribute(att) = att {
+                return Some(att);
+            }
+        }
+        None
+    }
+
+    pub fn get_enclosing_method(&self) -> Option<&EnclosingMethodAttribute> {
+        for att in self.attributes() {
+            if let Attribute::EnclosingMethodAttribute(att) = att {
+                return Some(att);
+            }
+        }
+        None
+    }
+}
+
+impl RawAttributes {
+    fn decode(&self) -> Decoded {
+        let mut file = FileReader::from_bytes(self.bytes.clone());
+        match Attributes::from(&mut file, &self.cp) {
+            Ok(attributes) => attributes.decoded.into_inner().unwrap_or_default(),
+            Err(e) => Decoded {
+                error: Some(e.to_string()),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+impl fmt::Debug for RawAttributes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "RawAttributes({} bytes)", self.bytes.len())
+    }
 }
 
 impl Attribute {
+    pub fn name_index(&self) -> u16 {
+        match self {
+            Attribute::Code(att) => att.attribute_name_index,
+            Attribute::SourceFile(att) => att.attribute_name_index,
+            Attribute::LineNumberTable(att) => att.attribute_name_index,
+            Attribute::LocalVariableTable(att) => att.attribute_name_index,
+            Attribute::LocalVariableTypeTable(att) => att.attribute_name_index,
+            Attribute::StackMapTable(att) => att.attribute_name_index,
+            Attribute::Deprecated(att) => att.attribute_name_index,
+            Attribute::Exceptions(att) => att.attribute_name_index,
+            Attribute::RuntimeVisibleAnnotationsAttribute(att) => att.attribute_name_index,
+            Attribute::RecordAttribute(att) => att.attribute_name_index,
+            Attribute::InnerClassesAttribute(att) => att.attribute_name_index,
+            Attribute::MethodParametersAttribute(att) => att.attribute_name_index,
+            Attribute::BootstrapMethodsAttribute(att) => att.attribute_name_index,
+            Attribute::SignatureAttribute(att) => att.attribute_name_index,
+            Attribute::NestHostAttribute(att) => att.attribute_name_index,
+            Attribute::NestMembersAttribute(att) => att.attribute_name_index,
+            Attribute::PermittedSubclassesAttribute(att) => att.attribute_name_index,
+            Attribute::EnclosingMethodAttribute(att) => att.attribute_name_index,
+            Attribute::ConstantValueAttribute(att) => att.attribute_name_index,
+            Attribute::SyntheticAttribute(att) => att.attribute_name_index,
+            Attribute::SourceDebugExtensionAttribute(att) => att.attribute_name_index,
+            Attribute::GenericAttribute(att) => att.attribute_name_index,
+        }
+    }
+
+    /// The `attribute_length` declared in the class file
+    pub fn length(&self) -> u32 {
+        match self {
+            Attribute::Code(att) => att.attribute_length,
+            Attribute::SourceFile(att) => att.attribute_length,
+            Attribute::LineNumberTable(att) => att.attribute_length,
+            Attribute::LocalVariableTable(att) => att.attribute_length,
+            Attribute::LocalVariableTypeTable(att) => att.attribute_length,
+            Attribute::StackMapTable(att) => att.attribute_length,
+            Attribute::Deprecated(att) => att.attribute_length,
+            Attribute::Exceptions(att) => att.attribute_length,
+            Attribute::RuntimeVisibleAnnotationsAttribute(att) => att.attribute_length,
+            Attribute::RecordAttribute(att) => att.attribute_length,
+            Attribute::InnerClassesAttribute(att) => att.attribute_length,
+            Attribute::MethodParametersAttribute(att) => att.attribute_length,
+            Attribute::BootstrapMethodsAttribute(att) => att.attribute_length,
+            Attribute::SignatureAttribute(att) => att.attribute_length,
+            Attribute::NestHostAttribute(att) => att.attribute_length,
+            Attribute::NestMembersAttribute(att) => att.attribute_length,
+            Attribute::PermittedSubclassesAttribute(att) => att.attribute_length,
+            Attribute::EnclosingMethodAttribute(att) => att.attribute_length,
+            Attribute::ConstantValueAttribute(att) => att.attribute_length,
+            Attribute::SyntheticAttribute(att) => att.attribute_length,
+            Attribute::SourceDebugExtensionAttribute(att) => att.attribute_length,
+            Attribute::GenericAttribute(att) => att.attribute_length,
+        }
+    }
+
+    /// Writes the name, the length of the content as written and the content
+    pub fn write(&self, file: &mut FileWriter) -> Result<()> {
+        file.write_attribute(self.name_index(), |file| match self {
+            Attribute::Code(att) => att.write(file),
+            Attribute::SourceFile(att) => att.write(file),
+            Attribute::LineNumberTable(att) => att.write(file),
+            Attribute::LocalVariableTable(att) => att.write(file),
+            Attribute::LocalVariableTypeTable(att) => att.write(file),
+            Attribute::StackMapTable(att) => att.write(file),
+            Attribute::Deprecated(att) => att.write(file),
+            Attribute::Exceptions(att) => att.write(file),
+            Attribute::RuntimeVisibleAnnotationsAttribute(att) => att.write(file),
+            Attribute::RecordAttribute(att) => att.write(file),
+            Attribute::InnerClassesAttribute(att) => att.write(file),
+            Attribute::MethodParametersAttribute(att) => att.write(file),
+            Attribute::BootstrapMethodsAttribute(att) => att.write(file),
+            Attribute::SignatureAttribute(att) => att.write(file),
+            Attribute::NestHostAttribute(att) => att.write(file),
+            Attribute::NestMembersAttribute(att) => att.write(file),
+            Attribute::PermittedSubclassesAttribute(att) => att.write(file),
+            Attribute::EnclosingMethodAttribute(att) => att.write(file),
+            Attribute::ConstantValueAttribute(att) => att.write(file),
+            Attribute::SyntheticAttribute(att) => att.write(file),
+            Attribute::SourceDebugExtensionAttribute(att) => att.write(file),
+            Attribute::GenericAttribute(att) => att.write(file),
+        })
+    }
+
+    pub fn name(&self, cp: &ConstantPool) -> String {
+        cp.get_to_string(self.name_index())
+    }
+
+    /// The name or an error if `attribute_name_index` isn't a Utf8 entry
+    pub fn try_name<'a>(&self, cp: &'a ConstantPool) -> Result<&'a str> {
+        cp.utf8(self.name_index())
+    }
+
+    /// See `Attributes::constant_indices`
+    fn constant_indices(&mut self, cp: &ConstantPool, f: &mut dyn FnMut(&mut u16)) -> Result<()> {
+        let name = self.try_name(cp)?.to_string();
+        match self {
+            Attribute::Code(att) => att.constant_indices(cp, f)?,
+            Attribute::SourceFile(att) => f(&mut att.sourcefile_index),
+            Attribute::LineNumberTable(_)
+            | Attribute::Deprecated(_)
+            | Attribute::SyntheticAttribute(_)
+            | Attribute::SourceDebugExtensionAttribute(_) => {}
+            Attribute::LocalVariableTable(att) | Attribute::LocalVariableTypeTable(att) => {
+                for variable in &mut att.local_variables {
+                    f(&mut variable.name_index);
+                    f(&mut variable.descriptor_index);
+                }
+            }
+            Attribute::StackMapTable(att) => att.constant_indices(f),
+            Attribute::Exceptions(att) => att.exception_index_table.iter_mut().for_each(&mut *f),
+            Attribute::RuntimeVisibleAnnotationsAttribute(att) => {
+                for annotation in &mut att.annotations {
+                    annotation.constant_indices(f);
+                }
+            }
+            Attribute::RecordAttribute(att) => att.constant_indices(cp, f)?,
+            Attribute::InnerClassesAttribute(att) => {
+                for class in &mut att.classes {
+                    f(&mut class.inner_class_info_index);
+                    class.outer_class_info_index.iter_mut().for_each(&mut *f);
+                    class.inner_name_index.iter_mut().for_each(&mut *f);
+                }
+            }
+            Attribute::MethodParametersAttribute(att) => att.constant_indices(f),
+            Attribute::BootstrapMethodsAttribute(att) => att.constant_indices(f),
+            Attribute::SignatureAttribute(att) => f(&mut att.signature_index),
+            Attribute::NestHostAttribute(att) => f(&mut att.host_class_index),
+            Attribute::NestMembersAttribute(att) => att.classes.iter_mut().for_each(&mut *f),
+            Attribute::PermittedSubclassesAttribute(att) => {
+                att.classes.iter_mut().for_each(&mut *f)
+            }
+            Attribute::EnclosingMethodAttribute(att) => {
+                f(&mut att.class_index);
+                f(&mut att.method_index);
+            }
+            Attribute::ConstantValueAttribute(att) => f(&mut att.constantvalue_index),
+            Attribute::GenericAttribute(att) => att.constant_indices(&name, f)?,
+        }
+        match self {
+            Attribute::Code(att) => f(&mut att.attribute_name_index),
+            Attribute::SourceFile(att) => f(&mut att.attribute_name_index),
+            Attribute::LineNumberTable(att) => f(&mut att.attribute_name_index),
+            Attribute::LocalVariableTable(att) => f(&mut att.attribute_name_index),
+            Attribute::LocalVariableTypeTable(att) => f(&mut att.attribute_name_index),
+            Attribute::StackMapTable(att) => f(&mut att.attribute_name_index),
+            Attribute::Deprecated(att) => f(&mut att.attribute_name_index),
+            Attribute::Exceptions(att) => f(&mut att.attribute_name_index),
+            Attribute::RuntimeVisibleAnnotationsAttribute(att) => f(&mut att.attribute_name_index),
+            Attribute::RecordAttribute(att) => f(&mut att.attribute_name_index),
+            Attribute::InnerClassesAttribute(att) => f(&mut att.attribute_name_index),
+            Attribute::MethodParametersAttribute(att) => f(&mut att.attribute_name_index),
+            Attribute::BootstrapMethodsAttribute(att) => f(&mut att.attribute_name_index),
+            Attribute::SignatureAttribute(att) => f(&mut att.attribute_name_index),
+            Attribute::NestHostAttribute(att) => f(&mut att.attribute_name_index),
+            Attribute::NestMembersAttribute(att) => f(&mut att.attribute_name_index),
+            Attribute::PermittedSubclassesAttribute(att) => f(&mut att.attribute_name_index),
+            Attribute::EnclosingMethodAttribute(att) => f(&mut att.attribute_name_index),
+            Attribute::ConstantValueAttribute(att) => f(&mut att.attribute_name_index),
+            Attribute::SyntheticAttribute(att) => f(&mut att.attribute_name_index),
+            Attribute::SourceDebugExtensionAttribute(att) => f(&mut att.attribute_name_index),
+            Attribute::GenericAttribute(att) => f(&mut att.attribute_name_index),
+        }
+        Ok(())
+    }
+
     pub fn to_string(&self, cp: &ConstantPool) -> String {
         let mut s = String::new();
         match self {
@@ -158,10 +723,12 @@ impl Attribute {
             Attribute::SourceFile(att) => {
                 s.push_str("SourceFile ");
                 s.push_str(&att.to_string(cp));
-                s.push_str("\n");
+                s.push('\n');
             }
             Attribute::Code(att) => s.push_str(&att.to_string(cp)),
             Attribute::LineNumberTable(att) => s.push_str(&att.to_string(cp)),
+            Attribute::LocalVariableTable(att) => s.push_str(&att.to_string(cp)),
+            Attribute::LocalVariableTypeTable(att) => s.push_str(&att.to_string(cp)),
             Attribute::StackMapTable(att) => s.push_str(&att.to_string(cp)),
             Attribute::Deprecated(att) => s.push_str(&att.to_string(cp)),
             Attribute::Exceptions(att) => s.push_str(&att.to_string(cp)),
@@ -171,6 +738,13 @@ impl Attribute {
             Attribute::MethodParametersAttribute(att) => s.push_str(&att.to_string(cp)),
             Attribute::BootstrapMethodsAttribute(att) => s.push_str(&att.to_string(cp)),
             Attribute::SignatureAttribute(att) => s.push_str(&att.to_string(cp)),
+            Attribute::NestHostAttribute(att) => s.push_str(&att.to_string(cp)),
+            Attribute::NestMembersAttribute(att) => s.push_str(&att.to_string(cp)),
+            Attribute::PermittedSubclassesAttribute(att) => s.push_str(&att.to_string(cp)),
+            Attribute::EnclosingMethodAttribute(att) => s.push_str(&att.to_string(cp)),
+            Attribute::ConstantValueAttribute(att) => s.push_str(&att.to_string(cp)),
+            Attribute::SyntheticAttribute(att) => s.push_str(&att.to_string(cp)),
+            Attribute::SourceDebugExtensionAttribute(att) => s.push_str(&att.to_string(cp)),
         }
         s
     }
@@ -1,11 +1,13 @@
-use super::attributes::AttStart;
-use crate::class_file::{constant_pool::ConstantPool, file_reader::FileReader};
+use super::attributes::{written_length, AttStart};
+use crate::class_file::{
+    constant_pool::ConstantPool, file_reader::FileReader, file_writer::FileWriter,
+};
 use anyhow::Result;
 
 #[derive(Debug)]
 pub struct BootstrapMethodsAttribute {
-    attribute_name_index: u16,
-    attribute_length: u32,
+    pub(super) attribute_name_index: u16,
+    pub(super) attribute_length: u32,
     bootstrap_methods: Vec<BootstrapMethod>,
 }
 
@@ -16,6 +18,27 @@ pub struct BootstrapMethod {
 }
 
 impl BootstrapMethodsAttribute {
+    /// Passes the method handles and static arguments to `f`, which may change them
+    pub(crate) fn constant_indices(&mut self, f: &mut dyn FnMut(&mut u16)) {
+        for method in &mut self.bootstrap_methods {
+            f(&mut method.method_ref);
+            method.arguments.iter_mut().for_each(&mut *f);
+        }
+    }
+
+    pub fn new(
+        attribute_name_index: u16,
+        bootstrap_methods: Vec<BootstrapMethod>,
+    ) -> BootstrapMethodsAttribute {
+        let mut att = BootstrapMethodsAttribute {
+            attribute_name_index,
+            attribute_length: 0,
+            bootstrap_methods,
+        };
+        att.attribute_length = written_length(|file| att.write(file));
+        att
+    }
+
     pub fn parse(file: &mut FileReader, att_start: &AttStart) -> Result<BootstrapMethodsAttribute> {
         let num_bootstrap_methods = file.read_u2_to_u16()?;
         let mut bootstrap_methods = Vec::with_capacity(num_bootstrap_methods as usize);
@@ -29,6 +52,27 @@ impl BootstrapMethodsAttribute {
         })
     }
 
+    pub fn write(&self, file: &mut FileWriter) -> Result<()> {
+        file.write_count(self.bootstrap_methods.len(), "bootstrap methods")?;
+        for bm in &self.bootstrap_methods {
+            file.write_u2(bm.method_ref);
+            file.write_count(bm.arguments.len(), "bootstrap arguments")?;
+            for arg in &bm.arguments {
+                file.write_u2(*arg);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn num_bootstrap_methods(&self) -> usize {
+        self.bootstrap_methods.len()
+    }
+
+    /// The bootstrap method an `InvokeDynamic` entry refers to
+    pub fn get(&self, index: u16) -> Option<&BootstrapMethod> {
+        self.bootstrap_methods.get(index as usize)
+    }
+
     pub fn to_string(&self, cp: &ConstantPool) -> String {
         let mut s = String::new();
         s.push_str("BootstrapMethods:");
@@ -36,12 +80,30 @@ impl BootstrapMethodsAttribute {
             s.push_str("\n\t- ");
             s.push_str(&bm.to_string(cp));
         }
-        s.push_str("\n");
+        s.push('\n');
         s
     }
 }
 
 impl BootstrapMethod {
+    /// Index of the `MethodHandle` of the bootstrap method
+    pub fn method_ref(&self) -> u16 {
+        self.method_ref
+    }
+
+    /// Indexes of the static arguments passed to the bootstrap method
+    pub fn arguments(&self) -> &[u16] {
+        &self.arguments
+    }
+
+    /// `method_ref` is a MethodHandle entry, the arguments are loadable constants
+    pub fn new(method_ref: u16, arguments: Vec<u16>) -> BootstrapMethod {
+        BootstrapMethod {
+            method_ref,
+            arguments,
+        }
+    }
+
     pub fn parse(file: &mut FileReader) -> Result<BootstrapMethod> {
         let method_ref = file.read_u2_to_u16()?;
         let argument_count = file.read_u2_to_u16()?;
@@ -63,7 +125,7 @@ impl BootstrapMethod {
             s.push_str(&cp.get_to_string(*arg));
             s.push_str(", ");
         }
-        s.push_str(")");
+        s.push(')');
         s
     }
 }
@@ -1,27 +1,31 @@
-use super::attributes::AttStart;
+use super::attributes::Attribute;
 use super::attributes::Attributes;
+use super::attributes::{written_length, AttStart};
+use super::StackMapTableAttribute;
 
+use crate::class_file::file_writer::FileWriter;
 use crate::class_file::{bytecode::ByteCode, constant_pool::ConstantPool, file_reader::FileReader};
 use anyhow::Result;
 
 #[derive(Debug)]
 pub struct CodeAttribute {
-    attribute_name_index: u16,
-    attribute_length: u32,
+    pub(super) attribute_name_index: u16,
+    pub(super) attribute_length: u32,
     max_stack: u16,
     max_locals: u16,
     code_length: u32,
     code: Vec<ByteCode>,
+    pcs: Vec<u32>,
     exception_table: Vec<ExceptionTable>,
     attributes: Attributes,
 }
 
 #[derive(Debug)]
 pub struct ExceptionTable {
-    start_pc: u16,
-    end_pc: u16,
-    handler_pc: u16,
-    catch_type: u16,
+    pub start_pc: u16,
+    pub end_pc: u16,
+    pub handler_pc: u16,
+    pub catch_type: u16, // 0 catches everything (finally)
 }
 
 impl CodeAttribute {
@@ -35,9 +39,11 @@ impl CodeAttribute {
         let code_length = file.read_u4_to_u32()?;
 
         let mut code = Vec::new();
+        let mut pcs = Vec::new();
         let mut curr_code = 0;
         while curr_code < code_length {
-            let (byte_code, len) = ByteCode::parse(file)?;
+            let (byte_code, len) = ByteCode::parse(file, curr_code)?;
+            pcs.push(curr_code);
             curr_code += len;
             code.push(byte_code);
         }
@@ -66,11 +72,59 @@ impl CodeAttribute {
             max_locals,
             code_length,
             code,
+            pcs,
             exception_table,
             attributes,
         })
     }
 
+    /// Writes every instruction in the number of bytes it was read in, the `pcs` stay the
+    /// same so the branch offsets and the pcs in the exception table and attributes still hold
+    pub fn write(&self, file: &mut FileWriter) -> Result<()> {
+        file.write_u2(self.max_stack);
+        file.write_u2(self.max_locals);
+        file.write_u4(self.code_length);
+        let ends = self.pcs.iter().skip(1).chain([&self.code_length]);
+        for ((pc, instruction), end) in self.instructions().zip(ends) {
+            instruction.write(file, pc, end - pc)?;
+        }
+        file.write_count(self.exception_table.len(), "exception handlers")?;
+        for et in &self.exception_table {
+            file.write_u2(et.start_pc);
+            file.write_u2(et.end_pc);
+            file.write_u2(et.handler_pc);
+            file.write_u2(et.catch_type);
+        }
+        self.attributes.write(file)
+    }
+
+    /// Code built in memory from instructions at increasing `pcs`, each written in the bytes
+    /// up to the next pc (or `code_length` for the last one), see `ByteCode::write`
+    pub fn new(
+        attribute_name_index: u16,
+        max_stack: u16,
+        max_locals: u16,
+        instructions: Vec<(u32, ByteCode)>,
+        code_length: u32,
+        exception_table: Vec<ExceptionTable>,
+        attributes: Attributes,
+    ) -> CodeAttribute {
+        let (pcs, code) = instructions.into_iter().unzip();
+        let mut att = CodeAttribute {
+            attribute_name_index,
+            attribute_length: 0,
+            max_stack,
+            max_locals,
+            code_length,
+            code,
+            pcs,
+            exception_table,
+            attributes,
+        };
+        att.attribute_length = written_length(|file| att.write(file));
+        att
+    }
+
     pub fn to_string(&self, cp: &ConstantPool) -> String {
         let mut s = String::new();
         s.push_str(&format!(
@@ -108,4 +162,147 @@ impl CodeAttribute {
     pub fn code(&self) -> &Vec<ByteCode> {
         &self.code
     }
+
+    /// The instructions with their pcs
+    pub fn instructions(&self) -> impl Iterator<Item = (u32, &ByteCode)> {
+        self.pcs.iter().copied().zip(self.code.iter())
+    }
+
+    pub fn code_length(&self) -> u32 {
+        self.code_length
+    }
+
+    pub fn exception_table(&self) -> &Vec<ExceptionTable> {
+        &self.exception_table
+    }
+
+    pub fn max_stack(&self) -> u16 {
+        self.max_stack
+    }
+
+    pub fn max_locals(&self) -> u16 {
+        self.max_locals
+    }
+
+    pub fn attributes(&self) -> &Attributes {
+        &self.attributes
+    }
+
+    /// Passes the constant pool indices of the instructions, the catch types and the
+    /// attributes to `f`, which may change them. `cp` is the pool the indices currently
+    /// point into. The index of an `ldc` must stay below 256, the code length is unchanged.
+    pub(crate) fn constant_indices(
+        &mut self,
+        cp: &ConstantPool,
+        f: &mut dyn FnMut(&mut u16),
+    ) -> Result<()> {
+        for instruction in &mut self.code {
+            instruction.constant_indices(f);
+        }
+        for entry in &mut self.exception_table {
+            f(&mut entry.catch_type);
+        }
+        self.attributes.constant_indices(cp, f)
+    }
+
+    /// Keeps only the attributes of the code for which `keep` returns true
+    pub(crate) fn retain_attributes(
+        &mut self,
+        keep: &mut dyn FnMut(&Attribute) -> Result<bool>,
+    ) -> Result<()> {
+        self.attributes.retain(keep)?;
+        self.attribute_length = written_length(|file| self.write(file));
+        Ok(())
+    }
+
+    /// Replaces the instructions in the `start..end` pc ranges, which can't be reached, by
+    /// `nop`s ending in an `athrow` and removes the ranges from the exception handlers, like
+    /// ASM does. The code then verifies with the frames of `Method::compute_frames`.
+    pub fn replace_dead_code(&mut self, dead_code: &[(u32, u32)]) {
+        if dead_code.is_empty() {
+            return;
+        }
+        let is_dead = |pc: u32| {
+            dead_code
+                .iter()
+                .any(|(start, end)| (*start..*end).contains(&pc))
+        };
+        let mut instructions = Vec::new();
+        for (pc, instruction) in self.pcs.iter().zip(&self.code) {
+            if !is_dead(*pc) {
+                instructions.push((*pc, instruction.clone()));
+            }
+        }
+        for (start, end) in dead_code {
+            instructions.extend((*start..end - 1).map(|pc| (pc, ByteCode::Nop)));
+            instructions.push((end - 1, ByteCode::Athrow));
+        }
+        instructions.sort_by_key(|(pc, _)| *pc);
+        (self.pcs, self.code) = instructions.into_iter().unzip();
+
+        let mut exception_table = Vec::new();
+        for entry in &self.exception_table {
+            let mut start = entry.start_pc as u32;
+            for (dead_start, dead_end) in dead_code {
+                if *dead_end <= start || *dead_start >= entry.end_pc as u32 {
+                    continue;
+                }
+                if start < *dead_start {
+                    exception_table.push(ExceptionTable {
+                        start_pc: start as u16,
+                        end_pc: *dead_start as u16,
+                        ..*entry
+                    });
+                }
+                start = *dead_end;
+            }
+            if start < entry.end_pc as u32 {
+                exception_table.push(ExceptionTable {
+                    start_pc: start as u16,
+                    ..*entry
+                });
+            }
+        }
+        self.exception_table = exception_table;
+        self.attribute_length = written_length(|file| self.write(file));
+    }
+
+    /// Replaces max_stack, max_locals and the StackMapTable (removed when `None`),
+    /// e.g. with the result of `Method::compute_frames`
+    pub fn set_frames(
+        &mut self,
+        max_stack: u16,
+        max_locals: u16,
+        stack_map_table: Option<StackMapTableAttribute>,
+    ) -> Result<()> {
+        let attributes = self.attributes.attributes_mut()?;
+        self.max_stack = max_stack;
+        self.max_locals = max_locals;
+
+        let position = attributes
+            .iter()
+            .position(|a| matches!(a, Attribute::StackMapTable(_)));
+        if let Some(position) = position {
+            attributes.remove(position);
+        }
+        if let Some(table) = stack_map_table {
+            let table = Attribute::StackMapTable(table);
+            match position {
+                Some(position) => attributes.insert(position, table),
+                None => attributes.push(table),
+            }
+        }
+
+        // max_stack, max_locals, code_length, code, exception_table_length, exception_table,
+        // attributes_count and the attributes with their name and length
+        self.attribute_length = 2
+            + 2
+            + 4
+            + self.code_length
+            + 2
+            + 8 * self.exception_table.len() as u32
+            + 2
+            + attributes.iter().map(|a| 6 + a.length()).sum::<u32>();
+        Ok(())
+    }
 }
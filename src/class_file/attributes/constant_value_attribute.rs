@@ -0,0 +1,71 @@
+use super::attributes::AttStart;
+use crate::class_file::constant_pool::{ConstantPool, Info};
+use crate::class_file::file_reader::FileReader;
+use crate::class_file::file_writer::FileWriter;
+use anyhow::{anyhow, Result};
+
+/// The value of a constant field (e.g. `static final int MAX = 10;`)
+#[derive(Debug)]
+pub struct ConstantValueAttribute {
+    pub(super) attribute_name_index: u16,
+    pub(super) attribute_length: u32,
+    pub constantvalue_index: u16,
+}
+
+/// The constant pool entry a `ConstantValue` attribute points to.
+/// `int`, `short`, `char`, `byte` and `boolean` fields all use `Int`.
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "json", derive(serde::Serialize), serde(untagged))]
+pub enum ConstantValue {
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    String(String),
+}
+
+impl ConstantValueAttribute {
+    pub fn new(attribute_name_index: u16, constantvalue_index: u16) -> ConstantValueAttribute {
+        ConstantValueAttribute {
+            attribute_name_index,
+            attribute_length: 2,
+            constantvalue_index,
+        }
+    }
+
+    pub fn parse(file: &mut FileReader, att_start: &AttStart) -> Result<ConstantValueAttribute> {
+        Ok(ConstantValueAttribute {
+            attribute_name_index: att_start.attribute_name_index,
+            attribute_length: att_start.attribute_length,
+            constantvalue_index: file.read_u2_to_u16()?,
+        })
+    }
+
+    pub fn write(&self, file: &mut FileWriter) -> Result<()> {
+        file.write_u2(self.constantvalue_index);
+        Ok(())
+    }
+
+    pub fn value(&self, cp: &ConstantPool) -> Result<ConstantValue> {
+        let index = self.constantvalue_index;
+        let info = cp
+            .try_get(index)
+            .ok_or_else(|| anyhow!("Invalid ConstantValue index #{}", index))?;
+        let value = match info {
+            Info::IntegerInfo(v) => ConstantValue::Int(*v),
+            Info::LongInfo(v) => ConstantValue::Long(*v),
+            Info::FloatInfo(v) => ConstantValue::Float(*v),
+            Info::DoubleInfo(v) => ConstantValue::Double(*v),
+            Info::StringInfo(_) => ConstantValue::String(cp.string(index)?.to_string()),
+            info => return Err(anyhow!("Invalid ConstantValue entry: {:?}", info)),
+        };
+        Ok(value)
+    }
+
+    pub fn to_string(&self, cp: &ConstantPool) -> String {
+        match self.value(cp) {
+            Ok(value) => format!("ConstantValue: {:?}\n", value),
+            Err(e) => format!("ConstantValue: {}\n", e),
+        }
+    }
+}
@@ -1,14 +1,23 @@
 use super::attributes::AttStart;
-use crate::class_file::{constant_pool::ConstantPool, file_reader::FileReader};
+use crate::class_file::{
+    constant_pool::ConstantPool, file_reader::FileReader, file_writer::FileWriter,
+};
 use anyhow::Result;
 
 #[derive(Debug)]
 pub struct DeprecatedAttribute {
-    attribute_name_index: u16,
-    attribute_length: u32,
+    pub(super) attribute_name_index: u16,
+    pub(super) attribute_length: u32,
 }
 
 impl DeprecatedAttribute {
+    pub fn new(attribute_name_index: u16) -> DeprecatedAttribute {
+        DeprecatedAttribute {
+            attribute_name_index,
+            attribute_length: 0,
+        }
+    }
+
     pub fn parse(_file: &mut FileReader, att_start: &AttStart) -> Result<DeprecatedAttribute> {
         Ok(DeprecatedAttribute {
             attribute_name_index: att_start.attribute_name_index,
@@ -16,6 +25,10 @@ impl DeprecatedAttribute {
         })
     }
 
+    pub fn write(&self, _file: &mut FileWriter) -> Result<()> {
+        Ok(())
+    }
+
     pub fn to_string(&self, _cp: &ConstantPool) -> String {
         "Deprecated\n".to_string()
     }
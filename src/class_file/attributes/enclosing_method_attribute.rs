@@ -0,0 +1,84 @@
+use super::attributes::AttStart;
+use crate::class_file::constant_pool::ConstantPool;
+use crate::class_file::file_reader::FileReader;
+use crate::class_file::file_writer::FileWriter;
+use anyhow::Result;
+
+/// Present on local and anonymous classes, points to the class (and method, if any) that
+/// lexically encloses the class declaration
+#[derive(Debug)]
+pub struct EnclosingMethodAttribute {
+    pub(super) attribute_name_index: u16,
+    pub(super) attribute_length: u32,
+    pub class_index: u16,
+    pub method_index: u16, // 0 when the class is not enclosed by a method (e.g. field initializers)
+}
+
+/// Resolved form of the `EnclosingMethod` attribute
+#[derive(Debug, PartialEq)]
+pub struct EnclosingMethod {
+    pub class: String,
+    pub method_name: Option<String>,
+    pub method_descriptor: Option<String>,
+}
+
+impl EnclosingMethodAttribute {
+    /// `method_index` is a NameAndType entry, 0 outside of methods
+    pub fn new(attribute_name_index: u16, class_index: u16, method_index: u16) -> Self {
+        EnclosingMethodAttribute {
+            attribute_name_index,
+            attribute_length: 4,
+            class_index,
+            method_index,
+        }
+    }
+
+    pub fn parse(file: &mut FileReader, att_start: &AttStart) -> Result<EnclosingMethodAttribute> {
+        Ok(EnclosingMethodAttribute {
+            attribute_name_index: att_start.attribute_name_index,
+            attribute_length: att_start.attribute_length,
+            class_index: file.read_u2_to_u16()?,
+            method_index: file.read_u2_to_u16()?,
+        })
+    }
+
+    pub fn write(&self, file: &mut FileWriter) -> Result<()> {
+        file.write_u2(self.class_index);
+        file.write_u2(self.method_index);
+        Ok(())
+    }
+
+    /// Binary name of the innermost class that encloses the declaration
+    pub fn class_name<'a>(&self, cp: &'a ConstantPool) -> Result<&'a str> {
+        cp.class_name(self.class_index)
+    }
+
+    /// Name and descriptor of the enclosing method, `None` outside of methods
+    pub fn method<'a>(&self, cp: &'a ConstantPool) -> Result<Option<(&'a str, &'a str)>> {
+        match self.method_index {
+            0 => Ok(None),
+            index => cp.name_and_type(index).map(Some),
+        }
+    }
+
+    pub fn resolve(&self, cp: &ConstantPool) -> Result<EnclosingMethod> {
+        let method = self.method(cp)?;
+        Ok(EnclosingMethod {
+            class: self.class_name(cp)?.to_string(),
+            method_name: method.map(|(name, _)| name.to_string()),
+            method_descriptor: method.map(|(_, descriptor)| descriptor.to_string()),
+        })
+    }
+
+    pub fn to_string(&self, cp: &ConstantPool) -> String {
+        match self.resolve(cp) {
+            Ok(EnclosingMethod {
+                class,
+                method_name: Some(name),
+                method_descriptor: Some(descriptor),
+            }) => format!("EnclosingMethod: {}.{}{}\n", class, name, descriptor),
+            Ok(enclosing) => format!("EnclosingMethod: {}\n", enclosing.class),
+            Err(e) => format!("EnclosingMethod: {}\n", e),
+        }
+    }
+}
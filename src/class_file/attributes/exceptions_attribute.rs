@@ -1,18 +1,30 @@
-use super::attributes::AttStart;
+use super::attributes::{written_length, AttStart};
 
-use crate::class_file::{constant_pool::ConstantPool, file_reader::FileReader};
+use crate::class_file::{
+    constant_pool::ConstantPool, file_reader::FileReader, file_writer::FileWriter,
+};
 
 use anyhow::Result;
 
 /// Indicates which checked exceptions a method may throw
 #[derive(Debug)]
 pub struct ExceptionsAttribute {
-    attribute_name_index: u16,
-    attribute_length: u32,
+    pub(super) attribute_name_index: u16,
+    pub(super) attribute_length: u32,
     pub exception_index_table: Vec<u16>,
 }
 
 impl ExceptionsAttribute {
+    pub fn new(attribute_name_index: u16, exception_index_table: Vec<u16>) -> ExceptionsAttribute {
+        let mut att = ExceptionsAttribute {
+            attribute_name_index,
+            attribute_length: 0,
+            exception_index_table,
+        };
+        att.attribute_length = written_length(|file| att.write(file));
+        att
+    }
+
     pub fn parse(file: &mut FileReader, att_start: &AttStart) -> Result<ExceptionsAttribute> {
         let number_of_exceptions = file.read_u2_to_u16()?;
         let mut exception_index_table = Vec::new();
@@ -27,6 +39,14 @@ impl ExceptionsAttribute {
         })
     }
 
+    pub fn write(&self, file: &mut FileWriter) -> Result<()> {
+        file.write_count(self.exception_index_table.len(), "exceptions")?;
+        for exception_index in &self.exception_index_table {
+            file.write_u2(*exception_index);
+        }
+        Ok(())
+    }
+
     pub fn to_string(&self, cp: &ConstantPool) -> String {
         let mut s = String::new();
         s.push_str("Exceptions\n");
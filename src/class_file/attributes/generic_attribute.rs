@@ -1,20 +1,31 @@
-use super::attributes::AttStart;
-use crate::class_file::{constant_pool::ConstantPool, file_reader::FileReader};
-use anyhow::Result;
+use super::attributes::{written_length, AttStart};
+use super::runtime_visible_annotations_attribute::{Annotation, ElementValue};
+use crate::class_file::{
+    constant_pool::ConstantPool, file_reader::FileReader, file_writer::FileWriter,
+};
+use anyhow::{anyhow, Result};
 
 #[derive(Debug)]
 pub struct GenericAttribute {
-    attribute_name_index: u16,
-    attribute_length: u32,
+    pub(super) attribute_name_index: u16,
+    pub(super) attribute_length: u32,
     info: Vec<u8>,
 }
 
 impl GenericAttribute {
+    pub fn new(attribute_name_index: u16, info: Vec<u8>) -> GenericAttribute {
+        let mut att = GenericAttribute {
+            attribute_name_index,
+            attribute_length: 0,
+            info,
+        };
+        att.attribute_length = written_length(|file| att.write(file));
+        att
+    }
+
     pub fn parse(file: &mut FileReader, att_start: &AttStart) -> Result<GenericAttribute> {
         let mut info = Vec::new();
-        for _j in 0..att_start.attribute_length {
-            info.push(file.read_u1()?);
-        }
+        file.read_into(&mut info, att_start.attribute_length as usize)?;
         Ok(GenericAttribute {
             attribute_name_index: att_start.attribute_name_index,
             attribute_length: att_start.attribute_length,
@@ -22,6 +33,75 @@ impl GenericAttribute {
         })
     }
 
+    pub fn write(&self, file: &mut FileWriter) -> Result<()> {
+        file.write_bytes(&self.info);
+        Ok(())
+    }
+
+    /// Passes the constant pool indices in the content of the attribute named `name` to
+    /// `f`, which may change them. Only the annotation attributes the parser keeps as
+    /// bytes are understood, their indices are rewritten in place.
+    pub(crate) fn constant_indices(
+        &mut self,
+        name: &str,
+        f: &mut dyn FnMut(&mut u16),
+    ) -> Result<()> {
+        let info = self.info.clone();
+        let mut file = FileReader::from_bytes(&info[..]);
+        let mut out = FileWriter::new();
+        let annotations = |file: &mut FileReader,
+                           out: &mut FileWriter,
+                           f: &mut dyn FnMut(&mut u16)|
+         -> Result<()> {
+            let count = file.read_u2_to_u16()?;
+            out.write_u2(count);
+            for _ in 0..count {
+                let mut annotation = Annotation::parse(file)?;
+                annotation.constant_indices(f);
+                annotation.write(out)?;
+            }
+            Ok(())
+        };
+        match name {
+            "RuntimeInvisibleAnnotations" => annotations(&mut file, &mut out, f)?,
+            "RuntimeVisibleParameterAnnotations" | "RuntimeInvisibleParameterAnnotations" => {
+                let parameters = file.read_u1()?;
+                out.write_u1(parameters);
+                for _ in 0..parameters {
+                    annotations(&mut file, &mut out, f)?;
+                }
+            }
+            "AnnotationDefault" => {
+                let mut value = ElementValue::parse(&mut file)?;
+                value.constant_indices(f);
+                value.write(&mut out)?;
+            }
+            "RuntimeVisibleTypeAnnotations" | "RuntimeInvisibleTypeAnnotations" => {
+                let count = file.read_u2_to_u16()?;
+                out.write_u2(count);
+                for _ in 0..count {
+                    // the target and the type path are copied, the annotation follows them
+                    let start = file.position();
+                    type_annotation_target(&mut file)?;
+                    out.write_bytes(&info[start..file.position()]);
+                    let mut annotation = Annotation::parse(&mut file)?;
+                    annotation.constant_indices(f);
+                    annotation.write(&mut out)?;
+                }
+            }
+            _ => return Err(anyhow!("The content of {} attributes is unknown", name)),
+        }
+        if !file.at_end()? {
+            return Err(anyhow!("{} attribute is longer than its content", name));
+        }
+        self.info = out.into_bytes();
+        Ok(())
+    }
+
+    pub fn info(&self) -> &[u8] {
+        &self.info
+    }
+
     pub fn to_string(&self, cp: &ConstantPool) -> String {
         let name = cp.get_to_string(self.attribute_name_index);
         format!(
@@ -30,3 +110,33 @@ impl GenericAttribute {
         )
     }
 }
+
+/// Skips the `target_type`, `target_info` and `type_path` of a type annotation (JVMS 4.7.20)
+fn type_annotation_target(file: &mut FileReader) -> Result<()> {
+    let target_type = file.read_u1()?;
+    let length = match target_type {
+        // type parameter, formal parameter
+        0x00 | 0x01 | 0x16 => 1,
+        // supertype, throws
+        0x10 | 0x17 => 2,
+        // type parameter bound
+        0x11 | 0x12 => 2,
+        // field, return, receiver
+        0x13..=0x15 => 0,
+        // local variable: a table of start_pc, length and index
+        0x40 | 0x41 => 6 * file.read_u2_to_u16()? as usize,
+        // catch, instanceof, new, method references
+        0x42..=0x46 => 2,
+        // casts and type arguments of calls
+        0x47..=0x4b => 3,
+        _ => {
+            return Err(anyhow!(
+                "Invalid type annotation target 0x{:x}",
+                target_type
+            ))
+        }
+    };
+    file.skip(length)?;
+    let path_length = file.read_u1()?;
+    file.skip(2 * path_length as usize)
+}
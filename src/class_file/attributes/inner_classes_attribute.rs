@@ -1,29 +1,57 @@
-use super::attributes::AttStart;
-use crate::class_file::access_flags::AccessFlags;
-use crate::class_file::{constant_pool::ConstantPool, file_reader::FileReader};
+use super::attributes::{written_length, AttStart};
+use crate::class_file::{
+    constant_pool::ConstantPool, file_reader::FileReader, file_writer::FileWriter, Visibility,
+};
 use anyhow::Result;
 
 #[derive(Debug)]
 pub struct InnerClassesAttribute {
-    attribute_name_index: u16,
-    attribute_length: u32,
-    classes: Vec<InnerClassInfo>,
+    pub(super) attribute_name_index: u16,
+    pub(super) attribute_length: u32,
+    pub classes: Vec<InnerClassInfo>,
 }
 
 #[derive(Debug)]
 pub struct InnerClassInfo {
-    inner_class_info_index: u16,
-    outer_class_info_index: u16,
-    inner_name_index: u16,
-    inner_class_access_flags: u16,
+    pub inner_class_info_index: u16,
+    pub outer_class_info_index: Option<u16>, // None for top level, local and anonymous classes
+    pub inner_name_index: Option<u16>,       // None for anonymous classes
+    pub inner_class_access_flags: AccessFlags,
+}
+
+#[derive(Debug)]
+pub struct AccessFlags(u16);
+
+#[derive(Debug, PartialEq)]
+pub enum AccessFlag {
+    Public,
+    Private,
+    Protected,
+    Static,
+    Final,
+    Interface,
+    Abstract,
+    Synthetic,
+    Annotation,
+    Enum,
 }
 
 impl InnerClassesAttribute {
+    pub fn new(attribute_name_index: u16, classes: Vec<InnerClassInfo>) -> InnerClassesAttribute {
+        let mut att = InnerClassesAttribute {
+            attribute_name_index,
+            attribute_length: 0,
+            classes,
+        };
+        att.attribute_length = written_length(|file| att.write(file));
+        att
+    }
+
     pub fn parse(file: &mut FileReader, att_start: &AttStart) -> Result<InnerClassesAttribute> {
         let inner_class_count = file.read_u2_to_u16()?;
-        let mut classes = Vec::new();
+        let mut classes = Vec::with_capacity(inner_class_count as usize);
         for _ in 0..inner_class_count {
-            let class = InnerClassInfo::parse(file)?;
+            classes.push(InnerClassInfo::parse(file)?);
         }
         Ok(InnerClassesAttribute {
             attribute_name_index: att_start.attribute_name_index,
@@ -32,6 +60,37 @@ impl InnerClassesAttribute {
         })
     }
 
+    pub fn write(&self, file: &mut FileWriter) -> Result<()> {
+        file.write_count(self.classes.len(), "inner classes")?;
+        for class in &self.classes {
+            file.write_u2(class.inner_class_info_index);
+            file.write_u2(class.outer_class_info_index.unwrap_or(0));
+            file.write_u2(class.inner_name_index.unwrap_or(0));
+            file.write_u2(class.inner_class_access_flags.0);
+        }
+        Ok(())
+    }
+
+    pub fn find(&self, binary_name: &str, cp: &ConstantPool) -> Option<&InnerClassInfo> {
+        self.classes
+            .iter()
+            .find(|c| c.inner_class_name(cp) == binary_name)
+    }
+
+    /// Maps a binary name like `com/example/Outer$Inner` to its source name `com.example.Outer.Inner`.
+    /// Local and anonymous classes have no source name so their binary name is used.
+    pub fn source_name(&self, binary_name: &str, cp: &ConstantPool) -> String {
+        let Some(class) = self.find(binary_name, cp) else {
+            return binary_name.replace('/', ".");
+        };
+        match (class.outer_class_name(cp), class.inner_name(cp)) {
+            (Some(outer), Some(inner)) if outer != binary_name => {
+                format!("{}.{}", self.source_name(&outer, cp), inner)
+            }
+            _ => binary_name.replace('/', "."),
+        }
+    }
+
     pub fn to_string(&self, cp: &ConstantPool) -> String {
         let mut s = String::new();
         s.push_str("InnerClasses: ");
@@ -41,35 +100,144 @@ impl InnerClassesAttribute {
             s.push_str(&class.to_string(cp));
             s.push_str(", ");
         }
-        s.push_str("\n");
+        s.push('\n');
         s
     }
 }
 
 impl InnerClassInfo {
+    pub fn new(
+        inner_class_info_index: u16,
+        outer_class_info_index: Option<u16>,
+        inner_name_index: Option<u16>,
+        access_flags: u16,
+    ) -> InnerClassInfo {
+        InnerClassInfo {
+            inner_class_info_index,
+            outer_class_info_index,
+            inner_name_index,
+            inner_class_access_flags: AccessFlags(access_flags),
+        }
+    }
+
     pub fn parse(file: &mut FileReader) -> Result<InnerClassInfo> {
         Ok(InnerClassInfo {
             inner_class_info_index: file.read_u2_to_u16()?,
-            outer_class_info_index: file.read_u2_to_u16()?,
-            inner_name_index: file.read_u2_to_u16()?,
-            inner_class_access_flags: file.read_u2_to_u16()?,
+            outer_class_info_index: optional_index(file.read_u2_to_u16()?),
+            inner_name_index: optional_index(file.read_u2_to_u16()?),
+            inner_class_access_flags: AccessFlags(file.read_u2_to_u16()?),
         })
     }
 
+    pub fn inner_class_name(&self, cp: &ConstantPool) -> String {
+        cp.get_to_string(self.inner_class_info_index)
+    }
+
+    pub fn outer_class_name(&self, cp: &ConstantPool) -> Option<String> {
+        self.outer_class_info_index.map(|i| cp.get_to_string(i))
+    }
+
+    /// The simple name as written in the source, `None` for anonymous classes
+    pub fn inner_name(&self, cp: &ConstantPool) -> Option<String> {
+        self.inner_name_index.map(|i| cp.get_to_string(i))
+    }
+
+    pub fn is_anonymous(&self) -> bool {
+        self.inner_name_index.is_none()
+    }
+
+    /// Static member classes have no outer instance
+    pub fn is_static(&self) -> bool {
+        self.inner_class_access_flags
+            .flag_vector()
+            .contains(&AccessFlag::Static)
+    }
+
+    pub fn is_synthetic(&self) -> bool {
+        self.inner_class_access_flags
+            .flag_vector()
+            .contains(&AccessFlag::Synthetic)
+    }
+
+    pub fn visibility(&self) -> Visibility {
+        let flags = self.inner_class_access_flags.flag_vector();
+        Visibility::of(
+            flags.contains(&AccessFlag::Public),
+            flags.contains(&AccessFlag::Protected),
+            flags.contains(&AccessFlag::Private),
+        )
+    }
+
     pub fn to_string(&self, cp: &ConstantPool) -> String {
         let mut s = String::new();
-        s.push_str(&cp.get_to_string(self.inner_class_info_index));
+        s.push_str(&self.inner_class_name(cp));
         s.push_str(", ");
-        s.push_str(&cp.get_to_string(self.outer_class_info_index));
+        s.push_str(&self.outer_class_name(cp).unwrap_or("<none>".to_string()));
         s.push_str(", ");
-        s.push_str(&cp.get_to_string(self.inner_name_index));
+        s.push_str(&self.inner_name(cp).unwrap_or("<anonymous>".to_string()));
         s.push_str(", ");
-        let flags = AccessFlags::new(self.inner_class_access_flags);
         s.push_str(&format!(
             "flags: {:04x} ({:?})",
-            self.inner_class_access_flags,
-            flags.flag_vector()
+            self.inner_class_access_flags.0,
+            self.inner_class_access_flags.flag_vector()
         ));
         s
     }
 }
+
+fn optional_index(index: u16) -> Option<u16> {
+    if index == 0 {
+        None
+    } else {
+        Some(index)
+    }
+}
+
+impl AccessFlags {
+    pub fn new(flags: u16) -> AccessFlags {
+        AccessFlags(flags)
+    }
+
+    pub fn flag_vector(&self) -> Vec<AccessFlag> {
+        let mut flags = Vec::new();
+        add_flag(&mut flags, self.0, 0x0001, AccessFlag::Public);
+        add_flag(&mut flags, self.0, 0x0002, AccessFlag::Private);
+        add_flag(&mut flags, self.0, 0x0004, AccessFlag::Protected);
+        add_flag(&mut flags, self.0, 0x0008, AccessFlag::Static);
+        add_flag(&mut flags, self.0, 0x0010, AccessFlag::Final);
+        add_flag(&mut flags, self.0, 0x0200, AccessFlag::Interface);
+        add_flag(&mut flags, self.0, 0x0400, AccessFlag::Abstract);
+        add_flag(&mut flags, self.0, 0x1000, AccessFlag::Synthetic);
+        add_flag(&mut flags, self.0, 0x2000, AccessFlag::Annotation);
+        add_flag(&mut flags, self.0, 0x4000, AccessFlag::Enum);
+        flags
+    }
+
+    /// Source modifiers in the order javap prints them, interfaces are implicitly abstract
+    pub fn modifiers(&self) -> Vec<&'static str> {
+        let flags = self.flag_vector();
+        let mut modifiers = Vec::new();
+        for (flag, modifier) in [
+            (AccessFlag::Public, "public"),
+            (AccessFlag::Private, "private"),
+            (AccessFlag::Protected, "protected"),
+            (AccessFlag::Static, "static"),
+            (AccessFlag::Abstract, "abstract"),
+            (AccessFlag::Final, "final"),
+        ] {
+            if flag == AccessFlag::Abstract && flags.contains(&AccessFlag::Interface) {
+                continue;
+            }
+            if flags.contains(&flag) {
+                modifiers.push(modifier);
+            }
+        }
+        modifiers
+    }
+}
+
+fn add_flag(flags: &mut Vec<AccessFlag>, bit_flags: u16, bit_mask: u16, access_flag: AccessFlag) {
+    if bit_flags & bit_mask == bit_mask {
+        flags.push(access_flag);
+    }
+}
@@ -1,23 +1,38 @@
-use super::attributes::AttStart;
+use super::attributes::{written_length, AttStart};
 
-use crate::class_file::{constant_pool::ConstantPool, file_reader::FileReader};
+use crate::class_file::{
+    constant_pool::ConstantPool, file_reader::FileReader, file_writer::FileWriter,
+};
 
 use anyhow::Result;
 
 #[derive(Debug)]
 pub struct LineNumberTableAttribute {
-    attribute_name_index: u16,
-    attribute_length: u32,
+    pub(super) attribute_name_index: u16,
+    pub(super) attribute_length: u32,
     line_number_table: Vec<LineNumberTable>,
 }
 
 #[derive(Debug)]
 pub struct LineNumberTable {
-    start_pc: u16, // The instruction offset from the start of the code array at which the line number begins.
-    line_number: u16, // The line number in the original source file.
+    pub start_pc: u16, // The instruction offset from the start of the code array at which the line number begins.
+    pub line_number: u16, // The line number in the original source file.
 }
 
 impl LineNumberTableAttribute {
+    pub fn new(
+        attribute_name_index: u16,
+        line_number_table: Vec<LineNumberTable>,
+    ) -> LineNumberTableAttribute {
+        let mut att = LineNumberTableAttribute {
+            attribute_name_index,
+            attribute_length: 0,
+            line_number_table,
+        };
+        att.attribute_length = written_length(|file| att.write(file));
+        att
+    }
+
     pub fn parse(file: &mut FileReader, att_start: &AttStart) -> Result<LineNumberTableAttribute> {
         let line_number_table_length = file.read_u2_to_u16()?;
         let mut line_number_table = Vec::with_capacity(line_number_table_length as usize);
@@ -36,6 +51,19 @@ impl LineNumberTableAttribute {
         })
     }
 
+    pub fn write(&self, file: &mut FileWriter) -> Result<()> {
+        file.write_count(self.line_number_table.len(), "line numbers")?;
+        for lnt in &self.line_number_table {
+            file.write_u2(lnt.start_pc);
+            file.write_u2(lnt.line_number);
+        }
+        Ok(())
+    }
+
+    pub fn line_numbers(&self) -> &[LineNumberTable] {
+        &self.line_number_table
+    }
+
     pub fn to_string(&self, _cp: &ConstantPool) -> String {
         let mut s = String::new();
         s.push_str("LineNumberTable\n");
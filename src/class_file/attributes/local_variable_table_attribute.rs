@@ -0,0 +1,108 @@
+use super::attributes::{written_length, AttStart};
+use crate::class_file::{
+    constant_pool::ConstantPool, file_reader::FileReader, file_writer::FileWriter,
+};
+use anyhow::Result;
+
+/// A `LocalVariableTable` or, with generic signatures instead of descriptors,
+/// a `LocalVariableTypeTable` attribute. Only present when compiled with `javac -g`.
+#[derive(Debug)]
+pub struct LocalVariableTableAttribute {
+    pub(super) attribute_name_index: u16,
+    pub(super) attribute_length: u32,
+    pub local_variables: Vec<LocalVariable>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LocalVariable {
+    /// The variable is live in `start_pc..start_pc + length`
+    pub start_pc: u16,
+    pub length: u16,
+    pub name_index: u16,
+    /// The field descriptor, or the signature in a `LocalVariableTypeTable`
+    pub descriptor_index: u16,
+    /// The local slot of the variable
+    pub index: u16,
+}
+
+impl LocalVariableTableAttribute {
+    pub fn new(
+        attribute_name_index: u16,
+        local_variables: Vec<LocalVariable>,
+    ) -> LocalVariableTableAttribute {
+        let mut att = LocalVariableTableAttribute {
+            attribute_name_index,
+            attribute_length: 0,
+            local_variables,
+        };
+        att.attribute_length = written_length(|file| att.write(file));
+        att
+    }
+
+    pub fn parse(
+        file: &mut FileReader,
+        att_start: &AttStart,
+    ) -> Result<LocalVariableTableAttribute> {
+        let local_variable_table_length = file.read_u2_to_u16()?;
+        let mut local_variables = Vec::with_capacity(local_variable_table_length as usize);
+        for _j in 0..local_variable_table_length {
+            local_variables.push(LocalVariable {
+                start_pc: file.read_u2_to_u16()?,
+                length: file.read_u2_to_u16()?,
+                name_index: file.read_u2_to_u16()?,
+                descriptor_index: file.read_u2_to_u16()?,
+                index: file.read_u2_to_u16()?,
+            });
+        }
+        Ok(LocalVariableTableAttribute {
+            attribute_name_index: att_start.attribute_name_index,
+            attribute_length: att_start.attribute_length,
+            local_variables,
+        })
+    }
+
+    pub fn write(&self, file: &mut FileWriter) -> Result<()> {
+        file.write_count(self.local_variables.len(), "local variables")?;
+        for variable in &self.local_variables {
+            file.write_u2(variable.start_pc);
+            file.write_u2(variable.length);
+            file.write_u2(variable.name_index);
+            file.write_u2(variable.descriptor_index);
+            file.write_u2(variable.index);
+        }
+        Ok(())
+    }
+
+    /// The variable in `slot` whose range covers `pc`
+    pub fn find(&self, slot: u16, pc: u32) -> Option<&LocalVariable> {
+        self.local_variables
+            .iter()
+            .find(|v| v.index == slot && v.covers(pc))
+    }
+
+    pub fn to_string(&self, cp: &ConstantPool) -> String {
+        let mut s = String::new();
+        s.push_str(&format!(
+            "{}\n",
+            cp.get_to_string(self.attribute_name_index)
+        ));
+        for v in &self.local_variables {
+            s.push_str(&format!(
+                "\t- start_pc {} length {} slot {} name {} {}\n",
+                v.start_pc,
+                v.length,
+                v.index,
+                cp.get_to_string(v.name_index),
+                cp.get_to_string(v.descriptor_index)
+            ));
+        }
+        s
+    }
+}
+
+impl LocalVariable {
+    pub fn covers(&self, pc: u32) -> bool {
+        let start = self.start_pc as u32;
+        start <= pc && pc < start + self.length as u32
+    }
+}
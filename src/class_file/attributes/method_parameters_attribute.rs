@@ -1,12 +1,14 @@
 use super::attributes::AttStart;
-use crate::class_file::{constant_pool::ConstantPool, file_reader::FileReader};
+use crate::class_file::{
+    constant_pool::ConstantPool, file_reader::FileReader, file_writer::FileWriter,
+};
 use crate::print_debug as p;
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 
 #[derive(Debug)]
 pub struct MethodParametersAttribute {
-    attribute_name_index: u16,
-    attribute_length: u32,
+    pub(super) attribute_name_index: u16,
+    pub(super) attribute_length: u32,
     parameters: Vec<MethodParameter>,
 }
 
@@ -19,6 +21,7 @@ pub struct MethodParameter {
 #[derive(Debug)]
 pub struct AccessFlags(u16);
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug)]
 pub enum AccessFlag {
     FINAL,     // Indicates that the formal parameter was declared final.
@@ -27,6 +30,14 @@ pub enum AccessFlag {
 }
 
 impl MethodParametersAttribute {
+    /// Passes the parameter names to `f`, which may change them, 0 for a parameter
+    /// without a name
+    pub(crate) fn constant_indices(&mut self, f: &mut dyn FnMut(&mut u16)) {
+        for parameter in &mut self.parameters {
+            f(&mut parameter.name_index);
+        }
+    }
+
     pub fn parse(file: &mut FileReader, att_start: &AttStart) -> Result<MethodParametersAttribute> {
         let mut parameters = Vec::new();
         let parameters_count = file.read_u1()?;
@@ -40,6 +51,17 @@ impl MethodParametersAttribute {
         })
     }
 
+    pub fn write(&self, file: &mut FileWriter) -> Result<()> {
+        let count = u8::try_from(self.parameters.len())
+            .map_err(|_| anyhow!("Too many method parameters: {}", self.parameters.len()))?;
+        file.write_u1(count);
+        for param in &self.parameters {
+            file.write_u2(param.name_index);
+            file.write_u2(param.access_flags.0);
+        }
+        Ok(())
+    }
+
     pub fn to_string(&self, cp: &ConstantPool) -> String {
         let mut s = String::new();
         p!("\nMethodParametersAttribute");
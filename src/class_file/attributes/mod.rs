@@ -1,30 +1,54 @@
+#[allow(clippy::module_inception)]
 mod attributes;
 mod bootstrap_methods_attribute;
 mod code_attribute;
+mod constant_value_attribute;
 mod deprecated_attribute;
+mod enclosing_method_attribute;
 mod exceptions_attribute;
 mod generic_attribute;
 mod inner_classes_attribute;
 mod line_number_table_attribute;
+mod local_variable_table_attribute;
 mod method_parameters_attribute;
+mod nest_host_attribute;
+mod nest_members_attribute;
+mod permitted_subclasses_attribute;
 mod record_attribute;
 mod runtime_visible_annotations_attribute;
 mod signature_attribute;
+mod source_debug_extension_attribute;
 mod source_file_attribute;
 mod stack_map_table_attribute;
+mod synthetic_attribute;
 
 pub use attributes::Attribute;
 pub use attributes::Attributes;
-pub use bootstrap_methods_attribute::BootstrapMethodsAttribute;
+pub use bootstrap_methods_attribute::{BootstrapMethod, BootstrapMethodsAttribute};
 pub use code_attribute::CodeAttribute;
+pub use code_attribute::ExceptionTable;
+pub use constant_value_attribute::ConstantValue;
+pub use constant_value_attribute::ConstantValueAttribute;
 pub use deprecated_attribute::DeprecatedAttribute;
+pub use enclosing_method_attribute::EnclosingMethod;
+pub use enclosing_method_attribute::EnclosingMethodAttribute;
 pub use exceptions_attribute::ExceptionsAttribute;
 pub use generic_attribute::GenericAttribute;
+pub use inner_classes_attribute::InnerClassInfo;
 pub use inner_classes_attribute::InnerClassesAttribute;
-pub use line_number_table_attribute::LineNumberTableAttribute;
+pub use line_number_table_attribute::{LineNumberTable, LineNumberTableAttribute};
+pub use local_variable_table_attribute::{LocalVariable, LocalVariableTableAttribute};
 pub use method_parameters_attribute::MethodParametersAttribute;
+pub use nest_host_attribute::NestHostAttribute;
+pub use nest_members_attribute::NestMembersAttribute;
+pub use permitted_subclasses_attribute::PermittedSubclassesAttribute;
 pub use record_attribute::RecordAttribute;
 pub use runtime_visible_annotations_attribute::RuntimeVisibleAnnotationsAttribute;
+pub use runtime_visible_annotations_attribute::{Annotation, ElementValue};
 pub use signature_attribute::SignatureAttribute;
+pub use source_debug_extension_attribute::SourceDebugExtensionAttribute;
+pub use source_debug_extension_attribute::{Smap, SmapFile, SmapLine, Stratum};
 pub use source_file_attribute::SourceFileAttribute;
 pub use stack_map_table_attribute::StackMapTableAttribute;
+pub use stack_map_table_attribute::{Frame, VerificationType};
+pub use synthetic_attribute::SyntheticAttribute;
@@ -0,0 +1,44 @@
+use super::attributes::AttStart;
+use crate::class_file::{
+    constant_pool::ConstantPool, file_reader::FileReader, file_writer::FileWriter,
+};
+use anyhow::Result;
+
+/// Records the nest host of a class that is a member of a nest (Java 11+)
+#[derive(Debug)]
+pub struct NestHostAttribute {
+    pub(super) attribute_name_index: u16,
+    pub(super) attribute_length: u32,
+    pub host_class_index: u16,
+}
+
+impl NestHostAttribute {
+    pub fn new(attribute_name_index: u16, host_class_index: u16) -> NestHostAttribute {
+        NestHostAttribute {
+            attribute_name_index,
+            attribute_length: 2,
+            host_class_index,
+        }
+    }
+
+    pub fn parse(file: &mut FileReader, att_start: &AttStart) -> Result<NestHostAttribute> {
+        Ok(NestHostAttribute {
+            attribute_name_index: att_start.attribute_name_index,
+            attribute_length: att_start.attribute_length,
+            host_class_index: file.read_u2_to_u16()?,
+        })
+    }
+
+    pub fn write(&self, file: &mut FileWriter) -> Result<()> {
+        file.write_u2(self.host_class_index);
+        Ok(())
+    }
+
+    pub fn host_class(&self, cp: &ConstantPool) -> String {
+        cp.get_to_string(self.host_class_index)
+    }
+
+    pub fn to_string(&self, cp: &ConstantPool) -> String {
+        format!("NestHost: {}\n", self.host_class(cp))
+    }
+}
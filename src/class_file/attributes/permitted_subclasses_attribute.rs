@@ -0,0 +1,64 @@
+use super::attributes::{written_length, AttStart};
+use crate::class_file::{
+    constant_pool::ConstantPool, file_reader::FileReader, file_writer::FileWriter,
+};
+use anyhow::Result;
+
+/// Lists the classes that are allowed to extend or implement a sealed class or interface (Java 17+)
+#[derive(Debug)]
+pub struct PermittedSubclassesAttribute {
+    pub(super) attribute_name_index: u16,
+    pub(super) attribute_length: u32,
+    pub classes: Vec<u16>,
+}
+
+impl PermittedSubclassesAttribute {
+    pub fn new(attribute_name_index: u16, classes: Vec<u16>) -> PermittedSubclassesAttribute {
+        let mut att = PermittedSubclassesAttribute {
+            attribute_name_index,
+            attribute_length: 0,
+            classes,
+        };
+        att.attribute_length = written_length(|file| att.write(file));
+        att
+    }
+
+    pub fn parse(
+        file: &mut FileReader,
+        att_start: &AttStart,
+    ) -> Result<PermittedSubclassesAttribute> {
+        let number_of_classes = file.read_u2_to_u16()?;
+        let mut classes = Vec::with_capacity(number_of_classes as usize);
+        for _ in 0..number_of_classes {
+            classes.push(file.read_u2_to_u16()?);
+        }
+        Ok(PermittedSubclassesAttribute {
+            attribute_name_index: att_start.attribute_name_index,
+            attribute_length: att_start.attribute_length,
+            classes,
+        })
+    }
+
+    pub fn write(&self, file: &mut FileWriter) -> Result<()> {
+        file.write_count(self.classes.len(), "classes")?;
+        for class in &self.classes {
+            file.write_u2(*class);
+        }
+        Ok(())
+    }
+
+    pub fn class_names(&self, cp: &ConstantPool) -> Vec<String> {
+        self.classes.iter().map(|c| cp.get_to_string(*c)).collect()
+    }
+
+    pub fn to_string(&self, cp: &ConstantPool) -> String {
+        let mut s = String::new();
+        s.push_str("PermittedSubclasses:");
+        for class in self.class_names(cp) {
+            s.push_str("\n\t- ");
+            s.push_str(&class);
+        }
+        s.push('\n');
+        s
+    }
+}
@@ -1,12 +1,14 @@
-use super::attributes::AttStart;
-use crate::class_file::attributes::Attributes;
-use crate::class_file::{constant_pool::ConstantPool, file_reader::FileReader};
+use super::attributes::{written_length, AttStart};
+use crate::class_file::attributes::{Attribute, Attributes};
+use crate::class_file::{
+    constant_pool::ConstantPool, file_reader::FileReader, file_writer::FileWriter,
+};
 use anyhow::Result;
 
 #[derive(Debug)]
 pub struct RecordAttribute {
-    attribute_name_index: u16,
-    attribute_length: u32,
+    pub(super) attribute_name_index: u16,
+    pub(super) attribute_length: u32,
     components: Vec<RecordComponentInfo>,
 }
 
@@ -37,6 +39,48 @@ impl RecordAttribute {
         })
     }
 
+    pub fn write(&self, file: &mut FileWriter) -> Result<()> {
+        file.write_count(self.components.len(), "record components")?;
+        for component in &self.components {
+            file.write_u2(component.name_index);
+            file.write_u2(component.descriptor_index);
+            component.attributes.write(file)?;
+        }
+        Ok(())
+    }
+
+    /// Passes the names and descriptors of the components and the constant pool indices
+    /// of their attributes to `f`, which may change them. `cp` is the pool the indices
+    /// currently point into.
+    pub(crate) fn constant_indices(
+        &mut self,
+        cp: &ConstantPool,
+        f: &mut dyn FnMut(&mut u16),
+    ) -> Result<()> {
+        for component in &mut self.components {
+            f(&mut component.name_index);
+            f(&mut component.descriptor_index);
+            component.attributes.constant_indices(cp, f)?;
+        }
+        Ok(())
+    }
+
+    /// Keeps only the component attributes for which `keep` returns true
+    pub(crate) fn retain_attributes(
+        &mut self,
+        keep: &mut dyn FnMut(&Attribute) -> Result<bool>,
+    ) -> Result<()> {
+        for component in &mut self.components {
+            component.attributes.retain(keep)?;
+        }
+        self.attribute_length = written_length(|file| self.write(file));
+        Ok(())
+    }
+
+    pub fn components(&self) -> &Vec<RecordComponentInfo> {
+        &self.components
+    }
+
     pub fn to_string(&self, cp: &ConstantPool) -> String {
         let mut s = String::new();
         s.push_str("Record: ");
@@ -44,7 +88,7 @@ impl RecordAttribute {
             s.push_str(&component.to_string(cp));
             s.push_str(", ");
         }
-        s.push_str("\n");
+        s.push('\n');
         s
     }
 }
@@ -62,6 +106,18 @@ impl RecordComponentInfo {
         })
     }
 
+    pub fn name(&self, cp: &ConstantPool) -> String {
+        cp.get_to_string(self.name_index)
+    }
+
+    pub fn descriptor(&self, cp: &ConstantPool) -> String {
+        cp.get_to_string(self.descriptor_index)
+    }
+
+    pub fn attributes(&self) -> &Attributes {
+        &self.attributes
+    }
+
     pub fn to_string(&self, cp: &ConstantPool) -> String {
         let mut s = String::new();
         s.push_str(&cp.get_to_string(self.name_index));
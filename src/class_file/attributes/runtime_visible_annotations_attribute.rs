@@ -1,31 +1,37 @@
 use super::attributes::AttStart;
 
-use crate::class_file::{constant_pool::ConstantPool, file_reader::FileReader};
+use crate::class_file::{
+    constant_pool::ConstantPool, file_reader::FileReader, file_writer::FileWriter,
+};
 
 use anyhow::Result;
 
 #[derive(Debug)]
 pub struct RuntimeVisibleAnnotationsAttribute {
-    attribute_name_index: u16,
-    attribute_length: u32,
-    annotations: Vec<Annotation>,
+    pub(super) attribute_name_index: u16,
+    pub(super) attribute_length: u32,
+    pub annotations: Vec<Annotation>,
 }
 
 #[derive(Debug)]
 pub struct Annotation {
-    type_index: u16,
-    element_value_pairs: Vec<ElementValuePair>,
+    pub type_index: u16,
+    pub element_value_pairs: Vec<ElementValuePair>,
 }
 
 #[derive(Debug)]
 pub struct ElementValuePair {
-    element_name_index: u16,
-    value: ElementValue,
+    pub element_name_index: u16,
+    pub value: ElementValue,
 }
 
 #[derive(Debug)]
 pub enum ElementValue {
-    ConstValueIndex(u16),
+    /// `tag` is the type of the constant, one of `BCDFIJSZs`
+    ConstValueIndex {
+        tag: u8,
+        const_value_index: u16,
+    },
     EnumConstValue {
         type_name_index: u16,
         const_name_index: u16,
@@ -53,6 +59,14 @@ impl RuntimeVisibleAnnotationsAttribute {
         })
     }
 
+    pub fn write(&self, file: &mut FileWriter) -> Result<()> {
+        file.write_count(self.annotations.len(), "annotations")?;
+        for annotation in &self.annotations {
+            annotation.write(file)?;
+        }
+        Ok(())
+    }
+
     pub fn to_string(&self, cp: &ConstantPool) -> String {
         let mut s = String::new();
         s.push_str("RuntimeVisibleAnnotations: ");
@@ -65,6 +79,16 @@ impl RuntimeVisibleAnnotationsAttribute {
 }
 
 impl Annotation {
+    /// Passes the constant pool indices of the annotation and its values to `f`, which
+    /// may change them
+    pub(crate) fn constant_indices(&mut self, f: &mut dyn FnMut(&mut u16)) {
+        f(&mut self.type_index);
+        for pair in &mut self.element_value_pairs {
+            f(&mut pair.element_name_index);
+            pair.value.constant_indices(f);
+        }
+    }
+
     pub fn parse(file: &mut FileReader) -> Result<Annotation> {
         let type_index = file.read_u2_to_u16()?;
         let num_pairs = file.read_u2_to_u16()?;
@@ -83,35 +107,64 @@ impl Annotation {
         })
     }
 
+    pub fn write(&self, file: &mut FileWriter) -> Result<()> {
+        file.write_u2(self.type_index);
+        file.write_count(self.element_value_pairs.len(), "element value pairs")?;
+        for pair in &self.element_value_pairs {
+            file.write_u2(pair.element_name_index);
+            pair.value.write(file)?;
+        }
+        Ok(())
+    }
+
     pub fn to_string(&self, cp: &ConstantPool) -> String {
         let mut s = String::new();
-        s.push_str("@");
+        s.push('@');
         s.push_str(&cp.get_to_string(self.type_index));
-        s.push_str("(");
+        s.push('(');
         for pair in &self.element_value_pairs {
             s.push_str(&cp.get_to_string(pair.element_name_index));
             s.push_str(" = ");
             s.push_str(&pair.value.to_string(cp));
             s.push_str(", ");
         }
-        s.push_str(")");
+        s.push(')');
         s
     }
 }
 
 impl ElementValue {
+    pub(crate) fn constant_indices(&mut self, f: &mut dyn FnMut(&mut u16)) {
+        match self {
+            ElementValue::ConstValueIndex {
+                const_value_index, ..
+            } => f(const_value_index),
+            ElementValue::EnumConstValue {
+                type_name_index,
+                const_name_index,
+            } => {
+                f(type_name_index);
+                f(const_name_index);
+            }
+            ElementValue::ClassInfoIndex(index) => f(index),
+            ElementValue::AnnotationValue(annotation) => annotation.constant_indices(f),
+            ElementValue::ArrayValue(values) => {
+                for value in values {
+                    value.constant_indices(f);
+                }
+            }
+        }
+    }
+
     pub fn parse(file: &mut FileReader) -> Result<ElementValue> {
         let tag = file.read_u1()?;
         match tag {
-            b'B' => Ok(ElementValue::ConstValueIndex(file.read_u2_to_u16()?)),
-            b'C' => Ok(ElementValue::ConstValueIndex(file.read_u2_to_u16()?)),
-            b'D' => Ok(ElementValue::ConstValueIndex(file.read_u2_to_u16()?)),
-            b'F' => Ok(ElementValue::ConstValueIndex(file.read_u2_to_u16()?)),
-            b'I' => Ok(ElementValue::ConstValueIndex(file.read_u2_to_u16()?)),
-            b'J' => Ok(ElementValue::ConstValueIndex(file.read_u2_to_u16()?)),
-            b'S' => Ok(ElementValue::ConstValueIndex(file.read_u2_to_u16()?)),
-            b'Z' => Ok(ElementValue::ConstValueIndex(file.read_u2_to_u16()?)),
-            b's' => Ok(ElementValue::ConstValueIndex(file.read_u2_to_u16()?)),
+            b'B' | b'C' | b'D' | b'F' | b'I' | b'J' | b'S' | b'Z' | b's' => {
+                Ok(ElementValue::ConstValueIndex {
+                    tag,
+                    const_value_index: file.read_u2_to_u16()?,
+                })
+            }
             b'e' => {
                 let type_name_index = file.read_u2_to_u16()?;
                 let const_name_index = file.read_u2_to_u16()?;
@@ -138,9 +191,47 @@ impl ElementValue {
         }
     }
 
+    pub fn write(&self, file: &mut FileWriter) -> Result<()> {
+        match self {
+            ElementValue::ConstValueIndex {
+                tag,
+                const_value_index,
+            } => {
+                file.write_u1(*tag);
+                file.write_u2(*const_value_index);
+            }
+            ElementValue::EnumConstValue {
+                type_name_index,
+                const_name_index,
+            } => {
+                file.write_u1(b'e');
+                file.write_u2(*type_name_index);
+                file.write_u2(*const_name_index);
+            }
+            ElementValue::ClassInfoIndex(index) => {
+                file.write_u1(b'c');
+                file.write_u2(*index);
+            }
+            ElementValue::AnnotationValue(annotation) => {
+                file.write_u1(b'@');
+                annotation.write(file)?;
+            }
+            ElementValue::ArrayValue(values) => {
+                file.write_u1(b'[');
+                file.write_count(values.len(), "array values")?;
+                for value in values {
+                    value.write(file)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
     pub fn to_string(&self, cp: &ConstantPool) -> String {
         match self {
-            ElementValue::ConstValueIndex(index) => cp.get_to_string(*index),
+            ElementValue::ConstValueIndex {
+                const_value_index, ..
+            } => cp.get_to_string(*const_value_index),
             ElementValue::EnumConstValue {
                 type_name_index,
                 const_name_index,
@@ -155,12 +246,12 @@ impl ElementValue {
             ElementValue::AnnotationValue(annotation) => annotation.to_string(cp),
             ElementValue::ArrayValue(values) => {
                 let mut s = String::new();
-                s.push_str("{");
+                s.push('{');
                 for value in values {
                     s.push_str(&value.to_string(cp));
                     s.push_str(", ");
                 }
-                s.push_str("}");
+                s.push('}');
                 s
             }
         }
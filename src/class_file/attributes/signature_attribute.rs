@@ -1,15 +1,25 @@
 use super::attributes::AttStart;
-use crate::class_file::{constant_pool::ConstantPool, file_reader::FileReader};
+use crate::class_file::{
+    constant_pool::ConstantPool, file_reader::FileReader, file_writer::FileWriter,
+};
 use anyhow::Result;
 
 #[derive(Debug)]
 pub struct SignatureAttribute {
-    attribute_name_index: u16,
-    attribute_length: u32,
-    signature_index: u16,
+    pub(super) attribute_name_index: u16,
+    pub(super) attribute_length: u32,
+    pub signature_index: u16,
 }
 
 impl SignatureAttribute {
+    pub fn new(attribute_name_index: u16, signature_index: u16) -> SignatureAttribute {
+        SignatureAttribute {
+            attribute_name_index,
+            attribute_length: 2,
+            signature_index,
+        }
+    }
+
     pub fn parse(file: &mut FileReader, att_start: &AttStart) -> Result<SignatureAttribute> {
         Ok(SignatureAttribute {
             attribute_name_index: att_start.attribute_name_index,
@@ -18,6 +28,11 @@ impl SignatureAttribute {
         })
     }
 
+    pub fn write(&self, file: &mut FileWriter) -> Result<()> {
+        file.write_u2(self.signature_index);
+        Ok(())
+    }
+
     pub fn to_string(&self, cp: &ConstantPool) -> String {
         let signature = cp.get_to_string(self.signature_index);
         format!("Signature: {}\n", signature)
@@ -0,0 +1,315 @@
+use super::attributes::AttStart;
+use crate::class_file::{
+    constant_pool::ConstantPool, file_reader::FileReader, file_writer::FileWriter,
+};
+use anyhow::{anyhow, Result};
+
+/// Extra debugging information, in practice a SMAP (JSR-045) that maps the lines of the
+/// generated class back to the original sources (JSP pages, Kotlin inline functions, ...)
+#[derive(Debug)]
+pub struct SourceDebugExtensionAttribute {
+    pub(super) attribute_name_index: u16,
+    pub(super) attribute_length: u32,
+    pub debug_extension: Vec<u8>,
+}
+
+/// Source map as described in JSR-045
+///
+/// ```text
+/// SMAP
+/// Foo.kt              <- name of the generated file
+/// Kotlin              <- default stratum
+/// *S Kotlin           <- stratum section
+/// *F                  <- file section
+/// + 1 Foo.kt          <- file id, name and (because of the +) a path on the next line
+/// Foo
+/// *L                  <- line section
+/// 1#1,10:1            <- InputStartLine#FileId,RepeatCount:OutputStartLine,OutputLineIncrement
+/// *E
+/// ```
+#[derive(Debug, PartialEq)]
+pub struct Smap {
+    pub output_file_name: String,
+    pub default_stratum: String,
+    pub strata: Vec<Stratum>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Stratum {
+    pub name: String,
+    pub files: Vec<SmapFile>,
+    pub lines: Vec<SmapLine>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct SmapFile {
+    pub id: u32,
+    pub name: String,
+    pub path: Option<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct SmapLine {
+    pub input_start_line: u32,
+    pub file_id: u32,
+    pub repeat_count: u32,
+    pub output_start_line: u32,
+    pub output_line_increment: u32,
+}
+
+impl SourceDebugExtensionAttribute {
+    pub fn parse(
+        file: &mut FileReader,
+        att_start: &AttStart,
+    ) -> Result<SourceDebugExtensionAttribute> {
+        let mut debug_extension = Vec::new();
+        file.read_into(&mut debug_extension, att_start.attribute_length as usize)?;
+        Ok(SourceDebugExtensionAttribute {
+            attribute_name_index: att_start.attribute_name_index,
+            attribute_length: att_start.attribute_length,
+            debug_extension,
+        })
+    }
+
+    pub fn write(&self, file: &mut FileWriter) -> Result<()> {
+        file.write_bytes(&self.debug_extension);
+        Ok(())
+    }
+
+    pub fn as_string(&self) -> String {
+        String::from_utf8_lossy(&self.debug_extension).to_string()
+    }
+
+    pub fn smap(&self) -> Result<Smap> {
+        Smap::parse(&self.as_string())
+    }
+
+    pub fn to_string(&self, _cp: &ConstantPool) -> String {
+        format!("SourceDebugExtension:\n{}\n", self.as_string())
+    }
+}
+
+impl Smap {
+    pub fn parse(s: &str) -> Result<Smap> {
+        let mut lines = s.lines().map(|l| l.trim_end_matches('\r')).peekable();
+
+        if lines.next() != Some("SMAP") {
+            return Err(anyhow!("SMAP header missing"));
+        }
+        let output_file_name = lines
+            .next()
+            .ok_or(anyhow!("SMAP output file name missing"))?
+            .to_string();
+        let default_stratum = lines
+            .next()
+            .ok_or(anyhow!("SMAP default stratum missing"))?
+            .to_string();
+
+        let mut strata: Vec<Stratum> = Vec::new();
+        while let Some(line) = lines.next() {
+            if let Some(name) = line.strip_prefix("*S ") {
+                strata.push(Stratum {
+                    name: name.trim().to_string(),
+                    files: Vec::new(),
+                    lines: Vec::new(),
+                });
+            } else if line == "*F" {
+                let stratum = strata.last_mut().ok_or(anyhow!("*F outside of stratum"))?;
+                while let Some(line) = lines.next_if(|l| !l.starts_with('*')) {
+                    let mut file = SmapFile::parse(line)?;
+                    if line.starts_with('+') {
+                        file.path = lines.next().map(|p| p.to_string());
+                    }
+                    stratum.files.push(file);
+                }
+            } else if line == "*L" {
+                let stratum = strata.last_mut().ok_or(anyhow!("*L outside of stratum"))?;
+                let mut file_id = 0;
+                while let Some(line) = lines.next_if(|l| !l.starts_with('*')) {
+                    let smap_line = SmapLine::parse(line, file_id)?;
+                    file_id = smap_line.file_id;
+                    stratum.lines.push(smap_line);
+                }
+            }
+            // end (*E), vendor (*V) and unknown sections are skipped, Kotlin emits an *E
+            // after every stratum so it can't be treated as the end of the map
+        }
+
+        Ok(Smap {
+            output_file_name,
+            default_stratum,
+            strata,
+        })
+    }
+
+    pub fn stratum(&self, name: &str) -> Option<&Stratum> {
+        self.strata.iter().find(|s| s.name == name)
+    }
+
+    /// Maps a line of the generated class (as found in the LineNumberTable) to the
+    /// source file and line in the default stratum
+    pub fn map_line(&self, output_line: u32) -> Option<(&SmapFile, u32)> {
+        self.stratum(&self.default_stratum)?.map_line(output_line)
+    }
+}
+
+impl Stratum {
+    pub fn file(&self, id: u32) -> Option<&SmapFile> {
+        self.files.iter().find(|f| f.id == id)
+    }
+
+    pub fn map_line(&self, output_line: u32) -> Option<(&SmapFile, u32)> {
+        for line in &self.lines {
+            if let Some(input_line) = line.map(output_line) {
+                return Some((self.file(line.file_id)?, input_line));
+            }
+        }
+        None
+    }
+}
+
+impl SmapFile {
+    fn parse(line: &str) -> Result<SmapFile> {
+        let line = line.strip_prefix('+').unwrap_or(line).trim();
+        let (id, name) = line
+            .split_once(' ')
+            .ok_or(anyhow!("Invalid SMAP file info: {}", line))?;
+        Ok(SmapFile {
+            id: id.parse()?,
+            name: name.to_string(),
+            path: None,
+        })
+    }
+}
+
+impl SmapLine {
+    /// `InputStartLine [#LineFileID] [,RepeatCount] : OutputStartLine [,OutputLineIncrement]`
+    /// the file id defaults to the one of the previous line
+    fn parse(line: &str, previous_file_id: u32) -> Result<SmapLine> {
+        let (input, output) = line
+            .split_once(':')
+            .ok_or(anyhow!("Invalid SMAP line info: {}", line))?;
+
+        let (input, repeat_count) = match input.split_once(',') {
+            Some((input, repeat_count)) => (input, repeat_count.trim().parse()?),
+            None => (input, 1),
+        };
+        let (input_start_line, file_id) = match input.split_once('#') {
+            Some((line, file_id)) => (line.trim().parse()?, file_id.trim().parse()?),
+            None => (input.trim().parse()?, previous_file_id),
+        };
+        let (output_start_line, output_line_increment) = match output.split_once(',') {
+            Some((line, increment)) => (line.trim().parse()?, increment.trim().parse()?),
+            None => (output.trim().parse()?, 1),
+        };
+
+        Ok(SmapLine {
+            input_start_line,
+            file_id,
+            repeat_count,
+            output_start_line,
+            output_line_increment,
+        })
+    }
+
+    fn map(&self, output_line: u32) -> Option<u32> {
+        if output_line < self.output_start_line {
+            return None;
+        }
+        let offset = output_line - self.output_start_line;
+        let n = match self.output_line_increment {
+            0 if offset == 0 => 0,
+            0 => return None,
+            increment => offset / increment,
+        };
+        if n < self.repeat_count {
+            Some(self.input_start_line + n)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KOTLIN_SMAP: &str = "SMAP
+Foo.kt
+Kotlin
+*S Kotlin
+*F
++ 1 Foo.kt
+com/example/FooKt
++ 2 Bar.kt
+com/example/BarKt
+*L
+1#1,10:1
+5#2,3:11
+20,2:14,2
+*E
+*S KotlinDebug
+*F
++ 1 Foo.kt
+com/example/FooKt
+*L
+4#1:11
+*E
+";
+
+    #[test]
+    fn test_parse_smap() {
+        let smap = Smap::parse(KOTLIN_SMAP).unwrap();
+        assert_eq!(smap.output_file_name, "Foo.kt");
+        assert_eq!(smap.default_stratum, "Kotlin");
+        assert_eq!(smap.strata.len(), 2);
+
+        let kotlin = smap.stratum("Kotlin").unwrap();
+        assert_eq!(
+            kotlin.files[1],
+            SmapFile {
+                id: 2,
+                name: "Bar.kt".to_string(),
+                path: Some("com/example/BarKt".to_string()),
+            }
+        );
+        assert_eq!(
+            kotlin.lines[2],
+            SmapLine {
+                input_start_line: 20,
+                file_id: 2,
+                repeat_count: 2,
+                output_start_line: 14,
+                output_line_increment: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn test_map_smap_lines() {
+        let smap = Smap::parse(KOTLIN_SMAP).unwrap();
+        let cases = vec![
+            (1, Some(("Foo.kt", 1))),
+            (10, Some(("Foo.kt", 10))),
+            (12, Some(("Bar.kt", 6))),
+            (15, Some(("Bar.kt", 20))),
+            (17, Some(("Bar.kt", 21))),
+            (18, None),
+        ];
+        for (output_line, expected) in cases {
+            let mapped = smap
+                .map_line(output_line)
+                .map(|(file, line)| (file.name.as_str(), line));
+            assert_eq!(mapped, expected, "output line {}", output_line);
+        }
+
+        let debug = smap.stratum("KotlinDebug").unwrap();
+        assert_eq!(debug.map_line(11).unwrap().1, 4);
+    }
+
+    #[test]
+    fn test_invalid_smap() {
+        assert!(Smap::parse("not a smap").is_err());
+        assert!(Smap::parse("SMAP\nFoo.jsp\nJSP\n*S JSP\n*L\n1#x:1\n*E\n").is_err());
+    }
+}
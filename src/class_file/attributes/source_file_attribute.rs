@@ -1,17 +1,27 @@
 use super::attributes::AttStart;
 
-use crate::class_file::{constant_pool::ConstantPool, file_reader::FileReader};
+use crate::class_file::{
+    constant_pool::ConstantPool, file_reader::FileReader, file_writer::FileWriter,
+};
 
 use anyhow::Result;
 
 #[derive(Debug)]
 pub struct SourceFileAttribute {
-    attribute_name_index: u16,
-    attribute_length: u32,
-    sourcefile_index: u16,
+    pub(super) attribute_name_index: u16,
+    pub(super) attribute_length: u32,
+    pub sourcefile_index: u16,
 }
 
 impl SourceFileAttribute {
+    pub fn new(attribute_name_index: u16, sourcefile_index: u16) -> SourceFileAttribute {
+        SourceFileAttribute {
+            attribute_name_index,
+            attribute_length: 2,
+            sourcefile_index,
+        }
+    }
+
     pub fn parse(file: &mut FileReader, att_start: &AttStart) -> Result<SourceFileAttribute> {
         let sourcefile_index = file.read_u2_to_u16()?;
         Ok(SourceFileAttribute {
@@ -21,6 +31,11 @@ impl SourceFileAttribute {
         })
     }
 
+    pub fn write(&self, file: &mut FileWriter) -> Result<()> {
+        file.write_u2(self.sourcefile_index);
+        Ok(())
+    }
+
     pub fn to_string(&self, cp: &ConstantPool) -> String {
         cp.get_to_string(self.sourcefile_index)
     }
@@ -1,12 +1,41 @@
 use super::attributes::AttStart;
-use crate::class_file::{constant_pool::ConstantPool, file_reader::FileReader};
-use anyhow::Result;
+use crate::class_file::{
+    constant_pool::ConstantPool, file_reader::FileReader, file_writer::FileWriter,
+};
+use crate::class_file::{BaseType, FieldType};
+use anyhow::{anyhow, Result};
+use std::fmt;
 
 #[derive(Debug)]
 pub struct StackMapTableAttribute {
-    attribute_name_index: u16,
-    attribute_length: u32,
-    entries: Vec<StackMapFrame>,
+    pub(super) attribute_name_index: u16,
+    pub(super) attribute_length: u32,
+    pub entries: Vec<StackMapFrame>,
+}
+
+/// A stack map frame expanded to its absolute pc with the full list of locals and stack entries.
+///
+/// Like in the class file, `Long` and `Double` locals are a single entry even though
+/// they take up two local variable slots.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Frame {
+    pub pc: u16,
+    pub locals: Vec<VerificationType>,
+    pub stack: Vec<VerificationType>,
+}
+
+/// `VerificationTypeInfo` with class references resolved against the constant pool
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub enum VerificationType {
+    Top,
+    Integer,
+    Float,
+    Long,
+    Double,
+    Null,
+    UninitializedThis,
+    Object(String), // binary class name or array descriptor, e.g. `java/lang/String` or `[I`
+    Uninitialized(u16), // pc of the `new` instruction that created the object
 }
 
 #[derive(Debug)]
@@ -22,51 +51,52 @@ pub enum StackMapFrame {
 
 #[derive(Debug)]
 pub struct SameFrame {
-    offset_delta: u8,
+    pub offset_delta: u8,
 }
 
 #[derive(Debug)]
 pub struct SameLocals1StackItemFrame {
     // type 64-127
-    offset_delta: u8,
-    stack: VerificationTypeInfo,
+    pub offset_delta: u8,
+    pub stack: VerificationTypeInfo,
 }
 
 #[derive(Debug)]
 pub struct SameLocals1StackItemFrameExtended {
     // type 247
-    offset_delta: u16,
-    stack: VerificationTypeInfo,
+    pub offset_delta: u16,
+    pub stack: VerificationTypeInfo,
 }
 
 #[derive(Debug)]
 pub struct ChopFrame {
     // type 248-250
-    k_absent: u8, // 251 - _type
-    offset_delta: u16,
+    pub k_absent: u8, // 251 - _type
+    pub offset_delta: u16,
 }
 
 #[derive(Debug)]
 pub struct SameFrameExtended {
     // type 251
-    offset_delta: u16,
+    pub offset_delta: u16,
 }
 
 #[derive(Debug)]
 pub struct AppendFrame {
     // type 252-254
-    offset_delta: u16,
-    locals: Vec<VerificationTypeInfo>, // length = _type - 251
+    pub offset_delta: u16,
+    pub locals: Vec<VerificationTypeInfo>, // length = _type - 251
 }
 
 #[derive(Debug)]
 pub struct FullFrame {
     // type 255
-    offset_delta: u16,
-    locals: Vec<VerificationTypeInfo>,
-    stack: Vec<VerificationTypeInfo>,
+    pub offset_delta: u16,
+    pub locals: Vec<VerificationTypeInfo>,
+    pub stack: Vec<VerificationTypeInfo>,
 }
 
+#[allow(clippy::enum_variant_names)]
 #[derive(Debug)]
 pub enum VerificationTypeInfo {
     TopVaiableInfo,                            // 0
@@ -81,6 +111,28 @@ pub enum VerificationTypeInfo {
 }
 
 impl StackMapTableAttribute {
+    /// Passes the class indices of the frames to `f`, which may change them
+    pub(crate) fn constant_indices(&mut self, f: &mut dyn FnMut(&mut u16)) {
+        for entry in &mut self.entries {
+            match entry {
+                StackMapFrame::Same(_)
+                | StackMapFrame::Chop(_)
+                | StackMapFrame::SameExtended(_) => {}
+                StackMapFrame::SameLocals1StackItem(frame) => frame.stack.constant_indices(f),
+                StackMapFrame::SameLocals1StackItemExtended(frame) => {
+                    frame.stack.constant_indices(f)
+                }
+                StackMapFrame::Append(frame) => {
+                    frame.locals.iter_mut().for_each(|v| v.constant_indices(f))
+                }
+                StackMapFrame::Full(frame) => {
+                    frame.locals.iter_mut().for_each(|v| v.constant_indices(f));
+                    frame.stack.iter_mut().for_each(|v| v.constant_indices(f));
+                }
+            }
+        }
+    }
+
     pub fn parse(file: &mut FileReader, att_start: &AttStart) -> Result<StackMapTableAttribute> {
         let mut entries = Vec::new();
         let number_of_entries = file.read_u2_to_u16()?;
@@ -96,6 +148,96 @@ impl StackMapTableAttribute {
         })
     }
 
+    pub fn write(&self, file: &mut FileWriter) -> Result<()> {
+        file.write_count(self.entries.len(), "stack map frames")?;
+        for entry in &self.entries {
+            entry.write(file);
+        }
+        Ok(())
+    }
+
+    /// Expands the delta encoded entries into frames with absolute pcs.
+    /// `initial_locals` is the implicit frame at pc 0 built from the method descriptor
+    /// (see `Method::initial_frame`).
+    pub fn frames(
+        &self,
+        initial_locals: Vec<VerificationType>,
+        cp: &ConstantPool,
+    ) -> Result<Vec<Frame>> {
+        let mut frames = Vec::with_capacity(self.entries.len());
+        let mut locals = initial_locals;
+        let mut previous_pc: Option<u16> = None;
+        for entry in &self.entries {
+            // the first frame is at offset_delta, the next ones at previous + offset_delta + 1
+            let pc = match previous_pc {
+                None => entry.offset_delta(),
+                Some(pc) => pc
+                    .checked_add(entry.offset_delta() + 1)
+                    .ok_or(anyhow!("StackMapTable pc overflow"))?,
+            };
+            let stack = match entry {
+                StackMapFrame::Same(_) | StackMapFrame::SameExtended(_) => Vec::new(),
+                StackMapFrame::SameLocals1StackItem(frame) => vec![frame.stack.resolve(cp)],
+                StackMapFrame::SameLocals1StackItemExtended(frame) => {
+                    vec![frame.stack.resolve(cp)]
+                }
+                StackMapFrame::Chop(frame) => {
+                    let k = frame.k_absent as usize;
+                    if k > locals.len() {
+                        return Err(anyhow!("Chop frame at {} removes too many locals", pc));
+                    }
+                    locals.truncate(locals.len() - k);
+                    Vec::new()
+                }
+                StackMapFrame::Append(frame) => {
+                    locals.extend(frame.locals.iter().map(|l| l.resolve(cp)));
+                    Vec::new()
+                }
+                StackMapFrame::Full(frame) => {
+                    locals = frame.locals.iter().map(|l| l.resolve(cp)).collect();
+                    frame.stack.iter().map(|s| s.resolve(cp)).collect()
+                }
+            };
+            frames.push(Frame {
+                pc,
+                locals: locals.clone(),
+                stack,
+            });
+            previous_pc = Some(pc);
+        }
+        Ok(frames)
+    }
+
+    /// Encodes frames with absolute pcs (sorted, as returned by `Method::compute_frames`)
+    /// using the most compact frame types, adding the referenced classes to the constant pool
+    pub fn from_frames(
+        attribute_name_index: u16,
+        initial_locals: &[VerificationType],
+        frames: &[Frame],
+        cp: &mut ConstantPool,
+    ) -> Result<StackMapTableAttribute> {
+        let mut entries = Vec::with_capacity(frames.len());
+        let mut locals = initial_locals;
+        let mut previous_pc: Option<u16> = None;
+        for frame in frames {
+            let offset_delta = match previous_pc {
+                None => frame.pc,
+                Some(pc) if frame.pc > pc => frame.pc - pc - 1,
+                Some(_) => return Err(anyhow!("Frames are not sorted at pc {}", frame.pc)),
+            };
+            entries.push(StackMapFrame::encode(offset_delta, locals, frame, cp)?);
+            locals = &frame.locals;
+            previous_pc = Some(frame.pc);
+        }
+
+        let attribute_length = 2 + entries.iter().map(|e| e.length()).sum::<u32>();
+        Ok(StackMapTableAttribute {
+            attribute_name_index,
+            attribute_length,
+            entries,
+        })
+    }
+
     pub fn to_string(&self, cp: &ConstantPool) -> String {
         let mut s = String::new();
         s.push_str("StackMapTable\n");
@@ -107,6 +249,139 @@ impl StackMapTableAttribute {
 }
 
 impl StackMapFrame {
+    pub fn offset_delta(&self) -> u16 {
+        match self {
+            Self::Same(frame) => frame.offset_delta as u16,
+            Self::SameLocals1StackItem(frame) => frame.offset_delta as u16,
+            Self::SameLocals1StackItemExtended(frame) => frame.offset_delta,
+            Self::Chop(frame) => frame.offset_delta,
+            Self::SameExtended(frame) => frame.offset_delta,
+            Self::Append(frame) => frame.offset_delta,
+            Self::Full(frame) => frame.offset_delta,
+        }
+    }
+
+    /// Picks the smallest frame type that describes `frame` relative to the `previous` locals
+    fn encode(
+        offset_delta: u16,
+        previous: &[VerificationType],
+        frame: &Frame,
+        cp: &mut ConstantPool,
+    ) -> Result<StackMapFrame> {
+        let same_locals = frame.locals == previous;
+        let encoded = if same_locals && frame.stack.is_empty() {
+            if offset_delta < 64 {
+                StackMapFrame::Same(SameFrame {
+                    offset_delta: offset_delta as u8,
+                })
+            } else {
+                StackMapFrame::SameExtended(SameFrameExtended { offset_delta })
+            }
+        } else if same_locals && frame.stack.len() == 1 {
+            let stack = VerificationTypeInfo::encode(&frame.stack[0], cp)?;
+            if offset_delta < 64 {
+                StackMapFrame::SameLocals1StackItem(SameLocals1StackItemFrame {
+                    offset_delta: offset_delta as u8,
+                    stack,
+                })
+            } else {
+                StackMapFrame::SameLocals1StackItemExtended(SameLocals1StackItemFrameExtended {
+                    offset_delta,
+                    stack,
+                })
+            }
+        } else if frame.stack.is_empty()
+            && frame.locals.len() > previous.len()
+            && frame.locals.len() - previous.len() <= 3
+            && frame.locals.starts_with(previous)
+        {
+            let locals = frame.locals[previous.len()..]
+                .iter()
+                .map(|l| VerificationTypeInfo::encode(l, cp))
+                .collect::<Result<Vec<_>>>()?;
+            StackMapFrame::Append(AppendFrame {
+                offset_delta,
+                locals,
+            })
+        } else if frame.stack.is_empty()
+            && frame.locals.len() < previous.len()
+            && previous.len() - frame.locals.len() <= 3
+            && previous.starts_with(&frame.locals)
+        {
+            StackMapFrame::Chop(ChopFrame {
+                k_absent: (previous.len() - frame.locals.len()) as u8,
+                offset_delta,
+            })
+        } else {
+            let encode_all = |types: &[VerificationType], cp: &mut ConstantPool| {
+                types
+                    .iter()
+                    .map(|t| VerificationTypeInfo::encode(t, cp))
+                    .collect::<Result<Vec<_>>>()
+            };
+            StackMapFrame::Full(FullFrame {
+                offset_delta,
+                locals: encode_all(&frame.locals, cp)?,
+                stack: encode_all(&frame.stack, cp)?,
+            })
+        };
+        Ok(encoded)
+    }
+
+    /// Size of the encoded frame in bytes
+    fn length(&self) -> u32 {
+        let sum = |types: &[VerificationTypeInfo]| types.iter().map(|t| t.length()).sum::<u32>();
+        match self {
+            Self::Same(_) => 1,
+            Self::SameLocals1StackItem(frame) => 1 + frame.stack.length(),
+            Self::SameLocals1StackItemExtended(frame) => 3 + frame.stack.length(),
+            Self::Chop(_) | Self::SameExtended(_) => 3,
+            Self::Append(frame) => 3 + sum(&frame.locals),
+            Self::Full(frame) => 3 + 2 + sum(&frame.locals) + 2 + sum(&frame.stack),
+        }
+    }
+
+    fn write(&self, file: &mut FileWriter) {
+        let write_all = |file: &mut FileWriter, types: &[VerificationTypeInfo]| {
+            for t in types {
+                t.write(file);
+            }
+        };
+        match self {
+            Self::Same(frame) => file.write_u1(frame.offset_delta),
+            Self::SameLocals1StackItem(frame) => {
+                file.write_u1(64 + frame.offset_delta);
+                frame.stack.write(file);
+            }
+            Self::SameLocals1StackItemExtended(frame) => {
+                file.write_u1(247);
+                file.write_u2(frame.offset_delta);
+                frame.stack.write(file);
+            }
+            Self::Chop(frame) => {
+                file.write_u1(251 - frame.k_absent);
+                file.write_u2(frame.offset_delta);
+            }
+            Self::SameExtended(frame) => {
+                file.write_u1(251);
+                file.write_u2(frame.offset_delta);
+            }
+            Self::Append(frame) => {
+                file.write_u1(251 + frame.locals.len() as u8);
+                file.write_u2(frame.offset_delta);
+                write_all(file, &frame.locals);
+            }
+            Self::Full(frame) => {
+                file.write_u1(255);
+                file.write_u2(frame.offset_delta);
+                file.write_u2(frame.locals.len() as u16);
+                write_all(file, &frame.locals);
+                file.write_u2(frame.stack.len() as u16);
+                write_all(file, &frame.stack);
+            }
+        }
+    }
+
     fn parse(file: &mut FileReader) -> Result<StackMapFrame> {
         let frame_type = file.read_u1()?;
         match frame_type {
@@ -117,11 +392,11 @@ impl StackMapFrame {
             251 => Self::parse_same_extended(file),
             252..=254 => Self::parse_append(file, frame_type),
             255 => Self::parse_full(file),
-            _ => panic!("Invalid frame type: {}", frame_type),
+            _ => Err(anyhow!("Invalid stack map frame type {}", frame_type)),
         }
     }
 
-    fn parse_same(file: &mut FileReader, frame_type: u8) -> Result<StackMapFrame> {
+    fn parse_same(_file: &mut FileReader, frame_type: u8) -> Result<StackMapFrame> {
         Ok(StackMapFrame::Same(SameFrame {
             offset_delta: frame_type,
         }))
@@ -252,6 +527,82 @@ impl StackMapFrame {
 }
 
 impl VerificationTypeInfo {
+    /// Passes the class index of an `Object` entry to `f`, which may change it
+    pub(crate) fn constant_indices(&mut self, f: &mut dyn FnMut(&mut u16)) {
+        if let VerificationTypeInfo::ObjectVariableInfo { cpool_index } = self {
+            f(cpool_index);
+        }
+    }
+
+    pub fn resolve(&self, cp: &ConstantPool) -> VerificationType {
+        match self {
+            VerificationTypeInfo::TopVaiableInfo => VerificationType::Top,
+            VerificationTypeInfo::IntegerVariableInfo => VerificationType::Integer,
+            VerificationTypeInfo::FloatVariableInfo => VerificationType::Float,
+            VerificationTypeInfo::LongVariableInfo => VerificationType::Long,
+            VerificationTypeInfo::DoubleVariableInfo => VerificationType::Double,
+            VerificationTypeInfo::NullVariableInfo => VerificationType::Null,
+            VerificationTypeInfo::UninitializedThisVariableInfo => {
+                VerificationType::UninitializedThis
+            }
+            VerificationTypeInfo::ObjectVariableInfo { cpool_index } => {
+                VerificationType::Object(cp.get_to_string(*cpool_index))
+            }
+            VerificationTypeInfo::UninitializedVariableInfo { offset } => {
+                VerificationType::Uninitialized(*offset)
+            }
+        }
+    }
+
+    fn encode(t: &VerificationType, cp: &mut ConstantPool) -> Result<VerificationTypeInfo> {
+        let info = match t {
+            VerificationType::Top => VerificationTypeInfo::TopVaiableInfo,
+            VerificationType::Integer => VerificationTypeInfo::IntegerVariableInfo,
+            VerificationType::Float => VerificationTypeInfo::FloatVariableInfo,
+            VerificationType::Long => VerificationTypeInfo::LongVariableInfo,
+            VerificationType::Double => VerificationTypeInfo::DoubleVariableInfo,
+            VerificationType::Null => VerificationTypeInfo::NullVariableInfo,
+            VerificationType::UninitializedThis => {
+                VerificationTypeInfo::UninitializedThisVariableInfo
+            }
+            VerificationType::Object(name) => VerificationTypeInfo::ObjectVariableInfo {
+                cpool_index: cp.add_class(name)?,
+            },
+            VerificationType::Uninitialized(offset) => {
+                VerificationTypeInfo::UninitializedVariableInfo { offset: *offset }
+            }
+        };
+        Ok(info)
+    }
+
+    fn length(&self) -> u32 {
+        match self {
+            VerificationTypeInfo::ObjectVariableInfo { .. }
+            | VerificationTypeInfo::UninitializedVariableInfo { .. } => 3,
+            _ => 1,
+        }
+    }
+
+    fn write(&self, file: &mut FileWriter) {
+        match self {
+            VerificationTypeInfo::TopVaiableInfo => file.write_u1(0),
+            VerificationTypeInfo::IntegerVariableInfo => file.write_u1(1),
+            VerificationTypeInfo::FloatVariableInfo => file.write_u1(2),
+            VerificationTypeInfo::DoubleVariableInfo => file.write_u1(3),
+            VerificationTypeInfo::LongVariableInfo => file.write_u1(4),
+            VerificationTypeInfo::NullVariableInfo => file.write_u1(5),
+            VerificationTypeInfo::UninitializedThisVariableInfo => file.write_u1(6),
+            VerificationTypeInfo::ObjectVariableInfo { cpool_index } => {
+                file.write_u1(7);
+                file.write_u2(*cpool_index);
+            }
+            VerificationTypeInfo::UninitializedVariableInfo { offset } => {
+                file.write_u1(8);
+                file.write_u2(*offset);
+            }
+        }
+    }
+
     fn parse(file: &mut FileReader) -> Result<VerificationTypeInfo> {
         let tag = file.read_u1()?;
         match tag {
@@ -268,7 +619,7 @@ impl VerificationTypeInfo {
             8 => Ok(VerificationTypeInfo::UninitializedVariableInfo {
                 offset: file.read_u2_to_u16()?,
             }),
-            _ => panic!("Invalid tag: {}", tag),
+            _ => Err(anyhow!("Invalid verification type tag {}", tag)),
         }
     }
 
@@ -290,3 +641,47 @@ impl VerificationTypeInfo {
         }
     }
 }
+
+impl VerificationType {
+    /// Number of local variable or operand stack slots the type takes up
+    pub fn size(&self) -> usize {
+        match self {
+            VerificationType::Long | VerificationType::Double => 2,
+            _ => 1,
+        }
+    }
+
+    /// The verification type of a field descriptor, all int-like types are `Integer`
+    pub fn from_descriptor(descriptor: &str) -> Result<VerificationType> {
+        Ok(VerificationType::from_field_type(&FieldType::parse(
+            descriptor,
+        )?))
+    }
+
+    /// Arrays are objects named by their descriptor, like in `CONSTANT_Class` entries
+    pub fn from_field_type(field_type: &FieldType) -> VerificationType {
+        match field_type {
+            FieldType::Base(BaseType::Float) => VerificationType::Float,
+            FieldType::Base(BaseType::Long) => VerificationType::Long,
+            FieldType::Base(BaseType::Double) => VerificationType::Double,
+            FieldType::Base(_) => VerificationType::Integer,
+            _ => VerificationType::Object(field_type.binary_name().unwrap_or_default()),
+        }
+    }
+}
+
+impl fmt::Display for VerificationType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VerificationType::Top => write!(f, "top"),
+            VerificationType::Integer => write!(f, "int"),
+            VerificationType::Float => write!(f, "float"),
+            VerificationType::Long => write!(f, "long"),
+            VerificationType::Double => write!(f, "double"),
+            VerificationType::Null => write!(f, "null"),
+            VerificationType::UninitializedThis => write!(f, "uninitialized_this"),
+            VerificationType::Object(name) => write!(f, "class {}", name),
+            VerificationType::Uninitialized(pc) => write!(f, "uninitialized {}", pc),
+        }
+    }
+}
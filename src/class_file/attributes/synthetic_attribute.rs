@@ -0,0 +1,36 @@
+use super::attributes::AttStart;
+use crate::class_file::{
+    constant_pool::ConstantPool, file_reader::FileReader, file_writer::FileWriter,
+};
+use anyhow::Result;
+
+/// Marks a class member that does not appear in the source code
+#[derive(Debug)]
+pub struct SyntheticAttribute {
+    pub(super) attribute_name_index: u16,
+    pub(super) attribute_length: u32,
+}
+
+impl SyntheticAttribute {
+    pub fn new(attribute_name_index: u16) -> SyntheticAttribute {
+        SyntheticAttribute {
+            attribute_name_index,
+            attribute_length: 0,
+        }
+    }
+
+    pub fn parse(_file: &mut FileReader, att_start: &AttStart) -> Result<SyntheticAttribute> {
+        Ok(SyntheticAttribute {
+            attribute_name_index: att_start.attribute_name_index,
+            attribute_length: att_start.attribute_length,
+        })
+    }
+
+    pub fn write(&self, _file: &mut FileWriter) -> Result<()> {
+        Ok(())
+    }
+
+    pub fn to_string(&self, _cp: &ConstantPool) -> String {
+        "Synthetic\n".to_string()
+    }
+}
@@ -0,0 +1,404 @@
+//! Class files that borrow from the bytes they are parsed from, e.g. a memory mapped jar
+//! entry. Strings and attribute payloads are slices of the input that are only decoded
+//! when they are accessed; parsing allocates just the lists of constants, members and
+//! attributes.
+
+use super::bytecode::ByteCode;
+use super::file_reader::{decode_modified_utf8, FileReader};
+use anyhow::{anyhow, Result};
+use std::borrow::Cow;
+
+const UTF8: u8 = 1;
+const INTEGER: u8 = 3;
+const FLOAT: u8 = 4;
+const LONG: u8 = 5;
+const DOUBLE: u8 = 6;
+const CLASS: u8 = 7;
+const STRING: u8 = 8;
+const FIELD_REF: u8 = 9;
+const METHOD_REF: u8 = 10;
+const INTERFACE_METHOD_REF: u8 = 11;
+const NAME_AND_TYPE: u8 = 12;
+const METHOD_HANDLE: u8 = 15;
+const METHOD_TYPE: u8 = 16;
+const DYNAMIC: u8 = 17;
+const INVOKE_DYNAMIC: u8 = 18;
+const MODULE: u8 = 19;
+const PACKAGE: u8 = 20;
+
+pub struct ClassFile<'a> {
+    pub minor_version: u16,
+    pub major_version: u16,
+    pub constant_pool: ConstantPool<'a>,
+    pub access_flags: u16,
+    pub this_class: u16,
+    pub super_class: u16,
+    pub interfaces: Vec<u16>,
+    pub fields: Vec<Member<'a>>,
+    pub methods: Vec<Member<'a>>,
+    pub attributes: Vec<Attribute<'a>>,
+    bytes: &'a [u8],
+}
+
+/// The constant pool as the tag and the bytes after it of each entry, the second slot
+/// of a long or double has the tag 0
+pub struct ConstantPool<'a> {
+    entries: Vec<(u8, &'a [u8])>,
+}
+
+/// A field or method
+pub struct Member<'a> {
+    pub access_flags: u16,
+    pub name_index: u16,
+    pub descriptor_index: u16,
+    pub attributes: Vec<Attribute<'a>>,
+}
+
+pub struct Attribute<'a> {
+    pub name_index: u16,
+    /// The content of the attribute, `attribute_length` bytes
+    pub info: &'a [u8],
+}
+
+/// The content of a `Code` attribute
+pub struct Code<'a> {
+    pub max_stack: u16,
+    pub max_locals: u16,
+    /// The bytecode, decoded by `instructions`
+    pub code: &'a [u8],
+    /// `start_pc`, `end_pc`, `handler_pc` and `catch_type` of each entry
+    pub exception_table: Vec<[u16; 4]>,
+    pub attributes: Vec<Attribute<'a>>,
+}
+
+impl<'a> ClassFile<'a> {
+    pub fn parse(bytes: &'a [u8]) -> Result<ClassFile<'a>> {
+        let mut reader = Reader { bytes, position: 0 };
+        let magic = reader.u4()?;
+        if magic != 0xCAFE_BABE {
+            return Err(anyhow!("Not a class file, bad magic {:08x}", magic));
+        }
+        let minor_version = reader.u2()?;
+        let major_version = reader.u2()?;
+        let constant_pool = ConstantPool::parse(&mut reader)?;
+        let access_flags = reader.u2()?;
+        let this_class = reader.u2()?;
+        let super_class = reader.u2()?;
+        let interfaces_count = reader.u2()?;
+        let interfaces = (0..interfaces_count)
+            .map(|_| reader.u2())
+            .collect::<Result<_>>()?;
+        let fields = Member::parse_all(&mut reader)?;
+        let methods = Member::parse_all(&mut reader)?;
+        let attributes = Attribute::parse_all(&mut reader)?;
+        if reader.position != bytes.len() {
+            return Err(anyhow!("Extra bytes at the end of the class file"));
+        }
+        Ok(ClassFile {
+            minor_version,
+            major_version,
+            constant_pool,
+            access_flags,
+            this_class,
+            super_class,
+            interfaces,
+            fields,
+            methods,
+            attributes,
+            bytes,
+        })
+    }
+
+    /// The bytes the class file was parsed from
+    pub fn bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    /// Binary name of the class, e.g. `com/example/Example`
+    pub fn class_name(&self) -> Result<Cow<'a, str>> {
+        self.constant_pool.class_name(self.this_class)
+    }
+
+    /// Binary name of the super class, `None` for `java/lang/Object` (and module-info)
+    pub fn super_class_name(&self) -> Result<Option<Cow<'a, str>>> {
+        match self.super_class {
+            0 => Ok(None),
+            index => self.constant_pool.class_name(index).map(Some),
+        }
+    }
+
+    pub fn find_method(&self, name: &str, descriptor: &str) -> Option<&Member<'a>> {
+        let cp = &self.constant_pool;
+        self.methods.iter().find(|m| {
+            cp.utf8_bytes(m.name_index)
+                .is_ok_and(|n| n == name.as_bytes())
+                && cp
+                    .utf8_bytes(m.descriptor_index)
+                    .is_ok_and(|d| d == descriptor.as_bytes())
+        })
+    }
+
+    /// Parses the bytes into an owned class file with all its attributes decoded
+    pub fn decode(&self) -> Result<super::ClassFile> {
+        super::parse_class_file(FileReader::from_bytes(self.bytes), super::Parsing::Eager)
+    }
+}
+
+impl<'a> ConstantPool<'a> {
+    fn parse(reader: &mut Reader<'a>) -> Result<ConstantPool<'a>> {
+        let count = reader.u2()?;
+        let mut entries = Vec::with_capacity(count as usize);
+        while entries.len() + 1 < count as usize {
+            let tag = reader.u1()?;
+            let length = match tag {
+                UTF8 => {
+                    let length = reader.u2()?;
+                    entries.push((tag, reader.take(length as usize)?));
+                    continue;
+                }
+                METHOD_HANDLE => 3,
+                CLASS | STRING | METHOD_TYPE | MODULE | PACKAGE => 2,
+                INTEGER | FLOAT | FIELD_REF | METHOD_REF | INTERFACE_METHOD_REF | NAME_AND_TYPE
+                | DYNAMIC | INVOKE_DYNAMIC => 4,
+                LONG | DOUBLE => 8,
+                _ => return Err(anyhow!("Unknown constant pool tag {}", tag)),
+            };
+            entries.push((tag, reader.take(length)?));
+            if tag == LONG || tag == DOUBLE {
+                entries.push((0, &[]));
+            }
+        }
+        Ok(ConstantPool { entries })
+    }
+
+    /// Number of entries, the `constant_pool_count` of the class file minus one
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The tag and the bytes after it of the entry at `index`, `None` for 0 and indexes
+    /// past the end of the pool
+    pub fn get(&self, index: u16) -> Option<(u8, &'a [u8])> {
+        self.entries.get((index as usize).checked_sub(1)?).copied()
+    }
+
+    /// The bytes of a Utf8 entry, in modified UTF-8
+    pub fn utf8_bytes(&self, index: u16) -> Result<&'a [u8]> {
+        self.entry(index, UTF8)
+    }
+
+    /// A Utf8 entry, borrowed unless it uses the modified UTF-8 encodings of NUL and
+    /// surrogate pairs that are not valid UTF-8
+    pub fn utf8(&self, index: u16) -> Result<Cow<'a, str>> {
+        let bytes = self.utf8_bytes(index)?;
+        Ok(match std::str::from_utf8(bytes) {
+            Ok(s) => Cow::Borrowed(s),
+            Err(_) => Cow::Owned(decode_modified_utf8(bytes)),
+        })
+    }
+
+    /// The name of a Class entry, a binary name or an array descriptor
+    pub fn class_name(&self, index: u16) -> Result<Cow<'a, str>> {
+        let info = self.entry(index, CLASS)?;
+        self.utf8(u16_at(info, 0))
+    }
+
+    /// The value of a String entry
+    pub fn string(&self, index: u16) -> Result<Cow<'a, str>> {
+        let info = self.entry(index, STRING)?;
+        self.utf8(u16_at(info, 0))
+    }
+
+    /// The name and descriptor of a NameAndType entry
+    pub fn name_and_type(&self, index: u16) -> Result<(Cow<'a, str>, Cow<'a, str>)> {
+        let info = self.entry(index, NAME_AND_TYPE)?;
+        Ok((self.utf8(u16_at(info, 0))?, self.utf8(u16_at(info, 2))?))
+    }
+
+    fn entry(&self, index: u16, tag: u8) -> Result<&'a [u8]> {
+        match self.get(index) {
+            Some((t, info)) if t == tag => Ok(info),
+            entry => Err(anyhow!(
+                "#{} must be a {} entry, not {}",
+                index,
+                tag_name(tag),
+                entry.map_or("missing", |(t, _)| tag_name(t))
+            )),
+        }
+    }
+}
+
+impl<'a> Member<'a> {
+    fn parse_all(reader: &mut Reader<'a>) -> Result<Vec<Member<'a>>> {
+        let count = reader.u2()?;
+        (0..count)
+            .map(|_| {
+                Ok(Member {
+                    access_flags: reader.u2()?,
+                    name_index: reader.u2()?,
+                    descriptor_index: reader.u2()?,
+                    attributes: Attribute::parse_all(reader)?,
+                })
+            })
+            .collect()
+    }
+
+    pub fn name(&self, cp: &ConstantPool<'a>) -> Result<Cow<'a, str>> {
+        cp.utf8(self.name_index)
+    }
+
+    pub fn descriptor(&self, cp: &ConstantPool<'a>) -> Result<Cow<'a, str>> {
+        cp.utf8(self.descriptor_index)
+    }
+
+    /// The attribute with the name, e.g. `Code`
+    pub fn attribute(&self, cp: &ConstantPool<'a>, name: &str) -> Option<&Attribute<'a>> {
+        find_attribute(&self.attributes, cp, name)
+    }
+
+    /// The `Code` attribute of a method, `None` for abstract and native methods
+    pub fn code(&self, cp: &ConstantPool<'a>) -> Option<Result<Code<'a>>> {
+        self.attribute(cp, "Code").map(Attribute::code)
+    }
+}
+
+impl<'a> Attribute<'a> {
+    fn parse_all(reader: &mut Reader<'a>) -> Result<Vec<Attribute<'a>>> {
+        let count = reader.u2()?;
+        (0..count)
+            .map(|_| {
+                let name_index = reader.u2()?;
+                let length = reader.u4()?;
+                let info = reader.take(length as usize)?;
+                Ok(Attribute { name_index, info })
+            })
+            .collect()
+    }
+
+    pub fn name(&self, cp: &ConstantPool<'a>) -> Result<Cow<'a, str>> {
+        cp.utf8(self.name_index)
+    }
+
+    /// Reads the content as a `Code` attribute
+    pub fn code(&self) -> Result<Code<'a>> {
+        let mut reader = Reader {
+            bytes: self.info,
+            position: 0,
+        };
+        let max_stack = reader.u2()?;
+        let max_locals = reader.u2()?;
+        let code_length = reader.u4()?;
+        let code = reader.take(code_length as usize)?;
+        let exception_table_length = reader.u2()?;
+        let exception_table = (0..exception_table_length)
+            .map(|_| Ok([reader.u2()?, reader.u2()?, reader.u2()?, reader.u2()?]))
+            .collect::<Result<_>>()?;
+        let attributes = Attribute::parse_all(&mut reader)?;
+        if reader.position != self.info.len() {
+            return Err(anyhow!(
+                "Code attribute of {} bytes has {} bytes of content",
+                self.info.len(),
+                reader.position
+            ));
+        }
+        Ok(Code {
+            max_stack,
+            max_locals,
+            code,
+            exception_table,
+            attributes,
+        })
+    }
+}
+
+impl<'a> Code<'a> {
+    /// Decodes the bytecode into instructions with their pcs
+    pub fn instructions(&self) -> Result<Vec<(u32, ByteCode)>> {
+        let mut file = FileReader::from_bytes(self.code);
+        let mut instructions = Vec::new();
+        let mut pc = 0;
+        while (pc as usize) < self.code.len() {
+            let (instruction, length) = ByteCode::parse(&mut file, pc)?;
+            instructions.push((pc, instruction));
+            pc += length;
+        }
+        Ok(instructions)
+    }
+
+    pub fn attribute(&self, cp: &ConstantPool<'a>, name: &str) -> Option<&Attribute<'a>> {
+        find_attribute(&self.attributes, cp, name)
+    }
+}
+
+fn find_attribute<'a, 'b>(
+    attributes: &'b [Attribute<'a>],
+    cp: &ConstantPool<'a>,
+    name: &str,
+) -> Option<&'b Attribute<'a>> {
+    attributes.iter().find(|att| {
+        cp.utf8_bytes(att.name_index)
+            .is_ok_and(|n| n == name.as_bytes())
+    })
+}
+
+/// Reads big endian numbers and slices from the input without copying
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+        let bytes = self
+            .bytes
+            .get(self.position..self.position.saturating_add(n))
+            .ok_or_else(|| anyhow!("Unexpected end of class file at byte {}", self.position))?;
+        self.position += n;
+        Ok(bytes)
+    }
+
+    fn u1(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u2(&mut self) -> Result<u16> {
+        Ok(u16_at(self.take(2)?, 0))
+    }
+
+    fn u4(&mut self) -> Result<u32> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+}
+
+/// Entries are at least as long as their tag requires, checked while parsing
+fn u16_at(bytes: &[u8], i: usize) -> u16 {
+    u16::from_be_bytes([bytes[i], bytes[i + 1]])
+}
+
+fn tag_name(tag: u8) -> &'static str {
+    match tag {
+        UTF8 => "Utf8",
+        INTEGER => "Integer",
+        FLOAT => "Float",
+        LONG => "Long",
+        DOUBLE => "Double",
+        CLASS => "Class",
+        STRING => "String",
+        FIELD_REF => "Fieldref",
+        METHOD_REF => "Methodref",
+        INTERFACE_METHOD_REF => "InterfaceMethodref",
+        NAME_AND_TYPE => "NameAndType",
+        METHOD_HANDLE => "MethodHandle",
+        METHOD_TYPE => "MethodType",
+        DYNAMIC => "Dynamic",
+        INVOKE_DYNAMIC => "InvokeDynamic",
+        MODULE => "Module",
+        PACKAGE => "Package",
+        _ => "unusable",
+    }
+}
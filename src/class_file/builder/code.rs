@@ -0,0 +1,1252 @@
+use super::{Constant, Constants};
+use crate::class_file::attributes::{
+    Attribute, Attributes, CodeAttribute, ExceptionTable, LineNumberTable,
+    LineNumberTableAttribute, LocalVariable, LocalVariableTableAttribute, StackMapTableAttribute,
+};
+use crate::class_file::bytecode::{ByteCode, LookupSwitch, TableSwitch};
+use crate::class_file::constant_pool::{ConstantPool, FieldRef, MethodHandle, MethodRef};
+use crate::class_file::methods::Method;
+use crate::class_file::transform;
+use crate::class_file::{BaseType, ClassHierarchy, MethodDescriptor};
+use anyhow::{anyhow, Result};
+
+/// A position in the code, the target of branches, exception handlers and local
+/// variable ranges. Created by `CodeBuilder::new_label` and placed with `place`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Label(usize);
+
+/// The body of a method. Instructions are emitted in order, their constant pool entries
+/// are added and their branches resolved when the class is built.
+///
+/// ```
+/// use jaust::class_file::builder::CodeBuilder;
+///
+/// // the absolute value of the first argument
+/// let mut code = CodeBuilder::new();
+/// let positive = code.new_label();
+/// code.iload(0).ifge(positive).iload(0).ineg().ireturn();
+/// code.place(positive).iload(0).ireturn();
+/// ```
+#[derive(Debug, Default)]
+pub struct CodeBuilder {
+    ops: Vec<Op>,
+    /// Index in `ops` of the instruction each label is placed before
+    labels: Vec<Option<usize>>,
+    handlers: Vec<Handler>,
+    /// Index in `ops` and line number of the `LineNumberTable` entries
+    lines: Vec<(usize, u16)>,
+    variables: Vec<Variable>,
+    max_stack: Option<u16>,
+    max_locals: Option<u16>,
+    stack_map: bool,
+    error: Option<String>,
+}
+
+/// An instruction whose branch targets are labels, resolved once all pcs are known
+#[derive(Debug)]
+pub(crate) enum Instruction {
+    /// Written in `len` bytes if the form is given (`iload_1`, `iload 1` or `wide iload 1`)
+    Fixed(ByteCode, Option<u32>),
+    Jump(Jump, Label),
+    TableSwitch {
+        low: i32,
+        targets: Vec<Label>,
+        default: Label,
+    },
+    LookupSwitch {
+        pairs: Vec<(i32, Label)>,
+        default: Label,
+    },
+}
+
+/// A branch instruction with a 16 or 32 bit offset
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Jump {
+    Short(fn(i16) -> ByteCode),
+    Wide(fn(i32) -> ByteCode),
+}
+
+/// An instruction before its constant pool entries are added
+#[derive(Debug)]
+enum Op {
+    Ready(Instruction),
+    Field(fn(u16) -> ByteCode, FieldRef),
+    Method(fn(u16) -> ByteCode, MethodRef),
+    InvokeInterface(MethodRef),
+    InvokeDynamic {
+        name: String,
+        descriptor: String,
+        bootstrap: MethodHandle,
+        arguments: Vec<Constant>,
+    },
+    Class(fn(u16) -> ByteCode, String),
+    MultiANewArray(String, u8),
+    Ldc(Constant),
+}
+
+#[derive(Debug)]
+struct Handler {
+    start: Label,
+    end: Label,
+    handler: Label,
+    catch_type: Option<String>,
+}
+
+#[derive(Debug)]
+struct Variable {
+    index: u16,
+    name: String,
+    descriptor: String,
+    signature: Option<String>,
+    start: Label,
+    end: Label,
+}
+
+/// What the frames of a Code attribute are completed with
+pub(super) struct Limits {
+    max_stack: Option<u16>,
+    max_locals: Option<u16>,
+    stack_map: bool,
+}
+
+impl CodeBuilder {
+    pub fn new() -> CodeBuilder {
+        CodeBuilder {
+            stack_map: true,
+            ..CodeBuilder::default()
+        }
+    }
+
+    pub fn new_label(&mut self) -> Label {
+        self.labels.push(None);
+        Label(self.labels.len() - 1)
+    }
+
+    /// Places `label` before the next instruction
+    pub fn place(&mut self, label: Label) -> &mut Self {
+        match self.labels[label.0] {
+            Some(_) => self.fail("A label is placed twice"),
+            None => self.labels[label.0] = Some(self.ops.len()),
+        }
+        self
+    }
+
+    /// Instructions from `start` to `end` (excluded) jump to `handler` when they throw
+    /// an instance of `catch_type`, or anything if it is `None` (`finally`)
+    pub fn try_catch(
+        &mut self,
+        start: Label,
+        end: Label,
+        handler: Label,
+        catch_type: Option<&str>,
+    ) -> &mut Self {
+        self.handlers.push(Handler {
+            start,
+            end,
+            handler,
+            catch_type: catch_type.map(str::to_string),
+        });
+        self
+    }
+
+    /// The next instructions come from `line` of the source file
+    pub fn line_number(&mut self, line: u16) -> &mut Self {
+        self.lines.push((self.ops.len(), line));
+        self
+    }
+
+    /// A `LocalVariableTable` entry, and a `LocalVariableTypeTable` one if it has a
+    /// generic `signature`
+    pub fn local_variable(
+        &mut self,
+        index: u16,
+        name: &str,
+        descriptor: &str,
+        signature: Option<&str>,
+        start: Label,
+        end: Label,
+    ) -> &mut Self {
+        self.variables.push(Variable {
+            index,
+            name: name.to_string(),
+            descriptor: descriptor.to_string(),
+            signature: signature.map(str::to_string),
+            start,
+            end,
+        });
+        self
+    }
+
+    /// Sets `max_stack` instead of computing it
+    pub fn max_stack(&mut self, max_stack: u16) -> &mut Self {
+        self.max_stack = Some(max_stack);
+        self
+    }
+
+    /// Sets `max_locals` instead of computing it
+    pub fn max_locals(&mut self, max_locals: u16) -> &mut Self {
+        self.max_locals = Some(max_locals);
+        self
+    }
+
+    /// Leaves the StackMapTable out, for code whose frames can't be computed. The class
+    /// only loads with a version before 50 or without verification.
+    pub fn no_stack_map(&mut self) -> &mut Self {
+        self.stack_map = false;
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    fn fail(&mut self, error: &str) {
+        self.error.get_or_insert(error.to_string());
+    }
+
+    fn push(&mut self, op: Op) -> &mut Self {
+        self.ops.push(op);
+        self
+    }
+
+    /// Emits an instruction without constant pool entries or branches, in `len` bytes
+    /// if it is not the shortest form
+    pub(crate) fn instruction(&mut self, instruction: ByteCode, len: Option<u32>) -> &mut Self {
+        self.push(Op::Ready(Instruction::Fixed(instruction, len)))
+    }
+
+    fn simple(&mut self, instruction: ByteCode) -> &mut Self {
+        self.instruction(instruction, None)
+    }
+
+    pub(crate) fn jump(&mut self, jump: Jump, target: Label) -> &mut Self {
+        self.push(Op::Ready(Instruction::Jump(jump, target)))
+    }
+
+    /// Pushes an int with the shortest instruction: `iconst_<n>`, `bipush`, `sipush` or `ldc`
+    pub fn iconst(&mut self, value: i32) -> &mut Self {
+        if (-1..=5).contains(&value) {
+            self.simple(ByteCode::IConstn(value as i8))
+        } else if let Ok(value) = i8::try_from(value) {
+            self.simple(ByteCode::BiPush(value))
+        } else if let Ok(value) = i16::try_from(value) {
+            self.simple(ByteCode::SiPush(value))
+        } else {
+            self.ldc(Constant::Int(value))
+        }
+    }
+
+    /// `lconst_0`, `lconst_1` or `ldc2_w`
+    pub fn lconst(&mut self, value: i64) -> &mut Self {
+        match value {
+            0 | 1 => self.simple(ByteCode::LConstn(value as u8)),
+            _ => self.ldc(Constant::Long(value)),
+        }
+    }
+
+    /// `fconst_<n>` or `ldc`. `-0.0` is not `fconst_0`.
+    pub fn fconst(&mut self, value: f32) -> &mut Self {
+        match value {
+            0.0 | 1.0 | 2.0 if value.is_sign_positive() => {
+                self.simple(ByteCode::FConstn(value as u8))
+            }
+            _ => self.ldc(Constant::Float(value)),
+        }
+    }
+
+    /// `dconst_<n>` or `ldc2_w`
+    pub fn dconst(&mut self, value: f64) -> &mut Self {
+        match value {
+            0.0 | 1.0 if value.is_sign_positive() => self.simple(ByteCode::DConstn(value as u8)),
+            _ => self.ldc(Constant::Double(value)),
+        }
+    }
+
+    /// `ldc`, `ldc_w` or `ldc2_w`, whichever the index and the type of the constant need
+    pub fn ldc(&mut self, constant: Constant) -> &mut Self {
+        self.push(Op::Ldc(constant))
+    }
+
+    pub fn aconst_null(&mut self) -> &mut Self {
+        self.simple(ByteCode::AConstNull)
+    }
+
+    pub fn iload(&mut self, index: u16) -> &mut Self {
+        self.simple(ByteCode::ILoad(index))
+    }
+
+    pub fn lload(&mut self, index: u16) -> &mut Self {
+        self.simple(ByteCode::Lload(index))
+    }
+
+    pub fn fload(&mut self, index: u16) -> &mut Self {
+        self.simple(ByteCode::FLoad(index))
+    }
+
+    pub fn dload(&mut self, index: u16) -> &mut Self {
+        self.simple(ByteCode::DLoad(index))
+    }
+
+    pub fn aload(&mut self, index: u16) -> &mut Self {
+        self.simple(ByteCode::ALoad(index))
+    }
+
+    pub fn istore(&mut self, index: u16) -> &mut Self {
+        self.simple(ByteCode::IStore(index))
+    }
+
+    pub fn lstore(&mut self, index: u16) -> &mut Self {
+        self.simple(ByteCode::LStore(index))
+    }
+
+    pub fn fstore(&mut self, index: u16) -> &mut Self {
+        self.simple(ByteCode::FStore(index))
+    }
+
+    pub fn dstore(&mut self, index: u16) -> &mut Self {
+        self.simple(ByteCode::DStore(index))
+    }
+
+    pub fn astore(&mut self, index: u16) -> &mut Self {
+        self.simple(ByteCode::AStore(index))
+    }
+
+    /// Adds `delta` to the int in local `index`
+    pub fn iinc(&mut self, index: u16, delta: i16) -> &mut Self {
+        self.simple(ByteCode::IInc(index, delta))
+    }
+
+    pub fn ret(&mut self, index: u16) -> &mut Self {
+        self.simple(ByteCode::Ret(index))
+    }
+
+    pub fn iaload(&mut self) -> &mut Self {
+        self.simple(ByteCode::IALoad)
+    }
+
+    pub fn laload(&mut self) -> &mut Self {
+        self.simple(ByteCode::LALoad)
+    }
+
+    pub fn faload(&mut self) -> &mut Self {
+        self.simple(ByteCode::FALoad)
+    }
+
+    pub fn daload(&mut self) -> &mut Self {
+        self.simple(ByteCode::DALoad)
+    }
+
+    pub fn aaload(&mut self) -> &mut Self {
+        self.simple(ByteCode::AALoad)
+    }
+
+    pub fn baload(&mut self) -> &mut Self {
+        self.simple(ByteCode::BALoad)
+    }
+
+    pub fn caload(&mut self) -> &mut Self {
+        self.simple(ByteCode::CALoad)
+    }
+
+    pub fn saload(&mut self) -> &mut Self {
+        self.simple(ByteCode::SALoad)
+    }
+
+    pub fn iastore(&mut self) -> &mut Self {
+        self.simple(ByteCode::IAStore)
+    }
+
+    pub fn lastore(&mut self) -> &mut Self {
+        self.simple(ByteCode::LAStore)
+    }
+
+    pub fn fastore(&mut self) -> &mut Self {
+        self.simple(ByteCode::FAStore)
+    }
+
+    pub fn dastore(&mut self) -> &mut Self {
+        self.simple(ByteCode::DAStore)
+    }
+
+    pub fn aastore(&mut self) -> &mut Self {
+        self.simple(ByteCode::AAStore)
+    }
+
+    pub fn bastore(&mut self) -> &mut Self {
+        self.simple(ByteCode::BAStore)
+    }
+
+    pub fn castore(&mut self) -> &mut Self {
+        self.simple(ByteCode::CAStore)
+    }
+
+    pub fn sastore(&mut self) -> &mut Self {
+        self.simple(ByteCode::SAStore)
+    }
+
+    pub fn pop(&mut self) -> &mut Self {
+        self.simple(ByteCode::Pop)
+    }
+
+    pub fn pop2(&mut self) -> &mut Self {
+        self.simple(ByteCode::Pop2)
+    }
+
+    pub fn dup(&mut self) -> &mut Self {
+        self.simple(ByteCode::Duplicate)
+    }
+
+    pub fn dup_x1(&mut self) -> &mut Self {
+        self.simple(ByteCode::DupX1)
+    }
+
+    pub fn dup_x2(&mut self) -> &mut Self {
+        self.simple(ByteCode::DupX2)
+    }
+
+    pub fn dup2(&mut self) -> &mut Self {
+        self.simple(ByteCode::Dup2)
+    }
+
+    pub fn dup2_x1(&mut self) -> &mut Self {
+        self.simple(ByteCode::Dup2X1)
+    }
+
+    pub fn dup2_x2(&mut self) -> &mut Self {
+        self.simple(ByteCode::Dup2X2)
+    }
+
+    pub fn swap(&mut self) -> &mut Self {
+        self.simple(ByteCode::Swap)
+    }
+
+    pub fn iadd(&mut self) -> &mut Self {
+        self.simple(ByteCode::IAdd)
+    }
+
+    pub fn ladd(&mut self) -> &mut Self {
+        self.simple(ByteCode::LAdd)
+    }
+
+    pub fn fadd(&mut self) -> &mut Self {
+        self.simple(ByteCode::FAdd)
+    }
+
+    pub fn dadd(&mut self) -> &mut Self {
+        self.simple(ByteCode::DAdd)
+    }
+
+    pub fn isub(&mut self) -> &mut Self {
+        self.simple(ByteCode::ISub)
+    }
+
+    pub fn lsub(&mut self) -> &mut Self {
+        self.simple(ByteCode::LSub)
+    }
+
+    pub fn fsub(&mut self) -> &mut Self {
+        self.simple(ByteCode::FSub)
+    }
+
+    pub fn dsub(&mut self) -> &mut Self {
+        self.simple(ByteCode::DSub)
+    }
+
+    pub fn imul(&mut self) -> &mut Self {
+        self.simple(ByteCode::IMul)
+    }
+
+    pub fn lmul(&mut self) -> &mut Self {
+        self.simple(ByteCode::LMul)
+    }
+
+    pub fn fmul(&mut self) -> &mut Self {
+        self.simple(ByteCode::FMul)
+    }
+
+    pub fn dmul(&mut self) -> &mut Self {
+        self.simple(ByteCode::DMul)
+    }
+
+    pub fn idiv(&mut self) -> &mut Self {
+        self.simple(ByteCode::IDiv)
+    }
+
+    pub fn ldiv(&mut self) -> &mut Self {
+        self.simple(ByteCode::LDiv)
+    }
+
+    pub fn fdiv(&mut self) -> &mut Self {
+        self.simple(ByteCode::FDiv)
+    }
+
+    pub fn ddiv(&mut self) -> &mut Self {
+        self.simple(ByteCode::DDiv)
+    }
+
+    pub fn irem(&mut self) -> &mut Self {
+        self.simple(ByteCode::IRem)
+    }
+
+    pub fn lrem(&mut self) -> &mut Self {
+        self.simple(ByteCode::LRem)
+    }
+
+    pub fn frem(&mut self) -> &mut Self {
+        self.simple(ByteCode::FRem)
+    }
+
+    pub fn drem(&mut self) -> &mut Self {
+        self.simple(ByteCode::DRem)
+    }
+
+    pub fn ineg(&mut self) -> &mut Self {
+        self.simple(ByteCode::INeg)
+    }
+
+    pub fn lneg(&mut self) -> &mut Self {
+        self.simple(ByteCode::LNeg)
+    }
+
+    pub fn fneg(&mut self) -> &mut Self {
+        self.simple(ByteCode::FNeg)
+    }
+
+    pub fn dneg(&mut self) -> &mut Self {
+        self.simple(ByteCode::DNeg)
+    }
+
+    pub fn ishl(&mut self) -> &mut Self {
+        self.simple(ByteCode::IShl)
+    }
+
+    pub fn lshl(&mut self) -> &mut Self {
+        self.simple(ByteCode::LShl)
+    }
+
+    pub fn ishr(&mut self) -> &mut Self {
+        self.simple(ByteCode::IShr)
+    }
+
+    pub fn lshr(&mut self) -> &mut Self {
+        self.simple(ByteCode::LShr)
+    }
+
+    pub fn iushr(&mut self) -> &mut Self {
+        self.simple(ByteCode::IUShr)
+    }
+
+    pub fn lushr(&mut self) -> &mut Self {
+        self.simple(ByteCode::LUShr)
+    }
+
+    pub fn iand(&mut self) -> &mut Self {
+        self.simple(ByteCode::IAnd)
+    }
+
+    pub fn land(&mut self) -> &mut Self {
+        self.simple(ByteCode::LAnd)
+    }
+
+    pub fn ior(&mut self) -> &mut Self {
+        self.simple(ByteCode::IOr)
+    }
+
+    pub fn lor(&mut self) -> &mut Self {
+        self.simple(ByteCode::LOr)
+    }
+
+    pub fn ixor(&mut self) -> &mut Self {
+        self.simple(ByteCode::IXor)
+    }
+
+    pub fn lxor(&mut self) -> &mut Self {
+        self.simple(ByteCode::LXor)
+    }
+
+    pub fn i2l(&mut self) -> &mut Self {
+        self.simple(ByteCode::I2L)
+    }
+
+    pub fn i2f(&mut self) -> &mut Self {
+        self.simple(ByteCode::I2F)
+    }
+
+    pub fn i2d(&mut self) -> &mut Self {
+        self.simple(ByteCode::I2D)
+    }
+
+    pub fn l2i(&mut self) -> &mut Self {
+        self.simple(ByteCode::L2i)
+    }
+
+    pub fn l2f(&mut self) -> &mut Self {
+        self.simple(ByteCode::L2F)
+    }
+
+    pub fn l2d(&mut self) -> &mut Self {
+        self.simple(ByteCode::L2D)
+    }
+
+    pub fn f2i(&mut self) -> &mut Self {
+        self.simple(ByteCode::F2I)
+    }
+
+    pub fn f2l(&mut self) -> &mut Self {
+        self.simple(ByteCode::F2L)
+    }
+
+    pub fn f2d(&mut self) -> &mut Self {
+        self.simple(ByteCode::F2D)
+    }
+
+    pub fn d2i(&mut self) -> &mut Self {
+        self.simple(ByteCode::D2I)
+    }
+
+    pub fn d2l(&mut self) -> &mut Self {
+        self.simple(ByteCode::D2L)
+    }
+
+    pub fn d2f(&mut self) -> &mut Self {
+        self.simple(ByteCode::D2F)
+    }
+
+    pub fn i2b(&mut self) -> &mut Self {
+        self.simple(ByteCode::I2B)
+    }
+
+    pub fn i2c(&mut self) -> &mut Self {
+        self.simple(ByteCode::I2C)
+    }
+
+    pub fn i2s(&mut self) -> &mut Self {
+        self.simple(ByteCode::I2S)
+    }
+
+    pub fn lcmp(&mut self) -> &mut Self {
+        self.simple(ByteCode::Lcmp)
+    }
+
+    pub fn fcmpl(&mut self) -> &mut Self {
+        self.simple(ByteCode::FCmpl)
+    }
+
+    pub fn fcmpg(&mut self) -> &mut Self {
+        self.simple(ByteCode::FCmpg)
+    }
+
+    pub fn dcmpl(&mut self) -> &mut Self {
+        self.simple(ByteCode::DCmpl)
+    }
+
+    pub fn dcmpg(&mut self) -> &mut Self {
+        self.simple(ByteCode::DCmpg)
+    }
+
+    pub fn ifeq(&mut self, target: Label) -> &mut Self {
+        self.jump(Jump::Short(ByteCode::Ifeq), target)
+    }
+
+    pub fn ifne(&mut self, target: Label) -> &mut Self {
+        self.jump(Jump::Short(ByteCode::Ifne), target)
+    }
+
+    pub fn iflt(&mut self, target: Label) -> &mut Self {
+        self.jump(Jump::Short(ByteCode::Iflt), target)
+    }
+
+    pub fn ifge(&mut self, target: Label) -> &mut Self {
+        self.jump(Jump::Short(ByteCode::Ifge), target)
+    }
+
+    pub fn ifgt(&mut self, target: Label) -> &mut Self {
+        self.jump(Jump::Short(ByteCode::Ifgt), target)
+    }
+
+    pub fn ifle(&mut self, target: Label) -> &mut Self {
+        self.jump(Jump::Short(ByteCode::Ifle), target)
+    }
+
+    pub fn if_icmpeq(&mut self, target: Label) -> &mut Self {
+        self.jump(Jump::Short(ByteCode::IfICmpEq), target)
+    }
+
+    pub fn if_icmpne(&mut self, target: Label) -> &mut Self {
+        self.jump(Jump::Short(ByteCode::IfICmpNe), target)
+    }
+
+    pub fn if_icmplt(&mut self, target: Label) -> &mut Self {
+        self.jump(Jump::Short(ByteCode::IfICmpLt), target)
+    }
+
+    pub fn if_icmpge(&mut self, target: Label) -> &mut Self {
+        self.jump(Jump::Short(ByteCode::IfICmpGe), target)
+    }
+
+    pub fn if_icmpgt(&mut self, target: Label) -> &mut Self {
+        self.jump(Jump::Short(ByteCode::IfICmpGt), target)
+    }
+
+    pub fn if_icmple(&mut self, target: Label) -> &mut Self {
+        self.jump(Jump::Short(ByteCode::IfICmpLe), target)
+    }
+
+    pub fn if_acmpeq(&mut self, target: Label) -> &mut Self {
+        self.jump(Jump::Short(ByteCode::IfACmpEq), target)
+    }
+
+    pub fn if_acmpne(&mut self, target: Label) -> &mut Self {
+        self.jump(Jump::Short(ByteCode::IfACmpNe), target)
+    }
+
+    pub fn ifnull(&mut self, target: Label) -> &mut Self {
+        self.jump(Jump::Short(ByteCode::IfNull), target)
+    }
+
+    pub fn ifnonnull(&mut self, target: Label) -> &mut Self {
+        self.jump(Jump::Short(ByteCode::IfNonNull), target)
+    }
+
+    pub fn goto(&mut self, target: Label) -> &mut Self {
+        self.jump(Jump::Short(ByteCode::Goto), target)
+    }
+
+    /// `goto` with a 32 bit offset
+    pub fn goto_w(&mut self, target: Label) -> &mut Self {
+        self.jump(Jump::Wide(ByteCode::GotoW), target)
+    }
+
+    pub fn jsr(&mut self, target: Label) -> &mut Self {
+        self.jump(Jump::Short(ByteCode::Jsr), target)
+    }
+
+    pub fn jsr_w(&mut self, target: Label) -> &mut Self {
+        self.jump(Jump::Wide(ByteCode::JsrW), target)
+    }
+
+    /// Jumps to `targets[value - low]`, or `default` if the value is out of range
+    pub fn tableswitch(&mut self, low: i32, targets: &[Label], default: Label) -> &mut Self {
+        self.push(Op::Ready(Instruction::TableSwitch {
+            low,
+            targets: targets.to_vec(),
+            default,
+        }))
+    }
+
+    /// Jumps to the label of the key equal to the value, or `default`. The JVM requires
+    /// the keys in increasing order, they are written in the order they are given.
+    pub fn lookupswitch(&mut self, pairs: &[(i32, Label)], default: Label) -> &mut Self {
+        self.push(Op::Ready(Instruction::LookupSwitch {
+            pairs: pairs.to_vec(),
+            default,
+        }))
+    }
+
+    pub fn ireturn(&mut self) -> &mut Self {
+        self.simple(ByteCode::IReturn)
+    }
+
+    pub fn lreturn(&mut self) -> &mut Self {
+        self.simple(ByteCode::LReturn)
+    }
+
+    pub fn freturn(&mut self) -> &mut Self {
+        self.simple(ByteCode::FReturn)
+    }
+
+    pub fn dreturn(&mut self) -> &mut Self {
+        self.simple(ByteCode::DReturn)
+    }
+
+    pub fn areturn(&mut self) -> &mut Self {
+        self.simple(ByteCode::AReturn)
+    }
+
+    /// `return` from a `void` method
+    pub fn return_(&mut self) -> &mut Self {
+        self.simple(ByteCode::Return)
+    }
+
+    fn field(
+        &mut self,
+        make: fn(u16) -> ByteCode,
+        owner: &str,
+        name: &str,
+        desc: &str,
+    ) -> &mut Self {
+        let field = FieldRef {
+            owner: owner.to_string(),
+            name: name.to_string(),
+            descriptor: desc.to_string(),
+        };
+        self.push(Op::Field(make, field))
+    }
+
+    pub fn getstatic(&mut self, owner: &str, name: &str, descriptor: &str) -> &mut Self {
+        self.field(ByteCode::GetStatic, owner, name, descriptor)
+    }
+
+    pub fn putstatic(&mut self, owner: &str, name: &str, descriptor: &str) -> &mut Self {
+        self.field(ByteCode::PutStatic, owner, name, descriptor)
+    }
+
+    pub fn getfield(&mut self, owner: &str, name: &str, descriptor: &str) -> &mut Self {
+        self.field(ByteCode::GetField, owner, name, descriptor)
+    }
+
+    pub fn putfield(&mut self, owner: &str, name: &str, descriptor: &str) -> &mut Self {
+        self.field(ByteCode::PutField, owner, name, descriptor)
+    }
+
+    fn method(
+        &mut self,
+        make: fn(u16) -> ByteCode,
+        owner: &str,
+        name: &str,
+        descriptor: &str,
+        is_interface: bool,
+    ) -> &mut Self {
+        let method = MethodRef {
+            owner: owner.to_string(),
+            name: name.to_string(),
+            descriptor: descriptor.to_string(),
+            is_interface,
+        };
+        self.push(Op::Method(make, method))
+    }
+
+    pub fn invokevirtual(&mut self, owner: &str, name: &str, descriptor: &str) -> &mut Self {
+        self.method(ByteCode::InvokeVirtual, owner, name, descriptor, false)
+    }
+
+    /// Calls a constructor, a private method or a method of the super class
+    pub fn invokespecial(&mut self, owner: &str, name: &str, descriptor: &str) -> &mut Self {
+        self.method(ByteCode::InvokeSpecial, owner, name, descriptor, false)
+    }
+
+    /// `invokespecial` of a default method of a super interface
+    pub fn invokespecial_interface(
+        &mut self,
+        owner: &str,
+        name: &str,
+        descriptor: &str,
+    ) -> &mut Self {
+        self.method(ByteCode::InvokeSpecial, owner, name, descriptor, true)
+    }
+
+    pub fn invokestatic(&mut self, owner: &str, name: &str, descriptor: &str) -> &mut Self {
+        self.method(ByteCode::InvokeStatic, owner, name, descriptor, false)
+    }
+
+    /// `invokestatic` of a static method of an interface
+    pub fn invokestatic_interface(
+        &mut self,
+        owner: &str,
+        name: &str,
+        descriptor: &str,
+    ) -> &mut Self {
+        self.method(ByteCode::InvokeStatic, owner, name, descriptor, true)
+    }
+
+    pub fn invokeinterface(&mut self, owner: &str, name: &str, descriptor: &str) -> &mut Self {
+        let method = MethodRef {
+            owner: owner.to_string(),
+            name: name.to_string(),
+            descriptor: descriptor.to_string(),
+            is_interface: true,
+        };
+        self.push(Op::InvokeInterface(method))
+    }
+
+    /// Calls the call site `bootstrap` returns for `name` and `descriptor`, `arguments`
+    /// are the static arguments of the bootstrap method
+    pub fn invokedynamic(
+        &mut self,
+        name: &str,
+        descriptor: &str,
+        bootstrap: MethodHandle,
+        arguments: Vec<Constant>,
+    ) -> &mut Self {
+        self.push(Op::InvokeDynamic {
+            name: name.to_string(),
+            descriptor: descriptor.to_string(),
+            bootstrap,
+            arguments,
+        })
+    }
+
+    /// `new` of the class with the binary name `class`
+    pub fn new_(&mut self, class: &str) -> &mut Self {
+        self.push(Op::Class(ByteCode::New, class.to_string()))
+    }
+
+    pub fn newarray(&mut self, element: BaseType) -> &mut Self {
+        let atype = match element {
+            BaseType::Boolean => 4,
+            BaseType::Char => 5,
+            BaseType::Float => 6,
+            BaseType::Double => 7,
+            BaseType::Byte => 8,
+            BaseType::Short => 9,
+            BaseType::Int => 10,
+            BaseType::Long => 11,
+        };
+        self.simple(ByteCode::NewArray(atype))
+    }
+
+    /// An array of `class`, a binary name or an array descriptor
+    pub fn anewarray(&mut self, class: &str) -> &mut Self {
+        self.push(Op::Class(ByteCode::ANewArray, class.to_string()))
+    }
+
+    /// An array of the array type `descriptor` with its first `dimensions` created
+    pub fn multianewarray(&mut self, descriptor: &str, dimensions: u8) -> &mut Self {
+        self.push(Op::MultiANewArray(descriptor.to_string(), dimensions))
+    }
+
+    pub fn arraylength(&mut self) -> &mut Self {
+        self.simple(ByteCode::ArrayLength)
+    }
+
+    pub fn athrow(&mut self) -> &mut Self {
+        self.simple(ByteCode::Athrow)
+    }
+
+    pub fn checkcast(&mut self, class: &str) -> &mut Self {
+        self.push(Op::Class(ByteCode::CheckCast, class.to_string()))
+    }
+
+    pub fn instanceof(&mut self, class: &str) -> &mut Self {
+        self.push(Op::Class(ByteCode::InstanceOf, class.to_string()))
+    }
+
+    pub fn monitorenter(&mut self) -> &mut Self {
+        self.simple(ByteCode::MonitorEnter)
+    }
+
+    pub fn monitorexit(&mut self) -> &mut Self {
+        self.simple(ByteCode::MonitorExit)
+    }
+
+    pub fn nop(&mut self) -> &mut Self {
+        self.simple(ByteCode::Nop)
+    }
+
+    /// Emits an instruction read by `transform`, its constant pool indices are those of the
+    /// class being transformed
+    pub fn emit(&mut self, instruction: transform::Instruction) -> &mut Self {
+        match instruction {
+            transform::Instruction::Plain(instruction) => {
+                if instruction.branch_targets(0).is_empty() {
+                    self.simple(instruction)
+                } else {
+                    self.fail("Branches are emitted with the labels they jump to");
+                    self
+                }
+            }
+            transform::Instruction::Jump(instruction, target) => match Jump::of(&instruction) {
+                Some(jump) => self.jump(jump, target),
+                None => {
+                    self.fail(&format!("{:?} is not a branch", instruction));
+                    self
+                }
+            },
+            transform::Instruction::TableSwitch {
+                low,
+                targets,
+                default,
+            } => self.tableswitch(low, &targets, default),
+            transform::Instruction::LookupSwitch { pairs, default } => {
+                self.lookupswitch(&pairs, default)
+            }
+        }
+    }
+
+    /// The Code attribute with the constants of the instructions added, `None` for
+    /// methods without instructions
+    pub(super) fn build(
+        self,
+        constants: &mut Constants,
+    ) -> Result<Option<(CodeAttribute, Limits)>> {
+        if let Some(error) = self.error {
+            return Err(anyhow!(error));
+        }
+        if self.ops.is_empty() {
+            return Ok(None);
+        }
+        let mut instructions = Vec::with_capacity(self.ops.len());
+        for op in self.ops {
+            instructions.push(op.instruction(constants)?);
+        }
+        let mut pcs = Vec::with_capacity(instructions.len() + 1);
+        let mut pc = 0;
+        for instruction in &instructions {
+            pcs.push(pc);
+            pc += instruction.size(pc);
+        }
+        pcs.push(pc);
+        let code_length = pc;
+        let labels = self.labels;
+        let find = |label: Label| match labels[label.0] {
+            Some(index) => Ok(pcs[index]),
+            None => Err(anyhow!("A label is used but never placed")),
+        };
+        let short = |pc: u32| u16::try_from(pc).map_err(|_| anyhow!("pc {} is past 65535", pc));
+
+        let mut code = Vec::with_capacity(instructions.len());
+        for (instruction, pc) in instructions.iter().zip(&pcs) {
+            code.push((*pc, instruction.resolve(*pc, &find)?));
+        }
+        let cp = &mut constants.cp;
+        let mut exception_table = Vec::new();
+        for handler in &self.handlers {
+            // a range whose instructions were all removed covers nothing
+            if find(handler.start)? == find(handler.end)? {
+                continue;
+            }
+            exception_table.push(ExceptionTable {
+                start_pc: short(find(handler.start)?)?,
+                end_pc: short(find(handler.end)?)?,
+                handler_pc: short(find(handler.handler)?)?,
+                catch_type: match &handler.catch_type {
+                    Some(class) => cp.add_class(class)?,
+                    None => 0,
+                },
+            });
+        }
+
+        let mut attributes = Vec::new();
+        if !self.lines.is_empty() {
+            let mut lines = Vec::new();
+            for (index, line_number) in self.lines {
+                lines.push(LineNumberTable {
+                    start_pc: short(pcs[index])?,
+                    line_number,
+                });
+            }
+            let name_index = cp.add_utf8("LineNumberTable")?;
+            let att = LineNumberTableAttribute::new(name_index, lines);
+            attributes.push(Attribute::LineNumberTable(att));
+        }
+        let mut variables = Vec::new();
+        let mut variable_types = Vec::new();
+        for variable in &self.variables {
+            let start = find(variable.start)?;
+            let length = find(variable.end)?
+                .checked_sub(start)
+                .ok_or(anyhow!("{} ends before it starts", variable.name))?;
+            let name_index = cp.add_utf8(&variable.name)?;
+            let mut local = |descriptor: &str| -> Result<LocalVariable> {
+                Ok(LocalVariable {
+                    start_pc: short(start)?,
+                    length: short(length)?,
+                    name_index,
+                    descriptor_index: cp.add_utf8(descriptor)?,
+                    index: variable.index,
+                })
+            };
+            variables.push(local(&variable.descriptor)?);
+            if let Some(signature) = &variable.signature {
+                variable_types.push(local(signature)?);
+            }
+        }
+        if !variables.is_empty() {
+            let name_index = cp.add_utf8("LocalVariableTable")?;
+            let att = LocalVariableTableAttribute::new(name_index, variables);
+            attributes.push(Attribute::LocalVariableTable(att));
+        }
+        if !variable_types.is_empty() {
+            let name_index = cp.add_utf8("LocalVariableTypeTable")?;
+            let att = LocalVariableTableAttribute::new(name_index, variable_types);
+            attributes.push(Attribute::LocalVariableTypeTable(att));
+        }
+
+        let attribute = CodeAttribute::new(
+            cp.add_utf8("Code")?,
+            self.max_stack.unwrap_or(0),
+            self.max_locals.unwrap_or(0),
+            code,
+            code_length,
+            exception_table,
+            Attributes::new(attributes),
+        );
+        let limits = Limits {
+            max_stack: self.max_stack,
+            max_locals: self.max_locals,
+            stack_map: self.stack_map,
+        };
+        Ok(Some((attribute, limits)))
+    }
+}
+
+impl Limits {
+    /// Computes what wasn't set of `max_stack`, `max_locals` and the StackMapTable of
+    /// `method`
+    pub(super) fn complete(
+        &self,
+        method: &mut Method,
+        class_name: &str,
+        major_version: u16,
+        cp: &mut ConstantPool,
+        hierarchy: &dyn ClassHierarchy,
+    ) -> Result<()> {
+        let stack_map = self.stack_map && major_version >= 50;
+        if !stack_map && self.max_stack.is_some() && self.max_locals.is_some() {
+            return Ok(());
+        }
+        let computed = method.compute_frames(class_name, cp, hierarchy)?;
+        let table = match stack_map && !computed.frames.is_empty() {
+            true => {
+                let initial = method.initial_frame(class_name, cp)?;
+                Some(StackMapTableAttribute::from_frames(
+                    cp.add_utf8("StackMapTable")?,
+                    &initial.locals,
+                    &computed.frames,
+                    cp,
+                )?)
+            }
+            false => None,
+        };
+        for attribute in method.attributes.attributes_mut()? {
+            if let Attribute::Code(code) = attribute {
+                if stack_map {
+                    code.replace_dead_code(&computed.dead_code);
+                }
+                code.set_frames(
+                    self.max_stack.unwrap_or(computed.max_stack),
+                    self.max_locals.unwrap_or(computed.max_locals),
+                    table,
+                )?;
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Op {
+    /// The instruction with its constants added to the pool
+    fn instruction(self, constants: &mut Constants) -> Result<Instruction> {
+        let cp = &mut constants.cp;
+        let instruction = match self {
+            Op::Ready(instruction) => return Ok(instruction),
+            Op::Field(make, field) => make(cp.add_field_ref(&field)?),
+            Op::Method(make, method) => make(cp.add_method_ref(&method)?),
+            Op::InvokeInterface(method) => {
+                let count = MethodDescriptor::parse(&method.descriptor)?.args_size(false);
+                ByteCode::InvokeInterface(cp.add_method_ref(&method)?, count as u8)
+            }
+            Op::InvokeDynamic {
+                name,
+                descriptor,
+                bootstrap,
+                arguments,
+            } => {
+                let mut indexes = Vec::with_capacity(arguments.len());
+                for argument in &arguments {
+                    indexes.push(argument.add(&mut constants.cp)?);
+                }
+                let index =
+                    constants.add_invoke_dynamic(&name, &descriptor, &bootstrap, indexes)?;
+                ByteCode::InvokeDynamic(index)
+            }
+            Op::Class(make, class) => make(cp.add_class(&class)?),
+            Op::MultiANewArray(descriptor, dimensions) => {
+                ByteCode::MultiANewArray(cp.add_class(&descriptor)?, dimensions)
+            }
+            Op::Ldc(constant) => {
+                let index = constant.add(cp)?;
+                match (&constant, u8::try_from(index)) {
+                    (Constant::Long(_) | Constant::Double(_), _) => ByteCode::Ldc2W(index),
+                    (_, Ok(index)) => ByteCode::Ldc(index),
+                    (_, Err(_)) => ByteCode::LdcW(index),
+                }
+            }
+        };
+        Ok(Instruction::Fixed(instruction, None))
+    }
+}
+
+impl Jump {
+    /// The branch of `instruction`, whatever its offset, `None` if it doesn't branch or is
+    /// a switch
+    pub(crate) fn of(instruction: &ByteCode) -> Option<Jump> {
+        let short: fn(i16) -> ByteCode = match instruction {
+            ByteCode::Ifeq(_) => ByteCode::Ifeq,
+            ByteCode::Ifne(_) => ByteCode::Ifne,
+            ByteCode::Iflt(_) => ByteCode::Iflt,
+            ByteCode::Ifge(_) => ByteCode::Ifge,
+            ByteCode::Ifgt(_) => ByteCode::Ifgt,
+            ByteCode::Ifle(_) => ByteCode::Ifle,
+            ByteCode::IfICmpEq(_) => ByteCode::IfICmpEq,
+            ByteCode::IfICmpNe(_) => ByteCode::IfICmpNe,
+            ByteCode::IfICmpLt(_) => ByteCode::IfICmpLt,
+            ByteCode::IfICmpGe(_) => ByteCode::IfICmpGe,
+            ByteCode::IfICmpGt(_) => ByteCode::IfICmpGt,
+            ByteCode::IfICmpLe(_) => ByteCode::IfICmpLe,
+            ByteCode::IfACmpEq(_) => ByteCode::IfACmpEq,
+            ByteCode::IfACmpNe(_) => ByteCode::IfACmpNe,
+            ByteCode::IfNull(_) => ByteCode::IfNull,
+            ByteCode::IfNonNull(_) => ByteCode::IfNonNull,
+            ByteCode::Goto(_) => ByteCode::Goto,
+            ByteCode::Jsr(_) => ByteCode::Jsr,
+            ByteCode::GotoW(_) => return Some(Jump::Wide(ByteCode::GotoW)),
+            ByteCode::JsrW(_) => return Some(Jump::Wide(ByteCode::JsrW)),
+            _ => return None,
+        };
+        Some(Jump::Short(short))
+    }
+}
+
+impl Instruction {
+    /// Number of bytes the instruction takes at `pc`
+    pub fn size(&self, pc: u32) -> u32 {
+        match self {
+            Instruction::Fixed(_, Some(len)) => *len,
+            Instruction::Fixed(instruction, None) => instruction.size(pc),
+            Instruction::Jump(Jump::Short(_), _) => 3,
+            Instruction::Jump(Jump::Wide(_), _) => 5,
+            Instruction::TableSwitch { .. } | Instruction::LookupSwitch { .. } => self
+                .resolve(pc, &|_| Ok(pc))
+                .map_or(0, |instruction| instruction.size(pc)),
+        }
+    }
+
+    /// The instruction at `pc` with the offsets to its labels
+    pub fn resolve(&self, pc: u32, find: &dyn Fn(Label) -> Result<u32>) -> Result<ByteCode> {
+        let offset = |label: Label| find(label).map(|target| target as i64 - pc as i64);
+        let wide = |label: Label| offset(label).map(|offset| offset as i32);
+        let instruction = match self {
+            Instruction::Fixed(instruction, _) => instruction.clone(),
+            Instruction::Jump(Jump::Short(make), target) => {
+                let offset = offset(*target)?;
+                make(i16::try_from(offset).map_err(|_| {
+                    anyhow!(
+                        "A branch at pc {} is too far from its target, use goto_w",
+                        pc
+                    )
+                })?)
+            }
+            Instruction::Jump(Jump::Wide(make), target) => make(wide(*target)?),
+            Instruction::TableSwitch {
+                low,
+                targets,
+                default,
+            } => ByteCode::TableSwitch(TableSwitch {
+                default: wide(*default)?,
+                low: *low,
+                high: low + targets.len() as i32 - 1,
+                offsets: targets.iter().map(|l| wide(*l)).collect::<Result<_>>()?,
+            }),
+            Instruction::LookupSwitch { pairs, default } => ByteCode::LookupSwitch(LookupSwitch {
+                default: wide(*default)?,
+                pairs: pairs
+                    .iter()
+                    .map(|(key, l)| Ok((*key, wide(*l)?)))
+                    .collect::<Result<_>>()?,
+            }),
+        };
+        Ok(instruction)
+    }
+}
@@ -0,0 +1,377 @@
+//! Builds class files from Rust code.
+//!
+//! ```
+//! use jaust::class_file::builder::{ClassBuilder, CodeBuilder, ACC_PUBLIC, ACC_STATIC};
+//! use jaust::class_file::ClassHierarchyMap;
+//!
+//! let mut constructor = CodeBuilder::new();
+//! constructor
+//!     .aload(0)
+//!     .invokespecial("java/lang/Object", "<init>", "()V")
+//!     .return_();
+//! let mut twice = CodeBuilder::new();
+//! twice.iload(0).iconst(2).imul().ireturn();
+//!
+//! let class = ClassBuilder::new("com/foo/Bar")
+//!     .field(ACC_PUBLIC, "name", "Ljava/lang/String;")
+//!     .method(ACC_PUBLIC, "<init>", "()V", constructor)
+//!     .method(ACC_PUBLIC | ACC_STATIC, "twice", "(I)I", twice)
+//!     .build(&ClassHierarchyMap::new())
+//!     .unwrap();
+//! assert_eq!(class.class_name(), "com/foo/Bar");
+//! ```
+//!
+//! The constant pool entries are added once, when the class is built. `max_stack`,
+//! `max_locals` and the StackMapTable are computed from the instructions, `hierarchy`
+//! gives the super classes to merge the types of the frames.
+
+mod code;
+
+pub(crate) use code::Jump;
+pub use code::{CodeBuilder, Label};
+
+use super::access_flags::AccessFlags;
+use super::attributes::{
+    Attribute, Attributes, BootstrapMethod, BootstrapMethodsAttribute, ConstantValueAttribute,
+    SourceFileAttribute,
+};
+use super::constant_pool::{ConstantPool, InvokeDynamic, MethodHandle};
+use super::fields::{self, Field, Fields};
+use super::interfaces::Interfaces;
+use super::methods::{self, Method, Methods};
+use super::{ClassFile, ClassHierarchy};
+use anyhow::{anyhow, Result};
+
+// Access flags of classes, fields and methods, some share a bit (JVMS 4.1, 4.5, 4.6)
+pub const ACC_PUBLIC: u16 = 0x0001;
+pub const ACC_PRIVATE: u16 = 0x0002;
+pub const ACC_PROTECTED: u16 = 0x0004;
+pub const ACC_STATIC: u16 = 0x0008;
+pub const ACC_FINAL: u16 = 0x0010;
+pub const ACC_SUPER: u16 = 0x0020;
+pub const ACC_SYNCHRONIZED: u16 = 0x0020;
+pub const ACC_VOLATILE: u16 = 0x0040;
+pub const ACC_BRIDGE: u16 = 0x0040;
+pub const ACC_TRANSIENT: u16 = 0x0080;
+pub const ACC_VARARGS: u16 = 0x0080;
+pub const ACC_NATIVE: u16 = 0x0100;
+pub const ACC_INTERFACE: u16 = 0x0200;
+pub const ACC_ABSTRACT: u16 = 0x0400;
+pub const ACC_STRICT: u16 = 0x0800;
+pub const ACC_SYNTHETIC: u16 = 0x1000;
+pub const ACC_ANNOTATION: u16 = 0x2000;
+pub const ACC_ENUM: u16 = 0x4000;
+pub const ACC_MODULE: u16 = 0x8000;
+
+/// A loadable constant, the operand of `ldc`, the value of a constant field or an
+/// argument of a bootstrap method
+#[derive(Debug, Clone, PartialEq)]
+pub enum Constant {
+    Int(i32),
+    Float(f32),
+    Long(i64),
+    Double(f64),
+    String(String),
+    /// Binary name of a class or descriptor of an array
+    Class(String),
+    /// A method descriptor
+    MethodType(String),
+    MethodHandle(MethodHandle),
+}
+
+impl Constant {
+    /// Index of the constant in the pool, added if it isn't there yet
+    pub(crate) fn add(&self, cp: &mut ConstantPool) -> Result<u16> {
+        match self {
+            Constant::Int(value) => cp.add_integer(*value),
+            Constant::Float(value) => cp.add_float(*value),
+            Constant::Long(value) => cp.add_long(*value),
+            Constant::Double(value) => cp.add_double(*value),
+            Constant::String(value) => cp.add_string(value),
+            Constant::Class(name) => cp.add_class(name),
+            Constant::MethodType(descriptor) => cp.add_method_type(descriptor),
+            Constant::MethodHandle(handle) => cp.add_method_handle(handle),
+        }
+    }
+}
+
+/// The constant pool and the bootstrap methods the instructions are added to
+#[derive(Default)]
+pub(crate) struct Constants {
+    pub cp: ConstantPool,
+    /// MethodHandle entry and arguments of each bootstrap method
+    pub bootstrap_methods: Vec<(u16, Vec<u16>)>,
+}
+
+impl Constants {
+    /// The InvokeDynamic entry of a call site, its bootstrap method is added once
+    pub fn add_invoke_dynamic(
+        &mut self,
+        name: &str,
+        descriptor: &str,
+        bootstrap: &MethodHandle,
+        arguments: Vec<u16>,
+    ) -> Result<u16> {
+        let bootstrap = (self.cp.add_method_handle(bootstrap)?, arguments);
+        let index = match self.bootstrap_methods.iter().position(|b| *b == bootstrap) {
+            Some(index) => index,
+            None => {
+                self.bootstrap_methods.push(bootstrap);
+                self.bootstrap_methods.len() - 1
+            }
+        };
+        self.cp.add_invoke_dynamic(&InvokeDynamic {
+            bootstrap_method_attr_index: u16::try_from(index)
+                .map_err(|_| anyhow!("Too many bootstrap methods"))?,
+            name: name.to_string(),
+            descriptor: descriptor.to_string(),
+        })
+    }
+}
+
+/// A class file under construction, see the module documentation
+pub struct ClassBuilder {
+    name: String,
+    major_version: u16,
+    minor_version: u16,
+    access_flags: u16,
+    super_class: Option<String>,
+    interfaces: Vec<String>,
+    source_file: Option<String>,
+    fields: Vec<FieldEntry>,
+    methods: Vec<MethodEntry>,
+    attributes: Vec<Attribute>,
+    constants: Constants,
+}
+
+struct FieldEntry {
+    access_flags: u16,
+    name: String,
+    descriptor: String,
+    constant: Option<Constant>,
+    attributes: Vec<Attribute>,
+}
+
+struct MethodEntry {
+    access_flags: u16,
+    name: String,
+    descriptor: String,
+    code: CodeBuilder,
+    attributes: Vec<Attribute>,
+}
+
+impl ClassBuilder {
+    /// A public class (with `ACC_SUPER`) of version 52 (Java 8) named with its binary
+    /// name, e.g. `com/foo/Bar`, that extends `java/lang/Object`
+    pub fn new(name: &str) -> ClassBuilder {
+        ClassBuilder::with_constants(name, Constants::default())
+    }
+
+    /// A class whose constant pool already has the entries of `constants`
+    pub(crate) fn with_constants(name: &str, constants: Constants) -> ClassBuilder {
+        ClassBuilder {
+            name: name.to_string(),
+            major_version: 52,
+            minor_version: 0,
+            access_flags: ACC_PUBLIC | ACC_SUPER,
+            super_class: None,
+            interfaces: Vec::new(),
+            source_file: None,
+            fields: Vec::new(),
+            methods: Vec::new(),
+            attributes: Vec::new(),
+            constants,
+        }
+    }
+
+    /// Classes from version 50 get a StackMapTable
+    pub fn version(mut self, major: u16, minor: u16) -> ClassBuilder {
+        self.major_version = major;
+        self.minor_version = minor;
+        self
+    }
+
+    /// `ACC_*` flags of the class
+    pub fn access_flags(mut self, access_flags: u16) -> ClassBuilder {
+        self.access_flags = access_flags;
+        self
+    }
+
+    pub fn super_class(mut self, name: &str) -> ClassBuilder {
+        self.super_class = Some(name.to_string());
+        self
+    }
+
+    pub fn interface(mut self, name: &str) -> ClassBuilder {
+        self.interfaces.push(name.to_string());
+        self
+    }
+
+    /// The `SourceFile` attribute, the file name stack traces show
+    pub fn source_file(mut self, name: &str) -> ClassBuilder {
+        self.source_file = Some(name.to_string());
+        self
+    }
+
+    pub fn field(self, access_flags: u16, name: &str, descriptor: &str) -> ClassBuilder {
+        self.field_with(access_flags, name, descriptor, None, Vec::new())
+    }
+
+    /// A field with a `ConstantValue` attribute, the initial value of static fields
+    pub fn constant_field(
+        self,
+        access_flags: u16,
+        name: &str,
+        descriptor: &str,
+        value: Constant,
+    ) -> ClassBuilder {
+        self.field_with(access_flags, name, descriptor, Some(value), Vec::new())
+    }
+
+    /// A method with the instructions of `code`. Abstract and native methods take an
+    /// empty `CodeBuilder`, they don't get a Code attribute.
+    pub fn method(
+        self,
+        access_flags: u16,
+        name: &str,
+        descriptor: &str,
+        code: CodeBuilder,
+    ) -> ClassBuilder {
+        self.method_with(access_flags, name, descriptor, code, Vec::new())
+    }
+
+    pub(crate) fn field_with(
+        mut self,
+        access_flags: u16,
+        name: &str,
+        descriptor: &str,
+        constant: Option<Constant>,
+        attributes: Vec<Attribute>,
+    ) -> ClassBuilder {
+        self.fields.push(FieldEntry {
+            access_flags,
+            name: name.to_string(),
+            descriptor: descriptor.to_string(),
+            constant,
+            attributes,
+        });
+        self
+    }
+
+    pub(crate) fn method_with(
+        mut self,
+        access_flags: u16,
+        name: &str,
+        descriptor: &str,
+        code: CodeBuilder,
+        attributes: Vec<Attribute>,
+    ) -> ClassBuilder {
+        self.methods.push(MethodEntry {
+            access_flags,
+            name: name.to_string(),
+            descriptor: descriptor.to_string(),
+            code,
+            attributes,
+        });
+        self
+    }
+
+    /// A class attribute whose entries are already in `constants`
+    pub(crate) fn attribute(mut self, attribute: Attribute) -> ClassBuilder {
+        self.attributes.push(attribute);
+        self
+    }
+
+    /// The class file, with the frames of its methods computed
+    pub fn build(self, hierarchy: &dyn ClassHierarchy) -> Result<ClassFile> {
+        let mut constants = self.constants;
+        let cp = &mut constants.cp;
+        let this_class = cp.add_class(&self.name)?;
+        let super_class = match (&self.super_class, self.name.as_str()) {
+            (Some(name), _) => cp.add_class(name)?,
+            (None, "java/lang/Object") => 0,
+            (None, _) => cp.add_class("java/lang/Object")?,
+        };
+        let mut interfaces = Vec::new();
+        for name in &self.interfaces {
+            interfaces.push(cp.add_class(name)?);
+        }
+
+        let mut fields = Vec::new();
+        for field in self.fields {
+            let cp = &mut constants.cp;
+            let mut attributes = Vec::new();
+            if let Some(constant) = &field.constant {
+                let name_index = cp.add_utf8("ConstantValue")?;
+                let att = ConstantValueAttribute::new(name_index, constant.add(cp)?);
+                attributes.push(Attribute::ConstantValueAttribute(att));
+            }
+            attributes.extend(field.attributes);
+            fields.push(Field {
+                access_flags: fields::AccessFlags::new(field.access_flags),
+                name_index: cp.add_utf8(&field.name)?,
+                descriptor_index: cp.add_utf8(&field.descriptor)?,
+                attributes: Attributes::new(attributes),
+            });
+        }
+
+        let mut methods = Vec::new();
+        for method in self.methods {
+            let signature = format!("{}{}", method.name, method.descriptor);
+            let code = method
+                .code
+                .build(&mut constants)
+                .map_err(|e| anyhow!("{} in {}", e, signature))?;
+            let cp = &mut constants.cp;
+            let mut attributes = method.attributes;
+            let limits = code.map(|(attribute, limits)| {
+                attributes.insert(0, Attribute::Code(attribute));
+                limits
+            });
+            let mut built = Method {
+                access_flags: methods::AccessFlags::new(method.access_flags),
+                name_index: cp.add_utf8(&method.name)?,
+                descriptor_index: cp.add_utf8(&method.descriptor)?,
+                attributes: Attributes::new(attributes),
+            };
+            if let Some(limits) = limits {
+                limits
+                    .complete(&mut built, &self.name, self.major_version, cp, hierarchy)
+                    .map_err(|e| anyhow!("Could not compute the frames of {}: {}", signature, e))?;
+            }
+            methods.push(built);
+        }
+
+        let cp = &mut constants.cp;
+        let mut attributes = Vec::new();
+        if let Some(source_file) = &self.source_file {
+            let name_index = cp.add_utf8("SourceFile")?;
+            let att = SourceFileAttribute::new(name_index, cp.add_utf8(source_file)?);
+            attributes.push(Attribute::SourceFile(att));
+        }
+        attributes.extend(self.attributes);
+        if !constants.bootstrap_methods.is_empty() {
+            let name_index = cp.add_utf8("BootstrapMethods")?;
+            let methods = constants
+                .bootstrap_methods
+                .into_iter()
+                .map(|(method_ref, arguments)| BootstrapMethod::new(method_ref, arguments))
+                .collect();
+            let att = BootstrapMethodsAttribute::new(name_index, methods);
+            attributes.push(Attribute::BootstrapMethodsAttribute(att));
+        }
+
+        Ok(ClassFile {
+            minor_version: self.minor_version,
+            major_version: self.major_version,
+            constant_pool: constants.cp,
+            access_flags: AccessFlags::new(self.access_flags),
+            this_class,
+            super_class,
+            interfaces: Interfaces { interfaces },
+            fields: Fields { fields },
+            methods: Methods { methods },
+            attributes: Attributes::new(attributes),
+        })
+    }
+}
@@ -1,116 +1,949 @@
 use super::file_reader::FileReader;
-use anyhow::Result;
+use super::file_writer::FileWriter;
+use anyhow::{anyhow, Result};
 
-#[derive(Debug)]
+/// A JVM instruction. Instructions with short forms (`iload_1`, `iconst_m1`, ...) and their
+/// `wide` variants are folded into a single variant with the index or value as operand.
+/// Branch offsets are kept relative to the pc of the instruction, like in the class file.
+#[derive(Debug, Clone, PartialEq)]
 pub enum ByteCode {
-    IConstn(u8),              // Push int constant
-    LConstn(u8),              // Push long constant
-    Lcmp,                     // Compare long
-    IReturn,                  // Return int from method
-    New(u16),                 // create new object
-    ANewArray(u16),           // Create new array of reference
-    InvokeSpecial(u16), // Invoke instance method; special handling for superclass, private, and instance initialization method invocations
+    Nop,                        // Do nothing
+    AConstNull,                 // Push null
+    IConstn(i8),                // Push int constant
+    LConstn(u8),                // Push long constant
+    FConstn(u8),                // Push float constant
+    DConstn(u8),                // Push double constant
+    BiPush(i8),                 // Push byte
+    SiPush(i16),                // Push short
+    Ldc(u8),                    // Push item from run-time constant pool
+    LdcW(u16),                  // Push item from run-time constant pool (wide index)
+    Ldc2W(u16),                 // Push long or double from run-time constant pool (wide index)
+    ILoad(u16),                 // Load int from local variable
+    Lload(u16),                 // Load long from local variable
+    FLoad(u16),                 // Load float from local variable
+    DLoad(u16),                 // Load double from local variable
+    ALoad(u16),                 // Load reference from local variable
+    IALoad,                     // Load int from array
+    LALoad,                     // Load long from array
+    FALoad,                     // Load float from array
+    DALoad,                     // Load double from array
+    AALoad,                     // Load reference from array
+    BALoad,                     // Load byte or boolean from array
+    CALoad,                     // Load char from array
+    SALoad,                     // Load short from array
+    IStore(u16),                // Store int into local variable
+    LStore(u16),                // Store long into local variable
+    FStore(u16),                // Store float into local variable
+    DStore(u16),                // Store double into local variable
+    AStore(u16),                // Store reference into local variable
+    IAStore,                    // Store into int array
+    LAStore,                    // Store into long array
+    FAStore,                    // Store into float array
+    DAStore,                    // Store into double array
+    AAStore,                    // Store into reference array
+    BAStore,                    // Store into byte or boolean array
+    CAStore,                    // Store into char array
+    SAStore,                    // Store into short array
+    Pop,                        // Pop the top operand stack value
+    Pop2,                       // Pop the top one or two operand stack values
+    Duplicate,                  // Duplicate the top operand stack value
+    DupX1,                      // Duplicate the top value and insert two values down
+    DupX2,                      // Duplicate the top value and insert two or three values down
+    Dup2,                       // Duplicate the top one or two operand stack values
+    Dup2X1,                     // Duplicate the top one or two values and insert them further down
+    Dup2X2,         // Duplicate the top one or two values and insert them even further down
+    Swap,           // Swap the top two operand stack values
+    IAdd,           // Add int
+    LAdd,           // Add long
+    FAdd,           // Add float
+    DAdd,           // Add double
+    ISub,           // Subtract int
+    LSub,           // Subtract long
+    FSub,           // Subtract float
+    DSub,           // Subtract double
+    IMul,           // Multiply int
+    LMul,           // Multiply long
+    FMul,           // Multiply float
+    DMul,           // Multiply double
+    IDiv,           // Divide int
+    LDiv,           // Divide long
+    FDiv,           // Divide float
+    DDiv,           // Divide double
+    IRem,           // Remainder int
+    LRem,           // Remainder long
+    FRem,           // Remainder float
+    DRem,           // Remainder double
+    INeg,           // Negate int
+    LNeg,           // Negate long
+    FNeg,           // Negate float
+    DNeg,           // Negate double
+    IShl,           // Shift left int
+    LShl,           // Shift left long
+    IShr,           // Arithmetic shift right int
+    LShr,           // Arithmetic shift right long
+    IUShr,          // Logical shift right int
+    LUShr,          // Logical shift right long
+    IAnd,           // Boolean AND int
+    LAnd,           // Boolean AND long
+    IOr,            // Boolean OR int
+    LOr,            // Boolean OR long
+    IXor,           // Boolean XOR int
+    LXor,           // Boolean XOR long
+    IInc(u16, i16), // Increment local variable by constant
+    I2L,            // Convert int to long
+    I2F,            // Convert int to float
+    I2D,            // Convert int to double
+    L2i,            // Convert long to int
+    L2F,            // Convert long to float
+    L2D,            // Convert long to double
+    F2I,            // Convert float to int
+    F2L,            // Convert float to long
+    F2D,            // Convert float to double
+    D2I,            // Convert double to int
+    D2L,            // Convert double to long
+    D2F,            // Convert double to float
+    I2B,            // Convert int to byte
+    I2C,            // Convert int to char
+    I2S,            // Convert int to short
+    Lcmp,           // Compare long
+    FCmpl,          // Compare float (-1 on NaN)
+    FCmpg,          // Compare float (1 on NaN)
+    DCmpl,          // Compare double (-1 on NaN)
+    DCmpg,          // Compare double (1 on NaN)
+    Ifeq(i16),      // Branch if int value = 0
+    Ifne(i16),      // Branch if int value != 0
+    Iflt(i16),      // Branch if int value < 0
+    Ifge(i16),      // Branch if int value >= 0
+    Ifgt(i16),      // Branch if int value > 0
+    Ifle(i16),      // Branch if int value <= 0
+    IfICmpEq(i16),  // Branch if int values are equal
+    IfICmpNe(i16),  // Branch if int values are not equal
+    IfICmpLt(i16),  // Branch if int value1 < value2
+    IfICmpGe(i16),  // Branch if int value1 >= value2
+    IfICmpGt(i16),  // Branch if int value1 > value2
+    IfICmpLe(i16),  // Branch if int value1 <= value2
+    IfACmpEq(i16),  // Branch if references are equal
+    IfACmpNe(i16),  // Branch if references are not equal
+    Goto(i16),      // Branch always
+    Jsr(i16),       // Jump subroutine
+    Ret(u16),       // Return from subroutine
+    TableSwitch(TableSwitch), // Access jump table by index and jump
+    LookupSwitch(LookupSwitch), // Access jump table by key match and jump
+    IReturn,        // Return int from method
+    LReturn,        // Return long
+    FReturn,        // Return float
+    DReturn,        // Return double
+    AReturn,        // Return reference from method
+    Return,         // Return void
+    GetStatic(u16), // Get static field from class
+    PutStatic(u16), // Set static field in class
+    GetField(u16),  // Fetch field from object
+    PutField(u16),  // Set field in object
     InvokeVirtual(u16), // Invoke instance method; dispatch based on class
+    InvokeSpecial(u16), // Invoke instance method; special handling for superclass, private, and instance initialization method invocations
     InvokeStatic(u16),  // Invoke a class (static) method
-    InvokeDynamic(u16), // Invoke dynamic method
     InvokeInterface(u16, u8), // Invoke interface method
-    Duplicate,          // Duplicate the top operand stack value
-    AReturn,            // Return reference from method
-    ALoad(u8),          // Load reference from local variable
-    Lload(u8),          // Load long from local variable
-    ILoad(u8),          // Load int from local variable
-    AStore(u8),         // Store reference into local variable
-    LStore(u8),         // Store long into local variable
-    AAStore,            // Store into reference array
-    PutStatic(u16),     // Set static field in class
-    GetStatic(u16),     // Get static field from class
-    Ldc(u8),            // Push item from run-time constant pool
-    Return,             // Return void
-    LReturn,            // Return long
-    LAdd,               // Add long
-    L2i,                // Convert long to int
-    I2L,                // Convert int to long
+    InvokeDynamic(u16), // Invoke dynamic method
+    New(u16),           // create new object
+    NewArray(u8),       // Create new array of primitive type (atype)
+    ANewArray(u16),     // Create new array of reference
+    ArrayLength,        // Get length of array
     Athrow,             // Throw exception or error
-    Ifeq(i16),          // Branch if int value = 0
-    Ifne(i16),          // Branch if int value != 0
-    Iflt(i16),          // Branch if int value < 0
-    Ifge(i16),          // Branch if int value >= 0
-    Ifgt(i16),          // Branch if int value > 0
-    Ifle(i16),          // Branch if int value <= 0
-    PutField(u16),      // Set field in object
-    GetField(u16),      // Fetch field from object
     CheckCast(u16),     // Check whether object is of given type
+    InstanceOf(u16),    // Determine if object is of given type
+    MonitorEnter,       // Enter monitor for object
+    MonitorExit,        // Exit monitor for object
+    MultiANewArray(u16, u8), // Create new multidimensional array
+    IfNull(i16),        // Branch if reference is null
+    IfNonNull(i16),     // Branch if reference not null
+    GotoW(i32),         // Branch always (wide index)
+    JsrW(i32),          // Jump subroutine (wide index)
+
+    Generic(u8), // reserved opcodes (breakpoint, impdep1, impdep2)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableSwitch {
+    pub default: i32,
+    pub low: i32,
+    pub high: i32,
+    pub offsets: Vec<i32>,
+}
 
-    Generic(u8),
+#[derive(Debug, Clone, PartialEq)]
+pub struct LookupSwitch {
+    pub default: i32,
+    pub pairs: Vec<(i32, i32)>, // (match, offset) sorted by match
 }
 
 impl ByteCode {
-    pub fn parse(file: &mut FileReader) -> Result<(ByteCode, u32)> {
+    /// Parses the instruction at `pc` (needed for the padding of the switch instructions)
+    /// and returns it with its length in bytes
+    pub fn parse(file: &mut FileReader, pc: u32) -> Result<(ByteCode, u32)> {
         let opcode = file.read_u1()?;
         let (code, len) = match opcode {
-            0x02..=0x08 => (ByteCode::IConstn(opcode - 0x03), 1),
-            0x09..=0x0a => (ByteCode::IConstn(opcode - 0x09), 1),
+            0x00 => (ByteCode::Nop, 1),
+            0x01 => (ByteCode::AConstNull, 1),
+            0x02..=0x08 => (ByteCode::IConstn(opcode as i8 - 0x03), 1),
+            0x09..=0x0a => (ByteCode::LConstn(opcode - 0x09), 1),
+            0x0b..=0x0d => (ByteCode::FConstn(opcode - 0x0b), 1),
+            0x0e..=0x0f => (ByteCode::DConstn(opcode - 0x0e), 1),
+            0x10 => (ByteCode::BiPush(file.read_u1()? as i8), 2),
+            0x11 => (ByteCode::SiPush(file.read_i16()?), 3),
+            0x12 => (ByteCode::Ldc(file.read_u1()?), 2),
+            0x13 => (ByteCode::LdcW(file.read_u2_to_u16()?), 3),
+            0x14 => (ByteCode::Ldc2W(file.read_u2_to_u16()?), 3),
+            0x15..=0x19 | 0x36..=0x3a | 0xa9 => {
+                let index = file.read_u1()? as u16;
+                (Self::local_variable_instruction(opcode, index)?, 2)
+            }
+            0x1a..=0x1d => (ByteCode::ILoad((opcode - 0x1a) as u16), 1),
+            0x1e..=0x21 => (ByteCode::Lload((opcode - 0x1e) as u16), 1),
+            0x22..=0x25 => (ByteCode::FLoad((opcode - 0x22) as u16), 1),
+            0x26..=0x29 => (ByteCode::DLoad((opcode - 0x26) as u16), 1),
+            0x2a..=0x2d => (ByteCode::ALoad((opcode - 0x2a) as u16), 1),
+            0x2e => (ByteCode::IALoad, 1),
+            0x2f => (ByteCode::LALoad, 1),
+            0x30 => (ByteCode::FALoad, 1),
+            0x31 => (ByteCode::DALoad, 1),
+            0x32 => (ByteCode::AALoad, 1),
+            0x33 => (ByteCode::BALoad, 1),
+            0x34 => (ByteCode::CALoad, 1),
+            0x35 => (ByteCode::SALoad, 1),
+            0x3b..=0x3e => (ByteCode::IStore((opcode - 0x3b) as u16), 1),
+            0x3f..=0x42 => (ByteCode::LStore((opcode - 0x3f) as u16), 1),
+            0x43..=0x46 => (ByteCode::FStore((opcode - 0x43) as u16), 1),
+            0x47..=0x4a => (ByteCode::DStore((opcode - 0x47) as u16), 1),
+            0x4b..=0x4e => (ByteCode::AStore((opcode - 0x4b) as u16), 1),
+            0x4f => (ByteCode::IAStore, 1),
+            0x50 => (ByteCode::LAStore, 1),
+            0x51 => (ByteCode::FAStore, 1),
+            0x52 => (ByteCode::DAStore, 1),
+            0x53 => (ByteCode::AAStore, 1),
+            0x54 => (ByteCode::BAStore, 1),
+            0x55 => (ByteCode::CAStore, 1),
+            0x56 => (ByteCode::SAStore, 1),
+            0x57 => (ByteCode::Pop, 1),
+            0x58 => (ByteCode::Pop2, 1),
+            0x59 => (ByteCode::Duplicate, 1),
+            0x5a => (ByteCode::DupX1, 1),
+            0x5b => (ByteCode::DupX2, 1),
+            0x5c => (ByteCode::Dup2, 1),
+            0x5d => (ByteCode::Dup2X1, 1),
+            0x5e => (ByteCode::Dup2X2, 1),
+            0x5f => (ByteCode::Swap, 1),
+            0x60 => (ByteCode::IAdd, 1),
+            0x61 => (ByteCode::LAdd, 1),
+            0x62 => (ByteCode::FAdd, 1),
+            0x63 => (ByteCode::DAdd, 1),
+            0x64 => (ByteCode::ISub, 1),
+            0x65 => (ByteCode::LSub, 1),
+            0x66 => (ByteCode::FSub, 1),
+            0x67 => (ByteCode::DSub, 1),
+            0x68 => (ByteCode::IMul, 1),
+            0x69 => (ByteCode::LMul, 1),
+            0x6a => (ByteCode::FMul, 1),
+            0x6b => (ByteCode::DMul, 1),
+            0x6c => (ByteCode::IDiv, 1),
+            0x6d => (ByteCode::LDiv, 1),
+            0x6e => (ByteCode::FDiv, 1),
+            0x6f => (ByteCode::DDiv, 1),
+            0x70 => (ByteCode::IRem, 1),
+            0x71 => (ByteCode::LRem, 1),
+            0x72 => (ByteCode::FRem, 1),
+            0x73 => (ByteCode::DRem, 1),
+            0x74 => (ByteCode::INeg, 1),
+            0x75 => (ByteCode::LNeg, 1),
+            0x76 => (ByteCode::FNeg, 1),
+            0x77 => (ByteCode::DNeg, 1),
+            0x78 => (ByteCode::IShl, 1),
+            0x79 => (ByteCode::LShl, 1),
+            0x7a => (ByteCode::IShr, 1),
+            0x7b => (ByteCode::LShr, 1),
+            0x7c => (ByteCode::IUShr, 1),
+            0x7d => (ByteCode::LUShr, 1),
+            0x7e => (ByteCode::IAnd, 1),
+            0x7f => (ByteCode::LAnd, 1),
+            0x80 => (ByteCode::IOr, 1),
+            0x81 => (ByteCode::LOr, 1),
+            0x82 => (ByteCode::IXor, 1),
+            0x83 => (ByteCode::LXor, 1),
+            0x84 => {
+                let index = file.read_u1()? as u16;
+                let constant = file.read_u1()? as i8 as i16;
+                (ByteCode::IInc(index, constant), 3)
+            }
+            0x85 => (ByteCode::I2L, 1),
+            0x86 => (ByteCode::I2F, 1),
+            0x87 => (ByteCode::I2D, 1),
+            0x88 => (ByteCode::L2i, 1),
+            0x89 => (ByteCode::L2F, 1),
+            0x8a => (ByteCode::L2D, 1),
+            0x8b => (ByteCode::F2I, 1),
+            0x8c => (ByteCode::F2L, 1),
+            0x8d => (ByteCode::F2D, 1),
+            0x8e => (ByteCode::D2I, 1),
+            0x8f => (ByteCode::D2L, 1),
+            0x90 => (ByteCode::D2F, 1),
+            0x91 => (ByteCode::I2B, 1),
+            0x92 => (ByteCode::I2C, 1),
+            0x93 => (ByteCode::I2S, 1),
             0x94 => (ByteCode::Lcmp, 1),
+            0x95 => (ByteCode::FCmpl, 1),
+            0x96 => (ByteCode::FCmpg, 1),
+            0x97 => (ByteCode::DCmpl, 1),
+            0x98 => (ByteCode::DCmpg, 1),
+            0x99 => (ByteCode::Ifeq(file.read_i16()?), 3),
+            0x9a => (ByteCode::Ifne(file.read_i16()?), 3),
+            0x9b => (ByteCode::Iflt(file.read_i16()?), 3),
+            0x9c => (ByteCode::Ifge(file.read_i16()?), 3),
+            0x9d => (ByteCode::Ifgt(file.read_i16()?), 3),
+            0x9e => (ByteCode::Ifle(file.read_i16()?), 3),
+            0x9f => (ByteCode::IfICmpEq(file.read_i16()?), 3),
+            0xa0 => (ByteCode::IfICmpNe(file.read_i16()?), 3),
+            0xa1 => (ByteCode::IfICmpLt(file.read_i16()?), 3),
+            0xa2 => (ByteCode::IfICmpGe(file.read_i16()?), 3),
+            0xa3 => (ByteCode::IfICmpGt(file.read_i16()?), 3),
+            0xa4 => (ByteCode::IfICmpLe(file.read_i16()?), 3),
+            0xa5 => (ByteCode::IfACmpEq(file.read_i16()?), 3),
+            0xa6 => (ByteCode::IfACmpNe(file.read_i16()?), 3),
+            0xa7 => (ByteCode::Goto(file.read_i16()?), 3),
+            0xa8 => (ByteCode::Jsr(file.read_i16()?), 3),
+            0xaa | 0xab => {
+                // the operands are padded to a 4 byte boundary
+                let padding = (4 - (pc + 1) % 4) % 4;
+                for _ in 0..padding {
+                    file.read_u1()?;
+                }
+                let default = read_i32(file)?;
+                if opcode == 0xaa {
+                    let low = read_i32(file)?;
+                    let high = read_i32(file)?;
+                    if high < low {
+                        return Err(anyhow!("tableswitch at {} with low > high", pc));
+                    }
+                    let offsets = (0..(high as i64 - low as i64 + 1))
+                        .map(|_| read_i32(file))
+                        .collect::<Result<Vec<_>>>()?;
+                    let len = 1 + padding + 12 + offsets.len() as u32 * 4;
+                    let switch = TableSwitch {
+                        default,
+                        low,
+                        high,
+                        offsets,
+                    };
+                    (ByteCode::TableSwitch(switch), len)
+                } else {
+                    let npairs = read_i32(file)?;
+                    if npairs < 0 {
+                        return Err(anyhow!("lookupswitch at {} with negative npairs", pc));
+                    }
+                    let pairs = (0..npairs)
+                        .map(|_| Ok((read_i32(file)?, read_i32(file)?)))
+                        .collect::<Result<Vec<_>>>()?;
+                    let len = 1 + padding + 8 + pairs.len() as u32 * 8;
+                    (ByteCode::LookupSwitch(LookupSwitch { default, pairs }), len)
+                }
+            }
             0xac => (ByteCode::IReturn, 1),
-            0xbb => (ByteCode::New(file.read_u2_to_u16()?), 3),
-            0xbd => (ByteCode::ANewArray(file.read_u2_to_u16()?), 3),
-            0x59 => (ByteCode::Duplicate, 1),
-            0xb7 => (ByteCode::InvokeSpecial(file.read_u2_to_u16()?), 3),
+            0xad => (ByteCode::LReturn, 1),
+            0xae => (ByteCode::FReturn, 1),
+            0xaf => (ByteCode::DReturn, 1),
+            0xb0 => (ByteCode::AReturn, 1),
+            0xb1 => (ByteCode::Return, 1),
+            0xb2 => (ByteCode::GetStatic(file.read_u2_to_u16()?), 3),
+            0xb3 => (ByteCode::PutStatic(file.read_u2_to_u16()?), 3),
+            0xb4 => (ByteCode::GetField(file.read_u2_to_u16()?), 3),
+            0xb5 => (ByteCode::PutField(file.read_u2_to_u16()?), 3),
             0xb6 => (ByteCode::InvokeVirtual(file.read_u2_to_u16()?), 3),
+            0xb7 => (ByteCode::InvokeSpecial(file.read_u2_to_u16()?), 3),
             0xb8 => (ByteCode::InvokeStatic(file.read_u2_to_u16()?), 3),
             0xb9 => {
                 let interface_index = file.read_u2_to_u16()?;
                 let count = file.read_u1()?;
-                assert_eq!(file.read_u1()?, 0);
+                file.read_u1()?; // always 0
                 (ByteCode::InvokeInterface(interface_index, count), 5)
             }
             0xba => {
                 let method_index = file.read_u2_to_u16()?;
-                assert_eq!(file.read_u1()?, 0);
-                assert_eq!(file.read_u1()?, 0);
+                file.read_u2_to_u16()?; // always 0
                 (ByteCode::InvokeDynamic(method_index), 5)
             }
-            0xb0 => (ByteCode::AReturn, 1),
-            0xb1 => (ByteCode::Return, 1),
-            0xad => (ByteCode::LReturn, 1),
-            0x1a..=0x1d => (ByteCode::ILoad(opcode - 0x1a), 1),
-            0x12 => (ByteCode::Ldc(file.read_u1()?), 2),
-            0xb3 => (ByteCode::PutStatic(file.read_u2_to_u16()?), 3),
-            0xb2 => (ByteCode::GetStatic(file.read_u2_to_u16()?), 3),
-            0x2a..=0x2d => (ByteCode::ALoad(opcode - 0x2a), 1),
-            0x19 => (ByteCode::ALoad(file.read_u1()?), 2),
-            0x1e..=0x21 => (ByteCode::Lload(opcode - 0x1e), 1),
-            0x16 => (ByteCode::Lload(file.read_u1()?), 2),
-            0x61 => (ByteCode::LAdd, 1),
-            0x88 => (ByteCode::L2i, 1),
-            0x85 => (ByteCode::I2L, 1),
-            0x4b..=0x4e => (ByteCode::AStore(opcode - 0x4b), 1),
-            0x37 => (ByteCode::LStore(file.read_u1()?), 2),
-            0x53 => (ByteCode::AAStore, 1),
+            0xbb => (ByteCode::New(file.read_u2_to_u16()?), 3),
+            0xbc => (ByteCode::NewArray(file.read_u1()?), 2),
+            0xbd => (ByteCode::ANewArray(file.read_u2_to_u16()?), 3),
+            0xbe => (ByteCode::ArrayLength, 1),
             0xbf => (ByteCode::Athrow, 1),
-            0x99 => (ByteCode::Ifeq(file.read_i16()?), 3),
-            0x9a => (ByteCode::Ifne(file.read_i16()?), 3),
-            0x9b => (ByteCode::Iflt(file.read_i16()?), 3),
-            0x9c => (ByteCode::Ifge(file.read_i16()?), 3),
-            0x9d => (ByteCode::Ifgt(file.read_i16()?), 3),
-            0x9e => (ByteCode::Ifle(file.read_i16()?), 3),
-            0xb5 => (ByteCode::PutField(file.read_u2_to_u16()?), 3),
-            0xb4 => (ByteCode::GetField(file.read_u2_to_u16()?), 3),
             0xc0 => (ByteCode::CheckCast(file.read_u2_to_u16()?), 3),
-            _ => (ByteCode::Generic(opcode), 1),
+            0xc1 => (ByteCode::InstanceOf(file.read_u2_to_u16()?), 3),
+            0xc2 => (ByteCode::MonitorEnter, 1),
+            0xc3 => (ByteCode::MonitorExit, 1),
+            0xc4 => {
+                let opcode = file.read_u1()?;
+                let index = file.read_u2_to_u16()?;
+                if opcode == 0x84 {
+                    (ByteCode::IInc(index, file.read_i16()?), 6)
+                } else {
+                    (Self::local_variable_instruction(opcode, index)?, 4)
+                }
+            }
+            0xc5 => {
+                let index = file.read_u2_to_u16()?;
+                let dimensions = file.read_u1()?;
+                (ByteCode::MultiANewArray(index, dimensions), 4)
+            }
+            0xc6 => (ByteCode::IfNull(file.read_i16()?), 3),
+            0xc7 => (ByteCode::IfNonNull(file.read_i16()?), 3),
+            0xc8 => (ByteCode::GotoW(read_i32(file)?), 5),
+            0xc9 => (ByteCode::JsrW(read_i32(file)?), 5),
+            0xca | 0xfe | 0xff => (ByteCode::Generic(opcode), 1),
+            _ => return Err(anyhow!("Invalid opcode 0x{:x} at {}", opcode, pc)),
         };
         Ok((code, len))
     }
 
+    /// Number of bytes of the shortest encoding of the instruction at `pc`
+    pub fn size(&self, pc: u32) -> u32 {
+        let mut file = FileWriter::new();
+        match self.encode(&mut file, pc, None) {
+            Ok(()) => file.position() as u32,
+            // only switches with too many cases can't be encoded, count them anyway
+            Err(_) => 1 + (4 - (pc + 1) % 4) % 4 + 12,
+        }
+    }
+
+    /// Writes the instruction at `pc` in `len` bytes. Loads, stores, `iinc` and `ret` have
+    /// a short (`iload_1`), a normal and a `wide` form, the one of that length is written,
+    /// so code keeps the layout it was read with.
+    pub fn write(&self, file: &mut FileWriter, pc: u32, len: u32) -> Result<()> {
+        let start = file.position();
+        self.encode(file, pc, Some(len))?;
+        let written = (file.position() - start) as u32;
+        if written != len {
+            return Err(anyhow!(
+                "{} at {} takes {} bytes, not {}",
+                self.to_string(),
+                pc,
+                written,
+                len
+            ));
+        }
+        Ok(())
+    }
+
+    /// Writes the instruction in `len` bytes or, if `None`, in its shortest form
+    fn encode(&self, file: &mut FileWriter, pc: u32, len: Option<u32>) -> Result<()> {
+        let simple = |file: &mut FileWriter, opcode: u8| file.write_u1(opcode);
+        let index = |file: &mut FileWriter, opcode: u8, index: u16| {
+            file.write_u1(opcode);
+            file.write_u2(index);
+        };
+        let branch = |file: &mut FileWriter, opcode: u8, offset: i16| {
+            file.write_u1(opcode);
+            file.write_i16(offset);
+        };
+        match self {
+            ByteCode::Nop => simple(file, 0x00),
+            ByteCode::AConstNull => simple(file, 0x01),
+            ByteCode::IConstn(n) => simple(file, (0x03 + *n) as u8),
+            ByteCode::LConstn(n) => simple(file, 0x09 + *n),
+            ByteCode::FConstn(n) => simple(file, 0x0b + *n),
+            ByteCode::DConstn(n) => simple(file, 0x0e + *n),
+            ByteCode::BiPush(n) => {
+                file.write_u1(0x10);
+                file.write_u1(*n as u8);
+            }
+            ByteCode::SiPush(n) => {
+                file.write_u1(0x11);
+                file.write_i16(*n);
+            }
+            ByteCode::Ldc(i) => {
+                file.write_u1(0x12);
+                file.write_u1(*i);
+            }
+            ByteCode::LdcW(i) => index(file, 0x13, *i),
+            ByteCode::Ldc2W(i) => index(file, 0x14, *i),
+            ByteCode::ILoad(i) => local_variable(file, 0x15, Some(0x1a), *i, len)?,
+            ByteCode::Lload(i) => local_variable(file, 0x16, Some(0x1e), *i, len)?,
+            ByteCode::FLoad(i) => local_variable(file, 0x17, Some(0x22), *i, len)?,
+            ByteCode::DLoad(i) => local_variable(file, 0x18, Some(0x26), *i, len)?,
+            ByteCode::ALoad(i) => local_variable(file, 0x19, Some(0x2a), *i, len)?,
+            ByteCode::IALoad => simple(file, 0x2e),
+            ByteCode::LALoad => simple(file, 0x2f),
+            ByteCode::FALoad => simple(file, 0x30),
+            ByteCode::DALoad => simple(file, 0x31),
+            ByteCode::AALoad => simple(file, 0x32),
+            ByteCode::BALoad => simple(file, 0x33),
+            ByteCode::CALoad => simple(file, 0x34),
+            ByteCode::SALoad => simple(file, 0x35),
+            ByteCode::IStore(i) => local_variable(file, 0x36, Some(0x3b), *i, len)?,
+            ByteCode::LStore(i) => local_variable(file, 0x37, Some(0x3f), *i, len)?,
+            ByteCode::FStore(i) => local_variable(file, 0x38, Some(0x43), *i, len)?,
+            ByteCode::DStore(i) => local_variable(file, 0x39, Some(0x47), *i, len)?,
+            ByteCode::AStore(i) => local_variable(file, 0x3a, Some(0x4b), *i, len)?,
+            ByteCode::IAStore => simple(file, 0x4f),
+            ByteCode::LAStore => simple(file, 0x50),
+            ByteCode::FAStore => simple(file, 0x51),
+            ByteCode::DAStore => simple(file, 0x52),
+            ByteCode::AAStore => simple(file, 0x53),
+            ByteCode::BAStore => simple(file, 0x54),
+            ByteCode::CAStore => simple(file, 0x55),
+            ByteCode::SAStore => simple(file, 0x56),
+            ByteCode::Pop => simple(file, 0x57),
+            ByteCode::Pop2 => simple(file, 0x58),
+            ByteCode::Duplicate => simple(file, 0x59),
+            ByteCode::DupX1 => simple(file, 0x5a),
+            ByteCode::DupX2 => simple(file, 0x5b),
+            ByteCode::Dup2 => simple(file, 0x5c),
+            ByteCode::Dup2X1 => simple(file, 0x5d),
+            ByteCode::Dup2X2 => simple(file, 0x5e),
+            ByteCode::Swap => simple(file, 0x5f),
+            ByteCode::IAdd => simple(file, 0x60),
+            ByteCode::LAdd => simple(file, 0x61),
+            ByteCode::FAdd => simple(file, 0x62),
+            ByteCode::DAdd => simple(file, 0x63),
+            ByteCode::ISub => simple(file, 0x64),
+            ByteCode::LSub => simple(file, 0x65),
+            ByteCode::FSub => simple(file, 0x66),
+            ByteCode::DSub => simple(file, 0x67),
+            ByteCode::IMul => simple(file, 0x68),
+            ByteCode::LMul => simple(file, 0x69),
+            ByteCode::FMul => simple(file, 0x6a),
+            ByteCode::DMul => simple(file, 0x6b),
+            ByteCode::IDiv => simple(file, 0x6c),
+            ByteCode::LDiv => simple(file, 0x6d),
+            ByteCode::FDiv => simple(file, 0x6e),
+            ByteCode::DDiv => simple(file, 0x6f),
+            ByteCode::IRem => simple(file, 0x70),
+            ByteCode::LRem => simple(file, 0x71),
+            ByteCode::FRem => simple(file, 0x72),
+            ByteCode::DRem => simple(file, 0x73),
+            ByteCode::INeg => simple(file, 0x74),
+            ByteCode::LNeg => simple(file, 0x75),
+            ByteCode::FNeg => simple(file, 0x76),
+            ByteCode::DNeg => simple(file, 0x77),
+            ByteCode::IShl => simple(file, 0x78),
+            ByteCode::LShl => simple(file, 0x79),
+            ByteCode::IShr => simple(file, 0x7a),
+            ByteCode::LShr => simple(file, 0x7b),
+            ByteCode::IUShr => simple(file, 0x7c),
+            ByteCode::LUShr => simple(file, 0x7d),
+            ByteCode::IAnd => simple(file, 0x7e),
+            ByteCode::LAnd => simple(file, 0x7f),
+            ByteCode::IOr => simple(file, 0x80),
+            ByteCode::LOr => simple(file, 0x81),
+            ByteCode::IXor => simple(file, 0x82),
+            ByteCode::LXor => simple(file, 0x83),
+            ByteCode::IInc(i, delta) => {
+                let fits = *i <= u8::MAX as u16 && i8::try_from(*delta).is_ok();
+                if fits && len != Some(6) {
+                    file.write_u1(0x84);
+                    file.write_u1(*i as u8);
+                    file.write_u1(*delta as i8 as u8);
+                } else {
+                    file.write_u1(0xc4);
+                    file.write_u1(0x84);
+                    file.write_u2(*i);
+                    file.write_i16(*delta);
+                }
+            }
+            ByteCode::I2L => simple(file, 0x85),
+            ByteCode::I2F => simple(file, 0x86),
+            ByteCode::I2D => simple(file, 0x87),
+            ByteCode::L2i => simple(file, 0x88),
+            ByteCode::L2F => simple(file, 0x89),
+            ByteCode::L2D => simple(file, 0x8a),
+            ByteCode::F2I => simple(file, 0x8b),
+            ByteCode::F2L => simple(file, 0x8c),
+            ByteCode::F2D => simple(file, 0x8d),
+            ByteCode::D2I => simple(file, 0x8e),
+            ByteCode::D2L => simple(file, 0x8f),
+            ByteCode::D2F => simple(file, 0x90),
+            ByteCode::I2B => simple(file, 0x91),
+            ByteCode::I2C => simple(file, 0x92),
+            ByteCode::I2S => simple(file, 0x93),
+            ByteCode::Lcmp => simple(file, 0x94),
+            ByteCode::FCmpl => simple(file, 0x95),
+            ByteCode::FCmpg => simple(file, 0x96),
+            ByteCode::DCmpl => simple(file, 0x97),
+            ByteCode::DCmpg => simple(file, 0x98),
+            ByteCode::Ifeq(o) => branch(file, 0x99, *o),
+            ByteCode::Ifne(o) => branch(file, 0x9a, *o),
+            ByteCode::Iflt(o) => branch(file, 0x9b, *o),
+            ByteCode::Ifge(o) => branch(file, 0x9c, *o),
+            ByteCode::Ifgt(o) => branch(file, 0x9d, *o),
+            ByteCode::Ifle(o) => branch(file, 0x9e, *o),
+            ByteCode::IfICmpEq(o) => branch(file, 0x9f, *o),
+            ByteCode::IfICmpNe(o) => branch(file, 0xa0, *o),
+            ByteCode::IfICmpLt(o) => branch(file, 0xa1, *o),
+            ByteCode::IfICmpGe(o) => branch(file, 0xa2, *o),
+            ByteCode::IfICmpGt(o) => branch(file, 0xa3, *o),
+            ByteCode::IfICmpLe(o) => branch(file, 0xa4, *o),
+            ByteCode::IfACmpEq(o) => branch(file, 0xa5, *o),
+            ByteCode::IfACmpNe(o) => branch(file, 0xa6, *o),
+            ByteCode::Goto(o) => branch(file, 0xa7, *o),
+            ByteCode::Jsr(o) => branch(file, 0xa8, *o),
+            ByteCode::Ret(i) => local_variable(file, 0xa9, None, *i, len)?,
+            ByteCode::TableSwitch(switch) => {
+                file.write_u1(0xaa);
+                pad(file, pc);
+                file.write_i32(switch.default);
+                file.write_i32(switch.low);
+                file.write_i32(switch.high);
+                for offset in &switch.offsets {
+                    file.write_i32(*offset);
+                }
+            }
+            ByteCode::LookupSwitch(switch) => {
+                file.write_u1(0xab);
+                pad(file, pc);
+                file.write_i32(switch.default);
+                file.write_i32(switch.pairs.len() as i32);
+                for (key, offset) in &switch.pairs {
+                    file.write_i32(*key);
+                    file.write_i32(*offset);
+                }
+            }
+            ByteCode::IReturn => simple(file, 0xac),
+            ByteCode::LReturn => simple(file, 0xad),
+            ByteCode::FReturn => simple(file, 0xae),
+            ByteCode::DReturn => simple(file, 0xaf),
+            ByteCode::AReturn => simple(file, 0xb0),
+            ByteCode::Return => simple(file, 0xb1),
+            ByteCode::GetStatic(i) => index(file, 0xb2, *i),
+            ByteCode::PutStatic(i) => index(file, 0xb3, *i),
+            ByteCode::GetField(i) => index(file, 0xb4, *i),
+            ByteCode::PutField(i) => index(file, 0xb5, *i),
+            ByteCode::InvokeVirtual(i) => index(file, 0xb6, *i),
+            ByteCode::InvokeSpecial(i) => index(file, 0xb7, *i),
+            ByteCode::InvokeStatic(i) => index(file, 0xb8, *i),
+            ByteCode::InvokeInterface(i, count) => {
+                index(file, 0xb9, *i);
+                file.write_u1(*count);
+                file.write_u1(0);
+            }
+            ByteCode::InvokeDynamic(i) => {
+                index(file, 0xba, *i);
+                file.write_u2(0);
+            }
+            ByteCode::New(i) => index(file, 0xbb, *i),
+            ByteCode::NewArray(atype) => {
+                file.write_u1(0xbc);
+                file.write_u1(*atype);
+            }
+            ByteCode::ANewArray(i) => index(file, 0xbd, *i),
+            ByteCode::ArrayLength => simple(file, 0xbe),
+            ByteCode::Athrow => simple(file, 0xbf),
+            ByteCode::CheckCast(i) => index(file, 0xc0, *i),
+            ByteCode::InstanceOf(i) => index(file, 0xc1, *i),
+            ByteCode::MonitorEnter => simple(file, 0xc2),
+            ByteCode::MonitorExit => simple(file, 0xc3),
+            ByteCode::MultiANewArray(i, dimensions) => {
+                index(file, 0xc5, *i);
+                file.write_u1(*dimensions);
+            }
+            ByteCode::IfNull(o) => branch(file, 0xc6, *o),
+            ByteCode::IfNonNull(o) => branch(file, 0xc7, *o),
+            ByteCode::GotoW(o) => {
+                file.write_u1(0xc8);
+                file.write_i32(*o);
+            }
+            ByteCode::JsrW(o) => {
+                file.write_u1(0xc9);
+                file.write_i32(*o);
+            }
+            ByteCode::Generic(opcode) => simple(file, *opcode),
+        }
+        Ok(())
+    }
+
+    /// The loads, stores and `ret` that take a local variable index (possibly `wide`)
+    fn local_variable_instruction(opcode: u8, index: u16) -> Result<ByteCode> {
+        let code = match opcode {
+            0x15 => ByteCode::ILoad(index),
+            0x16 => ByteCode::Lload(index),
+            0x17 => ByteCode::FLoad(index),
+            0x18 => ByteCode::DLoad(index),
+            0x19 => ByteCode::ALoad(index),
+            0x36 => ByteCode::IStore(index),
+            0x37 => ByteCode::LStore(index),
+            0x38 => ByteCode::FStore(index),
+            0x39 => ByteCode::DStore(index),
+            0x3a => ByteCode::AStore(index),
+            0xa9 => ByteCode::Ret(index),
+            _ => return Err(anyhow!("Invalid wide opcode 0x{:x}", opcode)),
+        };
+        Ok(code)
+    }
+
+    /// Absolute pcs this instruction can jump to (not including the next instruction)
+    pub fn branch_targets(&self, pc: u32) -> Vec<u32> {
+        let target = |offset: i32| (pc as i64 + offset as i64) as u32;
+        match self {
+            ByteCode::Ifeq(offset)
+            | ByteCode::Ifne(offset)
+            | ByteCode::Iflt(offset)
+            | ByteCode::Ifge(offset)
+            | ByteCode::Ifgt(offset)
+            | ByteCode::Ifle(offset)
+            | ByteCode::IfICmpEq(offset)
+            | ByteCode::IfICmpNe(offset)
+            | ByteCode::IfICmpLt(offset)
+            | ByteCode::IfICmpGe(offset)
+            | ByteCode::IfICmpGt(offset)
+            | ByteCode::IfICmpLe(offset)
+            | ByteCode::IfACmpEq(offset)
+            | ByteCode::IfACmpNe(offset)
+            | ByteCode::IfNull(offset)
+            | ByteCode::IfNonNull(offset)
+            | ByteCode::Goto(offset)
+            | ByteCode::Jsr(offset) => vec![target(*offset as i32)],
+            ByteCode::GotoW(offset) | ByteCode::JsrW(offset) => vec![target(*offset)],
+            ByteCode::TableSwitch(switch) => std::iter::once(switch.default)
+                .chain(switch.offsets.iter().copied())
+                .map(target)
+                .collect(),
+            ByteCode::LookupSwitch(switch) => std::iter::once(switch.default)
+                .chain(switch.pairs.iter().map(|(_, offset)| *offset))
+                .map(target)
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Passes the constant pool index of the instruction, if it has one, to `f`, which may
+    /// change it. The index of `ldc` must stay below 256.
+    pub(crate) fn constant_indices(&mut self, f: &mut dyn FnMut(&mut u16)) {
+        match self {
+            ByteCode::Ldc(index) => {
+                let mut wide = *index as u16;
+                f(&mut wide);
+                debug_assert!(wide <= u8::MAX as u16, "ldc #{} needs ldc_w", wide);
+                *index = wide as u8;
+            }
+            ByteCode::LdcW(index)
+            | ByteCode::Ldc2W(index)
+            | ByteCode::GetStatic(index)
+            | ByteCode::PutStatic(index)
+            | ByteCode::GetField(index)
+            | ByteCode::PutField(index)
+            | ByteCode::InvokeVirtual(index)
+            | ByteCode::InvokeSpecial(index)
+            | ByteCode::InvokeStatic(index)
+            | ByteCode::InvokeInterface(index, _)
+            | ByteCode::InvokeDynamic(index)
+            | ByteCode::New(index)
+            | ByteCode::ANewArray(index)
+            | ByteCode::CheckCast(index)
+            | ByteCode::InstanceOf(index)
+            | ByteCode::MultiANewArray(index, _) => f(index),
+            _ => (),
+        }
+    }
+
+    /// Whether execution can continue with the next instruction
+    pub fn falls_through(&self) -> bool {
+        !matches!(
+            self,
+            ByteCode::Goto(_)
+                | ByteCode::GotoW(_)
+                | ByteCode::Ret(_)
+                | ByteCode::TableSwitch(_)
+                | ByteCode::LookupSwitch(_)
+                | ByteCode::IReturn
+                | ByteCode::LReturn
+                | ByteCode::FReturn
+                | ByteCode::DReturn
+                | ByteCode::AReturn
+                | ByteCode::Return
+                | ByteCode::Athrow
+        )
+    }
+
+    /// The mnemonic of the instruction as in the JVM specification, e.g. `iconst_1` or
+    /// `invokespecial`. Loads and stores have the form with an index operand, `iload` and
+    /// not `iload_0`.
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            ByteCode::Nop => "nop",
+            ByteCode::AConstNull => "aconst_null",
+            ByteCode::IConstn(-1) => "iconst_m1",
+            ByteCode::IConstn(0) => "iconst_0",
+            ByteCode::IConstn(1) => "iconst_1",
+            ByteCode::IConstn(2) => "iconst_2",
+            ByteCode::IConstn(3) => "iconst_3",
+            ByteCode::IConstn(4) => "iconst_4",
+            ByteCode::IConstn(5) => "iconst_5",
+            ByteCode::LConstn(0) => "lconst_0",
+            ByteCode::LConstn(1) => "lconst_1",
+            ByteCode::FConstn(0) => "fconst_0",
+            ByteCode::FConstn(1) => "fconst_1",
+            ByteCode::FConstn(2) => "fconst_2",
+            ByteCode::DConstn(0) => "dconst_0",
+            ByteCode::DConstn(1) => "dconst_1",
+            ByteCode::BiPush(..) => "bipush",
+            ByteCode::SiPush(..) => "sipush",
+            ByteCode::Ldc(..) => "ldc",
+            ByteCode::LdcW(..) => "ldc_w",
+            ByteCode::Ldc2W(..) => "ldc2_w",
+            ByteCode::ILoad(..) => "iload",
+            ByteCode::Lload(..) => "lload",
+            ByteCode::FLoad(..) => "fload",
+            ByteCode::DLoad(..) => "dload",
+            ByteCode::ALoad(..) => "aload",
+            ByteCode::IALoad => "iaload",
+            ByteCode::LALoad => "laload",
+            ByteCode::FALoad => "faload",
+            ByteCode::DALoad => "daload",
+            ByteCode::AALoad => "aaload",
+            ByteCode::BALoad => "baload",
+            ByteCode::CALoad => "caload",
+            ByteCode::SALoad => "saload",
+            ByteCode::IStore(..) => "istore",
+            ByteCode::LStore(..) => "lstore",
+            ByteCode::FStore(..) => "fstore",
+            ByteCode::DStore(..) => "dstore",
+            ByteCode::AStore(..) => "astore",
+            ByteCode::IAStore => "iastore",
+            ByteCode::LAStore => "lastore",
+            ByteCode::FAStore => "fastore",
+            ByteCode::DAStore => "dastore",
+            ByteCode::AAStore => "aastore",
+            ByteCode::BAStore => "bastore",
+            ByteCode::CAStore => "castore",
+            ByteCode::SAStore => "sastore",
+            ByteCode::Pop => "pop",
+            ByteCode::Pop2 => "pop2",
+            ByteCode::Duplicate => "dup",
+            ByteCode::DupX1 => "dup_x1",
+            ByteCode::DupX2 => "dup_x2",
+            ByteCode::Dup2 => "dup2",
+            ByteCode::Dup2X1 => "dup2_x1",
+            ByteCode::Dup2X2 => "dup2_x2",
+            ByteCode::Swap => "swap",
+            ByteCode::IAdd => "iadd",
+            ByteCode::LAdd => "ladd",
+            ByteCode::FAdd => "fadd",
+            ByteCode::DAdd => "dadd",
+            ByteCode::ISub => "isub",
+            ByteCode::LSub => "lsub",
+            ByteCode::FSub => "fsub",
+            ByteCode::DSub => "dsub",
+            ByteCode::IMul => "imul",
+            ByteCode::LMul => "lmul",
+            ByteCode::FMul => "fmul",
+            ByteCode::DMul => "dmul",
+            ByteCode::IDiv => "idiv",
+            ByteCode::LDiv => "ldiv",
+            ByteCode::FDiv => "fdiv",
+            ByteCode::DDiv => "ddiv",
+            ByteCode::IRem => "irem",
+            ByteCode::LRem => "lrem",
+            ByteCode::FRem => "frem",
+            ByteCode::DRem => "drem",
+            ByteCode::INeg => "ineg",
+            ByteCode::LNeg => "lneg",
+            ByteCode::FNeg => "fneg",
+            ByteCode::DNeg => "dneg",
+            ByteCode::IShl => "ishl",
+            ByteCode::LShl => "lshl",
+            ByteCode::IShr => "ishr",
+            ByteCode::LShr => "lshr",
+            ByteCode::IUShr => "iushr",
+            ByteCode::LUShr => "lushr",
+            ByteCode::IAnd => "iand",
+            ByteCode::LAnd => "land",
+            ByteCode::IOr => "ior",
+            ByteCode::LOr => "lor",
+            ByteCode::IXor => "ixor",
+            ByteCode::LXor => "lxor",
+            ByteCode::IInc(..) => "iinc",
+            ByteCode::I2L => "i2l",
+            ByteCode::I2F => "i2f",
+            ByteCode::I2D => "i2d",
+            ByteCode::L2i => "l2i",
+            ByteCode::L2F => "l2f",
+            ByteCode::L2D => "l2d",
+            ByteCode::F2I => "f2i",
+            ByteCode::F2L => "f2l",
+            ByteCode::F2D => "f2d",
+            ByteCode::D2I => "d2i",
+            ByteCode::D2L => "d2l",
+            ByteCode::D2F => "d2f",
+            ByteCode::I2B => "i2b",
+            ByteCode::I2C => "i2c",
+            ByteCode::I2S => "i2s",
+            ByteCode::Lcmp => "lcmp",
+            ByteCode::FCmpl => "fcmpl",
+            ByteCode::FCmpg => "fcmpg",
+            ByteCode::DCmpl => "dcmpl",
+            ByteCode::DCmpg => "dcmpg",
+            ByteCode::Ifeq(..) => "ifeq",
+            ByteCode::Ifne(..) => "ifne",
+            ByteCode::Iflt(..) => "iflt",
+            ByteCode::Ifge(..) => "ifge",
+            ByteCode::Ifgt(..) => "ifgt",
+            ByteCode::Ifle(..) => "ifle",
+            ByteCode::IfICmpEq(..) => "if_icmpeq",
+            ByteCode::IfICmpNe(..) => "if_icmpne",
+            ByteCode::IfICmpLt(..) => "if_icmplt",
+            ByteCode::IfICmpGe(..) => "if_icmpge",
+            ByteCode::IfICmpGt(..) => "if_icmpgt",
+            ByteCode::IfICmpLe(..) => "if_icmple",
+            ByteCode::IfACmpEq(..) => "if_acmpeq",
+            ByteCode::IfACmpNe(..) => "if_acmpne",
+            ByteCode::Goto(..) => "goto",
+            ByteCode::Jsr(..) => "jsr",
+            ByteCode::Ret(..) => "ret",
+            ByteCode::TableSwitch(..) => "tableswitch",
+            ByteCode::LookupSwitch(..) => "lookupswitch",
+            ByteCode::IReturn => "ireturn",
+            ByteCode::LReturn => "lreturn",
+            ByteCode::FReturn => "freturn",
+            ByteCode::DReturn => "dreturn",
+            ByteCode::AReturn => "areturn",
+            ByteCode::Return => "return",
+            ByteCode::GetStatic(..) => "getstatic",
+            ByteCode::PutStatic(..) => "putstatic",
+            ByteCode::GetField(..) => "getfield",
+            ByteCode::PutField(..) => "putfield",
+            ByteCode::InvokeVirtual(..) => "invokevirtual",
+            ByteCode::InvokeSpecial(..) => "invokespecial",
+            ByteCode::InvokeStatic(..) => "invokestatic",
+            ByteCode::InvokeInterface(..) => "invokeinterface",
+            ByteCode::InvokeDynamic(..) => "invokedynamic",
+            ByteCode::New(..) => "new",
+            ByteCode::NewArray(..) => "newarray",
+            ByteCode::ANewArray(..) => "anewarray",
+            ByteCode::ArrayLength => "arraylength",
+            ByteCode::Athrow => "athrow",
+            ByteCode::CheckCast(..) => "checkcast",
+            ByteCode::InstanceOf(..) => "instanceof",
+            ByteCode::MonitorEnter => "monitorenter",
+            ByteCode::MonitorExit => "monitorexit",
+            ByteCode::MultiANewArray(..) => "multianewarray",
+            ByteCode::IfNull(..) => "ifnull",
+            ByteCode::IfNonNull(..) => "ifnonnull",
+            ByteCode::GotoW(..) => "goto_w",
+            ByteCode::JsrW(..) => "jsr_w",
+            // constants without an instruction, never parsed
+            ByteCode::IConstn(_) => "iconst",
+            ByteCode::LConstn(_) => "lconst",
+            ByteCode::FConstn(_) => "fconst",
+            ByteCode::DConstn(_) => "dconst",
+            ByteCode::Generic(0xca) => "breakpoint",
+            ByteCode::Generic(0xfe) => "impdep1",
+            ByteCode::Generic(_) => "impdep2",
+        }
+    }
+
+    #[allow(clippy::inherent_to_string)]
     pub fn to_string(&self) -> String {
         match self {
-            ByteCode::IConstn(u8) => format!("IConst({})", u8),
+            ByteCode::IConstn(i8) => format!("IConst({})", i8),
             ByteCode::LConstn(u8) => format!("LConst({})", u8),
-            ByteCode::Lcmp => "Lcmp".to_string(),
-            ByteCode::IReturn => "IReturn".to_string(),
             ByteCode::New(u16) => format!("New(0x{:x?})", u16),
             ByteCode::ANewArray(u16) => format!("ANewArray(0x{:x?})", u16),
-            ByteCode::Duplicate => "Duplicate".to_string(),
             ByteCode::InvokeSpecial(u16) => format!("InvokeSpecial(0x{:x?})", u16),
             ByteCode::InvokeVirtual(u16) => format!("InvokeVirtual(0x{:x?})", u16),
             ByteCode::InvokeStatic(u16) => format!("InvokeStatic(0x{:x?})", u16),
@@ -119,29 +952,63 @@ impl ByteCode {
             ByteCode::AReturn => "Reference Return (areturn)".to_string(),
             ByteCode::Return => "Return void (return)".to_string(),
             ByteCode::LReturn => "Long Return (lreturn)".to_string(),
-            ByteCode::Ldc(u8) => format!("Ldc({})", u8),
-            ByteCode::ALoad(u8) => format!("ALoad({})", u8),
-            ByteCode::Lload(u8) => format!("Lload({})", u8),
-            ByteCode::ILoad(u8) => format!("ILoad({})", u8),
-            ByteCode::AStore(u8) => format!("AStore({})", u8),
-            ByteCode::LStore(u8) => format!("LStore({})", u8),
-            ByteCode::AAStore => "AAStore".to_string(),
             ByteCode::PutStatic(u16) => format!("PutStatic(0x{:x?})", u16),
             ByteCode::GetStatic(u16) => format!("GetStatic(0x{:x?})", u16),
-            ByteCode::LAdd => "LAdd".to_string(),
-            ByteCode::L2i => "L2i".to_string(),
-            ByteCode::I2L => "I2L".to_string(),
-            ByteCode::Athrow => "Athrow".to_string(),
-            ByteCode::Ifeq(i16) => format!("Ifeq({})", i16),
-            ByteCode::Ifne(i16) => format!("Ifne({})", i16),
-            ByteCode::Iflt(i16) => format!("Iflt({})", i16),
-            ByteCode::Ifge(i16) => format!("Ifge({})", i16),
-            ByteCode::Ifgt(i16) => format!("Ifgt({})", i16),
-            ByteCode::Ifle(i16) => format!("Ifle({})", i16),
             ByteCode::PutField(u16) => format!("PutField(0x{:x?})", u16),
             ByteCode::GetField(u16) => format!("GetField(0x{:x?})", u16),
             ByteCode::CheckCast(u16) => format!("CheckCast(0x{:x?})", u16),
+            ByteCode::InstanceOf(u16) => format!("InstanceOf(0x{:x?})", u16),
             ByteCode::Generic(u8) => format!("Generic(0x{:x?})", u8),
+            _ => format!("{:?}", self),
         }
     }
 }
+
+/// A load, store or `ret` in `len` bytes: 1 for the short form (`short` plus the index),
+/// 2 for the index in a byte and 4 for `wide`. `None` takes the shortest.
+fn local_variable(
+    file: &mut FileWriter,
+    opcode: u8,
+    short: Option<u8>,
+    index: u16,
+    len: Option<u32>,
+) -> Result<()> {
+    let short = short.filter(|_| index <= 3);
+    let len = len.unwrap_or(match (short, index) {
+        (Some(_), _) => 1,
+        (None, 0..=255) => 2,
+        _ => 4,
+    });
+    match (len, short) {
+        (1, Some(short)) => file.write_u1(short + index as u8),
+        (2, _) if index <= u8::MAX as u16 => {
+            file.write_u1(opcode);
+            file.write_u1(index as u8);
+        }
+        (4, _) => {
+            file.write_u1(0xc4);
+            file.write_u1(opcode);
+            file.write_u2(index);
+        }
+        _ => {
+            return Err(anyhow!(
+                "No {} byte form of opcode 0x{:x} {}",
+                len,
+                opcode,
+                index
+            ))
+        }
+    }
+    Ok(())
+}
+
+/// The operands of the switch instructions start at a multiple of 4
+fn pad(file: &mut FileWriter, pc: u32) {
+    for _ in 0..(4 - (pc + 1) % 4) % 4 {
+        file.write_u1(0);
+    }
+}
+
+fn read_i32(file: &mut FileReader) -> Result<i32> {
+    Ok(i32::from_be_bytes(file.read_u4()?))
+}
@@ -0,0 +1,199 @@
+//! Control flow graph of the bytecode of a method
+
+use super::attributes::CodeAttribute;
+use super::constant_pool::ConstantPool;
+use anyhow::{anyhow, Result};
+use std::collections::BTreeSet;
+
+#[derive(Debug)]
+pub struct ControlFlowGraph {
+    /// Blocks sorted by pc, the entry block is always the first one
+    pub blocks: Vec<BasicBlock>,
+}
+
+/// A sequence of instructions that is only entered at the first one
+/// and only left after the last one (or by an exception)
+#[derive(Debug)]
+pub struct BasicBlock {
+    pub start_pc: u32,
+    /// pc after the last instruction of the block
+    pub end_pc: u32,
+    /// pcs of the instructions in the block
+    pub instructions: Vec<u32>,
+    pub successors: Vec<Edge>,
+    pub predecessors: Vec<Edge>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Edge {
+    /// index of the other block in `ControlFlowGraph::blocks`
+    pub block: usize,
+    pub kind: EdgeKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EdgeKind {
+    /// fall through, jump or switch
+    Normal,
+    /// to an exception handler covering the block, with the caught class
+    /// (`None` for `finally`, which catches everything)
+    Exceptional(Option<String>),
+}
+
+impl ControlFlowGraph {
+    pub fn build(code: &CodeAttribute, cp: &ConstantPool) -> Result<ControlFlowGraph> {
+        let instructions = code.instructions().collect::<Vec<_>>();
+        let is_instruction = |pc: u32| instructions.binary_search_by_key(&pc, |i| i.0).is_ok();
+
+        // blocks start at the entry, jump targets, after jumps and at the borders
+        // of the ranges covered by exception handlers
+        let mut leaders = BTreeSet::new();
+        leaders.insert(0);
+        for (i, (pc, instruction)) in instructions.iter().enumerate() {
+            let targets = instruction.branch_targets(*pc);
+            if !targets.is_empty() || !instruction.falls_through() {
+                if let Some((next, _)) = instructions.get(i + 1) {
+                    leaders.insert(*next);
+                }
+            }
+            for target in targets {
+                if !is_instruction(target) {
+                    return Err(anyhow!("Jump at {} to invalid pc {}", pc, target));
+                }
+                leaders.insert(target);
+            }
+        }
+        // only end_pc may be the code length, the pc after the last instruction
+        for entry in code.exception_table() {
+            let (start, end) = (entry.start_pc as u32, entry.end_pc as u32);
+            if !is_instruction(start) || start >= end {
+                return Err(anyhow!(
+                    "Exception table entry with invalid start_pc {}",
+                    start
+                ));
+            }
+            if !is_instruction(end) && end != code.code_length() {
+                return Err(anyhow!("Exception table entry with invalid end_pc {}", end));
+            }
+            if !is_instruction(entry.handler_pc as u32) {
+                return Err(anyhow!(
+                    "Exception table entry with invalid handler_pc {}",
+                    entry.handler_pc
+                ));
+            }
+            leaders.extend([start, end, entry.handler_pc as u32]);
+        }
+
+        let mut blocks: Vec<BasicBlock> = Vec::new();
+        for (i, (pc, _)) in instructions.iter().enumerate() {
+            if leaders.contains(pc) {
+                blocks.push(BasicBlock {
+                    start_pc: *pc,
+                    end_pc: *pc,
+                    instructions: Vec::new(),
+                    successors: Vec::new(),
+                    predecessors: Vec::new(),
+                });
+            }
+            let block = blocks.last_mut().unwrap();
+            block.instructions.push(*pc);
+            block.end_pc = match instructions.get(i + 1) {
+                Some((next, _)) => *next,
+                None => code.code_length(),
+            };
+        }
+
+        let mut cfg = ControlFlowGraph { blocks };
+        for from in 0..cfg.blocks.len() {
+            let last_pc = *cfg.blocks[from].instructions.last().unwrap();
+            let (_, last) = instructions.iter().find(|(pc, _)| *pc == last_pc).unwrap();
+            for target in last.branch_targets(last_pc) {
+                let to = cfg
+                    .block_index(target)
+                    .ok_or_else(|| anyhow!("Jump at {} to invalid pc {}", last_pc, target))?;
+                cfg.add_edge(from, to, EdgeKind::Normal);
+            }
+            if last.falls_through() && from + 1 < cfg.blocks.len() {
+                cfg.add_edge(from, from + 1, EdgeKind::Normal);
+            }
+        }
+        for entry in code.exception_table() {
+            let catch_type = match entry.catch_type {
+                0 => None,
+                index => Some(cp.class_name(index)?.to_string()),
+            };
+            let handler = cfg.block_index(entry.handler_pc as u32).ok_or_else(|| {
+                anyhow!(
+                    "Exception table entry with invalid handler_pc {}",
+                    entry.handler_pc
+                )
+            })?;
+            let range = entry.start_pc as u32..entry.end_pc as u32;
+            for from in 0..cfg.blocks.len() {
+                if range.contains(&cfg.blocks[from].start_pc) {
+                    cfg.add_edge(from, handler, EdgeKind::Exceptional(catch_type.clone()));
+                }
+            }
+        }
+        Ok(cfg)
+    }
+
+    /// Index of the block containing the instruction at `pc`
+    pub fn block_index(&self, pc: u32) -> Option<usize> {
+        self.blocks
+            .iter()
+            .position(|b| b.start_pc <= pc && pc < b.end_pc)
+    }
+
+    fn add_edge(&mut self, from: usize, to: usize, kind: EdgeKind) {
+        let edge = Edge {
+            block: to,
+            kind: kind.clone(),
+        };
+        // a switch can jump to the same block for several cases
+        if self.blocks[from].successors.contains(&edge) {
+            return;
+        }
+        self.blocks[from].successors.push(edge);
+        self.blocks[to]
+            .predecessors
+            .push(Edge { block: from, kind });
+    }
+
+    /// The graph in the Graphviz DOT format, each node lists the instructions of its block.
+    /// Exceptional edges are dashed and labelled with the caught class.
+    pub fn to_dot(&self, name: &str, code: &CodeAttribute) -> String {
+        let mut out = String::new();
+        out.push_str(&format!("digraph \"{}\" {{\n", escape(name)));
+        out.push_str("  node [shape=box, fontname=\"monospace\"];\n");
+        let instructions = code.instructions().collect::<Vec<_>>();
+        for (i, block) in self.blocks.iter().enumerate() {
+            let mut label = String::new();
+            for (pc, instruction) in &instructions {
+                if block.instructions.contains(pc) {
+                    label.push_str(&format!("{}: {}\\l", pc, escape(&instruction.to_string())));
+                }
+            }
+            out.push_str(&format!("  b{} [label=\"{}\"];\n", i, label));
+        }
+        for (i, block) in self.blocks.iter().enumerate() {
+            for edge in &block.successors {
+                match &edge.kind {
+                    EdgeKind::Normal => out.push_str(&format!("  b{} -> b{};\n", i, edge.block)),
+                    EdgeKind::Exceptional(catch_type) => out.push_str(&format!(
+                        "  b{} -> b{} [style=dashed, label=\"{}\"];\n",
+                        i,
+                        edge.block,
+                        escape(catch_type.as_deref().unwrap_or("any"))
+                    )),
+                }
+            }
+        }
+        out.push_str("}\n");
+        out
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
@@ -0,0 +1,200 @@
+//! Finds and loads classes by their binary name, like the `-classpath` of the JVM.
+//!
+//! Entries are searched in the order they were added: directories with class files,
+//! jar files and the classes of a JDK, from its `lib/modules` image or its `jmods`.
+//! Loaded classes are cached.
+
+use super::frames::ClassHierarchy;
+use super::jimage::JImage;
+use super::{read_class_bytes, read_class_file, ClassFile};
+use anyhow::{anyhow, Result};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use zip::ZipArchive;
+
+const OBJECT: &str = "java/lang/Object";
+
+enum Entry {
+    Directory(PathBuf),
+    /// A jar or jmod, the class files of a jmod are in `classes/`
+    Archive {
+        archive: RefCell<ZipArchive<File>>,
+        prefix: &'static str,
+    },
+    /// The runtime image of a JDK with the modules to look the classes up in
+    Image {
+        image: JImage,
+        modules: Vec<String>,
+    },
+}
+
+#[derive(Default)]
+pub struct ClassPath {
+    entries: Vec<Entry>,
+    /// `None` for classes that were looked up but not found
+    classes: RefCell<HashMap<String, Option<Rc<ClassFile>>>>,
+}
+
+impl ClassPath {
+    pub fn new() -> ClassPath {
+        ClassPath::default()
+    }
+
+    /// A class path from a list of paths separated like `-classpath` (`:` on unix)
+    pub fn from_class_path(class_path: &str) -> Result<ClassPath> {
+        let mut cp = ClassPath::new();
+        for path in std::env::split_paths(class_path) {
+            cp.add(&path)?;
+        }
+        Ok(cp)
+    }
+
+    /// Adds a directory, a `.jar` (or `.zip`) or a `.jmod` file
+    pub fn add(&mut self, path: &Path) -> Result<()> {
+        if path.is_dir() {
+            self.entries.push(Entry::Directory(path.to_path_buf()));
+            return Ok(());
+        }
+        let prefix = match path.extension().and_then(|e| e.to_str()) {
+            Some("jar" | "zip") => "",
+            Some("jmod") => "classes/",
+            _ => return Err(anyhow!("Unsupported class path entry {}", path.display())),
+        };
+        let archive = ZipArchive::new(File::open(path)?)
+            .map_err(|e| anyhow!("Failed to open {}: {}", path.display(), e))?;
+        self.entries.push(Entry::Archive {
+            archive: RefCell::new(archive),
+            prefix,
+        });
+        Ok(())
+    }
+
+    /// Adds the classes of the JDK found through `JAVA_HOME` or the `java` on the `PATH`
+    pub fn add_jdk(&mut self) -> Result<()> {
+        let java_home = find_java_home().ok_or(anyhow!("No JDK found, set JAVA_HOME"))?;
+        let image = java_home.join("lib").join("modules");
+        if image.is_file() {
+            // the modules are listed in the release file, java.base first
+            let release = std::fs::read_to_string(java_home.join("release"))?;
+            let modules = release
+                .lines()
+                .find_map(|line| line.strip_prefix("MODULES="))
+                .ok_or(anyhow!("No MODULES in {}/release", java_home.display()))?
+                .trim_matches('"')
+                .split_whitespace()
+                .map(|m| m.to_string())
+                .collect();
+            self.entries.push(Entry::Image {
+                image: JImage::open(&image)?,
+                modules,
+            });
+            return Ok(());
+        }
+
+        let jmods = java_home.join("jmods");
+        let mut modules = std::fs::read_dir(&jmods)
+            .map_err(|e| anyhow!("Failed to read {}: {}", jmods.display(), e))?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<std::io::Result<Vec<_>>>()?;
+        modules.sort();
+        for module in modules {
+            if module.extension().is_some_and(|e| e == "jmod") {
+                self.add(&module)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Makes an already parsed class available, it takes precedence over the entries
+    pub fn add_class_file(&mut self, cf: ClassFile) {
+        self.classes
+            .borrow_mut()
+            .insert(cf.class_name(), Some(Rc::new(cf)));
+    }
+
+    /// Loads the class with the binary name, e.g. `java/lang/String`
+    pub fn load(&self, name: &str) -> Result<Rc<ClassFile>> {
+        if let Some(class) = self.classes.borrow().get(name) {
+            return class
+                .clone()
+                .ok_or(anyhow!("Class {} not found on the class path", name));
+        }
+        let class = self.find(name)?.map(Rc::new);
+        self.classes
+            .borrow_mut()
+            .insert(name.to_string(), class.clone());
+        class.ok_or(anyhow!("Class {} not found on the class path", name))
+    }
+
+    fn find(&self, name: &str) -> Result<Option<ClassFile>> {
+        let file_name = format!("{}.class", name);
+        for entry in &self.entries {
+            match entry {
+                Entry::Directory(dir) => {
+                    let path = dir.join(&file_name);
+                    if path.is_file() {
+                        let path = path.to_str().ok_or(anyhow!("Invalid path {:?}", path))?;
+                        return Ok(Some(read_class_file(path)?));
+                    }
+                }
+                Entry::Archive { archive, prefix } => {
+                    let mut archive = archive.borrow_mut();
+                    let mut file = match archive.by_name(&format!("{}{}", prefix, file_name)) {
+                        Ok(file) => file,
+                        Err(zip::result::ZipError::FileNotFound) => continue,
+                        Err(e) => return Err(anyhow!("Failed to read {}: {}", file_name, e)),
+                    };
+                    let mut bytes = Vec::with_capacity(file.size() as usize);
+                    file.read_to_end(&mut bytes)?;
+                    return Ok(Some(read_class_bytes(bytes)?));
+                }
+                Entry::Image { image, modules } => {
+                    for module in modules {
+                        if let Some(bytes) = image.read(&format!("/{}/{}", module, file_name))? {
+                            return Ok(Some(read_class_bytes(bytes)?));
+                        }
+                    }
+                }
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Unknown classes are treated as direct subclasses of `java/lang/Object`
+impl ClassHierarchy for ClassPath {
+    fn super_class(&self, class: &str) -> Option<String> {
+        match self.load(class) {
+            Ok(cf) => cf.super_class_name(),
+            Err(_) if class == OBJECT => None,
+            Err(_) => Some(OBJECT.to_string()),
+        }
+    }
+
+    fn is_interface(&self, class: &str) -> bool {
+        self.load(class)
+            .map(|cf| cf.is_interface())
+            .unwrap_or(false)
+    }
+}
+
+/// The JDK of `JAVA_HOME`, otherwise of the `java` on the `PATH`
+pub fn find_java_home() -> Option<PathBuf> {
+    if let Some(home) = std::env::var_os("JAVA_HOME").filter(|h| !h.is_empty()) {
+        return Some(PathBuf::from(home));
+    }
+    // `bin/java` is usually a chain of symlinks into the JDK
+    let path = std::env::var_os("PATH")?;
+    let java = std::env::split_paths(&path)
+        .map(|dir| dir.join("java"))
+        .find(|java| java.is_file())?;
+    java.canonicalize()
+        .ok()?
+        .parent()?
+        .parent()
+        .map(|p| p.to_path_buf())
+}
@@ -1,11 +1,21 @@
 use crate::class_file::file_reader::FileReader;
-use anyhow::Result;
+use crate::class_file::file_writer::FileWriter;
+use crate::class_file::format_check;
+use anyhow::{anyhow, Result};
+use std::fmt;
+use std::sync::Arc;
 
+/// Clones share the entries until one of them adds an entry
+#[derive(Clone, Default)]
 pub struct ConstantPool {
-    constants: Vec<Info>,
+    constants: Arc<Vec<Info>>,
 }
 
 const UTF8: u8 = 1;
+const INTEGER: u8 = 3;
+const FLOAT: u8 = 4;
+const LONG: u8 = 5;
+const DOUBLE: u8 = 6;
 const METHOD_REF: u8 = 10;
 const INTERFACE_METHOD_REF: u8 = 11;
 const CLASS: u8 = 7;
@@ -16,9 +26,15 @@ const INVOKEDYNAMIC: u8 = 18;
 const METHOD_HANDLE: u8 = 15;
 const METHOD_TYPE: u8 = 16;
 
-#[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Info {
     Utf8Info(String),
+    IntegerInfo(i32),
+    FloatInfo(f32),
+    LongInfo(i64),
+    DoubleInfo(f64),
+    Unusable, // the slot after a long or double entry
     NameAndTypeInfo(NameAndTypeInfo),
     ClassInfo(ClassInfo),
     MethodRefInfo(MethodRefInfo),
@@ -30,58 +46,58 @@ pub enum Info {
     MethodTypeInfo(MethodTypeInfo),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct MethodTypeInfo {
     descriptor_index: u16,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct MethodRefInfo {
     class_index: u16,
     name_and_type_index: u16,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct InterfaceMethodRefInfo {
     class_index: u16,
     name_and_type_index: u16,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct ClassInfo {
     name_index: u16,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct NameAndTypeInfo {
-    name_index: u16,
-    descriptor_index: u16,
+    pub name_index: u16,
+    pub descriptor_index: u16,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct FieldRefInfo {
     class_index: u16,
     name_and_type_index: u16,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct StringInfo {
     string_index: u16,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct InvokeDynamicInfo {
     bootstrap_method_attr_index: u16,
     name_and_type_index: u16,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct MethodHandleInfo {
     reference_kind: MethodHandleReferenceKind,
     reference_index: u16,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum MethodHandleReferenceKind {
     RefGetField,         // 1     getfield C.f:T
     RefGetStatic,        // 2     getstatic C.f:T
@@ -94,9 +110,68 @@ pub enum MethodHandleReferenceKind {
     RefInvokeInterface,  // 9     invokeinterface C.m:(A*)T
 }
 
+/// A `Fieldref` entry
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldRef {
+    /// Binary name of the class the field is looked up in
+    pub owner: String,
+    pub name: String,
+    pub descriptor: String,
+}
+
+/// A `Methodref` entry or, if `is_interface`, an `InterfaceMethodref` entry
+#[derive(Debug, Clone, PartialEq)]
+pub struct MethodRef {
+    /// Binary name of the class or interface the method is looked up in
+    pub owner: String,
+    pub name: String,
+    pub descriptor: String,
+    pub is_interface: bool,
+}
+
+/// The field or method a `MethodHandle` entry refers to
+#[derive(Debug, Clone, PartialEq)]
+pub enum MemberRef {
+    Field(FieldRef),
+    Method(MethodRef),
+}
+
+/// A `MethodHandle` entry
+#[derive(Debug, Clone, PartialEq)]
+pub struct MethodHandle {
+    pub kind: MethodHandleReferenceKind,
+    pub reference: MemberRef,
+}
+
+/// An `InvokeDynamic` entry, the call site of an `invokedynamic` instruction
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvokeDynamic {
+    /// Index of the bootstrap method in the `BootstrapMethods` attribute
+    pub bootstrap_method_attr_index: u16,
+    pub name: String,
+    pub descriptor: String,
+}
+
+/// Formatted like javap, `java/lang/System.out:Ljava/io/PrintStream;`
+impl fmt::Display for FieldRef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}:{}", self.owner, self.name, self.descriptor)
+    }
+}
+
+/// Formatted like javap, `java/lang/Object."<init>":()V`
+impl fmt::Display for MethodRef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.name.starts_with('<') {
+            true => write!(f, "{}.\"{}\":{}", self.owner, self.name, self.descriptor),
+            false => write!(f, "{}.{}:{}", self.owner, self.name, self.descriptor),
+        }
+    }
+}
+
 impl MethodHandleReferenceKind {
-    pub fn from_u8(value: u8) -> MethodHandleReferenceKind {
-        match value {
+    pub fn from_u8(value: u8) -> Result<MethodHandleReferenceKind> {
+        let kind = match value {
             1 => MethodHandleReferenceKind::RefGetField,
             2 => MethodHandleReferenceKind::RefGetStatic,
             3 => MethodHandleReferenceKind::RefPutField,
@@ -106,10 +181,38 @@ impl MethodHandleReferenceKind {
             7 => MethodHandleReferenceKind::RefInvokeSpecial,
             8 => MethodHandleReferenceKind::RefNewInvokeSpecial,
             9 => MethodHandleReferenceKind::RefInvokeInterface,
-            _ => panic!("invalid reference kind {}", value),
+            _ => return Err(anyhow!("Invalid method handle reference kind {}", value)),
+        };
+        Ok(kind)
+    }
+
+    pub fn to_u8(self) -> u8 {
+        match self {
+            MethodHandleReferenceKind::RefGetField => 1,
+            MethodHandleReferenceKind::RefGetStatic => 2,
+            MethodHandleReferenceKind::RefPutField => 3,
+            MethodHandleReferenceKind::RefPutStatic => 4,
+            MethodHandleReferenceKind::RefInvokeVirtual => 5,
+            MethodHandleReferenceKind::RefInvokeStatic => 6,
+            MethodHandleReferenceKind::RefInvokeSpecial => 7,
+            MethodHandleReferenceKind::RefNewInvokeSpecial => 8,
+            MethodHandleReferenceKind::RefInvokeInterface => 9,
         }
     }
 
+    /// The kind named like `to_string`, e.g. `invokeStatic`
+    pub fn from_name(name: &str) -> Option<MethodHandleReferenceKind> {
+        (1..=9)
+            .filter_map(|value| MethodHandleReferenceKind::from_u8(value).ok())
+            .find(|kind| kind.to_string() == name)
+    }
+
+    /// Whether the handle refers to a field rather than a method
+    pub fn is_field(self) -> bool {
+        self.to_u8() <= 4
+    }
+
+    #[allow(clippy::inherent_to_string)]
     pub fn to_string(&self) -> String {
         let out = match self {
             MethodHandleReferenceKind::RefGetField => "getField",
@@ -126,12 +229,55 @@ impl MethodHandleReferenceKind {
     }
 }
 
+impl Info {
+    /// Passes the indices of the entries this entry refers to to `f`, which may change them
+    fn constant_indices(&mut self, f: &mut dyn FnMut(&mut u16)) {
+        match self {
+            Info::Utf8Info(_)
+            | Info::IntegerInfo(_)
+            | Info::FloatInfo(_)
+            | Info::LongInfo(_)
+            | Info::DoubleInfo(_)
+            | Info::Unusable => {}
+            Info::NameAndTypeInfo(info) => {
+                f(&mut info.name_index);
+                f(&mut info.descriptor_index);
+            }
+            Info::ClassInfo(info) => f(&mut info.name_index),
+            Info::MethodRefInfo(info) => {
+                f(&mut info.class_index);
+                f(&mut info.name_and_type_index);
+            }
+            Info::InterfaceMethodRefInfo(info) => {
+                f(&mut info.class_index);
+                f(&mut info.name_and_type_index);
+            }
+            Info::FieldRefInfo(info) => {
+                f(&mut info.class_index);
+                f(&mut info.name_and_type_index);
+            }
+            Info::StringInfo(info) => f(&mut info.string_index),
+            // the bootstrap method index points into the BootstrapMethods attribute
+            Info::InvokeDynamicInfo(info) => f(&mut info.name_and_type_index),
+            Info::MethodHandleInfo(info) => f(&mut info.reference_index),
+            Info::MethodTypeInfo(info) => f(&mut info.descriptor_index),
+        }
+    }
+}
+
 impl ConstantPool {
+    /// A pool without entries, for building a class file
+    pub fn new() -> ConstantPool {
+        ConstantPool::default()
+    }
+
     pub fn from(file: &mut FileReader) -> Result<ConstantPool> {
         let mut constant_pool = Vec::new();
 
         let constant_pool_count = file.read_u2_to_u16()?;
-        for _i in 1..constant_pool_count {
+        let mut i = 1;
+        while i < constant_pool_count {
+            i += 1;
             let tag = file.read_u1()?;
 
             let constant = match tag {
@@ -147,6 +293,23 @@ impl ConstantPool {
                     descriptor_index: file.read_u2_to_u16()?,
                 }),
                 UTF8 => Info::Utf8Info(file.read_string()?),
+                INTEGER => Info::IntegerInfo(i32::from_be_bytes(file.read_u4()?)),
+                FLOAT => Info::FloatInfo(f32::from_be_bytes(file.read_u4()?)),
+                LONG | DOUBLE => {
+                    let high = file.read_u4()?;
+                    let low = file.read_u4()?;
+                    let bytes = [
+                        high[0], high[1], high[2], high[3], low[0], low[1], low[2], low[3],
+                    ];
+                    // 8 byte constants take up two entries in the constant pool
+                    i += 1;
+                    constant_pool.push(if tag == LONG {
+                        Info::LongInfo(i64::from_be_bytes(bytes))
+                    } else {
+                        Info::DoubleInfo(f64::from_be_bytes(bytes))
+                    });
+                    Info::Unusable
+                }
                 FIELD_REF => Info::FieldRefInfo(FieldRefInfo {
                     class_index: file.read_u2_to_u16()?,
                     name_and_type_index: file.read_u2_to_u16()?,
@@ -159,7 +322,7 @@ impl ConstantPool {
                     name_and_type_index: file.read_u2_to_u16()?,
                 }),
                 METHOD_HANDLE => Info::MethodHandleInfo(MethodHandleInfo {
-                    reference_kind: MethodHandleReferenceKind::from_u8(file.read_u1()?),
+                    reference_kind: MethodHandleReferenceKind::from_u8(file.read_u1()?)?,
                     reference_index: file.read_u2_to_u16()?,
                 }),
                 METHOD_TYPE => Info::MethodTypeInfo(MethodTypeInfo {
@@ -170,15 +333,18 @@ impl ConstantPool {
                     name_and_type_index: file.read_u2_to_u16()?,
                 }),
                 _ => {
-                    println!("tag not implemented: {}", tag);
-                    panic!();
+                    return Err(anyhow!(
+                        "Constant pool tag {} at #{} is not supported",
+                        tag,
+                        i - 1
+                    ))
                 }
             };
             constant_pool.push(constant);
         }
 
         Ok(ConstantPool {
-            constants: constant_pool,
+            constants: Arc::new(constant_pool),
         })
     }
 
@@ -186,10 +352,580 @@ impl ConstantPool {
         &self.constants[index as usize - 1]
     }
 
+    /// The entry at `index`, `None` for 0 and indexes past the end of the pool
+    pub fn try_get(&self, index: u16) -> Option<&Info> {
+        self.constants.get((index as usize).checked_sub(1)?)
+    }
+
+    /// Number of entries, the `constant_pool_count` of the class file minus one
+    pub fn len(&self) -> usize {
+        self.constants.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.constants.is_empty()
+    }
+
+    /// Index of the Utf8 entry for `value`, added at the end of the pool if missing
+    pub fn add_utf8(&mut self, value: &str) -> Result<u16> {
+        let existing = self
+            .constants
+            .iter()
+            .position(|c| matches!(c, Info::Utf8Info(s) if s == value));
+        match existing {
+            Some(i) => Ok(i as u16 + 1),
+            None => self.push(Info::Utf8Info(value.to_string())),
+        }
+    }
+
+    /// Index of the Class entry for the binary name (or array descriptor) `name`,
+    /// added at the end of the pool if missing
+    pub fn add_class(&mut self, name: &str) -> Result<u16> {
+        let existing = self.constants.iter().position(|c| match c {
+            Info::ClassInfo(class) => self.get_to_string(class.name_index) == name,
+            _ => false,
+        });
+        if let Some(i) = existing {
+            return Ok(i as u16 + 1);
+        }
+        let name_index = self.add_utf8(name)?;
+        self.push(Info::ClassInfo(ClassInfo { name_index }))
+    }
+
+    pub fn add_integer(&mut self, value: i32) -> Result<u16> {
+        self.add(Info::IntegerInfo(value))
+    }
+
+    pub fn add_float(&mut self, value: f32) -> Result<u16> {
+        self.add(Info::FloatInfo(value))
+    }
+
+    pub fn add_long(&mut self, value: i64) -> Result<u16> {
+        self.add(Info::LongInfo(value))
+    }
+
+    pub fn add_double(&mut self, value: f64) -> Result<u16> {
+        self.add(Info::DoubleInfo(value))
+    }
+
+    pub fn add_string(&mut self, value: &str) -> Result<u16> {
+        let string_index = self.add_utf8(value)?;
+        self.add(Info::StringInfo(StringInfo { string_index }))
+    }
+
+    pub fn add_name_and_type(&mut self, name: &str, descriptor: &str) -> Result<u16> {
+        let name_index = self.add_utf8(name)?;
+        let descriptor_index = self.add_utf8(descriptor)?;
+        self.add(Info::NameAndTypeInfo(NameAndTypeInfo {
+            name_index,
+            descriptor_index,
+        }))
+    }
+
+    pub fn add_field_ref(&mut self, field: &FieldRef) -> Result<u16> {
+        let class_index = self.add_class(&field.owner)?;
+        let name_and_type_index = self.add_name_and_type(&field.name, &field.descriptor)?;
+        self.add(Info::FieldRefInfo(FieldRefInfo {
+            class_index,
+            name_and_type_index,
+        }))
+    }
+
+    /// A Methodref or, if `is_interface`, an InterfaceMethodref entry
+    pub fn add_method_ref(&mut self, method: &MethodRef) -> Result<u16> {
+        let class_index = self.add_class(&method.owner)?;
+        let name_and_type_index = self.add_name_and_type(&method.name, &method.descriptor)?;
+        self.add(match method.is_interface {
+            true => Info::InterfaceMethodRefInfo(InterfaceMethodRefInfo {
+                class_index,
+                name_and_type_index,
+            }),
+            false => Info::MethodRefInfo(MethodRefInfo {
+                class_index,
+                name_and_type_index,
+            }),
+        })
+    }
+
+    pub fn add_method_handle(&mut self, handle: &MethodHandle) -> Result<u16> {
+        let reference_index = match &handle.reference {
+            MemberRef::Field(field) => self.add_field_ref(field)?,
+            MemberRef::Method(method) => self.add_method_ref(method)?,
+        };
+        self.add(Info::MethodHandleInfo(MethodHandleInfo {
+            reference_kind: handle.kind,
+            reference_index,
+        }))
+    }
+
+    pub fn add_method_type(&mut self, descriptor: &str) -> Result<u16> {
+        let descriptor_index = self.add_utf8(descriptor)?;
+        self.add(Info::MethodTypeInfo(MethodTypeInfo { descriptor_index }))
+    }
+
+    pub fn add_invoke_dynamic(&mut self, call_site: &InvokeDynamic) -> Result<u16> {
+        let name_and_type_index = self.add_name_and_type(&call_site.name, &call_site.descriptor)?;
+        self.add(Info::InvokeDynamicInfo(InvokeDynamicInfo {
+            bootstrap_method_attr_index: call_site.bootstrap_method_attr_index,
+            name_and_type_index,
+        }))
+    }
+
+    /// Index of an equal entry, `info` added at the end of the pool if missing. Floating
+    /// point constants are compared by their bits so NaN and -0.0 get their own entries.
+    fn add(&mut self, info: Info) -> Result<u16> {
+        let existing = self.constants.iter().position(|c| match (c, &info) {
+            (Info::FloatInfo(a), Info::FloatInfo(b)) => a.to_bits() == b.to_bits(),
+            (Info::DoubleInfo(a), Info::DoubleInfo(b)) => a.to_bits() == b.to_bits(),
+            (Info::FloatInfo(_), _) | (Info::DoubleInfo(_), _) => false,
+            (c, info) => c == info,
+        });
+        match existing {
+            Some(i) => Ok(i as u16 + 1),
+            None => self.push(info),
+        }
+    }
+
+    fn push(&mut self, info: Info) -> Result<u16> {
+        let wide = matches!(info, Info::LongInfo(_) | Info::DoubleInfo(_));
+        // constant_pool_count is a u2 and counts the unused entry 0
+        if self.constants.len() + 1 + wide as usize >= u16::MAX as usize {
+            return Err(anyhow!("Constant pool is full"));
+        }
+        let constants = Arc::make_mut(&mut self.constants);
+        constants.push(info);
+        let index = constants.len() as u16;
+        if wide {
+            constants.push(Info::Unusable);
+        }
+        Ok(index)
+    }
+
+    /// The pool with only the entries in `used` and the entries they refer to, in their
+    /// original order, and the new index of every old index, 0 for dropped entries.
+    /// Indices only get smaller, so an `ldc` operand still fits in a byte. 0 in `used` is
+    /// skipped, any other index that isn't an entry, in `used` or in the entries it refers
+    /// to, is an error.
+    pub(crate) fn compact(
+        &self,
+        used: impl IntoIterator<Item = u16>,
+    ) -> Result<(ConstantPool, Vec<u16>)> {
+        let mut keep = vec![false; self.constants.len() + 1];
+        let mut pending: Vec<u16> = used.into_iter().filter(|&i| i != 0).collect();
+        while let Some(index) = pending.pop() {
+            match self.try_get(index) {
+                Some(Info::Unusable) | None => {
+                    return Err(anyhow!("Invalid constant pool index {}", index))
+                }
+                Some(_) if keep[index as usize] => {}
+                Some(info) => {
+                    keep[index as usize] = true;
+                    info.clone().constant_indices(&mut |i| pending.push(*i));
+                }
+            }
+        }
+
+        let mut map = vec![0; keep.len()];
+        let mut constants = Vec::new();
+        for (i, info) in self.constants.iter().enumerate() {
+            if keep[i + 1] {
+                constants.push(info.clone());
+                map[i + 1] = constants.len() as u16;
+                if matches!(info, Info::LongInfo(_) | Info::DoubleInfo(_)) {
+                    constants.push(Info::Unusable);
+                }
+            }
+        }
+        for info in &mut constants {
+            info.constant_indices(&mut |i| *i = map[*i as usize]);
+        }
+        let cp = ConstantPool {
+            constants: Arc::new(constants),
+        };
+        Ok((cp, map))
+    }
+
+    /// Writes `constant_pool_count` and the entries
+    pub fn write(&self, file: &mut FileWriter) -> Result<()> {
+        file.write_u2(self.constants.len() as u16 + 1);
+        for info in self.constants.iter() {
+            match info {
+                Info::Utf8Info(s) => {
+                    file.write_u1(UTF8);
+                    file.write_string(s)?;
+                }
+                Info::IntegerInfo(v) => {
+                    file.write_u1(INTEGER);
+                    file.write_i32(*v);
+                }
+                Info::FloatInfo(v) => {
+                    file.write_u1(FLOAT);
+                    file.write_u4(v.to_bits());
+                }
+                Info::LongInfo(v) => {
+                    file.write_u1(LONG);
+                    file.write_bytes(&v.to_be_bytes());
+                }
+                Info::DoubleInfo(v) => {
+                    file.write_u1(DOUBLE);
+                    file.write_bytes(&v.to_bits().to_be_bytes());
+                }
+                Info::Unusable => {}
+                Info::NameAndTypeInfo(n) => {
+                    file.write_u1(NAME_AND_TYPE);
+                    file.write_u2(n.name_index);
+                    file.write_u2(n.descriptor_index);
+                }
+                Info::ClassInfo(c) => {
+                    file.write_u1(CLASS);
+                    file.write_u2(c.name_index);
+                }
+                Info::MethodRefInfo(m) => {
+                    file.write_u1(METHOD_REF);
+                    file.write_u2(m.class_index);
+                    file.write_u2(m.name_and_type_index);
+                }
+                Info::FieldRefInfo(f) => {
+                    file.write_u1(FIELD_REF);
+                    file.write_u2(f.class_index);
+                    file.write_u2(f.name_and_type_index);
+                }
+                Info::StringInfo(s) => {
+                    file.write_u1(STRING);
+                    file.write_u2(s.string_index);
+                }
+                Info::InvokeDynamicInfo(i) => {
+                    file.write_u1(INVOKEDYNAMIC);
+                    file.write_u2(i.bootstrap_method_attr_index);
+                    file.write_u2(i.name_and_type_index);
+                }
+                Info::MethodHandleInfo(m) => {
+                    file.write_u1(METHOD_HANDLE);
+                    file.write_u1(m.reference_kind.to_u8());
+                    file.write_u2(m.reference_index);
+                }
+                Info::InterfaceMethodRefInfo(i) => {
+                    file.write_u1(INTERFACE_METHOD_REF);
+                    file.write_u2(i.class_index);
+                    file.write_u2(i.name_and_type_index);
+                }
+                Info::MethodTypeInfo(m) => {
+                    file.write_u1(METHOD_TYPE);
+                    file.write_u2(m.descriptor_index);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The string of a Utf8 entry
+    pub fn utf8(&self, index: u16) -> Result<&str> {
+        match self.try_get(index) {
+            Some(Info::Utf8Info(s)) => Ok(s),
+            info => Err(wrong_kind(index, "Utf8", info)),
+        }
+    }
+
+    /// Binary name of a Class entry, e.g. `java/lang/String` or `[I` for array classes
+    pub fn class_name(&self, index: u16) -> Result<&str> {
+        match self.try_get(index) {
+            Some(Info::ClassInfo(c)) => self.utf8(c.name_index),
+            info => Err(wrong_kind(index, "Class", info)),
+        }
+    }
+
+    /// The value of a String entry
+    pub fn string(&self, index: u16) -> Result<&str> {
+        match self.try_get(index) {
+            Some(Info::StringInfo(s)) => self.utf8(s.string_index),
+            info => Err(wrong_kind(index, "String", info)),
+        }
+    }
+
+    /// Name and descriptor of a NameAndType entry
+    pub fn name_and_type(&self, index: u16) -> Result<(&str, &str)> {
+        match self.try_get(index) {
+            Some(Info::NameAndTypeInfo(n)) => {
+                Ok((self.utf8(n.name_index)?, self.utf8(n.descriptor_index)?))
+            }
+            info => Err(wrong_kind(index, "NameAndType", info)),
+        }
+    }
+
+    pub fn field_ref(&self, index: u16) -> Result<FieldRef> {
+        match self.try_get(index) {
+            Some(Info::FieldRefInfo(r)) => {
+                let (name, descriptor) = self.name_and_type(r.name_and_type_index)?;
+                Ok(FieldRef {
+                    owner: self.class_name(r.class_index)?.to_string(),
+                    name: name.to_string(),
+                    descriptor: descriptor.to_string(),
+                })
+            }
+            info => Err(wrong_kind(index, "Fieldref", info)),
+        }
+    }
+
+    /// A Methodref or InterfaceMethodref entry
+    pub fn method_ref(&self, index: u16) -> Result<MethodRef> {
+        let (class_index, name_and_type_index, is_interface) = match self.try_get(index) {
+            Some(Info::MethodRefInfo(r)) => (r.class_index, r.name_and_type_index, false),
+            Some(Info::InterfaceMethodRefInfo(r)) => (r.class_index, r.name_and_type_index, true),
+            info => return Err(wrong_kind(index, "Methodref", info)),
+        };
+        let (name, descriptor) = self.name_and_type(name_and_type_index)?;
+        Ok(MethodRef {
+            owner: self.class_name(class_index)?.to_string(),
+            name: name.to_string(),
+            descriptor: descriptor.to_string(),
+            is_interface,
+        })
+    }
+
+    pub fn method_handle(&self, index: u16) -> Result<MethodHandle> {
+        match self.try_get(index) {
+            Some(Info::MethodHandleInfo(m)) => {
+                let reference = match m.reference_kind {
+                    MethodHandleReferenceKind::RefGetField
+                    | MethodHandleReferenceKind::RefGetStatic
+                    | MethodHandleReferenceKind::RefPutField
+                    | MethodHandleReferenceKind::RefPutStatic => {
+                        MemberRef::Field(self.field_ref(m.reference_index)?)
+                    }
+                    _ => MemberRef::Method(self.method_ref(m.reference_index)?),
+                };
+                Ok(MethodHandle {
+                    kind: m.reference_kind,
+                    reference,
+                })
+            }
+            info => Err(wrong_kind(index, "MethodHandle", info)),
+        }
+    }
+
+    /// The method descriptor of a MethodType entry
+    pub fn method_type(&self, index: u16) -> Result<&str> {
+        match self.try_get(index) {
+            Some(Info::MethodTypeInfo(m)) => self.utf8(m.descriptor_index),
+            info => Err(wrong_kind(index, "MethodType", info)),
+        }
+    }
+
+    pub fn invoke_dynamic(&self, index: u16) -> Result<InvokeDynamic> {
+        match self.try_get(index) {
+            Some(Info::InvokeDynamicInfo(i)) => {
+                let (name, descriptor) = self.name_and_type(i.name_and_type_index)?;
+                Ok(InvokeDynamic {
+                    bootstrap_method_attr_index: i.bootstrap_method_attr_index,
+                    name: name.to_string(),
+                    descriptor: descriptor.to_string(),
+                })
+            }
+            info => Err(wrong_kind(index, "InvokeDynamic", info)),
+        }
+    }
+
+    /// Violations of the structure of the entries (JVMS 4.4) with the index of the entry:
+    /// references to entries of the wrong kind and invalid names and descriptors.
+    /// `bootstrap_methods` is the number of entries in the `BootstrapMethods` attribute.
+    pub(crate) fn format_errors(
+        &self,
+        major_version: u16,
+        bootstrap_methods: usize,
+    ) -> Vec<(u16, String)> {
+        let mut errors = Vec::new();
+        for (i, info) in self.constants.iter().enumerate() {
+            let result = match info {
+                Info::ClassInfo(c) => self.checked_utf8(c.name_index).and_then(|name| {
+                    if format_check::is_class_name(name) {
+                        Ok(())
+                    } else {
+                        Err(format!("Invalid class name {:?}", name))
+                    }
+                }),
+                Info::StringInfo(s) => self.checked_utf8(s.string_index).map(|_| ()),
+                Info::NameAndTypeInfo(n) => self.checked_utf8(n.name_index).and_then(|_| {
+                    let descriptor = self.checked_utf8(n.descriptor_index)?;
+                    if format_check::is_field_descriptor(descriptor)
+                        || format_check::is_method_descriptor(descriptor)
+                    {
+                        Ok(())
+                    } else {
+                        Err(format!("Invalid descriptor {:?}", descriptor))
+                    }
+                }),
+                Info::FieldRefInfo(r) => {
+                    self.check_member(r.class_index, r.name_and_type_index, false)
+                }
+                Info::MethodRefInfo(r) => {
+                    self.check_member(r.class_index, r.name_and_type_index, true)
+                }
+                Info::InterfaceMethodRefInfo(r) => {
+                    self.check_member(r.class_index, r.name_and_type_index, true)
+                }
+                Info::MethodTypeInfo(m) => {
+                    self.checked_utf8(m.descriptor_index)
+                        .and_then(|descriptor| {
+                            if format_check::is_method_descriptor(descriptor) {
+                                Ok(())
+                            } else {
+                                Err(format!("Invalid method descriptor {:?}", descriptor))
+                            }
+                        })
+                }
+                Info::MethodHandleInfo(m) => self.check_method_handle(m, major_version),
+                Info::InvokeDynamicInfo(d) => {
+                    if d.bootstrap_method_attr_index as usize >= bootstrap_methods {
+                        Err(format!(
+                            "Bootstrap method {} does not exist, the class has {}",
+                            d.bootstrap_method_attr_index, bootstrap_methods
+                        ))
+                    } else {
+                        let (_, descriptor) =
+                            match self.checked_name_and_type(d.name_and_type_index) {
+                                Ok(nat) => nat,
+                                Err(e) => {
+                                    errors.push((i as u16 + 1, e));
+                                    continue;
+                                }
+                            };
+                        if format_check::is_method_descriptor(descriptor) {
+                            Ok(())
+                        } else {
+                            Err(format!("Invalid method descriptor {:?}", descriptor))
+                        }
+                    }
+                }
+                _ => Ok(()),
+            };
+            if let Err(message) = result {
+                errors.push((i as u16 + 1, message));
+            }
+        }
+        errors
+    }
+
+    /// Class and NameAndType of a field or method reference, the name and descriptor must
+    /// be valid for the kind of member
+    fn check_member(
+        &self,
+        class_index: u16,
+        name_and_type_index: u16,
+        method: bool,
+    ) -> Result<(), String> {
+        self.kind(class_index, "Class")?;
+        let (name, descriptor) = self.checked_name_and_type(name_and_type_index)?;
+        if method {
+            if !format_check::is_method_name(name) {
+                return Err(format!("Invalid method name {:?}", name));
+            }
+            if !format_check::is_method_descriptor(descriptor) {
+                return Err(format!("Invalid method descriptor {:?}", descriptor));
+            }
+            if name == "<init>" && !descriptor.ends_with(")V") {
+                return Err(format!("<init> must return void, not {:?}", descriptor));
+            }
+        } else {
+            if !format_check::is_unqualified_name(name) {
+                return Err(format!("Invalid field name {:?}", name));
+            }
+            if !format_check::is_field_descriptor(descriptor) {
+                return Err(format!("Invalid field descriptor {:?}", descriptor));
+            }
+        }
+        Ok(())
+    }
+
+    fn check_method_handle(
+        &self,
+        handle: &MethodHandleInfo,
+        major_version: u16,
+    ) -> Result<(), String> {
+        use MethodHandleReferenceKind::*;
+        let reference = self.try_get(handle.reference_index);
+        let valid = match handle.reference_kind {
+            RefGetField | RefGetStatic | RefPutField | RefPutStatic => {
+                matches!(reference, Some(Info::FieldRefInfo(_)))
+            }
+            RefInvokeVirtual | RefNewInvokeSpecial => {
+                matches!(reference, Some(Info::MethodRefInfo(_)))
+            }
+            RefInvokeStatic | RefInvokeSpecial => match reference {
+                Some(Info::MethodRefInfo(_)) => true,
+                Some(Info::InterfaceMethodRefInfo(_)) => major_version >= 52,
+                _ => false,
+            },
+            RefInvokeInterface => matches!(reference, Some(Info::InterfaceMethodRefInfo(_))),
+        };
+        if !valid {
+            return Err(format!(
+                "#{} is a {}, not a valid reference for {}",
+                handle.reference_index,
+                kind_name(reference),
+                handle.reference_kind.to_string()
+            ));
+        }
+        let name_and_type_index = match reference {
+            Some(Info::FieldRefInfo(r)) => r.name_and_type_index,
+            Some(Info::MethodRefInfo(r)) => r.name_and_type_index,
+            Some(Info::InterfaceMethodRefInfo(r)) => r.name_and_type_index,
+            _ => unreachable!(),
+        };
+        let (name, _) = self.checked_name_and_type(name_and_type_index)?;
+        match handle.reference_kind {
+            RefNewInvokeSpecial if name != "<init>" => {
+                Err(format!("newInvokeSpecial of {:?} instead of <init>", name))
+            }
+            RefInvokeVirtual | RefInvokeStatic | RefInvokeSpecial | RefInvokeInterface
+                if name.starts_with('<') =>
+            {
+                Err(format!("{} of {}", handle.reference_kind.to_string(), name))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// The entry at `index` if it is of the `kind` named like in `kind_name`
+    pub(crate) fn kind(&self, index: u16, kind: &str) -> Result<&Info, String> {
+        let info = self.try_get(index);
+        match info {
+            Some(info) if kind_name(Some(info)) == kind => Ok(info),
+            _ => Err(wrong_kind_message(index, kind, info)),
+        }
+    }
+
+    /// The name of a Class entry, an error message for any other entry
+    pub(crate) fn checked_class(&self, index: u16) -> Result<&str, String> {
+        match self.kind(index, "Class")? {
+            Info::ClassInfo(c) => self.checked_utf8(c.name_index),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Name and descriptor of a NameAndType entry without panicking on invalid indexes
+    fn checked_name_and_type(&self, index: u16) -> Result<(&str, &str), String> {
+        match self.kind(index, "NameAndType")? {
+            Info::NameAndTypeInfo(nat) => Ok((
+                self.checked_utf8(nat.name_index)?,
+                self.checked_utf8(nat.descriptor_index)?,
+            )),
+            _ => unreachable!(),
+        }
+    }
+
+    /// The string of a Utf8 entry, an error message for any other entry
+    pub(crate) fn checked_utf8(&self, index: u16) -> Result<&str, String> {
+        match self.kind(index, "Utf8")? {
+            Info::Utf8Info(s) => Ok(s),
+            _ => unreachable!(),
+        }
+    }
+
     pub fn get_to_string(&self, index: u16) -> String {
         self.info_to_string(self.get(index))
     }
 
+    #[allow(clippy::inherent_to_string)]
     pub fn to_string(&self) -> String {
         let mut s = String::new();
         for (i, info) in self.constants.iter().enumerate() {
@@ -201,6 +937,11 @@ impl ConstantPool {
     pub fn info_to_string(&self, info: &Info) -> String {
         match info {
             Info::Utf8Info(s) => s.clone(),
+            Info::IntegerInfo(v) => v.to_string(),
+            Info::FloatInfo(v) => v.to_string(),
+            Info::LongInfo(v) => v.to_string(),
+            Info::DoubleInfo(v) => v.to_string(),
+            Info::Unusable => "(unusable)".to_string(),
             Info::NameAndTypeInfo(n) => {
                 let name = self.get(n.name_index);
                 let descriptor = self.get(n.descriptor_index);
@@ -259,3 +1000,38 @@ impl ConstantPool {
         }
     }
 }
+
+fn wrong_kind(index: u16, kind: &str, info: Option<&Info>) -> anyhow::Error {
+    anyhow!(wrong_kind_message(index, kind, info))
+}
+
+fn wrong_kind_message(index: u16, kind: &str, info: Option<&Info>) -> String {
+    format!(
+        "#{} must be a {} entry, not {}",
+        index,
+        kind,
+        kind_name(info)
+    )
+}
+
+/// Name of the kind of entry for error messages
+fn kind_name(info: Option<&Info>) -> &'static str {
+    match info {
+        None => "missing",
+        Some(Info::Utf8Info(_)) => "Utf8",
+        Some(Info::IntegerInfo(_)) => "Integer",
+        Some(Info::FloatInfo(_)) => "Float",
+        Some(Info::LongInfo(_)) => "Long",
+        Some(Info::DoubleInfo(_)) => "Double",
+        Some(Info::Unusable) => "unusable",
+        Some(Info::NameAndTypeInfo(_)) => "NameAndType",
+        Some(Info::ClassInfo(_)) => "Class",
+        Some(Info::MethodRefInfo(_)) => "Methodref",
+        Some(Info::FieldRefInfo(_)) => "Fieldref",
+        Some(Info::StringInfo(_)) => "String",
+        Some(Info::InvokeDynamicInfo(_)) => "InvokeDynamic",
+        Some(Info::MethodHandleInfo(_)) => "MethodHandle",
+        Some(Info::InterfaceMethodRefInfo(_)) => "InterfaceMethodref",
+        Some(Info::MethodTypeInfo(_)) => "MethodType",
+    }
+}
@@ -0,0 +1,160 @@
+//! Parses many class files at once: files, the class files in directories and the ones in
+//! jars, in parallel on the rayon thread pool.
+//!
+//! The results are in a deterministic order, the order of the inputs with the class files of
+//! a directory or jar sorted by path, whatever order the threads finish in. They are parsed a
+//! chunk at a time as the results are consumed, so a large corpus isn't held in memory at once.
+//! A class file that fails to parse doesn't stop the others, its error is kept with its source.
+
+use super::{read_class_bytes_with, ClassFile, Parsing};
+use anyhow::{anyhow, Result};
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use zip::ZipArchive;
+
+/// Where a class file was read from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    File(PathBuf),
+    /// An entry of a `.jar`, `.zip` or `.jmod`
+    JarEntry {
+        jar: PathBuf,
+        entry: String,
+    },
+}
+
+pub struct Parsed {
+    pub source: Source,
+    pub class_file: Result<ClassFile>,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Source::File(path) => write!(f, "{}", path.display()),
+            Source::JarEntry { jar, entry } => write!(f, "{}!/{}", jar.display(), entry),
+        }
+    }
+}
+
+/// How many class files are parsed in parallel before their results are handed out
+const CHUNK_SIZE: usize = 1024;
+
+/// Parses the class files, directories (recursively) and jars in parallel
+///
+/// An input that can't be listed, e.g. a missing file or a corrupt jar, gives a single
+/// result with the error.
+pub fn parse_all<P: AsRef<Path>>(inputs: &[P], parsing: Parsing) -> impl Iterator<Item = Parsed> {
+    // the class files to read, or the input with the error listing it
+    let mut sources: Vec<Result<Source, (Source, anyhow::Error)>> = Vec::new();
+    for input in inputs {
+        let input = input.as_ref();
+        let mut listed = Vec::new();
+        match list_class_files(input, &mut listed) {
+            Ok(()) => sources.extend(listed.into_iter().map(Ok)),
+            Err(e) => sources.push(Err((Source::File(input.to_path_buf()), e))),
+        }
+    }
+
+    let mut sources = sources.into_iter();
+    std::iter::from_fn(move || {
+        let chunk = sources.by_ref().take(CHUNK_SIZE).collect::<Vec<_>>();
+        (!chunk.is_empty()).then(|| parse_chunk(chunk, parsing))
+    })
+    .flatten()
+}
+
+/// Parses the sources in parallel, the results are in the order of the sources
+fn parse_chunk(
+    sources: Vec<Result<Source, (Source, anyhow::Error)>>,
+    parsing: Parsing,
+) -> Vec<Parsed> {
+    sources
+        .into_par_iter()
+        .map_init(HashMap::new, |archives, source| match source {
+            Ok(source) => {
+                let class_file = read_source(&source, archives)
+                    .and_then(|bytes| read_class_bytes_with(bytes, parsing));
+                Parsed { source, class_file }
+            }
+            Err((source, e)) => Parsed {
+                source,
+                class_file: Err(e),
+            },
+        })
+        .collect()
+}
+
+/// Adds the class files of the input to `sources`, sorted by path
+fn list_class_files(input: &Path, sources: &mut Vec<Source>) -> Result<()> {
+    if input.is_dir() {
+        let mut files = Vec::new();
+        list_directory(input, &mut files)?;
+        files.sort();
+        sources.extend(files.into_iter().map(Source::File));
+        return Ok(());
+    }
+    match input.extension().and_then(|e| e.to_str()) {
+        Some("jar" | "zip" | "jmod") => {
+            let archive = ZipArchive::new(File::open(input)?)
+                .map_err(|e| anyhow!("Failed to open {}: {}", input.display(), e))?;
+            let mut entries = archive
+                .file_names()
+                .filter(|name| name.ends_with(".class"))
+                .map(|name| name.to_string())
+                .collect::<Vec<_>>();
+            entries.sort();
+            sources.extend(entries.into_iter().map(|entry| Source::JarEntry {
+                jar: input.to_path_buf(),
+                entry,
+            }));
+            Ok(())
+        }
+        _ if input.is_file() => {
+            sources.push(Source::File(input.to_path_buf()));
+            Ok(())
+        }
+        _ => Err(anyhow!("No such file or directory")),
+    }
+}
+
+fn list_directory(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            list_directory(&path, files)?;
+        } else if path.extension().is_some_and(|e| e == "class") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Reads the bytes of the class file, `archives` are the jars already opened by the thread
+fn read_source(
+    source: &Source,
+    archives: &mut HashMap<PathBuf, ZipArchive<File>>,
+) -> Result<Vec<u8>> {
+    match source {
+        Source::File(path) => {
+            std::fs::read(path).map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))
+        }
+        Source::JarEntry { jar, entry } => {
+            let archive = match archives.get_mut(jar) {
+                Some(archive) => archive,
+                None => {
+                    let archive = ZipArchive::new(File::open(jar)?)?;
+                    archives.entry(jar.clone()).or_insert(archive)
+                }
+            };
+            let mut file = archive.by_name(entry)?;
+            let mut bytes = Vec::with_capacity(file.size() as usize);
+            file.read_to_end(&mut bytes)?;
+            Ok(bytes)
+        }
+    }
+}
@@ -0,0 +1,579 @@
+use super::{Analysis, Direction};
+use crate::class_file::attributes::VerificationType;
+use crate::class_file::bytecode::ByteCode;
+use crate::class_file::constant_pool::{ConstantPool, Info, MethodRef};
+use crate::class_file::methods::Method;
+use crate::class_file::MethodDescriptor;
+use anyhow::{anyhow, Result};
+
+/// A value in the locals or on the operand stack of a `FrameAnalysis`
+pub trait AbstractValue: Clone + PartialEq + std::fmt::Debug {
+    /// Number of slots the value takes up, 2 for longs and doubles
+    fn size(&self) -> usize;
+}
+
+/// Decides the values `FrameAnalysis` puts in the locals and on the stack,
+/// the shuffling of the values (dup, swap, ...) is done by `FrameAnalysis`
+pub trait Interpreter {
+    type Value: AbstractValue;
+
+    /// Value of an unset local and of the second slot of a long or double
+    fn empty(&self) -> Self::Value;
+
+    /// Value of the local `slot` on method entry (`this` and the arguments)
+    fn entry(&self, slot: u16, t: &VerificationType) -> Self::Value;
+
+    /// The caught exception pushed at the start of a handler
+    fn exception(&self, handler_pc: u32, catch_type: &str) -> Self::Value;
+
+    /// An instruction that pushes a value computed from the popped `inputs` (bottom of the
+    /// stack first). `result` is the type as far as it is known without looking at the
+    /// inputs, `aaload` pushes a `java/lang/Object`.
+    fn operation(
+        &self,
+        pc: u32,
+        instruction: &ByteCode,
+        inputs: &[Self::Value],
+        result: &VerificationType,
+    ) -> Result<Self::Value>;
+
+    /// An instruction that pops `inputs` without pushing a value (branches, returns,
+    /// field and array stores, void methods)
+    fn consume(&self, _pc: u32, _instruction: &ByteCode, _inputs: &[Self::Value]) -> Result<()> {
+        Ok(())
+    }
+
+    /// A load pushes the returned value for the `value` of the local
+    fn load(&self, pc: u32, instruction: &ByteCode, value: &Self::Value) -> Result<Self::Value>;
+
+    /// A store (or `iinc`) sets the local to the returned value
+    fn store(&self, pc: u32, instruction: &ByteCode, value: &Self::Value) -> Result<Self::Value>;
+
+    /// Called after an `<init>` of `owner` on `receiver`, a returned value replaces every
+    /// copy of the receiver in the locals and on the stack
+    fn initialize(
+        &self,
+        _pc: u32,
+        _receiver: &Self::Value,
+        _owner: &str,
+    ) -> Result<Option<Self::Value>> {
+        Ok(None)
+    }
+
+    fn merge(&self, a: &Self::Value, b: &Self::Value) -> Self::Value;
+}
+
+/// The locals and the operand stack before or after an instruction. The locals have one
+/// entry per slot (the second slot of a long or double is `Interpreter::empty`) while the
+/// stack has one entry per value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct State<V> {
+    pub locals: Vec<V>,
+    pub stack: Vec<V>,
+}
+
+impl<V: AbstractValue> State<V> {
+    /// Size of the operand stack in slots
+    pub fn stack_size(&self) -> usize {
+        self.stack.iter().map(|v| v.size()).sum()
+    }
+
+    /// The values an invoke instruction pops, the receiver (if any) followed by
+    /// the arguments, when called on the state before the instruction
+    pub fn invoke_arguments(&self, instruction: &ByteCode, cp: &ConstantPool) -> Result<&[V]> {
+        let (descriptor, receiver) = match instruction {
+            ByteCode::InvokeDynamic(index) => (cp.invoke_dynamic(*index)?.descriptor, 0),
+            ByteCode::InvokeStatic(index) => (cp.method_ref(*index)?.descriptor, 0),
+            ByteCode::InvokeVirtual(index)
+            | ByteCode::InvokeSpecial(index)
+            | ByteCode::InvokeInterface(index, _) => (cp.method_ref(*index)?.descriptor, 1),
+            _ => return Err(anyhow!("{} is not an invoke", instruction.to_string())),
+        };
+        let count = MethodDescriptor::parse(&descriptor)?.parameters.len() + receiver;
+        if count > self.stack.len() {
+            return Err(anyhow!("Operand stack underflow"));
+        }
+        Ok(&self.stack[self.stack.len() - count..])
+    }
+}
+
+/// Simulates the operand stack and the locals of a method with the values of an
+/// `Interpreter`, forward from the arguments of the method
+pub struct FrameAnalysis<'a, I: Interpreter> {
+    interpreter: I,
+    cp: &'a ConstantPool,
+    entry: State<I::Value>,
+}
+
+impl<'a, I: Interpreter> FrameAnalysis<'a, I> {
+    pub fn new(
+        interpreter: I,
+        method: &Method,
+        class_name: &str,
+        cp: &'a ConstantPool,
+    ) -> Result<FrameAnalysis<'a, I>> {
+        let mut locals = Vec::new();
+        for t in method.initial_frame(class_name, cp)?.locals {
+            locals.push(interpreter.entry(locals.len() as u16, &t));
+            if t.size() == 2 {
+                locals.push(interpreter.empty());
+            }
+        }
+        let entry = State {
+            locals,
+            stack: Vec::new(),
+        };
+        Ok(FrameAnalysis {
+            interpreter,
+            cp,
+            entry,
+        })
+    }
+
+    pub fn interpreter(&self) -> &I {
+        &self.interpreter
+    }
+
+    fn pop(&self, state: &mut State<I::Value>) -> Result<I::Value> {
+        state.stack.pop().ok_or(anyhow!("Operand stack underflow"))
+    }
+
+    /// Pops `n` values, returned bottom of the stack first
+    fn pop_n(&self, state: &mut State<I::Value>, n: usize) -> Result<Vec<I::Value>> {
+        if n > state.stack.len() {
+            return Err(anyhow!("Operand stack underflow"));
+        }
+        Ok(state.stack.split_off(state.stack.len() - n))
+    }
+
+    fn local(&self, state: &State<I::Value>, index: u16) -> Result<I::Value> {
+        state
+            .locals
+            .get(index as usize)
+            .cloned()
+            .ok_or(anyhow!("Load of unset local {}", index))
+    }
+
+    fn set_local(&self, state: &mut State<I::Value>, index: u16, value: I::Value) {
+        let index = index as usize;
+        let size = value.size();
+        if state.locals.len() < index + size {
+            state.locals.resize(index + size, self.interpreter.empty());
+        }
+        // overwriting the second slot of a long or double invalidates it
+        if index > 0 && state.locals[index - 1].size() == 2 {
+            state.locals[index - 1] = self.interpreter.empty();
+        }
+        state.locals[index] = value;
+        if size == 2 {
+            state.locals[index + 1] = self.interpreter.empty();
+        }
+    }
+
+    /// The stack manipulation instructions only move values around
+    fn shuffle(&self, state: &mut State<I::Value>, instruction: &ByteCode) -> Result<()> {
+        let v1 = self.pop(state)?;
+        let values = match instruction {
+            ByteCode::Pop => vec![],
+            ByteCode::Pop2 if v1.size() == 2 => vec![],
+            ByteCode::Pop2 => {
+                self.pop(state)?;
+                vec![]
+            }
+            ByteCode::Duplicate => vec![v1.clone(), v1],
+            ByteCode::DupX1 => {
+                let v2 = self.pop(state)?;
+                vec![v1.clone(), v2, v1]
+            }
+            ByteCode::DupX2 => {
+                let v2 = self.pop(state)?;
+                if v2.size() == 2 {
+                    vec![v1.clone(), v2, v1]
+                } else {
+                    let v3 = self.pop(state)?;
+                    vec![v1.clone(), v3, v2, v1]
+                }
+            }
+            ByteCode::Dup2 if v1.size() == 2 => vec![v1.clone(), v1],
+            ByteCode::Dup2 => {
+                let v2 = self.pop(state)?;
+                vec![v2.clone(), v1.clone(), v2, v1]
+            }
+            ByteCode::Dup2X1 if v1.size() == 2 => {
+                let v2 = self.pop(state)?;
+                vec![v1.clone(), v2, v1]
+            }
+            ByteCode::Dup2X1 => {
+                let v2 = self.pop(state)?;
+                let v3 = self.pop(state)?;
+                vec![v2.clone(), v1.clone(), v3, v2, v1]
+            }
+            ByteCode::Dup2X2 if v1.size() == 2 => {
+                let v2 = self.pop(state)?;
+                if v2.size() == 2 {
+                    vec![v1.clone(), v2, v1]
+                } else {
+                    let v3 = self.pop(state)?;
+                    vec![v1.clone(), v3, v2, v1]
+                }
+            }
+            ByteCode::Dup2X2 => {
+                let v2 = self.pop(state)?;
+                let v3 = self.pop(state)?;
+                if v3.size() == 2 {
+                    vec![v2.clone(), v1.clone(), v3, v2, v1]
+                } else {
+                    let v4 = self.pop(state)?;
+                    vec![v2.clone(), v1.clone(), v4, v3, v2, v1]
+                }
+            }
+            ByteCode::Swap => {
+                let v2 = self.pop(state)?;
+                vec![v1, v2]
+            }
+            _ => unreachable!(),
+        };
+        state.stack.extend(values);
+        Ok(())
+    }
+
+    fn invoke(
+        &self,
+        state: &mut State<I::Value>,
+        pc: u32,
+        instruction: &ByteCode,
+        method: &MethodRef,
+        has_receiver: bool,
+    ) -> Result<()> {
+        let count = MethodDescriptor::parse(&method.descriptor)?
+            .parameters
+            .len()
+            + has_receiver as usize;
+        let inputs = self.pop_n(state, count)?;
+        if has_receiver && method.name == "<init>" {
+            let receiver = &inputs[0];
+            if let Some(initialized) = self.interpreter.initialize(pc, receiver, &method.owner)? {
+                for value in state.locals.iter_mut().chain(state.stack.iter_mut()) {
+                    if value == receiver {
+                        *value = initialized.clone();
+                    }
+                }
+            }
+        }
+        match return_type(&method.descriptor)? {
+            Some(t) => {
+                let value = self.interpreter.operation(pc, instruction, &inputs, &t)?;
+                state.stack.push(value);
+            }
+            None => self.interpreter.consume(pc, instruction, &inputs)?,
+        }
+        Ok(())
+    }
+
+    /// Number of values popped by the remaining instructions and the type they push
+    fn effect(&self, pc: u32, instruction: &ByteCode) -> Result<(usize, Option<VerificationType>)> {
+        use VerificationType::*;
+        let object = |name: &str| Some(Object(name.to_string()));
+        let effect = match instruction {
+            ByteCode::Nop | ByteCode::Goto(_) | ByteCode::GotoW(_) | ByteCode::Return => (0, None),
+            ByteCode::AConstNull => (0, Some(Null)),
+            ByteCode::IConstn(_) | ByteCode::BiPush(_) | ByteCode::SiPush(_) => (0, Some(Integer)),
+            ByteCode::LConstn(_) => (0, Some(Long)),
+            ByteCode::FConstn(_) => (0, Some(Float)),
+            ByteCode::DConstn(_) => (0, Some(Double)),
+            ByteCode::Ldc(index) => (0, Some(self.constant_type(*index as u16)?)),
+            ByteCode::LdcW(index) | ByteCode::Ldc2W(index) => {
+                (0, Some(self.constant_type(*index)?))
+            }
+            ByteCode::IALoad | ByteCode::BALoad | ByteCode::CALoad | ByteCode::SALoad => {
+                (2, Some(Integer))
+            }
+            ByteCode::LALoad => (2, Some(Long)),
+            ByteCode::FALoad => (2, Some(Float)),
+            ByteCode::DALoad => (2, Some(Double)),
+            ByteCode::AALoad => (2, object("java/lang/Object")),
+            ByteCode::IAStore
+            | ByteCode::LAStore
+            | ByteCode::FAStore
+            | ByteCode::DAStore
+            | ByteCode::AAStore
+            | ByteCode::BAStore
+            | ByteCode::CAStore
+            | ByteCode::SAStore => (3, None),
+            ByteCode::IAdd
+            | ByteCode::ISub
+            | ByteCode::IMul
+            | ByteCode::IDiv
+            | ByteCode::IRem
+            | ByteCode::IShl
+            | ByteCode::IShr
+            | ByteCode::IUShr
+            | ByteCode::IAnd
+            | ByteCode::IOr
+            | ByteCode::IXor
+            | ByteCode::Lcmp
+            | ByteCode::FCmpl
+            | ByteCode::FCmpg
+            | ByteCode::DCmpl
+            | ByteCode::DCmpg => (2, Some(Integer)),
+            ByteCode::LAdd
+            | ByteCode::LSub
+            | ByteCode::LMul
+            | ByteCode::LDiv
+            | ByteCode::LRem
+            | ByteCode::LShl
+            | ByteCode::LShr
+            | ByteCode::LUShr
+            | ByteCode::LAnd
+            | ByteCode::LOr
+            | ByteCode::LXor => (2, Some(Long)),
+            ByteCode::FAdd | ByteCode::FSub | ByteCode::FMul | ByteCode::FDiv | ByteCode::FRem => {
+                (2, Some(Float))
+            }
+            ByteCode::DAdd | ByteCode::DSub | ByteCode::DMul | ByteCode::DDiv | ByteCode::DRem => {
+                (2, Some(Double))
+            }
+            ByteCode::INeg
+            | ByteCode::L2i
+            | ByteCode::F2I
+            | ByteCode::D2I
+            | ByteCode::I2B
+            | ByteCode::I2C
+            | ByteCode::I2S
+            | ByteCode::ArrayLength
+            | ByteCode::InstanceOf(_) => (1, Some(Integer)),
+            ByteCode::LNeg | ByteCode::I2L | ByteCode::F2L | ByteCode::D2L => (1, Some(Long)),
+            ByteCode::FNeg | ByteCode::I2F | ByteCode::L2F | ByteCode::D2F => (1, Some(Float)),
+            ByteCode::DNeg | ByteCode::I2D | ByteCode::L2D | ByteCode::F2D => (1, Some(Double)),
+            ByteCode::Ifeq(_)
+            | ByteCode::Ifne(_)
+            | ByteCode::Iflt(_)
+            | ByteCode::Ifge(_)
+            | ByteCode::Ifgt(_)
+            | ByteCode::Ifle(_)
+            | ByteCode::IfNull(_)
+            | ByteCode::IfNonNull(_)
+            | ByteCode::TableSwitch(_)
+            | ByteCode::LookupSwitch(_)
+            | ByteCode::IReturn
+            | ByteCode::LReturn
+            | ByteCode::FReturn
+            | ByteCode::DReturn
+            | ByteCode::AReturn
+            | ByteCode::Athrow
+            | ByteCode::MonitorEnter
+            | ByteCode::MonitorExit
+            | ByteCode::PutStatic(_) => (1, None),
+            ByteCode::IfICmpEq(_)
+            | ByteCode::IfICmpNe(_)
+            | ByteCode::IfICmpLt(_)
+            | ByteCode::IfICmpGe(_)
+            | ByteCode::IfICmpGt(_)
+            | ByteCode::IfICmpLe(_)
+            | ByteCode::IfACmpEq(_)
+            | ByteCode::IfACmpNe(_)
+            | ByteCode::PutField(_) => (2, None),
+            ByteCode::GetStatic(index) => {
+                let field = self.cp.field_ref(*index)?;
+                (
+                    0,
+                    Some(VerificationType::from_descriptor(&field.descriptor)?),
+                )
+            }
+            ByteCode::GetField(index) => {
+                let field = self.cp.field_ref(*index)?;
+                (
+                    1,
+                    Some(VerificationType::from_descriptor(&field.descriptor)?),
+                )
+            }
+            ByteCode::New(_) => (0, Some(Uninitialized(pc as u16))),
+            ByteCode::NewArray(atype) => {
+                let component = match atype {
+                    4 => 'Z',
+                    5 => 'C',
+                    6 => 'F',
+                    7 => 'D',
+                    8 => 'B',
+                    9 => 'S',
+                    10 => 'I',
+                    11 => 'J',
+                    _ => return Err(anyhow!("Invalid newarray type {}", atype)),
+                };
+                (1, Some(Object(format!("[{}", component))))
+            }
+            ByteCode::ANewArray(index) => {
+                let component = self.cp.class_name(*index)?;
+                if component.starts_with('[') {
+                    (1, Some(Object(format!("[{}", component))))
+                } else {
+                    (1, Some(Object(format!("[L{};", component))))
+                }
+            }
+            ByteCode::CheckCast(index) => {
+                (1, Some(Object(self.cp.class_name(*index)?.to_string())))
+            }
+            ByteCode::MultiANewArray(index, dimensions) => (
+                *dimensions as usize,
+                Some(Object(self.cp.class_name(*index)?.to_string())),
+            ),
+            _ => return Err(anyhow!("No simple stack effect for {:?}", instruction)),
+        };
+        Ok(effect)
+    }
+
+    /// Type pushed by ldc, ldc_w and ldc2_w
+    fn constant_type(&self, index: u16) -> Result<VerificationType> {
+        use VerificationType::*;
+        let class = |name: &str| Object(name.to_string());
+        let t = match self.cp.get(index) {
+            Info::IntegerInfo(_) => Integer,
+            Info::FloatInfo(_) => Float,
+            Info::LongInfo(_) => Long,
+            Info::DoubleInfo(_) => Double,
+            Info::StringInfo(_) => class("java/lang/String"),
+            Info::ClassInfo(_) => class("java/lang/Class"),
+            Info::MethodTypeInfo(_) => class("java/lang/invoke/MethodType"),
+            Info::MethodHandleInfo(_) => class("java/lang/invoke/MethodHandle"),
+            info => return Err(anyhow!("ldc of unloadable constant {:?}", info)),
+        };
+        Ok(t)
+    }
+}
+
+impl<I: Interpreter> Analysis for FrameAnalysis<'_, I> {
+    type State = State<I::Value>;
+
+    fn direction(&self) -> Direction {
+        Direction::Forward
+    }
+
+    fn boundary(&self) -> Result<Self::State> {
+        Ok(self.entry.clone())
+    }
+
+    fn join(&self, state: &mut Self::State, other: &Self::State) -> Result<()> {
+        if state.stack.len() != other.stack.len() {
+            return Err(anyhow!(
+                "Inconsistent stack height: {} != {}",
+                state.stack.len(),
+                other.stack.len()
+            ));
+        }
+
+        let empty = self.interpreter.empty();
+        let len = state.locals.len().max(other.locals.len());
+        state.locals.resize(len, empty.clone());
+        for (slot, local) in state.locals.iter_mut().enumerate() {
+            let other = other.locals.get(slot).unwrap_or(&empty);
+            *local = self.interpreter.merge(local, other);
+        }
+        // a long or double that lost its second slot is unusable
+        for slot in 0..len.saturating_sub(1) {
+            if state.locals[slot].size() == 2 && state.locals[slot + 1] != empty {
+                state.locals[slot] = empty.clone();
+            }
+        }
+        for (value, other) in state.stack.iter_mut().zip(&other.stack) {
+            *value = self.interpreter.merge(value, other);
+        }
+        Ok(())
+    }
+
+    fn transfer(&self, pc: u32, instruction: &ByteCode, state: &mut Self::State) -> Result<()> {
+        match instruction {
+            ByteCode::ILoad(index)
+            | ByteCode::Lload(index)
+            | ByteCode::FLoad(index)
+            | ByteCode::DLoad(index)
+            | ByteCode::ALoad(index) => {
+                let local = self.local(state, *index)?;
+                let value = self.interpreter.load(pc, instruction, &local)?;
+                state.stack.push(value);
+            }
+            ByteCode::IStore(index)
+            | ByteCode::LStore(index)
+            | ByteCode::FStore(index)
+            | ByteCode::DStore(index)
+            | ByteCode::AStore(index) => {
+                let value = self.pop(state)?;
+                let value = self.interpreter.store(pc, instruction, &value)?;
+                self.set_local(state, *index, value);
+            }
+            ByteCode::IInc(index, _) => {
+                let local = self.local(state, *index)?;
+                let value = self.interpreter.store(pc, instruction, &local)?;
+                self.set_local(state, *index, value);
+            }
+            ByteCode::Pop
+            | ByteCode::Pop2
+            | ByteCode::Duplicate
+            | ByteCode::DupX1
+            | ByteCode::DupX2
+            | ByteCode::Dup2
+            | ByteCode::Dup2X1
+            | ByteCode::Dup2X2
+            | ByteCode::Swap => self.shuffle(state, instruction)?,
+            ByteCode::InvokeVirtual(index)
+            | ByteCode::InvokeSpecial(index)
+            | ByteCode::InvokeInterface(index, _) => {
+                let method = self.cp.method_ref(*index)?;
+                self.invoke(state, pc, instruction, &method, true)?;
+            }
+            ByteCode::InvokeStatic(index) => {
+                let method = self.cp.method_ref(*index)?;
+                self.invoke(state, pc, instruction, &method, false)?;
+            }
+            ByteCode::InvokeDynamic(index) => {
+                let call_site = self.cp.invoke_dynamic(*index)?;
+                // a call site has no owner
+                let method = MethodRef {
+                    owner: String::new(),
+                    name: call_site.name,
+                    descriptor: call_site.descriptor,
+                    is_interface: false,
+                };
+                self.invoke(state, pc, instruction, &method, false)?;
+            }
+            ByteCode::Jsr(_) | ByteCode::JsrW(_) | ByteCode::Ret(_) => {
+                return Err(anyhow!(
+                    "jsr and ret are not supported (class files before version 50)"
+                ))
+            }
+            ByteCode::Generic(opcode) => {
+                return Err(anyhow!("Reserved opcode 0x{:x} in code", opcode))
+            }
+            _ => {
+                let (pops, result) = self.effect(pc, instruction)?;
+                let inputs = self.pop_n(state, pops)?;
+                match result {
+                    Some(t) => {
+                        let value = self.interpreter.operation(pc, instruction, &inputs, &t)?;
+                        state.stack.push(value);
+                    }
+                    None => self.interpreter.consume(pc, instruction, &inputs)?,
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn exception(
+        &self,
+        state: &Self::State,
+        handler_pc: u32,
+        catch_type: Option<&str>,
+    ) -> Self::State {
+        let catch_type = catch_type.unwrap_or("java/lang/Throwable");
+        State {
+            locals: state.locals.clone(),
+            stack: vec![self.interpreter.exception(handler_pc, catch_type)],
+        }
+    }
+}
+
+/// The type a method descriptor returns, `None` for void
+fn return_type(descriptor: &str) -> Result<Option<VerificationType>> {
+    let return_type = MethodDescriptor::parse(descriptor)?.return_type;
+    Ok(return_type.as_ref().map(VerificationType::from_field_type))
+}
@@ -0,0 +1,56 @@
+use super::{solve, Analysis, DataflowResult, Direction};
+use crate::class_file::bytecode::ByteCode;
+use crate::class_file::constant_pool::ConstantPool;
+use crate::class_file::methods::Method;
+use anyhow::Result;
+use std::collections::BTreeSet;
+
+/// Backward analysis of the local variable slots whose current value is read later on.
+/// A long or double local is identified by its first slot.
+pub struct Liveness;
+
+/// The live locals before (live-in) and after (live-out) every instruction
+pub fn liveness(method: &Method, cp: &ConstantPool) -> Result<DataflowResult<BTreeSet<u16>>> {
+    let code = method.code()?;
+    solve(&Liveness, code, cp)
+}
+
+impl Analysis for Liveness {
+    type State = BTreeSet<u16>;
+
+    fn direction(&self) -> Direction {
+        Direction::Backward
+    }
+
+    fn boundary(&self) -> Result<BTreeSet<u16>> {
+        Ok(BTreeSet::new())
+    }
+
+    fn join(&self, state: &mut BTreeSet<u16>, other: &BTreeSet<u16>) -> Result<()> {
+        state.extend(other);
+        Ok(())
+    }
+
+    fn transfer(&self, _pc: u32, instruction: &ByteCode, state: &mut BTreeSet<u16>) -> Result<()> {
+        match instruction {
+            ByteCode::IStore(index)
+            | ByteCode::LStore(index)
+            | ByteCode::FStore(index)
+            | ByteCode::DStore(index)
+            | ByteCode::AStore(index) => {
+                state.remove(index);
+            }
+            ByteCode::ILoad(index)
+            | ByteCode::Lload(index)
+            | ByteCode::FLoad(index)
+            | ByteCode::DLoad(index)
+            | ByteCode::ALoad(index)
+            | ByteCode::IInc(index, _)
+            | ByteCode::Ret(index) => {
+                state.insert(*index);
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,297 @@
+//! Dataflow analyses over the instructions of a method.
+//!
+//! `solve` runs an `Analysis` to a fixed point, forward or backward, on the graph of
+//! instructions (with edges for jumps, fall through and exception handlers) and returns
+//! the state before and after every reachable instruction.
+//!
+//! Analyses that need the operand stack and the locals implement an `Interpreter` for
+//! their value domain and are run through `FrameAnalysis`, which simulates the stack
+//! and local variable slots. Built in are:
+//! - `type_states`: the verification types of the locals and the stack
+//! - `reaching_definitions`: which stores, parameters and instructions produced the
+//!   values in the locals and on the stack
+//! - `liveness`: the local slots that are read later on
+
+mod frame_analysis;
+mod liveness;
+mod reaching_definitions;
+mod type_state;
+
+pub use frame_analysis::{AbstractValue, FrameAnalysis, Interpreter, State};
+pub use liveness::{liveness, Liveness};
+pub use reaching_definitions::{
+    reaching_definitions, Definition, Definitions, ReachingDefinitions,
+};
+pub use type_state::{type_states, TypeState};
+
+use super::attributes::CodeAttribute;
+use super::bytecode::ByteCode;
+use super::constant_pool::ConstantPool;
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    Forward,
+    Backward,
+}
+
+pub trait Analysis {
+    type State: Clone + PartialEq;
+
+    fn direction(&self) -> Direction;
+
+    /// State at the method entry for forward analyses. For backward analyses the state
+    /// after every instruction starts as this before the successors are joined into it,
+    /// so it should be the empty state.
+    fn boundary(&self) -> Result<Self::State>;
+
+    /// Merges `other` into `state` where control flow joins
+    fn join(&self, state: &mut Self::State, other: &Self::State) -> Result<()>;
+
+    /// Applies the effect of the instruction, in a backward analysis `state` is the
+    /// state after the instruction and becomes the state before it
+    fn transfer(&self, pc: u32, instruction: &ByteCode, state: &mut Self::State) -> Result<()>;
+
+    /// State passed along the edge to the exception handler at `handler_pc`. In a forward
+    /// analysis `state` is the one before the throwing instruction, in a backward analysis
+    /// it is the one at the start of the handler.
+    fn exception(
+        &self,
+        state: &Self::State,
+        _handler_pc: u32,
+        _catch_type: Option<&str>,
+    ) -> Self::State {
+        state.clone()
+    }
+}
+
+/// The fixed point of an analysis, states are `None` for unreachable instructions
+#[derive(Debug)]
+pub struct DataflowResult<S> {
+    pcs: Vec<u32>,
+    before: Vec<Option<S>>,
+    after: Vec<Option<S>>,
+}
+
+impl<S> DataflowResult<S> {
+    /// pcs of all instructions of the method
+    pub fn pcs(&self) -> &[u32] {
+        &self.pcs
+    }
+
+    /// State before the instruction at `pc` (live-in for liveness)
+    pub fn before(&self, pc: u32) -> Option<&S> {
+        let i = self.pcs.binary_search(&pc).ok()?;
+        self.before[i].as_ref()
+    }
+
+    /// State after the instruction at `pc` (live-out for liveness)
+    pub fn after(&self, pc: u32) -> Option<&S> {
+        let i = self.pcs.binary_search(&pc).ok()?;
+        self.after[i].as_ref()
+    }
+}
+
+/// Edges between the instructions, by index into the instruction list
+struct InstructionGraph {
+    successors: Vec<Vec<usize>>,
+    /// handlers covering the instruction with their catch type
+    handlers: Vec<Vec<(usize, Option<String>)>>,
+    /// the last instruction falls through to the end of the code
+    falls_off: bool,
+}
+
+impl InstructionGraph {
+    fn build(
+        instructions: &[(u32, &ByteCode)],
+        code: &CodeAttribute,
+        cp: &ConstantPool,
+    ) -> Result<Self> {
+        let index_of = instructions
+            .iter()
+            .enumerate()
+            .map(|(i, (pc, _))| (*pc, i))
+            .collect::<HashMap<_, _>>();
+        let index = |pc: u32| {
+            index_of
+                .get(&pc)
+                .copied()
+                .ok_or(anyhow!("Jump to {} is not the start of an instruction", pc))
+        };
+
+        let mut successors = Vec::with_capacity(instructions.len());
+        let mut falls_off = false;
+        for (i, (pc, instruction)) in instructions.iter().enumerate() {
+            let mut targets = instruction
+                .branch_targets(*pc)
+                .into_iter()
+                .map(index)
+                .collect::<Result<Vec<_>>>()?;
+            if instruction.falls_through() {
+                if i + 1 < instructions.len() {
+                    targets.push(i + 1);
+                } else {
+                    falls_off = true;
+                }
+            }
+            targets.dedup();
+            successors.push(targets);
+        }
+
+        let mut handlers = vec![Vec::new(); instructions.len()];
+        for entry in code.exception_table() {
+            let handler = index(entry.handler_pc as u32)?;
+            let catch_type = match entry.catch_type {
+                0 => None,
+                index => Some(cp.class_name(index)?.to_string()),
+            };
+            let range = entry.start_pc as u32..entry.end_pc as u32;
+            for (i, (pc, _)) in instructions.iter().enumerate() {
+                if range.contains(pc) {
+                    handlers[i].push((handler, catch_type.clone()));
+                }
+            }
+        }
+
+        Ok(InstructionGraph {
+            successors,
+            handlers,
+            falls_off,
+        })
+    }
+}
+
+/// Runs the analysis on the code until the states don't change anymore
+pub fn solve<A: Analysis>(
+    analysis: &A,
+    code: &CodeAttribute,
+    cp: &ConstantPool,
+) -> Result<DataflowResult<A::State>> {
+    let instructions = code.instructions().collect::<Vec<_>>();
+    let graph = InstructionGraph::build(&instructions, code, cp)?;
+    let pcs = instructions.iter().map(|(pc, _)| *pc).collect::<Vec<_>>();
+    let mut result = DataflowResult {
+        pcs,
+        before: vec![None; instructions.len()],
+        after: vec![None; instructions.len()],
+    };
+    if instructions.is_empty() {
+        return Ok(result);
+    }
+
+    match analysis.direction() {
+        Direction::Forward => solve_forward(analysis, &instructions, &graph, &mut result)?,
+        Direction::Backward => solve_backward(analysis, &instructions, &graph, &mut result)?,
+    }
+    Ok(result)
+}
+
+fn solve_forward<A: Analysis>(
+    analysis: &A,
+    instructions: &[(u32, &ByteCode)],
+    graph: &InstructionGraph,
+    result: &mut DataflowResult<A::State>,
+) -> Result<()> {
+    result.before[0] = Some(analysis.boundary()?);
+    let mut work_list = vec![0];
+    while let Some(i) = work_list.pop() {
+        let (pc, instruction) = instructions[i];
+        let input = result.before[i].clone().unwrap();
+
+        for (handler, catch_type) in &graph.handlers[i] {
+            let (handler_pc, _) = instructions[*handler];
+            let state = analysis.exception(&input, handler_pc, catch_type.as_deref());
+            if join_into(analysis, &mut result.before[*handler], state)? {
+                work_list.push(*handler);
+            }
+        }
+
+        let mut output = input;
+        analysis
+            .transfer(pc, instruction, &mut output)
+            .map_err(|e| anyhow!("{} at pc {}", e, pc))?;
+        if graph.falls_off && i + 1 == instructions.len() {
+            return Err(anyhow!("Execution falls off the end of the code at {}", pc));
+        }
+        for successor in &graph.successors[i] {
+            if join_into(analysis, &mut result.before[*successor], output.clone())? {
+                work_list.push(*successor);
+            }
+        }
+        result.after[i] = Some(output);
+    }
+    Ok(())
+}
+
+fn solve_backward<A: Analysis>(
+    analysis: &A,
+    instructions: &[(u32, &ByteCode)],
+    graph: &InstructionGraph,
+    result: &mut DataflowResult<A::State>,
+) -> Result<()> {
+    let mut predecessors = vec![Vec::new(); instructions.len()];
+    for i in 0..instructions.len() {
+        for successor in &graph.successors[i] {
+            predecessors[*successor].push(i);
+        }
+        for (handler, _) in &graph.handlers[i] {
+            predecessors[*handler].push(i);
+        }
+    }
+
+    // start with the last instruction, most code flows forward
+    let mut work_list = (0..instructions.len()).collect::<Vec<_>>();
+    while let Some(i) = work_list.pop() {
+        let (pc, instruction) = instructions[i];
+
+        let mut output = analysis.boundary()?;
+        for successor in &graph.successors[i] {
+            if let Some(state) = &result.before[*successor] {
+                analysis.join(&mut output, state)?;
+            }
+        }
+        for (handler, catch_type) in &graph.handlers[i] {
+            if let Some(state) = &result.before[*handler] {
+                let (handler_pc, _) = instructions[*handler];
+                let state = analysis.exception(state, handler_pc, catch_type.as_deref());
+                analysis.join(&mut output, &state)?;
+            }
+        }
+
+        let mut input = output.clone();
+        analysis
+            .transfer(pc, instruction, &mut input)
+            .map_err(|e| anyhow!("{} at pc {}", e, pc))?;
+        result.after[i] = Some(output);
+        if result.before[i].as_ref() != Some(&input) {
+            result.before[i] = Some(input);
+            work_list.extend(&predecessors[i]);
+        }
+    }
+    Ok(())
+}
+
+/// Joins `state` into `target`, returns whether `target` changed
+fn join_into<A: Analysis>(
+    analysis: &A,
+    target: &mut Option<A::State>,
+    state: A::State,
+) -> Result<bool> {
+    match target {
+        None => {
+            *target = Some(state);
+            Ok(true)
+        }
+        Some(current) => {
+            let mut merged = current.clone();
+            analysis.join(&mut merged, &state)?;
+            if merged == *current {
+                Ok(false)
+            } else {
+                *current = merged;
+                Ok(true)
+            }
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use super::{solve, AbstractValue, DataflowResult, FrameAnalysis, Interpreter, State};
+use crate::class_file::attributes::VerificationType;
+use crate::class_file::bytecode::ByteCode;
+use crate::class_file::constant_pool::ConstantPool;
+use crate::class_file::methods::Method;
+use anyhow::Result;
+use std::collections::BTreeSet;
+
+/// Where a value was defined
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Definition {
+    /// `this` or an argument in the local `slot` on method entry
+    Parameter(u16),
+    /// the instruction at this pc stored the local or pushed the value
+    Instruction(u32),
+    /// the exception caught by the handler at this pc
+    Exception(u32),
+}
+
+/// All definitions that can reach a local or a stack entry
+#[derive(Debug, Clone, PartialEq)]
+pub struct Definitions {
+    pub size: usize,
+    pub definitions: BTreeSet<Definition>,
+}
+
+/// Reaching definitions of the locals, which also tracks the definitions of the stack values.
+///
+/// A local is defined by the stores (and `iinc`) to it, a load pushes the definitions of
+/// the local, so the value of `aload_1; invokevirtual` comes from the stores to local 1
+/// (or the parameter). Values computed by an instruction are defined by that instruction.
+/// Stack manipulations like `dup` and `swap` keep the definitions of the values they copy.
+pub struct ReachingDefinitions;
+
+/// The definitions reaching the locals and stack entries before and after every instruction
+pub fn reaching_definitions(
+    method: &Method,
+    class_name: &str,
+    cp: &ConstantPool,
+) -> Result<DataflowResult<State<Definitions>>> {
+    let code = method.code()?;
+    let analysis = FrameAnalysis::new(ReachingDefinitions, method, class_name, cp)?;
+    solve(&analysis, code, cp)
+}
+
+impl Definitions {
+    fn new(size: usize, definition: Definition) -> Definitions {
+        Definitions {
+            size,
+            definitions: BTreeSet::from([definition]),
+        }
+    }
+}
+
+impl AbstractValue for Definitions {
+    fn size(&self) -> usize {
+        self.size
+    }
+}
+
+impl Interpreter for ReachingDefinitions {
+    type Value = Definitions;
+
+    fn empty(&self) -> Definitions {
+        Definitions {
+            size: 1,
+            definitions: BTreeSet::new(),
+        }
+    }
+
+    fn entry(&self, slot: u16, t: &VerificationType) -> Definitions {
+        Definitions::new(t.size(), Definition::Parameter(slot))
+    }
+
+    fn exception(&self, handler_pc: u32, _catch_type: &str) -> Definitions {
+        Definitions::new(1, Definition::Exception(handler_pc))
+    }
+
+    fn operation(
+        &self,
+        pc: u32,
+        _instruction: &ByteCode,
+        _inputs: &[Definitions],
+        result: &VerificationType,
+    ) -> Result<Definitions> {
+        Ok(Definitions::new(result.size(), Definition::Instruction(pc)))
+    }
+
+    fn load(&self, _pc: u32, _instruction: &ByteCode, value: &Definitions) -> Result<Definitions> {
+        Ok(value.clone())
+    }
+
+    fn store(&self, pc: u32, instruction: &ByteCode, value: &Definitions) -> Result<Definitions> {
+        let size = match instruction {
+            ByteCode::IInc(_, _) => 1,
+            _ => value.size,
+        };
+        Ok(Definitions::new(size, Definition::Instruction(pc)))
+    }
+
+    fn merge(&self, a: &Definitions, b: &Definitions) -> Definitions {
+        Definitions {
+            size: if a.size == b.size { a.size } else { 1 },
+            definitions: a.definitions.union(&b.definitions).cloned().collect(),
+        }
+    }
+}
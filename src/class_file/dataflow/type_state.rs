@@ -0,0 +1,164 @@
+use super::{solve, DataflowResult, FrameAnalysis, Interpreter, State};
+use crate::class_file::attributes::VerificationType;
+use crate::class_file::bytecode::ByteCode;
+use crate::class_file::constant_pool::ConstantPool;
+use crate::class_file::frames::ClassHierarchy;
+use crate::class_file::methods::Method;
+use anyhow::{anyhow, Result};
+
+const OBJECT: &str = "java/lang/Object";
+
+/// Infers the verification types of the locals and the stack. Where paths join, reference
+/// types are merged to their common super class and incompatible types become `Top`.
+pub struct TypeState<'a> {
+    class_name: &'a str,
+    hierarchy: &'a dyn ClassHierarchy,
+}
+
+/// The types before and after every instruction of the method
+pub fn type_states(
+    method: &Method,
+    class_name: &str,
+    cp: &ConstantPool,
+    hierarchy: &dyn ClassHierarchy,
+) -> Result<DataflowResult<State<VerificationType>>> {
+    let code = method.code()?;
+    let analysis = FrameAnalysis::new(
+        TypeState::new(class_name, hierarchy),
+        method,
+        class_name,
+        cp,
+    )?;
+    solve(&analysis, code, cp)
+}
+
+impl<'a> TypeState<'a> {
+    pub fn new(class_name: &'a str, hierarchy: &'a dyn ClassHierarchy) -> TypeState<'a> {
+        TypeState {
+            class_name,
+            hierarchy,
+        }
+    }
+
+    /// Common super type of two class names or array descriptors
+    fn merge_references(&self, a: &str, b: &str) -> String {
+        if a == b {
+            return a.to_string();
+        }
+        match (a.strip_prefix('['), b.strip_prefix('[')) {
+            (Some(a), Some(b)) => {
+                // arrays of references are covariant, `[LA;` and `[LB;` merge to `[LC;`
+                let component = |c: &str| match c.strip_prefix('L') {
+                    Some(name) => Some(name.trim_end_matches(';').to_string()),
+                    None if c.starts_with('[') => Some(c.to_string()),
+                    None => None,
+                };
+                match (component(a), component(b)) {
+                    (Some(a), Some(b)) => {
+                        let merged = self.merge_references(&a, &b);
+                        if merged.starts_with('[') {
+                            format!("[{}", merged)
+                        } else {
+                            format!("[L{};", merged)
+                        }
+                    }
+                    _ => OBJECT.to_string(),
+                }
+            }
+            (None, None) => self.hierarchy.common_super_class(a, b),
+            _ => OBJECT.to_string(),
+        }
+    }
+}
+
+impl super::AbstractValue for VerificationType {
+    fn size(&self) -> usize {
+        VerificationType::size(self)
+    }
+}
+
+impl Interpreter for TypeState<'_> {
+    type Value = VerificationType;
+
+    fn empty(&self) -> VerificationType {
+        VerificationType::Top
+    }
+
+    fn entry(&self, _slot: u16, t: &VerificationType) -> VerificationType {
+        t.clone()
+    }
+
+    fn exception(&self, _handler_pc: u32, catch_type: &str) -> VerificationType {
+        VerificationType::Object(catch_type.to_string())
+    }
+
+    fn operation(
+        &self,
+        _pc: u32,
+        instruction: &ByteCode,
+        inputs: &[VerificationType],
+        result: &VerificationType,
+    ) -> Result<VerificationType> {
+        if let ByteCode::AALoad = instruction {
+            let component = match &inputs[0] {
+                VerificationType::Object(array) if array.starts_with('[') => {
+                    VerificationType::from_descriptor(&array[1..])?
+                }
+                VerificationType::Null => VerificationType::Null,
+                _ => result.clone(),
+            };
+            return Ok(component);
+        }
+        Ok(result.clone())
+    }
+
+    fn load(
+        &self,
+        _pc: u32,
+        _instruction: &ByteCode,
+        value: &VerificationType,
+    ) -> Result<VerificationType> {
+        Ok(value.clone())
+    }
+
+    fn store(
+        &self,
+        _pc: u32,
+        _instruction: &ByteCode,
+        value: &VerificationType,
+    ) -> Result<VerificationType> {
+        Ok(value.clone())
+    }
+
+    fn initialize(
+        &self,
+        _pc: u32,
+        receiver: &VerificationType,
+        owner: &str,
+    ) -> Result<Option<VerificationType>> {
+        match receiver {
+            // super() or this() in a constructor initializes `this`
+            VerificationType::UninitializedThis => {
+                Ok(Some(VerificationType::Object(self.class_name.to_string())))
+            }
+            VerificationType::Uninitialized(_) => {
+                Ok(Some(VerificationType::Object(owner.to_string())))
+            }
+            _ => Err(anyhow!(
+                "<init> of {} called on an initialized object",
+                owner
+            )),
+        }
+    }
+
+    fn merge(&self, a: &VerificationType, b: &VerificationType) -> VerificationType {
+        use VerificationType::*;
+        match (a, b) {
+            _ if a == b => a.clone(),
+            (Null, Object(_)) => b.clone(),
+            (Object(_), Null) => a.clone(),
+            (Object(a), Object(b)) => Object(self.merge_references(a, b)),
+            _ => Top,
+        }
+    }
+}
@@ -0,0 +1,797 @@
+//! The Java syntax tree methods are decompiled to
+
+use crate::class_file::FieldType;
+
+/// Index of a variable in `MethodBody::vars`
+pub(super) type VarId = usize;
+
+/// Identifies the loop, switch or other statement a `break` or `continue` jumps out of
+pub(super) type Label = usize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Shl,
+    Shr,
+    UShr,
+    And,
+    Or,
+    Xor,
+    Eq,
+    Ne,
+    Lt,
+    Ge,
+    Gt,
+    Le,
+    LogicalAnd,
+    LogicalOr,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum UnaryOp {
+    Neg,
+    Not,
+    BitNot,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum Expr {
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    Bool(bool),
+    Char(u16),
+    String(String),
+    Null,
+    /// `Foo.class`
+    Class(FieldType),
+    Var(VarId),
+    This,
+    /// `Outer.this` in an inner class, with the binary name of the outer class
+    OuterThis(String),
+    /// The exception at the start of a handler
+    Caught,
+    /// A field of the outer method captured by an anonymous class, `val$name`
+    Captured(String),
+    StaticField(Member),
+    Field(Box<Expr>, Member),
+    ArrayElement(Box<Expr>, Box<Expr>),
+    ArrayLength(Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    Unary(UnaryOp, Box<Expr>),
+    Cast(FieldType, Box<Expr>),
+    InstanceOf(Box<Expr>, FieldType),
+    Invoke(Box<Invoke>),
+    New(Box<New>),
+    /// `new int[n][m][]`, the type of the whole array and the given dimensions
+    NewArray(FieldType, Vec<Expr>),
+    /// `new int[] {1, 2, 3}` of `size` elements, the missing ones are the default value
+    ArrayInit(FieldType, usize, Vec<Expr>),
+    Conditional(Box<Expr>, Box<Expr>, Box<Expr>),
+    Assign(Box<Expr>, Box<Expr>),
+    CompoundAssign(BinaryOp, Box<Expr>, Box<Expr>),
+    /// `x++` (`increment`, not `prefix`), `x--`, `++x` or `--x`
+    Increment {
+        target: Box<Expr>,
+        increment: bool,
+        prefix: bool,
+    },
+    /// String concatenation of an `invokedynamic makeConcatWithConstants`
+    Concat(Vec<Expr>),
+    Lambda(Box<Lambda>),
+    MethodRef(Box<MethodRef>),
+    /// The -1/0/1 result of `lcmp`, `fcmpl`, ... when it is not compared right away
+    Compare(CompareKind, Box<Expr>, Box<Expr>),
+    /// The object of a `new` at this pc before its constructor is called
+    Uninitialized(u32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum CompareKind {
+    Long,
+    /// `fcmpl`/`dcmpl` (`nan_greater` false) and `fcmpg`/`dcmpg`
+    Float {
+        double: bool,
+        nan_greater: bool,
+    },
+}
+
+/// A field or method reference
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct Member {
+    /// Binary name of the class the member is looked up in
+    pub owner: String,
+    pub name: String,
+    pub descriptor: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct Invoke {
+    pub kind: InvokeKind,
+    pub method: Member,
+    pub args: Vec<Expr>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum InvokeKind {
+    Static,
+    Virtual(Expr),
+    /// `super.m()`
+    Super,
+    /// `Iface.super.m()` of a default method
+    InterfaceSuper,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct New {
+    /// Binary name of the class
+    pub class: String,
+    pub descriptor: String,
+    pub args: Vec<Expr>,
+    /// The body of an anonymous class
+    pub anonymous: Option<AnonymousClass>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct AnonymousClass {
+    pub class: super::class::ClassDecl,
+    /// The arguments of the `new` passed on to the super constructor
+    pub super_args: Vec<Expr>,
+    /// The expressions of the `val$name` fields of captured variables
+    pub captures: Vec<(String, Expr)>,
+}
+
+/// A class declared in a method body
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct LocalClass {
+    pub class: super::class::ClassDecl,
+    /// The captured variables, the arguments javac appends to the first `new` of the
+    /// class
+    pub captures: Vec<(String, Expr)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct Lambda {
+    /// The body decompiled from the synthetic `lambda$` method
+    pub body: MethodBody,
+    /// The parameters of the lambda in the body
+    pub params: Vec<VarId>,
+    /// The first parameters of the synthetic method are the captured variables
+    pub captures: Vec<(VarId, Expr)>,
+    /// Binary name of the functional interface
+    pub interface: String,
+    /// The generic type of the functional interface, e.g. `Ljava/util/function/Function<...>;`
+    pub signature: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct MethodRef {
+    /// `Owner::name`, `expr::name` or `Owner::new`
+    pub target: MethodRefTarget,
+    pub name: String,
+    pub interface: String,
+    pub signature: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum MethodRefTarget {
+    Class(String),
+    Expr(Expr),
+    Super,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum Stmt {
+    Expr(Expr),
+    /// Declaration of a local variable, with its initial value
+    Declare(VarId, Option<Expr>),
+    Return(Option<Expr>),
+    Throw(Expr),
+    If {
+        label: Label,
+        cond: Expr,
+        then: Vec<Stmt>,
+        otherwise: Vec<Stmt>,
+    },
+    While {
+        label: Label,
+        cond: Expr,
+        body: Vec<Stmt>,
+    },
+    DoWhile {
+        label: Label,
+        body: Vec<Stmt>,
+        cond: Expr,
+    },
+    For {
+        label: Label,
+        init: Vec<Stmt>,
+        cond: Option<Expr>,
+        update: Vec<Expr>,
+        body: Vec<Stmt>,
+    },
+    ForEach {
+        label: Label,
+        var: VarId,
+        iterable: Expr,
+        body: Vec<Stmt>,
+    },
+    Switch {
+        label: Label,
+        value: Expr,
+        cases: Vec<Case>,
+    },
+    Try {
+        label: Label,
+        body: Vec<Stmt>,
+        catches: Vec<Catch>,
+        finally: Option<Vec<Stmt>>,
+    },
+    Synchronized {
+        lock: Expr,
+        body: Vec<Stmt>,
+    },
+    Break(Label),
+    Continue(Label),
+    /// `super(...)` or `this(...)` at the start of a constructor
+    ConstructorCall {
+        this: bool,
+        descriptor: String,
+        args: Vec<Expr>,
+    },
+    /// `monitorenter` (`enter`) or `monitorexit`, before they are turned into `synchronized`
+    Monitor {
+        enter: bool,
+        lock: Expr,
+    },
+    LocalClass(Box<LocalClass>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct Case {
+    /// The constants of the case, empty for `default`
+    pub values: Vec<CaseValue>,
+    pub default: bool,
+    pub body: Vec<Stmt>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum CaseValue {
+    Int(i32),
+    String(String),
+    /// The name of an enum constant
+    Enum(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct Catch {
+    /// Binary names of the caught classes, more than one for a multi-catch
+    pub types: Vec<String>,
+    pub var: VarId,
+    pub body: Vec<Stmt>,
+}
+
+/// The decompiled code of a method, lambda or initializer
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct MethodBody {
+    pub vars: Vec<Variable>,
+    /// The declared parameters, without `this`
+    pub params: Vec<VarId>,
+    pub stmts: Vec<Stmt>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct Variable {
+    pub name: String,
+    /// The name in the `LocalVariableTable` or `MethodParameters` attribute
+    pub hint: Option<String>,
+    pub ty: Option<FieldType>,
+    /// The generic type, from the `LocalVariableTypeTable` or the method signature
+    pub signature: Option<String>,
+    pub kind: VarKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum VarKind {
+    Parameter,
+    /// A local variable of the source
+    Local,
+    /// A value left on the operand stack from one block to the next, like the result
+    /// of a `?:`
+    Stack,
+    /// A copy of a value the decompiler needs to keep the order of evaluation
+    Temp,
+}
+
+impl BinaryOp {
+    /// The operator of the condition that is true when this one is false, for
+    /// comparisons that don't involve NaN
+    pub fn negated(self) -> Option<BinaryOp> {
+        let op = match self {
+            BinaryOp::Eq => BinaryOp::Ne,
+            BinaryOp::Ne => BinaryOp::Eq,
+            BinaryOp::Lt => BinaryOp::Ge,
+            BinaryOp::Ge => BinaryOp::Lt,
+            BinaryOp::Gt => BinaryOp::Le,
+            BinaryOp::Le => BinaryOp::Gt,
+            _ => return None,
+        };
+        Some(op)
+    }
+
+    pub fn is_comparison(self) -> bool {
+        matches!(
+            self,
+            BinaryOp::Eq | BinaryOp::Ne | BinaryOp::Lt | BinaryOp::Ge | BinaryOp::Gt | BinaryOp::Le
+        )
+    }
+
+    pub fn symbol(self) -> &'static str {
+        match self {
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mul => "*",
+            BinaryOp::Div => "/",
+            BinaryOp::Rem => "%",
+            BinaryOp::Shl => "<<",
+            BinaryOp::Shr => ">>",
+            BinaryOp::UShr => ">>>",
+            BinaryOp::And => "&",
+            BinaryOp::Or => "|",
+            BinaryOp::Xor => "^",
+            BinaryOp::Eq => "==",
+            BinaryOp::Ne => "!=",
+            BinaryOp::Lt => "<",
+            BinaryOp::Ge => ">=",
+            BinaryOp::Gt => ">",
+            BinaryOp::Le => "<=",
+            BinaryOp::LogicalAnd => "&&",
+            BinaryOp::LogicalOr => "||",
+        }
+    }
+
+    /// Java operator precedence, higher binds tighter
+    pub fn precedence(self) -> u8 {
+        match self {
+            BinaryOp::Mul | BinaryOp::Div | BinaryOp::Rem => 13,
+            BinaryOp::Add | BinaryOp::Sub => 12,
+            BinaryOp::Shl | BinaryOp::Shr | BinaryOp::UShr => 11,
+            BinaryOp::Lt | BinaryOp::Ge | BinaryOp::Gt | BinaryOp::Le => 10,
+            BinaryOp::Eq | BinaryOp::Ne => 9,
+            BinaryOp::And => 8,
+            BinaryOp::Xor => 7,
+            BinaryOp::Or => 6,
+            BinaryOp::LogicalAnd => 5,
+            BinaryOp::LogicalOr => 4,
+        }
+    }
+}
+
+impl Expr {
+    pub fn binary(op: BinaryOp, left: Expr, right: Expr) -> Expr {
+        Expr::Binary(op, Box::new(left), Box::new(right))
+    }
+
+    pub fn not(e: Expr) -> Expr {
+        Expr::Unary(UnaryOp::Not, Box::new(e))
+    }
+
+    pub fn assign(target: Expr, value: Expr) -> Expr {
+        Expr::Assign(Box::new(target), Box::new(value))
+    }
+
+    /// Literals, variables and `this`, which can be evaluated again or out of order
+    pub fn is_trivial(&self) -> bool {
+        matches!(
+            self,
+            Expr::Int(_)
+                | Expr::Long(_)
+                | Expr::Float(_)
+                | Expr::Double(_)
+                | Expr::Bool(_)
+                | Expr::Char(_)
+                | Expr::String(_)
+                | Expr::Null
+                | Expr::Class(_)
+                | Expr::Var(_)
+                | Expr::This
+                | Expr::OuterThis(_)
+                | Expr::Captured(_)
+                | Expr::Uninitialized(_)
+        )
+    }
+
+    /// Whether evaluating the expression can change state or throw, not counting reads
+    /// of fields and array elements
+    pub fn has_side_effects(&self) -> bool {
+        let mut found = false;
+        self.visit(&mut |e| {
+            if matches!(
+                e,
+                Expr::Invoke(_)
+                    | Expr::New(_)
+                    | Expr::Assign(..)
+                    | Expr::CompoundAssign(..)
+                    | Expr::Increment { .. }
+                    | Expr::NewArray(..)
+                    | Expr::Caught
+            ) {
+                found = true;
+            }
+        });
+        found
+    }
+
+    /// The condition that is true when this one is false
+    pub fn negate(self) -> Expr {
+        match self {
+            Expr::Unary(UnaryOp::Not, e) => *e,
+            Expr::Bool(b) => Expr::Bool(!b),
+            // `!(a < b)` is not `a >= b` for NaN, relational operators are flipped
+            // once the operand types are known
+            Expr::Binary(op @ (BinaryOp::Eq | BinaryOp::Ne), l, r) => {
+                Expr::Binary(op.negated().unwrap(), l, r)
+            }
+            Expr::Binary(BinaryOp::LogicalAnd, l, r) => {
+                Expr::binary(BinaryOp::LogicalOr, l.negate(), r.negate())
+            }
+            Expr::Binary(BinaryOp::LogicalOr, l, r) => {
+                Expr::binary(BinaryOp::LogicalAnd, l.negate(), r.negate())
+            }
+            e => Expr::not(e),
+        }
+    }
+
+    /// Calls `f` on the expression and all its subexpressions, in evaluation order,
+    /// without entering lambda and anonymous class bodies
+    pub fn visit(&self, f: &mut dyn FnMut(&Expr)) {
+        f(self);
+        for child in self.children() {
+            child.visit(f);
+        }
+    }
+
+    /// The direct subexpressions in evaluation order
+    pub fn children(&self) -> Vec<&Expr> {
+        match self {
+            Expr::Field(target, _) => vec![target],
+            Expr::ArrayElement(a, i) => vec![a, i],
+            Expr::ArrayLength(a) => vec![a],
+            Expr::Binary(_, l, r) => vec![l, r],
+            Expr::Unary(_, e) | Expr::Cast(_, e) | Expr::InstanceOf(e, _) => vec![e],
+            Expr::Invoke(invoke) => {
+                let mut children = Vec::new();
+                if let InvokeKind::Virtual(receiver) = &invoke.kind {
+                    children.push(receiver);
+                }
+                children.extend(&invoke.args);
+                children
+            }
+            Expr::New(new) => {
+                let mut children = new.args.iter().collect::<Vec<_>>();
+                if let Some(anonymous) = &new.anonymous {
+                    children.extend(&anonymous.super_args);
+                    children.extend(anonymous.captures.iter().map(|(_, e)| e));
+                }
+                children
+            }
+            Expr::NewArray(_, dimensions) => dimensions.iter().collect(),
+            Expr::ArrayInit(_, _, elements) => elements.iter().collect(),
+            Expr::Conditional(c, t, e) => vec![c, t, e],
+            Expr::Assign(target, value) | Expr::CompoundAssign(_, target, value) => {
+                vec![target, value]
+            }
+            Expr::Increment { target, .. } => vec![target],
+            Expr::Concat(parts) => parts.iter().collect(),
+            Expr::Lambda(lambda) => lambda.captures.iter().map(|(_, e)| e).collect(),
+            Expr::MethodRef(method_ref) => match &method_ref.target {
+                MethodRefTarget::Expr(e) => vec![e],
+                _ => Vec::new(),
+            },
+            Expr::Compare(_, l, r) => vec![l, r],
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn children_mut(&mut self) -> Vec<&mut Expr> {
+        match self {
+            Expr::Field(target, _) => vec![target],
+            Expr::ArrayElement(a, i) => vec![a, i],
+            Expr::ArrayLength(a) => vec![a],
+            Expr::Binary(_, l, r) => vec![l, r],
+            Expr::Unary(_, e) | Expr::Cast(_, e) | Expr::InstanceOf(e, _) => vec![e],
+            Expr::Invoke(invoke) => {
+                let invoke = &mut **invoke;
+                let mut children = Vec::new();
+                if let InvokeKind::Virtual(receiver) = &mut invoke.kind {
+                    children.push(receiver);
+                }
+                children.extend(&mut invoke.args);
+                children
+            }
+            Expr::New(new) => {
+                let new = &mut **new;
+                let mut children = new.args.iter_mut().collect::<Vec<_>>();
+                if let Some(anonymous) = &mut new.anonymous {
+                    children.extend(&mut anonymous.super_args);
+                    children.extend(anonymous.captures.iter_mut().map(|(_, e)| e));
+                }
+                children
+            }
+            Expr::NewArray(_, dimensions) => dimensions.iter_mut().collect(),
+            Expr::ArrayInit(_, _, elements) => elements.iter_mut().collect(),
+            Expr::Conditional(c, t, e) => vec![c, t, e],
+            Expr::Assign(target, value) | Expr::CompoundAssign(_, target, value) => {
+                vec![target, value]
+            }
+            Expr::Increment { target, .. } => vec![target],
+            Expr::Concat(parts) => parts.iter_mut().collect(),
+            Expr::Lambda(lambda) => lambda.captures.iter_mut().map(|(_, e)| e).collect(),
+            Expr::MethodRef(method_ref) => match &mut method_ref.target {
+                MethodRefTarget::Expr(e) => vec![e],
+                _ => Vec::new(),
+            },
+            Expr::Compare(_, l, r) => vec![l, r],
+            _ => Vec::new(),
+        }
+    }
+
+    /// Rewrites the expression bottom up, children before their parents
+    pub fn rewrite(&mut self, f: &mut dyn FnMut(&mut Expr)) {
+        for child in self.children_mut() {
+            child.rewrite(f);
+        }
+        f(self);
+    }
+
+    /// Whether the expression assigns the variable
+    pub fn writes(&self, var: VarId) -> bool {
+        let mut found = false;
+        self.visit(&mut |e| match e {
+            Expr::Assign(target, _)
+            | Expr::CompoundAssign(_, target, _)
+            | Expr::Increment { target, .. }
+                if **target == Expr::Var(var) =>
+            {
+                found = true
+            }
+            _ => {}
+        });
+        found
+    }
+
+    /// Whether the variable appears in the expression at all
+    pub fn uses(&self, var: VarId) -> bool {
+        let mut found = false;
+        self.visit(&mut |e| {
+            if *e == Expr::Var(var) {
+                found = true;
+            }
+        });
+        found
+    }
+}
+
+impl Stmt {
+    /// The expressions directly in the statement, not in nested statements
+    pub fn exprs(&self) -> Vec<&Expr> {
+        match self {
+            Stmt::Expr(e) | Stmt::Throw(e) => vec![e],
+            Stmt::Declare(_, e) | Stmt::Return(e) => e.iter().collect(),
+            Stmt::If { cond, .. } | Stmt::While { cond, .. } | Stmt::DoWhile { cond, .. } => {
+                vec![cond]
+            }
+            Stmt::For { cond, update, .. } => cond.iter().chain(update).collect(),
+            Stmt::ForEach { iterable, .. } => vec![iterable],
+            Stmt::Switch { value, .. } => vec![value],
+            Stmt::Synchronized { lock, .. } | Stmt::Monitor { lock, .. } => vec![lock],
+            Stmt::ConstructorCall { args, .. } => args.iter().collect(),
+            Stmt::LocalClass(local) => local.captures.iter().map(|(_, e)| e).collect(),
+            Stmt::Try { .. } | Stmt::Break(_) | Stmt::Continue(_) => Vec::new(),
+        }
+    }
+
+    pub fn exprs_mut(&mut self) -> Vec<&mut Expr> {
+        match self {
+            Stmt::Expr(e) | Stmt::Throw(e) => vec![e],
+            Stmt::Declare(_, e) | Stmt::Return(e) => e.iter_mut().collect(),
+            Stmt::If { cond, .. } | Stmt::While { cond, .. } | Stmt::DoWhile { cond, .. } => {
+                vec![cond]
+            }
+            Stmt::For { cond, update, .. } => cond.iter_mut().chain(update).collect(),
+            Stmt::ForEach { iterable, .. } => vec![iterable],
+            Stmt::Switch { value, .. } => vec![value],
+            Stmt::Synchronized { lock, .. } | Stmt::Monitor { lock, .. } => vec![lock],
+            Stmt::ConstructorCall { args, .. } => args.iter_mut().collect(),
+            Stmt::LocalClass(local) => local.captures.iter_mut().map(|(_, e)| e).collect(),
+            Stmt::Try { .. } | Stmt::Break(_) | Stmt::Continue(_) => Vec::new(),
+        }
+    }
+
+    /// The nested statement lists, in order
+    pub fn blocks(&self) -> Vec<&Vec<Stmt>> {
+        match self {
+            Stmt::If {
+                then, otherwise, ..
+            } => vec![then, otherwise],
+            Stmt::While { body, .. }
+            | Stmt::DoWhile { body, .. }
+            | Stmt::ForEach { body, .. }
+            | Stmt::Synchronized { body, .. } => vec![body],
+            Stmt::For { init, body, .. } => vec![init, body],
+            Stmt::Switch { cases, .. } => cases.iter().map(|c| &c.body).collect(),
+            Stmt::Try {
+                body,
+                catches,
+                finally,
+                ..
+            } => {
+                let mut blocks = vec![body];
+                blocks.extend(catches.iter().map(|c| &c.body));
+                blocks.extend(finally);
+                blocks
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn blocks_mut(&mut self) -> Vec<&mut Vec<Stmt>> {
+        match self {
+            Stmt::If {
+                then, otherwise, ..
+            } => vec![then, otherwise],
+            Stmt::While { body, .. }
+            | Stmt::DoWhile { body, .. }
+            | Stmt::ForEach { body, .. }
+            | Stmt::Synchronized { body, .. } => vec![body],
+            Stmt::For { init, body, .. } => vec![init, body],
+            Stmt::Switch { cases, .. } => cases.iter_mut().map(|c| &mut c.body).collect(),
+            Stmt::Try {
+                body,
+                catches,
+                finally,
+                ..
+            } => {
+                let mut blocks = vec![body];
+                blocks.extend(catches.iter_mut().map(|c| &mut c.body));
+                blocks.extend(finally);
+                blocks
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn label(&self) -> Option<Label> {
+        match self {
+            Stmt::If { label, .. }
+            | Stmt::While { label, .. }
+            | Stmt::DoWhile { label, .. }
+            | Stmt::For { label, .. }
+            | Stmt::ForEach { label, .. }
+            | Stmt::Switch { label, .. }
+            | Stmt::Try { label, .. } => Some(*label),
+            _ => None,
+        }
+    }
+
+    pub fn is_loop(&self) -> bool {
+        matches!(
+            self,
+            Stmt::While { .. } | Stmt::DoWhile { .. } | Stmt::For { .. } | Stmt::ForEach { .. }
+        )
+    }
+}
+
+/// Calls `f` on every expression of the statements, nested ones included
+pub(super) fn visit_exprs(stmts: &[Stmt], f: &mut dyn FnMut(&Expr)) {
+    for stmt in stmts {
+        if let Stmt::Declare(var, _) = stmt {
+            f(&Expr::Var(*var));
+        }
+        if let Stmt::ForEach { var, .. } = stmt {
+            f(&Expr::Var(*var));
+        }
+        if let Stmt::Try { catches, .. } = stmt {
+            for c in catches {
+                f(&Expr::Var(c.var));
+            }
+        }
+        for e in stmt.exprs() {
+            e.visit(f);
+        }
+        for block in stmt.blocks() {
+            visit_exprs(block, f);
+        }
+    }
+}
+
+/// Rewrites every expression of the statements bottom up
+pub(super) fn rewrite_exprs(stmts: &mut [Stmt], f: &mut dyn FnMut(&mut Expr)) {
+    for stmt in stmts {
+        for e in stmt.exprs_mut() {
+            e.rewrite(f);
+        }
+        for block in stmt.blocks_mut() {
+            rewrite_exprs(block, f);
+        }
+    }
+}
+
+/// Rewrites every statement list, inner ones first
+pub(super) fn rewrite_blocks(stmts: &mut Vec<Stmt>, f: &mut dyn FnMut(&mut Vec<Stmt>)) {
+    for stmt in stmts.iter_mut() {
+        for block in stmt.blocks_mut() {
+            rewrite_blocks(block, f);
+        }
+    }
+    f(stmts);
+}
+
+/// Whether the statements can complete normally, roughly JLS 14.22 without
+/// following `break`s out of nested statements
+pub(super) fn completes_normally(stmts: &[Stmt]) -> bool {
+    match stmts.last() {
+        None => true,
+        Some(stmt) => stmt_completes_normally(stmt),
+    }
+}
+
+fn stmt_completes_normally(stmt: &Stmt) -> bool {
+    match stmt {
+        Stmt::Return(_) | Stmt::Throw(_) | Stmt::Break(_) | Stmt::Continue(_) => false,
+        Stmt::If {
+            label,
+            then,
+            otherwise,
+            ..
+        } => {
+            completes_normally(then)
+                || completes_normally(otherwise)
+                || breaks_to(then, *label)
+                || breaks_to(otherwise, *label)
+        }
+        Stmt::While {
+            label, cond, body, ..
+        } => *cond != Expr::Bool(true) || breaks_to(body, *label),
+        Stmt::DoWhile { label, body, cond } => *cond != Expr::Bool(true) || breaks_to(body, *label),
+        Stmt::For {
+            label, cond, body, ..
+        } => cond.is_some() || breaks_to(body, *label),
+        Stmt::Try {
+            label,
+            body,
+            catches,
+            finally,
+        } => {
+            if let Some(finally) = finally {
+                if !completes_normally(finally) {
+                    return false;
+                }
+            }
+            completes_normally(body)
+                || catches.iter().any(|c| completes_normally(&c.body))
+                || breaks_to(body, *label)
+                || catches.iter().any(|c| breaks_to(&c.body, *label))
+        }
+        Stmt::Switch { label, cases, .. } => {
+            !cases.iter().any(|c| c.default)
+                || cases.last().is_none_or(|c| completes_normally(&c.body))
+                || cases.iter().any(|c| breaks_to(&c.body, *label))
+        }
+        Stmt::Synchronized { body, .. } => completes_normally(body),
+        _ => true,
+    }
+}
+
+/// Whether a `break` in the statements jumps to the label
+pub(super) fn breaks_to(stmts: &[Stmt], label: Label) -> bool {
+    stmts.iter().any(|stmt| match stmt {
+        Stmt::Break(l) => *l == label,
+        stmt => stmt.blocks().iter().any(|b| breaks_to(b, label)),
+    })
+}
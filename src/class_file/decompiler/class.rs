@@ -0,0 +1,1026 @@
+//! Lays out a class as its source declares it
+//!
+//! javac moves field initializers into `<clinit>` and the constructors, and adds members
+//! of its own: the `values` and `valueOf` methods of enums, the accessors of records,
+//! bridge methods and default constructors. These are taken back out, and member,
+//! local and anonymous classes are decompiled in place.
+
+use super::ast::{
+    rewrite_exprs, visit_exprs, AnonymousClass, Expr, LocalClass, MethodBody, Stmt, VarKind,
+    Variable,
+};
+use super::{method_body, names, printer, sugar, Context};
+use crate::class_file::attributes::ConstantValue;
+use crate::class_file::jaustp::signature::{
+    parse_class_signature, parse_method_signature, JavaTypeSignature, ReferenceTypeSignature,
+};
+use crate::class_file::methods::Method;
+use crate::class_file::{access_flags, fields, methods};
+use crate::class_file::{BaseType, ClassFile, ClassPath, FieldType, MethodDescriptor};
+use anyhow::{anyhow, Result};
+use std::collections::{HashMap, HashSet};
+
+/// Decompiles the class with the binary name and its member classes to Java source
+pub fn decompile(class_path: &ClassPath, name: &str) -> Result<String> {
+    let cf = class_path.load(&name.replace('.', "/"))?;
+    let decl = class_decl(class_path, &cf)?;
+    Ok(printer::print(&decl))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct ClassDecl {
+    /// Binary name of the class
+    pub name: String,
+    /// The name of a class declared in a method, `Name` for `Outer$1Name`
+    pub local_name: Option<String>,
+    pub kind: ClassKind,
+    pub modifiers: Vec<&'static str>,
+    /// The generic signature of the class
+    pub signature: Option<String>,
+    pub super_type: String,
+    /// The super constructor an anonymous class calls
+    pub super_descriptor: String,
+    pub interfaces: Vec<String>,
+    /// Binary names of the permitted subclasses of a sealed class
+    pub permits: Vec<String>,
+    pub record_components: Vec<Component>,
+    pub members: Vec<ClassMember>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum ClassKind {
+    Class,
+    Interface,
+    Enum,
+    Record,
+    Annotation,
+    Anonymous,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct Component {
+    pub name: String,
+    pub ty: FieldType,
+    pub signature: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum ClassMember {
+    Field(FieldDecl),
+    Method(MethodDecl),
+    /// `static { ... }` or `{ ... }`
+    Initializer {
+        is_static: bool,
+        body: MethodBody,
+    },
+    Class(ClassDecl),
+    EnumConstant(EnumConstant),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct FieldDecl {
+    pub modifiers: Vec<&'static str>,
+    pub name: String,
+    pub ty: FieldType,
+    pub signature: Option<String>,
+    pub init: Option<Expr>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct MethodDecl {
+    pub modifiers: Vec<&'static str>,
+    /// `<init>` for constructors
+    pub name: String,
+    pub descriptor: String,
+    pub signature: Option<String>,
+    pub throws: Vec<String>,
+    pub varargs: bool,
+    /// The parameters and the decompiled code
+    pub body: MethodBody,
+    /// Parameters javac added, the name and ordinal of enum constants or the outer instance
+    pub synthetic_params: usize,
+    /// Parameters javac appended for the variables a local class captures
+    pub captured_params: usize,
+    pub code: Code,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum Code {
+    /// Abstract and native methods
+    None,
+    Body,
+    /// The method could not be decompiled
+    Failed(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct EnumConstant {
+    pub name: String,
+    pub args: Vec<Expr>,
+    pub body: Option<ClassDecl>,
+}
+
+fn class_modifiers(class_path: &ClassPath, cf: &ClassFile, kind: ClassKind) -> Vec<&'static str> {
+    let cp = &cf.constant_pool;
+    let name = cf.class_name();
+    let entry = cf
+        .attributes
+        .get_inner_classes()
+        .and_then(|a| a.find(&name, cp))
+        .filter(|e| e.outer_class_name(cp).is_some());
+    let implicit_static = matches!(
+        kind,
+        ClassKind::Interface | ClassKind::Enum | ClassKind::Record | ClassKind::Annotation
+    );
+    let modifiers = match entry {
+        Some(entry) => entry.inner_class_access_flags.modifiers(),
+        None => cf
+            .access_flags
+            .flag_vector()
+            .iter()
+            .filter_map(|flag| match flag {
+                access_flags::AccessFlag::Public => Some("public"),
+                access_flags::AccessFlag::Abstract => Some("abstract"),
+                access_flags::AccessFlag::Final => Some("final"),
+                _ => None,
+            })
+            .collect(),
+    };
+    let mut modifiers = modifiers
+        .into_iter()
+        .filter(|m| match *m {
+            "abstract" | "final" => kind == ClassKind::Class,
+            "static" => !implicit_static,
+            _ => true,
+        })
+        .collect::<Vec<_>>();
+    // javac makes enums with constant bodies sealed, they can't be declared so
+    if matches!(kind, ClassKind::Class | ClassKind::Interface) {
+        if cf.is_sealed() {
+            modifiers.push("sealed");
+        } else if cf.is_non_sealed(|super_type| {
+            let super_cf = class_path.load(super_type).ok()?;
+            Some(super_cf.permitted_subclasses().unwrap_or_default())
+        }) {
+            modifiers.push("non-sealed");
+        }
+    }
+    modifiers
+}
+
+fn class_kind(cf: &ClassFile) -> ClassKind {
+    let flags = cf.access_flags.flag_vector();
+    let super_class = cf.super_class_name();
+    if flags.contains(&access_flags::AccessFlag::Annotation) {
+        ClassKind::Annotation
+    } else if flags.contains(&access_flags::AccessFlag::Interface) {
+        ClassKind::Interface
+    } else if flags.contains(&access_flags::AccessFlag::Enum)
+        && super_class.as_deref() == Some("java/lang/Enum")
+    {
+        ClassKind::Enum
+    } else if super_class.as_deref() == Some("java/lang/Record")
+        && cf.attributes.get_record().is_some()
+    {
+        ClassKind::Record
+    } else {
+        ClassKind::Class
+    }
+}
+
+pub(super) fn class_decl(class_path: &ClassPath, cf: &ClassFile) -> Result<ClassDecl> {
+    let cp = &cf.constant_pool;
+    let class_name = cf.class_name();
+    let ctx = Context {
+        class_path,
+        cf,
+        class_name: class_name.clone(),
+    };
+    let is_anonymous = cf
+        .attributes
+        .get_inner_classes()
+        .and_then(|a| a.find(&class_name, cp))
+        .is_some_and(|e| e.is_anonymous());
+    let mut kind = class_kind(cf);
+    let mut interfaces = cf
+        .interfaces
+        .interfaces
+        .iter()
+        .map(|i| cp.get_to_string(*i))
+        .collect::<Vec<_>>();
+    let mut super_type = cf
+        .super_class_name()
+        .unwrap_or("java/lang/Object".to_string());
+    match kind {
+        ClassKind::Annotation => interfaces.retain(|i| i != "java/lang/annotation/Annotation"),
+        ClassKind::Class if is_anonymous => {
+            kind = ClassKind::Anonymous;
+            if super_type == "java/lang/Object" && interfaces.len() == 1 {
+                super_type = interfaces.remove(0);
+            }
+        }
+        _ => {}
+    }
+    let record_components = match cf.attributes.get_record() {
+        Some(record) if kind == ClassKind::Record => record
+            .components()
+            .iter()
+            .map(|c| Component {
+                name: c.name(cp),
+                ty: FieldType::parse(&c.descriptor(cp))
+                    .unwrap_or(FieldType::Object("java/lang/Object".to_string())),
+                signature: c.attributes().get_signature(cp),
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    let mut decl = ClassDecl {
+        name: class_name.clone(),
+        local_name: None,
+        kind,
+        modifiers: class_modifiers(class_path, cf, kind),
+        signature: cf.attributes.get_signature(cp),
+        super_type,
+        super_descriptor: "()V".to_string(),
+        interfaces,
+        permits: match kind {
+            ClassKind::Class | ClassKind::Interface => {
+                cf.permitted_subclasses().unwrap_or_default()
+            }
+            _ => Vec::new(),
+        },
+        record_components,
+        members: Vec::new(),
+    };
+
+    let mut fields = Vec::new();
+    for field in &cf.fields.fields {
+        let flags = field.access_flags.flag_vector();
+        let name = cp.utf8(field.name_index)?.to_string();
+        if field.is_synthetic() || flags.contains(&fields::AccessFlag::Enum) {
+            continue;
+        }
+        if kind == ClassKind::Record && !field.is_static() {
+            continue;
+        }
+        let ty = FieldType::parse(cp.utf8(field.descriptor_index)?)?;
+        let init = match (field.is_static(), field.constant_value(cp)) {
+            (true, Some(Ok(value))) => Some(constant(value, &ty)),
+            _ => None,
+        };
+        fields.push(FieldDecl {
+            modifiers: field_modifiers(&flags, kind),
+            name,
+            signature: field.attributes.get_signature(cp),
+            ty,
+            init,
+        });
+    }
+
+    let mut methods = Vec::new();
+    let mut static_init = None;
+    for method in &cf.methods.methods {
+        let name = cp.utf8(method.name_index)?;
+        let flags = method.access_flags.flag_vector();
+        if method.is_synthetic() || flags.contains(&methods::AccessFlag::Bridge) {
+            continue;
+        }
+        if name == "<clinit>" {
+            static_init = Some(
+                method_body(&ctx, method)
+                    .map_err(|e| anyhow!("Static initializer of {}: {}", class_name, e))?,
+            );
+            continue;
+        }
+        methods.push(method_decl(&ctx, method, kind)?);
+    }
+
+    let mut constants = Vec::new();
+    if let Some(mut body) = static_init {
+        if kind == ClassKind::Enum {
+            constants = enum_constants(&ctx, &mut body);
+        }
+        static_field_inits(&class_name, &mut body, &mut fields);
+        if !body.stmts.is_empty() {
+            names::name_vars(&mut body, &HashSet::new());
+            decl.members.push(ClassMember::Initializer {
+                is_static: true,
+                body,
+            });
+        }
+    }
+    let initializer = constructors(&ctx, &mut decl, &mut methods, &mut fields);
+    local_classes(&ctx, &mut methods)?;
+
+    let mut members = constants
+        .into_iter()
+        .map(ClassMember::EnumConstant)
+        .chain(fields.into_iter().map(ClassMember::Field))
+        .collect::<Vec<_>>();
+    members.append(&mut decl.members);
+    members.extend(initializer);
+    members.extend(methods.into_iter().map(ClassMember::Method));
+    for class in member_classes(cf) {
+        if let Ok(inner) = class_path.load(&class) {
+            members.push(ClassMember::Class(class_decl(class_path, &inner)?));
+        }
+    }
+    decl.members = members;
+    Ok(decl)
+}
+
+fn field_modifiers(flags: &[fields::AccessFlag], kind: ClassKind) -> Vec<&'static str> {
+    use fields::AccessFlag as F;
+    if matches!(kind, ClassKind::Interface | ClassKind::Annotation) {
+        return Vec::new();
+    }
+    [
+        (F::Public, "public"),
+        (F::Protected, "protected"),
+        (F::Private, "private"),
+        (F::Static, "static"),
+        (F::Final, "final"),
+        (F::Transient, "transient"),
+        (F::Volatile, "volatile"),
+    ]
+    .into_iter()
+    .filter(|(flag, _)| flags.contains(flag))
+    .map(|(_, modifier)| modifier)
+    .collect()
+}
+
+fn constant(value: ConstantValue, ty: &FieldType) -> Expr {
+    match value {
+        ConstantValue::Int(n) => match ty {
+            FieldType::Base(BaseType::Boolean) => Expr::Bool(n != 0),
+            FieldType::Base(BaseType::Char) => Expr::Char(n as u16),
+            FieldType::Base(b @ (BaseType::Byte | BaseType::Short)) => {
+                Expr::Cast(FieldType::Base(*b), Box::new(Expr::Int(n)))
+            }
+            _ => Expr::Int(n),
+        },
+        ConstantValue::Long(n) => Expr::Long(n),
+        ConstantValue::Float(f) => Expr::Float(f),
+        ConstantValue::Double(d) => Expr::Double(d),
+        ConstantValue::String(s) => Expr::String(s),
+    }
+}
+
+fn method_decl(ctx: &Context, method: &Method, kind: ClassKind) -> Result<MethodDecl> {
+    let cp = &ctx.cf.constant_pool;
+    let name = cp.utf8(method.name_index)?.to_string();
+    let descriptor = cp.utf8(method.descriptor_index)?.to_string();
+    let flags = method.access_flags.flag_vector();
+    let signature = method.attributes.get_signature(cp);
+    let (mut body, code) = match method.get_code() {
+        None => (parameters(&descriptor)?, Code::None),
+        Some(_) => match method_body(ctx, method) {
+            Ok(body) => (body, Code::Body),
+            Err(e) => (parameters(&descriptor)?, Code::Failed(e.to_string())),
+        },
+    };
+    if code != Code::Body {
+        if let Some(parameters) = signature.as_deref().and_then(sugar::split_parameters) {
+            if parameters.len() == body.params.len() {
+                for (var, signature) in body.params.clone().into_iter().zip(parameters) {
+                    body.vars[var].signature = Some(signature);
+                }
+            }
+        }
+    }
+    names::name_vars(&mut body, &HashSet::new());
+
+    use methods::AccessFlag as F;
+    let interface = matches!(kind, ClassKind::Interface | ClassKind::Annotation);
+    let mut modifiers = Vec::new();
+    for (flag, modifier) in [
+        (F::Public, "public"),
+        (F::Protected, "protected"),
+        (F::Private, "private"),
+        (F::Abstract, "abstract"),
+        (F::Static, "static"),
+        (F::Final, "final"),
+        (F::Synchronized, "synchronized"),
+        (F::Native, "native"),
+        (F::Strict, "strictfp"),
+    ] {
+        if !flags.contains(&flag) {
+            continue;
+        }
+        // interface members are public and their methods without code abstract
+        if interface && matches!(flag, F::Public | F::Abstract) {
+            continue;
+        }
+        modifiers.push(modifier);
+    }
+    if interface
+        && code != Code::None
+        && !flags.contains(&F::Static)
+        && !flags.contains(&F::Private)
+    {
+        modifiers.insert(0, "default");
+    }
+    if flags.contains(&F::Strict) && ctx.cf.major_version >= 61 {
+        modifiers.retain(|m| *m != "strictfp");
+    }
+    Ok(MethodDecl {
+        modifiers,
+        name,
+        descriptor,
+        signature,
+        throws: method.attributes.get_checked_exceptions(cp),
+        varargs: flags.contains(&F::Varargs),
+        body,
+        synthetic_params: 0,
+        captured_params: 0,
+        code,
+    })
+}
+
+/// A body with only the parameters of the descriptor, for methods without code
+fn parameters(descriptor: &str) -> Result<MethodBody> {
+    let descriptor = MethodDescriptor::parse(descriptor)?;
+    let vars = descriptor
+        .parameters
+        .into_iter()
+        .map(|ty| Variable {
+            name: String::new(),
+            hint: None,
+            ty: Some(ty),
+            signature: None,
+            kind: VarKind::Parameter,
+        })
+        .collect::<Vec<_>>();
+    Ok(MethodBody {
+        params: (0..vars.len()).collect(),
+        vars,
+        stmts: Vec::new(),
+    })
+}
+
+/// Whether the expression is a constant expression or reads nothing of the method
+fn is_free(e: &Expr) -> bool {
+    let mut free = true;
+    e.visit(&mut |e| {
+        if matches!(e, Expr::Var(_) | Expr::Caught) {
+            free = false;
+        }
+    });
+    free
+}
+
+/// The enum constants are created at the start of `<clinit>`, followed by the
+/// `$VALUES` array
+fn enum_constants(ctx: &Context, body: &mut MethodBody) -> Vec<EnumConstant> {
+    let mut constants = Vec::new();
+    while let Some(Stmt::Expr(Expr::Assign(target, value))) = body.stmts.first() {
+        let Expr::StaticField(field) = &**target else {
+            break;
+        };
+        if field.owner != ctx.class_name {
+            break;
+        }
+        if field.name == "$VALUES" {
+            body.stmts.remove(0);
+            continue;
+        }
+        let Expr::New(new) = &**value else {
+            break;
+        };
+        let (args, class_body) = match &new.anonymous {
+            Some(anonymous) => (&anonymous.super_args, Some(anonymous.class.clone())),
+            None => (&new.args, None),
+        };
+        if args.len() < 2 || args[0] != Expr::String(field.name.clone()) || !is_free(value) {
+            break;
+        }
+        constants.push(EnumConstant {
+            name: field.name.clone(),
+            args: args[2..].to_vec(),
+            body: class_body,
+        });
+        body.stmts.remove(0);
+    }
+    constants
+}
+
+/// Static fields assigned at the start of `<clinit>` in the order they are declared
+/// are written with their initializer
+fn static_field_inits(class_name: &str, body: &mut MethodBody, fields: &mut [FieldDecl]) {
+    let mut last = None;
+    while let Some(Stmt::Expr(Expr::Assign(target, value))) = body.stmts.first() {
+        let Expr::StaticField(field) = &**target else {
+            break;
+        };
+        let Some(index) = fields.iter().position(|f| {
+            f.name == field.name && f.ty.descriptor() == field.descriptor && f.init.is_none()
+        }) else {
+            break;
+        };
+        if field.owner != class_name
+            || !fields[index].modifiers.contains(&"static") && !fields[index].modifiers.is_empty()
+            || last.is_some_and(|last| index < last)
+            || !is_free(value)
+        {
+            break;
+        }
+        fields[index].init = Some((**value).clone());
+        last = Some(index);
+        body.stmts.remove(0);
+    }
+}
+
+/// Takes the field initializers and the synthetic parts out of the constructors, and
+/// leaves out the default constructor
+fn constructors(
+    ctx: &Context,
+    decl: &mut ClassDecl,
+    methods: &mut Vec<MethodDecl>,
+    fields: &mut [FieldDecl],
+) -> Option<ClassMember> {
+    let kind = decl.kind;
+    let outer = outer_instance(ctx.cf);
+    for method in methods.iter_mut().filter(|m| m.name == "<init>") {
+        if method.code != Code::Body {
+            continue;
+        }
+        let stmts = &mut method.body.stmts;
+        // `this.this$0 = outer` comes before the super constructor call
+        let synthetic = stmts
+            .iter()
+            .take_while(
+                |s| matches!(s, Stmt::Expr(Expr::Assign(target, _)) if is_synthetic_field(target)),
+            )
+            .count();
+        // the captured variables of a local class are the last parameters
+        method.captured_params = stmts[..synthetic]
+            .iter()
+            .filter(|s| match s {
+                Stmt::Expr(Expr::Assign(target, _)) => {
+                    matches!(&**target, Expr::Field(_, f) if f.name.starts_with("val$"))
+                }
+                _ => false,
+            })
+            .count();
+        stmts.drain(..synthetic);
+        if kind == ClassKind::Enum {
+            method.synthetic_params = 2;
+            if let Some(Stmt::ConstructorCall { this, args, .. }) = stmts.first_mut() {
+                match this {
+                    true => {
+                        args.drain(..2.min(args.len()));
+                    }
+                    false => {
+                        stmts.remove(0);
+                    }
+                }
+            }
+        } else if outer.is_some() && synthetic > 0 {
+            method.synthetic_params = 1;
+        }
+    }
+
+    // the initializers of instance fields follow the super constructor call in every
+    // constructor that calls one
+    let mut inits: Option<Vec<Stmt>> = None;
+    for method in methods.iter().filter(|m| m.name == "<init>") {
+        let stmts = &method.body.stmts;
+        let start = match stmts.first() {
+            Some(Stmt::ConstructorCall { this: true, .. }) => continue,
+            Some(Stmt::ConstructorCall { .. }) => 1,
+            _ if kind == ClassKind::Enum => 0,
+            _ => {
+                inits = Some(Vec::new());
+                continue;
+            }
+        };
+        let prefix = stmts[start..]
+            .iter()
+            .take_while(|s| is_field_init(&ctx.class_name, s, fields))
+            .cloned()
+            .collect::<Vec<_>>();
+        inits = Some(match inits {
+            None => prefix,
+            Some(inits) => inits
+                .into_iter()
+                .zip(prefix)
+                .take_while(|(a, b)| a == b)
+                .map(|(a, _)| a)
+                .collect(),
+        });
+    }
+    let inits = inits.unwrap_or_default();
+    if !inits.is_empty() {
+        for method in methods.iter_mut().filter(|m| m.name == "<init>") {
+            let stmts = &mut method.body.stmts;
+            if let Some(Stmt::ConstructorCall { this: true, .. }) = stmts.first() {
+                continue;
+            }
+            let start = matches!(stmts.first(), Some(Stmt::ConstructorCall { .. })) as usize;
+            stmts.drain(start..start + inits.len());
+        }
+        for init in &inits {
+            let Stmt::Expr(Expr::Assign(target, value)) = init else {
+                unreachable!()
+            };
+            let Expr::Field(_, field) = &**target else {
+                unreachable!()
+            };
+            if let Some(f) = fields.iter_mut().find(|f| f.name == field.name) {
+                f.init = Some((**value).clone());
+            }
+        }
+    }
+
+    for method in methods.iter_mut().filter(|m| m.name == "<init>") {
+        let stmts = &mut method.body.stmts;
+        if let Some(Stmt::ConstructorCall {
+            this: false, args, ..
+        }) = stmts.first()
+        {
+            if args.is_empty() {
+                stmts.remove(0);
+            }
+        }
+    }
+
+    let mut initializer = None;
+    match kind {
+        ClassKind::Anonymous => {
+            // the constructor of an anonymous class is generated, what it does besides
+            // calling the super constructor is an instance initializer
+            if let Some(index) = methods.iter().position(|m| m.name == "<init>") {
+                let method = methods.remove(index);
+                decl.super_descriptor = method.descriptor.clone();
+                if !method.body.stmts.is_empty() {
+                    initializer = Some(ClassMember::Initializer {
+                        is_static: false,
+                        body: method.body,
+                    });
+                }
+            }
+        }
+        ClassKind::Record => {
+            let components = decl.record_components.clone();
+            methods.retain(|m| !is_record_member(m, &components));
+        }
+        _ => {
+            let constructors = methods.iter().filter(|m| m.name == "<init>").count();
+            methods.retain(|m| {
+                !(constructors == 1
+                    && m.name == "<init>"
+                    && m.code == Code::Body
+                    && m.body.stmts.is_empty()
+                    && m.body.params.len() == m.synthetic_params + m.captured_params
+                    && m.throws.is_empty()
+                    && default_constructor_modifiers(&decl.modifiers, kind) == m.modifiers)
+            });
+        }
+    }
+    if kind == ClassKind::Enum {
+        methods.retain(|m| {
+            let name = m.name.as_str();
+            !(name == "values" && m.body.params.is_empty()
+                || name == "valueOf" && m.descriptor.starts_with("(Ljava/lang/String;)"))
+        });
+    }
+    initializer
+}
+
+fn default_constructor_modifiers(class: &[&'static str], kind: ClassKind) -> Vec<&'static str> {
+    match kind {
+        ClassKind::Enum => vec!["private"],
+        _ => class
+            .iter()
+            .copied()
+            .filter(|m| matches!(*m, "public" | "protected" | "private"))
+            .collect(),
+    }
+}
+
+fn is_synthetic_field(e: &Expr) -> bool {
+    match e {
+        Expr::Field(target, field) => {
+            **target == Expr::This
+                && (field.name.starts_with("this$") || field.name.starts_with("val$"))
+        }
+        _ => false,
+    }
+}
+
+fn is_field_init(class_name: &str, stmt: &Stmt, fields: &[FieldDecl]) -> bool {
+    let Stmt::Expr(Expr::Assign(target, value)) = stmt else {
+        return false;
+    };
+    let Expr::Field(object, field) = &**target else {
+        return false;
+    };
+    **object == Expr::This
+        && field.owner == class_name
+        && is_free(value)
+        && fields
+            .iter()
+            .any(|f| f.name == field.name && !f.modifiers.contains(&"static"))
+}
+
+/// The canonical constructor, accessors, `toString`, `hashCode` and `equals` javac
+/// generates for a record
+fn is_record_member(method: &MethodDecl, components: &[Component]) -> bool {
+    let canonical = format!(
+        "({})V",
+        components
+            .iter()
+            .map(|c| c.ty.descriptor())
+            .collect::<String>()
+    );
+    match method.name.as_str() {
+        "<init>" if method.descriptor == canonical => {
+            method.body.stmts.len() == components.len()
+                && method.body.stmts.iter().zip(components).zip(&method.body.params).all(
+                    |((stmt, component), param)| match stmt {
+                        Stmt::Expr(Expr::Assign(target, value)) => {
+                            matches!(&**target, Expr::Field(object, f) if **object == Expr::This && f.name == component.name)
+                                && **value == Expr::Var(*param)
+                        }
+                        _ => false,
+                    },
+                )
+        }
+        "toString" | "hashCode" | "equals" => matches!(method.code, Code::Failed(_)),
+        name => components.iter().any(|c| {
+            c.name == name
+                && method.descriptor == format!("(){}", c.ty.descriptor())
+                && matches!(method.body.stmts.as_slice(), [Stmt::Return(Some(Expr::Field(object, f)))] if **object == Expr::This && f.name == name)
+        }),
+    }
+}
+
+/// The outer class of an inner (not static) member or local class
+pub(super) fn outer_instance(cf: &ClassFile) -> Option<String> {
+    cf.fields.fields.iter().find_map(|f| {
+        let name = cf.constant_pool.utf8(f.name_index).ok()?;
+        if !name.starts_with("this$") {
+            return None;
+        }
+        match FieldType::parse(cf.constant_pool.utf8(f.descriptor_index).ok()?).ok()? {
+            FieldType::Object(class) => Some(class),
+            _ => None,
+        }
+    })
+}
+
+/// The member classes declared in the class
+fn member_classes(cf: &ClassFile) -> Vec<String> {
+    let cp = &cf.constant_pool;
+    let name = cf.class_name();
+    cf.inner_classes()
+        .into_iter()
+        .filter(|c| c.outer_class_name(cp).as_deref() == Some(name.as_str()))
+        .filter(|c| c.inner_name(cp).is_some())
+        .filter(|c| !c.is_synthetic())
+        .map(|c| c.inner_class_name(cp))
+        .collect()
+}
+
+/// Declares the local classes of the methods in their bodies. The declaration goes in
+/// the innermost block around all the `new`s of the class, before the first one, and
+/// the outer instance and captured variables javac passes to them are left out.
+fn local_classes(ctx: &Context, methods: &mut [MethodDecl]) -> Result<()> {
+    let cp = &ctx.cf.constant_pool;
+    for entry in ctx.cf.inner_classes() {
+        let Some(local_name) = entry.inner_name(cp) else {
+            continue;
+        };
+        if entry.outer_class_name(cp).is_some() || entry.is_synthetic() {
+            continue;
+        }
+        let class = entry.inner_class_name(cp);
+        let Ok(cf) = ctx.class_path.load(&class) else {
+            continue;
+        };
+        let Some(enclosing) = cf.enclosing_method().transpose()? else {
+            continue;
+        };
+        if enclosing.class != ctx.class_name {
+            continue;
+        }
+        // classes of initializers and lambdas are not placed yet
+        let Some(method) = methods.iter_mut().find(|m| {
+            m.code == Code::Body
+                && enclosing.method_name.as_ref() == Some(&m.name)
+                && enclosing.method_descriptor.as_ref() == Some(&m.descriptor)
+        }) else {
+            continue;
+        };
+        let mut decl = class_decl(ctx.class_path, &cf)?;
+        decl.local_name = Some(local_name);
+
+        // the captured variables are the last arguments, in the order of their fields
+        let captured = cf
+            .fields
+            .fields
+            .iter()
+            .filter_map(|f| {
+                let name = cf.constant_pool.utf8(f.name_index).ok()?;
+                name.strip_prefix("val$").map(str::to_string)
+            })
+            .collect::<Vec<_>>();
+        let mut captures = None;
+        rewrite_exprs(&mut method.body.stmts, &mut |e| {
+            let Expr::New(new) = e else {
+                return;
+            };
+            if new.class != class || new.args.len() < captured.len() {
+                return;
+            }
+            let args = new.args.split_off(new.args.len() - captured.len());
+            if let Ok(mut descriptor) = MethodDescriptor::parse(&new.descriptor) {
+                let params = descriptor.parameters.len().saturating_sub(captured.len());
+                descriptor.parameters.truncate(params);
+                new.descriptor = descriptor.descriptor();
+            }
+            captures.get_or_insert(args);
+        });
+        let captures = captured
+            .into_iter()
+            .zip(captures.unwrap_or_default())
+            .collect();
+        declare_local_class(
+            &mut method.body.stmts,
+            LocalClass {
+                class: decl,
+                captures,
+            },
+        );
+    }
+    Ok(())
+}
+
+fn declare_local_class(stmts: &mut Vec<Stmt>, local: LocalClass) {
+    let class = local.class.name.as_str();
+    let creates = |stmts: &[Stmt]| {
+        let mut found = false;
+        visit_exprs(stmts, &mut |e| {
+            found |= matches!(e, Expr::New(new) if new.class == class);
+        });
+        found
+    };
+    let users = (0..stmts.len())
+        .filter(|k| creates(&stmts[*k..*k + 1]))
+        .collect::<Vec<_>>();
+    if let [k] = users[..] {
+        let stmt = &stmts[k];
+        let mut in_exprs = false;
+        for e in stmt.exprs() {
+            e.visit(&mut |e| in_exprs |= matches!(e, Expr::New(new) if new.class == class));
+        }
+        let blocks = stmt.blocks();
+        let inner = (0..blocks.len())
+            .filter(|b| creates(blocks[*b]))
+            .collect::<Vec<_>>();
+        // a `for` initializer can't declare a class
+        let is_for_init = matches!(stmt, Stmt::For { .. }) && inner == [0];
+        if let ([b], false, false) = (&inner[..], in_exprs, is_for_init) {
+            let block = stmts[k].blocks_mut().swap_remove(*b);
+            return declare_local_class(block, local);
+        }
+    }
+    let at = users.first().copied().unwrap_or(0);
+    stmts.insert(at, Stmt::LocalClass(Box::new(local)));
+}
+
+/// The body of the anonymous class created by `new class(args)`, `None` if the class is
+/// not anonymous
+pub(super) fn anonymous_class(
+    ctx: &Context,
+    class: &str,
+    args: &[Expr],
+) -> Result<Option<AnonymousClass>> {
+    let Ok(cf) = ctx.class_path.load(class) else {
+        return Ok(None);
+    };
+    let cp = &cf.constant_pool;
+    let is_anonymous = cf
+        .attributes
+        .get_inner_classes()
+        .and_then(|a| a.find(class, cp))
+        .is_some_and(|e| e.is_anonymous());
+    if !is_anonymous {
+        return Ok(None);
+    }
+    let mut decl = class_decl(ctx.class_path, &cf)?;
+    let constructor = cf
+        .methods
+        .methods
+        .iter()
+        .find(|m| cp.utf8(m.name_index).ok() == Some("<init>"))
+        .ok_or(anyhow!("Anonymous class {} without constructor", class))?;
+    let inner_ctx = Context {
+        class_path: ctx.class_path,
+        cf: &cf,
+        class_name: class.to_string(),
+    };
+    // the constructor stores the outer instance and the captured variables, then
+    // passes the rest of its arguments to the super constructor
+    let body = method_body(&inner_ctx, constructor)?;
+    let param = |e: &Expr| match e {
+        Expr::Var(v) => body.params.iter().position(|p| p == v),
+        _ => None,
+    };
+    let mut captures = Vec::new();
+    let mut super_args = Vec::new();
+    for stmt in &body.stmts {
+        match stmt {
+            Stmt::Expr(Expr::Assign(target, value)) if is_synthetic_field(target) => {
+                let Expr::Field(_, field) = &**target else {
+                    unreachable!()
+                };
+                if let Some(name) = field.name.strip_prefix("val$") {
+                    let index = param(value).ok_or(anyhow!("Captured value is not a parameter"))?;
+                    let arg = args.get(index).ok_or(anyhow!("Missing captured value"))?;
+                    captures.push((name.to_string(), arg.clone()));
+                }
+            }
+            Stmt::ConstructorCall {
+                this: false,
+                args: call_args,
+                ..
+            } => {
+                for arg in call_args {
+                    super_args.push(match param(arg) {
+                        Some(index) => args
+                            .get(index)
+                            .cloned()
+                            .ok_or(anyhow!("Missing super constructor argument"))?,
+                        None if is_free(arg) => arg.clone(),
+                        None => return Err(anyhow!("Unsupported anonymous class constructor")),
+                    });
+                }
+                break;
+            }
+            _ => {}
+        }
+    }
+    decl.modifiers.clear();
+    Ok(Some(AnonymousClass {
+        class: decl,
+        super_args,
+        captures,
+    }))
+}
+
+/// The generic type of a lambda or method reference: the type parameters of the
+/// functional interface that appear in its method are the types of the instantiated
+/// method, e.g. `Function<String, Integer>` for `String::length`
+pub(super) fn functional_signature(
+    ctx: &Context,
+    interface: &str,
+    name: &str,
+    erased: &str,
+    instantiated: &str,
+) -> Option<String> {
+    let cf = ctx.class_path.load(interface).ok()?;
+    let cp = &cf.constant_pool;
+    let class_signature = parse_class_signature(&cf.attributes.get_signature(cp)?).ok()?;
+    let method = cf.find_method(name, erased)?;
+    let method_signature = parse_method_signature(&method.attributes.get_signature(cp)?).ok()?;
+    let instantiated = MethodDescriptor::parse(instantiated).ok()?;
+
+    let mut bindings: HashMap<String, String> = HashMap::new();
+    let pairs = method_signature
+        .parameters
+        .iter()
+        .zip(instantiated.parameters.iter().map(Some))
+        .chain(std::iter::once((
+            &method_signature.result,
+            instantiated.return_type.as_ref(),
+        )));
+    for (generic, actual) in pairs {
+        if let (
+            JavaTypeSignature::ReferenceTypeSignature(
+                ReferenceTypeSignature::TypeVariableSignature(variable),
+            ),
+            Some(actual),
+        ) = (generic, actual)
+        {
+            if actual.is_reference() {
+                bindings.insert(variable.clone(), actual.descriptor());
+            }
+        }
+    }
+    if bindings.is_empty() {
+        return None;
+    }
+    let arguments = class_signature
+        .type_parameters
+        .iter()
+        .map(|p| {
+            bindings
+                .get(&p.identifier)
+                .cloned()
+                .unwrap_or("*".to_string())
+        })
+        .collect::<String>();
+    Some(format!("L{}<{}>;", interface, arguments))
+}
@@ -0,0 +1,1199 @@
+//! Turns the instructions of every basic block into statements and expressions by
+//! simulating the operand stack with expressions instead of values
+//!
+//! A value stays on the stack as an expression until an instruction consumes it, so
+//! `iload_1; iconst_2; imul; istore_2` becomes `x2 = x1 * 2`. Values copied by `dup`
+//! are kept in temporary variables, which later turn back into `x = y = z`, `a[i]++`
+//! and array initializers. Values left on the stack at the end of a block are passed
+//! on to the next block, or assigned to a variable when control flow joins, which is
+//! how `?:` is recovered later.
+
+use super::ast::{
+    BinaryOp, CompareKind, Expr, Invoke, InvokeKind, Lambda, Member, MethodRef, MethodRefTarget,
+    New, Stmt, UnaryOp, VarId, VarKind,
+};
+use super::variables::Variables;
+use super::Context;
+use crate::class_file::bytecode::ByteCode;
+use crate::class_file::constant_pool::Info;
+use crate::class_file::dataflow::{DataflowResult, Definitions, State};
+use crate::class_file::methods::Method;
+use crate::class_file::{
+    BaseType, ControlFlowGraph, EdgeKind, FieldType, MemberRef, MethodDescriptor,
+    MethodHandleReferenceKind,
+};
+use anyhow::{anyhow, Result};
+use std::collections::{HashMap, HashSet};
+
+/// The statements of a basic block and how it is left
+#[derive(Debug)]
+pub(super) struct Block {
+    pub start_pc: u32,
+    pub stmts: Vec<Stmt>,
+    pub term: Terminator,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum Terminator {
+    /// Jumps or falls through to the block at the pc
+    Goto(u32),
+    /// Jumps to `target` if the condition holds, otherwise continues at `next`
+    If { cond: Expr, target: u32, next: u32 },
+    Switch {
+        value: Expr,
+        cases: Vec<(i32, u32)>,
+        default: u32,
+    },
+    /// The block ends with a `return` or `throw`
+    Exit,
+}
+
+impl Terminator {
+    pub fn targets(&self) -> Vec<u32> {
+        match self {
+            Terminator::Goto(target) => vec![*target],
+            Terminator::If { target, next, .. } => vec![*next, *target],
+            Terminator::Switch { cases, default, .. } => cases
+                .iter()
+                .map(|(_, target)| *target)
+                .chain(std::iter::once(*default))
+                .collect(),
+            Terminator::Exit => Vec::new(),
+        }
+    }
+}
+
+/// The blocks of the method in pc order, unreachable blocks are left out
+pub(super) fn build_blocks(
+    ctx: &Context,
+    method: &Method,
+    definitions: &DataflowResult<State<Definitions>>,
+    vars: &mut Variables,
+) -> Result<Vec<Block>> {
+    let cp = &ctx.cf.constant_pool;
+    let code = method
+        .get_code()
+        .ok_or(anyhow!("Method has no Code attribute"))?;
+    let cfg = ControlFlowGraph::build(code, cp)?;
+    let instructions = code.instructions().collect::<Vec<_>>();
+    let handlers = code
+        .exception_table()
+        .iter()
+        .map(|e| e.handler_pc as u32)
+        .collect::<HashSet<_>>();
+
+    let mut blocks: Vec<Block> = Vec::new();
+    // the stack left at the end of each block, by block index in the graph
+    let mut exits: HashMap<usize, Vec<Expr>> = HashMap::new();
+    // index in `blocks` of the blocks of the graph
+    let mut positions: HashMap<usize, usize> = HashMap::new();
+    let mut news = HashMap::new();
+    for (index, bb) in cfg.blocks.iter().enumerate() {
+        let Some(state) = definitions.before(bb.start_pc) else {
+            continue;
+        };
+        let mut stmts = Vec::new();
+        let stack = if handlers.contains(&bb.start_pc) {
+            vec![Expr::Caught]
+        } else if state.stack.is_empty() {
+            Vec::new()
+        } else {
+            let predecessors = bb
+                .predecessors
+                .iter()
+                .filter(|e| e.kind == EdgeKind::Normal && exits.contains_key(&e.block))
+                .map(|e| e.block)
+                .collect::<Vec<_>>();
+            if bb
+                .predecessors
+                .iter()
+                .any(|e| e.block >= index && e.kind == EdgeKind::Normal)
+            {
+                return Err(anyhow!("Values on the stack of a loop at {}", bb.start_pc));
+            }
+            match predecessors.as_slice() {
+                [] => return Err(anyhow!("No stack for the block at {}", bb.start_pc)),
+                [predecessor] => exits[predecessor].clone(),
+                _ => join_stacks(&predecessors, &exits, &positions, &mut blocks, vars),
+            }
+        };
+
+        let mut simulator = Simulator {
+            ctx,
+            vars: &mut *vars,
+            definitions,
+            stack,
+            stmts: &mut stmts,
+            news: &mut news,
+        };
+        let block_instructions = instructions
+            .iter()
+            .filter(|(pc, _)| bb.start_pc <= *pc && *pc < bb.end_pc)
+            .copied()
+            .collect::<Vec<_>>();
+        let term = simulator.run(&block_instructions, bb.end_pc)?;
+        let mut stack = std::mem::take(&mut simulator.stack);
+        if matches!(term, Terminator::If { .. } | Terminator::Switch { .. }) {
+            simulator.stack = stack;
+            simulator.spill(&[]);
+            stack = std::mem::take(&mut simulator.stack);
+        }
+        exits.insert(index, stack);
+        positions.insert(index, blocks.len());
+        blocks.push(Block {
+            start_pc: bb.start_pc,
+            stmts,
+            term,
+        });
+    }
+    Ok(blocks)
+}
+
+/// The stack at a block where several paths join: entries that are the same on all paths
+/// are kept, others are assigned to a new variable at the end of every predecessor
+fn join_stacks(
+    predecessors: &[usize],
+    exits: &HashMap<usize, Vec<Expr>>,
+    positions: &HashMap<usize, usize>,
+    blocks: &mut [Block],
+    vars: &mut Variables,
+) -> Vec<Expr> {
+    let depth = predecessors
+        .iter()
+        .map(|p| exits[p].len())
+        .min()
+        .unwrap_or(0);
+    let mut stack = Vec::with_capacity(depth);
+    for (k, first) in exits[&predecessors[0]][..depth].iter().enumerate() {
+        if first.is_trivial() && predecessors.iter().all(|p| exits[p][k] == *first) {
+            stack.push(first.clone());
+            continue;
+        }
+        let var = vars.add(VarKind::Stack, None);
+        for p in predecessors {
+            let value = exits[p][k].clone();
+            blocks[positions[p]]
+                .stmts
+                .push(Stmt::Expr(Expr::assign(Expr::Var(var), value)));
+        }
+        stack.push(Expr::Var(var));
+    }
+    stack
+}
+
+struct Simulator<'a, 'b> {
+    ctx: &'a Context<'a>,
+    vars: &'b mut Variables,
+    definitions: &'a DataflowResult<State<Definitions>>,
+    stack: Vec<Expr>,
+    stmts: &'b mut Vec<Stmt>,
+    /// The class of every `new` by pc
+    news: &'b mut HashMap<u32, String>,
+}
+
+impl Simulator<'_, '_> {
+    fn run(&mut self, instructions: &[(u32, &ByteCode)], end_pc: u32) -> Result<Terminator> {
+        let mut i = 0;
+        while i < instructions.len() {
+            let (pc, instruction) = instructions[i];
+            let next = instructions.get(i + 1).copied();
+            i += 1;
+            if let Some(term) = self
+                .instruction(pc, instruction, next, end_pc, &mut i)
+                .map_err(|e| anyhow!("{} at pc {}", e, pc))?
+            {
+                return Ok(term);
+            }
+        }
+        Ok(Terminator::Goto(end_pc))
+    }
+
+    fn pop(&mut self) -> Result<Expr> {
+        self.stack.pop().ok_or(anyhow!("Pop from an empty stack"))
+    }
+
+    fn pop_n(&mut self, n: usize) -> Result<Vec<Expr>> {
+        if self.stack.len() < n {
+            return Err(anyhow!(
+                "Pop of {} values from a stack of {}",
+                n,
+                self.stack.len()
+            ));
+        }
+        Ok(self.stack.split_off(self.stack.len() - n))
+    }
+
+    /// Sizes of the values on the stack before the instruction, top of the stack last
+    fn sizes(&self, pc: u32) -> Result<Vec<usize>> {
+        let state = self
+            .definitions
+            .before(pc)
+            .ok_or(anyhow!("No stack before {}", pc))?;
+        if state.stack.len() != self.stack.len() {
+            return Err(anyhow!(
+                "Stack of {} values, expected {}",
+                self.stack.len(),
+                state.stack.len()
+            ));
+        }
+        Ok(state.stack.iter().map(|d| d.size).collect())
+    }
+
+    fn is_temp(&self, var: VarId) -> bool {
+        self.vars.vars[var].kind == VarKind::Temp
+    }
+
+    fn emit(&mut self, stmt: Stmt) {
+        let written = written_vars(&stmt);
+        self.spill(&written);
+        self.stmts.push(stmt);
+    }
+
+    /// Moves the values on the stack that are not side effect free (or read a variable
+    /// that is about to be written) into temporary variables, so they are evaluated
+    /// before the next statement
+    fn spill(&mut self, written: &[VarId]) {
+        for i in 0..self.stack.len() {
+            let entry = &self.stack[i];
+            let spill = match entry {
+                Expr::Uninitialized(_) => false,
+                Expr::Var(v) => written.contains(v),
+                e => !e.is_trivial(),
+            };
+            if !spill {
+                continue;
+            }
+            let entry = std::mem::replace(&mut self.stack[i], Expr::Null);
+            let var = match &entry {
+                Expr::Assign(target, _) if matches!(**target, Expr::Var(t) if self.is_temp(t)) => {
+                    let Expr::Var(t) = **target else {
+                        unreachable!()
+                    };
+                    self.stmts.push(Stmt::Expr(entry));
+                    t
+                }
+                _ => {
+                    let t = self.vars.add(VarKind::Temp, None);
+                    self.stmts
+                        .push(Stmt::Expr(Expr::assign(Expr::Var(t), entry)));
+                    t
+                }
+            };
+            self.stack[i] = Expr::Var(var);
+        }
+    }
+
+    /// A copy of the value for `dup`: the value itself if it can be evaluated twice,
+    /// otherwise it is assigned to a temporary variable which is the copy
+    fn duplicate(&mut self, e: Expr) -> (Expr, Expr) {
+        if e.is_trivial() {
+            return (e.clone(), e);
+        }
+        if let Expr::Assign(target, _) = &e {
+            if let Expr::Var(t) = **target {
+                if self.is_temp(t) {
+                    return (e, Expr::Var(t));
+                }
+            }
+        }
+        let t = self.vars.add(VarKind::Temp, None);
+        (Expr::assign(Expr::Var(t), e), Expr::Var(t))
+    }
+
+    /// Copies the top `n` values below the `m` values under them
+    fn dup(&mut self, n: usize, m: usize) -> Result<()> {
+        let len = self.stack.len();
+        if len < n + m {
+            return Err(anyhow!("dup of {} values on a stack of {}", n + m, len));
+        }
+        // the copies move below values that were evaluated before them
+        if self.stack[len - n - m..len - n]
+            .iter()
+            .any(|e| !e.is_trivial())
+        {
+            self.spill(&[]);
+        }
+        let copies = self.stack.split_off(len - n);
+        let skipped = self.stack.split_off(len - n - m);
+        let mut seconds = Vec::new();
+        for e in copies {
+            let (first, second) = self.duplicate(e);
+            self.stack.push(first);
+            seconds.push(second);
+        }
+        self.stack.extend(skipped);
+        self.stack.extend(seconds);
+        Ok(())
+    }
+
+    /// The assignment `t = e` left on the stack by the `dup` of a value into `t`
+    fn find_twin(&mut self, t: VarId) -> Option<&mut Expr> {
+        let others = self.stack.iter().filter(|e| e.uses(t)).count();
+        let twin = self
+            .stack
+            .iter_mut()
+            .find(|e| matches!(e, Expr::Assign(target, _) if **target == Expr::Var(t)))?;
+        // the copy must not be used elsewhere
+        match others {
+            1 => Some(twin),
+            _ => None,
+        }
+    }
+
+    /// Stores the value, as part of the expression a `dup` copied if there is one
+    fn store(&mut self, target: Expr, value: Expr) -> Result<()> {
+        // `x = y = value` or `f(x = value)`: the value was copied before it was stored
+        if let Expr::Var(t) = value {
+            if self.is_temp(t) {
+                if let Some(twin) = self.find_twin(t) {
+                    let Expr::Assign(_, e) = std::mem::replace(twin, Expr::Null) else {
+                        unreachable!()
+                    };
+                    *twin = Expr::Assign(Box::new(target), e);
+                    return Ok(());
+                }
+            }
+        }
+        // `x++` as an expression, the old value was copied before it was incremented
+        if let Expr::Binary(op @ (BinaryOp::Add | BinaryOp::Sub), left, right) = &value {
+            if let Expr::Var(t) = **left {
+                if is_one(right) && self.is_temp(t) {
+                    let op = *op;
+                    if let Some(twin) = self.find_twin(t) {
+                        if matches!(twin, Expr::Assign(_, e) if **e == target) {
+                            *twin = Expr::Increment {
+                                target: Box::new(target),
+                                increment: op == BinaryOp::Add,
+                                prefix: false,
+                            };
+                            return Ok(());
+                        }
+                    }
+                }
+            }
+        }
+        // the elements of an array initializer are stored into the copy of the new array
+        if let Expr::ArrayElement(array, index) = &target {
+            if let Expr::Var(t) = **array {
+                if self.is_temp(t) {
+                    if let Some(Expr::Assign(_, init)) = self.find_twin(t) {
+                        match (&mut **init, &**index) {
+                            (Expr::NewArray(ty, dimensions), Expr::Int(0))
+                                if dimensions.len() == 1 =>
+                            {
+                                if let Expr::Int(size) = dimensions[0] {
+                                    **init =
+                                        Expr::ArrayInit(ty.clone(), size as usize, vec![value]);
+                                    return Ok(());
+                                }
+                            }
+                            (Expr::ArrayInit(_, size, elements), Expr::Int(i))
+                                if *i as usize == elements.len() && elements.len() < *size =>
+                            {
+                                elements.push(value);
+                                return Ok(());
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+            }
+        }
+        self.emit(Stmt::Expr(Expr::assign(target, value)));
+        Ok(())
+    }
+
+    fn load(&self, pc: u32) -> Result<Expr> {
+        Ok(match self.vars.at(pc)? {
+            Some(var) => Expr::Var(var),
+            None => Expr::This,
+        })
+    }
+
+    fn binary(&mut self, op: BinaryOp) -> Result<()> {
+        let right = self.pop()?;
+        let left = self.pop()?;
+        let e = match (op, &right) {
+            // javac compiles `~x` to `x ^ -1`
+            (BinaryOp::Xor, Expr::Int(-1) | Expr::Long(-1)) => {
+                Expr::Unary(UnaryOp::BitNot, Box::new(left))
+            }
+            _ => Expr::binary(op, left, right),
+        };
+        self.stack.push(e);
+        Ok(())
+    }
+
+    fn cast(&mut self, base_type: BaseType) -> Result<()> {
+        let e = self.pop()?;
+        self.stack
+            .push(Expr::Cast(FieldType::Base(base_type), Box::new(e)));
+        Ok(())
+    }
+
+    fn compare(&mut self, kind: CompareKind) -> Result<()> {
+        let right = self.pop()?;
+        let left = self.pop()?;
+        self.stack
+            .push(Expr::Compare(kind, Box::new(left), Box::new(right)));
+        Ok(())
+    }
+
+    /// The condition of `if<op>`, which compares the value with 0
+    fn compare_zero(&mut self, op: BinaryOp) -> Result<Expr> {
+        let value = self.pop()?;
+        let Expr::Compare(kind, left, right) = value else {
+            return Ok(Expr::binary(op, value, Expr::Int(0)));
+        };
+        Ok(match kind {
+            CompareKind::Long => Expr::Binary(op, left, right),
+            CompareKind::Float { nan_greater, .. } => {
+                // with NaN the comparison result is 1 or -1 and every Java comparison
+                // but `!=` is false
+                let nan = if nan_greater { 1 } else { -1 };
+                let jumps_on_nan = match op {
+                    BinaryOp::Eq => false,
+                    BinaryOp::Ne => true,
+                    BinaryOp::Lt => nan < 0,
+                    BinaryOp::Ge => nan >= 0,
+                    BinaryOp::Gt => nan > 0,
+                    BinaryOp::Le => nan <= 0,
+                    _ => unreachable!(),
+                };
+                match (jumps_on_nan, op) {
+                    (_, BinaryOp::Eq | BinaryOp::Ne) | (false, _) => Expr::Binary(op, left, right),
+                    (true, _) => Expr::not(Expr::Binary(op.negated().unwrap(), left, right)),
+                }
+            }
+        })
+    }
+
+    fn if_cmp(&mut self, op: BinaryOp) -> Result<Expr> {
+        let right = self.pop()?;
+        let left = self.pop()?;
+        Ok(Expr::binary(op, left, right))
+    }
+
+    fn constant(&self, index: u16) -> Result<Expr> {
+        let cp = &self.ctx.cf.constant_pool;
+        Ok(match cp.try_get(index) {
+            Some(Info::IntegerInfo(v)) => Expr::Int(*v),
+            Some(Info::FloatInfo(v)) => Expr::Float(*v),
+            Some(Info::LongInfo(v)) => Expr::Long(*v),
+            Some(Info::DoubleInfo(v)) => Expr::Double(*v),
+            Some(Info::StringInfo(_)) => Expr::String(cp.string(index)?.to_string()),
+            Some(Info::ClassInfo(_)) => {
+                Expr::Class(FieldType::from_binary_name(cp.class_name(index)?)?)
+            }
+            info => return Err(anyhow!("Unsupported constant {:?}", info)),
+        })
+    }
+
+    fn branch(pc: u32, offset: i32) -> u32 {
+        (pc as i64 + offset as i64) as u32
+    }
+
+    /// Simulates the instruction, returns how the block ends for the last one
+    fn instruction(
+        &mut self,
+        pc: u32,
+        instruction: &ByteCode,
+        next: Option<(u32, &ByteCode)>,
+        end_pc: u32,
+        index: &mut usize,
+    ) -> Result<Option<Terminator>> {
+        let next_pc = next.map(|(pc, _)| pc).unwrap_or(end_pc);
+        let cp = &self.ctx.cf.constant_pool;
+        match instruction {
+            ByteCode::Nop => {}
+            ByteCode::AConstNull => self.stack.push(Expr::Null),
+            ByteCode::IConstn(n) => self.stack.push(Expr::Int(*n as i32)),
+            ByteCode::LConstn(n) => self.stack.push(Expr::Long(*n as i64)),
+            ByteCode::FConstn(n) => self.stack.push(Expr::Float(*n as f32)),
+            ByteCode::DConstn(n) => self.stack.push(Expr::Double(*n as f64)),
+            ByteCode::BiPush(n) => self.stack.push(Expr::Int(*n as i32)),
+            ByteCode::SiPush(n) => self.stack.push(Expr::Int(*n as i32)),
+            ByteCode::Ldc(index) => self.stack.push(self.constant(*index as u16)?),
+            ByteCode::LdcW(index) | ByteCode::Ldc2W(index) => {
+                self.stack.push(self.constant(*index)?)
+            }
+            ByteCode::ILoad(_)
+            | ByteCode::Lload(_)
+            | ByteCode::FLoad(_)
+            | ByteCode::DLoad(_)
+            | ByteCode::ALoad(_) => {
+                let e = self.load(pc)?;
+                self.stack.push(e);
+            }
+            ByteCode::IALoad
+            | ByteCode::LALoad
+            | ByteCode::FALoad
+            | ByteCode::DALoad
+            | ByteCode::AALoad
+            | ByteCode::BALoad
+            | ByteCode::CALoad
+            | ByteCode::SALoad => {
+                let index = self.pop()?;
+                let array = self.pop()?;
+                self.stack
+                    .push(Expr::ArrayElement(Box::new(array), Box::new(index)));
+            }
+            ByteCode::IStore(_)
+            | ByteCode::LStore(_)
+            | ByteCode::FStore(_)
+            | ByteCode::DStore(_)
+            | ByteCode::AStore(_) => {
+                let value = self.pop()?;
+                let target = self.load(pc)?;
+                self.store(target, value)?;
+            }
+            ByteCode::IAStore
+            | ByteCode::LAStore
+            | ByteCode::FAStore
+            | ByteCode::DAStore
+            | ByteCode::AAStore
+            | ByteCode::BAStore
+            | ByteCode::CAStore
+            | ByteCode::SAStore => {
+                let value = self.pop()?;
+                let index = self.pop()?;
+                let array = self.pop()?;
+                self.store(Expr::ArrayElement(Box::new(array), Box::new(index)), value)?;
+            }
+            ByteCode::Pop => {
+                let value = self.pop()?;
+                self.discard(value, next);
+            }
+            ByteCode::Pop2 => {
+                let sizes = self.sizes(pc)?;
+                if sizes.last() == Some(&2) {
+                    let value = self.pop()?;
+                    self.discard(value, next);
+                } else {
+                    let values = self.pop_n(2)?;
+                    for value in values {
+                        self.discard(value, next);
+                    }
+                }
+            }
+            ByteCode::Duplicate => self.dup(1, 0)?,
+            ByteCode::DupX1 => self.dup(1, 1)?,
+            ByteCode::DupX2 => {
+                let sizes = self.sizes(pc)?;
+                let m = if sizes.len() >= 2 && sizes[sizes.len() - 2] == 2 {
+                    1
+                } else {
+                    2
+                };
+                self.dup(1, m)?
+            }
+            ByteCode::Dup2 | ByteCode::Dup2X1 | ByteCode::Dup2X2 => {
+                let sizes = self.sizes(pc)?;
+                let n = if sizes.last() == Some(&2) { 1 } else { 2 };
+                let m = match instruction {
+                    ByteCode::Dup2 => 0,
+                    ByteCode::Dup2X1 => 1,
+                    _ if sizes.len() > n && sizes[sizes.len() - n - 1] == 2 => 1,
+                    _ => 2,
+                };
+                self.dup(n, m)?
+            }
+            ByteCode::Swap => {
+                if self.stack.iter().rev().take(2).any(|e| !e.is_trivial()) {
+                    self.spill(&[]);
+                }
+                let len = self.stack.len();
+                if len < 2 {
+                    return Err(anyhow!("swap on a stack of {}", len));
+                }
+                self.stack.swap(len - 1, len - 2);
+            }
+            ByteCode::IAdd | ByteCode::LAdd | ByteCode::FAdd | ByteCode::DAdd => {
+                self.binary(BinaryOp::Add)?
+            }
+            ByteCode::ISub | ByteCode::LSub | ByteCode::FSub | ByteCode::DSub => {
+                self.binary(BinaryOp::Sub)?
+            }
+            ByteCode::IMul | ByteCode::LMul | ByteCode::FMul | ByteCode::DMul => {
+                self.binary(BinaryOp::Mul)?
+            }
+            ByteCode::IDiv | ByteCode::LDiv | ByteCode::FDiv | ByteCode::DDiv => {
+                self.binary(BinaryOp::Div)?
+            }
+            ByteCode::IRem | ByteCode::LRem | ByteCode::FRem | ByteCode::DRem => {
+                self.binary(BinaryOp::Rem)?
+            }
+            ByteCode::INeg | ByteCode::LNeg | ByteCode::FNeg | ByteCode::DNeg => {
+                let e = self.pop()?;
+                self.stack.push(Expr::Unary(UnaryOp::Neg, Box::new(e)));
+            }
+            ByteCode::IShl | ByteCode::LShl => self.binary(BinaryOp::Shl)?,
+            ByteCode::IShr | ByteCode::LShr => self.binary(BinaryOp::Shr)?,
+            ByteCode::IUShr | ByteCode::LUShr => self.binary(BinaryOp::UShr)?,
+            ByteCode::IAnd | ByteCode::LAnd => self.binary(BinaryOp::And)?,
+            ByteCode::IOr | ByteCode::LOr => self.binary(BinaryOp::Or)?,
+            ByteCode::IXor | ByteCode::LXor => self.binary(BinaryOp::Xor)?,
+            ByteCode::IInc(_, delta) => self.iinc(pc, *delta, next, index)?,
+            ByteCode::I2L | ByteCode::F2L | ByteCode::D2L => self.cast(BaseType::Long)?,
+            ByteCode::I2F | ByteCode::L2F | ByteCode::D2F => self.cast(BaseType::Float)?,
+            ByteCode::I2D | ByteCode::L2D | ByteCode::F2D => self.cast(BaseType::Double)?,
+            ByteCode::L2i | ByteCode::F2I | ByteCode::D2I => self.cast(BaseType::Int)?,
+            ByteCode::I2B => self.cast(BaseType::Byte)?,
+            ByteCode::I2C => self.cast(BaseType::Char)?,
+            ByteCode::I2S => self.cast(BaseType::Short)?,
+            ByteCode::Lcmp => self.compare(CompareKind::Long)?,
+            ByteCode::FCmpl | ByteCode::FCmpg | ByteCode::DCmpl | ByteCode::DCmpg => {
+                self.compare(CompareKind::Float {
+                    double: matches!(instruction, ByteCode::DCmpl | ByteCode::DCmpg),
+                    nan_greater: matches!(instruction, ByteCode::FCmpg | ByteCode::DCmpg),
+                })?
+            }
+            ByteCode::Ifeq(offset)
+            | ByteCode::Ifne(offset)
+            | ByteCode::Iflt(offset)
+            | ByteCode::Ifge(offset)
+            | ByteCode::Ifgt(offset)
+            | ByteCode::Ifle(offset) => {
+                let op = match instruction {
+                    ByteCode::Ifeq(_) => BinaryOp::Eq,
+                    ByteCode::Ifne(_) => BinaryOp::Ne,
+                    ByteCode::Iflt(_) => BinaryOp::Lt,
+                    ByteCode::Ifge(_) => BinaryOp::Ge,
+                    ByteCode::Ifgt(_) => BinaryOp::Gt,
+                    _ => BinaryOp::Le,
+                };
+                let cond = self.compare_zero(op)?;
+                return Ok(Some(Terminator::If {
+                    cond,
+                    target: Self::branch(pc, *offset as i32),
+                    next: next_pc,
+                }));
+            }
+            ByteCode::IfICmpEq(offset)
+            | ByteCode::IfICmpNe(offset)
+            | ByteCode::IfICmpLt(offset)
+            | ByteCode::IfICmpGe(offset)
+            | ByteCode::IfICmpGt(offset)
+            | ByteCode::IfICmpLe(offset)
+            | ByteCode::IfACmpEq(offset)
+            | ByteCode::IfACmpNe(offset) => {
+                let op = match instruction {
+                    ByteCode::IfICmpEq(_) | ByteCode::IfACmpEq(_) => BinaryOp::Eq,
+                    ByteCode::IfICmpNe(_) | ByteCode::IfACmpNe(_) => BinaryOp::Ne,
+                    ByteCode::IfICmpLt(_) => BinaryOp::Lt,
+                    ByteCode::IfICmpGe(_) => BinaryOp::Ge,
+                    ByteCode::IfICmpGt(_) => BinaryOp::Gt,
+                    _ => BinaryOp::Le,
+                };
+                let cond = self.if_cmp(op)?;
+                return Ok(Some(Terminator::If {
+                    cond,
+                    target: Self::branch(pc, *offset as i32),
+                    next: next_pc,
+                }));
+            }
+            ByteCode::IfNull(offset) | ByteCode::IfNonNull(offset) => {
+                let op = match instruction {
+                    ByteCode::IfNull(_) => BinaryOp::Eq,
+                    _ => BinaryOp::Ne,
+                };
+                let value = self.pop()?;
+                return Ok(Some(Terminator::If {
+                    cond: Expr::binary(op, value, Expr::Null),
+                    target: Self::branch(pc, *offset as i32),
+                    next: next_pc,
+                }));
+            }
+            ByteCode::Goto(offset) => {
+                return Ok(Some(Terminator::Goto(Self::branch(pc, *offset as i32))))
+            }
+            ByteCode::GotoW(offset) => {
+                return Ok(Some(Terminator::Goto(Self::branch(pc, *offset))))
+            }
+            ByteCode::TableSwitch(switch) => {
+                let value = self.pop()?;
+                let cases = switch
+                    .offsets
+                    .iter()
+                    .enumerate()
+                    .map(|(i, offset)| (switch.low + i as i32, Self::branch(pc, *offset)))
+                    .collect();
+                return Ok(Some(Terminator::Switch {
+                    value,
+                    cases,
+                    default: Self::branch(pc, switch.default),
+                }));
+            }
+            ByteCode::LookupSwitch(switch) => {
+                let value = self.pop()?;
+                let cases = switch
+                    .pairs
+                    .iter()
+                    .map(|(key, offset)| (*key, Self::branch(pc, *offset)))
+                    .collect();
+                return Ok(Some(Terminator::Switch {
+                    value,
+                    cases,
+                    default: Self::branch(pc, switch.default),
+                }));
+            }
+            ByteCode::IReturn
+            | ByteCode::LReturn
+            | ByteCode::FReturn
+            | ByteCode::DReturn
+            | ByteCode::AReturn => {
+                let value = self.pop()?;
+                self.emit(Stmt::Return(Some(value)));
+                return Ok(Some(Terminator::Exit));
+            }
+            ByteCode::Return => {
+                self.emit(Stmt::Return(None));
+                return Ok(Some(Terminator::Exit));
+            }
+            ByteCode::Athrow => {
+                let value = self.pop()?;
+                self.emit(Stmt::Throw(value));
+                return Ok(Some(Terminator::Exit));
+            }
+            ByteCode::GetStatic(index) => {
+                let field = cp.field_ref(*index)?;
+                self.stack.push(Expr::StaticField(member(
+                    field.owner,
+                    field.name,
+                    field.descriptor,
+                )));
+            }
+            ByteCode::PutStatic(index) => {
+                let field = cp.field_ref(*index)?;
+                let value = self.pop()?;
+                self.store(
+                    Expr::StaticField(member(field.owner, field.name, field.descriptor)),
+                    value,
+                )?;
+            }
+            ByteCode::GetField(index) => {
+                let field = cp.field_ref(*index)?;
+                let target = self.pop()?;
+                self.stack.push(Expr::Field(
+                    Box::new(target),
+                    member(field.owner, field.name, field.descriptor),
+                ));
+            }
+            ByteCode::PutField(index) => {
+                let field = cp.field_ref(*index)?;
+                let value = self.pop()?;
+                let target = self.pop()?;
+                self.store(
+                    Expr::Field(
+                        Box::new(target),
+                        member(field.owner, field.name, field.descriptor),
+                    ),
+                    value,
+                )?;
+            }
+            ByteCode::InvokeVirtual(index)
+            | ByteCode::InvokeSpecial(index)
+            | ByteCode::InvokeStatic(index)
+            | ByteCode::InvokeInterface(index, _) => self.invoke(instruction, *index)?,
+            ByteCode::InvokeDynamic(index) => {
+                let e = self.invoke_dynamic(*index)?;
+                self.stack.push(e);
+            }
+            ByteCode::New(index) => {
+                self.news.insert(pc, cp.class_name(*index)?.to_string());
+                self.stack.push(Expr::Uninitialized(pc));
+            }
+            ByteCode::NewArray(atype) => {
+                let base_type = match atype {
+                    4 => BaseType::Boolean,
+                    5 => BaseType::Char,
+                    6 => BaseType::Float,
+                    7 => BaseType::Double,
+                    8 => BaseType::Byte,
+                    9 => BaseType::Short,
+                    10 => BaseType::Int,
+                    11 => BaseType::Long,
+                    _ => return Err(anyhow!("Invalid newarray type {}", atype)),
+                };
+                let count = self.pop()?;
+                self.stack.push(Expr::NewArray(
+                    FieldType::Array(Box::new(FieldType::Base(base_type))),
+                    vec![count],
+                ));
+            }
+            ByteCode::ANewArray(index) => {
+                let component = FieldType::from_binary_name(cp.class_name(*index)?)?;
+                let count = self.pop()?;
+                self.stack.push(Expr::NewArray(
+                    FieldType::Array(Box::new(component)),
+                    vec![count],
+                ));
+            }
+            ByteCode::MultiANewArray(index, dimensions) => {
+                let ty = FieldType::from_binary_name(cp.class_name(*index)?)?;
+                let dimensions = self.pop_n(*dimensions as usize)?;
+                self.stack.push(Expr::NewArray(ty, dimensions));
+            }
+            ByteCode::ArrayLength => {
+                let array = self.pop()?;
+                self.stack.push(Expr::ArrayLength(Box::new(array)));
+            }
+            ByteCode::CheckCast(index) => {
+                let ty = FieldType::from_binary_name(cp.class_name(*index)?)?;
+                let e = self.pop()?;
+                self.stack.push(Expr::Cast(ty, Box::new(e)));
+            }
+            ByteCode::InstanceOf(index) => {
+                let ty = FieldType::from_binary_name(cp.class_name(*index)?)?;
+                let e = self.pop()?;
+                self.stack.push(Expr::InstanceOf(Box::new(e), ty));
+            }
+            ByteCode::MonitorEnter | ByteCode::MonitorExit => {
+                let lock = self.pop()?;
+                self.emit(Stmt::Monitor {
+                    enter: matches!(instruction, ByteCode::MonitorEnter),
+                    lock,
+                });
+            }
+            ByteCode::Jsr(_) | ByteCode::JsrW(_) | ByteCode::Ret(_) => {
+                return Err(anyhow!("Subroutines (jsr/ret) are not supported"))
+            }
+            ByteCode::Generic(opcode) => return Err(anyhow!("Reserved opcode {}", opcode)),
+        }
+        Ok(None)
+    }
+
+    /// A value popped off the stack, kept as a statement if evaluating it does something
+    fn discard(&mut self, value: Expr, next: Option<(u32, &ByteCode)>) {
+        if value.is_trivial() {
+            return;
+        }
+        // javac checks the receiver of a bound method reference `x::m` with
+        // `Objects.requireNonNull(x)` before the `invokedynamic`
+        if let Expr::Invoke(invoke) = &value {
+            if invoke.method.owner == "java/util/Objects"
+                && invoke.method.name == "requireNonNull"
+                && matches!(next, Some((_, ByteCode::InvokeDynamic(_))))
+            {
+                return;
+            }
+        }
+        self.emit(Stmt::Expr(value));
+    }
+
+    /// `x++` when the old value is on the stack, `++x` when the variable is loaded right
+    /// after, otherwise the statement `x += delta`
+    fn iinc(
+        &mut self,
+        pc: u32,
+        delta: i16,
+        next: Option<(u32, &ByteCode)>,
+        index: &mut usize,
+    ) -> Result<()> {
+        let var = self.vars.at(pc)?.ok_or(anyhow!("iinc of this"))?;
+        let target = Expr::Var(var);
+        if delta.abs() == 1 && self.stack.last() == Some(&target) {
+            self.stack.pop();
+            self.stack.push(Expr::Increment {
+                target: Box::new(target),
+                increment: delta > 0,
+                prefix: false,
+            });
+            return Ok(());
+        }
+        let loads_next = match next {
+            Some((next_pc, ByteCode::ILoad(_))) => self.vars.at(next_pc)? == Some(var),
+            _ => false,
+        };
+        if loads_next && !self.stack.is_empty() {
+            *index += 1;
+            let e = match delta.abs() {
+                1 => Expr::Increment {
+                    target: Box::new(target),
+                    increment: delta > 0,
+                    prefix: true,
+                },
+                _ => Expr::assign(target.clone(), add(target, delta)),
+            };
+            self.stack.push(e);
+            return Ok(());
+        }
+        self.emit(Stmt::Expr(Expr::assign(target.clone(), add(target, delta))));
+        Ok(())
+    }
+
+    fn invoke(&mut self, instruction: &ByteCode, index: u16) -> Result<()> {
+        let cp = &self.ctx.cf.constant_pool;
+        let method = cp.method_ref(index)?;
+        let descriptor = MethodDescriptor::parse(&method.descriptor)?;
+        let args = self.pop_n(descriptor.parameters.len())?;
+        let is_static = matches!(instruction, ByteCode::InvokeStatic(_));
+        let receiver = match is_static {
+            true => None,
+            false => Some(self.pop()?),
+        };
+        let target = member(method.owner.clone(), method.name.clone(), method.descriptor);
+
+        if method.name == "<init>" {
+            match receiver {
+                Some(Expr::Uninitialized(new_pc)) => {
+                    let class = self
+                        .news
+                        .get(&new_pc)
+                        .cloned()
+                        .ok_or(anyhow!("No new for the constructor call"))?;
+                    let anonymous = super::class::anonymous_class(self.ctx, &class, &args)?;
+                    let args = match anonymous {
+                        Some(_) => Vec::new(),
+                        None => args,
+                    };
+                    let new = Expr::New(Box::new(New {
+                        class,
+                        descriptor: target.descriptor,
+                        args,
+                        anonymous,
+                    }));
+                    let mut copies = 0;
+                    for e in self.stack.iter_mut() {
+                        if *e == Expr::Uninitialized(new_pc) {
+                            *e = new.clone();
+                            copies += 1;
+                        }
+                    }
+                    if copies == 0 {
+                        self.emit(Stmt::Expr(new));
+                    }
+                }
+                Some(Expr::This) => self.emit(Stmt::ConstructorCall {
+                    this: method.owner == self.ctx.class_name,
+                    descriptor: target.descriptor,
+                    args,
+                }),
+                _ => return Err(anyhow!("Constructor call on an unknown object")),
+            }
+            return Ok(());
+        }
+
+        let kind = match (instruction, receiver) {
+            (_, None) => InvokeKind::Static,
+            (ByteCode::InvokeSpecial(_), Some(Expr::This))
+                if method.owner != self.ctx.class_name =>
+            {
+                match method.is_interface {
+                    true => InvokeKind::InterfaceSuper,
+                    false => InvokeKind::Super,
+                }
+            }
+            (_, Some(receiver)) => InvokeKind::Virtual(receiver),
+        };
+        let e = Expr::Invoke(Box::new(Invoke {
+            kind,
+            method: target,
+            args,
+        }));
+        match descriptor.return_type {
+            Some(_) => self.stack.push(e),
+            None => self.emit(Stmt::Expr(e)),
+        }
+        Ok(())
+    }
+
+    fn invoke_dynamic(&mut self, index: u16) -> Result<Expr> {
+        let cf = self.ctx.cf;
+        let cp = &cf.constant_pool;
+        let call_site = cp.invoke_dynamic(index)?;
+        let descriptor = MethodDescriptor::parse(&call_site.descriptor)?;
+        let args = self.pop_n(descriptor.parameters.len())?;
+        let bootstrap = cf
+            .attributes
+            .get_bootstrap_methods()
+            .and_then(|b| b.get(call_site.bootstrap_method_attr_index))
+            .ok_or(anyhow!("No bootstrap method for invokedynamic"))?;
+        let MemberRef::Method(bootstrap_method) =
+            cp.method_handle(bootstrap.method_ref())?.reference
+        else {
+            return Err(anyhow!("Bootstrap method is not a method"));
+        };
+        let arguments = bootstrap.arguments();
+        match (
+            bootstrap_method.owner.as_str(),
+            bootstrap_method.name.as_str(),
+        ) {
+            ("java/lang/invoke/StringConcatFactory", "makeConcatWithConstants") => {
+                let recipe = cp.string(
+                    *arguments
+                        .first()
+                        .ok_or(anyhow!("No recipe for makeConcatWithConstants"))?,
+                )?;
+                let mut constants = arguments[1..].iter();
+                // a `char` or `boolean` argument may still be an `int` expression here,
+                // the cast keeps it printed as a character or `true`/`false`, and an
+                // `int` argument may be a `char` expression printed as its code
+                let mut args =
+                    args.into_iter()
+                        .zip(&descriptor.parameters)
+                        .map(|(arg, ty)| match ty {
+                            FieldType::Base(
+                                BaseType::Boolean
+                                | BaseType::Char
+                                | BaseType::Byte
+                                | BaseType::Short
+                                | BaseType::Int,
+                            ) => Expr::Cast(ty.clone(), Box::new(arg)),
+                            _ => arg,
+                        });
+                let mut parts = Vec::new();
+                let mut literal = String::new();
+                for c in recipe.chars() {
+                    let part = match c {
+                        '\u{1}' => args.next().ok_or(anyhow!("Missing concat argument"))?,
+                        '\u{2}' => self.constant(
+                            *constants.next().ok_or(anyhow!("Missing concat constant"))?,
+                        )?,
+                        c => {
+                            literal.push(c);
+                            continue;
+                        }
+                    };
+                    if !literal.is_empty() {
+                        parts.push(Expr::String(std::mem::take(&mut literal)));
+                    }
+                    parts.push(part);
+                }
+                if !literal.is_empty() || parts.is_empty() {
+                    parts.push(Expr::String(literal));
+                }
+                Ok(Expr::Concat(parts))
+            }
+            ("java/lang/invoke/StringConcatFactory", "makeConcat") => Ok(Expr::Concat(args)),
+            ("java/lang/invoke/LambdaMetafactory", "metafactory" | "altMetafactory") => {
+                let [sam, implementation, instantiated, ..] = arguments else {
+                    return Err(anyhow!("Missing LambdaMetafactory arguments"));
+                };
+                let interface = match &descriptor.return_type {
+                    Some(FieldType::Object(name)) => name.clone(),
+                    _ => return Err(anyhow!("Lambda without a functional interface")),
+                };
+                let signature = super::class::functional_signature(
+                    self.ctx,
+                    &interface,
+                    &call_site.name,
+                    cp.method_type(*sam)?,
+                    cp.method_type(*instantiated)?,
+                );
+                let handle = cp.method_handle(*implementation)?;
+                let MemberRef::Method(target) = handle.reference else {
+                    return Err(anyhow!("Lambda implemented by a field"));
+                };
+                if target.owner == self.ctx.class_name && target.name.starts_with("lambda$") {
+                    return self.lambda(
+                        &target.name,
+                        &target.descriptor,
+                        args,
+                        interface,
+                        signature,
+                    );
+                }
+                let receiver = args.into_iter().next();
+                let (target_expr, name) = match (handle.kind, receiver) {
+                    (MethodHandleReferenceKind::RefNewInvokeSpecial, _) => {
+                        (MethodRefTarget::Class(target.owner), "new".to_string())
+                    }
+                    (MethodHandleReferenceKind::RefInvokeSpecial, Some(Expr::This))
+                        if target.owner == self.ctx.class_name =>
+                    {
+                        (MethodRefTarget::Expr(Expr::This), target.name)
+                    }
+                    (MethodHandleReferenceKind::RefInvokeSpecial, _) => {
+                        (MethodRefTarget::Super, target.name)
+                    }
+                    (_, Some(receiver)) => (MethodRefTarget::Expr(receiver), target.name),
+                    (_, None) => (MethodRefTarget::Class(target.owner), target.name),
+                };
+                Ok(Expr::MethodRef(Box::new(MethodRef {
+                    target: target_expr,
+                    name,
+                    interface,
+                    signature,
+                })))
+            }
+            (owner, name) => Err(anyhow!(
+                "Unsupported invokedynamic bootstrap method {}.{}",
+                owner,
+                name
+            )),
+        }
+    }
+
+    /// A lambda whose body is the synthetic method `name` of the class, the first
+    /// parameters of which are the captured values
+    fn lambda(
+        &mut self,
+        name: &str,
+        descriptor: &str,
+        args: Vec<Expr>,
+        interface: String,
+        signature: Option<String>,
+    ) -> Result<Expr> {
+        let method = self
+            .ctx
+            .cf
+            .find_method(name, descriptor)
+            .ok_or(anyhow!("No method {} for the lambda", name))?;
+        let body = super::method_body(self.ctx, method)?;
+        let mut args = args.into_iter();
+        if !method.is_static() {
+            // the receiver of an instance lambda is `this`
+            args.next();
+        }
+        let captures = body.params.iter().copied().zip(args).collect::<Vec<_>>();
+        let params = body.params[captures.len()..].to_vec();
+        Ok(Expr::Lambda(Box::new(Lambda {
+            body,
+            params,
+            captures,
+            interface,
+            signature,
+        })))
+    }
+}
+
+fn member(owner: String, name: String, descriptor: String) -> Member {
+    Member {
+        owner,
+        name,
+        descriptor,
+    }
+}
+
+/// `x + delta` of an `iinc`, `x - 1` for a negative delta
+fn add(target: Expr, delta: i16) -> Expr {
+    match delta < 0 {
+        true => Expr::binary(BinaryOp::Sub, target, Expr::Int(-(delta as i32))),
+        false => Expr::binary(BinaryOp::Add, target, Expr::Int(delta as i32)),
+    }
+}
+
+fn is_one(e: &Expr) -> bool {
+    matches!(e, Expr::Int(1) | Expr::Long(1))
+        || matches!(e, Expr::Float(f) if *f == 1.0)
+        || matches!(e, Expr::Double(d) if *d == 1.0)
+}
+
+/// The variables a statement assigns, directly or in nested assignments
+fn written_vars(stmt: &Stmt) -> Vec<VarId> {
+    let mut written = Vec::new();
+    for e in stmt.exprs() {
+        e.visit(&mut |e| match e {
+            Expr::Assign(target, _)
+            | Expr::CompoundAssign(_, target, _)
+            | Expr::Increment { target, .. } => {
+                if let Expr::Var(v) = **target {
+                    written.push(v);
+                }
+            }
+            _ => {}
+        });
+    }
+    written
+}
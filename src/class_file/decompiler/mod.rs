@@ -0,0 +1,62 @@
+//! Decompiles class files back to Java source
+//!
+//! A method body goes through these passes:
+//! - `variables`: splits the local slots into variables with the reaching definitions
+//! - `expressions`: simulates the operand stack to turn each block into statements
+//! - `structure`: nests the blocks into loops, `if`, `switch` and `try`
+//! - `sugar`: inlines temporaries and recovers `?:`, `for`, `foreach`, `synchronized`,
+//!   string switches and compound assignments, `types` infers the variable types and
+//!   turns `int` constants into `boolean` and `char` where the types ask for them
+//!
+//! `class` then lays out the class with its fields, methods and nested classes and
+//! `printer` prints it with imports.
+//!
+//! The output is meant to compile again and behave the same for the code javac
+//! generates. Methods that can't be decompiled are printed with a comment and a
+//! body that throws.
+
+mod ast;
+mod class;
+mod expressions;
+mod names;
+mod printer;
+mod structure;
+mod sugar;
+mod types;
+mod variables;
+
+pub use class::decompile;
+
+use crate::class_file::dataflow;
+use crate::class_file::methods::Method;
+use crate::class_file::{ClassFile, ClassPath};
+use anyhow::{anyhow, Result};
+use ast::MethodBody;
+use variables::Variables;
+
+/// The class being decompiled
+pub(super) struct Context<'a> {
+    pub class_path: &'a ClassPath,
+    pub cf: &'a ClassFile,
+    /// Binary name of the class
+    pub class_name: String,
+}
+
+/// Decompiles the code of a method, or of the synthetic method of a lambda
+fn method_body(ctx: &Context, method: &Method) -> Result<MethodBody> {
+    let cp = &ctx.cf.constant_pool;
+    let code = method
+        .get_code()
+        .ok_or(anyhow!("Method has no Code attribute"))?;
+    let definitions = dataflow::reaching_definitions(method, &ctx.class_name, cp)?;
+    let mut vars = Variables::build(method, &definitions, cp)?;
+    let blocks = expressions::build_blocks(ctx, method, &definitions, &mut vars)?;
+    let stmts = structure::structure(blocks, code, cp, &mut vars)?;
+    let mut body = MethodBody {
+        vars: vars.vars,
+        params: vars.params,
+        stmts,
+    };
+    sugar::sugar(ctx, method, &mut body)?;
+    Ok(body)
+}
@@ -0,0 +1,191 @@
+//! Names for the variables of a method
+//!
+//! Variables keep their name from the `LocalVariableTable`, the others are named
+//! after their type, `i` for an `int` and `stringBuilder` for a `StringBuilder`, with a
+//! number when the name is taken.
+
+use super::ast::{rewrite_exprs, Expr, MethodBody};
+use crate::class_file::{BaseType, FieldType};
+use std::collections::HashSet;
+
+const KEYWORDS: &[&str] = &[
+    "abstract",
+    "assert",
+    "boolean",
+    "break",
+    "byte",
+    "case",
+    "catch",
+    "char",
+    "class",
+    "const",
+    "continue",
+    "default",
+    "do",
+    "double",
+    "else",
+    "enum",
+    "extends",
+    "false",
+    "final",
+    "finally",
+    "float",
+    "for",
+    "goto",
+    "if",
+    "implements",
+    "import",
+    "instanceof",
+    "int",
+    "interface",
+    "long",
+    "native",
+    "new",
+    "null",
+    "package",
+    "private",
+    "protected",
+    "public",
+    "record",
+    "return",
+    "short",
+    "static",
+    "strictfp",
+    "super",
+    "switch",
+    "synchronized",
+    "this",
+    "throw",
+    "throws",
+    "transient",
+    "true",
+    "try",
+    "var",
+    "void",
+    "volatile",
+    "while",
+    "yield",
+    "_",
+];
+
+pub(super) fn is_keyword(name: &str) -> bool {
+    KEYWORDS.contains(&name)
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_alphabetic() || c == '_' || c == '$')
+        && chars.all(|c| c.is_alphanumeric() || c == '_' || c == '$')
+        && !is_keyword(name)
+}
+
+/// Names the variables of the body and of the lambdas in it, none of them the same as
+/// one of `reserved`
+pub(super) fn name_vars(body: &mut MethodBody, reserved: &HashSet<String>) {
+    let mut taken = reserved.clone();
+    // parameters first, they are the ones seen in the signature
+    let order = body
+        .params
+        .iter()
+        .copied()
+        .chain((0..body.vars.len()).filter(|v| !body.params.contains(v)))
+        .collect::<Vec<_>>();
+    for var in order {
+        let var = &mut body.vars[var];
+        let name = match &var.hint {
+            Some(hint) if is_identifier(hint) && !taken.contains(hint) => hint.clone(),
+            Some(hint) if is_identifier(hint) => unique(hint, &taken),
+            _ => unique(&type_name(var.ty.as_ref()), &taken),
+        };
+        taken.insert(name.clone());
+        var.name = name;
+    }
+    rewrite_exprs(&mut body.stmts, &mut |e| {
+        if let Expr::Lambda(lambda) = e {
+            name_vars(&mut lambda.body, &taken);
+        }
+    });
+}
+
+fn unique(base: &str, taken: &HashSet<String>) -> String {
+    // `i`, `j`, `k` ... before `i2`
+    if base == "i" {
+        for name in ["i", "j", "k", "m", "n"] {
+            if !taken.contains(name) {
+                return name.to_string();
+            }
+        }
+    }
+    if !taken.contains(base) && !is_keyword(base) {
+        return base.to_string();
+    }
+    (2..)
+        .map(|n| format!("{}{}", base, n))
+        .find(|name| !taken.contains(name))
+        .unwrap()
+}
+
+/// The name for a variable of the type
+pub(super) fn type_name(ty: Option<&FieldType>) -> String {
+    match ty {
+        None => "v".to_string(),
+        Some(FieldType::Base(base)) => match base {
+            BaseType::Int | BaseType::Short | BaseType::Byte => "i",
+            BaseType::Long => "l",
+            BaseType::Float => "f",
+            BaseType::Double => "d",
+            BaseType::Char => "c",
+            BaseType::Boolean => "flag",
+        }
+        .to_string(),
+        Some(FieldType::Object(class)) => class_var_name(class),
+        Some(array) => {
+            let element = array.element_type();
+            let base = match element {
+                FieldType::Base(base) => base.keyword().to_string(),
+                element => type_name(Some(element)),
+            };
+            format!("{}Array", base)
+        }
+    }
+}
+
+fn class_var_name(class: &str) -> String {
+    let simple = class.rsplit(['/', '$']).next().unwrap_or(class);
+    match simple {
+        "Object" => return "obj".to_string(),
+        "String" => return "str".to_string(),
+        _ => {}
+    }
+    if simple.ends_with("Exception") || simple.ends_with("Error") || simple == "Throwable" {
+        return "e".to_string();
+    }
+    let mut chars = simple.chars();
+    match chars.next() {
+        Some(first) if first.is_alphabetic() => {
+            // `URLConnection` is `urlConnection`
+            let upper = simple.chars().take_while(|c| c.is_uppercase()).count();
+            let lower = match upper {
+                0 | 1 => 1,
+                n if n == simple.chars().count() => n,
+                n => n - 1,
+            };
+            let name = simple
+                .chars()
+                .enumerate()
+                .map(|(i, c)| match i < lower {
+                    true => c.to_lowercase().next().unwrap_or(c),
+                    false => c,
+                })
+                .collect::<String>();
+            let name = name.replace('$', "");
+            match is_identifier(&name) {
+                true => name,
+                false => "obj".to_string(),
+            }
+        }
+        _ => "obj".to_string(),
+    }
+}
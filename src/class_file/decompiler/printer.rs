@@ -0,0 +1,1232 @@
+//! Prints a decompiled class as Java source
+//!
+//! The class is printed twice: the first time records the classes it names, which
+//! decide the imports, the second prints imported classes by their simple name.
+
+use super::ast::{
+    BinaryOp, Case, CaseValue, Catch, CompareKind, Expr, InvokeKind, Label, Lambda, MethodBody,
+    MethodRefTarget, New, Stmt, UnaryOp, VarId, Variable,
+};
+use super::class::{ClassDecl, ClassKind, ClassMember, Code, EnumConstant, FieldDecl, MethodDecl};
+use super::types::parameter_types;
+use crate::class_file::jaustp::signature::{
+    parse_class_signature, parse_method_signature, parse_signature, type_parameters_to_source,
+};
+use crate::class_file::{BaseType, FieldType, MethodDescriptor};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+const INDENT: &str = "    ";
+
+/// Precedence of expressions that are not operators: literals, names, calls
+const PRIMARY: u8 = 16;
+const POSTFIX: u8 = 15;
+const UNARY: u8 = 14;
+const CONDITIONAL: u8 = 3;
+const ASSIGNMENT: u8 = 2;
+const LAMBDA: u8 = 1;
+
+pub(super) fn print(decl: &ClassDecl) -> String {
+    let mut recorder = Printer::new(None);
+    recorder.class(decl, HashMap::new());
+    let (names, imports) = resolve_imports(decl, &recorder.used.borrow());
+
+    let mut printer = Printer::new(Some(names));
+    printer.class(decl, HashMap::new());
+    let mut out = String::new();
+    if let Some((package, _)) = decl.name.rsplit_once('/') {
+        out.push_str(&format!("package {};\n\n", package.replace('/', ".")));
+    }
+    for import in &imports {
+        out.push_str(&format!("import {};\n", import.replace(['/', '$'], ".")));
+    }
+    if !imports.is_empty() {
+        out.push('\n');
+    }
+    out.push_str(&printer.out);
+    out
+}
+
+fn package(binary_name: &str) -> &str {
+    binary_name.rsplit_once('/').map(|(p, _)| p).unwrap_or("")
+}
+
+/// The top level class of a binary name and its simple name, `java/util/Map` and `Map`
+/// for `java/util/Map$Entry`
+fn top_level(binary_name: &str) -> (String, &str) {
+    let (package, class) = match binary_name.rsplit_once('/') {
+        Some((package, class)) => (Some(package), class),
+        None => (None, binary_name),
+    };
+    let simple = match class.find('$') {
+        Some(0) | None => class,
+        Some(i) => &class[..i],
+    };
+    match package {
+        Some(package) => (format!("{}/{}", package, simple), simple),
+        None => (simple.to_string(), simple),
+    }
+}
+
+/// Picks the class printed by its simple name for every simple name: the class itself,
+/// a class of its package, one of `java.lang` or the first imported one. The others
+/// are printed with their package.
+fn resolve_imports(
+    decl: &ClassDecl,
+    used: &BTreeSet<String>,
+) -> (HashMap<String, String>, Vec<String>) {
+    let mut nested = HashSet::new();
+    nested_names(decl, &mut nested);
+    let (own, _) = top_level(&decl.name);
+    let own_package = package(&decl.name);
+
+    let mut by_simple: BTreeMap<&str, Vec<&String>> = BTreeMap::new();
+    for class in used {
+        let simple = class.rsplit('/').next().unwrap_or(class);
+        by_simple.entry(simple).or_default().push(class);
+    }
+    let mut names = HashMap::new();
+    let mut imports = Vec::new();
+    for (simple, candidates) in by_simple {
+        let winner = if candidates.contains(&&own) {
+            &own
+        } else if nested.contains(simple) {
+            // a member class of the same name hides the import
+            continue;
+        } else if let Some(c) = candidates.iter().find(|c| package(c) == own_package) {
+            c
+        } else if let Some(c) = candidates.iter().find(|c| package(c) == "java/lang") {
+            c
+        } else {
+            candidates[0]
+        };
+        if package(winner) != own_package && package(winner) != "java/lang" {
+            imports.push(winner.clone());
+        }
+        names.insert(simple.to_string(), winner.clone());
+    }
+    imports.sort();
+    (names, imports)
+}
+
+fn nested_names(decl: &ClassDecl, names: &mut HashSet<String>) {
+    for member in &decl.members {
+        if let ClassMember::Class(class) = member {
+            names.insert(simple_name(&class.name, true).to_string());
+            nested_names(class, names);
+        }
+    }
+}
+
+/// The name of the class in its declaration
+fn simple_name(binary_name: &str, nested: bool) -> &str {
+    let name = binary_name.rsplit('/').next().unwrap_or(binary_name);
+    match nested {
+        true => name.rsplit('$').next().unwrap_or(name),
+        false => name,
+    }
+}
+
+/// Whether the binary name is that of an anonymous class, `Outer$1`
+fn is_anonymous(binary_name: &str) -> bool {
+    match binary_name.rsplit_once('$') {
+        Some((_, n)) => !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()),
+        None => false,
+    }
+}
+
+/// The variables of the method or lambda being printed
+struct Scope<'a> {
+    vars: &'a [Variable],
+    /// The captured variables of a lambda, printed as the expression of the enclosing
+    /// method they are copied from
+    captures: HashMap<VarId, String>,
+}
+
+/// A class being printed, with the variables captured by an anonymous class
+struct ClassFrame {
+    name: String,
+    captures: HashMap<String, String>,
+}
+
+struct Printer<'a> {
+    out: String,
+    indent: usize,
+    /// The class printed for each simple name, `None` while recording
+    names: Option<HashMap<String, String>>,
+    /// Top level classes named in the source
+    used: RefCell<BTreeSet<String>>,
+    classes: Vec<ClassFrame>,
+    /// The names of the local classes declared so far, by binary name
+    local_names: HashMap<String, String>,
+    scopes: Vec<Scope<'a>>,
+    /// Loops (`true`) and switches around the statement, the targets of an unlabeled
+    /// `break`
+    breakables: Vec<(Label, bool)>,
+    /// Labels a `break` or `continue` names
+    labels: HashSet<Label>,
+}
+
+impl<'a> Printer<'a> {
+    fn new(names: Option<HashMap<String, String>>) -> Printer<'a> {
+        Printer {
+            out: String::new(),
+            indent: 0,
+            names,
+            used: RefCell::new(BTreeSet::new()),
+            classes: Vec::new(),
+            local_names: HashMap::new(),
+            scopes: Vec::new(),
+            breakables: Vec::new(),
+            labels: HashSet::new(),
+        }
+    }
+
+    fn line(&mut self, text: &str) {
+        for _ in 0..self.indent {
+            self.out.push_str(INDENT);
+        }
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+
+    fn blank_line(&mut self) {
+        if !self.out.is_empty() && !self.out.ends_with("{\n") && !self.out.ends_with("\n\n") {
+            self.out.push('\n');
+        }
+    }
+
+    /// The class as it is written in the source
+    fn class_name(&self, binary_name: &str) -> String {
+        if binary_name.starts_with('[') {
+            if let Ok(t) = FieldType::parse(binary_name) {
+                return self.ty(&t);
+            }
+        }
+        if let Some(name) = self.local_names.get(binary_name) {
+            return name.clone();
+        }
+        let (top, simple) = top_level(binary_name);
+        let nested = binary_name[top.len()..].replace('$', ".");
+        match &self.names {
+            None => {
+                self.used.borrow_mut().insert(top.clone());
+                binary_name.replace(['/', '$'], ".")
+            }
+            Some(names) if names.get(simple) == Some(&top) => format!("{}{}", simple, nested),
+            Some(_) => format!("{}{}", top.replace('/', "."), nested),
+        }
+    }
+
+    fn ty(&self, t: &FieldType) -> String {
+        match t {
+            FieldType::Base(base) => base.keyword().to_string(),
+            FieldType::Object(class) => self.class_name(class),
+            FieldType::Array(component) => format!("{}[]", self.ty(component)),
+        }
+    }
+
+    /// A field or variable type from its generic signature
+    fn signature(&self, signature: &str) -> Option<String> {
+        parse_signature(signature)
+            .ok()
+            .map(|s| s.to_source(&|b| self.class_name(b)))
+    }
+
+    fn var_type(&self, var: &Variable) -> String {
+        if let Some(t) = var.signature.as_deref().and_then(|s| self.signature(s)) {
+            return t;
+        }
+        match &var.ty {
+            Some(t) => self.ty(t),
+            None => self.class_name("java/lang/Object"),
+        }
+    }
+
+    fn var_name(&self, var: VarId) -> String {
+        let Some(scope) = self.scopes.last() else {
+            return format!("v{}", var);
+        };
+        if let Some(capture) = scope.captures.get(&var) {
+            return capture.clone();
+        }
+        scope
+            .vars
+            .get(var)
+            .map(|v| v.name.clone())
+            .unwrap_or(format!("v{}", var))
+    }
+
+    /// Whether a local variable hides a field of the name
+    fn is_shadowed(&self, name: &str) -> bool {
+        self.scopes
+            .iter()
+            .any(|s| s.vars.iter().any(|v| v.name == name))
+    }
+
+    fn current_class(&self) -> &str {
+        self.classes.last().map(|c| c.name.as_str()).unwrap_or("")
+    }
+
+    fn class(&mut self, decl: &'a ClassDecl, captures: HashMap<String, String>) {
+        self.blank_line();
+        let signature = decl
+            .signature
+            .as_deref()
+            .and_then(|s| parse_class_signature(s).ok());
+        let mut header = String::new();
+        for modifier in &decl.modifiers {
+            header.push_str(modifier);
+            header.push(' ');
+        }
+        header.push_str(match decl.kind {
+            ClassKind::Interface => "interface ",
+            ClassKind::Enum => "enum ",
+            ClassKind::Record => "record ",
+            ClassKind::Annotation => "@interface ",
+            ClassKind::Class | ClassKind::Anonymous => "class ",
+        });
+        header.push_str(match &decl.local_name {
+            Some(name) => name,
+            None => simple_name(&decl.name, !self.classes.is_empty()),
+        });
+        if let Some(signature) = &signature {
+            header.push_str(&type_parameters_to_source(
+                &signature.type_parameters,
+                &|b| self.class_name(b),
+            ));
+        }
+        if decl.kind == ClassKind::Record {
+            let components = decl
+                .record_components
+                .iter()
+                .map(|c| {
+                    let ty = match c.signature.as_deref().and_then(|s| self.signature(s)) {
+                        Some(ty) => ty,
+                        None => self.ty(&c.ty),
+                    };
+                    format!("{} {}", ty, c.name)
+                })
+                .collect::<Vec<_>>();
+            header.push_str(&format!("({})", components.join(", ")));
+        }
+        if decl.kind == ClassKind::Class && decl.super_type != "java/lang/Object" {
+            let super_type = match &signature {
+                Some(s) => s.superclass.to_source(&|b| self.class_name(b)),
+                None => self.class_name(&decl.super_type),
+            };
+            header.push_str(&format!(" extends {}", super_type));
+        }
+        let interfaces = match &signature {
+            Some(s) if decl.kind != ClassKind::Annotation => s
+                .superinterfaces
+                .iter()
+                .map(|i| i.to_source(&|b| self.class_name(b)))
+                .collect(),
+            _ => decl
+                .interfaces
+                .iter()
+                .map(|i| self.class_name(i))
+                .collect::<Vec<_>>(),
+        };
+        if !interfaces.is_empty() {
+            let keyword = match decl.kind {
+                ClassKind::Interface => "extends",
+                _ => "implements",
+            };
+            header.push_str(&format!(" {} {}", keyword, interfaces.join(", ")));
+        }
+        if !decl.permits.is_empty() {
+            let permits = decl.permits.iter().map(|p| self.class_name(p));
+            header.push_str(&format!(
+                " permits {}",
+                permits.collect::<Vec<_>>().join(", ")
+            ));
+        }
+        header.push_str(" {");
+        self.line(&header);
+        self.class_body(decl, captures);
+        self.line("}");
+    }
+
+    fn class_body(&mut self, decl: &'a ClassDecl, captures: HashMap<String, String>) {
+        self.classes.push(ClassFrame {
+            name: decl.name.clone(),
+            captures,
+        });
+        // the variables of the enclosing method are out of scope in the class
+        let scopes = std::mem::take(&mut self.scopes);
+        let breakables = std::mem::take(&mut self.breakables);
+        self.indent += 1;
+
+        let constants = decl
+            .members
+            .iter()
+            .filter_map(|m| match m {
+                ClassMember::EnumConstant(c) => Some(c),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        for (k, constant) in constants.iter().enumerate() {
+            let end = match k + 1 == constants.len() {
+                true => ";",
+                false => ",",
+            };
+            self.enum_constant(constant, end);
+        }
+        if decl.kind == ClassKind::Enum && constants.is_empty() && !decl.members.is_empty() {
+            self.line(";");
+        }
+        let mut previous = None;
+        for member in &decl.members {
+            match member {
+                ClassMember::EnumConstant(_) => continue,
+                ClassMember::Field(field) => {
+                    if previous != Some("field") {
+                        self.blank_line();
+                    }
+                    previous = Some("field");
+                    self.field(field, decl.kind);
+                }
+                ClassMember::Method(method) => {
+                    previous = Some("method");
+                    self.method(method, decl);
+                }
+                ClassMember::Initializer { is_static, body } => {
+                    previous = Some("initializer");
+                    self.blank_line();
+                    self.line(match is_static {
+                        true => "static {",
+                        false => "{",
+                    });
+                    self.body(body);
+                    self.line("}");
+                }
+                ClassMember::Class(class) => {
+                    previous = Some("class");
+                    self.class(class, HashMap::new());
+                }
+            }
+        }
+
+        self.indent -= 1;
+        self.breakables = breakables;
+        self.scopes = scopes;
+        self.classes.pop();
+    }
+
+    fn enum_constant(&mut self, constant: &'a EnumConstant, end: &str) {
+        let mut text = constant.name.clone();
+        if !constant.args.is_empty() {
+            let args = constant
+                .args
+                .iter()
+                .map(|a| self.expr(a, LAMBDA))
+                .collect::<Vec<_>>();
+            text.push_str(&format!("({})", args.join(", ")));
+        }
+        match &constant.body {
+            Some(body) => {
+                self.line(&format!("{} {{", text));
+                self.class_body(body, HashMap::new());
+                self.line(&format!("}}{}", end));
+            }
+            None => self.line(&format!("{}{}", text, end)),
+        }
+    }
+
+    fn field(&mut self, field: &'a FieldDecl, kind: ClassKind) {
+        let mut text = String::new();
+        for modifier in &field.modifiers {
+            text.push_str(modifier);
+            text.push(' ');
+        }
+        let ty = match field.signature.as_deref().and_then(|s| self.signature(s)) {
+            Some(ty) => ty,
+            None => self.ty(&field.ty),
+        };
+        text.push_str(&format!("{} {}", ty, field.name));
+        match &field.init {
+            Some(init) => text.push_str(&format!(" = {}", self.expr(init, LAMBDA))),
+            // interface fields are initialized
+            None if matches!(kind, ClassKind::Interface | ClassKind::Annotation) => {
+                text.push_str(&format!(" = {}", default_value(&field.ty)))
+            }
+            None => {}
+        }
+        text.push(';');
+        self.line(&text);
+    }
+
+    fn method(&mut self, method: &'a MethodDecl, class: &ClassDecl) {
+        self.blank_line();
+        let signature = method
+            .signature
+            .as_deref()
+            .and_then(|s| parse_method_signature(s).ok());
+        self.scopes.push(Scope {
+            vars: &method.body.vars,
+            captures: HashMap::new(),
+        });
+        let mut text = String::new();
+        for modifier in &method.modifiers {
+            text.push_str(modifier);
+            text.push(' ');
+        }
+        if let Some(signature) = &signature {
+            let type_parameters =
+                type_parameters_to_source(&signature.type_parameters, &|b| self.class_name(b));
+            if !type_parameters.is_empty() {
+                text.push_str(&type_parameters);
+                text.push(' ');
+            }
+        }
+        let is_constructor = method.name == "<init>";
+        if is_constructor {
+            text.push_str(match &class.local_name {
+                Some(name) => name,
+                None => simple_name(&class.name, self.classes.len() > 1),
+            });
+        } else {
+            let result = match &signature {
+                Some(s) => s.result.to_source(&|b| self.class_name(b)),
+                None => match MethodDescriptor::parse(&method.descriptor)
+                    .ok()
+                    .and_then(|d| d.return_type)
+                {
+                    Some(t) => self.ty(&t),
+                    None => "void".to_string(),
+                },
+            };
+            text.push_str(&format!("{} {}", result, method.name));
+        }
+        let params = &method.body.params;
+        let end = params.len().saturating_sub(method.captured_params);
+        let params = &params[method.synthetic_params.min(end)..end];
+        let params = params
+            .iter()
+            .enumerate()
+            .map(|(k, p)| {
+                let var = &method.body.vars[*p];
+                let mut ty = self.var_type(var);
+                if method.varargs && k + 1 == params.len() && ty.ends_with("[]") {
+                    ty.truncate(ty.len() - 2);
+                    ty.push_str("...");
+                }
+                format!("{} {}", ty, var.name)
+            })
+            .collect::<Vec<_>>();
+        text.push_str(&format!("({})", params.join(", ")));
+        let throws = match &signature {
+            Some(s) if !s.throws.is_empty() => s
+                .throws
+                .iter()
+                .map(|t| t.to_source(&|b| self.class_name(b)))
+                .collect(),
+            _ => method
+                .throws
+                .iter()
+                .map(|t| self.class_name(t))
+                .collect::<Vec<_>>(),
+        };
+        if !throws.is_empty() {
+            text.push_str(&format!(" throws {}", throws.join(", ")));
+        }
+        match &method.code {
+            Code::None => {
+                text.push(';');
+                self.line(&text);
+            }
+            Code::Body => {
+                text.push_str(" {");
+                self.line(&text);
+                self.indent += 1;
+                self.stmts(&method.body.stmts);
+                self.indent -= 1;
+                self.line("}");
+            }
+            Code::Failed(error) => {
+                text.push_str(" {");
+                self.line(&text);
+                self.indent += 1;
+                for line in error.lines() {
+                    self.line(&format!("// Could not decompile: {}", line));
+                }
+                let exception = self.class_name("java/lang/UnsupportedOperationException");
+                self.line(&format!("throw new {}();", exception));
+                self.indent -= 1;
+                self.line("}");
+            }
+        }
+        self.scopes.pop();
+    }
+
+    /// The statements of an initializer
+    fn body(&mut self, body: &'a MethodBody) {
+        self.scopes.push(Scope {
+            vars: &body.vars,
+            captures: HashMap::new(),
+        });
+        self.indent += 1;
+        self.stmts(&body.stmts);
+        self.indent -= 1;
+        self.scopes.pop();
+    }
+
+    fn stmts(&mut self, stmts: &'a [Stmt]) {
+        for stmt in stmts {
+            self.stmt(stmt);
+        }
+    }
+
+    /// Prints the statement with a label if a `break` or `continue` needs one
+    fn stmt(&mut self, stmt: &'a Stmt) {
+        let start = self.out.len();
+        let label = stmt.label();
+        match stmt {
+            Stmt::Switch { label, .. } => self.breakables.push((*label, false)),
+            stmt if stmt.is_loop() => self.breakables.push((label.unwrap(), true)),
+            _ => {}
+        }
+        self.unlabeled_stmt(stmt);
+        if matches!(stmt, Stmt::Switch { .. }) || stmt.is_loop() {
+            self.breakables.pop();
+        }
+        if let Some(label) = label {
+            if self.labels.remove(&label) {
+                let text = format!("{}label{}:\n", INDENT.repeat(self.indent), label);
+                self.out.insert_str(start, &text);
+            }
+        }
+    }
+
+    fn unlabeled_stmt(&mut self, stmt: &'a Stmt) {
+        match stmt {
+            Stmt::Expr(e) => {
+                let e = self.expr(e, 0);
+                self.line(&format!("{};", e));
+            }
+            Stmt::Declare(var, init) => {
+                let text = self.declaration(*var, init.as_ref());
+                self.line(&format!("{};", text));
+            }
+            Stmt::Return(None) => self.line("return;"),
+            Stmt::Return(Some(e)) => {
+                let e = self.expr(e, 0);
+                self.line(&format!("return {};", e));
+            }
+            Stmt::Throw(e) => {
+                let e = self.expr(e, 0);
+                self.line(&format!("throw {};", e));
+            }
+            Stmt::If {
+                cond,
+                then,
+                otherwise,
+                ..
+            } => {
+                let cond = self.expr(cond, 0);
+                self.line(&format!("if ({}) {{", cond));
+                self.block(then);
+                self.else_branch(otherwise);
+            }
+            Stmt::While { cond, body, .. } => {
+                let cond = self.expr(cond, 0);
+                self.line(&format!("while ({}) {{", cond));
+                self.block(body);
+                self.line("}");
+            }
+            Stmt::DoWhile { body, cond, .. } => {
+                self.line("do {");
+                self.block(body);
+                let cond = self.expr(cond, 0);
+                self.line(&format!("}} while ({});", cond));
+            }
+            Stmt::For {
+                init,
+                cond,
+                update,
+                body,
+                ..
+            } => {
+                let init = self.for_init(init);
+                let cond = match cond {
+                    Some(cond) => self.expr(cond, 0),
+                    None => String::new(),
+                };
+                let update = update
+                    .iter()
+                    .map(|e| self.expr(e, 0))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let header = format!("for ({}; {}; {})", init, cond, update);
+                self.line(&format!("{} {{", header.replace("; )", ";)")));
+                self.block(body);
+                self.line("}");
+            }
+            Stmt::ForEach {
+                var,
+                iterable,
+                body,
+                ..
+            } => {
+                let var = &self.scopes.last().unwrap().vars[*var];
+                let declaration = format!("{} {}", self.var_type(var), var.name);
+                let iterable = self.expr(iterable, 0);
+                self.line(&format!("for ({} : {}) {{", declaration, iterable));
+                self.block(body);
+                self.line("}");
+            }
+            Stmt::Switch { value, cases, .. } => {
+                let value = self.expr(value, 0);
+                self.line(&format!("switch ({}) {{", value));
+                self.indent += 1;
+                for case in cases {
+                    self.case(case);
+                }
+                self.indent -= 1;
+                self.line("}");
+            }
+            Stmt::Try {
+                body,
+                catches,
+                finally,
+                ..
+            } => {
+                self.line("try {");
+                self.block(body);
+                for catch in catches {
+                    self.catch(catch);
+                }
+                if let Some(finally) = finally {
+                    self.line("} finally {");
+                    self.block(finally);
+                }
+                self.line("}");
+            }
+            Stmt::Synchronized { lock, body } => {
+                let lock = self.expr(lock, 0);
+                self.line(&format!("synchronized ({}) {{", lock));
+                self.block(body);
+                self.line("}");
+            }
+            Stmt::Break(label) => {
+                let text = match self.breakables.last() {
+                    Some((l, _)) if l == label => "break;".to_string(),
+                    _ => {
+                        self.labels.insert(*label);
+                        format!("break label{};", label)
+                    }
+                };
+                self.line(&text);
+            }
+            Stmt::Continue(label) => {
+                let innermost = self.breakables.iter().rev().find(|(_, is_loop)| *is_loop);
+                let text = match innermost {
+                    Some((l, _)) if l == label => "continue;".to_string(),
+                    _ => {
+                        self.labels.insert(*label);
+                        format!("continue label{};", label)
+                    }
+                };
+                self.line(&text);
+            }
+            Stmt::ConstructorCall {
+                this,
+                descriptor,
+                args,
+            } => {
+                let args = self.args(args, descriptor);
+                let keyword = match this {
+                    true => "this",
+                    false => "super",
+                };
+                self.line(&format!("{}({});", keyword, args));
+            }
+            Stmt::Monitor { enter, lock } => {
+                let lock = self.expr(lock, 0);
+                let instruction = match enter {
+                    true => "monitorenter",
+                    false => "monitorexit",
+                };
+                self.line(&format!("// {} {}", instruction, lock));
+            }
+            Stmt::LocalClass(local) => {
+                if let Some(name) = &local.class.local_name {
+                    self.local_names
+                        .insert(local.class.name.clone(), name.clone());
+                }
+                let captures = local
+                    .captures
+                    .iter()
+                    .map(|(name, e)| (name.clone(), self.expr(e, PRIMARY)))
+                    .collect();
+                self.class(&local.class, captures);
+            }
+        }
+    }
+
+    fn block(&mut self, stmts: &'a [Stmt]) {
+        self.indent += 1;
+        self.stmts(stmts);
+        self.indent -= 1;
+    }
+
+    /// `} else {`, or `} else if` for an `if` that is the only statement of the `else`
+    fn else_branch(&mut self, otherwise: &'a [Stmt]) {
+        match otherwise {
+            [] => self.line("}"),
+            [Stmt::If {
+                label,
+                cond,
+                then,
+                otherwise,
+            }] if !super::ast::breaks_to(then, *label)
+                && !super::ast::breaks_to(otherwise, *label) =>
+            {
+                let cond = self.expr(cond, 0);
+                self.line(&format!("}} else if ({}) {{", cond));
+                self.block(then);
+                self.else_branch(otherwise);
+            }
+            otherwise => {
+                self.line("} else {");
+                self.block(otherwise);
+                self.line("}");
+            }
+        }
+    }
+
+    fn case(&mut self, case: &'a Case) {
+        for value in &case.values {
+            let value = match value {
+                CaseValue::Int(n) => n.to_string(),
+                CaseValue::String(s) => string_literal(s),
+                CaseValue::Enum(name) => name.clone(),
+            };
+            self.line(&format!("case {}:", value));
+        }
+        if case.default {
+            self.line("default:");
+        }
+        self.block(&case.body);
+    }
+
+    fn catch(&mut self, catch: &'a Catch) {
+        let types = catch
+            .types
+            .iter()
+            .map(|t| self.class_name(t))
+            .collect::<Vec<_>>()
+            .join(" | ");
+        let name = self.var_name(catch.var);
+        self.line(&format!("}} catch ({} {}) {{", types, name));
+        self.block(&catch.body);
+    }
+
+    fn declaration(&mut self, var: VarId, init: Option<&'a Expr>) -> String {
+        let variable = &self.scopes.last().unwrap().vars[var];
+        let mut text = format!("{} {}", self.var_type(variable), variable.name);
+        if let Some(init) = init {
+            text.push_str(&format!(" = {}", self.expr(init, LAMBDA)));
+        }
+        text
+    }
+
+    /// The declarations or expressions before the first `;` of a `for`
+    fn for_init(&mut self, init: &'a [Stmt]) -> String {
+        let mut parts = Vec::new();
+        for stmt in init {
+            match stmt {
+                Stmt::Declare(var, value) if parts.is_empty() => {
+                    parts.push(self.declaration(*var, value.as_ref()))
+                }
+                Stmt::Declare(var, value) => {
+                    let mut text = self.var_name(*var);
+                    if let Some(value) = value {
+                        text.push_str(&format!(" = {}", self.expr(value, LAMBDA)));
+                    }
+                    parts.push(text);
+                }
+                Stmt::Expr(e) => parts.push(self.expr(e, 0)),
+                _ => {}
+            }
+        }
+        parts.join(", ")
+    }
+
+    /// The arguments of a call, with `null` cast to the parameter type so it picks the
+    /// same overload
+    fn args(&mut self, args: &'a [Expr], descriptor: &str) -> String {
+        let types = parameter_types(descriptor);
+        // arguments javac adds, like the outer instance, are not in the source
+        let skip = types.len().saturating_sub(args.len());
+        args.iter()
+            .enumerate()
+            .map(|(k, arg)| match (arg, types.get(k + skip)) {
+                (Expr::Null, Some(t)) => format!("({}) null", self.ty(t)),
+                _ => self.expr(arg, LAMBDA),
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Prints the expression, in parentheses if it binds less tightly than `min`
+    fn expr(&mut self, e: &'a Expr, min: u8) -> String {
+        let (text, precedence) = self.expr_precedence(e);
+        match precedence < min {
+            true => format!("({})", text),
+            false => text,
+        }
+    }
+
+    fn expr_precedence(&mut self, e: &'a Expr) -> (String, u8) {
+        let primary = |text: String| (text, PRIMARY);
+        match e {
+            Expr::Int(n) => number(n.to_string()),
+            Expr::Long(n) => number(format!("{}L", n)),
+            Expr::Float(f) => self.float(*f as f64, true),
+            Expr::Double(d) => self.float(*d, false),
+            Expr::Bool(b) => primary(b.to_string()),
+            Expr::Char(c) => primary(char_literal(*c)),
+            Expr::String(s) => primary(string_literal(s)),
+            Expr::Null => primary("null".to_string()),
+            Expr::Class(t) => primary(format!("{}.class", self.ty(t))),
+            Expr::Var(v) => primary(self.var_name(*v)),
+            Expr::This => primary("this".to_string()),
+            Expr::OuterThis(class) => primary(format!("{}.this", self.class_name(class))),
+            Expr::Caught | Expr::Uninitialized(_) => primary("null".to_string()),
+            Expr::Captured(name) => {
+                let capture = self
+                    .classes
+                    .last()
+                    .and_then(|c| c.captures.get(name))
+                    .cloned();
+                primary(capture.unwrap_or(name.clone()))
+            }
+            Expr::StaticField(field) => {
+                if field.owner == self.current_class() && !self.is_shadowed(&field.name) {
+                    return primary(field.name.clone());
+                }
+                primary(format!("{}.{}", self.class_name(&field.owner), field.name))
+            }
+            Expr::Field(object, field) => {
+                // an anonymous class has no name to qualify its `this` with
+                let implicit = match &**object {
+                    Expr::This => true,
+                    Expr::OuterThis(class) => is_anonymous(class),
+                    _ => false,
+                };
+                if implicit && !self.is_shadowed(&field.name) {
+                    return primary(field.name.clone());
+                }
+                let object = self.expr(object, PRIMARY);
+                primary(format!("{}.{}", object, field.name))
+            }
+            Expr::ArrayElement(array, index) => {
+                let array = match &**array {
+                    // `new int[1][0]` is an array creation
+                    Expr::NewArray(..) | Expr::ArrayInit(..) => {
+                        format!("({})", self.expr(array, 0))
+                    }
+                    array => self.expr(array, PRIMARY),
+                };
+                let index = self.expr(index, 0);
+                primary(format!("{}[{}]", array, index))
+            }
+            Expr::ArrayLength(array) => {
+                let array = self.expr(array, PRIMARY);
+                primary(format!("{}.length", array))
+            }
+            Expr::Binary(op, l, r) => {
+                let precedence = op.precedence();
+                let l = self.expr(l, precedence);
+                let r = self.expr(r, precedence + 1);
+                (format!("{} {} {}", l, op.symbol(), r), precedence)
+            }
+            Expr::Unary(op, operand) => {
+                let operand = self.expr(operand, UNARY);
+                let symbol = match op {
+                    UnaryOp::Neg => "-",
+                    UnaryOp::Not => "!",
+                    UnaryOp::BitNot => "~",
+                };
+                // `- -x` is not `--x`
+                let operand = match *op == UnaryOp::Neg && operand.starts_with('-') {
+                    true => format!("({})", operand),
+                    false => operand,
+                };
+                (format!("{}{}", symbol, operand), UNARY)
+            }
+            Expr::Cast(t, operand) => {
+                // `(Integer) -x` is a subtraction
+                let min = match t.is_reference() {
+                    true => POSTFIX,
+                    false => UNARY,
+                };
+                let operand = self.expr(operand, min);
+                (format!("({}) {}", self.ty(t), operand), UNARY)
+            }
+            Expr::InstanceOf(operand, t) => {
+                let operand = self.expr(operand, 10);
+                (format!("{} instanceof {}", operand, self.ty(t)), 10)
+            }
+            Expr::Invoke(invoke) => {
+                let method = &invoke.method;
+                let args = self.args(&invoke.args, &method.descriptor);
+                let target = match &invoke.kind {
+                    InvokeKind::Static if method.owner == self.current_class() => String::new(),
+                    InvokeKind::Static => format!("{}.", self.class_name(&method.owner)),
+                    InvokeKind::Virtual(Expr::This) => String::new(),
+                    InvokeKind::Virtual(Expr::OuterThis(class)) if is_anonymous(class) => {
+                        String::new()
+                    }
+                    InvokeKind::Virtual(receiver) => format!("{}.", self.expr(receiver, PRIMARY)),
+                    InvokeKind::Super => "super.".to_string(),
+                    InvokeKind::InterfaceSuper => {
+                        format!("{}.super.", self.class_name(&method.owner))
+                    }
+                };
+                primary(format!("{}{}({})", target, method.name, args))
+            }
+            Expr::New(new) => primary(self.new_object(new)),
+            Expr::NewArray(t, dimensions) => {
+                let mut text = format!("new {}", self.ty(t.element_type()));
+                for d in dimensions {
+                    text.push_str(&format!("[{}]", self.expr(d, 0)));
+                }
+                for _ in dimensions.len()..t.dimensions() {
+                    text.push_str("[]");
+                }
+                primary(text)
+            }
+            Expr::ArrayInit(t, size, elements) => {
+                let mut values = elements
+                    .iter()
+                    .map(|e| self.expr(e, LAMBDA))
+                    .collect::<Vec<_>>();
+                if let Some(component) = t.component_type() {
+                    while values.len() < *size {
+                        values.push(default_value(component));
+                    }
+                }
+                primary(format!("new {} {{{}}}", self.ty(t), values.join(", ")))
+            }
+            Expr::Conditional(c, a, b) => {
+                let c = self.expr(c, CONDITIONAL + 1);
+                let a = self.expr(a, CONDITIONAL);
+                let b = self.expr(b, CONDITIONAL);
+                (format!("{} ? {} : {}", c, a, b), CONDITIONAL)
+            }
+            Expr::Assign(target, value) => {
+                let target = self.expr(target, POSTFIX);
+                let value = self.expr(value, ASSIGNMENT);
+                (format!("{} = {}", target, value), ASSIGNMENT)
+            }
+            Expr::CompoundAssign(op, target, value) => {
+                let target = self.expr(target, POSTFIX);
+                let value = self.expr(value, ASSIGNMENT);
+                (format!("{} {}= {}", target, op.symbol(), value), ASSIGNMENT)
+            }
+            Expr::Increment {
+                target,
+                increment,
+                prefix,
+            } => {
+                let target = self.expr(target, POSTFIX);
+                let symbol = match increment {
+                    true => "++",
+                    false => "--",
+                };
+                match prefix {
+                    true => (format!("{}{}", symbol, target), UNARY),
+                    false => (format!("{}{}", target, symbol), POSTFIX),
+                }
+            }
+            Expr::Concat(parts) => {
+                let add = BinaryOp::Add.precedence();
+                let parts = parts
+                    .iter()
+                    .enumerate()
+                    .map(|(k, p)| match k {
+                        0 => self.expr(p, add),
+                        _ => self.expr(p, add + 1),
+                    })
+                    .collect::<Vec<_>>();
+                match parts.len() {
+                    0 => primary("\"\"".to_string()),
+                    _ => (parts.join(" + "), add),
+                }
+            }
+            Expr::Lambda(lambda) => (self.lambda(lambda), LAMBDA),
+            Expr::MethodRef(method_ref) => {
+                let target = match &method_ref.target {
+                    MethodRefTarget::Class(class) => self.class_name(class),
+                    MethodRefTarget::Expr(e) => self.expr(e, PRIMARY),
+                    MethodRefTarget::Super => "super".to_string(),
+                };
+                let name = match method_ref.name.as_str() {
+                    "<init>" => "new",
+                    name => name,
+                };
+                primary(format!("{}::{}", target, name))
+            }
+            Expr::Compare(kind, l, r) => {
+                let class = match kind {
+                    CompareKind::Long => "java/lang/Long",
+                    CompareKind::Float { double: false, .. } => "java/lang/Float",
+                    CompareKind::Float { double: true, .. } => "java/lang/Double",
+                };
+                let l = self.expr(l, LAMBDA);
+                let r = self.expr(r, LAMBDA);
+                primary(format!("{}.compare({}, {})", self.class_name(class), l, r))
+            }
+        }
+    }
+
+    fn float(&self, value: f64, single: bool) -> (String, u8) {
+        let class = match single {
+            true => "java/lang/Float",
+            false => "java/lang/Double",
+        };
+        if value.is_nan() {
+            return (format!("{}.NaN", self.class_name(class)), PRIMARY);
+        }
+        if value.is_infinite() {
+            let name = match value > 0.0 {
+                true => "POSITIVE_INFINITY",
+                false => "NEGATIVE_INFINITY",
+            };
+            return (format!("{}.{}", self.class_name(class), name), PRIMARY);
+        }
+        // the shortest representation that reads back as the same value
+        match single {
+            true => number(format!("{:?}f", value as f32)),
+            false => number(format!("{:?}", value)),
+        }
+    }
+
+    fn new_object(&mut self, new: &'a New) -> String {
+        let Some(anonymous) = &new.anonymous else {
+            let args = self.args(&new.args, &new.descriptor);
+            return format!("new {}({})", self.class_name(&new.class), args);
+        };
+        let class = &anonymous.class;
+        // the type arguments of an anonymous class are those of its super type
+        let signature = class
+            .signature
+            .as_deref()
+            .and_then(|s| parse_class_signature(s).ok());
+        let super_type = match signature {
+            Some(s) if class.super_type == s.superclass.binary_name() => {
+                s.superclass.to_source(&|b| self.class_name(b))
+            }
+            Some(s) => match s.superinterfaces.first() {
+                Some(i) => i.to_source(&|b| self.class_name(b)),
+                None => self.class_name(&class.super_type),
+            },
+            None => self.class_name(&class.super_type),
+        };
+        let args = self.args(&anonymous.super_args, &class.super_descriptor);
+        let captures = anonymous
+            .captures
+            .iter()
+            .map(|(name, e)| (name.clone(), self.expr(e, PRIMARY)))
+            .collect();
+        let out = std::mem::take(&mut self.out);
+        self.class_body(class, captures);
+        let body = std::mem::replace(&mut self.out, out);
+        format!(
+            "new {}({}) {{\n{}{}}}",
+            super_type,
+            args,
+            body,
+            INDENT.repeat(self.indent)
+        )
+    }
+
+    fn lambda(&mut self, lambda: &'a Lambda) -> String {
+        let captures = lambda
+            .captures
+            .iter()
+            .map(|(var, e)| (*var, self.expr(e, PRIMARY)))
+            .collect();
+        self.scopes.push(Scope {
+            vars: &lambda.body.vars,
+            captures,
+        });
+        let params = lambda
+            .params
+            .iter()
+            .map(|p| self.var_name(*p))
+            .collect::<Vec<_>>();
+        let params = match params.len() {
+            1 => params[0].clone(),
+            _ => format!("({})", params.join(", ")),
+        };
+        let body = match lambda.body.stmts.as_slice() {
+            [Stmt::Return(Some(e))] => self.expr(e, LAMBDA),
+            [Stmt::Expr(e @ (Expr::Invoke(_) | Expr::New(_) | Expr::Assign(..)))] => {
+                self.expr(e, LAMBDA)
+            }
+            stmts => {
+                let out = std::mem::take(&mut self.out);
+                let breakables = std::mem::take(&mut self.breakables);
+                self.indent += 1;
+                self.stmts(stmts);
+                self.indent -= 1;
+                self.breakables = breakables;
+                let body = std::mem::replace(&mut self.out, out);
+                format!("{{\n{}{}}}", body, INDENT.repeat(self.indent))
+            }
+        };
+        self.scopes.pop();
+        format!("{} -> {}", params, body)
+    }
+}
+
+/// Negative numbers are a unary minus applied to the literal
+fn number(text: String) -> (String, u8) {
+    match text.starts_with('-') {
+        true => (text, UNARY),
+        false => (text, PRIMARY),
+    }
+}
+
+fn default_value(t: &FieldType) -> String {
+    match t {
+        FieldType::Base(BaseType::Boolean) => "false",
+        FieldType::Base(BaseType::Char) => "'\\0'",
+        FieldType::Base(BaseType::Long) => "0L",
+        FieldType::Base(BaseType::Float) => "0.0f",
+        FieldType::Base(BaseType::Double) => "0.0",
+        FieldType::Base(_) => "0",
+        _ => "null",
+    }
+    .to_string()
+}
+
+/// Escapes a UTF-16 code unit for a character or string literal
+fn escape(c: u16, quote: char) -> String {
+    match char::from_u32(c as u32) {
+        Some('\n') => "\\n".to_string(),
+        Some('\t') => "\\t".to_string(),
+        Some('\r') => "\\r".to_string(),
+        Some('\u{8}') => "\\b".to_string(),
+        Some('\u{c}') => "\\f".to_string(),
+        Some('\\') => "\\\\".to_string(),
+        Some(c) if c == quote => format!("\\{}", c),
+        Some(c) if (c as u32) < 0x20 || c as u32 == 0x7f => format!("\\{:03o}", c as u32),
+        Some(c) if c.is_ascii() => c.to_string(),
+        _ => format!("\\u{:04x}", c),
+    }
+}
+
+fn char_literal(c: u16) -> String {
+    format!("'{}'", escape(c, '\''))
+}
+
+fn string_literal(s: &str) -> String {
+    let escaped = s.encode_utf16().map(|c| escape(c, '"')).collect::<String>();
+    format!("\"{}\"", escaped)
+}
@@ -0,0 +1,781 @@
+//! Nests the blocks into statements: loops from back edges, `if`/`else` from forward
+//! branches, `switch`, and `try` from the exception table
+//!
+//! javac lays code out in source order, so every construct covers a contiguous range
+//! of blocks. `range` turns the blocks of a range into statements, given where control
+//! continues when the range completes (`follow`). Jumps that don't go to the next
+//! statement become `break` or `continue` of an enclosing loop or switch.
+
+use super::ast::{completes_normally, Case, CaseValue, Catch, Expr, Label, Stmt, VarKind};
+use super::expressions::{Block, Terminator};
+use super::variables::Variables;
+use crate::class_file::attributes::CodeAttribute;
+use crate::class_file::ConstantPool;
+use anyhow::{anyhow, Result};
+use std::collections::{HashMap, HashSet};
+
+/// A terminator with block indexes instead of pcs, `blocks.len()` is the end of the method
+#[derive(Debug, Clone)]
+enum Term {
+    Goto(usize),
+    If {
+        cond: Expr,
+        target: usize,
+    },
+    Switch {
+        value: Expr,
+        cases: Vec<(i32, usize)>,
+        default: usize,
+    },
+    Exit,
+}
+
+impl Term {
+    /// The blocks the terminator jumps to, without falling through to the next block
+    fn jumps(&self, index: usize) -> Vec<usize> {
+        match self {
+            Term::Goto(target) if *target != index + 1 => vec![*target],
+            Term::If { target, .. } => vec![*target],
+            Term::Switch { cases, default, .. } => cases
+                .iter()
+                .map(|(_, target)| *target)
+                .chain(std::iter::once(*default))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn targets(&self, index: usize) -> Vec<usize> {
+        match self {
+            Term::Goto(target) => vec![*target],
+            Term::If { target, .. } => vec![index + 1, *target],
+            term => term.jumps(index),
+        }
+    }
+}
+
+/// An exception table entry with block indexes
+struct Entry {
+    start: usize,
+    end: usize,
+    handler: usize,
+    /// Binary name of the caught class, `None` for `finally`
+    catch_type: Option<String>,
+}
+
+/// A loop or switch that `break` or `continue` can jump out of
+struct Scope {
+    label: Label,
+    breaks: usize,
+    continues: Vec<usize>,
+}
+
+struct TryPlan {
+    handlers: Vec<usize>,
+    end: usize,
+}
+
+pub(super) fn structure(
+    mut blocks: Vec<Block>,
+    code: &CodeAttribute,
+    cp: &ConstantPool,
+    vars: &mut Variables,
+) -> Result<Vec<Stmt>> {
+    merge_conditions(&mut blocks, code);
+    let index_of = |pc: u32| blocks.partition_point(|b| b.start_pc < pc);
+    let mut terms = Vec::with_capacity(blocks.len());
+    let mut stmts = Vec::with_capacity(blocks.len());
+    for (i, block) in blocks.iter().enumerate() {
+        let exact = |pc: u32| {
+            let index = index_of(pc);
+            match blocks.get(index) {
+                Some(b) if b.start_pc == pc => Ok(index),
+                None if pc >= code.code_length() => Ok(index),
+                _ => Err(anyhow!("Jump to {} is not the start of a block", pc)),
+            }
+        };
+        terms.push(match &block.term {
+            Terminator::Goto(target) => Term::Goto(exact(*target)?),
+            Terminator::If { cond, target, next } => {
+                if exact(*next)? != i + 1 {
+                    return Err(anyhow!("Branch at block {} doesn't fall through", i));
+                }
+                Term::If {
+                    cond: cond.clone(),
+                    target: exact(*target)?,
+                }
+            }
+            Terminator::Switch {
+                value,
+                cases,
+                default,
+            } => Term::Switch {
+                value: value.clone(),
+                cases: cases
+                    .iter()
+                    .map(|(key, target)| Ok((*key, exact(*target)?)))
+                    .collect::<Result<_>>()?,
+                default: exact(*default)?,
+            },
+            Terminator::Exit => Term::Exit,
+        });
+        stmts.push(block.stmts.clone());
+    }
+
+    let mut entries = Vec::new();
+    for e in code.exception_table() {
+        let handler = index_of(e.handler_pc as u32);
+        if blocks.get(handler).map(|b| b.start_pc) != Some(e.handler_pc as u32) {
+            // the handler is unreachable
+            continue;
+        }
+        let start = index_of(e.start_pc as u32);
+        let end = index_of(e.end_pc as u32);
+        // javac covers the start of `finally` handlers with themselves
+        if start >= end || (start..end).contains(&handler) {
+            continue;
+        }
+        let catch_type = match e.catch_type {
+            0 => None,
+            index => Some(cp.class_name(index)?.to_string()),
+        };
+        entries.push(Entry {
+            start,
+            end,
+            handler,
+            catch_type,
+        });
+    }
+
+    let mut structurer = Structurer {
+        done: vec![false; entries.len()],
+        stmts,
+        terms,
+        entries,
+        loops_done: HashSet::new(),
+        scopes: Vec::new(),
+        next_label: 0,
+        vars,
+    };
+    let n = structurer.terms.len();
+    structurer.range(0, n, n)
+}
+
+/// Merges the conditions of `&&` and `||`: a branch to a block that only branches
+/// again becomes one branch on the combined condition
+fn merge_conditions(blocks: &mut Vec<Block>, code: &CodeAttribute) {
+    let handlers = code
+        .exception_table()
+        .iter()
+        .map(|e| e.handler_pc as u32)
+        .collect::<HashSet<_>>();
+    let coverage = |pc: u32| {
+        code.exception_table()
+            .iter()
+            .enumerate()
+            .filter(|(_, e)| e.start_pc as u32 <= pc && pc < e.end_pc as u32)
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>()
+    };
+    loop {
+        let mut predecessors: HashMap<u32, usize> = HashMap::new();
+        for block in blocks.iter() {
+            for target in block.term.targets() {
+                *predecessors.entry(target).or_default() += 1;
+            }
+        }
+        let mut merged = false;
+        for a in 0..blocks.len().saturating_sub(1) {
+            let b = a + 1;
+            let Terminator::If {
+                cond: c1,
+                target: t1,
+                next: n1,
+            } = &blocks[a].term
+            else {
+                continue;
+            };
+            let Terminator::If {
+                cond: c2,
+                target: t2,
+                next: n2,
+            } = &blocks[b].term
+            else {
+                continue;
+            };
+            let start = blocks[b].start_pc;
+            if *n1 != start
+                || !blocks[b].stmts.is_empty()
+                || predecessors.get(&start) != Some(&1)
+                || handlers.contains(&start)
+                || coverage(blocks[a].start_pc) != coverage(start)
+            {
+                continue;
+            }
+            let cond = if t1 == t2 {
+                Expr::binary(super::ast::BinaryOp::LogicalOr, c1.clone(), c2.clone())
+            } else if t1 == n2 {
+                Expr::binary(
+                    super::ast::BinaryOp::LogicalAnd,
+                    c1.clone().negate(),
+                    c2.clone(),
+                )
+            } else {
+                continue;
+            };
+            blocks[a].term = Terminator::If {
+                cond,
+                target: *t2,
+                next: *n2,
+            };
+            blocks.remove(b);
+            merged = true;
+            break;
+        }
+        if !merged {
+            break;
+        }
+    }
+}
+
+struct Structurer<'a> {
+    stmts: Vec<Vec<Stmt>>,
+    terms: Vec<Term>,
+    entries: Vec<Entry>,
+    done: Vec<bool>,
+    loops_done: HashSet<usize>,
+    scopes: Vec<Scope>,
+    next_label: Label,
+    vars: &'a mut Variables,
+}
+
+impl Structurer<'_> {
+    fn new_label(&mut self) -> Label {
+        self.next_label += 1;
+        self.next_label - 1
+    }
+
+    /// The statements of the blocks `from..end`, control continues at `follow` after them
+    fn range(&mut self, from: usize, end: usize, follow: usize) -> Result<Vec<Stmt>> {
+        let mut stmts = Vec::new();
+        let mut i = from;
+        while i < end {
+            i = self.statement(i, end, follow, &mut stmts)?;
+        }
+        Ok(stmts)
+    }
+
+    /// Adds the statement starting at block `i`, returns the block after it
+    fn statement(
+        &mut self,
+        i: usize,
+        end: usize,
+        follow: usize,
+        stmts: &mut Vec<Stmt>,
+    ) -> Result<usize> {
+        let loop_tail = self.loop_tail(i, end);
+        if let Some(plan) = self.try_plan(i, end) {
+            if loop_tail.is_none_or(|tail| tail < plan.handlers[0]) {
+                return self.try_stmt(i, plan, end, follow, stmts);
+            }
+        }
+        if let Some(tail) = loop_tail {
+            return self.loop_stmt(i, tail, stmts);
+        }
+
+        stmts.extend(self.stmts[i].iter().cloned());
+        let natural = if i + 1 < end { i + 1 } else { follow };
+        match self.terms[i].clone() {
+            Term::Exit => Ok(i + 1),
+            Term::Goto(target) => {
+                stmts.extend(self.jump(target, natural)?);
+                Ok(i + 1)
+            }
+            Term::If { cond, target } => self.if_stmt(i, cond, target, end, follow, stmts),
+            Term::Switch {
+                value,
+                cases,
+                default,
+            } => self.switch_stmt(i, value, cases, default, end, follow, stmts),
+        }
+    }
+
+    /// The `break` or `continue` that jumps to the block, nothing if control gets there
+    /// anyway
+    fn jump(&self, target: usize, natural: usize) -> Result<Option<Stmt>> {
+        if target == natural {
+            return Ok(None);
+        }
+        self.scope_jump(target)
+            .map(Some)
+            .ok_or(anyhow!("Unstructured jump to block {}", target))
+    }
+
+    fn scope_jump(&self, target: usize) -> Option<Stmt> {
+        for scope in self.scopes.iter().rev() {
+            if scope.breaks == target {
+                return Some(Stmt::Break(scope.label));
+            }
+            if scope.continues.contains(&target) {
+                return Some(Stmt::Continue(scope.label));
+            }
+        }
+        None
+    }
+
+    fn is_scope_target(&self, target: usize) -> bool {
+        self.scope_jump(target).is_some()
+    }
+
+    fn if_stmt(
+        &mut self,
+        i: usize,
+        cond: Expr,
+        target: usize,
+        end: usize,
+        follow: usize,
+        stmts: &mut Vec<Stmt>,
+    ) -> Result<usize> {
+        let natural = if i + 1 < end { i + 1 } else { follow };
+        let label = self.new_label();
+        if target == natural {
+            if cond.has_side_effects() {
+                stmts.push(Stmt::If {
+                    label,
+                    cond,
+                    then: Vec::new(),
+                    otherwise: Vec::new(),
+                });
+            }
+            stmts.extend(self.jump(i + 1, natural)?);
+            return Ok(i + 1);
+        }
+        if i < target && target < end {
+            // the block before the target jumps over the else part
+            if let Term::Goto(after) = self.terms[target - 1] {
+                if target - 1 > i && after > target && (after <= end || after == follow) {
+                    let (after, inner_follow) = match after < end {
+                        true => (after, after),
+                        false => (end, follow),
+                    };
+                    let then = self.range(i + 1, target, inner_follow)?;
+                    let otherwise = self.range(target, after, inner_follow)?;
+                    stmts.push(Stmt::If {
+                        label,
+                        cond: cond.negate(),
+                        then,
+                        otherwise,
+                    });
+                    return Ok(after);
+                }
+            }
+            let then = self.range(i + 1, target, target)?;
+            stmts.push(Stmt::If {
+                label,
+                cond: cond.negate(),
+                then,
+                otherwise: Vec::new(),
+            });
+            return Ok(target);
+        }
+        // `if (c) { ... }` at the end of a loop body rather than `if (!c) continue;`
+        if target == follow && i + 1 < end {
+            let then = self.range(i + 1, end, follow)?;
+            stmts.push(Stmt::If {
+                label,
+                cond: cond.negate(),
+                then,
+                otherwise: Vec::new(),
+            });
+            return Ok(end);
+        }
+        if let Some(jump) = self.scope_jump(target) {
+            stmts.push(Stmt::If {
+                label,
+                cond,
+                then: vec![jump],
+                otherwise: Vec::new(),
+            });
+            stmts.extend(self.jump(i + 1, natural)?);
+            return Ok(i + 1);
+        }
+        if target == follow {
+            let mut then = self.range(i + 1, end, follow)?;
+            if i + 1 >= end {
+                then.extend(self.jump(i + 1, follow)?);
+            }
+            stmts.push(Stmt::If {
+                label,
+                cond: cond.negate(),
+                then,
+                otherwise: Vec::new(),
+            });
+            return Ok(end.max(i + 1));
+        }
+        Err(anyhow!("Unstructured branch to block {}", target))
+    }
+
+    /// The last block of the loop with its header at `i`, which jumps back to it
+    fn loop_tail(&self, i: usize, end: usize) -> Option<usize> {
+        if self.loops_done.contains(&i) {
+            return None;
+        }
+        (i..end)
+            .rev()
+            .find(|&j| self.terms[j].targets(j).contains(&i))
+    }
+
+    fn loop_stmt(&mut self, i: usize, tail: usize, stmts: &mut Vec<Stmt>) -> Result<usize> {
+        self.loops_done.insert(i);
+        let exit = tail + 1;
+        let label = self.new_label();
+        match self.terms[tail].clone() {
+            Term::If { cond, target } if target == i => {
+                let continues = match tail > i && self.stmts[tail].is_empty() {
+                    true => vec![tail],
+                    false => Vec::new(),
+                };
+                self.scopes.push(Scope {
+                    label,
+                    breaks: exit,
+                    continues,
+                });
+                let body = self.range(i, tail, tail);
+                self.scopes.pop();
+                let mut body = body?;
+                body.extend(self.stmts[tail].iter().cloned());
+                stmts.push(Stmt::DoWhile { label, body, cond });
+            }
+            term => {
+                // a `while` unless a `continue` jumps to statements at the end of the
+                // loop, then they are the update of a `for`
+                let state = (self.loops_done.clone(), self.done.clone());
+                self.scopes.push(Scope {
+                    label,
+                    breaks: exit,
+                    continues: vec![i],
+                });
+                let body = self.range(i, exit, i);
+                self.scopes.pop();
+                match body {
+                    Ok(body) => stmts.push(Stmt::While {
+                        label,
+                        cond: Expr::Bool(true),
+                        body,
+                    }),
+                    Err(e) => {
+                        let has_update = matches!(term, Term::Goto(target) if target == i)
+                            && tail > i
+                            && !self.stmts[tail].is_empty()
+                            && self.stmts[tail].iter().all(|s| matches!(s, Stmt::Expr(_)));
+                        if !has_update {
+                            return Err(e);
+                        }
+                        (self.loops_done, self.done) = state;
+                        self.scopes.push(Scope {
+                            label,
+                            breaks: exit,
+                            continues: vec![tail],
+                        });
+                        let body = self.range(i, tail, tail);
+                        self.scopes.pop();
+                        let update = self.stmts[tail]
+                            .iter()
+                            .map(|s| match s {
+                                Stmt::Expr(e) => e.clone(),
+                                _ => unreachable!(),
+                            })
+                            .collect();
+                        stmts.push(Stmt::For {
+                            label,
+                            init: Vec::new(),
+                            cond: None,
+                            update,
+                            body: body?,
+                        });
+                    }
+                }
+            }
+        }
+        Ok(exit)
+    }
+
+    /// Where the statement that starts with `from` ends when nothing jumps past it: after
+    /// the blocks it jumps to, up to the first block it falls out of
+    fn extent(&self, from: usize, end: usize) -> usize {
+        let mut e = from + 1;
+        let mut k = from;
+        while k < e && k < end {
+            for target in self.terms[k].jumps(k) {
+                if target >= e && target < end && !self.is_scope_target(target) {
+                    e = target + 1;
+                }
+            }
+            k += 1;
+        }
+        e.min(end)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn switch_stmt(
+        &mut self,
+        i: usize,
+        value: Expr,
+        keys: Vec<(i32, usize)>,
+        default: usize,
+        end: usize,
+        follow: usize,
+        stmts: &mut Vec<Stmt>,
+    ) -> Result<usize> {
+        let mut starts = keys.iter().map(|(_, target)| *target).collect::<Vec<_>>();
+        starts.push(default);
+        starts.sort();
+        starts.dedup();
+        let last = *starts.last().unwrap();
+        // a `break` jumps to the end of the switch, past the start of the last case
+        let breaks = (i + 1..last)
+            .flat_map(|k| self.terms[k].jumps(k))
+            .filter(|t| *t >= last && *t <= end && !self.is_scope_target(*t))
+            .min();
+        let switch_end = match breaks {
+            Some(e) => e,
+            None if last >= end => end,
+            None => self.extent(last, end),
+        };
+        if starts[0] <= i {
+            return Err(anyhow!("Switch at block {} jumps backwards", i));
+        }
+        let label = self.new_label();
+        let after = if switch_end < end { switch_end } else { follow };
+        self.scopes.push(Scope {
+            label,
+            breaks: switch_end,
+            continues: Vec::new(),
+        });
+        let mut cases = Vec::new();
+        let breaking = keys
+            .iter()
+            .filter(|(_, target)| *target == switch_end && default != switch_end)
+            .map(|(key, _)| CaseValue::Int(*key))
+            .collect::<Vec<_>>();
+        if !breaking.is_empty() {
+            cases.push(Case {
+                values: breaking,
+                default: false,
+                body: vec![Stmt::Break(label)],
+            });
+        }
+        let starts = starts
+            .into_iter()
+            .filter(|s| *s < switch_end)
+            .collect::<Vec<_>>();
+        let mut result = Ok(());
+        for (k, start) in starts.iter().enumerate() {
+            let (case_end, case_follow) = match starts.get(k + 1) {
+                Some(next) => (*next, *next),
+                None => (switch_end, after),
+            };
+            let body = match self.range(*start, case_end, case_follow) {
+                Ok(body) => body,
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            };
+            cases.push(Case {
+                values: keys
+                    .iter()
+                    .filter(|(_, target)| target == start)
+                    .map(|(key, _)| CaseValue::Int(*key))
+                    .collect(),
+                default: *start == default,
+                body,
+            });
+        }
+        self.scopes.pop();
+        result?;
+        stmts.push(Stmt::Switch {
+            label,
+            value,
+            cases,
+        });
+        if switch_end >= end {
+            stmts.extend(self.jump(switch_end, follow)?);
+        }
+        Ok(switch_end.min(end).max(i + 1))
+    }
+
+    /// The handlers and the end of the outermost `try` starting at block `i`
+    fn try_plan(&self, i: usize, end: usize) -> Option<TryPlan> {
+        let starting = (0..self.entries.len())
+            .filter(|&k| !self.done[k] && self.entries[k].start == i)
+            .collect::<Vec<_>>();
+        let max_end = starting.iter().map(|&k| self.entries[k].end).max()?;
+        let mut handlers = starting
+            .iter()
+            .filter(|&&k| self.entries[k].end == max_end)
+            .map(|&k| self.entries[k].handler)
+            .collect::<Vec<_>>();
+        handlers.sort();
+        handlers.dedup();
+        let last = *handlers.last()?;
+        if handlers[0] <= i || last >= end {
+            return None;
+        }
+        let try_end = (i..last)
+            .flat_map(|k| self.terms[k].jumps(k))
+            .filter(|t| *t >= last && *t <= end && !self.is_scope_target(*t))
+            .min()
+            .unwrap_or_else(|| self.extent(last, end));
+        Some(TryPlan {
+            handlers,
+            end: try_end,
+        })
+    }
+
+    fn try_stmt(
+        &mut self,
+        i: usize,
+        plan: TryPlan,
+        end: usize,
+        follow: usize,
+        stmts: &mut Vec<Stmt>,
+    ) -> Result<usize> {
+        let mut types: HashMap<usize, Vec<Option<String>>> = HashMap::new();
+        for (k, entry) in self.entries.iter().enumerate() {
+            if plan.handlers.contains(&entry.handler) {
+                self.done[k] = true;
+                let types = types.entry(entry.handler).or_default();
+                if !types.contains(&entry.catch_type) {
+                    types.push(entry.catch_type.clone());
+                }
+            }
+        }
+        let after = if plan.end < end { plan.end } else { follow };
+        let body = self.range(i, plan.handlers[0], after)?;
+        let mut catches = Vec::new();
+        for (k, handler) in plan.handlers.iter().enumerate() {
+            let region_end = plan.handlers.get(k + 1).copied().unwrap_or(plan.end);
+            let mut body = self.range(*handler, region_end, after)?;
+            let var = self.catch_var(&mut body)?;
+            let types = types.remove(handler).unwrap_or_default();
+            catches.push((types, var, body));
+        }
+
+        let label = self.new_label();
+        let (last_types, var, last_body) = catches.last().unwrap();
+        let mut stmt = None;
+        if *last_types == [None] && last_body.last() == Some(&Stmt::Throw(Expr::Var(*var))) {
+            let finally = last_body[..last_body.len() - 1].to_vec();
+            let mut body = body.clone();
+            let mut others = catches[..catches.len() - 1].to_vec();
+            if strip_finally(&mut body, &finally)
+                && others
+                    .iter_mut()
+                    .all(|(_, _, body)| strip_finally(body, &finally))
+            {
+                stmt = Some(Stmt::Try {
+                    label,
+                    body,
+                    catches: others.into_iter().map(catch).collect(),
+                    finally: Some(finally),
+                });
+            }
+        }
+        let stmt = match stmt {
+            Some(stmt) => stmt,
+            None => Stmt::Try {
+                label,
+                body,
+                catches: catches.into_iter().map(catch).collect(),
+                finally: None,
+            },
+        };
+        stmts.push(stmt);
+        if plan.end >= end {
+            stmts.extend(self.jump(plan.end, follow)?);
+        }
+        Ok(plan.end.min(end))
+    }
+
+    /// Removes the store of the exception at the start of a handler
+    fn catch_var(&mut self, body: &mut Vec<Stmt>) -> Result<usize> {
+        match body.first() {
+            Some(Stmt::Expr(Expr::Assign(target, value))) if **value == Expr::Caught => {
+                if let Expr::Var(var) = **target {
+                    body.remove(0);
+                    return Ok(var);
+                }
+            }
+            Some(Stmt::Expr(Expr::Caught)) => {
+                body.remove(0);
+                return Ok(self.vars.add(VarKind::Local, None));
+            }
+            _ => {}
+        }
+        Err(anyhow!("Handler doesn't start with storing the exception"))
+    }
+}
+
+fn catch((types, var, body): (Vec<Option<String>>, usize, Vec<Stmt>)) -> Catch {
+    Catch {
+        types: types
+            .into_iter()
+            .map(|t| t.unwrap_or("java/lang/Throwable".to_string()))
+            .collect(),
+        var,
+        body,
+    }
+}
+
+/// Removes the copies of the `finally` block javac puts before every way out of the
+/// `try` and its catches, false if one is missing
+fn strip_finally(stmts: &mut Vec<Stmt>, finally: &[Stmt]) -> bool {
+    if !strip_exits(stmts, finally, &mut Vec::new()) {
+        return false;
+    }
+    if completes_normally(stmts) {
+        return strip_before(stmts, stmts.len(), finally);
+    }
+    true
+}
+
+fn strip_before(stmts: &mut Vec<Stmt>, at: usize, finally: &[Stmt]) -> bool {
+    if at < finally.len() || stmts[at - finally.len()..at] != *finally {
+        return false;
+    }
+    stmts.drain(at - finally.len()..at);
+    true
+}
+
+fn strip_exits(stmts: &mut Vec<Stmt>, finally: &[Stmt], inner: &mut Vec<Label>) -> bool {
+    let mut k = 0;
+    while k < stmts.len() {
+        let exits = match &stmts[k] {
+            Stmt::Return(_) => true,
+            Stmt::Break(label) | Stmt::Continue(label) => !inner.contains(label),
+            _ => false,
+        };
+        if exits {
+            if !strip_before(stmts, k, finally) {
+                return false;
+            }
+            k -= finally.len();
+        } else {
+            let label = stmts[k].label();
+            inner.extend(label);
+            let ok = stmts[k]
+                .blocks_mut()
+                .into_iter()
+                .all(|block| strip_exits(block, finally, inner));
+            if label.is_some() {
+                inner.pop();
+            }
+            if !ok {
+                return false;
+            }
+        }
+        k += 1;
+    }
+    true
+}
@@ -0,0 +1,1543 @@
+//! Turns the structured statements back into the code javac compiled them from
+//!
+//! Temporaries are inlined where that keeps the order of evaluation, `if`/`else`
+//! assigning a stack value becomes `?:`, `monitorenter` with its `finally` becomes
+//! `synchronized`, and loops over arrays and iterators, `for` loops, string switches
+//! and compound assignments are recognized. Then the variables are declared where
+//! they are first assigned.
+
+use super::ast::{
+    rewrite_blocks, rewrite_exprs, visit_exprs, BinaryOp, Case, CaseValue, Expr, InvokeKind, Label,
+    MethodBody, MethodRefTarget, Stmt, UnaryOp, VarId, VarKind,
+};
+use super::class;
+use super::types::{self, expr_type, field_info};
+use super::Context;
+use crate::class_file::bytecode::ByteCode;
+use crate::class_file::jaustp::signature::{
+    parse_signature, JavaTypeSignature, ReferenceTypeSignature, TypeArgument,
+};
+use crate::class_file::methods::Method;
+use crate::class_file::{BaseType, ClassHierarchy, FieldType, MethodDescriptor};
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+
+pub(super) fn sugar(ctx: &Context, method: &Method, body: &mut MethodBody) -> Result<()> {
+    let cp = &ctx.cf.constant_pool;
+    let descriptor = MethodDescriptor::parse(cp.utf8(method.descriptor_index)?)?;
+    let return_type = descriptor.return_type.as_ref();
+
+    outer_references(&mut body.stmts);
+    rewrite_blocks(&mut body.stmts, &mut synchronized);
+    catch_types(ctx, body);
+    parameter_signatures(ctx, method, body);
+    rewrite_blocks(&mut body.stmts, &mut |stmts| ternaries(stmts, &body.vars));
+    inline(ctx, body);
+
+    types::infer_var_types(ctx, body, return_type);
+    functional_signatures(body);
+    types::coerce(ctx, body, return_type);
+    inner_instances(ctx, body);
+    types::flip_comparisons(ctx, body);
+
+    loops(ctx, body);
+    rewrite_blocks(&mut body.stmts, &mut string_switch);
+    rewrite_blocks(&mut body.stmts, &mut |stmts| enum_switch(ctx, stmts));
+    compound_assignments(ctx, body);
+    types::flip_comparisons(ctx, body);
+
+    cleanup(body);
+    if body.stmts.last() == Some(&Stmt::Return(None)) {
+        body.stmts.pop();
+    }
+    declare(body);
+    validate(&body.stmts)
+}
+
+/// The synthetic fields of inner and anonymous classes, `this$0` for the outer instance
+/// and `val$x` for a captured variable, are read as `Outer.this` and `x`, also through
+/// the outer instances of a class nested in another, `this.this$1.this$0`. The
+/// constructor storing them is left alone.
+fn outer_references(stmts: &mut [Stmt]) {
+    let rewrite = |e: &mut Expr| {
+        e.rewrite(&mut |e| {
+            let Expr::Field(object, field) = e else {
+                return;
+            };
+            if !matches!(**object, Expr::This | Expr::OuterThis(_)) {
+                return;
+            }
+            if let Some(name) = field.name.strip_prefix("val$") {
+                *e = Expr::Captured(name.to_string());
+            } else if field.name.starts_with("this$") {
+                if let Ok(FieldType::Object(class)) = FieldType::parse(&field.descriptor) {
+                    *e = Expr::OuterThis(class);
+                }
+            }
+        })
+    };
+    for stmt in stmts {
+        if let Stmt::Expr(Expr::Assign(target, value)) = stmt {
+            if let Expr::Field(object, field) = &**target {
+                if **object == Expr::This
+                    && (field.name.starts_with("this$") || field.name.starts_with("val$"))
+                {
+                    rewrite(value);
+                    continue;
+                }
+            }
+        }
+        for e in stmt.exprs_mut() {
+            rewrite(e);
+        }
+        for block in stmt.blocks_mut() {
+            outer_references(block);
+        }
+    }
+}
+
+/// `new Inner(this)` of an inner member or local class is `new Inner()`, the outer
+/// instance is implicit
+fn inner_instances(ctx: &Context, body: &mut MethodBody) {
+    rewrite_exprs(&mut body.stmts, &mut |e| {
+        let Expr::New(new) = e else {
+            return;
+        };
+        if new.anonymous.is_some()
+            || !matches!(new.args.first(), Some(Expr::This | Expr::OuterThis(_)))
+        {
+            return;
+        }
+        let Ok(cf) = ctx.class_path.load(&new.class) else {
+            return;
+        };
+        let cp = &cf.constant_pool;
+        let is_inner = cf
+            .attributes
+            .get_inner_classes()
+            .and_then(|a| a.find(&new.class, cp))
+            .is_some_and(|c| match c.outer_class_name(cp) {
+                Some(_) => !c.is_static(),
+                // a local class in a static method has no outer instance
+                None => c.inner_name(cp).is_some() && class::outer_instance(&cf).is_some(),
+            });
+        if is_inner {
+            new.args.remove(0);
+        }
+    });
+}
+
+/// `monitorenter` followed by a `try` whose `finally` is the `monitorexit`
+fn synchronized(stmts: &mut Vec<Stmt>) {
+    let mut k = 0;
+    while k + 1 < stmts.len() {
+        let (
+            Stmt::Monitor { enter: true, lock },
+            Stmt::Try {
+                catches, finally, ..
+            },
+        ) = (&stmts[k], &stmts[k + 1])
+        else {
+            k += 1;
+            continue;
+        };
+        let Some(
+            [Stmt::Monitor {
+                enter: false,
+                lock: Expr::Var(copy),
+            }],
+        ) = finally.as_deref()
+        else {
+            k += 1;
+            continue;
+        };
+        if !catches.is_empty() {
+            k += 1;
+            continue;
+        }
+        let copy = *copy;
+        // `synchronized (x)` stores the lock in a variable, `monitorenter` is given the
+        // assignment or the value that was assigned just before
+        let (lock, start) = match lock {
+            Expr::Assign(target, value) if **target == Expr::Var(copy) => ((**value).clone(), k),
+            lock if k > 0
+                && stmts[k - 1] == Stmt::Expr(Expr::assign(Expr::Var(copy), lock.clone())) =>
+            {
+                (lock.clone(), k - 1)
+            }
+            _ => {
+                k += 1;
+                continue;
+            }
+        };
+        let Stmt::Try { body, .. } = stmts.remove(k + 1) else {
+            unreachable!()
+        };
+        stmts.drain(start..=k);
+        stmts.insert(start, Stmt::Synchronized { lock, body });
+        k = start + 1;
+    }
+}
+
+/// The type of the exception variables is the caught class
+fn catch_types(ctx: &Context, body: &mut MethodBody) {
+    fn visit(ctx: &Context, stmts: &[Stmt], types: &mut Vec<(VarId, String)>) {
+        for stmt in stmts {
+            if let Stmt::Try { catches, .. } = stmt {
+                for catch in catches {
+                    let class = catch
+                        .types
+                        .iter()
+                        .cloned()
+                        .reduce(|a, b| ctx.class_path.common_super_class(&a, &b))
+                        .unwrap_or("java/lang/Throwable".to_string());
+                    types.push((catch.var, class));
+                }
+            }
+            for block in stmt.blocks() {
+                visit(ctx, block, types);
+            }
+        }
+    }
+    let mut types = Vec::new();
+    visit(ctx, &body.stmts, &mut types);
+    for (var, class) in types {
+        body.vars[var].ty = Some(FieldType::Object(class));
+        body.vars[var].kind = VarKind::Local;
+    }
+}
+
+/// The generic types of the parameters from the `Signature` of the method
+fn parameter_signatures(ctx: &Context, method: &Method, body: &mut MethodBody) {
+    let cp = &ctx.cf.constant_pool;
+    let Some(signature) = method.attributes.get_signature(cp) else {
+        return;
+    };
+    let Some(parameters) = split_parameters(&signature) else {
+        return;
+    };
+    // enum and inner class constructors have parameters the signature leaves out
+    if parameters.len() != body.params.len() {
+        return;
+    }
+    for (var, parameter) in body.params.iter().zip(parameters) {
+        if body.vars[*var].signature.is_none()
+            && (parameter.trim_start_matches('[').starts_with('T') || parameter.contains('<'))
+        {
+            body.vars[*var].signature = Some(parameter);
+        }
+    }
+}
+
+/// The signatures of the parameters of a method signature, as written in it
+pub(super) fn split_parameters(signature: &str) -> Option<Vec<String>> {
+    let start = signature.find('(')?;
+    let end = signature.rfind(')')?;
+    let mut parameters = Vec::new();
+    let mut current = String::new();
+    let mut depth = 0;
+    let mut in_class = false;
+    for c in signature[start + 1..end].chars() {
+        current.push(c);
+        match c {
+            '<' => depth += 1,
+            '>' => depth -= 1,
+            'L' | 'T' if !in_class => in_class = true,
+            ';' if depth == 0 => in_class = false,
+            '[' => continue,
+            _ if in_class => continue,
+            _ => {}
+        }
+        if !in_class && depth == 0 && c != '[' {
+            parameters.push(std::mem::take(&mut current));
+        }
+    }
+    Some(parameters)
+}
+
+/// `if (c) { s = a; } else { s = b; }` of a value left on the stack is `s = c ? a : b`
+fn ternaries(stmts: &mut [Stmt], vars: &[super::ast::Variable]) {
+    for stmt in stmts.iter_mut() {
+        let Stmt::If {
+            cond,
+            then,
+            otherwise,
+            ..
+        } = stmt
+        else {
+            continue;
+        };
+        let ([Stmt::Expr(Expr::Assign(target, a))], [Stmt::Expr(Expr::Assign(other, b))]) =
+            (then.as_slice(), otherwise.as_slice())
+        else {
+            continue;
+        };
+        let Expr::Var(var) = **target else {
+            continue;
+        };
+        if **other != Expr::Var(var) || vars[var].kind != VarKind::Stack {
+            continue;
+        }
+        let value = Expr::Conditional(Box::new(cond.clone()), a.clone(), b.clone());
+        *stmt = Stmt::Expr(Expr::assign(Expr::Var(var), value));
+    }
+}
+
+/// How often every variable is read and written
+#[derive(Default)]
+struct Counts {
+    reads: HashMap<VarId, usize>,
+    writes: HashMap<VarId, usize>,
+}
+
+impl Counts {
+    fn of(stmts: &[Stmt]) -> Counts {
+        let mut counts = Counts::default();
+        let mut occurrences: HashMap<VarId, usize> = HashMap::new();
+        let mut assignments: HashMap<VarId, usize> = HashMap::new();
+        fn visit(
+            stmts: &[Stmt],
+            occurrences: &mut HashMap<VarId, usize>,
+            assignments: &mut HashMap<VarId, usize>,
+            writes: &mut HashMap<VarId, usize>,
+        ) {
+            for stmt in stmts {
+                for e in stmt.exprs() {
+                    e.visit(&mut |e| match e {
+                        Expr::Var(v) => *occurrences.entry(*v).or_default() += 1,
+                        Expr::Assign(target, _) => {
+                            if let Expr::Var(v) = **target {
+                                *assignments.entry(v).or_default() += 1;
+                                *writes.entry(v).or_default() += 1;
+                            }
+                        }
+                        Expr::CompoundAssign(_, target, _) | Expr::Increment { target, .. } => {
+                            if let Expr::Var(v) = **target {
+                                *writes.entry(v).or_default() += 1;
+                            }
+                        }
+                        _ => {}
+                    });
+                }
+                for block in stmt.blocks() {
+                    visit(block, occurrences, assignments, writes);
+                }
+            }
+        }
+        visit(
+            stmts,
+            &mut occurrences,
+            &mut assignments,
+            &mut counts.writes,
+        );
+        for (v, n) in occurrences {
+            let assigned = assignments.get(&v).copied().unwrap_or(0);
+            counts.reads.insert(v, n - assigned);
+        }
+        counts
+    }
+
+    fn reads(&self, var: VarId) -> usize {
+        self.reads.get(&var).copied().unwrap_or(0)
+    }
+
+    fn writes(&self, var: VarId) -> usize {
+        self.writes.get(&var).copied().unwrap_or(0)
+    }
+}
+
+/// Inlines the variables assigned once and read once in the next statement, where the
+/// value can be evaluated there without changing the order of the side effects
+fn inline(ctx: &Context, body: &mut MethodBody) {
+    loop {
+        let counts = Counts::of(&body.stmts);
+        let vars = &body.vars;
+        let mut changed = false;
+        rewrite_blocks(&mut body.stmts, &mut |stmts| {
+            let mut k = 0;
+            while k + 1 < stmts.len() {
+                let Stmt::Expr(Expr::Assign(target, value)) = &stmts[k] else {
+                    k += 1;
+                    continue;
+                };
+                let Expr::Var(var) = **target else {
+                    k += 1;
+                    continue;
+                };
+                let inlinable = match vars[var].kind {
+                    VarKind::Temp | VarKind::Stack => true,
+                    VarKind::Local => vars[var].hint.is_none(),
+                    VarKind::Parameter => false,
+                };
+                if !inlinable
+                    || counts.reads(var) != 1
+                    || counts.writes(var) != 1
+                    || value.uses(var)
+                    || matches!(**value, Expr::Caught)
+                {
+                    k += 1;
+                    continue;
+                }
+                let value = (**value).clone();
+                let inliner = Inliner {
+                    ctx,
+                    var,
+                    value: &value,
+                    effects: value.has_side_effects(),
+                };
+                if inliner.stmt(&stmts[k + 1]) == Walk::Found {
+                    for e in stmts[k + 1].exprs_mut() {
+                        e.rewrite(&mut |e| {
+                            if *e == Expr::Var(var) {
+                                *e = value.clone();
+                            }
+                        });
+                    }
+                    stmts.remove(k);
+                    changed = true;
+                } else {
+                    k += 1;
+                }
+            }
+        });
+        if !changed {
+            break;
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Walk {
+    NotFound,
+    Found,
+    Unsafe,
+}
+
+struct Inliner<'a> {
+    ctx: &'a Context<'a>,
+    var: VarId,
+    value: &'a Expr,
+    /// Whether evaluating the value has side effects
+    effects: bool,
+}
+
+impl Inliner<'_> {
+    fn stmt(&self, stmt: &Stmt) -> Walk {
+        // loop conditions are evaluated more than once
+        if matches!(
+            stmt,
+            Stmt::While { .. } | Stmt::DoWhile { .. } | Stmt::For { .. }
+        ) {
+            return Walk::Unsafe;
+        }
+        let functional = matches!(
+            stmt,
+            Stmt::Return(_) | Stmt::Declare(..) | Stmt::ConstructorCall { .. }
+        );
+        self.sequence(&stmt.exprs(), functional)
+    }
+
+    /// Walks expressions evaluated one after the other
+    fn sequence(&self, exprs: &[&Expr], functional: bool) -> Walk {
+        for e in exprs {
+            if e.uses(self.var) {
+                return self.walk(e, functional);
+            }
+            if !self.is_safe(e) {
+                return Walk::Unsafe;
+            }
+        }
+        Walk::NotFound
+    }
+
+    /// Finds the variable in `e`, `functional` if a lambda can be there
+    fn walk(&self, e: &Expr, functional: bool) -> Walk {
+        if *e == Expr::Var(self.var) {
+            let is_functional = matches!(self.value, Expr::Lambda(_) | Expr::MethodRef(_));
+            return match is_functional && !functional {
+                true => Walk::Unsafe,
+                false => Walk::Found,
+            };
+        }
+        match e {
+            Expr::Assign(target, value) => {
+                if **target == Expr::Var(self.var) {
+                    return Walk::Unsafe;
+                }
+                // the value is evaluated after the array, index or object of the target
+                let mut exprs = match &**target {
+                    Expr::Var(_) | Expr::StaticField(_) => Vec::new(),
+                    target => target.children(),
+                };
+                exprs.push(value);
+                if exprs.iter().any(|e| e.uses(self.var)) {
+                    return self.walk_children(&exprs, |k| k + 1 == exprs.len());
+                }
+                Walk::NotFound
+            }
+            Expr::Conditional(c, _, _) if !c.uses(self.var) && self.effects => Walk::Unsafe,
+            Expr::Binary(BinaryOp::LogicalAnd | BinaryOp::LogicalOr, l, _)
+                if !l.uses(self.var) && self.effects =>
+            {
+                Walk::Unsafe
+            }
+            Expr::Lambda(_) | Expr::New(_) if self.captures_var(e) => {
+                // captured values are copied into the lambda, only constants can be
+                // printed in place of the variable
+                match self.value {
+                    Expr::Int(_)
+                    | Expr::Long(_)
+                    | Expr::Float(_)
+                    | Expr::Double(_)
+                    | Expr::Bool(_)
+                    | Expr::Char(_)
+                    | Expr::String(_) => Walk::Found,
+                    _ => Walk::Unsafe,
+                }
+            }
+            Expr::Invoke(invoke) => {
+                let receiver = match &invoke.kind {
+                    InvokeKind::Virtual(_) => 1,
+                    _ => 0,
+                };
+                self.walk_children(&e.children(), |k| k >= receiver)
+            }
+            Expr::New(_) => self.walk_children(&e.children(), |_| true),
+            Expr::Cast(..) => self.walk_children(&e.children(), |_| true),
+            e => self.walk_children(&e.children(), |_| false),
+        }
+    }
+
+    fn walk_children(&self, children: &[&Expr], functional: impl Fn(usize) -> bool) -> Walk {
+        for (k, child) in children.iter().enumerate() {
+            if child.uses(self.var) {
+                return self.walk(child, functional(k));
+            }
+            if !self.is_safe(child) {
+                return Walk::Unsafe;
+            }
+        }
+        Walk::NotFound
+    }
+
+    fn captures_var(&self, e: &Expr) -> bool {
+        match e {
+            Expr::Lambda(lambda) => lambda.captures.iter().any(|(_, c)| c.uses(self.var)),
+            Expr::New(new) => new
+                .anonymous
+                .as_ref()
+                .is_some_and(|a| a.captures.iter().any(|(_, c)| c.uses(self.var))),
+            _ => false,
+        }
+    }
+
+    /// Whether the value can be evaluated after `e` instead of before it
+    fn is_safe(&self, e: &Expr) -> bool {
+        if e.has_side_effects() {
+            return false;
+        }
+        let mut safe = true;
+        e.visit(&mut |e| match e {
+            Expr::Var(v) if self.value.writes(*v) => safe = false,
+            Expr::Field(_, member) | Expr::StaticField(member)
+                if self.effects
+                    && !field_info(self.ctx, &member.owner, &member.name)
+                        .is_some_and(|f| f.is_final) =>
+            {
+                safe = false
+            }
+            Expr::ArrayElement(..) | Expr::ArrayLength(_) if self.effects => safe = false,
+            _ => {}
+        });
+        safe
+    }
+}
+
+/// Variables holding lambdas and method references get the generic type of the
+/// functional interface
+fn functional_signatures(body: &mut MethodBody) {
+    let mut signatures = Vec::new();
+    visit_exprs(&body.stmts, &mut |e| {
+        if let Expr::Assign(target, value) = e {
+            if let Expr::Var(v) = **target {
+                let signature = match &**value {
+                    Expr::Lambda(lambda) => lambda.signature.clone(),
+                    Expr::MethodRef(method_ref) => method_ref.signature.clone(),
+                    _ => None,
+                };
+                signatures.extend(signature.map(|s| (v, s)));
+            }
+        }
+    });
+    for (var, signature) in signatures {
+        if body.vars[var].signature.is_none() {
+            body.vars[var].signature = Some(signature);
+        }
+    }
+}
+
+/// Loop conditions, `for` and enhanced `for` loops
+fn loops(ctx: &Context, body: &mut MethodBody) {
+    let mut vars = std::mem::take(&mut body.vars);
+    rewrite_blocks(&mut body.stmts, &mut |stmts| {
+        for stmt in stmts.iter_mut() {
+            loop_condition(ctx, &vars, stmt);
+            fold_for(stmt);
+        }
+    });
+    loop {
+        let counts = Counts::of(&body.stmts);
+        let mut changed = false;
+        rewrite_blocks(&mut body.stmts, &mut |stmts| {
+            changed |= foreach_array(stmts, &counts, &mut vars);
+            changed |= foreach_iterator(ctx, stmts, &counts, &mut vars);
+        });
+        if !changed {
+            break;
+        }
+    }
+    rewrite_blocks(&mut body.stmts, &mut for_loops);
+    body.vars = vars;
+}
+
+/// `while (true) { if (c) break; ... }` is `while (!c) { ... }`
+fn loop_condition(ctx: &Context, vars: &[super::ast::Variable], stmt: &mut Stmt) {
+    let (label, body) = match stmt {
+        Stmt::While {
+            label,
+            cond: Expr::Bool(true),
+            body,
+        }
+        | Stmt::For {
+            label,
+            cond: None,
+            body,
+            ..
+        } => (*label, body),
+        _ => return,
+    };
+    let Some(Stmt::If {
+        cond,
+        then,
+        otherwise,
+        ..
+    }) = body.first()
+    else {
+        return;
+    };
+    if *then != [Stmt::Break(label)] || !otherwise.is_empty() {
+        return;
+    }
+    let mut cond = cond.clone().negate();
+    if let Expr::Unary(UnaryOp::Not, inner) = &cond {
+        if let Expr::Binary(op, l, r) = &**inner {
+            if op.is_comparison()
+                && !types::is_floating(&expr_type(ctx, vars, l))
+                && !types::is_floating(&expr_type(ctx, vars, r))
+            {
+                cond = Expr::Binary(op.negated().unwrap(), l.clone(), r.clone());
+            }
+        }
+    }
+    body.remove(0);
+    match stmt {
+        Stmt::While { cond: c, .. } => *c = cond,
+        Stmt::For { cond: c, .. } => *c = Some(cond),
+        _ => unreachable!(),
+    }
+}
+
+/// A `for` without `continue` is a `while` with the update at the end of the body,
+/// which `for_loops` turns back into a `for` if it has an initializer
+fn fold_for(stmt: &mut Stmt) {
+    let Stmt::For {
+        label,
+        init,
+        cond,
+        update,
+        body,
+    } = stmt
+    else {
+        return;
+    };
+    if !init.is_empty() || continues_to(body, *label) {
+        return;
+    }
+    let mut body = std::mem::take(body);
+    if super::ast::completes_normally(&body) {
+        body.extend(update.drain(..).map(Stmt::Expr));
+    }
+    *stmt = Stmt::While {
+        label: *label,
+        cond: cond.take().unwrap_or(Expr::Bool(true)),
+        body,
+    };
+}
+
+fn continues_to(stmts: &[Stmt], label: Label) -> bool {
+    stmts.iter().any(|stmt| match stmt {
+        Stmt::Continue(l) => *l == label,
+        stmt => stmt.blocks().iter().any(|b| continues_to(b, label)),
+    })
+}
+
+/// The variable a statement like `i++`, `i += 2` or `i = i + 2` updates
+fn updated_var(e: &Expr) -> Option<VarId> {
+    match e {
+        Expr::Increment { target, .. }
+        | Expr::CompoundAssign(_, target, _)
+        | Expr::Assign(target, _) => match **target {
+            Expr::Var(v) => Some(v),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn is_increment_of(e: &Expr, var: VarId) -> bool {
+    match e {
+        Expr::Increment {
+            target,
+            increment: true,
+            ..
+        } => **target == Expr::Var(var),
+        Expr::Assign(target, value) => {
+            **target == Expr::Var(var)
+                && **value == Expr::binary(BinaryOp::Add, Expr::Var(var), Expr::Int(1))
+        }
+        Expr::CompoundAssign(BinaryOp::Add, target, value) => {
+            **target == Expr::Var(var) && **value == Expr::Int(1)
+        }
+        _ => false,
+    }
+}
+
+/// Splits the loop into its condition, body and `i++` update
+fn counting_loop(stmt: &Stmt) -> Option<(Label, &Expr, &[Stmt], &Expr)> {
+    match stmt {
+        Stmt::While { label, cond, body } => match body.split_last() {
+            Some((Stmt::Expr(update), body)) => Some((*label, cond, body, update)),
+            _ => None,
+        },
+        Stmt::For {
+            label,
+            init,
+            cond: Some(cond),
+            update,
+            body,
+        } if init.is_empty() && update.len() == 1 => Some((*label, cond, body, &update[0])),
+        _ => None,
+    }
+}
+
+/// `for (T v : array)`: javac copies the array and its length to variables and counts
+/// an index up to the length
+fn foreach_array(
+    stmts: &mut Vec<Stmt>,
+    counts: &Counts,
+    vars: &mut Vec<super::ast::Variable>,
+) -> bool {
+    for k in 0..stmts.len().saturating_sub(3) {
+        let [Stmt::Expr(Expr::Assign(a, array)), Stmt::Expr(Expr::Assign(n, length)), Stmt::Expr(Expr::Assign(i, zero)), stmt] =
+            &stmts[k..k + 4]
+        else {
+            continue;
+        };
+        let (Expr::Var(a), Expr::Var(n), Expr::Var(i)) = (&**a, &**n, &**i) else {
+            continue;
+        };
+        let (a, n, i) = (*a, *n, *i);
+        if **length != Expr::ArrayLength(Box::new(Expr::Var(a))) || **zero != Expr::Int(0) {
+            continue;
+        }
+        let Some((label, cond, body, update)) = counting_loop(stmt) else {
+            continue;
+        };
+        if *cond != Expr::binary(BinaryOp::Lt, Expr::Var(i), Expr::Var(n))
+            || !is_increment_of(update, i)
+            || counts.reads(a) != 2
+            || counts.reads(n) != 1
+            || counts.reads(i) != 3
+            || counts.writes(i) != 2
+        {
+            continue;
+        }
+        let element = Expr::ArrayElement(Box::new(Expr::Var(a)), Box::new(Expr::Var(i)));
+        let component = vars[a]
+            .ty
+            .as_ref()
+            .and_then(|t| t.component_type())
+            .cloned();
+        let Some((var, body)) = loop_var(body, &element, component, vars) else {
+            continue;
+        };
+        let iterable = (**array).clone();
+        stmts.splice(
+            k..k + 4,
+            [Stmt::ForEach {
+                label,
+                var,
+                iterable,
+                body,
+            }],
+        );
+        return true;
+    }
+    false
+}
+
+/// The variable the element is assigned to at the start of the body, or a new one if
+/// it was inlined where it is used
+fn loop_var(
+    body: &[Stmt],
+    element: &Expr,
+    ty: Option<FieldType>,
+    vars: &mut Vec<super::ast::Variable>,
+) -> Option<(VarId, Vec<Stmt>)> {
+    if let Some(Stmt::Expr(Expr::Assign(target, value))) = body.first() {
+        if let Expr::Var(v) = **target {
+            if **value == *element && vars[v].kind != VarKind::Parameter {
+                return Some((v, body[1..].to_vec()));
+            }
+        }
+    }
+    let first = body.first()?;
+    let mut found = 0;
+    for e in first.exprs() {
+        e.visit(&mut |e| found += (e == element) as usize);
+    }
+    if found != 1 {
+        return None;
+    }
+    vars.push(super::ast::Variable {
+        name: String::new(),
+        hint: None,
+        ty,
+        signature: None,
+        kind: VarKind::Local,
+    });
+    let var = vars.len() - 1;
+    let mut body = body.to_vec();
+    for e in body[0].exprs_mut() {
+        e.rewrite(&mut |e| {
+            if e == element {
+                *e = Expr::Var(var);
+            }
+        });
+    }
+    Some((var, body))
+}
+
+/// `for (T v : iterable)`: `it = iterable.iterator(); while (it.hasNext()) { v = (T) it.next(); ... }`
+fn foreach_iterator(
+    ctx: &Context,
+    stmts: &mut Vec<Stmt>,
+    counts: &Counts,
+    vars: &mut Vec<super::ast::Variable>,
+) -> bool {
+    for k in 0..stmts.len().saturating_sub(1) {
+        let [Stmt::Expr(Expr::Assign(it, iterator)), Stmt::While { label, cond, body }] =
+            &stmts[k..k + 2]
+        else {
+            continue;
+        };
+        let Expr::Var(it) = **it else {
+            continue;
+        };
+        let Some(iterable) = call_on(iterator, "iterator", "()Ljava/util/Iterator;") else {
+            continue;
+        };
+        if call_on(cond, "hasNext", "()Z") != Some(&Expr::Var(it))
+            || counts.reads(it) != 2
+            || counts.writes(it) != 1
+        {
+            continue;
+        }
+        let next = Expr::Invoke(Box::new(super::ast::Invoke {
+            kind: InvokeKind::Virtual(Expr::Var(it)),
+            method: iterator_method(iterator, "next", "()Ljava/lang/Object;"),
+            args: Vec::new(),
+        }));
+        // the element is cast to the type argument of the iterable, unless it is `Object`
+        let mut element = next.clone();
+        let mut ty = FieldType::Object("java/lang/Object".to_string());
+        let first = match body.first() {
+            Some(Stmt::Expr(Expr::Assign(_, value))) => Some(&**value),
+            Some(Stmt::Expr(value)) => Some(value),
+            _ => None,
+        };
+        if let Some(value) = first {
+            let mut cast = None;
+            value.visit(&mut |e| {
+                if let Expr::Cast(t, inner) = e {
+                    if **inner == next {
+                        cast = Some(t.clone());
+                    }
+                }
+            });
+            if let Some(t) = cast {
+                element = Expr::Cast(t.clone(), Box::new(next.clone()));
+                ty = t;
+            }
+        }
+        if let FieldType::Object(class) = &ty {
+            if class != "java/lang/Object"
+                && element_type(ctx, iterable, vars).as_ref() != Some(class)
+            {
+                continue;
+            }
+        }
+        let Some((var, body)) = loop_var(body, &element, Some(ty), vars) else {
+            continue;
+        };
+        let foreach = Stmt::ForEach {
+            label: *label,
+            var,
+            iterable: iterable.clone(),
+            body,
+        };
+        stmts.splice(k..k + 2, [foreach]);
+        return true;
+    }
+    false
+}
+
+/// The receiver of a call to the method without arguments
+fn call_on<'a>(e: &'a Expr, name: &str, descriptor: &str) -> Option<&'a Expr> {
+    match e {
+        Expr::Invoke(invoke)
+            if invoke.method.name == name
+                && invoke.method.descriptor == descriptor
+                && invoke.args.is_empty() =>
+        {
+            match &invoke.kind {
+                InvokeKind::Virtual(receiver) => Some(receiver),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+fn iterator_method(_iterator: &Expr, name: &str, descriptor: &str) -> super::ast::Member {
+    super::ast::Member {
+        owner: "java/util/Iterator".to_string(),
+        name: name.to_string(),
+        descriptor: descriptor.to_string(),
+    }
+}
+
+/// The class of the first type argument of the generic type of the expression
+fn element_type(ctx: &Context, e: &Expr, vars: &[super::ast::Variable]) -> Option<String> {
+    let signature = match e {
+        Expr::Var(v) => vars[*v].signature.clone()?,
+        Expr::Field(_, member) | Expr::StaticField(member) => {
+            field_info(ctx, &member.owner, &member.name)?.signature?
+        }
+        _ => return None,
+    };
+    let JavaTypeSignature::ReferenceTypeSignature(ReferenceTypeSignature::ClassTypeSignature(
+        class,
+    )) = parse_signature(&signature).ok()?
+    else {
+        return None;
+    };
+    let arguments = match class.class_type_signature_suffix.last() {
+        Some(suffix) => &suffix.type_arguments,
+        None => &class.simple_class_type_signature.type_arguments,
+    };
+    match arguments.first()? {
+        TypeArgument::Exact(ReferenceTypeSignature::ClassTypeSignature(c))
+        | TypeArgument::Extends(ReferenceTypeSignature::ClassTypeSignature(c)) => {
+            Some(c.binary_name())
+        }
+        _ => None,
+    }
+}
+
+/// `i = 0; while (i < n) { ...; i++; }` is `for (i = 0; i < n; i++) { ... }`
+fn for_loops(stmts: &mut Vec<Stmt>) {
+    let mut k = 1;
+    while k < stmts.len() {
+        let Stmt::Expr(Expr::Assign(target, _)) = &stmts[k - 1] else {
+            k += 1;
+            continue;
+        };
+        let Expr::Var(var) = **target else {
+            k += 1;
+            continue;
+        };
+        let is_for = match &stmts[k] {
+            Stmt::While { label, cond, body } => {
+                cond.uses(var)
+                    && !continues_to(body, *label)
+                    && matches!(body.last(), Some(Stmt::Expr(e)) if updated_var(e) == Some(var))
+            }
+            Stmt::For { init, update, .. } => {
+                init.is_empty() && update.iter().any(|e| updated_var(e) == Some(var))
+            }
+            _ => false,
+        };
+        if !is_for {
+            k += 1;
+            continue;
+        }
+        let init = stmts.remove(k - 1);
+        let stmt = &mut stmts[k - 1];
+        if let Stmt::While { label, cond, body } = stmt {
+            let Some(Stmt::Expr(update)) = body.pop() else {
+                unreachable!()
+            };
+            *stmt = Stmt::For {
+                label: *label,
+                init: vec![init],
+                cond: Some(std::mem::replace(cond, Expr::Null)),
+                update: vec![update],
+                body: std::mem::take(body),
+            };
+        } else if let Stmt::For { init: i, .. } = stmt {
+            i.push(init);
+        }
+    }
+}
+
+/// `switch (s)` on a string: javac switches on `s.hashCode()` to find the index of the
+/// string with `equals`, then switches on the index
+fn string_switch(stmts: &mut Vec<Stmt>) {
+    let mut k = 0;
+    while k + 3 < stmts.len() {
+        if let Some(stmt) = match_string_switch(&stmts[k..k + 4]) {
+            stmts.splice(k..k + 4, [stmt]);
+        }
+        k += 1;
+    }
+}
+
+/// `switch (e)` on an enum: javac switches on `$SwitchMap$Color[e.ordinal()]`, an
+/// array in a synthetic class that its static initializer fills with the case numbers
+fn enum_switch(ctx: &Context, stmts: &mut Vec<Stmt>) {
+    for stmt in stmts {
+        let Stmt::Switch { value, cases, .. } = stmt else {
+            continue;
+        };
+        let Expr::ArrayElement(map, index) = &*value else {
+            continue;
+        };
+        let Expr::StaticField(field) = &**map else {
+            continue;
+        };
+        if !field.name.starts_with("$SwitchMap$") {
+            continue;
+        }
+        let Some(switched) = call_on(index, "ordinal", "()I") else {
+            continue;
+        };
+        let Some(constants) = switch_map(ctx, &field.owner, &field.name) else {
+            continue;
+        };
+        let values = cases
+            .iter()
+            .map(|case| {
+                case.values
+                    .iter()
+                    .map(|v| match v {
+                        CaseValue::Int(n) => constants.get(n).cloned().map(CaseValue::Enum),
+                        _ => None,
+                    })
+                    .collect::<Option<Vec<_>>>()
+            })
+            .collect::<Option<Vec<_>>>();
+        let Some(values) = values else {
+            continue;
+        };
+        *value = switched.clone();
+        for (case, values) in cases.iter_mut().zip(values) {
+            case.values = values;
+        }
+    }
+}
+
+/// The enum constants of the case numbers in a `$SwitchMap$` array, from the
+/// `map[Color.RED.ordinal()] = 1` assignments of the static initializer
+fn switch_map(ctx: &Context, class: &str, map: &str) -> Option<HashMap<i32, String>> {
+    let cf = ctx.class_path.load(class).ok()?;
+    let cp = &cf.constant_pool;
+    let clinit = cf
+        .methods
+        .methods
+        .iter()
+        .find(|m| cp.utf8(m.name_index).ok() == Some("<clinit>"))?;
+    let instructions = clinit
+        .get_code()?
+        .instructions()
+        .map(|(_, instruction)| instruction)
+        .collect::<Vec<_>>();
+    let mut constants = HashMap::new();
+    for window in instructions.windows(5) {
+        let [ByteCode::GetStatic(array), ByteCode::GetStatic(constant), ByteCode::InvokeVirtual(ordinal), number, ByteCode::IAStore] =
+            window
+        else {
+            continue;
+        };
+        let number = match number {
+            ByteCode::IConstn(n) => *n as i32,
+            ByteCode::BiPush(n) => *n as i32,
+            ByteCode::SiPush(n) => *n as i32,
+            _ => continue,
+        };
+        if cp.field_ref(*array).ok()?.name != map || cp.method_ref(*ordinal).ok()?.name != "ordinal"
+        {
+            continue;
+        }
+        constants.insert(number, cp.field_ref(*constant).ok()?.name);
+    }
+    Some(constants)
+}
+
+fn match_string_switch(stmts: &[Stmt]) -> Option<Stmt> {
+    let [Stmt::Expr(Expr::Assign(copy, value)), Stmt::Expr(Expr::Assign(index, minus_one)), Stmt::Switch {
+        label: hash_label,
+        value: hash,
+        cases: hash_cases,
+    }, Stmt::Switch {
+        label,
+        value: Expr::Var(switched),
+        cases,
+    }] = stmts
+    else {
+        return None;
+    };
+    let (Expr::Var(copy), Expr::Var(index)) = (&**copy, &**index) else {
+        return None;
+    };
+    if **minus_one != Expr::Int(-1)
+        || switched != index
+        || call_on(hash, "hashCode", "()I") != Some(&Expr::Var(*copy))
+    {
+        return None;
+    }
+    let mut strings = HashMap::new();
+    for case in hash_cases {
+        if !string_cases(&case.body, *copy, *index, *hash_label, &mut strings) {
+            return None;
+        }
+    }
+    let mut new_cases = Vec::new();
+    for case in cases {
+        let values = case
+            .values
+            .iter()
+            .map(|v| match v {
+                CaseValue::Int(n) => strings.get(n).cloned().map(CaseValue::String),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()?;
+        new_cases.push(Case {
+            values,
+            default: case.default,
+            body: case.body.clone(),
+        });
+    }
+    Some(Stmt::Switch {
+        label: *label,
+        value: (**value).clone(),
+        cases: new_cases,
+    })
+}
+
+/// Collects the `if (s.equals("x")) index = n;` of a case of the `hashCode` switch
+fn string_cases(
+    stmts: &[Stmt],
+    copy: VarId,
+    index: VarId,
+    label: Label,
+    strings: &mut HashMap<i32, String>,
+) -> bool {
+    let equals = |e: &Expr| -> Option<String> {
+        let Expr::Invoke(invoke) = e else {
+            return None;
+        };
+        match (
+            &invoke.kind,
+            invoke.method.name.as_str(),
+            invoke.args.as_slice(),
+        ) {
+            (InvokeKind::Virtual(Expr::Var(v)), "equals", [Expr::String(s)]) if *v == copy => {
+                Some(s.clone())
+            }
+            _ => None,
+        }
+    };
+    let assigned = |stmt: Option<&Stmt>| -> Option<i32> {
+        match stmt {
+            Some(Stmt::Expr(Expr::Assign(target, value))) if **target == Expr::Var(index) => {
+                match **value {
+                    Expr::Int(n) => Some(n),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    };
+    let is_break = |stmts: &[Stmt]| stmts.iter().all(|s| *s == Stmt::Break(label));
+    let mut k = 0;
+    while k < stmts.len() {
+        match &stmts[k] {
+            Stmt::Break(l) if *l == label => {}
+            Stmt::If {
+                cond,
+                then,
+                otherwise,
+                ..
+            } => {
+                if let Some(s) = equals(cond) {
+                    let Some(n) = assigned(then.first()) else {
+                        return false;
+                    };
+                    if !is_break(&then[1..]) {
+                        return false;
+                    }
+                    strings.insert(n, s);
+                    if !string_cases(otherwise, copy, index, label, strings) {
+                        return false;
+                    }
+                } else if let Expr::Unary(UnaryOp::Not, inner) = cond {
+                    let Some(s) = equals(inner) else {
+                        return false;
+                    };
+                    if !is_break(then) {
+                        return false;
+                    }
+                    let rest = otherwise
+                        .iter()
+                        .chain(&stmts[k + 1..])
+                        .cloned()
+                        .collect::<Vec<_>>();
+                    let Some(n) = assigned(rest.first()) else {
+                        return false;
+                    };
+                    strings.insert(n, s);
+                    return string_cases(&rest[1..], copy, index, label, strings);
+                } else {
+                    return false;
+                }
+            }
+            _ => return false,
+        }
+        k += 1;
+    }
+    true
+}
+
+/// `x = x + y` is `x += y`, `x = x + 1` is `++x` (`x++` as a statement). The temporaries
+/// javac keeps the array and index of `a[i] += y` in are inlined first.
+fn compound_assignments(ctx: &Context, body: &mut MethodBody) {
+    let counts = Counts::of(&body.stmts);
+    let vars = &body.vars;
+    let temps = |v: VarId| vars[v].kind == VarKind::Temp && counts.reads(v) == 1;
+    rewrite_exprs(&mut body.stmts, &mut |e| {
+        let Expr::Assign(target, value) = e else {
+            return;
+        };
+        // `a[t = i] = a[t] + y`
+        let mut replaced = Vec::new();
+        let mut plain = (**target).clone();
+        for child in plain.children_mut() {
+            if let Expr::Assign(t, inner) = child {
+                if let Expr::Var(t) = **t {
+                    if temps(t) {
+                        replaced.push((t, (**inner).clone()));
+                        *child = (**inner).clone();
+                    }
+                }
+            }
+        }
+        let mut value = (**value).clone();
+        for (t, inner) in &replaced {
+            value.rewrite(&mut |e| {
+                if *e == Expr::Var(*t) {
+                    *e = inner.clone();
+                }
+            });
+        }
+        if plain.has_side_effects() {
+            return;
+        }
+        let ty = expr_type(ctx, vars, &plain);
+        // narrowing casts are implicit in compound assignments
+        let operation = match value {
+            Expr::Cast(t, inner) if Some(&t) == ty.as_ref() => *inner,
+            value => value,
+        };
+        let Expr::Binary(op, left, right) = operation else {
+            return;
+        };
+        if *left != plain
+            || op.is_comparison()
+            || matches!(op, BinaryOp::LogicalAnd | BinaryOp::LogicalOr)
+        {
+            return;
+        }
+        if ty == Some(FieldType::Object("java/lang/String".to_string())) {
+            return;
+        }
+        let is_one = matches!(*right, Expr::Int(1) | Expr::Long(1))
+            || matches!(*right, Expr::Float(f) if f == 1.0)
+            || matches!(*right, Expr::Double(d) if d == 1.0);
+        let is_number = !types::is_boolean(&ty);
+        *e = match op {
+            BinaryOp::Add | BinaryOp::Sub if is_one && is_number => Expr::Increment {
+                target: Box::new(plain),
+                increment: op == BinaryOp::Add,
+                prefix: true,
+            },
+            op => Expr::CompoundAssign(op, Box::new(plain), right),
+        };
+    });
+    // increments as statements are written `i++`
+    fn postfix(e: &mut Expr) {
+        if let Expr::Increment { prefix, .. } = e {
+            *prefix = false;
+        }
+    }
+    fn statements(stmts: &mut [Stmt]) {
+        for stmt in stmts {
+            match stmt {
+                Stmt::Expr(e) => postfix(e),
+                Stmt::For { update, .. } => update.iter_mut().for_each(postfix),
+                _ => {}
+            }
+            for block in stmt.blocks_mut() {
+                statements(block);
+            }
+        }
+    }
+    statements(&mut body.stmts);
+}
+
+/// Whether the expression can be a statement on its own
+fn is_statement_expr(e: &Expr) -> bool {
+    matches!(
+        e,
+        Expr::Invoke(_)
+            | Expr::New(_)
+            | Expr::Assign(..)
+            | Expr::CompoundAssign(..)
+            | Expr::Increment { .. }
+    )
+}
+
+/// Removes assignments to variables that are never read and splits expression
+/// statements that are not statement expressions into their side effects
+fn cleanup(body: &mut MethodBody) {
+    let counts = Counts::of(&body.stmts);
+    let vars = &body.vars;
+    let unused = |v: VarId| {
+        counts.reads(v) == 0
+            && matches!(
+                vars[v].kind,
+                VarKind::Temp | VarKind::Stack | VarKind::Local
+            )
+    };
+    rewrite_exprs(&mut body.stmts, &mut |e| {
+        if let Expr::Assign(target, value) = e {
+            if let Expr::Var(v) = **target {
+                if unused(v) {
+                    *e = std::mem::replace(&mut **value, Expr::Null);
+                }
+            }
+        }
+    });
+    rewrite_blocks(&mut body.stmts, &mut |stmts| {
+        let mut k = 0;
+        while k < stmts.len() {
+            let Stmt::Expr(e) = &stmts[k] else {
+                k += 1;
+                continue;
+            };
+            if is_statement_expr(e) {
+                k += 1;
+                continue;
+            }
+            let mut effects = Vec::new();
+            side_effects(e, &mut effects);
+            let n = effects.len();
+            stmts.splice(k..k + 1, effects.into_iter().map(Stmt::Expr));
+            k += n;
+        }
+    });
+}
+
+fn side_effects(e: &Expr, out: &mut Vec<Expr>) {
+    if is_statement_expr(e) {
+        out.push(e.clone());
+        return;
+    }
+    for child in e.children() {
+        side_effects(child, out);
+    }
+}
+
+/// Declares every local variable at its first assignment in the innermost block that
+/// contains all its uses, or before the statement it is first used in
+fn declare(body: &mut MethodBody) {
+    // the path of nested statements and blocks to every use of every variable
+    let mut paths: HashMap<VarId, Vec<Vec<(usize, usize)>>> = HashMap::new();
+    fn collect(
+        stmts: &[Stmt],
+        path: &mut Vec<(usize, usize)>,
+        paths: &mut HashMap<VarId, Vec<Vec<(usize, usize)>>>,
+    ) {
+        for (k, stmt) in stmts.iter().enumerate() {
+            let mut record = |v: VarId, path: &Vec<(usize, usize)>| {
+                let mut p = path.clone();
+                p.push((k, usize::MAX));
+                paths.entry(v).or_default().push(p);
+            };
+            for e in stmt.exprs() {
+                e.visit(&mut |e| {
+                    if let Expr::Var(v) = e {
+                        record(*v, path);
+                    }
+                });
+            }
+            for (b, block) in stmt.blocks().into_iter().enumerate() {
+                path.push((k, b));
+                collect(block, path, paths);
+                path.pop();
+            }
+        }
+    }
+    collect(&body.stmts, &mut Vec::new(), &mut paths);
+
+    let mut excluded = Vec::new();
+    visit_stmts(&body.stmts, &mut |stmt| match stmt {
+        Stmt::ForEach { var, .. } => excluded.push(*var),
+        Stmt::Try { catches, .. } => excluded.extend(catches.iter().map(|c| c.var)),
+        _ => {}
+    });
+    let mut captured = Vec::new();
+    visit_exprs(&body.stmts, &mut |e| {
+        if let Expr::Lambda(lambda) = e {
+            for (_, c) in &lambda.captures {
+                if let Expr::Var(v) = c {
+                    captured.push(*v);
+                }
+            }
+        }
+        if let Expr::New(new) = e {
+            for (_, c) in new.anonymous.iter().flat_map(|a| &a.captures) {
+                if let Expr::Var(v) = c {
+                    captured.push(*v);
+                }
+            }
+        }
+    });
+
+    let mut declarations = Vec::new();
+    for (var, uses) in paths {
+        if body.vars[var].kind == VarKind::Parameter || excluded.contains(&var) {
+            continue;
+        }
+        // the longest common prefix of the paths, ending at a block
+        let first = &uses[0];
+        let mut common = first.len() - 1;
+        for path in &uses[1..] {
+            let same = first.iter().zip(path).take_while(|(a, b)| a == b).count();
+            common = common.min(same).min(path.len() - 1);
+        }
+        let block = first[..common].to_vec();
+        let position = uses.iter().map(|p| p[common].0).min().unwrap();
+        declarations.push((var, block, position));
+    }
+    // inner blocks and later positions first, so the indexes stay valid
+    declarations.sort_by(|a, b| {
+        b.1.len()
+            .cmp(&a.1.len())
+            .then(b.2.cmp(&a.2))
+            .then(b.0.cmp(&a.0))
+    });
+    for (var, path, position) in declarations {
+        let stmts = block_at(&mut body.stmts, &path);
+        let stmt = &mut stmts[position];
+        match stmt {
+            Stmt::Expr(Expr::Assign(target, value))
+                if **target == Expr::Var(var) && !value.uses(var) =>
+            {
+                let value = std::mem::replace(&mut **value, Expr::Null);
+                *stmt = Stmt::Declare(var, Some(value));
+            }
+            Stmt::For { init, .. } if matches!(init.as_slice(), [Stmt::Expr(Expr::Assign(target, _))] if **target == Expr::Var(var)) =>
+            {
+                let Some(Stmt::Expr(Expr::Assign(_, value))) = init.pop() else {
+                    unreachable!()
+                };
+                init.push(Stmt::Declare(var, Some(*value)));
+            }
+            _ => {
+                let value = match captured.contains(&var) {
+                    true => None,
+                    false => body.vars[var].ty.as_ref().map(default_value),
+                };
+                stmts.insert(position, Stmt::Declare(var, value));
+            }
+        }
+    }
+}
+
+fn visit_stmts(stmts: &[Stmt], f: &mut dyn FnMut(&Stmt)) {
+    for stmt in stmts {
+        f(stmt);
+        for block in stmt.blocks() {
+            visit_stmts(block, f);
+        }
+    }
+}
+
+fn block_at<'a>(stmts: &'a mut Vec<Stmt>, path: &[(usize, usize)]) -> &'a mut Vec<Stmt> {
+    match path.split_first() {
+        None => stmts,
+        Some(((k, b), rest)) => {
+            let block = stmts[*k].blocks_mut().into_iter().nth(*b).unwrap();
+            block_at(block, rest)
+        }
+    }
+}
+
+fn default_value(ty: &FieldType) -> Expr {
+    match ty {
+        FieldType::Base(BaseType::Boolean) => Expr::Bool(false),
+        FieldType::Base(BaseType::Char) => Expr::Char(0),
+        FieldType::Base(BaseType::Long) => Expr::Long(0),
+        FieldType::Base(BaseType::Float) => Expr::Float(0.0),
+        FieldType::Base(BaseType::Double) => Expr::Double(0.0),
+        FieldType::Base(_) => Expr::Int(0),
+        _ => Expr::Null,
+    }
+}
+
+/// Fails for what can't be printed as Java
+fn validate(stmts: &[Stmt]) -> Result<()> {
+    let mut error = None;
+    visit_stmts(stmts, &mut |stmt| {
+        if let Stmt::Monitor { .. } = stmt {
+            error = Some(anyhow!(
+                "monitorenter or monitorexit outside of synchronized"
+            ));
+        }
+    });
+    visit_exprs(stmts, &mut |e| match e {
+        Expr::Caught => error = Some(anyhow!("Exception used outside of a catch")),
+        Expr::Uninitialized(_) => error = Some(anyhow!("Object used before its constructor")),
+        Expr::MethodRef(method_ref) => {
+            if let MethodRefTarget::Expr(Expr::Uninitialized(_)) = method_ref.target {
+                error = Some(anyhow!("Object used before its constructor"));
+            }
+        }
+        _ => {}
+    });
+    match error {
+        Some(error) => Err(error),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,669 @@
+//! Types of expressions and variables
+//!
+//! The JVM has no `boolean`, `char`, `byte` or `short` on the operand stack, so a `true`
+//! and a `1` are the same `iconst_1`. The variables get the types of the values
+//! assigned to them, then `coerce` walks the statements with the type every expression
+//! is expected to have, turning `1` into `true` or `'a'`, `x == 0` into `!x` and adding
+//! the casts that picking the same overload or storing to a narrower type needs.
+
+use super::ast::{
+    rewrite_exprs, visit_exprs, BinaryOp, Expr, InvokeKind, MethodBody, Stmt, UnaryOp, VarId,
+    Variable,
+};
+use super::Context;
+use crate::class_file::fields::AccessFlag;
+use crate::class_file::{BaseType, ClassHierarchy, FieldType, MethodDescriptor};
+use std::collections::HashMap;
+
+const OBJECT: &str = "java/lang/Object";
+
+fn base(base_type: BaseType) -> Option<FieldType> {
+    Some(FieldType::Base(base_type))
+}
+
+fn object(name: &str) -> Option<FieldType> {
+    Some(FieldType::Object(name.to_string()))
+}
+
+pub(super) fn is_boolean(t: &Option<FieldType>) -> bool {
+    *t == base(BaseType::Boolean)
+}
+
+fn is_int_like(t: &FieldType) -> bool {
+    matches!(
+        t,
+        FieldType::Base(BaseType::Int | BaseType::Char | BaseType::Short | BaseType::Byte)
+    )
+}
+
+pub(super) fn is_floating(t: &Option<FieldType>) -> bool {
+    matches!(t, Some(FieldType::Base(BaseType::Float | BaseType::Double)))
+}
+
+/// The type of `int` arithmetic on the type: `byte`, `short` and `char` are promoted
+fn promote(t: Option<FieldType>) -> Option<FieldType> {
+    match t {
+        Some(t) if is_int_like(&t) => base(BaseType::Int),
+        t => t,
+    }
+}
+
+fn binary_promote(a: Option<FieldType>, b: Option<FieldType>) -> Option<FieldType> {
+    let rank = |t: &Option<FieldType>| match t {
+        Some(FieldType::Base(BaseType::Double)) => 3,
+        Some(FieldType::Base(BaseType::Float)) => 2,
+        Some(FieldType::Base(BaseType::Long)) => 1,
+        _ => 0,
+    };
+    match rank(&a).max(rank(&b)) {
+        3 => base(BaseType::Double),
+        2 => base(BaseType::Float),
+        1 => base(BaseType::Long),
+        _ => base(BaseType::Int),
+    }
+}
+
+/// Field flags and generic signature of a field of the class or one of its super classes
+pub(super) struct FieldInfo {
+    pub is_final: bool,
+    pub signature: Option<String>,
+}
+
+pub(super) fn field_info(ctx: &Context, owner: &str, name: &str) -> Option<FieldInfo> {
+    let mut class = owner.to_string();
+    for _ in 0..32 {
+        let loaded;
+        let cf = match class == ctx.class_name {
+            true => ctx.cf,
+            false => {
+                loaded = ctx.class_path.load(&class).ok()?;
+                &*loaded
+            }
+        };
+        let cp = &cf.constant_pool;
+        for field in &cf.fields.fields {
+            if cp.utf8(field.name_index).ok() == Some(name) {
+                return Some(FieldInfo {
+                    is_final: field
+                        .access_flags
+                        .flag_vector()
+                        .contains(&AccessFlag::Final),
+                    signature: field.attributes.get_signature(cp).map(|s| s.to_string()),
+                });
+            }
+        }
+        class = cf.super_class_name()?;
+    }
+    None
+}
+
+/// The static type of the expression, `None` for `null` and unknown types
+pub(super) fn expr_type(ctx: &Context, vars: &[Variable], e: &Expr) -> Option<FieldType> {
+    let ty = |e: &Expr| expr_type(ctx, vars, e);
+    match e {
+        Expr::Int(_) => base(BaseType::Int),
+        Expr::Long(_) => base(BaseType::Long),
+        Expr::Float(_) => base(BaseType::Float),
+        Expr::Double(_) => base(BaseType::Double),
+        Expr::Bool(_) => base(BaseType::Boolean),
+        Expr::Char(_) => base(BaseType::Char),
+        Expr::String(_) | Expr::Concat(_) => object("java/lang/String"),
+        Expr::Null | Expr::Uninitialized(_) => None,
+        Expr::Class(_) => object("java/lang/Class"),
+        Expr::Var(v) => vars.get(*v).and_then(|v| v.ty.clone()),
+        Expr::This => object(&ctx.class_name),
+        Expr::OuterThis(class) => object(class),
+        Expr::Caught => object("java/lang/Throwable"),
+        Expr::Captured(name) => {
+            let cp = &ctx.cf.constant_pool;
+            let field_name = format!("val${}", name);
+            ctx.cf
+                .fields
+                .fields
+                .iter()
+                .find(|f| cp.utf8(f.name_index).ok() == Some(field_name.as_str()))
+                .and_then(|f| FieldType::parse(cp.utf8(f.descriptor_index).ok()?).ok())
+        }
+        Expr::StaticField(m) | Expr::Field(_, m) => FieldType::parse(&m.descriptor).ok(),
+        Expr::ArrayElement(array, _) => ty(array)?.component_type().cloned(),
+        Expr::ArrayLength(_) | Expr::Compare(..) => base(BaseType::Int),
+        Expr::Binary(op, l, r) => match op {
+            _ if op.is_comparison() => base(BaseType::Boolean),
+            BinaryOp::LogicalAnd | BinaryOp::LogicalOr => base(BaseType::Boolean),
+            BinaryOp::Shl | BinaryOp::Shr | BinaryOp::UShr => promote(ty(l)),
+            BinaryOp::And | BinaryOp::Or | BinaryOp::Xor
+                if is_boolean(&ty(l)) || is_boolean(&ty(r)) =>
+            {
+                base(BaseType::Boolean)
+            }
+            _ => binary_promote(ty(l), ty(r)),
+        },
+        Expr::Unary(UnaryOp::Not, _) | Expr::InstanceOf(..) => base(BaseType::Boolean),
+        Expr::Unary(_, e) => promote(ty(e)),
+        Expr::Cast(t, _) | Expr::NewArray(t, _) | Expr::ArrayInit(t, _, _) => Some(t.clone()),
+        Expr::Invoke(invoke) => {
+            MethodDescriptor::parse(&invoke.method.descriptor)
+                .ok()?
+                .return_type
+        }
+        Expr::New(new) => match &new.anonymous {
+            Some(anonymous) => object(&anonymous.class.super_type),
+            None => object(&new.class),
+        },
+        Expr::Conditional(_, a, b) => match (ty(a), ty(b)) {
+            (Some(a), Some(b)) if a != b && !a.is_reference() && !b.is_reference() => {
+                binary_promote(Some(a), Some(b))
+            }
+            (None, b) => b,
+            (a, _) => a,
+        },
+        Expr::Assign(target, _)
+        | Expr::CompoundAssign(_, target, _)
+        | Expr::Increment { target, .. } => ty(target),
+        Expr::Lambda(lambda) => object(&lambda.interface),
+        Expr::MethodRef(method_ref) => object(&method_ref.interface),
+    }
+}
+
+/// The type both types can be assigned to
+fn join(ctx: &Context, a: FieldType, b: FieldType) -> FieldType {
+    if a == b {
+        return a;
+    }
+    match (&a, &b) {
+        (FieldType::Object(x), FieldType::Object(y)) => {
+            FieldType::Object(ctx.class_path.common_super_class(x, y))
+        }
+        (FieldType::Base(_), FieldType::Base(_)) => binary_promote(Some(a), Some(b)).unwrap(),
+        _ => FieldType::Object(OBJECT.to_string()),
+    }
+}
+
+/// Gives the variables without a type the join of the types of the values assigned to
+/// them. Variables only ever assigned `int` constants become `boolean` or `char` when
+/// they are used as one.
+pub(super) fn infer_var_types(
+    ctx: &Context,
+    body: &mut MethodBody,
+    return_type: Option<&FieldType>,
+) {
+    let untyped = (0..body.vars.len())
+        .filter(|v| body.vars[*v].ty.is_none())
+        .collect::<Vec<_>>();
+    if untyped.is_empty() {
+        return;
+    }
+    let mut assigned: HashMap<VarId, Vec<Expr>> = HashMap::new();
+    visit_exprs(&body.stmts, &mut |e| {
+        if let Expr::Assign(target, value) = e {
+            if let Expr::Var(v) = **target {
+                assigned.entry(v).or_default().push((**value).clone());
+            }
+        }
+    });
+    // the types of variables depend on the types of the variables assigned to them
+    for _ in 0..4 {
+        let mut changed = false;
+        for &v in &untyped {
+            let mut strong = Vec::new();
+            for value in assigned.get(&v).into_iter().flatten() {
+                collect_value_types(ctx, &body.vars, value, &mut strong);
+            }
+            let ty = strong.into_iter().reduce(|a, b| join(ctx, a, b));
+            if ty.is_some() && ty != body.vars[v].ty {
+                body.vars[v].ty = ty;
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    let weak = untyped
+        .iter()
+        .copied()
+        .filter(|v| body.vars[*v].ty.is_none())
+        .collect::<Vec<_>>();
+    if weak.is_empty() {
+        return;
+    }
+    let mut uses: HashMap<VarId, Vec<FieldType>> = HashMap::new();
+    expected_uses(ctx, &body.vars, &body.stmts, return_type, &mut uses);
+    for v in weak {
+        let only_constants = assigned
+            .get(&v)
+            .into_iter()
+            .flatten()
+            .any(|value| !matches!(value, Expr::Null));
+        let expected = uses.get(&v).cloned().unwrap_or_default();
+        body.vars[v].ty = Some(if !only_constants {
+            FieldType::Object(OBJECT.to_string())
+        } else if expected.contains(&FieldType::Base(BaseType::Boolean)) {
+            FieldType::Base(BaseType::Boolean)
+        } else if expected.contains(&FieldType::Base(BaseType::Char)) {
+            FieldType::Base(BaseType::Char)
+        } else {
+            FieldType::Base(BaseType::Int)
+        });
+    }
+}
+
+/// The types of a value assigned to a variable, `int` constants don't count
+fn collect_value_types(ctx: &Context, vars: &[Variable], value: &Expr, out: &mut Vec<FieldType>) {
+    match value {
+        Expr::Int(_) | Expr::Null => {}
+        Expr::Conditional(_, a, b) => {
+            collect_value_types(ctx, vars, a, out);
+            collect_value_types(ctx, vars, b, out);
+        }
+        value => out.extend(expr_type(ctx, vars, value)),
+    }
+}
+
+/// The types the variables are used as: arguments, returned values and stored values
+fn expected_uses(
+    ctx: &Context,
+    vars: &[Variable],
+    stmts: &[Stmt],
+    return_type: Option<&FieldType>,
+    uses: &mut HashMap<VarId, Vec<FieldType>>,
+) {
+    fn record(uses: &mut HashMap<VarId, Vec<FieldType>>, e: &Expr, t: Option<FieldType>) {
+        if let (Expr::Var(v), Some(t)) = (e, t) {
+            uses.entry(*v).or_default().push(t);
+        }
+    }
+    for stmt in stmts {
+        match stmt {
+            Stmt::Return(Some(e)) => record(uses, e, return_type.cloned()),
+            Stmt::ConstructorCall {
+                descriptor, args, ..
+            } => {
+                for (arg, t) in args.iter().zip(parameter_types(descriptor)) {
+                    record(uses, arg, Some(t));
+                }
+            }
+            _ => {}
+        }
+        for e in stmt.exprs() {
+            e.visit(&mut |e| match e {
+                Expr::Invoke(invoke) => {
+                    for (arg, t) in invoke
+                        .args
+                        .iter()
+                        .zip(parameter_types(&invoke.method.descriptor))
+                    {
+                        record(uses, arg, Some(t));
+                    }
+                }
+                Expr::New(new) => {
+                    for (arg, t) in new.args.iter().zip(parameter_types(&new.descriptor)) {
+                        record(uses, arg, Some(t));
+                    }
+                }
+                Expr::Assign(target, value) if !matches!(**target, Expr::Var(_)) => {
+                    record(uses, value, expr_type(ctx, vars, target));
+                }
+                Expr::Cast(t, value) => record(uses, value, Some(t.clone())),
+                _ => {}
+            });
+        }
+        for block in stmt.blocks() {
+            expected_uses(ctx, vars, block, return_type, uses);
+        }
+    }
+}
+
+pub(super) fn parameter_types(descriptor: &str) -> Vec<FieldType> {
+    MethodDescriptor::parse(descriptor)
+        .map(|d| d.parameters)
+        .unwrap_or_default()
+}
+
+/// Whether a value of type `from` needs a cast to be used as `to`, `invocation` for
+/// arguments, where the cast also picks the same overload
+fn needs_cast(from: &FieldType, to: &FieldType, invocation: bool) -> bool {
+    let (FieldType::Base(from), FieldType::Base(to)) = (from, to) else {
+        return false;
+    };
+    if from == to || *from == BaseType::Boolean || *to == BaseType::Boolean {
+        return false;
+    }
+    if invocation {
+        return true;
+    }
+    let rank = |t: &BaseType| match t {
+        BaseType::Byte => 1,
+        BaseType::Short | BaseType::Char => 2,
+        BaseType::Int => 3,
+        BaseType::Long => 4,
+        BaseType::Float => 5,
+        BaseType::Double => 6,
+        BaseType::Boolean => 0,
+    };
+    // char and short can't be assigned to each other, byte only widens to short
+    rank(from) >= rank(to) || *to == BaseType::Char || *from == BaseType::Char
+}
+
+/// Coerces every expression to the type its context expects and simplifies the
+/// conditions that come out of it
+pub(super) fn coerce(ctx: &Context, body: &mut MethodBody, return_type: Option<&FieldType>) {
+    let vars = body.vars.clone();
+    let coercer = Coercer { ctx, vars: &vars };
+    coercer.stmts(&mut body.stmts, return_type);
+}
+
+struct Coercer<'a> {
+    ctx: &'a Context<'a>,
+    vars: &'a [Variable],
+}
+
+impl Coercer<'_> {
+    fn ty(&self, e: &Expr) -> Option<FieldType> {
+        expr_type(self.ctx, self.vars, e)
+    }
+
+    fn stmts(&self, stmts: &mut [Stmt], return_type: Option<&FieldType>) {
+        let boolean = FieldType::Base(BaseType::Boolean);
+        for stmt in stmts {
+            match &mut *stmt {
+                Stmt::Return(Some(e)) => self.expr(e, return_type, false),
+                Stmt::If { cond, .. }
+                | Stmt::While { cond, .. }
+                | Stmt::DoWhile { cond, .. }
+                | Stmt::For {
+                    cond: Some(cond), ..
+                } => self.expr(cond, Some(&boolean), false),
+                Stmt::Declare(v, Some(e)) => {
+                    let ty = self.vars[*v].ty.clone();
+                    self.expr(e, ty.as_ref(), false)
+                }
+                Stmt::ConstructorCall {
+                    descriptor, args, ..
+                } => {
+                    for (arg, t) in args.iter_mut().zip(parameter_types(descriptor)) {
+                        self.expr(arg, Some(&t), true);
+                    }
+                }
+                Stmt::For { .. } => {}
+                other => {
+                    for e in other.exprs_mut() {
+                        self.expr(e, None, false);
+                    }
+                }
+            }
+            if let Stmt::For { update, .. } = stmt {
+                for e in update {
+                    self.expr(e, None, false);
+                }
+            }
+            for block in stmt.blocks_mut() {
+                self.stmts(block, return_type);
+            }
+        }
+    }
+
+    /// Coerces the expression to `expected`, `invocation` for method arguments
+    fn expr(&self, e: &mut Expr, expected: Option<&FieldType>, invocation: bool) {
+        let boolean = FieldType::Base(BaseType::Boolean);
+        let int = FieldType::Base(BaseType::Int);
+        match &mut *e {
+            Expr::Assign(target, value) => {
+                self.expr(target, None, false);
+                let ty = self.ty(target);
+                self.expr(value, ty.as_ref(), false);
+            }
+            Expr::CompoundAssign(op, target, value) => {
+                self.expr(target, None, false);
+                let ty = match op {
+                    BinaryOp::And | BinaryOp::Or | BinaryOp::Xor => self.ty(target),
+                    _ => None,
+                };
+                self.expr(value, ty.filter(|t| *t == boolean).as_ref(), false);
+            }
+            Expr::Invoke(invoke) => {
+                if let InvokeKind::Virtual(receiver) = &mut invoke.kind {
+                    self.expr(receiver, None, false);
+                }
+                let types = parameter_types(&invoke.method.descriptor);
+                for (arg, t) in invoke.args.iter_mut().zip(types) {
+                    self.expr(arg, Some(&t), true);
+                }
+            }
+            Expr::New(new) => {
+                let types = parameter_types(&new.descriptor);
+                for (arg, t) in new.args.iter_mut().zip(types) {
+                    self.expr(arg, Some(&t), true);
+                }
+                if let Some(anonymous) = &mut new.anonymous {
+                    let types = parameter_types(&anonymous.class.super_descriptor);
+                    for (arg, t) in anonymous.super_args.iter_mut().zip(types) {
+                        self.expr(arg, Some(&t), true);
+                    }
+                    for (_, capture) in &mut anonymous.captures {
+                        self.expr(capture, None, false);
+                    }
+                }
+            }
+            Expr::ArrayInit(t, _, elements) => {
+                let component = t.component_type().cloned();
+                for element in elements {
+                    self.expr(element, component.as_ref(), false);
+                }
+            }
+            Expr::NewArray(_, dimensions) => {
+                for d in dimensions {
+                    self.expr(d, Some(&int), false);
+                }
+            }
+            Expr::ArrayElement(array, index) => {
+                self.expr(array, None, false);
+                self.expr(index, Some(&int), false);
+            }
+            Expr::Conditional(c, a, b) => {
+                self.expr(c, Some(&boolean), false);
+                self.expr(a, expected, invocation);
+                self.expr(b, expected, invocation);
+            }
+            Expr::Binary(BinaryOp::LogicalAnd | BinaryOp::LogicalOr, l, r) => {
+                self.expr(l, Some(&boolean), false);
+                self.expr(r, Some(&boolean), false);
+            }
+            Expr::Binary(op @ (BinaryOp::And | BinaryOp::Or | BinaryOp::Xor), l, r) => {
+                let _ = op;
+                let logical = expected == Some(&boolean)
+                    || is_boolean(&self.ty(l))
+                    || is_boolean(&self.ty(r));
+                let t = logical.then_some(&boolean);
+                self.expr(l, t, false);
+                self.expr(r, t, false);
+            }
+            Expr::Binary(op, l, r) if op.is_comparison() => {
+                self.expr(l, None, false);
+                self.expr(r, None, false);
+                let (tl, tr) = (self.ty(l), self.ty(r));
+                if is_boolean(&tl) {
+                    self.expr(r, Some(&boolean), false);
+                } else if is_boolean(&tr) {
+                    self.expr(l, Some(&boolean), false);
+                } else if tl == base(BaseType::Char) {
+                    char_constant(r);
+                } else if tr == base(BaseType::Char) {
+                    char_constant(l);
+                }
+            }
+            Expr::Unary(UnaryOp::Not, operand) => self.expr(operand, Some(&boolean), false),
+            Expr::Cast(FieldType::Base(BaseType::Boolean), inner) => {
+                let mut inner = std::mem::replace(&mut **inner, Expr::Null);
+                self.expr(&mut inner, Some(&boolean), false);
+                *e = inner;
+            }
+            Expr::Cast(t, inner) => {
+                let t = t.clone();
+                self.expr(inner, None, false);
+                if let FieldType::Base(BaseType::Char | BaseType::Byte | BaseType::Short) = t {
+                    if let Expr::Int(_) = **inner {
+                        if t == FieldType::Base(BaseType::Char) {
+                            char_constant(inner);
+                            *e = std::mem::replace(&mut **inner, Expr::Null);
+                        }
+                        return self.adapt(e, expected, invocation);
+                    }
+                }
+                if self.ty(inner) == Some(t) {
+                    *e = std::mem::replace(&mut **inner, Expr::Null);
+                }
+            }
+            Expr::Concat(parts) => {
+                for part in parts.iter_mut() {
+                    self.expr(part, None, false);
+                }
+                // `a + b + "x"` adds the numbers first, a leading `""` makes it all
+                // string concatenation
+                let string = object("java/lang/String");
+                if !parts.iter().take(2).any(|p| self.ty(p) == string) {
+                    parts.insert(0, Expr::String(String::new()));
+                }
+            }
+            other => {
+                for child in other.children_mut() {
+                    self.expr(child, None, false);
+                }
+            }
+        }
+        self.adapt(e, expected, invocation);
+    }
+
+    /// Converts the expression itself to the expected type
+    fn adapt(&self, e: &mut Expr, expected: Option<&FieldType>, invocation: bool) {
+        let Some(expected) = expected else {
+            simplify(e);
+            return;
+        };
+        match expected {
+            FieldType::Base(BaseType::Boolean) => {
+                to_boolean(self, e);
+            }
+            FieldType::Base(BaseType::Char) if char_constant(e) => {}
+            FieldType::Base(b @ (BaseType::Byte | BaseType::Short)) => {
+                if let Expr::Int(_) = e {
+                    if invocation {
+                        let value = std::mem::replace(e, Expr::Null);
+                        *e = Expr::Cast(FieldType::Base(*b), Box::new(value));
+                    }
+                } else if let Some(t) = self.ty(e) {
+                    if needs_cast(&t, expected, invocation) {
+                        cast(e, expected);
+                    }
+                }
+            }
+            expected => {
+                if let Some(t) = self.ty(e) {
+                    if !matches!(e, Expr::Int(_)) && needs_cast(&t, expected, invocation) {
+                        cast(e, expected);
+                    }
+                }
+            }
+        }
+        simplify(e);
+    }
+}
+
+fn cast(e: &mut Expr, t: &FieldType) {
+    let value = std::mem::replace(e, Expr::Null);
+    *e = Expr::Cast(t.clone(), Box::new(value));
+}
+
+/// Turns an `int` constant into a `char` constant
+fn char_constant(e: &mut Expr) -> bool {
+    match e {
+        Expr::Int(n) if (0..=0xFFFF).contains(n) => {
+            *e = Expr::Char(*n as u16);
+            true
+        }
+        Expr::Char(_) => true,
+        _ => false,
+    }
+}
+
+fn to_boolean(coercer: &Coercer, e: &mut Expr) {
+    let boolean = FieldType::Base(BaseType::Boolean);
+    match e {
+        Expr::Int(0) => *e = Expr::Bool(false),
+        Expr::Int(_) => *e = Expr::Bool(true),
+        Expr::Conditional(_, a, b) => {
+            to_boolean(coercer, a);
+            to_boolean(coercer, b);
+        }
+        Expr::Binary(BinaryOp::And | BinaryOp::Or | BinaryOp::Xor, l, r) => {
+            to_boolean(coercer, l);
+            to_boolean(coercer, r);
+        }
+        e => {
+            if let Some(t) = coercer.ty(e) {
+                if t != boolean && is_int_like(&t) {
+                    let value = std::mem::replace(e, Expr::Null);
+                    *e = Expr::binary(BinaryOp::Ne, value, Expr::Int(0));
+                }
+            }
+        }
+    }
+}
+
+/// Simplifies the boolean expression at the top of `e`
+pub(super) fn simplify(e: &mut Expr) {
+    let value = std::mem::replace(e, Expr::Null);
+    *e = match value {
+        Expr::Binary(op @ (BinaryOp::Eq | BinaryOp::Ne), l, r)
+            if matches!(*r, Expr::Bool(_)) || matches!(*l, Expr::Bool(_)) =>
+        {
+            let (value, constant) = match *r {
+                Expr::Bool(b) => (*l, b),
+                _ => match *l {
+                    Expr::Bool(b) => (*r, b),
+                    _ => unreachable!(),
+                },
+            };
+            match (op == BinaryOp::Eq) == constant {
+                true => value,
+                false => value.negate(),
+            }
+        }
+        Expr::Unary(UnaryOp::Not, inner) => match *inner {
+            Expr::Unary(UnaryOp::Not, x) => *x,
+            Expr::Bool(b) => Expr::Bool(!b),
+            Expr::Binary(op @ (BinaryOp::Eq | BinaryOp::Ne), l, r) => {
+                Expr::Binary(op.negated().unwrap(), l, r)
+            }
+            inner => Expr::Unary(UnaryOp::Not, Box::new(inner)),
+        },
+        Expr::Conditional(c, a, b) => match (*a, *b) {
+            (Expr::Bool(true), Expr::Bool(false)) => *c,
+            (Expr::Bool(false), Expr::Bool(true)) => c.negate(),
+            (a, Expr::Bool(false)) => Expr::binary(BinaryOp::LogicalAnd, *c, a),
+            (Expr::Bool(true), b) => Expr::binary(BinaryOp::LogicalOr, *c, b),
+            (Expr::Bool(false), b) => Expr::binary(BinaryOp::LogicalAnd, c.negate(), b),
+            (a, Expr::Bool(true)) => Expr::binary(BinaryOp::LogicalOr, c.negate(), a),
+            (a, b) => Expr::Conditional(c, Box::new(a), Box::new(b)),
+        },
+        value => value,
+    };
+}
+
+/// Flips negated comparisons, `!(a < b)` to `a >= b`, where NaN can't make them differ
+pub(super) fn flip_comparisons(ctx: &Context, body: &mut MethodBody) {
+    let vars = body.vars.clone();
+    rewrite_exprs(&mut body.stmts, &mut |e| {
+        if let Expr::Unary(UnaryOp::Not, inner) = e {
+            if let Expr::Binary(op, l, r) = &**inner {
+                if op.is_comparison()
+                    && !is_floating(&expr_type(ctx, &vars, l))
+                    && !is_floating(&expr_type(ctx, &vars, r))
+                {
+                    *e = Expr::Binary(op.negated().unwrap(), l.clone(), r.clone());
+                }
+            }
+        }
+    });
+}
@@ -0,0 +1,235 @@
+//! Splits the local variable slots of a method into the variables of the source
+//!
+//! javac reuses a slot for variables in different scopes and every variable gets its own
+//! slot, so the variables are the webs of the reaching definitions: a load joins all the
+//! stores that reach it into one variable. The `LocalVariableTable` (only written by
+//! `javac -g`) gives the webs their names and types.
+
+use super::ast::{VarId, VarKind, Variable};
+use crate::class_file::attributes::LocalVariableTableAttribute;
+use crate::class_file::bytecode::ByteCode;
+use crate::class_file::dataflow::{DataflowResult, Definition, Definitions, State};
+use crate::class_file::methods::Method;
+use crate::class_file::{ConstantPool, FieldType, MethodDescriptor};
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+
+pub(super) struct Variables {
+    pub vars: Vec<Variable>,
+    /// The variables of the parameters (without `this`) in order
+    pub params: Vec<VarId>,
+    /// The variable loaded or stored by the instruction at a pc, `None` for loads of `this`
+    at_pc: HashMap<u32, Option<VarId>>,
+}
+
+impl Variables {
+    pub fn build(
+        method: &Method,
+        definitions: &DataflowResult<State<Definitions>>,
+        cp: &ConstantPool,
+    ) -> Result<Variables> {
+        let code = method
+            .get_code()
+            .ok_or(anyhow!("Method has no Code attribute"))?;
+        let descriptor = MethodDescriptor::parse(cp.utf8(method.descriptor_index)?)?;
+        let is_static = method.is_static();
+
+        let pcs = code.instructions().map(|(pc, _)| pc).collect::<Vec<_>>();
+        let next_pc = |pc: u32| match pcs.binary_search(&pc) {
+            Ok(i) if i + 1 < pcs.len() => pcs[i + 1],
+            _ => pc,
+        };
+        let mut webs = UnionFind::default();
+        let mut accesses = Vec::new();
+        for (pc, instruction) in code.instructions() {
+            let Some(slot) = local_slot(instruction) else {
+                continue;
+            };
+            let Some(state) = definitions.before(pc) else {
+                // unreachable code
+                continue;
+            };
+            let stores = matches!(instruction, ByteCode::IInc(..)) || is_store(instruction);
+            let loads = !is_store(instruction);
+            let mut nodes = Vec::new();
+            if loads {
+                let reaching = &state
+                    .locals
+                    .get(slot as usize)
+                    .ok_or(anyhow!("Load of local {} at {} is out of range", slot, pc))?
+                    .definitions;
+                if reaching.is_empty() {
+                    return Err(anyhow!("Load of unset local {} at {}", slot, pc));
+                }
+                if !is_static && slot == 0 && *reaching == [Definition::Parameter(0)].into() {
+                    accesses.push((pc, None));
+                    continue;
+                }
+                nodes.extend(reaching.iter().cloned());
+            }
+            if stores {
+                nodes.push(Definition::Instruction(pc));
+            }
+            for node in &nodes[1..] {
+                webs.union(&nodes[0], node);
+            }
+            // a variable's range starts after the store that initializes it
+            let name_pc = match is_store(instruction) {
+                true => next_pc(pc),
+                false => pc,
+            };
+            accesses.push((pc, Some((slot, name_pc, nodes[0].clone()))));
+        }
+
+        let lvt = code.attributes().get_local_variable_table();
+        let lvtt = code.attributes().get_local_variable_type_table();
+        let mut vars = Vec::new();
+        let mut web_vars: HashMap<Definition, VarId> = HashMap::new();
+
+        // the parameters come first so they keep their order
+        let mut params = Vec::new();
+        let mut slot = !is_static as u16;
+        for parameter in &descriptor.parameters {
+            let id = vars.len();
+            vars.push(Variable {
+                name: String::new(),
+                hint: None,
+                ty: Some(parameter.clone()),
+                signature: None,
+                kind: VarKind::Parameter,
+            });
+            name_from_tables(&mut vars[id], lvt, lvtt, slot, 0, cp);
+            web_vars.insert(webs.find(&Definition::Parameter(slot)), id);
+            params.push(id);
+            slot += parameter.size() as u16;
+        }
+
+        let mut at_pc = HashMap::new();
+        for (pc, access) in accesses {
+            let Some((slot, name_pc, node)) = access else {
+                at_pc.insert(pc, None);
+                continue;
+            };
+            let root = webs.find(&node);
+            let id = *web_vars.entry(root).or_insert_with(|| {
+                vars.push(Variable {
+                    name: String::new(),
+                    hint: None,
+                    ty: None,
+                    signature: None,
+                    kind: VarKind::Local,
+                });
+                vars.len() - 1
+            });
+            name_from_tables(&mut vars[id], lvt, lvtt, slot, name_pc, cp);
+            at_pc.insert(pc, Some(id));
+        }
+
+        Ok(Variables {
+            vars,
+            params,
+            at_pc,
+        })
+    }
+
+    /// The variable of the load, store or `iinc` at `pc`, `None` for `this`
+    pub fn at(&self, pc: u32) -> Result<Option<VarId>> {
+        self.at_pc
+            .get(&pc)
+            .copied()
+            .ok_or(anyhow!("No variable for the instruction at {}", pc))
+    }
+
+    pub fn add(&mut self, kind: VarKind, ty: Option<FieldType>) -> VarId {
+        self.vars.push(Variable {
+            name: String::new(),
+            hint: None,
+            ty,
+            signature: None,
+            kind,
+        });
+        self.vars.len() - 1
+    }
+}
+
+/// Takes the name, type and generic signature of the variable from the
+/// `LocalVariableTable` and `LocalVariableTypeTable` entries covering `pc`
+fn name_from_tables(
+    var: &mut Variable,
+    lvt: Option<&LocalVariableTableAttribute>,
+    lvtt: Option<&LocalVariableTableAttribute>,
+    slot: u16,
+    pc: u32,
+    cp: &ConstantPool,
+) {
+    if var.hint.is_some() {
+        return;
+    }
+    let Some(entry) = lvt.and_then(|lvt| lvt.find(slot, pc)) else {
+        return;
+    };
+    var.hint = cp.utf8(entry.name_index).ok().map(|s| s.to_string());
+    if var.ty.is_none() {
+        var.ty = cp
+            .utf8(entry.descriptor_index)
+            .ok()
+            .and_then(|d| FieldType::parse(d).ok());
+    }
+    if let Some(entry) = lvtt.and_then(|lvtt| lvtt.find(slot, pc)) {
+        var.signature = cp.utf8(entry.descriptor_index).ok().map(|s| s.to_string());
+    }
+}
+
+fn local_slot(instruction: &ByteCode) -> Option<u16> {
+    match instruction {
+        ByteCode::ILoad(slot)
+        | ByteCode::Lload(slot)
+        | ByteCode::FLoad(slot)
+        | ByteCode::DLoad(slot)
+        | ByteCode::ALoad(slot)
+        | ByteCode::IStore(slot)
+        | ByteCode::LStore(slot)
+        | ByteCode::FStore(slot)
+        | ByteCode::DStore(slot)
+        | ByteCode::AStore(slot)
+        | ByteCode::IInc(slot, _) => Some(*slot),
+        _ => None,
+    }
+}
+
+fn is_store(instruction: &ByteCode) -> bool {
+    matches!(
+        instruction,
+        ByteCode::IStore(_)
+            | ByteCode::LStore(_)
+            | ByteCode::FStore(_)
+            | ByteCode::DStore(_)
+            | ByteCode::AStore(_)
+    )
+}
+
+#[derive(Default)]
+struct UnionFind {
+    parent: HashMap<Definition, Definition>,
+}
+
+impl UnionFind {
+    fn find(&mut self, node: &Definition) -> Definition {
+        let parent = match self.parent.get(node) {
+            None => return node.clone(),
+            Some(parent) if parent == node => return node.clone(),
+            Some(parent) => parent.clone(),
+        };
+        let root = self.find(&parent);
+        self.parent.insert(node.clone(), root.clone());
+        root
+    }
+
+    fn union(&mut self, a: &Definition, b: &Definition) {
+        let a = self.find(a);
+        let b = self.find(b);
+        if a != b {
+            self.parent.insert(b, a);
+        }
+    }
+}
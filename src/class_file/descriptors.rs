@@ -0,0 +1,422 @@
+//! Field and method descriptors (JVMS 4.3) as types, converted between descriptor form
+//! (`[Ljava/lang/String;`), binary form (`java/lang/String`) and source form
+//! (`java.lang.String[]`)
+
+use super::format_check::is_unqualified_name;
+use anyhow::{anyhow, Result};
+use std::fmt;
+
+/// The primitive types of field descriptors
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BaseType {
+    Byte,
+    Char,
+    Double,
+    Float,
+    Int,
+    Long,
+    Short,
+    Boolean,
+}
+
+/// The type of a field, parameter or local variable
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum FieldType {
+    Base(BaseType),
+    /// Binary name of a class or interface, e.g. `java/lang/String`
+    Object(String),
+    /// An array of the component type
+    Array(Box<FieldType>),
+}
+
+/// The parameter types and the return type (`None` for `void`) of a method
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MethodDescriptor {
+    pub parameters: Vec<FieldType>,
+    pub return_type: Option<FieldType>,
+}
+
+/// Arrays can't have more than 255 dimensions (JVMS 4.3.2)
+const MAX_DIMENSIONS: usize = 255;
+
+impl BaseType {
+    fn from_char(c: u8) -> Option<BaseType> {
+        let base_type = match c {
+            b'B' => BaseType::Byte,
+            b'C' => BaseType::Char,
+            b'D' => BaseType::Double,
+            b'F' => BaseType::Float,
+            b'I' => BaseType::Int,
+            b'J' => BaseType::Long,
+            b'S' => BaseType::Short,
+            b'Z' => BaseType::Boolean,
+            _ => return None,
+        };
+        Some(base_type)
+    }
+
+    fn from_keyword(keyword: &str) -> Option<BaseType> {
+        let base_type = match keyword {
+            "byte" => BaseType::Byte,
+            "char" => BaseType::Char,
+            "double" => BaseType::Double,
+            "float" => BaseType::Float,
+            "int" => BaseType::Int,
+            "long" => BaseType::Long,
+            "short" => BaseType::Short,
+            "boolean" => BaseType::Boolean,
+            _ => return None,
+        };
+        Some(base_type)
+    }
+
+    /// The character of the type in descriptors, e.g. `I`
+    pub fn descriptor(&self) -> char {
+        match self {
+            BaseType::Byte => 'B',
+            BaseType::Char => 'C',
+            BaseType::Double => 'D',
+            BaseType::Float => 'F',
+            BaseType::Int => 'I',
+            BaseType::Long => 'J',
+            BaseType::Short => 'S',
+            BaseType::Boolean => 'Z',
+        }
+    }
+
+    /// The Java keyword of the type, e.g. `int`
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            BaseType::Byte => "byte",
+            BaseType::Char => "char",
+            BaseType::Double => "double",
+            BaseType::Float => "float",
+            BaseType::Int => "int",
+            BaseType::Long => "long",
+            BaseType::Short => "short",
+            BaseType::Boolean => "boolean",
+        }
+    }
+}
+
+impl FieldType {
+    /// Parses a field descriptor, e.g. `[Ljava/lang/String;`
+    pub fn parse(descriptor: &str) -> Result<FieldType> {
+        match parse_field_type(descriptor) {
+            Some((field_type, "")) => Ok(field_type),
+            _ => Err(anyhow!("Invalid field descriptor: {}", descriptor)),
+        }
+    }
+
+    /// The type a `CONSTANT_Class` entry names: a class in binary form, `java/lang/String`,
+    /// or an array descriptor, `[I`
+    pub fn from_binary_name(name: &str) -> Result<FieldType> {
+        if name.starts_with('[') {
+            return FieldType::parse(name);
+        }
+        match is_binary_name(name) {
+            true => Ok(FieldType::Object(name.to_string())),
+            false => Err(anyhow!("Invalid class name: {}", name)),
+        }
+    }
+
+    /// Parses a type in source form, e.g. `int[]` or `java.lang.String`
+    pub fn from_source_name(name: &str) -> Result<FieldType> {
+        if let Some(component) = name.strip_suffix("[]") {
+            let component = FieldType::from_source_name(component)?;
+            if component.dimensions() == MAX_DIMENSIONS {
+                return Err(anyhow!("Too many array dimensions: {}", name));
+            }
+            return Ok(FieldType::Array(Box::new(component)));
+        }
+        if let Some(base_type) = BaseType::from_keyword(name) {
+            return Ok(FieldType::Base(base_type));
+        }
+        let binary_name = name.replace('.', "/");
+        match is_binary_name(&binary_name) {
+            true => Ok(FieldType::Object(binary_name)),
+            false => Err(anyhow!("Invalid type name: {}", name)),
+        }
+    }
+
+    /// The descriptor of the type, e.g. `[Ljava/lang/String;`
+    pub fn descriptor(&self) -> String {
+        self.to_string()
+    }
+
+    /// The name of the type in a `CONSTANT_Class` entry, `None` for primitive types
+    pub fn binary_name(&self) -> Option<String> {
+        match self {
+            FieldType::Base(_) => None,
+            FieldType::Object(name) => Some(name.clone()),
+            FieldType::Array(_) => Some(self.descriptor()),
+        }
+    }
+
+    /// The type as written in Java, e.g. `java.lang.String[]`
+    pub fn source_name(&self) -> String {
+        match self {
+            FieldType::Base(base_type) => base_type.keyword().to_string(),
+            FieldType::Object(name) => name.replace('/', "."),
+            FieldType::Array(component) => format!("{}[]", component.source_name()),
+        }
+    }
+
+    /// Number of local variable slots the type takes up, 2 for `long` and `double`
+    pub fn size(&self) -> usize {
+        match self {
+            FieldType::Base(BaseType::Long | BaseType::Double) => 2,
+            _ => 1,
+        }
+    }
+
+    pub fn is_reference(&self) -> bool {
+        !matches!(self, FieldType::Base(_))
+    }
+
+    /// Number of array dimensions, 0 for types that are not arrays
+    pub fn dimensions(&self) -> usize {
+        match self {
+            FieldType::Array(component) => component.dimensions() + 1,
+            _ => 0,
+        }
+    }
+
+    /// The type of the elements of an array after all its dimensions, the type itself
+    /// for other types
+    pub fn element_type(&self) -> &FieldType {
+        match self {
+            FieldType::Array(component) => component.element_type(),
+            _ => self,
+        }
+    }
+
+    /// The component type of an array, `None` for other types
+    pub fn component_type(&self) -> Option<&FieldType> {
+        match self {
+            FieldType::Array(component) => Some(component),
+            _ => None,
+        }
+    }
+}
+
+impl MethodDescriptor {
+    /// Parses a method descriptor, e.g. `(I[Ljava/lang/String;)V`
+    pub fn parse(descriptor: &str) -> Result<MethodDescriptor> {
+        let invalid = || anyhow!("Invalid method descriptor: {}", descriptor);
+        let mut rest = descriptor.strip_prefix('(').ok_or_else(invalid)?;
+        let mut parameters = Vec::new();
+        while !rest.starts_with(')') {
+            let (parameter, next) = parse_field_type(rest).ok_or_else(invalid)?;
+            parameters.push(parameter);
+            rest = next;
+        }
+        let return_type = match &rest[1..] {
+            "V" => None,
+            return_type => Some(FieldType::parse(return_type).map_err(|_| invalid())?),
+        };
+        Ok(MethodDescriptor {
+            parameters,
+            return_type,
+        })
+    }
+
+    /// The descriptor of the method, e.g. `(I[Ljava/lang/String;)V`
+    pub fn descriptor(&self) -> String {
+        self.to_string()
+    }
+
+    /// Number of local variable slots of the parameters, longs and doubles take two
+    pub fn parameters_size(&self) -> usize {
+        self.parameters.iter().map(FieldType::size).sum()
+    }
+
+    /// The `args_size` javap prints: the parameter slots and one for `this` of instance
+    /// methods
+    pub fn args_size(&self, is_static: bool) -> usize {
+        self.parameters_size() + !is_static as usize
+    }
+
+    /// The return type as written in Java, `void` for methods without a return value
+    pub fn return_source_name(&self) -> String {
+        match &self.return_type {
+            Some(return_type) => return_type.source_name(),
+            None => "void".to_string(),
+        }
+    }
+}
+
+impl fmt::Display for FieldType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FieldType::Base(base_type) => write!(f, "{}", base_type.descriptor()),
+            FieldType::Object(name) => write!(f, "L{};", name),
+            FieldType::Array(component) => write!(f, "[{}", component),
+        }
+    }
+}
+
+impl fmt::Display for MethodDescriptor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "(")?;
+        for parameter in &self.parameters {
+            write!(f, "{}", parameter)?;
+        }
+        match &self.return_type {
+            Some(return_type) => write!(f, "){}", return_type),
+            None => write!(f, ")V"),
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+impl serde::Serialize for FieldType {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "json")]
+impl serde::Serialize for MethodDescriptor {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// The field type at the start of `descriptor` and the rest of it
+fn parse_field_type(descriptor: &str) -> Option<(FieldType, &str)> {
+    let dimensions = descriptor.bytes().take_while(|b| *b == b'[').count();
+    if dimensions > MAX_DIMENSIONS {
+        return None;
+    }
+    let rest = &descriptor[dimensions..];
+    let (mut field_type, rest) = match rest.bytes().next()? {
+        b'L' => {
+            let end = rest.find(';')?;
+            let name = &rest[1..end];
+            if !is_binary_name(name) {
+                return None;
+            }
+            (FieldType::Object(name.to_string()), &rest[end + 1..])
+        }
+        c => (FieldType::Base(BaseType::from_char(c)?), &rest[1..]),
+    };
+    for _ in 0..dimensions {
+        field_type = FieldType::Array(Box::new(field_type));
+    }
+    Some((field_type, rest))
+}
+
+/// A class or interface name in binary form, `java/lang/String`
+fn is_binary_name(name: &str) -> bool {
+    name.split('/').all(is_unqualified_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn object(name: &str) -> FieldType {
+        FieldType::Object(name.to_string())
+    }
+
+    fn array(component: FieldType) -> FieldType {
+        FieldType::Array(Box::new(component))
+    }
+
+    #[test]
+    fn test_parse_field_type() {
+        let cases = vec![
+            ("I", FieldType::Base(BaseType::Int), "int"),
+            ("J", FieldType::Base(BaseType::Long), "long"),
+            ("Z", FieldType::Base(BaseType::Boolean), "boolean"),
+            (
+                "Lcom/example/Class;",
+                object("com/example/Class"),
+                "com.example.Class",
+            ),
+            (
+                "[Lcom/example/Class;",
+                array(object("com/example/Class")),
+                "com.example.Class[]",
+            ),
+            (
+                "[[I",
+                array(array(FieldType::Base(BaseType::Int))),
+                "int[][]",
+            ),
+        ];
+
+        for (descriptor, expected, source_name) in cases {
+            let field_type = FieldType::parse(descriptor).unwrap();
+            assert_eq!(field_type, expected);
+            assert_eq!(field_type.descriptor(), descriptor);
+            assert_eq!(field_type.source_name(), source_name);
+            assert_eq!(FieldType::from_source_name(source_name).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_invalid_field_types() {
+        let too_many_dimensions = format!("{}I", "[".repeat(256));
+        for descriptor in [
+            "",
+            "V",
+            "II",
+            "L;",
+            "Ljava/lang/String",
+            "Ljava.lang.String;",
+            "Ljava//String;",
+            "[",
+            &too_many_dimensions,
+        ] {
+            assert!(FieldType::parse(descriptor).is_err(), "{:?}", descriptor);
+        }
+        assert!(FieldType::parse(&too_many_dimensions[1..]).is_ok());
+    }
+
+    #[test]
+    fn test_binary_names() {
+        let string = FieldType::from_binary_name("java/lang/String").unwrap();
+        assert_eq!(string, object("java/lang/String"));
+        assert_eq!(string.binary_name().unwrap(), "java/lang/String");
+        let ints = FieldType::from_binary_name("[[I").unwrap();
+        assert_eq!(ints.binary_name().unwrap(), "[[I");
+        assert_eq!(ints.dimensions(), 2);
+        assert_eq!(ints.element_type(), &FieldType::Base(BaseType::Int));
+        assert_eq!(ints.component_type().unwrap().descriptor(), "[I");
+        assert_eq!(FieldType::Base(BaseType::Int).binary_name(), None);
+        assert!(FieldType::from_binary_name("java.lang.String").is_err());
+    }
+
+    #[test]
+    fn test_parse_method_descriptor() {
+        let descriptor = MethodDescriptor::parse("(Ljava/lang/String;JI[D)[[I").unwrap();
+        assert_eq!(
+            descriptor.parameters,
+            vec![
+                object("java/lang/String"),
+                FieldType::Base(BaseType::Long),
+                FieldType::Base(BaseType::Int),
+                array(FieldType::Base(BaseType::Double)),
+            ]
+        );
+        assert_eq!(descriptor.return_source_name(), "int[][]");
+        assert_eq!(descriptor.descriptor(), "(Ljava/lang/String;JI[D)[[I");
+        assert_eq!(descriptor.parameters_size(), 5);
+        assert_eq!(descriptor.args_size(false), 6);
+
+        let descriptor = MethodDescriptor::parse("()V").unwrap();
+        assert_eq!(descriptor.return_type, None);
+        assert_eq!(descriptor.return_source_name(), "void");
+        assert_eq!(descriptor.args_size(true), 0);
+
+        for descriptor in ["", "V", "(", "()", "(V)V", "(I)VV", "I)V", "(I;)V"] {
+            assert!(
+                MethodDescriptor::parse(descriptor).is_err(),
+                "{:?}",
+                descriptor
+            );
+        }
+    }
+}
@@ -1,6 +1,8 @@
-use super::attributes::Attributes;
+use super::attributes::{Attributes, ConstantValue};
 use super::constant_pool::ConstantPool;
 use super::file_reader::FileReader;
+use super::file_writer::FileWriter;
+use super::{Parsing, Visibility};
 use anyhow::Result;
 
 #[derive(Debug)]
@@ -20,6 +22,11 @@ pub struct Field {
 pub struct AccessFlags(u16);
 
 #[derive(Debug, PartialEq)]
+#[cfg_attr(
+    feature = "json",
+    derive(serde::Serialize),
+    serde(rename_all = "lowercase")
+)]
 pub enum AccessFlag {
     Public,
     Private,
@@ -37,6 +44,11 @@ impl AccessFlags {
         AccessFlags(flags)
     }
 
+    /// The flags as written in the class file
+    pub fn bits(&self) -> u16 {
+        self.0
+    }
+
     pub fn flag_vector(&self) -> Vec<AccessFlag> {
         let mut flags = Vec::new();
         if self.0 & 0x0001 == 0x0001 {
@@ -70,8 +82,41 @@ impl AccessFlags {
     }
 }
 
+impl Field {
+    /// The initial value of a constant field, read from its `ConstantValue` attribute
+    pub fn constant_value(&self, cp: &ConstantPool) -> Option<Result<ConstantValue>> {
+        self.attributes.get_constant_value(cp)
+    }
+
+    pub fn is_deprecated(&self) -> bool {
+        self.attributes.is_deprecated()
+    }
+
+    pub fn is_synthetic(&self) -> bool {
+        self.access_flags
+            .flag_vector()
+            .contains(&AccessFlag::Synthetic)
+            || self.attributes.is_synthetic()
+    }
+
+    pub fn is_static(&self) -> bool {
+        self.access_flags
+            .flag_vector()
+            .contains(&AccessFlag::Static)
+    }
+
+    pub fn visibility(&self) -> Visibility {
+        let flags = self.access_flags.flag_vector();
+        Visibility::of(
+            flags.contains(&AccessFlag::Public),
+            flags.contains(&AccessFlag::Protected),
+            flags.contains(&AccessFlag::Private),
+        )
+    }
+}
+
 impl Fields {
-    pub fn from(file: &mut FileReader, cp: &ConstantPool) -> Result<Fields> {
+    pub fn from(file: &mut FileReader, cp: &ConstantPool, parsing: Parsing) -> Result<Fields> {
         let mut fields = Vec::new();
 
         let fields_count = file.read_u2_to_u16()?;
@@ -80,7 +125,7 @@ impl Fields {
             let name_index = file.read_u2_to_u16()?;
             let descriptor_index = file.read_u2_to_u16()?;
 
-            let attributes = Attributes::from(file, cp)?;
+            let attributes = Attributes::read(file, cp, parsing)?;
 
             fields.push(Field {
                 access_flags,
@@ -93,6 +138,17 @@ impl Fields {
         Ok(Fields { fields })
     }
 
+    pub fn write(&self, file: &mut FileWriter) -> Result<()> {
+        file.write_count(self.fields.len(), "fields")?;
+        for field in &self.fields {
+            file.write_u2(field.access_flags.bits());
+            file.write_u2(field.name_index);
+            file.write_u2(field.descriptor_index);
+            field.attributes.write(file)?;
+        }
+        Ok(())
+    }
+
     pub fn to_string(&self, cp: &ConstantPool) -> String {
         let mut s = String::new();
 
@@ -112,7 +168,7 @@ impl Fields {
             let flags = field.access_flags.flag_vector();
             s.push_str(format!("{:?}", flags).as_str());
 
-            s.push_str("\n");
+            s.push('\n');
         }
 
         s
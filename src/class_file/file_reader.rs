@@ -1,9 +1,10 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use std::fs::File;
-use std::io::Read;
+use std::io::{BufReader, Cursor, Read};
 
-pub struct FileReader {
-    file: File,
+pub struct FileReader<'a> {
+    file: Box<dyn Read + 'a>,
+    position: usize,
 }
 
 // The type names that are used in the class file documentation
@@ -11,55 +12,134 @@ type U1 = u8;
 type U2 = [u8; 2];
 type U4 = [u8; 4];
 
-impl FileReader {
-    pub fn new(filename: &str) -> Result<FileReader> {
+impl<'a> FileReader<'a> {
+    pub fn new(filename: &str) -> Result<FileReader<'a>> {
         let f = FileReader {
-            file: File::open(filename)?,
+            file: Box::new(BufReader::new(File::open(filename)?)),
+            position: 0,
         };
         Ok(f)
     }
 
+    /// Reads from a class file that is already in memory, e.g. extracted from a jar
+    pub fn from_bytes<B: AsRef<[u8]> + 'a>(bytes: B) -> FileReader<'a> {
+        FileReader {
+            file: Box::new(Cursor::new(bytes)),
+            position: 0,
+        }
+    }
+
+    /// Number of bytes read so far
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Whether all bytes have been read, reads a byte if not
+    pub fn at_end(&mut self) -> Result<bool> {
+        let mut buf = [0; 1];
+        Ok(self.file.read(&mut buf)? == 0)
+    }
+
+    pub fn skip(&mut self, n: usize) -> Result<()> {
+        let mut buf = vec![0; n];
+        self.read_exact(&mut buf)
+    }
+
+    /// Appends the next `n` bytes to `buf`
+    pub fn read_into(&mut self, buf: &mut Vec<u8>, n: usize) -> Result<()> {
+        // grows `buf` as the bytes arrive instead of trusting a length from the file
+        let read = (&mut self.file).take(n as u64).read_to_end(buf)?;
+        self.position += read;
+        if read < n {
+            return Err(anyhow!("Unexpected end of file, {} of {} bytes", read, n));
+        }
+        Ok(())
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<()> {
+        self.file.read_exact(buf)?;
+        self.position += buf.len();
+        Ok(())
+    }
+
     pub fn read_u1(&mut self) -> Result<U1> {
         let mut buf = [0; 1];
-        self.file.read_exact(&mut buf)?;
+        self.read_exact(&mut buf)?;
         Ok(buf[0])
     }
 
     pub fn read_u2(&mut self) -> Result<U2> {
         let mut buf = [0; 2];
-        self.file.read_exact(&mut buf)?;
+        self.read_exact(&mut buf)?;
         Ok(buf)
     }
 
     pub fn read_u2_to_u16(&mut self) -> Result<u16> {
         let mut buf = [0; 2];
-        self.file.read_exact(&mut buf)?;
+        self.read_exact(&mut buf)?;
         Ok(u16::from_be_bytes(buf))
     }
 
     pub fn read_i16(&mut self) -> Result<i16> {
         let mut buf = [0; 2];
-        self.file.read_exact(&mut buf)?;
+        self.read_exact(&mut buf)?;
         Ok(i16::from_be_bytes(buf))
     }
 
     pub fn read_u4(&mut self) -> Result<U4> {
         let mut buf = [0; 4];
-        self.file.read_exact(&mut buf)?;
+        self.read_exact(&mut buf)?;
         Ok(buf)
     }
 
     pub fn read_u4_to_u32(&mut self) -> Result<u32> {
         let mut buf = [0; 4];
-        self.file.read_exact(&mut buf)?;
+        self.read_exact(&mut buf)?;
         Ok(u32::from_be_bytes(buf))
     }
 
     pub fn read_string(&mut self) -> Result<String> {
         let length = self.read_u2_to_u16()?;
         let mut buf = vec![0; length as usize];
-        self.file.read_exact(&mut buf)?;
-        let s = String::from_utf8(buf.clone()).unwrap();
-        Ok(s)
+        self.read_exact(&mut buf)?;
+        Ok(decode_modified_utf8(&buf))
+    }
+}
+
+/// Decodes modified UTF-8 (JVMS 4.4.7), which is not valid UTF-8: NUL is encoded in two
+/// bytes and characters outside the BMP as surrogate pairs of three bytes each.
+/// Malformed bytes and unpaired surrogates are replaced with U+FFFD.
+pub(crate) fn decode_modified_utf8(bytes: &[u8]) -> String {
+    if let Ok(s) = std::str::from_utf8(bytes) {
+        return s.to_string();
+    }
+    let mut units = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let continuation = |k: usize| bytes.get(i + k).filter(|b| *b & 0xc0 == 0x80);
+        let b = bytes[i];
+        match b {
+            0x01..=0x7f => {
+                units.push(b as u16);
+                i += 1;
+            }
+            0xc0..=0xdf if continuation(1).is_some() => {
+                units.push(((b as u16 & 0x1f) << 6) | (bytes[i + 1] as u16 & 0x3f));
+                i += 2;
+            }
+            0xe0..=0xef if continuation(1).is_some() && continuation(2).is_some() => {
+                units.push(
+                    ((b as u16 & 0x0f) << 12)
+                        | ((bytes[i + 1] as u16 & 0x3f) << 6)
+                        | (bytes[i + 2] as u16 & 0x3f),
+                );
+                i += 3;
+            }
+            _ => {
+                units.push(0xfffd);
+                i += 1;
+            }
+        }
     }
+    String::from_utf16_lossy(&units)
 }
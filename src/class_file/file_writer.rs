@@ -0,0 +1,97 @@
+use anyhow::{anyhow, Result};
+
+/// The counterpart of `FileReader`, collects the bytes of a class file in memory
+#[derive(Default)]
+pub struct FileWriter {
+    bytes: Vec<u8>,
+}
+
+impl FileWriter {
+    pub fn new() -> FileWriter {
+        FileWriter::default()
+    }
+
+    /// Number of bytes written so far
+    pub fn position(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+
+    pub fn write_u1(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    pub fn write_u2(&mut self, value: u16) {
+        self.bytes.extend(value.to_be_bytes());
+    }
+
+    pub fn write_i16(&mut self, value: i16) {
+        self.bytes.extend(value.to_be_bytes());
+    }
+
+    pub fn write_u4(&mut self, value: u32) {
+        self.bytes.extend(value.to_be_bytes());
+    }
+
+    pub fn write_i32(&mut self, value: i32) {
+        self.bytes.extend(value.to_be_bytes());
+    }
+
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    /// A u2 count followed by the items, an error if there are more than 65535
+    pub fn write_count(&mut self, count: usize, what: &str) -> Result<()> {
+        let count = u16::try_from(count).map_err(|_| anyhow!("Too many {}: {}", what, count))?;
+        self.write_u2(count);
+        Ok(())
+    }
+
+    /// The u2 length and the bytes of a string in modified UTF-8 (JVMS 4.4.7): NUL takes
+    /// two bytes and characters outside the BMP are written as surrogate pairs
+    pub fn write_string(&mut self, s: &str) -> Result<()> {
+        let mut encoded = Vec::with_capacity(s.len());
+        for c in s.chars() {
+            match c as u32 {
+                0 => encoded.extend([0xc0, 0x80]),
+                1..=0x7f => encoded.push(c as u8),
+                0x80..=0xffff => {
+                    let mut buf = [0; 3];
+                    encoded.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                }
+                _ => {
+                    let mut units = [0; 2];
+                    for unit in c.encode_utf16(&mut units) {
+                        let unit = *unit as u32;
+                        encoded.push(0xe0 | (unit >> 12) as u8);
+                        encoded.push(0x80 | ((unit >> 6) & 0x3f) as u8);
+                        encoded.push(0x80 | (unit & 0x3f) as u8);
+                    }
+                }
+            }
+        }
+        let length = u16::try_from(encoded.len())
+            .map_err(|_| anyhow!("String of {} bytes is too long", encoded.len()))?;
+        self.write_u2(length);
+        self.bytes.extend(encoded);
+        Ok(())
+    }
+
+    /// Writes the attribute name, then the content written by `f` with its length
+    pub fn write_attribute(
+        &mut self,
+        name_index: u16,
+        f: impl FnOnce(&mut FileWriter) -> Result<()>,
+    ) -> Result<()> {
+        let mut info = FileWriter::new();
+        f(&mut info)?;
+        self.write_u2(name_index);
+        self.write_u4(info.position() as u32);
+        self.bytes.extend(info.bytes);
+        Ok(())
+    }
+}
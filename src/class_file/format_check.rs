@@ -0,0 +1,482 @@
+//! Format checking (JVMS 4.8), the structural checks the JVM does before verification:
+//! the constant pool entries reference entries of the right kind, names and descriptors
+//! are well formed, the access flags of the class, fields and methods are legal
+//! combinations and the attributes are as long as declared.
+//!
+//! Unlike parsing, which stops at the first problem, every violation is reported.
+
+use super::attributes::{Attribute, Attributes};
+use super::constant_pool::Info;
+use super::methods::Method;
+use super::{fields, methods, AccessFlag, ClassFile, FieldType, MethodDescriptor};
+use std::collections::HashSet;
+use std::fmt;
+
+/// A violation of the class file format
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormatError {
+    /// Where in the class file, e.g. `constant pool #12` or `method foo()V, attribute Code`
+    pub location: String,
+    pub message: String,
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.location, self.message)
+    }
+}
+
+/// Checks the structure of the class file, empty if it is well formed
+pub fn check_format(cf: &ClassFile) -> Vec<FormatError> {
+    let mut checker = FormatChecker {
+        cf,
+        errors: Vec::new(),
+    };
+    checker.check_constant_pool();
+    checker.check_class();
+    checker.check_fields();
+    checker.check_methods();
+    checker.check_attributes("class", &cf.attributes);
+    checker.errors
+}
+
+struct FormatChecker<'a> {
+    cf: &'a ClassFile,
+    errors: Vec<FormatError>,
+}
+
+impl FormatChecker<'_> {
+    fn error(&mut self, location: &str, message: String) {
+        self.errors.push(FormatError {
+            location: location.to_string(),
+            message,
+        });
+    }
+
+    fn check<T>(&mut self, location: &str, result: Result<T, String>) -> Option<T> {
+        match result {
+            Ok(value) => Some(value),
+            Err(message) => {
+                self.error(location, message);
+                None
+            }
+        }
+    }
+
+    fn check_constant_pool(&mut self) {
+        let bootstrap_methods = self
+            .cf
+            .attributes
+            .get_bootstrap_methods()
+            .map(|att| att.num_bootstrap_methods())
+            .unwrap_or(0);
+        let cp = &self.cf.constant_pool;
+        for (index, message) in cp.format_errors(self.cf.major_version, bootstrap_methods) {
+            self.error(&format!("constant pool #{}", index), message);
+        }
+    }
+
+    fn check_class(&mut self) {
+        let cp = &self.cf.constant_pool;
+        let flags = self.cf.access_flags.flag_vector();
+        let has = |flag: AccessFlag| flags.contains(&flag);
+        if has(AccessFlag::Interface) {
+            if !has(AccessFlag::Abstract) {
+                self.error("class", "Interface is not abstract".to_string());
+            }
+            for (flag, name) in [
+                (AccessFlag::Final, "final"),
+                (AccessFlag::Super, "super"),
+                (AccessFlag::Enum, "enum"),
+            ] {
+                if has(flag) {
+                    self.error("class", format!("Interface is {}", name));
+                }
+            }
+        } else {
+            if has(AccessFlag::Annotation) {
+                self.error("class", "Annotation is not an interface".to_string());
+            }
+            if has(AccessFlag::Final) && has(AccessFlag::Abstract) {
+                self.error("class", "Class is both final and abstract".to_string());
+            }
+        }
+
+        let this_class = cp.checked_class(self.cf.this_class);
+        let name = self.check("this_class", this_class).unwrap_or_default();
+        match self.cf.super_class {
+            // only Object and module-info have no super class
+            0 if name != "java/lang/Object" && name != "module-info" => {
+                self.error("super_class", "Missing super class".to_string())
+            }
+            0 => {}
+            index => {
+                let super_class = cp.checked_class(index);
+                if let Some(super_class) = self.check("super_class", super_class) {
+                    if super_class.starts_with('[') {
+                        let message = format!("Super class {} is an array", super_class);
+                        self.error("super_class", message);
+                    }
+                    if has(AccessFlag::Interface) && super_class != "java/lang/Object" {
+                        self.error(
+                            "super_class",
+                            "Super class of an interface is not java/lang/Object".to_string(),
+                        );
+                    }
+                }
+            }
+        }
+        for index in &self.cf.interfaces.interfaces {
+            let interface = cp.checked_class(*index);
+            self.check("interfaces", interface);
+        }
+    }
+
+    fn check_fields(&mut self) {
+        let cp = &self.cf.constant_pool;
+        let is_interface = self.cf.is_interface();
+        let mut seen = HashSet::new();
+        for (i, field) in self.cf.fields.fields.iter().enumerate() {
+            let name = cp.checked_utf8(field.name_index);
+            let descriptor = cp.checked_utf8(field.descriptor_index);
+            let location = match (&name, &descriptor) {
+                (Ok(name), Ok(descriptor)) => format!("field {}:{}", name, descriptor),
+                _ => format!("field {}", i),
+            };
+            if let Some(name) = self.check(&location, name) {
+                if !is_unqualified_name(name) {
+                    self.error(&location, format!("Invalid field name {:?}", name));
+                }
+            }
+            if let Some(descriptor) = self.check(&location, descriptor) {
+                if !is_field_descriptor(descriptor) {
+                    self.error(
+                        &location,
+                        format!("Invalid field descriptor {:?}", descriptor),
+                    );
+                }
+            }
+            if !seen.insert((field.name_index, field.descriptor_index)) {
+                self.error(&location, "Duplicate field".to_string());
+            }
+
+            use fields::AccessFlag::*;
+            let flags = field.access_flags.flag_vector();
+            let has = |flag: fields::AccessFlag| flags.contains(&flag);
+            if [Public, Private, Protected]
+                .into_iter()
+                .filter(|f| flags.contains(f))
+                .count()
+                > 1
+            {
+                self.error(
+                    &location,
+                    "More than one of public, private and protected".to_string(),
+                );
+            }
+            if has(Final) && has(Volatile) {
+                self.error(&location, "Field is both final and volatile".to_string());
+            }
+            if is_interface
+                && (!has(Public)
+                    || !has(Static)
+                    || !has(Final)
+                    || flags
+                        .iter()
+                        .any(|f| !matches!(f, Public | Static | Final | Synthetic)))
+            {
+                self.error(
+                    &location,
+                    "Interface field is not exactly public static final".to_string(),
+                );
+            }
+
+            self.check_constant_value(&location, field);
+            self.check_attributes(&location, &field.attributes);
+        }
+    }
+
+    /// The constant of a `ConstantValue` attribute must match the type of the field
+    fn check_constant_value(&mut self, location: &str, field: &fields::Field) {
+        let cp = &self.cf.constant_pool;
+        let Ok(descriptor) = cp.checked_utf8(field.descriptor_index) else {
+            return;
+        };
+        for att in field.attributes.attributes() {
+            let Attribute::ConstantValueAttribute(att) = att else {
+                continue;
+            };
+            let valid = matches!(
+                (descriptor, cp.try_get(att.constantvalue_index)),
+                ("I" | "S" | "C" | "B" | "Z", Some(Info::IntegerInfo(_)))
+                    | ("F", Some(Info::FloatInfo(_)))
+                    | ("J", Some(Info::LongInfo(_)))
+                    | ("D", Some(Info::DoubleInfo(_)))
+                    | ("Ljava/lang/String;", Some(Info::StringInfo(_)))
+            );
+            if !valid {
+                let message = format!(
+                    "ConstantValue #{} does not match the field type {}",
+                    att.constantvalue_index, descriptor
+                );
+                self.error(location, message);
+            }
+        }
+    }
+
+    fn check_methods(&mut self) {
+        let cp = &self.cf.constant_pool;
+        let mut seen = HashSet::new();
+        for (i, method) in self.cf.methods.methods.iter().enumerate() {
+            let name = cp.checked_utf8(method.name_index);
+            let descriptor = cp.checked_utf8(method.descriptor_index);
+            let location = match (&name, &descriptor) {
+                (Ok(name), Ok(descriptor)) => format!("method {}{}", name, descriptor),
+                _ => format!("method {}", i),
+            };
+            let name = self.check(&location, name).unwrap_or_default();
+            if let Some(descriptor) = self.check(&location, descriptor) {
+                if !is_method_descriptor(descriptor) {
+                    self.error(
+                        &location,
+                        format!("Invalid method descriptor {:?}", descriptor),
+                    );
+                } else if name.starts_with('<') && !descriptor.ends_with(")V") {
+                    self.error(&location, format!("{} does not return void", name));
+                }
+            }
+            if !is_method_name(name) || (name == "<init>" && self.cf.is_interface()) {
+                self.error(&location, format!("Invalid method name {:?}", name));
+            }
+            if !seen.insert((method.name_index, method.descriptor_index)) {
+                self.error(&location, "Duplicate method".to_string());
+            }
+
+            for message in self.method_flag_errors(method, name) {
+                self.error(&location, message);
+            }
+
+            let flags = method.access_flags.flag_vector();
+            let needs_code = !flags.contains(&methods::AccessFlag::Abstract)
+                && !flags.contains(&methods::AccessFlag::Native);
+            let code_attributes = method
+                .attributes
+                .attributes()
+                .iter()
+                .filter(|att| matches!(att, Attribute::Code(_)))
+                .count();
+            match (needs_code, code_attributes) {
+                (true, 0) => self.error(&location, "Missing Code attribute".to_string()),
+                (false, 1..) => {
+                    self.error(&location, "Abstract or native method with code".to_string())
+                }
+                (_, 2..) => self.error(&location, "More than one Code attribute".to_string()),
+                _ => {}
+            }
+
+            self.check_attributes(&location, &method.attributes);
+        }
+    }
+
+    fn method_flag_errors(&self, method: &Method, name: &str) -> Vec<String> {
+        use methods::AccessFlag::*;
+        let flags = method.access_flags.flag_vector();
+        let has = |flag: methods::AccessFlag| flags.contains(&flag);
+        let mut errors = Vec::new();
+        let access = [Public, Private, Protected]
+            .into_iter()
+            .filter(|f| flags.contains(f))
+            .count();
+        if access > 1 {
+            errors.push("More than one of public, private and protected".to_string());
+        }
+        // the flags of class initializers are ignored, apart from static since Java 7
+        if name == "<clinit>" {
+            if self.cf.major_version >= 51 && !has(Static) {
+                errors.push("<clinit> is not static".to_string());
+            }
+            return errors;
+        }
+        if self.cf.is_interface() {
+            if self.cf.major_version < 52 && !(has(Public) && has(Abstract)) {
+                errors.push("Interface method is not public abstract".to_string());
+            }
+            if access != 1 || has(Protected) {
+                errors.push("Interface method is not public or private".to_string());
+            }
+            for (flag, flag_name) in [
+                (Final, "final"),
+                (Synchronized, "synchronized"),
+                (Native, "native"),
+            ] {
+                if has(flag) {
+                    errors.push(format!("Interface method is {}", flag_name));
+                }
+            }
+        }
+        if has(Abstract) {
+            for (flag, flag_name) in [
+                (Private, "private"),
+                (Static, "static"),
+                (Final, "final"),
+                (Synchronized, "synchronized"),
+                (Native, "native"),
+            ] {
+                if has(flag) {
+                    errors.push(format!("Abstract method is {}", flag_name));
+                }
+            }
+        }
+        if name == "<init>"
+            && flags.iter().any(|f| {
+                !matches!(
+                    f,
+                    Public | Private | Protected | Varargs | Strict | Synthetic
+                )
+            })
+        {
+            errors.push(
+                "<init> can only have access, varargs, strict and synthetic flags".to_string(),
+            );
+        }
+        errors
+    }
+
+    fn check_attributes(&mut self, location: &str, attributes: &Attributes) {
+        let cp = &self.cf.constant_pool;
+        if let Err(e) = attributes.decode() {
+            self.error(location, e.to_string());
+        }
+        for mismatch in attributes.length_mismatches() {
+            let name = cp
+                .checked_utf8(mismatch.attribute_name_index)
+                .unwrap_or("?");
+            let message = format!(
+                "attribute_length of {} is {} but its content is {} bytes",
+                name, mismatch.attribute_length, mismatch.parsed_length
+            );
+            self.error(location, message);
+        }
+        for att in attributes.attributes() {
+            let name = match cp.checked_utf8(att.name_index()) {
+                Ok(name) => name,
+                Err(message) => {
+                    self.error(location, format!("Attribute name: {}", message));
+                    continue;
+                }
+            };
+            let location = format!("{}, attribute {}", location, name);
+            let class_indexes = match att {
+                Attribute::Code(code) => {
+                    self.check_code(&location, code);
+                    continue;
+                }
+                Attribute::Exceptions(att) => att.exception_index_table.clone(),
+                Attribute::NestHostAttribute(att) => vec![att.host_class_index],
+                Attribute::NestMembersAttribute(att) => att.classes.clone(),
+                Attribute::PermittedSubclassesAttribute(att) => att.classes.clone(),
+                Attribute::EnclosingMethodAttribute(att) => {
+                    if att.method_index != 0 {
+                        let method = cp.kind(att.method_index, "NameAndType");
+                        self.check(&location, method);
+                    }
+                    vec![att.class_index]
+                }
+                Attribute::InnerClassesAttribute(att) => {
+                    let mut classes = Vec::new();
+                    for class in &att.classes {
+                        classes.push(class.inner_class_info_index);
+                        classes.extend(class.outer_class_info_index);
+                        if let Some(index) = class.inner_name_index {
+                            let name = cp.checked_utf8(index);
+                            self.check(&location, name);
+                        }
+                    }
+                    classes
+                }
+                Attribute::SourceFile(att) => {
+                    let source_file = cp.checked_utf8(att.sourcefile_index);
+                    self.check(&location, source_file);
+                    continue;
+                }
+                Attribute::SignatureAttribute(att) => {
+                    let signature = cp.checked_utf8(att.signature_index);
+                    self.check(&location, signature);
+                    continue;
+                }
+                _ => continue,
+            };
+            for index in class_indexes {
+                let class = cp.checked_class(index);
+                self.check(&location, class);
+            }
+        }
+    }
+
+    fn check_code(&mut self, location: &str, code: &super::attributes::CodeAttribute) {
+        let code_length = code.code_length();
+        if code_length == 0 || code_length >= 65536 {
+            self.error(
+                location,
+                format!("code_length {} is not between 1 and 65535", code_length),
+            );
+        }
+        let pcs = code
+            .instructions()
+            .map(|(pc, _)| pc)
+            .collect::<HashSet<_>>();
+        for entry in code.exception_table() {
+            let (start, end, handler) = (
+                entry.start_pc as u32,
+                entry.end_pc as u32,
+                entry.handler_pc as u32,
+            );
+            let range = format!("exception table entry {}-{}", start, end);
+            if start >= end {
+                self.error(location, format!("Empty {}", range));
+            }
+            if !pcs.contains(&start) || !(pcs.contains(&end) || end == code_length) {
+                self.error(
+                    location,
+                    format!("The {} is not at instruction boundaries", range),
+                );
+            }
+            if !pcs.contains(&handler) {
+                self.error(
+                    location,
+                    format!("Handler {} of the {} is not an instruction", handler, range),
+                );
+            }
+            if entry.catch_type != 0 {
+                let catch_type = self.cf.constant_pool.checked_class(entry.catch_type);
+                self.check(location, catch_type);
+            }
+        }
+        self.check_attributes(location, code.attributes());
+    }
+}
+
+/// A class or interface name in internal form (`java/lang/String`) or an array descriptor
+pub(super) fn is_class_name(name: &str) -> bool {
+    FieldType::from_binary_name(name).is_ok()
+}
+
+/// Field and local variable names can't be empty or contain `.;[/`
+pub(super) fn is_unqualified_name(name: &str) -> bool {
+    !name.is_empty() && !name.contains(['.', ';', '[', '/'])
+}
+
+/// Method names can't contain `<` or `>` either, apart from `<init>` and `<clinit>`
+pub(super) fn is_method_name(name: &str) -> bool {
+    name == "<init>"
+        || name == "<clinit>"
+        || (is_unqualified_name(name) && !name.contains(['<', '>']))
+}
+
+pub(super) fn is_field_descriptor(descriptor: &str) -> bool {
+    FieldType::parse(descriptor).is_ok()
+}
+
+pub(super) fn is_method_descriptor(descriptor: &str) -> bool {
+    MethodDescriptor::parse(descriptor).is_ok()
+}
@@ -0,0 +1,209 @@
+//! Computes the StackMapTable frames, max_stack and max_locals of a method from its
+//! instructions (like ASM's `COMPUTE_FRAMES`), needed after the bytecode was modified.
+//!
+//! The types are inferred by the type-state dataflow analysis, when two paths join the
+//! reference types are merged to their common super class which is looked up through
+//! a `ClassHierarchy`.
+
+use super::attributes::{Frame, VerificationType};
+use super::constant_pool::ConstantPool;
+use super::dataflow;
+use super::methods::Method;
+use super::{AccessFlag, ClassFile};
+use anyhow::Result;
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+const OBJECT: &str = "java/lang/Object";
+const THROWABLE: &str = "java/lang/Throwable";
+
+/// Resolves the class hierarchy needed to merge reference types
+pub trait ClassHierarchy {
+    /// Binary name of the direct super class, `None` for `java/lang/Object` and unknown classes
+    fn super_class(&self, class: &str) -> Option<String>;
+
+    fn is_interface(&self, _class: &str) -> bool {
+        false
+    }
+
+    /// The most specific class both classes are assignable to, the default walks up the
+    /// super classes. Interfaces are merged to `java/lang/Object` like the verifier does.
+    fn common_super_class(&self, a: &str, b: &str) -> String {
+        if a == b {
+            return a.to_string();
+        }
+        if self.is_interface(a) || self.is_interface(b) {
+            return OBJECT.to_string();
+        }
+        let mut supers_a = HashSet::from([a.to_string()]);
+        let mut class = a.to_string();
+        while let Some(s) = self.super_class(&class) {
+            if !supers_a.insert(s.clone()) {
+                break;
+            }
+            class = s;
+        }
+        // a cyclic hierarchy ends the walk when it gets back to a class already seen
+        let mut visited = HashSet::new();
+        let mut class = b.to_string();
+        while visited.insert(class.clone()) {
+            if supers_a.contains(&class) {
+                return class;
+            }
+            match self.super_class(&class) {
+                Some(s) => class = s,
+                None => break,
+            }
+        }
+        OBJECT.to_string()
+    }
+}
+
+/// A hierarchy built from known classes, unknown classes are treated as direct
+/// subclasses of `java/lang/Object`
+#[derive(Debug, Default)]
+pub struct ClassHierarchyMap {
+    classes: HashMap<String, (Option<String>, bool)>,
+}
+
+impl ClassHierarchyMap {
+    pub fn new() -> ClassHierarchyMap {
+        ClassHierarchyMap::default()
+    }
+
+    pub fn add_class(&mut self, name: &str, super_class: Option<&str>, is_interface: bool) {
+        self.classes.insert(
+            name.to_string(),
+            (super_class.map(|s| s.to_string()), is_interface),
+        );
+    }
+
+    pub fn add_class_file(&mut self, cf: &ClassFile) {
+        let is_interface = cf
+            .access_flags
+            .flag_vector()
+            .contains(&AccessFlag::Interface);
+        self.add_class(
+            &cf.class_name(),
+            cf.super_class_name().as_deref(),
+            is_interface,
+        );
+    }
+}
+
+impl ClassHierarchy for ClassHierarchyMap {
+    fn super_class(&self, class: &str) -> Option<String> {
+        match self.classes.get(class) {
+            Some((super_class, _)) => super_class.clone(),
+            None if class == OBJECT => None,
+            None => Some(OBJECT.to_string()),
+        }
+    }
+
+    fn is_interface(&self, class: &str) -> bool {
+        self.classes.get(class).map(|c| c.1).unwrap_or(false)
+    }
+}
+
+/// The result of `compute_frames`
+#[derive(Debug, PartialEq)]
+pub struct ComputedFrames {
+    pub max_stack: u16,
+    pub max_locals: u16,
+    /// Frames at the branch targets, exception handlers and after unconditional jumps,
+    /// i.e. the frames the StackMapTable must contain
+    pub frames: Vec<Frame>,
+    /// The `start..end` pc ranges of the unreachable instructions. Like ASM does, the frame
+    /// at their start has no locals and a `Throwable` on the stack, which only verifies once
+    /// they are replaced by `nop`s and an `athrow`, see `CodeAttribute::replace_dead_code`.
+    pub dead_code: Vec<(u32, u32)>,
+}
+
+pub fn compute_frames(
+    method: &Method,
+    class_name: &str,
+    cp: &ConstantPool,
+    hierarchy: &dyn ClassHierarchy,
+) -> Result<ComputedFrames> {
+    let code = method.code()?;
+    let states = dataflow::type_states(method, class_name, cp, hierarchy)?;
+
+    // frames are required where control flow joins
+    let instructions = code.instructions().collect::<Vec<_>>();
+    let mut frame_targets = BTreeSet::new();
+    for (i, (pc, instruction)) in instructions.iter().enumerate() {
+        frame_targets.extend(instruction.branch_targets(*pc));
+        if !instruction.falls_through() {
+            if let Some((next, _)) = instructions.get(i + 1) {
+                frame_targets.insert(*next);
+            }
+        }
+    }
+    for entry in code.exception_table() {
+        frame_targets.insert(entry.handler_pc as u32);
+    }
+
+    let mut frames = Vec::new();
+    for pc in frame_targets {
+        frames.push(match states.before(pc) {
+            Some(state) => Frame {
+                pc: pc as u16,
+                locals: compress_locals(&state.locals),
+                stack: state.stack.clone(),
+            },
+            None => Frame {
+                pc: pc as u16,
+                locals: Vec::new(),
+                stack: vec![VerificationType::Object(THROWABLE.to_string())],
+            },
+        });
+    }
+
+    let mut dead_code: Vec<(u32, u32)> = Vec::new();
+    for (i, (pc, _)) in instructions.iter().enumerate() {
+        if states.before(*pc).is_some() {
+            continue;
+        }
+        let end = instructions
+            .get(i + 1)
+            .map_or(code.code_length(), |(next, _)| *next);
+        match dead_code.last_mut() {
+            Some(range) if range.1 == *pc => range.1 = end,
+            _ => dead_code.push((*pc, end)),
+        }
+    }
+
+    let initial = method.initial_frame(class_name, cp)?;
+    let mut max_locals = initial.locals.iter().map(|l| l.size()).sum::<usize>();
+    // the Throwable the dead code throws
+    let mut max_stack = usize::from(!dead_code.is_empty());
+    for pc in states.pcs() {
+        for state in [states.before(*pc), states.after(*pc)]
+            .into_iter()
+            .flatten()
+        {
+            max_locals = max_locals.max(state.locals.len());
+            max_stack = max_stack.max(state.stack_size());
+        }
+    }
+
+    Ok(ComputedFrames {
+        max_stack: max_stack as u16,
+        max_locals: max_locals as u16,
+        frames,
+        dead_code,
+    })
+}
+
+/// One entry per slot to the frame representation, without the trailing unused slots
+fn compress_locals(slots: &[VerificationType]) -> Vec<VerificationType> {
+    let mut locals = Vec::new();
+    let mut i = 0;
+    while i < slots.len() {
+        locals.push(slots[i].clone());
+        i += slots[i].size();
+    }
+    while locals.last() == Some(&VerificationType::Top) {
+        locals.pop();
+    }
+    locals
+}
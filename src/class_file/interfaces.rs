@@ -1,5 +1,6 @@
 use super::constant_pool::ConstantPool;
 use super::file_reader::FileReader;
+use super::file_writer::FileWriter;
 use anyhow::Result;
 
 use crate::print_debug as p;
@@ -21,6 +22,14 @@ impl Interfaces {
         Ok(Interfaces { interfaces })
     }
 
+    pub fn write(&self, file: &mut FileWriter) -> Result<()> {
+        file.write_count(self.interfaces.len(), "interfaces")?;
+        for i in &self.interfaces {
+            file.write_u2(*i);
+        }
+        Ok(())
+    }
+
     pub fn to_string(&self, constant_pool: &ConstantPool) -> String {
         let mut s = String::new();
         s.push_str("Interfaces:\n");
@@ -0,0 +1,794 @@
+use super::heap::{Object, ObjectRef};
+use super::{Invoke, Thrown, Value, Vm, MAX_CALL_DEPTH, NULL, NULL_POINTER_EXCEPTION};
+use crate::class_file::attributes::CodeAttribute;
+use crate::class_file::bytecode::ByteCode;
+use crate::class_file::constant_pool::{ConstantPool, FieldRef, Info};
+use crate::class_file::methods::Method;
+use crate::class_file::ClassFile;
+use crate::class_file::MethodDescriptor;
+use anyhow::{anyhow, Result};
+use std::rc::Rc;
+
+/// The local variables and operand stack of a method invocation
+struct Frame {
+    locals: Vec<Value>,
+    stack: Vec<Value>,
+}
+
+/// What to do after an instruction
+enum Step {
+    Next,
+    Jump(u32),
+    Return(Option<Value>),
+}
+
+impl Frame {
+    /// Longs and doubles take two local variable slots
+    fn new(max_locals: u16, args: Vec<Value>) -> Frame {
+        let mut locals = vec![Value::Int(0); max_locals as usize];
+        let mut slot = 0;
+        for arg in args {
+            if slot >= locals.len() {
+                locals.resize(slot + 1, Value::Int(0));
+            }
+            locals[slot] = arg;
+            slot += if arg.is_wide() { 2 } else { 1 };
+        }
+        Frame {
+            locals,
+            stack: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, value: Value) {
+        self.stack.push(value);
+    }
+
+    fn pop(&mut self) -> Result<Value> {
+        self.stack
+            .pop()
+            .ok_or_else(|| anyhow!("Operand stack underflow"))
+    }
+
+    fn pop_int(&mut self) -> Result<i32> {
+        match self.pop()? {
+            Value::Int(v) => Ok(v),
+            v => Err(anyhow!(
+                "Expected an int on the operand stack, found {:?}",
+                v
+            )),
+        }
+    }
+
+    fn pop_long(&mut self) -> Result<i64> {
+        match self.pop()? {
+            Value::Long(v) => Ok(v),
+            v => Err(anyhow!(
+                "Expected a long on the operand stack, found {:?}",
+                v
+            )),
+        }
+    }
+
+    fn pop_float(&mut self) -> Result<f32> {
+        match self.pop()? {
+            Value::Float(v) => Ok(v),
+            v => Err(anyhow!(
+                "Expected a float on the operand stack, found {:?}",
+                v
+            )),
+        }
+    }
+
+    fn pop_double(&mut self) -> Result<f64> {
+        match self.pop()? {
+            Value::Double(v) => Ok(v),
+            v => Err(anyhow!(
+                "Expected a double on the operand stack, found {:?}",
+                v
+            )),
+        }
+    }
+
+    fn pop_reference(&mut self) -> Result<Option<ObjectRef>> {
+        match self.pop()? {
+            Value::Reference(r) => Ok(r),
+            v => Err(anyhow!(
+                "Expected a reference on the operand stack, found {:?}",
+                v
+            )),
+        }
+    }
+
+    /// Pops the arguments of a call, the first one is pushed first
+    fn pop_args(&mut self, count: usize) -> Result<Vec<Value>> {
+        if count > self.stack.len() {
+            return Err(anyhow!("Operand stack underflow"));
+        }
+        Ok(self.stack.split_off(self.stack.len() - count))
+    }
+
+    fn load(&self, index: u16) -> Result<Value> {
+        self.locals
+            .get(index as usize)
+            .copied()
+            .ok_or_else(|| anyhow!("Local variable {} out of range", index))
+    }
+
+    fn store(&mut self, index: u16, value: Value) -> Result<()> {
+        let local = self
+            .locals
+            .get_mut(index as usize)
+            .ok_or_else(|| anyhow!("Local variable {} out of range", index))?;
+        *local = value;
+        Ok(())
+    }
+}
+
+/// Pops two operands of the type and pushes the result of the expression
+macro_rules! binary {
+    ($frame:ident, $pop:ident, $result:ident, |$a:ident, $b:ident| $e:expr) => {{
+        let $b = $frame.$pop()?;
+        let $a = $frame.$pop()?;
+        $frame.push(Value::$result($e));
+    }};
+}
+
+/// Pops one operand of the type and pushes the result of the expression
+macro_rules! unary {
+    ($frame:ident, $pop:ident, $result:ident, |$a:ident| $e:expr) => {{
+        let $a = $frame.$pop()?;
+        $frame.push(Value::$result($e));
+    }};
+}
+
+impl Vm {
+    pub(super) fn execute(
+        &mut self,
+        cf: &Rc<ClassFile>,
+        method: &Method,
+        args: Vec<Value>,
+    ) -> Result<Option<Value>> {
+        if self.depth >= MAX_CALL_DEPTH {
+            return Err(self.throw("java/lang/StackOverflowError", None));
+        }
+        self.depth += 1;
+        let result = self.run(cf, method, args);
+        self.depth -= 1;
+        result
+    }
+
+    fn run(
+        &mut self,
+        cf: &Rc<ClassFile>,
+        method: &Method,
+        args: Vec<Value>,
+    ) -> Result<Option<Value>> {
+        let cp = &cf.constant_pool;
+        let code = method.get_code().ok_or_else(|| {
+            anyhow!(
+                "{}.{} has no code",
+                cf.class_name(),
+                cp.get_to_string(method.name_index)
+            )
+        })?;
+        let instructions = code.instructions().collect::<Vec<_>>();
+        let position = |pc: u32| {
+            instructions
+                .binary_search_by_key(&pc, |(pc, _)| *pc)
+                .map_err(|_| anyhow!("Jump to pc {} which is not an instruction", pc))
+        };
+
+        let mut frame = Frame::new(code.max_locals(), args);
+        let mut index = 0;
+        loop {
+            let (pc, instruction) = *instructions
+                .get(index)
+                .ok_or_else(|| anyhow!("Execution fell off the end of the code"))?;
+            index = match self.step(cf, &mut frame, pc, instruction) {
+                Ok(Step::Next) => index + 1,
+                Ok(Step::Jump(target)) => position(target)?,
+                Ok(Step::Return(value)) => return Ok(value),
+                Err(e) => {
+                    let e = self.linkage_error(e);
+                    let Some(Thrown(exception)) = e.downcast_ref::<Thrown>() else {
+                        return Err(e);
+                    };
+                    let Some(handler) = self.find_handler(cp, code, pc, *exception) else {
+                        return Err(e);
+                    };
+                    frame.stack.clear();
+                    frame.push(Value::Reference(Some(*exception)));
+                    position(handler)?
+                }
+            };
+        }
+    }
+
+    /// The first entry of the exception table that covers the pc and catches the exception
+    fn find_handler(
+        &self,
+        cp: &ConstantPool,
+        code: &CodeAttribute,
+        pc: u32,
+        exception: ObjectRef,
+    ) -> Option<u32> {
+        let class = self.heap.get(exception).class_name();
+        code.exception_table()
+            .iter()
+            .find(|entry| {
+                (entry.start_pc as u32..entry.end_pc as u32).contains(&pc)
+                    && (entry.catch_type == 0
+                        || cp
+                            .class_name(entry.catch_type)
+                            .is_ok_and(|catch_type| self.is_assignable(&class, catch_type)))
+            })
+            .map(|entry| entry.handler_pc as u32)
+    }
+
+    fn step(
+        &mut self,
+        cf: &Rc<ClassFile>,
+        frame: &mut Frame,
+        pc: u32,
+        instruction: &ByteCode,
+    ) -> Result<Step> {
+        let cp = &cf.constant_pool;
+        let jump = |offset: i32| Step::Jump((pc as i64 + offset as i64) as u32);
+        let branch = |condition: bool, offset: i16| {
+            if condition {
+                jump(offset as i32)
+            } else {
+                Step::Next
+            }
+        };
+
+        match instruction {
+            ByteCode::Nop => {}
+            ByteCode::AConstNull => frame.push(NULL),
+            ByteCode::IConstn(v) => frame.push(Value::Int(*v as i32)),
+            ByteCode::LConstn(v) => frame.push(Value::Long(*v as i64)),
+            ByteCode::FConstn(v) => frame.push(Value::Float(*v as f32)),
+            ByteCode::DConstn(v) => frame.push(Value::Double(*v as f64)),
+            ByteCode::BiPush(v) => frame.push(Value::Int(*v as i32)),
+            ByteCode::SiPush(v) => frame.push(Value::Int(*v as i32)),
+            ByteCode::Ldc(index) => {
+                let value = self.constant(cp, *index as u16)?;
+                frame.push(value);
+            }
+            ByteCode::LdcW(index) | ByteCode::Ldc2W(index) => {
+                let value = self.constant(cp, *index)?;
+                frame.push(value);
+            }
+
+            ByteCode::ILoad(index)
+            | ByteCode::Lload(index)
+            | ByteCode::FLoad(index)
+            | ByteCode::DLoad(index)
+            | ByteCode::ALoad(index) => frame.push(frame.load(*index)?),
+            ByteCode::IStore(index)
+            | ByteCode::LStore(index)
+            | ByteCode::FStore(index)
+            | ByteCode::DStore(index)
+            | ByteCode::AStore(index) => {
+                let value = frame.pop()?;
+                frame.store(*index, value)?;
+            }
+
+            ByteCode::IALoad
+            | ByteCode::LALoad
+            | ByteCode::FALoad
+            | ByteCode::DALoad
+            | ByteCode::AALoad
+            | ByteCode::BALoad
+            | ByteCode::CALoad
+            | ByteCode::SALoad => {
+                let index = frame.pop_int()?;
+                let array = frame.pop_reference()?;
+                let element = self.array_elements(array, index)?.0[index as usize];
+                frame.push(element);
+            }
+            ByteCode::IAStore
+            | ByteCode::LAStore
+            | ByteCode::FAStore
+            | ByteCode::DAStore
+            | ByteCode::AAStore
+            | ByteCode::BAStore
+            | ByteCode::CAStore
+            | ByteCode::SAStore => {
+                let value = frame.pop()?;
+                let index = frame.pop_int()?;
+                let array = frame.pop_reference()?;
+                let (elements, component) = self.array_elements(array, index)?;
+                // the int is narrowed to the type of the array
+                elements[index as usize] = match (component.as_str(), value) {
+                    ("Z", Value::Int(v)) => Value::Int(v & 1),
+                    ("B", Value::Int(v)) => Value::Int(v as i8 as i32),
+                    ("C", Value::Int(v)) => Value::Int(v as u16 as i32),
+                    ("S", Value::Int(v)) => Value::Int(v as i16 as i32),
+                    _ => value,
+                };
+            }
+
+            ByteCode::Pop => {
+                frame.pop()?;
+            }
+            ByteCode::Pop2 => {
+                if !frame.pop()?.is_wide() {
+                    frame.pop()?;
+                }
+            }
+            ByteCode::Duplicate => {
+                let v1 = frame.pop()?;
+                frame.stack.extend([v1, v1]);
+            }
+            ByteCode::DupX1 => {
+                let v1 = frame.pop()?;
+                let v2 = frame.pop()?;
+                frame.stack.extend([v1, v2, v1]);
+            }
+            ByteCode::DupX2 => {
+                let v1 = frame.pop()?;
+                let v2 = frame.pop()?;
+                if v2.is_wide() {
+                    frame.stack.extend([v1, v2, v1]);
+                } else {
+                    let v3 = frame.pop()?;
+                    frame.stack.extend([v1, v3, v2, v1]);
+                }
+            }
+            ByteCode::Dup2 => {
+                let v1 = frame.pop()?;
+                if v1.is_wide() {
+                    frame.stack.extend([v1, v1]);
+                } else {
+                    let v2 = frame.pop()?;
+                    frame.stack.extend([v2, v1, v2, v1]);
+                }
+            }
+            ByteCode::Dup2X1 => {
+                let v1 = frame.pop()?;
+                let v2 = frame.pop()?;
+                if v1.is_wide() {
+                    frame.stack.extend([v1, v2, v1]);
+                } else {
+                    let v3 = frame.pop()?;
+                    frame.stack.extend([v2, v1, v3, v2, v1]);
+                }
+            }
+            ByteCode::Dup2X2 => {
+                let v1 = frame.pop()?;
+                let v2 = frame.pop()?;
+                match (v1.is_wide(), v2.is_wide()) {
+                    (true, true) => frame.stack.extend([v1, v2, v1]),
+                    (true, false) => {
+                        let v3 = frame.pop()?;
+                        frame.stack.extend([v1, v3, v2, v1]);
+                    }
+                    _ => {
+                        let v3 = frame.pop()?;
+                        if v3.is_wide() {
+                            frame.stack.extend([v2, v1, v3, v2, v1]);
+                        } else {
+                            let v4 = frame.pop()?;
+                            frame.stack.extend([v2, v1, v4, v3, v2, v1]);
+                        }
+                    }
+                }
+            }
+            ByteCode::Swap => {
+                let v1 = frame.pop()?;
+                let v2 = frame.pop()?;
+                frame.stack.extend([v1, v2]);
+            }
+
+            ByteCode::IAdd => binary!(frame, pop_int, Int, |a, b| a.wrapping_add(b)),
+            ByteCode::LAdd => binary!(frame, pop_long, Long, |a, b| a.wrapping_add(b)),
+            ByteCode::FAdd => binary!(frame, pop_float, Float, |a, b| a + b),
+            ByteCode::DAdd => binary!(frame, pop_double, Double, |a, b| a + b),
+            ByteCode::ISub => binary!(frame, pop_int, Int, |a, b| a.wrapping_sub(b)),
+            ByteCode::LSub => binary!(frame, pop_long, Long, |a, b| a.wrapping_sub(b)),
+            ByteCode::FSub => binary!(frame, pop_float, Float, |a, b| a - b),
+            ByteCode::DSub => binary!(frame, pop_double, Double, |a, b| a - b),
+            ByteCode::IMul => binary!(frame, pop_int, Int, |a, b| a.wrapping_mul(b)),
+            ByteCode::LMul => binary!(frame, pop_long, Long, |a, b| a.wrapping_mul(b)),
+            ByteCode::FMul => binary!(frame, pop_float, Float, |a, b| a * b),
+            ByteCode::DMul => binary!(frame, pop_double, Double, |a, b| a * b),
+            ByteCode::IDiv | ByteCode::IRem => {
+                let b = frame.pop_int()?;
+                let a = frame.pop_int()?;
+                if b == 0 {
+                    return Err(self.throw(ARITHMETIC_EXCEPTION, Some(DIVISION_BY_ZERO.into())));
+                }
+                let result = match instruction {
+                    ByteCode::IDiv => a.wrapping_div(b),
+                    _ => a.wrapping_rem(b),
+                };
+                frame.push(Value::Int(result));
+            }
+            ByteCode::LDiv | ByteCode::LRem => {
+                let b = frame.pop_long()?;
+                let a = frame.pop_long()?;
+                if b == 0 {
+                    return Err(self.throw(ARITHMETIC_EXCEPTION, Some(DIVISION_BY_ZERO.into())));
+                }
+                let result = match instruction {
+                    ByteCode::LDiv => a.wrapping_div(b),
+                    _ => a.wrapping_rem(b),
+                };
+                frame.push(Value::Long(result));
+            }
+            ByteCode::FDiv => binary!(frame, pop_float, Float, |a, b| a / b),
+            ByteCode::DDiv => binary!(frame, pop_double, Double, |a, b| a / b),
+            ByteCode::FRem => binary!(frame, pop_float, Float, |a, b| a % b),
+            ByteCode::DRem => binary!(frame, pop_double, Double, |a, b| a % b),
+            ByteCode::INeg => unary!(frame, pop_int, Int, |a| a.wrapping_neg()),
+            ByteCode::LNeg => unary!(frame, pop_long, Long, |a| a.wrapping_neg()),
+            ByteCode::FNeg => unary!(frame, pop_float, Float, |a| -a),
+            ByteCode::DNeg => unary!(frame, pop_double, Double, |a| -a),
+
+            // only the low 5 (int) or 6 (long) bits of the shift distance are used
+            ByteCode::IShl => binary!(frame, pop_int, Int, |a, b| a.wrapping_shl(b as u32)),
+            ByteCode::IShr => binary!(frame, pop_int, Int, |a, b| a.wrapping_shr(b as u32)),
+            ByteCode::IUShr => {
+                binary!(
+                    frame,
+                    pop_int,
+                    Int,
+                    |a, b| (a as u32).wrapping_shr(b as u32) as i32
+                )
+            }
+            ByteCode::LShl | ByteCode::LShr | ByteCode::LUShr => {
+                let b = frame.pop_int()? as u32;
+                let a = frame.pop_long()?;
+                let result = match instruction {
+                    ByteCode::LShl => a.wrapping_shl(b),
+                    ByteCode::LShr => a.wrapping_shr(b),
+                    _ => (a as u64).wrapping_shr(b) as i64,
+                };
+                frame.push(Value::Long(result));
+            }
+            ByteCode::IAnd => binary!(frame, pop_int, Int, |a, b| a & b),
+            ByteCode::LAnd => binary!(frame, pop_long, Long, |a, b| a & b),
+            ByteCode::IOr => binary!(frame, pop_int, Int, |a, b| a | b),
+            ByteCode::LOr => binary!(frame, pop_long, Long, |a, b| a | b),
+            ByteCode::IXor => binary!(frame, pop_int, Int, |a, b| a ^ b),
+            ByteCode::LXor => binary!(frame, pop_long, Long, |a, b| a ^ b),
+            ByteCode::IInc(index, constant) => match frame.load(*index)? {
+                Value::Int(v) => {
+                    frame.store(*index, Value::Int(v.wrapping_add(*constant as i32)))?
+                }
+                v => return Err(anyhow!("iinc of local variable {} holding {:?}", index, v)),
+            },
+
+            // rust's `as` saturates and turns NaN into 0 like the JVM does
+            ByteCode::I2L => unary!(frame, pop_int, Long, |a| a as i64),
+            ByteCode::I2F => unary!(frame, pop_int, Float, |a| a as f32),
+            ByteCode::I2D => unary!(frame, pop_int, Double, |a| a as f64),
+            ByteCode::L2i => unary!(frame, pop_long, Int, |a| a as i32),
+            ByteCode::L2F => unary!(frame, pop_long, Float, |a| a as f32),
+            ByteCode::L2D => unary!(frame, pop_long, Double, |a| a as f64),
+            ByteCode::F2I => unary!(frame, pop_float, Int, |a| a as i32),
+            ByteCode::F2L => unary!(frame, pop_float, Long, |a| a as i64),
+            ByteCode::F2D => unary!(frame, pop_float, Double, |a| a as f64),
+            ByteCode::D2I => unary!(frame, pop_double, Int, |a| a as i32),
+            ByteCode::D2L => unary!(frame, pop_double, Long, |a| a as i64),
+            ByteCode::D2F => unary!(frame, pop_double, Float, |a| a as f32),
+            ByteCode::I2B => unary!(frame, pop_int, Int, |a| a as i8 as i32),
+            ByteCode::I2C => unary!(frame, pop_int, Int, |a| a as u16 as i32),
+            ByteCode::I2S => unary!(frame, pop_int, Int, |a| a as i16 as i32),
+
+            ByteCode::Lcmp => binary!(frame, pop_long, Int, |a, b| a.cmp(&b) as i32),
+            ByteCode::FCmpl => binary!(frame, pop_float, Int, |a, b| compare(a, b, -1)),
+            ByteCode::FCmpg => binary!(frame, pop_float, Int, |a, b| compare(a, b, 1)),
+            ByteCode::DCmpl => binary!(frame, pop_double, Int, |a, b| compare(a, b, -1)),
+            ByteCode::DCmpg => binary!(frame, pop_double, Int, |a, b| compare(a, b, 1)),
+
+            ByteCode::Ifeq(offset) => return Ok(branch(frame.pop_int()? == 0, *offset)),
+            ByteCode::Ifne(offset) => return Ok(branch(frame.pop_int()? != 0, *offset)),
+            ByteCode::Iflt(offset) => return Ok(branch(frame.pop_int()? < 0, *offset)),
+            ByteCode::Ifge(offset) => return Ok(branch(frame.pop_int()? >= 0, *offset)),
+            ByteCode::Ifgt(offset) => return Ok(branch(frame.pop_int()? > 0, *offset)),
+            ByteCode::Ifle(offset) => return Ok(branch(frame.pop_int()? <= 0, *offset)),
+            ByteCode::IfICmpEq(offset)
+            | ByteCode::IfICmpNe(offset)
+            | ByteCode::IfICmpLt(offset)
+            | ByteCode::IfICmpGe(offset)
+            | ByteCode::IfICmpGt(offset)
+            | ByteCode::IfICmpLe(offset) => {
+                let b = frame.pop_int()?;
+                let a = frame.pop_int()?;
+                let condition = match instruction {
+                    ByteCode::IfICmpEq(_) => a == b,
+                    ByteCode::IfICmpNe(_) => a != b,
+                    ByteCode::IfICmpLt(_) => a < b,
+                    ByteCode::IfICmpGe(_) => a >= b,
+                    ByteCode::IfICmpGt(_) => a > b,
+                    _ => a <= b,
+                };
+                return Ok(branch(condition, *offset));
+            }
+            ByteCode::IfACmpEq(offset) | ByteCode::IfACmpNe(offset) => {
+                let b = frame.pop_reference()?;
+                let a = frame.pop_reference()?;
+                let equal = matches!(instruction, ByteCode::IfACmpEq(_));
+                return Ok(branch((a == b) == equal, *offset));
+            }
+            ByteCode::IfNull(offset) => {
+                return Ok(branch(frame.pop_reference()?.is_none(), *offset))
+            }
+            ByteCode::IfNonNull(offset) => {
+                return Ok(branch(frame.pop_reference()?.is_some(), *offset))
+            }
+            ByteCode::Goto(offset) => return Ok(jump(*offset as i32)),
+            ByteCode::GotoW(offset) => return Ok(jump(*offset)),
+            ByteCode::TableSwitch(switch) => {
+                let key = frame.pop_int()?;
+                let offset = match key.checked_sub(switch.low) {
+                    Some(i) if key <= switch.high => switch.offsets[i as usize],
+                    _ => switch.default,
+                };
+                return Ok(jump(offset));
+            }
+            ByteCode::LookupSwitch(switch) => {
+                let key = frame.pop_int()?;
+                let offset = switch
+                    .pairs
+                    .iter()
+                    .find(|(value, _)| *value == key)
+                    .map_or(switch.default, |(_, offset)| *offset);
+                return Ok(jump(offset));
+            }
+
+            ByteCode::IReturn
+            | ByteCode::LReturn
+            | ByteCode::FReturn
+            | ByteCode::DReturn
+            | ByteCode::AReturn => return Ok(Step::Return(Some(frame.pop()?))),
+            ByteCode::Return => return Ok(Step::Return(None)),
+
+            ByteCode::GetStatic(index) => {
+                let value = self.get_static(&cp.field_ref(*index)?)?;
+                frame.push(value);
+            }
+            ByteCode::PutStatic(index) => {
+                let value = frame.pop()?;
+                self.put_static(&cp.field_ref(*index)?, value)?;
+            }
+            ByteCode::GetField(index) => {
+                let field = cp.field_ref(*index)?;
+                let object = frame.pop_reference()?;
+                let fields = self.instance_fields(object, &field, "read")?;
+                let value = fields.get(&field.name).copied();
+                frame.push(value.unwrap_or(Value::default_for(&field.descriptor)));
+            }
+            ByteCode::PutField(index) => {
+                let field = cp.field_ref(*index)?;
+                let value = frame.pop()?;
+                let object = frame.pop_reference()?;
+                let fields = self.instance_fields(object, &field, "assign")?;
+                fields.insert(field.name, value);
+            }
+
+            ByteCode::InvokeStatic(index)
+            | ByteCode::InvokeSpecial(index)
+            | ByteCode::InvokeVirtual(index)
+            | ByteCode::InvokeInterface(index, _) => {
+                let method = cp.method_ref(*index)?;
+                let (kind, receiver) = match instruction {
+                    ByteCode::InvokeStatic(_) => (Invoke::Static, 0),
+                    ByteCode::InvokeSpecial(_) => (Invoke::Special, 1),
+                    _ => (Invoke::Virtual, 1),
+                };
+                let parameters = MethodDescriptor::parse(&method.descriptor)?.parameters;
+                let args = frame.pop_args(parameters.len() + receiver)?;
+                if let Some(value) = self.invoke(kind, &method, args)? {
+                    frame.push(value);
+                }
+            }
+            ByteCode::InvokeDynamic(index) => {
+                let call_site = cp.invoke_dynamic(*index)?;
+                let parameters = MethodDescriptor::parse(&call_site.descriptor)?.parameters;
+                let args = frame.pop_args(parameters.len())?;
+                let value = self.invoke_dynamic(cf, &call_site, args)?;
+                frame.push(value);
+            }
+
+            ByteCode::New(index) => {
+                let class = cp.class_name(*index)?;
+                self.initialize(class)?;
+                let object = self.new_instance(class)?;
+                frame.push(Value::Reference(Some(object)));
+            }
+            ByteCode::NewArray(atype) => {
+                let component = match atype {
+                    4 => "Z",
+                    5 => "C",
+                    6 => "F",
+                    7 => "D",
+                    8 => "B",
+                    9 => "S",
+                    10 => "I",
+                    11 => "J",
+                    _ => return Err(anyhow!("Invalid newarray type {}", atype)),
+                };
+                let length = frame.pop_int()?;
+                let array = self.new_array(&format!("[{}", component), &[length])?;
+                frame.push(Value::Reference(Some(array)));
+            }
+            ByteCode::ANewArray(index) => {
+                let class = cp.class_name(*index)?;
+                let descriptor = match class.starts_with('[') {
+                    true => format!("[{}", class),
+                    false => format!("[L{};", class),
+                };
+                let length = frame.pop_int()?;
+                let array = self.new_array(&descriptor, &[length])?;
+                frame.push(Value::Reference(Some(array)));
+            }
+            ByteCode::MultiANewArray(index, dimensions) => {
+                let descriptor = cp.class_name(*index)?;
+                let lengths = frame
+                    .pop_args(*dimensions as usize)?
+                    .into_iter()
+                    .map(|v| match v {
+                        Value::Int(length) => Ok(length),
+                        v => Err(anyhow!("Expected an int array length, found {:?}", v)),
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                let array = self.new_array(descriptor, &lengths)?;
+                frame.push(Value::Reference(Some(array)));
+            }
+            ByteCode::ArrayLength => match frame.pop_reference()? {
+                Some(array) => match self.heap.get(array) {
+                    Object::Array { elements, .. } => frame.push(Value::Int(elements.len() as i32)),
+                    object => return Err(anyhow!("arraylength of {:?}", object)),
+                },
+                None => return Err(self.throw(NULL_POINTER_EXCEPTION, None)),
+            },
+            ByteCode::Athrow => match frame.pop_reference()? {
+                Some(exception) => return Err(Thrown(exception).into()),
+                None => return Err(self.throw(NULL_POINTER_EXCEPTION, None)),
+            },
+            ByteCode::CheckCast(index) => {
+                let target = cp.class_name(*index)?;
+                if let Some(Value::Reference(Some(object))) = frame.stack.last() {
+                    let class = self.heap.get(*object).class_name();
+                    if !self.is_assignable(&class, target) {
+                        let message = format!(
+                            "class {} cannot be cast to class {}",
+                            class.replace('/', "."),
+                            target.replace('/', ".")
+                        );
+                        return Err(self.throw("java/lang/ClassCastException", Some(message)));
+                    }
+                }
+            }
+            ByteCode::InstanceOf(index) => {
+                let target = cp.class_name(*index)?;
+                let is_instance = match frame.pop_reference()? {
+                    Some(object) => {
+                        let class = self.heap.get(object).class_name();
+                        self.is_assignable(&class, target)
+                    }
+                    None => false,
+                };
+                frame.push(Value::Int(is_instance as i32));
+            }
+            // there is a single thread
+            ByteCode::MonitorEnter | ByteCode::MonitorExit => {
+                if frame.pop_reference()?.is_none() {
+                    return Err(self.throw(NULL_POINTER_EXCEPTION, None));
+                }
+            }
+
+            ByteCode::Jsr(_) | ByteCode::JsrW(_) | ByteCode::Ret(_) | ByteCode::Generic(_) => {
+                return Err(anyhow!(
+                    "{} at pc {} is not supported by the interpreter",
+                    instruction.to_string(),
+                    pc
+                ))
+            }
+        }
+        Ok(Step::Next)
+    }
+
+    /// The value `ldc` pushes for the constant pool entry
+    fn constant(&mut self, cp: &ConstantPool, index: u16) -> Result<Value> {
+        let value = match cp.get(index) {
+            Info::IntegerInfo(v) => Value::Int(*v),
+            Info::FloatInfo(v) => Value::Float(*v),
+            Info::LongInfo(v) => Value::Long(*v),
+            Info::DoubleInfo(v) => Value::Double(*v),
+            Info::StringInfo(_) => Value::Reference(Some(self.intern(cp.string(index)?))),
+            info => {
+                return Err(anyhow!(
+                    "ldc of {:?} is not supported by the interpreter",
+                    info
+                ))
+            }
+        };
+        Ok(value)
+    }
+
+    /// The elements and component type of an array after the null and bounds checks
+    fn array_elements(
+        &mut self,
+        array: Option<ObjectRef>,
+        index: i32,
+    ) -> Result<(&mut Vec<Value>, String)> {
+        let Some(array) = array else {
+            return Err(self.throw(NULL_POINTER_EXCEPTION, None));
+        };
+        let length = match self.heap.get(array) {
+            Object::Array { elements, .. } => elements.len(),
+            object => return Err(anyhow!("Expected an array, found {:?}", object)),
+        };
+        if index < 0 || index as usize >= length {
+            let message = format!("Index {} out of bounds for length {}", index, length);
+            return Err(self.throw("java/lang/ArrayIndexOutOfBoundsException", Some(message)));
+        }
+        match self.heap.get_mut(array) {
+            Object::Array {
+                elements,
+                component,
+            } => Ok((elements, component.clone())),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Allocates an array (of arrays for more than one length) of the type, e.g. `[[I`
+    fn new_array(&mut self, descriptor: &str, lengths: &[i32]) -> Result<ObjectRef> {
+        if let Some(length) = lengths.iter().find(|length| **length < 0) {
+            let message = length.to_string();
+            return Err(self.throw("java/lang/NegativeArraySizeException", Some(message)));
+        }
+        let component = &descriptor[1..];
+        let elements = match lengths {
+            [length] => vec![Value::default_for(component); *length as usize],
+            [length, rest @ ..] => (0..*length)
+                .map(|_| Ok(Value::Reference(Some(self.new_array(component, rest)?))))
+                .collect::<Result<Vec<_>>>()?,
+            [] => return Err(anyhow!("Array {} without dimensions", descriptor)),
+        };
+        Ok(self.heap.allocate(Object::Array {
+            component: component.to_string(),
+            elements,
+        }))
+    }
+
+    /// The fields of the object for `getfield` and `putfield`
+    fn instance_fields(
+        &mut self,
+        object: Option<ObjectRef>,
+        field: &FieldRef,
+        access: &str,
+    ) -> Result<&mut std::collections::HashMap<String, Value>> {
+        let Some(object) = object else {
+            let message = format!(
+                "Cannot {} field \"{}\" because the object is null",
+                access, field.name
+            );
+            return Err(self.throw(NULL_POINTER_EXCEPTION, Some(message)));
+        };
+        match self.heap.get_mut(object) {
+            Object::Instance { fields, .. } => Ok(fields),
+            object => Err(anyhow!(
+                "Field {} of {:?} which is not an instance",
+                field,
+                object
+            )),
+        }
+    }
+}
+
+const ARITHMETIC_EXCEPTION: &str = "java/lang/ArithmeticException";
+const DIVISION_BY_ZERO: &str = "/ by zero";
+
+/// `fcmp` and `dcmp`, `nan` is the result if either value is NaN
+fn compare<T: PartialOrd>(a: T, b: T, nan: i32) -> i32 {
+    match a.partial_cmp(&b) {
+        Some(ordering) => ordering as i32,
+        None => nan,
+    }
+}
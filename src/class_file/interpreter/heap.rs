@@ -0,0 +1,67 @@
+use super::Value;
+use std::collections::HashMap;
+
+/// Index of an object in the `Heap`
+pub type ObjectRef = usize;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Object {
+    /// An instance of a class with its instance fields by name
+    Instance {
+        class: String,
+        fields: HashMap<String, Value>,
+    },
+    /// `component` is the field descriptor of the elements, e.g. `I` or `[Ljava/lang/String;`
+    Array {
+        component: String,
+        elements: Vec<Value>,
+    },
+    /// A `java.lang.String`, kept as a rust string instead of a char array
+    String(String),
+}
+
+impl Object {
+    /// Binary name of the class of the object, the descriptor for arrays (e.g. `[I`)
+    pub fn class_name(&self) -> String {
+        match self {
+            Object::Instance { class, .. } => class.clone(),
+            Object::Array { component, .. } => format!("[{}", component),
+            Object::String(_) => "java/lang/String".to_string(),
+        }
+    }
+}
+
+/// Every object allocated by the interpreter. There is no garbage collection, objects
+/// live as long as the heap.
+#[derive(Debug, Default)]
+pub struct Heap {
+    objects: Vec<Object>,
+}
+
+impl Heap {
+    pub fn new() -> Heap {
+        Heap::default()
+    }
+
+    pub fn allocate(&mut self, object: Object) -> ObjectRef {
+        self.objects.push(object);
+        self.objects.len() - 1
+    }
+
+    pub fn get(&self, reference: ObjectRef) -> &Object {
+        &self.objects[reference]
+    }
+
+    pub fn get_mut(&mut self, reference: ObjectRef) -> &mut Object {
+        &mut self.objects[reference]
+    }
+
+    /// Number of objects allocated so far
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+}
@@ -0,0 +1,456 @@
+//! A bytecode interpreter that runs methods of parsed class files.
+//!
+//! Each invocation gets a frame with its local variables and operand stack and the
+//! instructions are executed one at a time. Objects live on a simple `Heap` and exceptions
+//! unwind the frames until a handler in an exception table catches them.
+//!
+//! Classes are loaded and linked by a `ClassLoader`, which resolves the fields and methods
+//! the instructions reference and selects the methods virtual calls run. Only application
+//! classes are interpreted: the
+//! classes of the JDK (`java/`, `javax/`, `jdk/` and `sun/`) need native methods and a lot
+//! of the runtime to initialize, so the interpreter only loads them for their hierarchy
+//! and implements the few methods simple programs use itself (see `native.rs`), e.g.
+//! `System.out.println`, string concatenation, `String.length` and the constructors and
+//! `getMessage` of exceptions.
+
+mod execute;
+mod heap;
+mod native;
+
+pub use heap::{Heap, Object, ObjectRef};
+
+use super::runtime::{ClassLoader, LinkageError};
+use super::{ClassFile, ClassPath, ConstantValue, FieldRef, MethodRef};
+use anyhow::{anyhow, Result};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::rc::Rc;
+
+/// Deepest call stack before a `StackOverflowError`, each Java call is a rust call
+const MAX_CALL_DEPTH: usize = 512;
+
+/// The field of `java.lang.Throwable` holding the message
+const DETAIL_MESSAGE: &str = "detailMessage";
+
+/// A value on the operand stack, in a local variable or in a field
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value {
+    /// `boolean`, `byte`, `char`, `short` and `int`
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    /// `None` is null
+    Reference(Option<ObjectRef>),
+}
+
+pub const NULL: Value = Value::Reference(None);
+
+impl Value {
+    /// The initial value of a field or array element of the type (JVMS 2.3)
+    pub fn default_for(descriptor: &str) -> Value {
+        match descriptor.as_bytes().first() {
+            Some(b'J') => Value::Long(0),
+            Some(b'F') => Value::Float(0.0),
+            Some(b'D') => Value::Double(0.0),
+            Some(b'L') | Some(b'[') => NULL,
+            _ => Value::Int(0),
+        }
+    }
+
+    /// Longs and doubles take two local variable slots and count as two values for
+    /// `pop2` and the `dup2` instructions
+    pub fn is_wide(&self) -> bool {
+        matches!(self, Value::Long(_) | Value::Double(_))
+    }
+}
+
+/// An exception no handler caught
+#[derive(Debug, Clone, PartialEq)]
+pub struct JavaException {
+    /// Binary name of the class of the exception, e.g. `java/lang/ArithmeticException`
+    pub class: String,
+    pub message: Option<String>,
+    pub object: ObjectRef,
+}
+
+/// Formatted like `Throwable.toString`, e.g. `java.lang.ArithmeticException: / by zero`
+impl fmt::Display for JavaException {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.class.replace('/', "."))?;
+        if let Some(message) = &self.message {
+            write!(f, ": {}", message)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for JavaException {}
+
+/// An exception unwinding the frames of the interpreter
+#[derive(Debug)]
+struct Thrown(ObjectRef);
+
+impl fmt::Display for Thrown {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Exception object {}", self.0)
+    }
+}
+
+impl std::error::Error for Thrown {}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Invoke {
+    /// `invokestatic`
+    Static,
+    /// `invokespecial`, no dispatch on the class of the receiver
+    Special,
+    /// `invokevirtual` and `invokeinterface`
+    Virtual,
+}
+
+pub struct Vm {
+    loader: ClassLoader,
+    heap: Heap,
+    /// Static fields by declaring class and name
+    statics: HashMap<(String, String), Value>,
+    /// Classes whose initialization started
+    initialized: HashSet<String>,
+    /// String literals, the same literal is the same object
+    strings: HashMap<String, ObjectRef>,
+    system_out: Option<ObjectRef>,
+    output: String,
+    depth: usize,
+}
+
+impl Vm {
+    pub fn new(class_path: ClassPath) -> Vm {
+        Vm {
+            loader: ClassLoader::new(class_path),
+            heap: Heap::new(),
+            statics: HashMap::new(),
+            initialized: HashSet::new(),
+            strings: HashMap::new(),
+            system_out: None,
+            output: String::new(),
+            depth: 0,
+        }
+    }
+
+    pub fn class_loader(&self) -> &ClassLoader {
+        &self.loader
+    }
+
+    pub fn heap(&self) -> &Heap {
+        &self.heap
+    }
+
+    /// What the program printed to `System.out` so far
+    pub fn output(&self) -> &str {
+        &self.output
+    }
+
+    pub fn take_output(&mut self) -> String {
+        std::mem::take(&mut self.output)
+    }
+
+    /// Runs a static method, initializing its class first. An exception that is not
+    /// caught is returned as a `JavaException` error.
+    pub fn invoke_static(
+        &mut self,
+        class: &str,
+        name: &str,
+        descriptor: &str,
+        args: Vec<Value>,
+    ) -> Result<Option<Value>> {
+        let method = MethodRef {
+            owner: class.to_string(),
+            name: name.to_string(),
+            descriptor: descriptor.to_string(),
+            is_interface: false,
+        };
+        self.invoke(Invoke::Static, &method, args).map_err(|e| {
+            match self.linkage_error(e).downcast::<Thrown>() {
+                Ok(Thrown(exception)) => self.uncaught(exception).into(),
+                Err(e) => e,
+            }
+        })
+    }
+
+    /// Runs `public static void main(String[] args)` of the class without arguments
+    pub fn run_main(&mut self, class: &str) -> Result<()> {
+        let args = self.heap.allocate(Object::Array {
+            component: "Ljava/lang/String;".to_string(),
+            elements: Vec::new(),
+        });
+        let args = vec![Value::Reference(Some(args))];
+        self.invoke_static(class, "main", "([Ljava/lang/String;)V", args)?;
+        Ok(())
+    }
+
+    /// The content of a `java.lang.String` reference
+    pub fn string(&self, value: &Value) -> Option<&str> {
+        match value {
+            Value::Reference(Some(reference)) => match self.heap.get(*reference) {
+                Object::String(s) => Some(s),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn uncaught(&self, exception: ObjectRef) -> JavaException {
+        let object = self.heap.get(exception);
+        let message = match object {
+            Object::Instance { fields, .. } => fields.get(DETAIL_MESSAGE),
+            _ => None,
+        };
+        JavaException {
+            class: object.class_name(),
+            message: message.and_then(|m| self.string(m)).map(|m| m.to_string()),
+            object: exception,
+        }
+    }
+
+    /// Creates an exception to return as the error of an instruction
+    fn throw(&mut self, class: &str, message: Option<String>) -> anyhow::Error {
+        let message = match message {
+            Some(message) => Value::Reference(Some(self.heap.allocate(Object::String(message)))),
+            None => NULL,
+        };
+        let fields = HashMap::from([(DETAIL_MESSAGE.to_string(), message)]);
+        let exception = self.heap.allocate(Object::Instance {
+            class: class.to_string(),
+            fields,
+        });
+        Thrown(exception).into()
+    }
+
+    /// Turns a `LinkageError` of resolution into the error the JVM throws
+    fn linkage_error(&mut self, e: anyhow::Error) -> anyhow::Error {
+        match e.downcast_ref::<LinkageError>() {
+            Some(error) => {
+                let message = error.message().to_string();
+                self.throw(error.class(), Some(message))
+            }
+            None => e,
+        }
+    }
+
+    fn intern(&mut self, s: &str) -> ObjectRef {
+        if let Some(reference) = self.strings.get(s) {
+            return *reference;
+        }
+        let reference = self.heap.allocate(Object::String(s.to_string()));
+        self.strings.insert(s.to_string(), reference);
+        reference
+    }
+
+    fn load(&self, class: &str) -> Result<Rc<ClassFile>> {
+        self.loader.class_path().load(class)
+    }
+
+    /// Runs the static initializer of the class after those of its super class and the
+    /// super interfaces with default methods (JVMS 5.5)
+    fn initialize(&mut self, class: &str) -> Result<()> {
+        if is_system_class(class) || !self.initialized.insert(class.to_string()) {
+            return Ok(());
+        }
+        let runtime_class = self.loader.load(class)?;
+        for dependency in runtime_class.initialization_dependencies() {
+            let name = self.loader.class(*dependency).name.clone();
+            self.initialize(&name)?;
+        }
+        let cf = &runtime_class.class_file;
+        let cp = &cf.constant_pool;
+        for field in cf.fields.fields.iter().filter(|f| f.is_static()) {
+            // constant fields get their value from the class file, not `<clinit>`
+            let value = match field.constant_value(cp).transpose()? {
+                Some(ConstantValue::Int(v)) => Value::Int(v),
+                Some(ConstantValue::Long(v)) => Value::Long(v),
+                Some(ConstantValue::Float(v)) => Value::Float(v),
+                Some(ConstantValue::Double(v)) => Value::Double(v),
+                Some(ConstantValue::String(s)) => Value::Reference(Some(self.intern(&s))),
+                None => Value::default_for(&cp.get_to_string(field.descriptor_index)),
+            };
+            let name = cp.get_to_string(field.name_index);
+            self.statics.insert((class.to_string(), name), value);
+        }
+        if let Some(clinit) = cf.find_method("<clinit>", "()V") {
+            self.execute(cf, clinit, Vec::new())?;
+        }
+        Ok(())
+    }
+
+    /// Allocates an object with the instance fields of the class and its super classes
+    fn new_instance(&mut self, class: &str) -> Result<ObjectRef> {
+        let mut fields = HashMap::new();
+        let mut current = Some(class.to_string());
+        while let Some(c) = current.filter(|c| !is_system_class(c)) {
+            let cf = self.load(&c)?;
+            let cp = &cf.constant_pool;
+            for field in cf.fields.fields.iter().filter(|f| !f.is_static()) {
+                let descriptor = cp.get_to_string(field.descriptor_index);
+                fields
+                    .entry(cp.get_to_string(field.name_index))
+                    .or_insert(Value::default_for(&descriptor));
+            }
+            current = cf.super_class_name();
+        }
+        Ok(self.heap.allocate(Object::Instance {
+            class: class.to_string(),
+            fields,
+        }))
+    }
+
+    fn get_static(&mut self, field: &FieldRef) -> Result<Value> {
+        if is_system_class(&field.owner) {
+            return self.native_static(&field.owner, &field.name);
+        }
+        let owner = self.static_field_owner(field)?;
+        self.initialize(&owner)?;
+        let value = self.statics.get(&(owner, field.name.clone())).copied();
+        Ok(value.unwrap_or(Value::default_for(&field.descriptor)))
+    }
+
+    fn put_static(&mut self, field: &FieldRef, value: Value) -> Result<()> {
+        if is_system_class(&field.owner) {
+            return Err(anyhow!("Can't set the JDK field {}", field));
+        }
+        let owner = self.static_field_owner(field)?;
+        self.initialize(&owner)?;
+        self.statics.insert((owner, field.name.clone()), value);
+        Ok(())
+    }
+
+    /// The class declaring a static field, it can be a super class or super interface
+    /// of the class the instruction names
+    fn static_field_owner(&self, field: &FieldRef) -> Result<String> {
+        let resolved = self
+            .loader
+            .resolve_field(&field.owner, &field.name, &field.descriptor)?;
+        let owner = self.loader.class(resolved.class);
+        if !owner.is_static_field(resolved.index) {
+            let message = format!("Expected static field {}.{}", owner.name, field.name);
+            return Err(LinkageError::IncompatibleClassChange(message).into());
+        }
+        Ok(owner.name.clone())
+    }
+
+    /// Resolves the method and runs it, for `Invoke::Virtual` the method selected for the
+    /// class of the receiver. Methods of JDK classes run if the interpreter implements them.
+    fn invoke(
+        &mut self,
+        kind: Invoke,
+        method: &MethodRef,
+        args: Vec<Value>,
+    ) -> Result<Option<Value>> {
+        let receiver = match (kind, args.first()) {
+            (Invoke::Static, _) => None,
+            (_, Some(Value::Reference(Some(receiver)))) => Some(*receiver),
+            (_, Some(Value::Reference(None))) => {
+                let message = format!(
+                    "Cannot invoke \"{}.{}()\"",
+                    method.owner.replace('/', "."),
+                    method.name
+                );
+                return Err(self.throw(NULL_POINTER_EXCEPTION, Some(message)));
+            }
+            _ => return Err(anyhow!("Missing receiver for {}", method)),
+        };
+
+        let (owner, name, descriptor) = (&method.owner, &method.name, &method.descriptor);
+        let resolved = match method.is_interface {
+            true => self
+                .loader
+                .resolve_interface_method(owner, name, descriptor)?,
+            false => self.loader.resolve_method(owner, name, descriptor)?,
+        };
+        let selected = match (kind, receiver) {
+            (Invoke::Virtual, Some(receiver)) => {
+                let receiver_class = self.heap.get(receiver).class_name();
+                self.loader.select_method(&receiver_class, resolved)?
+            }
+            _ => resolved,
+        };
+        let class = self.loader.class(selected.class);
+        if kind == Invoke::Static {
+            self.initialize(&class.name)?;
+        }
+        if is_system_class(&class.name) {
+            return self
+                .call_native(&class.name, name, descriptor, &args)
+                .unwrap_or_else(|| {
+                    Err(anyhow!(
+                        "{} is not supported by the interpreter",
+                        self.loader.method_string(selected)
+                    ))
+                });
+        }
+        let declared = class.method(selected.index);
+        if declared.get_code().is_none() {
+            let message = self.loader.method_string(selected);
+            return Err(match declared.is_abstract() {
+                true => LinkageError::AbstractMethod(message).into(),
+                false => anyhow!("{} has no code", message),
+            });
+        }
+        self.execute(&class.class_file, declared, args)
+    }
+
+    /// Whether an object of the class (or array descriptor) is an instance of `target`
+    fn is_assignable(&self, class: &str, target: &str) -> bool {
+        if class == target || target == OBJECT {
+            return true;
+        }
+        if let Some(component) = class.strip_prefix('[') {
+            if target == "java/lang/Cloneable" || target == "java/io/Serializable" {
+                return true;
+            }
+            let Some(target_component) = target.strip_prefix('[') else {
+                return false;
+            };
+            let is_primitive = |d: &str| !d.starts_with('L') && !d.starts_with('[');
+            if is_primitive(component) || is_primitive(target_component) {
+                return component == target_component;
+            }
+            return self.is_assignable(
+                component_class(component),
+                component_class(target_component),
+            );
+        }
+        let Ok(cf) = self.load(class) else {
+            return false;
+        };
+        interface_names(&cf)
+            .iter()
+            .chain(cf.super_class_name().iter())
+            .any(|s| self.is_assignable(s, target))
+    }
+}
+
+const OBJECT: &str = "java/lang/Object";
+const NULL_POINTER_EXCEPTION: &str = "java/lang/NullPointerException";
+
+/// Classes of the JDK, which are not interpreted
+fn is_system_class(class: &str) -> bool {
+    ["java/", "javax/", "jdk/", "sun/"]
+        .iter()
+        .any(|prefix| class.starts_with(prefix))
+}
+
+/// The class name of a reference component descriptor, `Ljava/lang/String;` -> `java/lang/String`
+fn component_class(descriptor: &str) -> &str {
+    descriptor
+        .strip_prefix('L')
+        .and_then(|d| d.strip_suffix(';'))
+        .unwrap_or(descriptor)
+}
+
+fn interface_names(cf: &ClassFile) -> Vec<String> {
+    cf.interfaces
+        .interfaces
+        .iter()
+        .map(|i| cf.constant_pool.get_to_string(*i))
+        .collect()
+}
@@ -0,0 +1,336 @@
+//! The methods and static fields of JDK classes the interpreter implements itself
+
+use super::heap::Object;
+use super::{Invoke, Value, Vm, DETAIL_MESSAGE, NULL};
+use crate::class_file::constant_pool::{InvokeDynamic, MemberRef, MethodRef};
+use crate::class_file::{BaseType, ClassFile, FieldType, MethodDescriptor};
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+
+const THROWABLE: &str = "java/lang/Throwable";
+const STRING_CONCAT_FACTORY: &str = "java/lang/invoke/StringConcatFactory";
+
+impl Vm {
+    pub(super) fn native_static(&mut self, class: &str, name: &str) -> Result<Value> {
+        match (class, name) {
+            ("java/lang/System", "out") => {
+                let out = match self.system_out {
+                    Some(out) => out,
+                    None => {
+                        let out = self.heap.allocate(Object::Instance {
+                            class: "java/io/PrintStream".to_string(),
+                            fields: HashMap::new(),
+                        });
+                        *self.system_out.insert(out)
+                    }
+                };
+                Ok(Value::Reference(Some(out)))
+            }
+            _ => Err(anyhow!(
+                "{}.{} is not supported by the interpreter",
+                class,
+                name
+            )),
+        }
+    }
+
+    /// Runs the JDK method if the interpreter implements it, `None` if it doesn't.
+    /// `args` start with the receiver for instance methods.
+    pub(super) fn call_native(
+        &mut self,
+        class: &str,
+        name: &str,
+        descriptor: &str,
+        args: &[Value],
+    ) -> Option<Result<Option<Value>>> {
+        let result = match (class, name, descriptor) {
+            ("java/lang/Object", "<init>", "()V") => Ok(None),
+            // the identity hash code is the index of the object on the heap
+            ("java/lang/Object", "hashCode", "()I") => Ok(Some(Value::Int(reference(args) as i32))),
+            ("java/lang/Object", "toString", "()Ljava/lang/String;") => {
+                let object = reference(args);
+                let class = self.heap.get(object).class_name().replace('/', ".");
+                let string = format!("{}@{:x}", class, object);
+                Ok(Some(self.new_string(string)))
+            }
+            // only arrays are `Cloneable`
+            ("java/lang/Object", "clone", "()Ljava/lang/Object;") => {
+                match self.heap.get(reference(args)).clone() {
+                    array @ Object::Array { .. } => {
+                        Ok(Some(Value::Reference(Some(self.heap.allocate(array)))))
+                    }
+                    object => Err(self.throw(
+                        "java/lang/CloneNotSupportedException",
+                        Some(object.class_name().replace('/', ".")),
+                    )),
+                }
+            }
+            ("java/io/PrintStream", "print" | "println", _) => self.print(name, descriptor, args),
+
+            ("java/lang/String", "length", "()I") => {
+                let length = self.native_string(args[0]).encode_utf16().count();
+                Ok(Some(Value::Int(length as i32)))
+            }
+            ("java/lang/String", "charAt", "(I)C") => {
+                let Value::Int(index) = args[1] else {
+                    return Some(Err(anyhow!("charAt with {:?}", args[1])));
+                };
+                let string = self.native_string(args[0]);
+                let length = string.encode_utf16().count();
+                match string
+                    .encode_utf16()
+                    .nth(index as usize)
+                    .filter(|_| index >= 0)
+                {
+                    Some(c) => Ok(Some(Value::Int(c as i32))),
+                    None => {
+                        let message = format!("index {}, length {}", index, length);
+                        let class = "java/lang/StringIndexOutOfBoundsException";
+                        Err(self.throw(class, Some(message)))
+                    }
+                }
+            }
+            ("java/lang/String", "equals", "(Ljava/lang/Object;)Z") => {
+                let equal = self.string(&args[1]) == Some(self.native_string(args[0]));
+                Ok(Some(Value::Int(equal as i32)))
+            }
+            ("java/lang/String", "hashCode", "()I") => {
+                let hash = self
+                    .native_string(args[0])
+                    .encode_utf16()
+                    .fold(0i32, |h, c| h.wrapping_mul(31).wrapping_add(c as i32));
+                Ok(Some(Value::Int(hash)))
+            }
+            ("java/lang/String", "toString", "()Ljava/lang/String;") => Ok(Some(args[0])),
+            ("java/lang/String", "valueOf", "(Ljava/lang/Object;)Ljava/lang/String;") => self
+                .string_value(args[0], &FieldType::Object("java/lang/Object".to_string()))
+                .map(|s| Some(self.new_string(s))),
+
+            ("java/lang/Math", _, _) => match (name, args) {
+                ("abs", [Value::Int(a)]) => Ok(Some(Value::Int(a.wrapping_abs()))),
+                ("abs", [Value::Long(a)]) => Ok(Some(Value::Long(a.wrapping_abs()))),
+                ("abs", [Value::Float(a)]) => Ok(Some(Value::Float(a.abs()))),
+                ("abs", [Value::Double(a)]) => Ok(Some(Value::Double(a.abs()))),
+                ("max", [Value::Int(a), Value::Int(b)]) => Ok(Some(Value::Int(*a.max(b)))),
+                ("max", [Value::Long(a), Value::Long(b)]) => Ok(Some(Value::Long(*a.max(b)))),
+                ("min", [Value::Int(a), Value::Int(b)]) => Ok(Some(Value::Int(*a.min(b)))),
+                ("min", [Value::Long(a), Value::Long(b)]) => Ok(Some(Value::Long(*a.min(b)))),
+                ("sqrt", [Value::Double(a)]) => Ok(Some(Value::Double(a.sqrt()))),
+                _ => return None,
+            },
+
+            (THROWABLE, "getMessage", "()Ljava/lang/String;") => {
+                let message = match self.heap.get(reference(args)) {
+                    Object::Instance { fields, .. } => fields.get(DETAIL_MESSAGE).copied(),
+                    _ => None,
+                };
+                Ok(Some(message.unwrap_or(NULL)))
+            }
+            // the constructors of the exceptions of the JDK only keep the message
+            (_, "<init>", "()V" | "(Ljava/lang/String;)V")
+                if self.is_assignable(class, THROWABLE) =>
+            {
+                let message = args.get(1).copied().unwrap_or(NULL);
+                if let Object::Instance { fields, .. } = self.heap.get_mut(reference(args)) {
+                    fields.insert(DETAIL_MESSAGE.to_string(), message);
+                }
+                Ok(None)
+            }
+            _ => return None,
+        };
+        Some(result)
+    }
+
+    fn print(&mut self, name: &str, descriptor: &str, args: &[Value]) -> Result<Option<Value>> {
+        let text = match (descriptor, args) {
+            ("()V", _) => String::new(),
+            // `char[]` is printed as text, not as an object
+            ("([C)V", [_, Value::Reference(Some(array))]) => match self.heap.get(*array) {
+                Object::Array { elements, .. } => {
+                    let chars = elements.iter().map(|c| match c {
+                        Value::Int(c) => *c as u16,
+                        _ => 0,
+                    });
+                    String::from_utf16_lossy(&chars.collect::<Vec<_>>())
+                }
+                object => return Err(anyhow!("print of {:?}", object)),
+            },
+            (_, [_, value]) => {
+                let arg = &MethodDescriptor::parse(descriptor)?.parameters[0];
+                self.string_value(*value, arg)?
+            }
+            _ => {
+                return Err(anyhow!(
+                    "PrintStream.{}{} is not supported",
+                    name,
+                    descriptor
+                ))
+            }
+        };
+        self.output.push_str(&text);
+        if name == "println" {
+            self.output.push('\n');
+        }
+        Ok(None)
+    }
+
+    /// Call sites of `invokedynamic`, only string concatenation (`StringConcatFactory`)
+    /// is supported
+    pub(super) fn invoke_dynamic(
+        &mut self,
+        cf: &ClassFile,
+        call_site: &InvokeDynamic,
+        args: Vec<Value>,
+    ) -> Result<Value> {
+        let cp = &cf.constant_pool;
+        let bootstrap_index = call_site.bootstrap_method_attr_index;
+        let bootstrap = cf
+            .attributes
+            .get_bootstrap_methods()
+            .and_then(|att| att.get(bootstrap_index))
+            .ok_or_else(|| anyhow!("Missing bootstrap method {}", bootstrap_index))?;
+        let bootstrap_method = match cp.method_handle(bootstrap.method_ref())?.reference {
+            MemberRef::Method(method) if method.owner == STRING_CONCAT_FACTORY => method,
+            reference => {
+                return Err(anyhow!(
+                "invokedynamic with the bootstrap method {:?} is not supported by the interpreter",
+                reference
+            ))
+            }
+        };
+
+        let arg_types = MethodDescriptor::parse(&call_site.descriptor)?.parameters;
+        let mut values = args.iter().zip(&arg_types);
+        // `makeConcatWithConstants` has a recipe with \1 for the arguments and \2 for the
+        // other static arguments, `makeConcat` concatenates the arguments
+        let recipe = match (
+            bootstrap_method.name.as_str(),
+            bootstrap.arguments().first(),
+        ) {
+            ("makeConcatWithConstants", Some(recipe)) => cp.string(*recipe)?.to_string(),
+            _ => "\u{1}".repeat(args.len()),
+        };
+        let mut constants = bootstrap.arguments().iter().skip(1);
+        let mut result = String::new();
+        for c in recipe.chars() {
+            match c {
+                '\u{1}' => {
+                    let (value, arg_type) = values
+                        .next()
+                        .ok_or_else(|| anyhow!("Missing argument for the recipe {:?}", recipe))?;
+                    result.push_str(&self.string_value(*value, arg_type)?);
+                }
+                '\u{2}' => {
+                    let constant = constants
+                        .next()
+                        .ok_or_else(|| anyhow!("Missing constant for the recipe {:?}", recipe))?;
+                    result.push_str(cp.string(*constant)?);
+                }
+                c => result.push(c),
+            }
+        }
+        Ok(self.new_string(result))
+    }
+
+    /// `String.valueOf` of a value of the type, calls `toString` on objects
+    fn string_value(&mut self, value: Value, field_type: &FieldType) -> Result<String> {
+        let string = match (field_type, value) {
+            (FieldType::Base(BaseType::Boolean), Value::Int(v)) => (v != 0).to_string(),
+            (FieldType::Base(BaseType::Char), Value::Int(v)) => {
+                String::from_utf16_lossy(&[v as u16])
+            }
+            (_, Value::Int(v)) => v.to_string(),
+            (_, Value::Long(v)) => v.to_string(),
+            (_, Value::Float(v)) => java_float(v),
+            (_, Value::Double(v)) => java_double(v),
+            (_, Value::Reference(None)) => "null".to_string(),
+            (_, Value::Reference(Some(_))) => {
+                let string = match self.string(&value) {
+                    Some(string) => return Ok(string.to_string()),
+                    None => {
+                        let to_string = MethodRef {
+                            owner: "java/lang/Object".to_string(),
+                            name: "toString".to_string(),
+                            descriptor: "()Ljava/lang/String;".to_string(),
+                            is_interface: false,
+                        };
+                        self.invoke(Invoke::Virtual, &to_string, vec![value])?
+                    }
+                };
+                match string {
+                    Some(string) => self
+                        .string_value(string, &FieldType::Object("java/lang/String".to_string()))?,
+                    None => "null".to_string(),
+                }
+            }
+        };
+        Ok(string)
+    }
+
+    fn new_string(&mut self, s: String) -> Value {
+        Value::Reference(Some(self.heap.allocate(Object::String(s))))
+    }
+
+    /// The content of the `String` receiver of a native method
+    fn native_string(&self, value: Value) -> &str {
+        self.string(&value).unwrap_or_default()
+    }
+}
+
+/// The receiver of an instance method, `invoke` already checked it is not null
+fn reference(args: &[Value]) -> usize {
+    match args.first() {
+        Some(Value::Reference(Some(reference))) => *reference,
+        _ => unreachable!("instance method without receiver"),
+    }
+}
+
+/// `Double.toString`: plain between 10^-3 and 10^7, in computerized scientific notation
+/// (`1.0E10`) otherwise and always with a fraction
+pub(super) fn java_double(v: f64) -> String {
+    java_decimal(v.is_nan(), v.is_infinite(), v.abs(), v.is_sign_negative())
+        .unwrap_or_else(|| java_notation(format!("{}", v), format!("{:e}", v), v.abs()))
+}
+
+/// `Float.toString`, see `java_double`
+pub(super) fn java_float(v: f32) -> String {
+    java_decimal(
+        v.is_nan(),
+        v.is_infinite(),
+        v.abs() as f64,
+        v.is_sign_negative(),
+    )
+    .unwrap_or_else(|| java_notation(format!("{}", v), format!("{:e}", v), v.abs() as f64))
+}
+
+/// The special values, `None` for finite non zero values
+fn java_decimal(nan: bool, infinite: bool, abs: f64, negative: bool) -> Option<String> {
+    let sign = if negative { "-" } else { "" };
+    if nan {
+        Some("NaN".to_string())
+    } else if infinite {
+        Some(format!("{}Infinity", sign))
+    } else if abs == 0.0 {
+        Some(format!("{}0.0", sign))
+    } else {
+        None
+    }
+}
+
+/// Rust prints the same shortest digits, only the notation differs
+fn java_notation(plain: String, scientific: String, abs: f64) -> String {
+    if (1e-3..1e7).contains(&abs) {
+        if plain.contains('.') {
+            plain
+        } else {
+            format!("{}.0", plain)
+        }
+    } else {
+        let (mantissa, exponent) = scientific.split_once('e').unwrap();
+        if mantissa.contains('.') {
+            format!("{}E{}", mantissa, exponent)
+        } else {
+            format!("{}.0E{}", mantissa, exponent)
+        }
+    }
+}
@@ -1,31 +1,55 @@
 use crate::class_file::{
-    access_flags::AccessFlag, fields::AccessFlag as FieldAccessFlag,
-    methods::AccessFlag as MethodAccessFlag, ClassFile,
+    access_flags::{AccessFlag, Visibility},
+    attributes::ConstantValue,
+    fields::AccessFlag as FieldAccessFlag,
+    methods::AccessFlag as MethodAccessFlag,
+    read_class_file, ClassFile, FieldType, MethodDescriptor,
 };
+use anyhow::Result;
+use std::fmt::{Display, LowerExp};
+use std::path::Path;
 
-use crate::class_file::jaustp::{
-    parse_method_descriptor::parse_method_descriptor,
-    parse_method_descriptor::parse_type_descriptor, print_code::print_code,
+use crate::class_file::jaustp::print_code::print_code;
+use crate::class_file::jaustp::signature::{
+    parse_class_signature, parse_method_signature, parse_signature, type_parameters_to_string,
 };
 
+#[derive(Default)]
 pub struct Options {
-    pub private: bool,
+    pub visibility: Visibility,
     pub code: bool,
+    /// Print the values of constant fields like `javap -constants`
+    pub constants: bool,
+    /// Print the class attributes after the members like `javap -v` (only InnerClasses for now)
+    pub verbose: bool,
+    /// Directories used to look up super types (needed to detect `non-sealed` classes)
+    pub class_path: Vec<String>,
+    /// Print the descriptor under each member like `javap -s`
+    pub descriptors: bool,
+    /// The output follows a `jaustp_sysinfo` header, `Compiled from` is indented under it
+    pub sysinfo: bool,
 }
 
 /// Print a summary of the class file. like javap does by default.
-pub fn jaustp_summary_print(cf: &ClassFile, opts: &Options) {
-    let out = jaustp_summary(cf, opts);
+pub fn jaustp_summary_print(cf: &ClassFile, opts: &Options) -> Result<()> {
+    let out = jaustp_summary(cf, opts)?;
     print!("{}", out);
+    Ok(())
 }
 
-pub fn jaustp_summary(cf: &ClassFile, opts: &Options) -> String {
+/// The class as javap prints it, fails if an attribute table of a lazily parsed class
+/// file can't be decoded
+pub fn jaustp_summary(cf: &ClassFile, opts: &Options) -> Result<String> {
+    cf.decode_attributes()?;
     let mut out = String::new();
     let source = cf.attributes.get_source_file(&cf.constant_pool);
     if let Some(source) = source {
+        if opts.sysinfo {
+            out.push_str("  ");
+        }
         out.push_str(&format!("Compiled from \"{}\"\n", source));
     }
-    add_class_line(cf, &mut out);
+    add_class_line(cf, &mut out, opts);
 
     add_fields(cf, &mut out, opts);
 
@@ -33,7 +57,11 @@ pub fn jaustp_summary(cf: &ClassFile, opts: &Options) -> String {
 
     out.push_str("}\n");
 
-    out
+    if opts.verbose {
+        add_inner_classes(cf, &mut out, opts);
+    }
+
+    Ok(out)
 }
 
 fn add_fields(cf: &ClassFile, out: &mut String, opts: &Options) {
@@ -45,13 +73,14 @@ fn add_fields(cf: &ClassFile, out: &mut String, opts: &Options) {
         let flags = field.access_flags.flag_vector();
         let mut modifiers = Vec::new();
 
+        if !opts.visibility.shows(field.visibility()) {
+            continue;
+        }
+
         if flags.contains(&FieldAccessFlag::Public) {
             modifiers.push("public");
         } else if flags.contains(&FieldAccessFlag::Private) {
             modifiers.push("private");
-            if !opts.private {
-                continue;
-            }
         } else if flags.contains(&FieldAccessFlag::Protected) {
             modifiers.push("protected");
         }
@@ -77,144 +106,481 @@ fn add_fields(cf: &ClassFile, out: &mut String, opts: &Options) {
 
         out.push_str(indent);
         out.push_str(&modifiers.join(" "));
-        if modifiers.len() > 0 {
-            out.push_str(" ");
+        if !modifiers.is_empty() {
+            out.push(' ');
         }
 
-        let mut descriptor = field_descriptor.chars().peekable();
-        let _type = parse_type_descriptor(&mut descriptor).unwrap_or_else(|e| e.to_string());
-        out.push_str(&_type);
+        let signature = field.attributes.get_signature(&cf.constant_pool);
+        out.push_str(&type_name(signature.as_deref(), &field_descriptor));
 
-        out.push_str(" ");
+        out.push(' ');
         out.push_str(&field_name);
+        if opts.constants {
+            if let Some(Ok(value)) = field.constant_value(&cf.constant_pool) {
+                out.push_str(" = ");
+                out.push_str(&format_constant(&value, &field_descriptor));
+            }
+        }
         out.push_str(";\n");
+        if opts.descriptors {
+            add_descriptor(out, &field_descriptor);
+        }
     }
 }
 
 fn add_methods(cf: &ClassFile, out: &mut String, opts: &Options) {
     let indent = "  ";
+    let mut first = true;
     for method in &cf.methods.methods {
         let flags = method.access_flags.flag_vector();
 
-        if flags.contains(&MethodAccessFlag::Private) && !opts.private {
+        if !opts.visibility.shows(method.visibility()) {
             continue;
         }
 
-        let modifiers = flags
-            .iter()
-            .map(|f| f.to_str().to_string())
-            .filter(|s| s != "synthetic") // synthetic methods are not marked synthetic in javap
-            .collect::<Vec<String>>()
-            .join(" ");
+        // javap separates methods with a blank line when it prints more than their header
+        if opts.descriptors && !opts.code && !first {
+            out.push('\n');
+        }
+        first = false;
+
+        let modifiers = method_modifiers(&flags, cf.is_interface());
         out.push_str(indent);
-        out.push_str(&modifiers);
+        out.push_str(&modifiers.join(" "));
 
-        if modifiers.len() > 0 {
-            out.push_str(" ");
+        if !modifiers.is_empty() {
+            out.push(' ');
         }
 
         let method_name = cf.constant_pool.get_to_string(method.name_index);
+        let descriptor = cf.constant_pool.get_to_string(method.descriptor_index);
         if method_name == "<clinit>" {
             // static initializer
             out.push_str("{};\n");
+            if opts.descriptors {
+                add_descriptor(out, &descriptor);
+            }
             continue;
         }
 
-        let descriptor = cf.constant_pool.get_to_string(method.descriptor_index);
-        let signature = parse_method_descriptor(&descriptor, method_name);
+        let (return_type, mut args) = match MethodDescriptor::parse(&descriptor) {
+            Ok(descriptor) => (
+                descriptor.return_source_name(),
+                descriptor
+                    .parameters
+                    .iter()
+                    .map(FieldType::source_name)
+                    .collect::<Vec<_>>(),
+            ),
+            Err(e) => (e.to_string(), Vec::new()),
+        };
+        let mut exceptions = method
+            .attributes
+            .get_checked_exceptions(&cf.constant_pool)
+            .iter()
+            .map(|e| e.replace('/', "."))
+            .collect::<Vec<String>>();
+
+        // the signature has the generic types, and leaves out the parameters the compiler adds
+        // to enum and inner class constructors
+        let signature = method
+            .attributes
+            .get_signature(&cf.constant_pool)
+            .and_then(|s| parse_method_signature(&s).ok());
+        let return_type = match signature {
+            Some(signature) => {
+                let type_parameters = type_parameters_to_string(&signature.type_parameters);
+                if !type_parameters.is_empty() {
+                    out.push_str(&type_parameters);
+                    out.push(' ');
+                }
+                args = signature.parameters.iter().map(|p| p.to_string()).collect();
+                if !signature.throws.is_empty() {
+                    exceptions = signature.throws.iter().map(|t| t.to_string()).collect();
+                }
+                signature.result.to_string()
+            }
+            None => return_type,
+        };
+
+        if flags.contains(&MethodAccessFlag::Varargs) {
+            if let Some(last) = args.last_mut() {
+                if let Some(component) = last.strip_suffix("[]") {
+                    *last = format!("{}...", component);
+                }
+            }
+        }
 
-        if signature.name == "<init>" {
+        if method_name == "<init>" {
             let class_name = cf.constant_pool.get_to_string(cf.this_class);
             let class_name = class_name.replace("/", ".");
             out.push_str(&class_name);
         } else {
-            out.push_str(&signature.return_type);
-            out.push_str(" ");
-            out.push_str(&signature.name);
+            out.push_str(&return_type);
+            out.push(' ');
+            out.push_str(&method_name);
         }
 
-        out.push_str("(");
-        out.push_str(&signature.args.join(", "));
-        out.push_str(")");
+        out.push('(');
+        out.push_str(&args.join(", "));
+        out.push(')');
 
-        let checked_exceptions = method.attributes.get_checked_exceptions(&cf.constant_pool);
-        if checked_exceptions.len() > 0 {
-            let exceptions = checked_exceptions
-                .iter()
-                .map(|e| e.replace('/', "."))
-                .collect::<Vec<String>>()
-                .join(" ");
+        if !exceptions.is_empty() {
             out.push_str(" throws ");
-            out.push_str(&exceptions);
+            out.push_str(&exceptions.join(", "));
         }
 
         out.push_str(";\n");
+        if opts.descriptors {
+            add_descriptor(out, &descriptor);
+        }
         if opts.code {
-            print_code(&method, &cf.constant_pool, out);
-            out.push_str("\n");
+            print_code(method, &cf.constant_pool, out);
+            out.push('\n');
         }
     }
 }
 
-pub fn add_class_line(cf: &ClassFile, out: &mut String) {
-    add_class_modifiers(cf, out);
+/// The modifiers javap prints for a method: not `bridge`, `varargs` or `synthetic`, and
+/// `default` for the interface methods that have a body
+fn method_modifiers(flags: &[MethodAccessFlag], interface: bool) -> Vec<&'static str> {
+    let mut modifiers = flags
+        .iter()
+        .filter_map(|f| match f {
+            MethodAccessFlag::Bridge | MethodAccessFlag::Varargs | MethodAccessFlag::Synthetic => {
+                None
+            }
+            MethodAccessFlag::Strict => Some("strictfp"),
+            f => Some(f.to_str()),
+        })
+        .collect::<Vec<_>>();
+    if interface
+        && !flags.contains(&MethodAccessFlag::Abstract)
+        && !flags.contains(&MethodAccessFlag::Static)
+        && !flags.contains(&MethodAccessFlag::Private)
+    {
+        modifiers.push("default");
+    }
+    modifiers
+}
+
+/// The type of a field or record component as written in Java, generic when it has a signature
+fn type_name(signature: Option<&str>, descriptor: &str) -> String {
+    if let Some(Ok(signature)) = signature.map(parse_signature) {
+        return signature.to_string();
+    }
+    FieldType::parse(descriptor)
+        .map(|t| t.source_name())
+        .unwrap_or_else(|e| e.to_string())
+}
+
+/// The `javap -s` line under a member
+fn add_descriptor(out: &mut String, descriptor: &str) {
+    out.push_str("    descriptor: ");
+    out.push_str(descriptor);
+    out.push('\n');
+}
+
+/// Prints the InnerClasses attribute the way `javap -v` does, e.g.
+/// `  public static #40= #29 of #12;          // Inner=class Outer$Inner of class Outer`
+fn add_inner_classes(cf: &ClassFile, out: &mut String, opts: &Options) {
+    let inner_classes = cf
+        .inner_classes()
+        .into_iter()
+        .filter(|c| opts.visibility.shows(c.visibility()))
+        .collect::<Vec<_>>();
+    if inner_classes.is_empty() {
+        return;
+    }
+    let cp = &cf.constant_pool;
+    out.push_str("InnerClasses:\n");
+    for class in inner_classes {
+        let mut line = String::from("  ");
+        for modifier in class.inner_class_access_flags.modifiers() {
+            line.push_str(modifier);
+            line.push(' ');
+        }
+        if let Some(inner_name_index) = class.inner_name_index {
+            line.push_str(&format!("#{}= ", inner_name_index));
+        }
+        line.push_str(&format!("#{}", class.inner_class_info_index));
+        if let Some(outer_class_info_index) = class.outer_class_info_index {
+            line.push_str(&format!(" of #{}", outer_class_info_index));
+        }
+        line.push(';');
+
+        let mut comment = String::from("// ");
+        if let Some(inner_name) = class.inner_name(cp) {
+            comment.push_str(&format!("{}=", inner_name));
+        }
+        comment.push_str(&format!("class {}", class.inner_class_name(cp)));
+        if let Some(outer_class_name) = class.outer_class_name(cp) {
+            comment.push_str(&format!(" of class {}", outer_class_name));
+        }
+
+        out.push_str(&format!("{:<41} {}\n", line, comment));
+    }
+}
+
+pub fn add_class_line(cf: &ClassFile, out: &mut String, opts: &Options) {
+    let kind = ClassKind::of(cf);
+    add_class_modifiers(cf, kind, out, opts);
 
     let class_name = cf.constant_pool.get_to_string(cf.this_class);
     let class_name = class_name.replace("/", ".");
-    out.push_str(" ");
+    out.push(' ');
     out.push_str(&class_name);
 
-    if cf.super_class != 0 {
-        let super_class_name = cf.constant_pool.get_to_string(cf.super_class);
-        if super_class_name != "java/lang/Object" {
-            let super_class_name = super_class_name.replace("/", ".");
+    // the signature has the type parameters and the generic super types
+    let signature = cf
+        .attributes
+        .get_signature(&cf.constant_pool)
+        .and_then(|s| parse_class_signature(&s).ok());
+    // javap separates the interfaces of the signature with ", " and the others with ","
+    let (super_class_name, interface_names, separator) = match &signature {
+        Some(signature) => {
+            out.push_str(&type_parameters_to_string(&signature.type_parameters));
+            (
+                Some(signature.superclass.to_string()),
+                signature
+                    .superinterfaces
+                    .iter()
+                    .map(|i| i.to_string())
+                    .collect::<Vec<String>>(),
+                ", ",
+            )
+        }
+        None => (
+            cf.super_class_name().map(|s| s.replace('/', ".")),
+            cf.interfaces
+                .interfaces
+                .iter()
+                .map(|i| cf.constant_pool.get_to_string(*i).replace('/', "."))
+                .collect(),
+            ",",
+        ),
+    };
+
+    if kind == ClassKind::Record {
+        add_record_components(cf, out);
+    }
+
+    match super_class_name.as_deref() {
+        None | Some("java.lang.Object") => {}
+        // interfaces extend Object in the class file, records always extend Record
+        Some("java.lang.Record") if kind == ClassKind::Record => {}
+        Some(super_class_name) => {
             out.push_str(" extends ");
-            out.push_str(&super_class_name);
+            out.push_str(super_class_name);
         }
     }
 
-    if cf.interfaces.interfaces.len() > 0 {
-        out.push_str(" implements ");
-        for interface in &cf.interfaces.interfaces {
-            let interface_name = cf.constant_pool.get_to_string(*interface);
-            let interface_name = interface_name.replace("/", ".");
-            out.push_str(&interface_name);
-            out.push_str(", ");
+    let interface_names = interface_names
+        .into_iter()
+        .filter(|i| kind != ClassKind::Annotation || i != "java.lang.annotation.Annotation")
+        .collect::<Vec<String>>();
+    if !interface_names.is_empty() {
+        match kind {
+            ClassKind::Interface | ClassKind::Annotation => out.push_str(" extends "),
+            ClassKind::Class | ClassKind::Record => out.push_str(" implements "),
         }
-        out.pop();
-        out.pop();
+        out.push_str(&interface_names.join(separator));
+    }
+
+    let is_enum = cf.access_flags.flag_vector().contains(&AccessFlag::Enum);
+    if let Some(permitted) = cf.permitted_subclasses().filter(|_| !is_enum) {
+        out.push_str(" permits ");
+        let permitted = permitted
+            .iter()
+            .map(|c| c.replace('/', "."))
+            .collect::<Vec<String>>()
+            .join(", ");
+        out.push_str(&permitted);
     }
 
     out.push_str(" {\n");
 }
 
-fn add_class_modifiers(cf: &ClassFile, out: &mut String) {
+/// The `(int x, java.lang.String y)` of a record header
+fn add_record_components(cf: &ClassFile, out: &mut String) {
+    let Some(record) = cf.attributes.get_record() else {
+        return;
+    };
+    let cp = &cf.constant_pool;
+    let components = record
+        .components()
+        .iter()
+        .map(|c| {
+            let signature = c.attributes().get_signature(cp);
+            let component_type = type_name(signature.as_deref(), &c.descriptor(cp));
+            format!("{} {}", component_type, c.name(cp))
+        })
+        .collect::<Vec<String>>();
+    out.push('(');
+    out.push_str(&components.join(", "));
+    out.push(')');
+}
+
+/// What the class line declares. Enums are printed as the final classes they compile to, like
+/// javap does, the constants are their `public static final` fields.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ClassKind {
+    Class,
+    Interface,
+    Annotation,
+    Record,
+}
+
+impl ClassKind {
+    fn of(cf: &ClassFile) -> ClassKind {
+        let flags = cf.access_flags.flag_vector();
+        if flags.contains(&AccessFlag::Annotation) {
+            ClassKind::Annotation
+        } else if flags.contains(&AccessFlag::Interface) {
+            ClassKind::Interface
+        } else if cf.attributes.get_record().is_some()
+            && cf.super_class_name().as_deref() == Some("java/lang/Record")
+        {
+            ClassKind::Record
+        } else {
+            ClassKind::Class
+        }
+    }
+
+    fn keyword(self) -> &'static str {
+        match self {
+            ClassKind::Class => "class",
+            ClassKind::Interface => "interface",
+            ClassKind::Annotation => "@interface",
+            ClassKind::Record => "record",
+        }
+    }
+}
+
+fn add_class_modifiers(cf: &ClassFile, kind: ClassKind, out: &mut String, opts: &Options) {
     let flags = cf.access_flags.flag_vector();
 
     let mut modifiers = Vec::new();
 
-    let kind = if flags.contains(&AccessFlag::Interface) {
-        "interface"
-    } else if flags.contains(&AccessFlag::Enum) {
-        "enum"
-    } else {
-        "class"
-    };
-
     if flags.contains(&AccessFlag::Public) {
         modifiers.push("public");
     }
 
-    if flags.contains(&AccessFlag::Final) {
+    // records are always final
+    if flags.contains(&AccessFlag::Final) && kind != ClassKind::Record {
         modifiers.push("final");
     }
 
-    if flags.contains(&AccessFlag::Abstract) && kind != "interface"
+    if flags.contains(&AccessFlag::Abstract) && kind == ClassKind::Class
     /* interfaces are always abstract */
     {
         modifiers.push("abstract");
     }
 
-    modifiers.push(kind);
+    // an enum is sealed when its constants have bodies, which the source doesn't say
+    if !flags.contains(&AccessFlag::Enum) {
+        if cf.is_sealed() {
+            modifiers.push("sealed");
+        } else if is_non_sealed(cf, opts) {
+            modifiers.push("non-sealed");
+        }
+    }
+
+    modifiers.push(kind.keyword());
     out.push_str(&modifiers.join(" "));
 }
+
+/// `ClassFile::is_non_sealed` with the super types looked up in the class path directories
+fn is_non_sealed(cf: &ClassFile, opts: &Options) -> bool {
+    cf.is_non_sealed(|super_type| {
+        opts.class_path.iter().find_map(|dir| {
+            let path = Path::new(dir).join(format!("{}.class", super_type));
+            let super_cf = read_class_file(path.to_str()?).ok()?;
+            Some(super_cf.permitted_subclasses().unwrap_or_default())
+        })
+    })
+}
+
+/// Formats a constant the way `javap -constants` does, the descriptor tells apart the
+/// types that are all stored as ints in the constant pool
+fn format_constant(value: &ConstantValue, descriptor: &str) -> String {
+    match value {
+        ConstantValue::Int(v) => match descriptor {
+            "Z" => (*v != 0).to_string(),
+            "C" => match char::from_u32(*v as u32) {
+                Some(c) => quote(&c.to_string(), '\''),
+                None => v.to_string(),
+            },
+            _ => v.to_string(),
+        },
+        ConstantValue::Long(v) => format!("{}l", v),
+        ConstantValue::Float(v) => format!("{}f", java_float_to_string(*v)),
+        ConstantValue::Double(v) => format!("{}d", java_float_to_string(*v)),
+        ConstantValue::String(s) => quote(s, '"'),
+    }
+}
+
+/// Java literal escaping as done by javap, non ascii characters are printed as `\uXXXX`
+fn quote(s: &str, quote: char) -> String {
+    let mut out = String::new();
+    out.push(quote);
+    for c in s.chars() {
+        match c {
+            '\u{8}' => out.push_str("\\b"),
+            '\t' => out.push_str("\\t"),
+            '\n' => out.push_str("\\n"),
+            '\u{c}' => out.push_str("\\f"),
+            '\r' => out.push_str("\\r"),
+            '\\' => out.push_str("\\\\"),
+            c if c == quote => {
+                out.push('\\');
+                out.push(c);
+            }
+            ' '..='~' => out.push(c),
+            c => {
+                let mut buf = [0; 2];
+                for unit in c.encode_utf16(&mut buf) {
+                    out.push_str(&format!("\\u{:04x}", unit));
+                }
+            }
+        }
+    }
+    out.push(quote);
+    out
+}
+
+/// Formats floating point numbers like `Float.toString`/`Double.toString`:
+/// plain notation for 10^-3 <= |v| < 10^7 and `1.0E10` style otherwise.
+/// Uses the shortest round trip digits (JDK 19+), older JDKs sometimes print more digits.
+fn java_float_to_string<T>(value: T) -> String
+where
+    T: Display + LowerExp + Into<f64> + Copy,
+{
+    let v: f64 = value.into();
+    if v.is_nan() {
+        return "NaN".to_string();
+    }
+    if v.is_infinite() {
+        return if v > 0.0 { "Infinity" } else { "-Infinity" }.to_string();
+    }
+    if v == 0.0 {
+        return if v.is_sign_negative() { "-0.0" } else { "0.0" }.to_string();
+    }
+    if (1e-3..1e7).contains(&v.abs()) {
+        let s = value.to_string();
+        return if s.contains('.') {
+            s
+        } else {
+            format!("{}.0", s)
+        };
+    }
+    // rust prints the shortest digits that round trip, e.g. 1.5e10
+    let s = format!("{:e}", value);
+    let (mantissa, exponent) = s.split_once('e').unwrap_or((&s, "0"));
+    let mantissa = if mantissa.contains('.') {
+        mantissa.to_string()
+    } else {
+        format!("{}.0", mantissa)
+    };
+    format!("{}E{}", mantissa, exponent)
+}
@@ -0,0 +1,376 @@
+//! `jaustp --format json`: the class, its flags, super types and members as JSON, for scripts.
+//!
+//! Names are binary names (`java/lang/String`) and types are descriptors, exactly as they are
+//! in the class file. Every key is always present (`null` or `[]` when there is nothing) except
+//! `code`, which is only there when the code is asked for.
+
+use super::jaustp_summary::Options;
+use crate::class_file::attributes::{Annotation, Attributes, ConstantValue, ElementValue};
+use crate::class_file::bytecode::ByteCode;
+use crate::class_file::constant_pool::{ConstantPool, Info, MemberRef};
+use crate::class_file::{
+    access_flags::AccessFlag, fields::AccessFlag as FieldAccessFlag, fields::Field,
+    methods::AccessFlag as MethodAccessFlag, methods::Method, ClassFile, Visibility,
+};
+use anyhow::{anyhow, Result};
+use serde::{Serialize, Serializer};
+use std::collections::BTreeMap;
+
+#[derive(Serialize)]
+struct ClassJson {
+    name: String,
+    major_version: u16,
+    minor_version: u16,
+    source_file: Option<String>,
+    flags: Vec<AccessFlag>,
+    super_class: Option<String>,
+    interfaces: Vec<String>,
+    signature: Option<String>,
+    annotations: Vec<AnnotationJson>,
+    fields: Vec<FieldJson>,
+    methods: Vec<MethodJson>,
+}
+
+#[derive(Serialize)]
+struct FieldJson {
+    name: String,
+    descriptor: String,
+    flags: Vec<FieldAccessFlag>,
+    signature: Option<String>,
+    constant_value: Option<ConstantValue>,
+    annotations: Vec<AnnotationJson>,
+}
+
+#[derive(Serialize)]
+struct MethodJson {
+    name: String,
+    descriptor: String,
+    flags: Vec<MethodAccessFlag>,
+    signature: Option<String>,
+    exceptions: Vec<String>,
+    annotations: Vec<AnnotationJson>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    code: Option<CodeJson>,
+}
+
+#[derive(Serialize)]
+struct CodeJson {
+    max_stack: u16,
+    max_locals: u16,
+    instructions: Vec<InstructionJson>,
+    exception_table: Vec<ExceptionJson>,
+}
+
+#[derive(Serialize)]
+struct InstructionJson {
+    pc: u32,
+    /// The mnemonic and its operands, constant pool entries resolved as javap prints them and
+    /// jump targets as pcs, e.g. `invokevirtual java/io/PrintStream.println:(I)V` or `goto 17`
+    instruction: String,
+}
+
+#[derive(Serialize)]
+struct ExceptionJson {
+    start_pc: u16,
+    end_pc: u16,
+    handler_pc: u16,
+    /// `null` for `finally`, which catches everything
+    catch_type: Option<String>,
+}
+
+#[derive(Serialize)]
+struct AnnotationJson {
+    /// The descriptor of the annotation interface, e.g. `Ljava/lang/Deprecated;`
+    #[serde(rename = "type")]
+    type_descriptor: String,
+    elements: BTreeMap<String, ElementValueJson>,
+}
+
+/// Constants are printed the way the constant pool prints them, enum constants, classes
+/// and nested annotations are objects that tell them apart
+#[derive(Serialize)]
+#[serde(untagged)]
+enum ElementValueJson {
+    Constant(String),
+    Enum {
+        #[serde(rename = "enum")]
+        type_descriptor: String,
+        constant: String,
+    },
+    Class {
+        class: String,
+    },
+    Annotation(AnnotationJson),
+    Array(Vec<ElementValueJson>),
+}
+
+/// The class as a single line of JSON, with the members `jaustp` would print for the options
+pub fn jaustp_json(cf: &ClassFile, opts: &Options) -> Result<String> {
+    cf.decode_attributes()?;
+    let json = ClassJson::new(cf, opts)?;
+    Ok(serde_json::to_string(&json).expect("class file JSON only has string keys"))
+}
+
+/// All the members of the class, without their code
+impl Serialize for ClassFile {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let opts = Options {
+            visibility: Visibility::Private,
+            ..Default::default()
+        };
+        ClassJson::new(self, &opts)
+            .map_err(serde::ser::Error::custom)?
+            .serialize(serializer)
+    }
+}
+
+impl ClassJson {
+    fn new(cf: &ClassFile, opts: &Options) -> Result<ClassJson> {
+        let cp = &cf.constant_pool;
+        Ok(ClassJson {
+            name: cf.class_name(),
+            major_version: cf.major_version,
+            minor_version: cf.minor_version,
+            source_file: cf.attributes.get_source_file(cp),
+            flags: cf.access_flags.flag_vector(),
+            super_class: cf.super_class_name(),
+            interfaces: cf
+                .interfaces
+                .interfaces
+                .iter()
+                .map(|i| cp.get_to_string(*i))
+                .collect(),
+            signature: cf.attributes.get_signature(cp),
+            annotations: annotations(&cf.attributes, cp),
+            fields: cf
+                .fields
+                .fields
+                .iter()
+                .filter(|f| opts.visibility.shows(f.visibility()))
+                .map(|f| FieldJson::new(f, cp))
+                .collect::<Result<_>>()?,
+            methods: cf
+                .methods
+                .methods
+                .iter()
+                .filter(|m| opts.visibility.shows(m.visibility()))
+                .map(|m| MethodJson::new(m, cp, opts.code))
+                .collect::<Result<_>>()?,
+        })
+    }
+}
+
+impl FieldJson {
+    fn new(field: &Field, cp: &ConstantPool) -> Result<FieldJson> {
+        Ok(FieldJson {
+            name: cp.get_to_string(field.name_index),
+            descriptor: cp.get_to_string(field.descriptor_index),
+            flags: field.access_flags.flag_vector(),
+            signature: field.attributes.get_signature(cp),
+            constant_value: field.constant_value(cp).transpose()?,
+            annotations: annotations(&field.attributes, cp),
+        })
+    }
+}
+
+impl MethodJson {
+    fn new(method: &Method, cp: &ConstantPool, code: bool) -> Result<MethodJson> {
+        let code = match method.get_code().filter(|_| code) {
+            Some(code) => Some(CodeJson {
+                max_stack: code.max_stack(),
+                max_locals: code.max_locals(),
+                instructions: code
+                    .instructions()
+                    .map(|(pc, instruction)| {
+                        Ok(InstructionJson {
+                            pc,
+                            instruction: instruction_to_string(pc, instruction, cp)?,
+                        })
+                    })
+                    .collect::<Result<_>>()?,
+                exception_table: code
+                    .exception_table()
+                    .iter()
+                    .map(|entry| {
+                        Ok(ExceptionJson {
+                            start_pc: entry.start_pc,
+                            end_pc: entry.end_pc,
+                            handler_pc: entry.handler_pc,
+                            catch_type: match entry.catch_type {
+                                0 => None,
+                                index => Some(cp.class_name(index)?.to_string()),
+                            },
+                        })
+                    })
+                    .collect::<Result<_>>()?,
+            }),
+            None => None,
+        };
+        Ok(MethodJson {
+            name: cp.get_to_string(method.name_index),
+            descriptor: cp.get_to_string(method.descriptor_index),
+            flags: method.access_flags.flag_vector(),
+            signature: method.attributes.get_signature(cp),
+            exceptions: method.attributes.get_checked_exceptions(cp),
+            annotations: annotations(&method.attributes, cp),
+            code,
+        })
+    }
+}
+
+fn instruction_to_string(pc: u32, instruction: &ByteCode, cp: &ConstantPool) -> Result<String> {
+    let targets = instruction.branch_targets(pc);
+    let operands = match instruction {
+        ByteCode::BiPush(value) => value.to_string(),
+        ByteCode::SiPush(value) => value.to_string(),
+        ByteCode::Ldc(index) => constant(*index as u16, cp)?,
+        ByteCode::LdcW(index) | ByteCode::Ldc2W(index) => constant(*index, cp)?,
+        ByteCode::ILoad(index)
+        | ByteCode::Lload(index)
+        | ByteCode::FLoad(index)
+        | ByteCode::DLoad(index)
+        | ByteCode::ALoad(index)
+        | ByteCode::IStore(index)
+        | ByteCode::LStore(index)
+        | ByteCode::FStore(index)
+        | ByteCode::DStore(index)
+        | ByteCode::AStore(index)
+        | ByteCode::Ret(index) => index.to_string(),
+        ByteCode::IInc(index, delta) => format!("{} {}", index, delta),
+        ByteCode::TableSwitch(switch) => {
+            let cases = (switch.low..=switch.high).zip(&targets[1..]);
+            switch_cases(cases, targets[0])
+        }
+        ByteCode::LookupSwitch(switch) => {
+            let cases = switch.pairs.iter().map(|(key, _)| *key).zip(&targets[1..]);
+            switch_cases(cases, targets[0])
+        }
+        ByteCode::GetStatic(index)
+        | ByteCode::PutStatic(index)
+        | ByteCode::GetField(index)
+        | ByteCode::PutField(index) => cp.field_ref(*index)?.to_string(),
+        ByteCode::InvokeVirtual(index)
+        | ByteCode::InvokeSpecial(index)
+        | ByteCode::InvokeStatic(index) => cp.method_ref(*index)?.to_string(),
+        ByteCode::InvokeInterface(index, count) => {
+            format!("{} {}", cp.method_ref(*index)?, count)
+        }
+        ByteCode::InvokeDynamic(index) => {
+            let call_site = cp.invoke_dynamic(*index)?;
+            format!("{}:{}", call_site.name, call_site.descriptor)
+        }
+        ByteCode::New(index)
+        | ByteCode::ANewArray(index)
+        | ByteCode::CheckCast(index)
+        | ByteCode::InstanceOf(index) => cp.class_name(*index)?.to_string(),
+        ByteCode::MultiANewArray(index, dimensions) => {
+            format!("{} {}", cp.class_name(*index)?, dimensions)
+        }
+        ByteCode::NewArray(atype) => {
+            let types = [
+                "boolean", "char", "float", "double", "byte", "short", "int", "long",
+            ];
+            let element = (*atype as usize).checked_sub(4).and_then(|i| types.get(i));
+            element
+                .ok_or_else(|| anyhow!("Invalid newarray type {}", atype))?
+                .to_string()
+        }
+        _ => match targets.first() {
+            Some(target) => target.to_string(),
+            None => return Ok(instruction.mnemonic().to_string()),
+        },
+    };
+    Ok(format!("{} {}", instruction.mnemonic(), operands))
+}
+
+/// `{ key: pc, ..., default: pc }`
+fn switch_cases<'a>(cases: impl Iterator<Item = (i32, &'a u32)>, default: u32) -> String {
+    let mut s = String::from("{ ");
+    for (key, target) in cases {
+        s.push_str(&format!("{}: {}, ", key, target));
+    }
+    s.push_str(&format!("default: {} }}", default));
+    s
+}
+
+/// A loadable constant as javap prints them, e.g. `"text"`, `10L` or `class A`
+fn constant(index: u16, cp: &ConstantPool) -> Result<String> {
+    let constant = match cp.try_get(index) {
+        Some(Info::IntegerInfo(value)) => value.to_string(),
+        Some(Info::FloatInfo(value)) => format!("{:?}f", value),
+        Some(Info::LongInfo(value)) => format!("{}L", value),
+        Some(Info::DoubleInfo(value)) => format!("{:?}d", value),
+        Some(Info::StringInfo(_)) => format!("{:?}", cp.string(index)?),
+        Some(Info::ClassInfo(_)) => format!("class {}", cp.class_name(index)?),
+        Some(Info::MethodTypeInfo(_)) => format!("MethodType {}", cp.method_type(index)?),
+        Some(Info::MethodHandleInfo(_)) => {
+            let handle = cp.method_handle(index)?;
+            let reference = match handle.reference {
+                MemberRef::Field(field) => field.to_string(),
+                MemberRef::Method(method) => method.to_string(),
+            };
+            format!("MethodHandle {} {}", handle.kind.to_string(), reference)
+        }
+        _ => return Err(anyhow!("#{} is not a loadable constant", index)),
+    };
+    Ok(constant)
+}
+
+fn annotations(attributes: &Attributes, cp: &ConstantPool) -> Vec<AnnotationJson> {
+    attributes
+        .get_annotations()
+        .map(|att| {
+            att.annotations
+                .iter()
+                .map(|a| AnnotationJson::new(a, cp))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+impl AnnotationJson {
+    fn new(annotation: &Annotation, cp: &ConstantPool) -> AnnotationJson {
+        AnnotationJson {
+            type_descriptor: cp.get_to_string(annotation.type_index),
+            elements: annotation
+                .element_value_pairs
+                .iter()
+                .map(|pair| {
+                    (
+                        cp.get_to_string(pair.element_name_index),
+                        ElementValueJson::new(&pair.value, cp),
+                    )
+                })
+                .collect(),
+        }
+    }
+}
+
+impl ElementValueJson {
+    fn new(value: &ElementValue, cp: &ConstantPool) -> ElementValueJson {
+        match value {
+            ElementValue::ConstValueIndex {
+                const_value_index, ..
+            } => ElementValueJson::Constant(cp.get_to_string(*const_value_index)),
+            ElementValue::EnumConstValue {
+                type_name_index,
+                const_name_index,
+            } => ElementValueJson::Enum {
+                type_descriptor: cp.get_to_string(*type_name_index),
+                constant: cp.get_to_string(*const_name_index),
+            },
+            ElementValue::ClassInfoIndex(index) => ElementValueJson::Class {
+                class: cp.get_to_string(*index),
+            },
+            ElementValue::AnnotationValue(annotation) => {
+                ElementValueJson::Annotation(AnnotationJson::new(annotation, cp))
+            }
+            ElementValue::ArrayValue(values) => ElementValueJson::Array(
+                values
+                    .iter()
+                    .map(|v| ElementValueJson::new(v, cp))
+                    .collect(),
+            ),
+        }
+    }
+}
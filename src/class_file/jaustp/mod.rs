@@ -1,9 +1,16 @@
 mod jaustp_summary;
-mod parse_method_descriptor;
+#[cfg(feature = "json")]
+mod json;
+mod print_cfg;
 mod print_code;
-mod signature;
+pub(super) mod signature;
+mod sysinfo;
 
 // public facing functions
 pub use jaustp_summary::jaustp_summary;
 pub use jaustp_summary::jaustp_summary_print;
 pub use jaustp_summary::Options as JavapOptions;
+#[cfg(feature = "json")]
+pub use json::jaustp_json;
+pub use print_cfg::jaustp_cfg;
+pub use sysinfo::jaustp_sysinfo;
@@ -0,0 +1,29 @@
+use crate::class_file::ClassFile;
+use anyhow::{anyhow, Result};
+
+/// The control flow graphs of the methods called `method_name` (all overloads)
+/// in the Graphviz DOT format, one digraph per method
+pub fn jaustp_cfg(cf: &ClassFile, method_name: &str) -> Result<String> {
+    let cp = &cf.constant_pool;
+    let mut out = String::new();
+    for method in &cf.methods.methods {
+        if cp.get_to_string(method.name_index) != method_name {
+            continue;
+        }
+        let Some(code) = method.get_code() else {
+            continue;
+        };
+        let name = format!(
+            "{}.{}{}",
+            cf.class_name().replace('/', "."),
+            method_name,
+            cp.get_to_string(method.descriptor_index)
+        );
+        let cfg = method.control_flow_graph(cp)?;
+        out.push_str(&cfg.to_dot(&name, code));
+    }
+    if out.is_empty() {
+        return Err(anyhow!("No method {} with code found", method_name));
+    }
+    Ok(out)
+}
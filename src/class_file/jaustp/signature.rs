@@ -11,9 +11,9 @@ use anyhow::Result;
 /// https://docs.oracle.com/javase/specs/jvms/se21/html/jvms-4.html#jvms-4.7.9
 ///
 ///
+use std::fmt;
 use std::iter::Peekable;
 use std::str::Chars;
-use std::string::ToString;
 
 #[derive(Debug)]
 pub enum JavaTypeSignature {
@@ -21,6 +21,7 @@ pub enum JavaTypeSignature {
     BaseTypeSignature(String),
 }
 
+#[allow(clippy::enum_variant_names)]
 #[derive(Debug)]
 pub enum ReferenceTypeSignature {
     ClassTypeSignature(ClassTypeSignature),
@@ -38,19 +39,98 @@ pub struct ClassTypeSignature {
 #[derive(Debug)]
 pub struct SimpleClassTypeSignature {
     pub identifier: String,
-    pub type_arguments: Vec<ReferenceTypeSignature>,
+    pub type_arguments: Vec<TypeArgument>,
+}
+
+#[derive(Debug)]
+pub enum TypeArgument {
+    /// `?`
+    Wildcard,
+    /// `? extends T`
+    Extends(ReferenceTypeSignature),
+    /// `? super T`
+    Super(ReferenceTypeSignature),
+    Exact(ReferenceTypeSignature),
+}
+
+/// A type parameter of a generic class or method, e.g. `T extends Comparable<? super T>`
+#[derive(Debug)]
+pub struct TypeParameter {
+    pub identifier: String,
+    pub class_bound: Option<ReferenceTypeSignature>,
+    pub interface_bounds: Vec<ReferenceTypeSignature>,
+}
+
+/// The signature of a generic class, or of a class that extends or implements one
+#[derive(Debug)]
+pub struct ClassSignature {
+    pub type_parameters: Vec<TypeParameter>,
+    pub superclass: ClassTypeSignature,
+    pub superinterfaces: Vec<ClassTypeSignature>,
+}
+
+/// The signature of a method with generic parameter or return types, or an enum or inner
+/// class constructor (without the parameters the compiler adds)
+#[derive(Debug)]
+pub struct MethodSignature {
+    pub type_parameters: Vec<TypeParameter>,
+    pub parameters: Vec<JavaTypeSignature>,
+    /// `void` is a `BaseTypeSignature`
+    pub result: JavaTypeSignature,
+    pub throws: Vec<ReferenceTypeSignature>,
 }
 
 type Pchars<'a> = Peekable<Chars<'a>>;
 
+/// Parses the signature of a field (or record component)
 pub fn parse_signature(signature: &str) -> Result<JavaTypeSignature> {
     let mut chars = signature.chars().peekable();
-    parse_java_type_signature(&mut chars)
+    let out = parse_java_type_signature(&mut chars)?;
+    expect_end(&mut chars, signature)?;
+    Ok(out)
+}
+
+pub fn parse_class_signature(signature: &str) -> Result<ClassSignature> {
+    let mut chars = signature.chars().peekable();
+    let type_parameters = parse_type_parameters(&mut chars)?;
+    let superclass = parse_class_type_signature(&mut chars)?;
+    let mut superinterfaces = Vec::new();
+    while chars.peek().is_some() {
+        superinterfaces.push(parse_class_type_signature(&mut chars)?);
+    }
+    Ok(ClassSignature {
+        type_parameters,
+        superclass,
+        superinterfaces,
+    })
+}
+
+pub fn parse_method_signature(signature: &str) -> Result<MethodSignature> {
+    let mut chars = signature.chars().peekable();
+    let type_parameters = parse_type_parameters(&mut chars)?;
+    expect_char(&mut chars, '(')?;
+    let mut parameters = Vec::new();
+    while chars.peek() != Some(&')') {
+        parameters.push(parse_java_type_signature(&mut chars)?);
+    }
+    expect_char(&mut chars, ')')?;
+    let result = parse_java_type_signature(&mut chars)?;
+    let mut throws = Vec::new();
+    while chars.peek().is_some() {
+        expect_char(&mut chars, '^')?;
+        throws.push(parse_reference_type_signature(&mut chars)?);
+    }
+    Ok(MethodSignature {
+        type_parameters,
+        parameters,
+        result,
+        throws,
+    })
 }
 
 fn parse_java_type_signature(chars: &mut Pchars) -> Result<JavaTypeSignature> {
     let out = match chars.peek() {
-        Some('L') => {
+        Some('L' | 'T' | '[') => {
             JavaTypeSignature::ReferenceTypeSignature(parse_reference_type_signature(chars)?)
         }
         Some(_) => JavaTypeSignature::BaseTypeSignature(parse_base_type_signature(chars)?),
@@ -60,7 +140,7 @@ fn parse_java_type_signature(chars: &mut Pchars) -> Result<JavaTypeSignature> {
 }
 
 fn parse_base_type_signature(chars: &mut Pchars) -> Result<String> {
-    let c = chars.next().unwrap();
+    let c = next_char(chars)?;
     let out = match c {
         'I' => "int".to_string(),
         'J' => "long".to_string(),
@@ -71,7 +151,7 @@ fn parse_base_type_signature(chars: &mut Pchars) -> Result<String> {
         'C' => "char".to_string(),
         'Z' => "boolean".to_string(),
         'V' => "void".to_string(),
-        _ => return Err(anyhow!("Invalid base type signature")),
+        _ => return Err(anyhow!("Invalid base type signature {}", c)),
     };
     Ok(out)
 }
@@ -84,9 +164,9 @@ fn parse_reference_type_signature(chars: &mut Pchars) -> Result<ReferenceTypeSig
         )),
         'T' => {
             chars.next();
-            Ok(ReferenceTypeSignature::TypeVariableSignature(
-                parse_identifier(chars),
-            ))
+            let identifier = parse_identifier(chars)?;
+            expect_char(chars, ';')?;
+            Ok(ReferenceTypeSignature::TypeVariableSignature(identifier))
         }
         '[' => {
             chars.next();
@@ -98,35 +178,39 @@ fn parse_reference_type_signature(chars: &mut Pchars) -> Result<ReferenceTypeSig
     }
 }
 
+fn next_char(chars: &mut Pchars) -> Result<char> {
+    chars.next().ok_or(anyhow!("Unexpected end of signature"))
+}
+
 fn expect_char(chars: &mut Pchars, expected: char) -> Result<()> {
-    let c = chars.next().expect("Unexpected end of signature");
+    let c = next_char(chars)?;
     if c != expected {
         return Err(anyhow!("Expected {} but got {}", expected, c));
     }
     Ok(())
 }
 
+fn expect_end(chars: &mut Pchars, signature: &str) -> Result<()> {
+    match chars.peek() {
+        None => Ok(()),
+        Some(_) => Err(anyhow!(
+            "Unexpected {} at the end of {}",
+            chars.collect::<String>(),
+            signature
+        )),
+    }
+}
+
 fn parse_class_type_signature(chars: &mut Pchars) -> Result<ClassTypeSignature> {
     expect_char(chars, 'L')?;
-    let package_specifiers = parse_package_specifiers(chars);
-    let identifier = package_specifiers.last().unwrap().clone();
-    let package_specifiers = package_specifiers[..package_specifiers.len() - 1].to_vec();
-    let package_specifiers = package_specifiers.to_vec();
+    let mut package_specifiers = parse_package_specifiers(chars)?;
+    let identifier = package_specifiers.pop().unwrap_or_default();
     let simple_class_type_signature = parse_simple_class_type_signature(chars, identifier)?;
     let mut class_type_signature_suffix = Vec::new();
-    loop {
-        match chars.peek() {
-            Some('.') => {
-                chars.next();
-                let identifier = parse_identifier(chars);
-                class_type_signature_suffix
-                    .push(parse_simple_class_type_signature(chars, identifier)?);
-            }
-            Some(';') => {
-                break;
-            }
-            _ => break,
-        }
+    while chars.peek() == Some(&'.') {
+        chars.next();
+        let identifier = parse_identifier(chars)?;
+        class_type_signature_suffix.push(parse_simple_class_type_signature(chars, identifier)?);
     }
     expect_char(chars, ';')?;
     let out = ClassTypeSignature {
@@ -148,140 +232,272 @@ fn parse_simple_class_type_signature(
     })
 }
 
-fn parse_type_arguments(chars: &mut Pchars) -> Result<Vec<ReferenceTypeSignature>> {
+fn parse_type_arguments(chars: &mut Pchars) -> Result<Vec<TypeArgument>> {
     let mut type_arguments = Vec::new();
-    if *chars.peek().unwrap() != '<' {
+    if chars.peek() != Some(&'<') {
         return Ok(type_arguments);
     }
 
     expect_char(chars, '<')?;
-    loop {
-        let c = chars.peek().unwrap();
-        match c {
-            '>' => break,
-            _ => {
-                let type_argument = parse_type_argument(chars)?;
-                type_arguments.push(type_argument);
-            }
-        }
+    while chars.peek() != Some(&'>') {
+        type_arguments.push(parse_type_argument(chars)?);
     }
-
     expect_char(chars, '>')?;
     Ok(type_arguments)
 }
 
-fn parse_type_argument(chars: &mut Pchars) -> Result<ReferenceTypeSignature> {
-    let c = chars.peek().unwrap();
+fn parse_type_argument(chars: &mut Pchars) -> Result<TypeArgument> {
+    let c = chars.peek().ok_or(anyhow!("Unexpected end of signature"))?;
     match c {
         '*' => {
             chars.next();
-            Ok(ReferenceTypeSignature::ArrayTypeSignature(Box::new(
-                JavaTypeSignature::BaseTypeSignature("*".to_string()),
-            )))
+            Ok(TypeArgument::Wildcard)
         }
-        '+' | '-' => {
+        '+' => {
             chars.next();
-            let reference_type_signature = parse_reference_type_signature(chars)?;
-            Ok(reference_type_signature)
+            Ok(TypeArgument::Extends(parse_reference_type_signature(
+                chars,
+            )?))
         }
-        _ => {
-            let reference_type_signature = parse_reference_type_signature(chars)?;
-            Ok(reference_type_signature)
+        '-' => {
+            chars.next();
+            Ok(TypeArgument::Super(parse_reference_type_signature(chars)?))
         }
+        _ => Ok(TypeArgument::Exact(parse_reference_type_signature(chars)?)),
     }
 }
 
-fn parse_package_specifiers(chars: &mut Pchars) -> Vec<String> {
-    let mut package_specifiers = Vec::new();
-    package_specifiers.push(parse_identifier(chars));
-    loop {
-        let c = chars.peek().unwrap();
-        match c {
-            '/' => {
-                chars.next();
-                package_specifiers.push(parse_identifier(chars));
-            }
-            _ => break,
+fn parse_type_parameters(chars: &mut Pchars) -> Result<Vec<TypeParameter>> {
+    let mut type_parameters = Vec::new();
+    if chars.peek() != Some(&'<') {
+        return Ok(type_parameters);
+    }
+
+    expect_char(chars, '<')?;
+    while chars.peek() != Some(&'>') {
+        let identifier = parse_identifier(chars)?;
+        expect_char(chars, ':')?;
+        // the class bound is empty when there are only interface bounds
+        let class_bound = match chars.peek() {
+            Some(':') => None,
+            _ => Some(parse_reference_type_signature(chars)?),
+        };
+        let mut interface_bounds = Vec::new();
+        while chars.peek() == Some(&':') {
+            chars.next();
+            interface_bounds.push(parse_reference_type_signature(chars)?);
         }
+        type_parameters.push(TypeParameter {
+            identifier,
+            class_bound,
+            interface_bounds,
+        });
+    }
+    expect_char(chars, '>')?;
+    Ok(type_parameters)
+}
+
+fn parse_package_specifiers(chars: &mut Pchars) -> Result<Vec<String>> {
+    let mut package_specifiers = vec![parse_identifier(chars)?];
+    while chars.peek() == Some(&'/') {
+        chars.next();
+        package_specifiers.push(parse_identifier(chars)?);
     }
-    package_specifiers
+    Ok(package_specifiers)
 }
 
-fn parse_identifier(chars: &mut Pchars) -> String {
+fn parse_identifier(chars: &mut Pchars) -> Result<String> {
     let mut identifier = String::new();
-    loop {
-        let c = chars.peek().unwrap();
+    while let Some(c) = chars.peek() {
         match c {
-            '/' | ';' | '<' | '>' | ':' | '[' => {
-                break;
-            }
-
+            '/' | ';' | '<' | '>' | ':' | '[' | '.' => break,
             _ => identifier.push(*c),
         }
         chars.next();
     }
-    identifier
+    if identifier.is_empty() {
+        return Err(anyhow!("Expected an identifier"));
+    }
+    Ok(identifier)
+}
+
+/// Source form of a binary class name, `java/util/Map$Entry` as `java.util.Map$Entry`
+/// like javap prints it
+fn binary_to_javap(name: &str) -> String {
+    name.replace('/', ".")
 }
 
 impl JavaTypeSignature {
+    #[allow(clippy::inherent_to_string)]
     pub fn to_string(&self) -> String {
+        self.to_source(&binary_to_javap)
+    }
+
+    /// The type with every class name mapped by `class_name` from its binary name,
+    /// e.g. to print imported classes by their simple name
+    pub fn to_source(&self, class_name: &dyn Fn(&str) -> String) -> String {
         match self {
-            JavaTypeSignature::ReferenceTypeSignature(r) => r.to_string(),
+            JavaTypeSignature::ReferenceTypeSignature(r) => r.to_source(class_name),
             JavaTypeSignature::BaseTypeSignature(b) => b.to_string(),
         }
     }
 }
 
 impl ReferenceTypeSignature {
+    #[allow(clippy::inherent_to_string)]
     pub fn to_string(&self) -> String {
+        self.to_source(&binary_to_javap)
+    }
+
+    pub fn to_source(&self, class_name: &dyn Fn(&str) -> String) -> String {
         match self {
-            ReferenceTypeSignature::ClassTypeSignature(c) => c.to_string(),
+            ReferenceTypeSignature::ClassTypeSignature(c) => c.to_source(class_name),
             ReferenceTypeSignature::TypeVariableSignature(t) => t.to_string(),
-            ReferenceTypeSignature::ArrayTypeSignature(a) => a.to_string(),
+            ReferenceTypeSignature::ArrayTypeSignature(a) => {
+                format!("{}[]", a.to_source(class_name))
+            }
+        }
+    }
+
+    /// Whether this is `java.lang.Object`, the bound type parameters don't print
+    fn is_object(&self) -> bool {
+        match self {
+            ReferenceTypeSignature::ClassTypeSignature(c) => {
+                c.package_specifiers == ["java", "lang"]
+                    && c.simple_class_type_signature.identifier == "Object"
+                    && c.class_type_signature_suffix.is_empty()
+            }
+            _ => false,
         }
     }
 }
 
 impl ClassTypeSignature {
+    #[allow(clippy::inherent_to_string)]
     pub fn to_string(&self) -> String {
-        let mut out = String::new();
-        out.push_str(&self.package_specifiers.join("."));
-        if self.package_specifiers.len() > 0 {
-            out.push_str(".");
+        self.to_source(&binary_to_javap)
+    }
+
+    /// Binary name of the outermost class, e.g. `java/util/Map$Entry`
+    pub fn binary_name(&self) -> String {
+        let mut name = self.package_specifiers.join("/");
+        if !self.package_specifiers.is_empty() {
+            name.push('/');
         }
-        out.push_str(&self.simple_class_type_signature.to_string());
+        name.push_str(&self.simple_class_type_signature.identifier);
+        name
+    }
+
+    pub fn to_source(&self, class_name: &dyn Fn(&str) -> String) -> String {
+        let mut out = class_name(&self.binary_name());
+        out.push_str(&type_arguments_to_source(
+            &self.simple_class_type_signature.type_arguments,
+            class_name,
+        ));
         for suffix in &self.class_type_signature_suffix {
-            out.push_str(".");
-            out.push_str(&suffix.to_string());
+            out.push('.');
+            out.push_str(&suffix.to_source(class_name));
         }
         out
     }
 }
 
 impl SimpleClassTypeSignature {
-    pub fn to_string(&self) -> String {
-        let mut out = String::new();
-        out.push_str(&self.identifier);
-        if self.type_arguments.len() == 0 {
-            return out;
+    pub fn to_source(&self, class_name: &dyn Fn(&str) -> String) -> String {
+        let mut out = self.identifier.clone();
+        out.push_str(&type_arguments_to_source(&self.type_arguments, class_name));
+        out
+    }
+}
+
+fn type_arguments_to_source(
+    type_arguments: &[TypeArgument],
+    class_name: &dyn Fn(&str) -> String,
+) -> String {
+    if type_arguments.is_empty() {
+        return String::new();
+    }
+    let type_arguments = type_arguments
+        .iter()
+        .map(|t| t.to_source(class_name))
+        .collect::<Vec<String>>()
+        .join(", ");
+    format!("<{}>", type_arguments)
+}
+
+impl TypeArgument {
+    pub fn to_source(&self, class_name: &dyn Fn(&str) -> String) -> String {
+        match self {
+            TypeArgument::Wildcard => "?".to_string(),
+            TypeArgument::Extends(t) => format!("? extends {}", t.to_source(class_name)),
+            TypeArgument::Super(t) => format!("? super {}", t.to_source(class_name)),
+            TypeArgument::Exact(t) => t.to_source(class_name),
+        }
+    }
+}
+
+/// As javap prints it, without an `Object` class bound: `T extends Comparable<T>` for
+/// `T:Ljava/lang/Object;:Ljava/lang/Comparable<TT;>;`
+impl fmt::Display for TypeParameter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.with_bounds(&binary_to_javap, false))
+    }
+}
+
+impl TypeParameter {
+    /// `T` for `T:Ljava/lang/Object;`, otherwise `T extends A & B`. An `Object` class
+    /// bound before interface bounds is kept, it is the erasure of `T`.
+    pub fn to_source(&self, class_name: &dyn Fn(&str) -> String) -> String {
+        self.with_bounds(class_name, true)
+    }
+
+    fn with_bounds(&self, class_name: &dyn Fn(&str) -> String, object_bound: bool) -> String {
+        let class_bound = self
+            .class_bound
+            .as_ref()
+            .filter(|b| !b.is_object() || (object_bound && !self.interface_bounds.is_empty()));
+        let bounds = class_bound
+            .into_iter()
+            .chain(&self.interface_bounds)
+            .collect::<Vec<_>>();
+        if bounds.is_empty() {
+            return self.identifier.clone();
         }
-        out.push_str("<");
-        let type_arguments = self
-            .type_arguments
+        let bounds = bounds
             .iter()
-            .map(|t| t.to_string())
+            .map(|b| b.to_source(class_name))
             .collect::<Vec<String>>()
-            .join("");
-        out.push_str(&type_arguments);
-        out.push_str(">");
-        out
+            .join(" & ");
+        format!("{} extends {}", self.identifier, bounds)
+    }
+}
+
+/// `<T, U extends Number>`, empty without type parameters
+pub fn type_parameters_to_string(type_parameters: &[TypeParameter]) -> String {
+    if type_parameters.is_empty() {
+        return String::new();
     }
+    let type_parameters = type_parameters
+        .iter()
+        .map(|t| t.to_string())
+        .collect::<Vec<String>>()
+        .join(", ");
+    format!("<{}>", type_parameters)
 }
 
-impl ToString for JavaTypeSignature {
-    fn to_string(&self) -> String {
-        self.to_string()
+pub fn type_parameters_to_source(
+    type_parameters: &[TypeParameter],
+    class_name: &dyn Fn(&str) -> String,
+) -> String {
+    if type_parameters.is_empty() {
+        return String::new();
     }
+    let type_parameters = type_parameters
+        .iter()
+        .map(|t| t.to_source(class_name))
+        .collect::<Vec<String>>()
+        .join(", ");
+    format!("<{}>", type_parameters)
 }
 
 ///
@@ -317,6 +533,29 @@ impl ToString for JavaTypeSignature {
 /// T Identifier ;
 /// ArrayTypeSignature:
 /// [ JavaTypeSignature
+///
+///ClassSignature:
+/// [TypeParameters] SuperclassSignature {SuperinterfaceSignature}
+///
+///TypeParameters:
+/// < TypeParameter {TypeParameter} >
+///
+///TypeParameter:
+/// Identifier ClassBound {InterfaceBound}
+///
+///ClassBound:
+/// : [ReferenceTypeSignature]
+///
+///InterfaceBound:
+/// : ReferenceTypeSignature
+///
+///MethodSignature:
+/// [TypeParameters] ( {JavaTypeSignature} ) Result {ThrowsSignature}
+///
+///ThrowsSignature:
+/// ^ ClassTypeSignature
+/// ^ TypeVariableSignature
+#[cfg(test)]
 mod tests {
     use super::*;
 
@@ -335,7 +574,62 @@ mod tests {
 
     signature_print_tests! {
         enum_sig, "Ljava/lang/Enum<LEnumTest;>;", "java.lang.Enum<EnumTest>",
-        //enum_constructor_sig, "(Ljava/lang/String;Ljava/lang/String;)V", "(java.lang.String, java.lang.String)",
+        wildcards_sig, "Ljava/util/Map<*+TT;-[I>;", "java.util.Map<?, ? extends T, ? super int[]>",
+        inner_sig, "LOuter<TT;>.Inner<Ljava/lang/String;>;", "Outer<T>.Inner<java.lang.String>",
+        type_variable_array_sig, "[[TT;", "T[][]",
+    }
+
+    #[test]
+    fn class_signature() {
+        let signature = parse_class_signature(
+            "<T::Ljava/lang/Comparable<-TT;>;U:Ljava/lang/Object;>Ljava/util/AbstractList<TT;>;Ljava/lang/Runnable;",
+        )
+        .unwrap();
+        assert_eq!(
+            type_parameters_to_string(&signature.type_parameters),
+            "<T extends java.lang.Comparable<? super T>, U>"
+        );
+        assert_eq!(
+            signature.superclass.to_string(),
+            "java.util.AbstractList<T>"
+        );
+        assert_eq!(signature.superinterfaces.len(), 1);
+    }
+
+    #[test]
+    fn method_signature() {
+        let signature = parse_method_signature(
+            "<R:Ljava/lang/Number;:Ljava/lang/Runnable;>(Ljava/util/List<-TR;>;[TT;)TR;^TE;",
+        )
+        .unwrap();
+        assert_eq!(
+            type_parameters_to_string(&signature.type_parameters),
+            "<R extends java.lang.Number & java.lang.Runnable>"
+        );
+        let parameters = signature
+            .parameters
+            .iter()
+            .map(|p| p.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(parameters, ["java.util.List<? super R>", "T[]"]);
+        assert_eq!(signature.result.to_string(), "R");
+        assert_eq!(signature.throws[0].to_string(), "E");
+
+        // javap leaves out the `Object` bound, the source keeps it for the erasure
+        let signature = parse_method_signature(
+            "<T:Ljava/lang/Object;:Ljava/lang/Comparable<-TT;>;>(Ljava/util/Collection<+TT;>;)TT;",
+        )
+        .unwrap();
+        assert_eq!(
+            type_parameters_to_string(&signature.type_parameters),
+            "<T extends java.lang.Comparable<? super T>>"
+        );
+        assert_eq!(
+            type_parameters_to_source(&signature.type_parameters, &binary_to_javap),
+            "<T extends java.lang.Object & java.lang.Comparable<? super T>>"
+        );
 
+        assert!(parse_method_signature("(I").is_err());
+        assert!(parse_signature("Ljava/lang/String;X").is_err());
     }
 }
@@ -0,0 +1,78 @@
+use crate::class_file::Source;
+use anyhow::{anyhow, Result};
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::Read;
+use std::time::UNIX_EPOCH;
+use zip::ZipArchive;
+
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// The header `javap -sysinfo` prints before the class: where the class file is, when it was
+/// last modified, its size and its SHA-256 checksum.
+///
+/// The modification time of files is in UTC, javap uses the local time zone.
+pub fn jaustp_sysinfo(source: &Source) -> Result<String> {
+    let (location, (year, month, day), bytes) = match source {
+        Source::File(path) => {
+            let bytes = std::fs::read(path)?;
+            let modified = std::fs::metadata(path)?
+                .modified()?
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs());
+            let path = std::fs::canonicalize(path)?;
+            (path.display().to_string(), civil_date(modified), bytes)
+        }
+        Source::JarEntry { jar, entry } => {
+            let mut archive = ZipArchive::new(File::open(jar)?)?;
+            let mut file = archive.by_name(entry)?;
+            let modified = file
+                .last_modified()
+                .ok_or_else(|| anyhow!("{} has no modification time", source))?;
+            let mut bytes = Vec::new();
+            file.read_to_end(&mut bytes)?;
+            let jar = std::fs::canonicalize(jar)?;
+            (
+                format!("jar:file:{}!/{}", jar.display(), entry),
+                (
+                    modified.year() as i64,
+                    modified.month() as usize,
+                    modified.day() as u32,
+                ),
+                bytes,
+            )
+        }
+    };
+
+    let checksum = Sha256::digest(&bytes)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<String>();
+    Ok(format!(
+        "Classfile {}\n  Last modified {} {}, {}; size {} bytes\n  SHA-256 checksum {}\n",
+        location,
+        MONTHS[month - 1],
+        day,
+        year,
+        bytes.len(),
+        checksum
+    ))
+}
+
+/// Year, month (1-12) and day of the UTC date `seconds` after the unix epoch, using
+/// Howard Hinnant's `civil_from_days`
+fn civil_date(seconds: u64) -> (i64, usize, u32) {
+    let days = (seconds / 86400) as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as usize;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
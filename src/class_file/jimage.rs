@@ -0,0 +1,171 @@
+//! Reads resources from a jimage, the `lib/modules` file the JDK stores its classes in
+//! since Java 9. The format is not specified, this follows `jdk.internal.jimage`:
+//!
+//! ```text
+//! header       magic 0xCAFEDADA, version, flags, resource count, table length,
+//!              locations size, strings size (u32 each, native byte order)
+//! redirect     i32[table length]   perfect hash of the resource names
+//! offsets      u32[table length]   offset of the location of each resource
+//! locations    attribute streams (module, parent, base, extension, offset, sizes)
+//! strings      NUL terminated names referenced by the locations
+//! resources    the content of the resources
+//! ```
+
+use anyhow::{anyhow, Result};
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+const MAGIC: u32 = 0xCAFE_DADA;
+const HEADER_SIZE: usize = 7 * 4;
+const HASH_MULTIPLIER: u32 = 0x0100_0193;
+
+const ATTRIBUTE_END: u8 = 0;
+const ATTRIBUTE_MODULE: u8 = 1;
+const ATTRIBUTE_PARENT: u8 = 2;
+const ATTRIBUTE_BASE: u8 = 3;
+const ATTRIBUTE_EXTENSION: u8 = 4;
+const ATTRIBUTE_OFFSET: u8 = 5;
+const ATTRIBUTE_COMPRESSED: u8 = 6;
+const ATTRIBUTE_UNCOMPRESSED: u8 = 7;
+
+pub struct JImage {
+    file: RefCell<File>,
+    /// header, redirect and offsets tables, locations and strings
+    index: Vec<u8>,
+    table_length: usize,
+    locations_start: usize,
+    strings_start: usize,
+}
+
+impl JImage {
+    pub fn open(path: &Path) -> Result<JImage> {
+        let mut file = File::open(path)?;
+        let mut header = [0; HEADER_SIZE];
+        file.read_exact(&mut header)?;
+        let field = |i: usize| u32::from_ne_bytes(header[i * 4..i * 4 + 4].try_into().unwrap());
+        if field(0) != MAGIC {
+            return Err(anyhow!("{} is not a jimage", path.display()));
+        }
+        let table_length = field(4) as usize;
+        let locations_size = field(5) as usize;
+        let strings_size = field(6) as usize;
+
+        let locations_start = HEADER_SIZE + table_length * 8;
+        let strings_start = locations_start + locations_size;
+        let mut index = vec![0; strings_start + strings_size];
+        index[..HEADER_SIZE].copy_from_slice(&header);
+        file.read_exact(&mut index[HEADER_SIZE..])?;
+
+        Ok(JImage {
+            file: RefCell::new(file),
+            index,
+            table_length,
+            locations_start,
+            strings_start,
+        })
+    }
+
+    /// Content of a resource like `/java.base/java/lang/Object.class`
+    pub fn read(&self, name: &str) -> Result<Option<Vec<u8>>> {
+        let Some(location) = self.find(name) else {
+            return Ok(None);
+        };
+        if location.compressed != 0 {
+            return Err(anyhow!(
+                "Compressed jimage resources are not supported: {}",
+                name
+            ));
+        }
+        let mut content = vec![0; location.uncompressed as usize];
+        let mut file = self.file.borrow_mut();
+        file.seek(SeekFrom::Start(self.index.len() as u64 + location.offset))?;
+        file.read_exact(&mut content)?;
+        Ok(Some(content))
+    }
+
+    fn find(&self, name: &str) -> Option<Location> {
+        if self.table_length == 0 {
+            return None;
+        }
+        let mut index = hash(name, HASH_MULTIPLIER) as usize % self.table_length;
+        let redirect = self.i32_at(HEADER_SIZE + index * 4);
+        match redirect {
+            0 => return None,
+            r if r < 0 => index = (-1 - r) as usize,
+            r => index = hash(name, r as u32) as usize % self.table_length,
+        }
+        let offset = self.i32_at(HEADER_SIZE + self.table_length * 4 + index * 4) as usize;
+        let location = self.location(self.locations_start + offset)?;
+        // the hash is perfect only for the names in the image
+        if location.name != name {
+            return None;
+        }
+        Some(location)
+    }
+
+    fn location(&self, mut position: usize) -> Option<Location> {
+        let mut location = Location::default();
+        let (mut module, mut parent, mut base, mut extension) = ("", "", "", "");
+        loop {
+            let byte = *self.index.get(position)?;
+            let kind = byte >> 3;
+            if kind == ATTRIBUTE_END {
+                break;
+            }
+            let length = (byte & 7) as usize + 1;
+            let bytes = self.index.get(position + 1..position + 1 + length)?;
+            let value = bytes.iter().fold(0u64, |v, b| (v << 8) | *b as u64);
+            position += 1 + length;
+            match kind {
+                ATTRIBUTE_MODULE => module = self.string(value as usize)?,
+                ATTRIBUTE_PARENT => parent = self.string(value as usize)?,
+                ATTRIBUTE_BASE => base = self.string(value as usize)?,
+                ATTRIBUTE_EXTENSION => extension = self.string(value as usize)?,
+                ATTRIBUTE_OFFSET => location.offset = value,
+                ATTRIBUTE_COMPRESSED => location.compressed = value,
+                ATTRIBUTE_UNCOMPRESSED => location.uncompressed = value,
+                _ => {}
+            }
+        }
+        let mut name = String::new();
+        if !module.is_empty() {
+            name.push_str(&format!("/{}/", module));
+        }
+        if !parent.is_empty() {
+            name.push_str(&format!("{}/", parent));
+        }
+        name.push_str(base);
+        if !extension.is_empty() {
+            name.push_str(&format!(".{}", extension));
+        }
+        location.name = name;
+        Some(location)
+    }
+
+    fn string(&self, offset: usize) -> Option<&str> {
+        let bytes = self.index.get(self.strings_start + offset..)?;
+        let end = bytes.iter().position(|b| *b == 0)?;
+        std::str::from_utf8(&bytes[..end]).ok()
+    }
+
+    fn i32_at(&self, position: usize) -> i32 {
+        i32::from_ne_bytes(self.index[position..position + 4].try_into().unwrap())
+    }
+}
+
+#[derive(Debug, Default)]
+struct Location {
+    name: String,
+    offset: u64,
+    compressed: u64,
+    uncompressed: u64,
+}
+
+/// FNV-1 style hash of the UTF-8 bytes, used for the redirect table
+fn hash(name: &str, seed: u32) -> u32 {
+    name.bytes()
+        .fold(seed, |h, b| h.wrapping_mul(HASH_MULTIPLIER) ^ b as u32)
+        & 0x7FFF_FFFF
+}
@@ -1,9 +1,14 @@
 use super::attributes::Attribute;
 use super::attributes::Attributes;
 use super::attributes::CodeAttribute;
+use super::attributes::{Frame, StackMapTableAttribute, VerificationType};
+use super::cfg::ControlFlowGraph;
 use super::constant_pool::ConstantPool;
 use super::file_reader::FileReader;
-use anyhow::Result;
+use super::file_writer::FileWriter;
+use super::frames::{self, ClassHierarchy, ComputedFrames};
+use super::{MethodDescriptor, Parsing, Visibility};
+use anyhow::{anyhow, Result};
 
 #[derive(Debug)]
 pub struct Methods {
@@ -22,6 +27,11 @@ pub struct Method {
 pub struct AccessFlags(u16);
 
 #[derive(Debug, PartialEq)]
+#[cfg_attr(
+    feature = "json",
+    derive(serde::Serialize),
+    serde(rename_all = "lowercase")
+)]
 pub enum AccessFlag {
     Public,
     Private,
@@ -38,7 +48,7 @@ pub enum AccessFlag {
 }
 
 impl Methods {
-    pub fn from(file: &mut FileReader, cp: &ConstantPool) -> Result<Methods> {
+    pub fn from(file: &mut FileReader, cp: &ConstantPool, parsing: Parsing) -> Result<Methods> {
         let mut methods = Vec::new();
 
         let methods_count = file.read_u2_to_u16()?;
@@ -46,7 +56,7 @@ impl Methods {
             let access_flags = AccessFlags::new(file.read_u2_to_u16()?);
             let name_index = file.read_u2_to_u16()?;
             let descriptor_index = file.read_u2_to_u16()?;
-            let attributes = Attributes::from(file, cp)?;
+            let attributes = Attributes::read(file, cp, parsing)?;
 
             methods.push(Method {
                 access_flags,
@@ -59,6 +69,17 @@ impl Methods {
         Ok(Methods { methods })
     }
 
+    pub fn write(&self, file: &mut FileWriter) -> Result<()> {
+        file.write_count(self.methods.len(), "methods")?;
+        for method in &self.methods {
+            file.write_u2(method.access_flags.bits());
+            file.write_u2(method.name_index);
+            file.write_u2(method.descriptor_index);
+            method.attributes.write(file)?;
+        }
+        Ok(())
+    }
+
     pub fn to_string(&self, cp: &ConstantPool) -> String {
         let mut s = String::new();
         s.push_str("Methods:\n");
@@ -84,13 +105,144 @@ impl Methods {
 
 impl Method {
     pub fn get_code(&self) -> Option<&CodeAttribute> {
-        for attribute in &self.attributes.attributes {
+        for attribute in self.attributes.attributes() {
             if let Attribute::Code(code) = attribute {
                 return Some(code);
             }
         }
         None
     }
+
+    /// The Code attribute, an error if the method has none or its attributes can't be
+    /// decoded
+    pub fn code(&self) -> Result<&CodeAttribute> {
+        self.attributes
+            .decode()?
+            .iter()
+            .find_map(|attribute| match attribute {
+                Attribute::Code(code) => Some(code),
+                _ => None,
+            })
+            .ok_or(anyhow!("Method has no Code attribute"))
+    }
+
+    pub fn is_deprecated(&self) -> bool {
+        self.attributes.is_deprecated()
+    }
+
+    pub fn is_synthetic(&self) -> bool {
+        self.access_flags
+            .flag_vector()
+            .contains(&AccessFlag::Synthetic)
+            || self.attributes.is_synthetic()
+    }
+
+    pub fn is_static(&self) -> bool {
+        self.access_flags
+            .flag_vector()
+            .contains(&AccessFlag::Static)
+    }
+
+    pub fn visibility(&self) -> Visibility {
+        let flags = self.access_flags.flag_vector();
+        Visibility::of(
+            flags.contains(&AccessFlag::Public),
+            flags.contains(&AccessFlag::Protected),
+            flags.contains(&AccessFlag::Private),
+        )
+    }
+
+    pub fn is_abstract(&self) -> bool {
+        self.access_flags
+            .flag_vector()
+            .contains(&AccessFlag::Abstract)
+    }
+
+    /// The implicit frame at pc 0: `this` (uninitialized in constructors) followed by the
+    /// arguments of the method descriptor
+    pub fn initial_frame(&self, class_name: &str, cp: &ConstantPool) -> Result<Frame> {
+        let name = cp.get_to_string(self.name_index);
+        let descriptor = cp.get_to_string(self.descriptor_index);
+
+        let mut locals = Vec::new();
+        if !self.is_static() {
+            if name == "<init>" && class_name != "java/lang/Object" {
+                locals.push(VerificationType::UninitializedThis);
+            } else {
+                locals.push(VerificationType::Object(class_name.to_string()));
+            }
+        }
+        for arg in MethodDescriptor::parse(&descriptor)?.parameters {
+            locals.push(VerificationType::from_field_type(&arg));
+        }
+
+        Ok(Frame {
+            pc: 0,
+            locals,
+            stack: Vec::new(),
+        })
+    }
+
+    /// The StackMapTable of the method expanded to frames with absolute pcs,
+    /// empty for methods without branches
+    pub fn stack_map_frames(&self, class_name: &str, cp: &ConstantPool) -> Result<Vec<Frame>> {
+        let code = self.code()?;
+        match code.attributes().get_stack_map_table() {
+            Some(table) => table.frames(self.initial_frame(class_name, cp)?.locals, cp),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    pub fn control_flow_graph(&self, cp: &ConstantPool) -> Result<ControlFlowGraph> {
+        let code = self.code()?;
+        ControlFlowGraph::build(code, cp)
+    }
+
+    /// Infers the frames, max_stack and max_locals from the instructions of the method
+    pub fn compute_frames(
+        &self,
+        class_name: &str,
+        cp: &ConstantPool,
+        hierarchy: &dyn ClassHierarchy,
+    ) -> Result<ComputedFrames> {
+        frames::compute_frames(self, class_name, cp, hierarchy)
+    }
+
+    /// Recomputes the StackMapTable, max_stack and max_locals of the Code attribute
+    /// after its instructions were modified. Methods without code are left untouched.
+    pub fn update_frames(
+        &mut self,
+        class_name: &str,
+        cp: &mut ConstantPool,
+        hierarchy: &dyn ClassHierarchy,
+    ) -> Result<()> {
+        self.attributes.decode()?;
+        if self.get_code().is_none() {
+            return Ok(());
+        }
+        let computed = self.compute_frames(class_name, cp, hierarchy)?;
+        let table = if computed.frames.is_empty() {
+            None
+        } else {
+            let initial = self.initial_frame(class_name, cp)?;
+            let name_index = cp.add_utf8("StackMapTable")?;
+            Some(StackMapTableAttribute::from_frames(
+                name_index,
+                &initial.locals,
+                &computed.frames,
+                cp,
+            )?)
+        };
+
+        for attribute in self.attributes.attributes_mut()? {
+            if let Attribute::Code(code) = attribute {
+                code.replace_dead_code(&computed.dead_code);
+                code.set_frames(computed.max_stack, computed.max_locals, table)?;
+                break;
+            }
+        }
+        Ok(())
+    }
 }
 
 impl AccessFlags {
@@ -98,6 +250,11 @@ impl AccessFlags {
         AccessFlags(flags)
     }
 
+    /// The flags as written in the class file
+    pub fn bits(&self) -> u16 {
+        self.0
+    }
+
     pub fn flag_vector(&self) -> Vec<AccessFlag> {
         let mut flags = Vec::new();
         add_flag(&mut flags, self.0, 0x0001, AccessFlag::Public);
@@ -27,29 +27,69 @@ ClassFile {
  */
 
 mod access_flags;
+pub mod assembler;
 mod attributes;
+pub mod borrowed;
+pub mod builder;
 mod bytecode;
+mod cfg;
+mod class_path;
 mod constant_pool;
+mod corpus;
+pub mod dataflow;
+pub mod decompiler;
+mod descriptors;
 mod fields;
 mod file_reader;
+mod file_writer;
+mod format_check;
+mod frames;
 mod interfaces;
+pub mod interpreter;
 mod jaustp;
+mod jimage;
 mod methods;
+pub mod runtime;
+mod strip;
+pub mod transform;
+mod verifier;
 
-use access_flags::AccessFlags;
+use access_flags::{AccessFlag, AccessFlags};
 use attributes::Attributes;
-use constant_pool::ConstantPool;
 use fields::Fields;
 use file_reader::FileReader;
+use file_writer::FileWriter;
 use interfaces::Interfaces;
-use methods::Methods;
+use methods::{Method, Methods};
 
 use crate::print_debug as p;
+pub use access_flags::Visibility;
+pub use attributes::ConstantValue;
+pub use attributes::EnclosingMethod;
+pub use attributes::InnerClassInfo;
+pub use attributes::{Annotation, ElementValue, ExceptionTable};
+pub use attributes::{Frame, VerificationType};
+pub use attributes::{Smap, SmapFile, SmapLine, Stratum};
+pub use bytecode::{ByteCode, LookupSwitch, TableSwitch};
+pub use cfg::{BasicBlock, ControlFlowGraph, Edge, EdgeKind};
+pub use class_path::{find_java_home, ClassPath};
+pub use constant_pool::{ConstantPool, FieldRef, InvokeDynamic, MemberRef, MethodHandle};
+pub use constant_pool::{MethodHandleReferenceKind, MethodRef};
+pub use corpus::{parse_all, Parsed, Source};
+pub use descriptors::{BaseType, FieldType, MethodDescriptor};
+pub use format_check::{check_format, FormatError};
+pub use frames::{ClassHierarchy, ClassHierarchyMap, ComputedFrames};
+pub use jaustp::jaustp_cfg;
+#[cfg(feature = "json")]
+pub use jaustp::jaustp_json;
 pub use jaustp::jaustp_summary;
 pub use jaustp::jaustp_summary_print;
+pub use jaustp::jaustp_sysinfo;
 pub use jaustp::JavapOptions;
+pub use strip::{strip, DEBUG_ATTRIBUTES, REQUIRED_ATTRIBUTES};
+pub use verifier::{verify, VerifyError};
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 
 pub struct ClassFile {
     pub minor_version: u16,
@@ -64,11 +104,191 @@ pub struct ClassFile {
     pub attributes: Attributes,
 }
 
+impl ClassFile {
+    /// Binary name of the class, e.g. `com/example/Example`
+    pub fn class_name(&self) -> String {
+        self.constant_pool.get_to_string(self.this_class)
+    }
+
+    /// Binary name of the super class, `None` for `java/lang/Object` (and module-info)
+    pub fn super_class_name(&self) -> Option<String> {
+        match self.super_class {
+            0 => None,
+            index => Some(self.constant_pool.get_to_string(index)),
+        }
+    }
+
+    pub fn is_final(&self) -> bool {
+        self.access_flags.flag_vector().contains(&AccessFlag::Final)
+    }
+
+    pub fn is_interface(&self) -> bool {
+        self.access_flags
+            .flag_vector()
+            .contains(&AccessFlag::Interface)
+    }
+
+    /// Decodes the attribute tables of the class and its members, the error of the first
+    /// one that can't be. Only lazily parsed class files have tables left to decode.
+    pub fn decode_attributes(&self) -> Result<()> {
+        self.attributes.decode()?;
+        for field in &self.fields.fields {
+            field.attributes.decode()?;
+        }
+        for method in &self.methods.methods {
+            method.attributes.decode()?;
+        }
+        Ok(())
+    }
+
+    pub fn find_method(&self, name: &str, descriptor: &str) -> Option<&Method> {
+        self.methods.methods.iter().find(|m| {
+            self.constant_pool.get_to_string(m.name_index) == name
+                && self.constant_pool.get_to_string(m.descriptor_index) == descriptor
+        })
+    }
+
+    pub fn is_sealed(&self) -> bool {
+        self.permitted_subclasses().is_some()
+    }
+
+    /// Binary names of the permitted subclasses, `None` if the class is not sealed
+    pub fn permitted_subclasses(&self) -> Option<Vec<String>> {
+        self.attributes
+            .get_permitted_subclasses(&self.constant_pool)
+    }
+
+    /// A class is `non-sealed` when it is not final, not sealed itself, and one of its direct
+    /// super types lists it as a permitted subclass. This is not recorded in the class file,
+    /// `permitted_subclasses` looks up those of a super type by its binary name.
+    pub fn is_non_sealed(
+        &self,
+        permitted_subclasses: impl Fn(&str) -> Option<Vec<String>>,
+    ) -> bool {
+        if self.is_final() || self.is_sealed() {
+            return false;
+        }
+        let class_name = self.class_name();
+        let super_types = self.super_class_name().into_iter().chain(
+            self.interfaces
+                .interfaces
+                .iter()
+                .map(|i| self.constant_pool.get_to_string(*i)),
+        );
+        for super_type in super_types {
+            if permitted_subclasses(&super_type).is_some_and(|p| p.contains(&class_name)) {
+                return true;
+            }
+        }
+        false
+    }
+
+    pub fn nest_host(&self) -> Option<String> {
+        self.attributes.get_nest_host(&self.constant_pool)
+    }
+
+    pub fn nest_members(&self) -> Vec<String> {
+        self.attributes.get_nest_members(&self.constant_pool)
+    }
+
+    /// Entries of the `InnerClasses` attribute, every nested class referenced by this class
+    pub fn inner_classes(&self) -> Vec<&InnerClassInfo> {
+        match self.attributes.get_inner_classes() {
+            Some(att) => att.classes.iter().collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Maps a binary name like `Outer$Inner` to the source name `Outer.Inner`
+    /// using the `InnerClasses` attribute of this class.
+    pub fn source_name(&self, binary_name: &str) -> String {
+        match self.attributes.get_inner_classes() {
+            Some(att) => att.source_name(binary_name, &self.constant_pool),
+            None => binary_name.replace('/', "."),
+        }
+    }
+
+    /// The source map of the `SourceDebugExtension` attribute (JSP, Kotlin inline functions)
+    pub fn smap(&self) -> Option<Result<Smap>> {
+        self.attributes
+            .get_source_debug_extension()
+            .map(|att| att.smap())
+    }
+
+    /// For local and anonymous classes, the class and method the class was declared in
+    pub fn enclosing_method(&self) -> Option<Result<EnclosingMethod>> {
+        self.attributes
+            .get_enclosing_method()
+            .map(|att| att.resolve(&self.constant_pool))
+    }
+
+    /// The bytes of the class file. A class file that was read and not modified is
+    /// written back byte for byte, except for attribute lengths that didn't match.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut file = FileWriter::new();
+        file.write_bytes(&[0xCA, 0xFE, 0xBA, 0xBE]);
+        file.write_u2(self.minor_version);
+        file.write_u2(self.major_version);
+        self.constant_pool.write(&mut file)?;
+        file.write_u2(self.access_flags.bits());
+        file.write_u2(self.this_class);
+        file.write_u2(self.super_class);
+        self.interfaces.write(&mut file)?;
+        self.fields.write(&mut file)?;
+        self.methods.write(&mut file)?;
+        self.attributes.write(&mut file)?;
+        Ok(file.into_bytes())
+    }
+
+    /// Recomputes the StackMapTable, max_stack and max_locals of every method,
+    /// needed after the bytecode was modified
+    pub fn update_frames(&mut self, hierarchy: &dyn ClassHierarchy) -> Result<()> {
+        let class_name = self.class_name();
+        for method in &mut self.methods.methods {
+            method.update_frames(&class_name, &mut self.constant_pool, hierarchy)?;
+        }
+        Ok(())
+    }
+}
+
+/// When the attributes of a class file (method bodies, annotations, ...) are decoded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Parsing {
+    /// While reading the class file
+    #[default]
+    Eager,
+    /// On first access, reading only keeps their bytes. For scans that only need the
+    /// names and descriptors of classes and their members.
+    Lazy,
+}
+
 pub fn read_class_file(filename: &str) -> Result<ClassFile> {
-    let mut file = FileReader::new(filename)?;
+    read_class_file_with(filename, Parsing::Eager)
+}
+
+pub fn read_class_file_with(filename: &str, parsing: Parsing) -> Result<ClassFile> {
+    parse_class_file(FileReader::new(filename)?, parsing)
+}
 
+/// Parses a class file from its bytes
+pub fn read_class_bytes(bytes: Vec<u8>) -> Result<ClassFile> {
+    read_class_bytes_with(bytes, Parsing::Eager)
+}
+
+pub fn read_class_bytes_with(bytes: Vec<u8>, parsing: Parsing) -> Result<ClassFile> {
+    parse_class_file(FileReader::from_bytes(bytes), parsing)
+}
+
+pub fn write_class_file(cf: &ClassFile, filename: &str) -> Result<()> {
+    std::fs::write(filename, cf.to_bytes()?)
+        .map_err(|e| anyhow!("Could not write {}: {}", filename, e))
+}
+
+fn parse_class_file(mut file: FileReader, parsing: Parsing) -> Result<ClassFile> {
     let magic = file.read_u4()?;
-    assert_eq!(magic, [0xCA, 0xFE, 0xBA, 0xBE]);
+    if magic != [0xCA, 0xFE, 0xBA, 0xBE] {
+        return Err(anyhow!("Not a class file, bad magic {:02x?}", magic));
+    }
 
     let minor_version = file.read_u2_to_u16()?;
     let major_version = file.read_u2_to_u16()?;
@@ -103,18 +323,22 @@ pub fn read_class_file(filename: &str) -> Result<ClassFile> {
     let interfaces = Interfaces::from(&mut file)?;
     p!("{}", interfaces.to_string(&constant_pool));
 
-    let fields = Fields::from(&mut file, &constant_pool)?;
+    let fields = Fields::from(&mut file, &constant_pool, parsing)?;
 
     p!("{}", fields.to_string(&constant_pool));
 
-    let methods = Methods::from(&mut file, &constant_pool)?;
+    let methods = Methods::from(&mut file, &constant_pool, parsing)?;
 
     p!("{}", methods.to_string(&constant_pool));
 
-    let attributes = Attributes::from(&mut file, &constant_pool)?;
+    let attributes = Attributes::read(&mut file, &constant_pool, parsing)?;
 
     p!("{}", attributes.to_string(&constant_pool));
 
+    if !file.at_end()? {
+        return Err(anyhow!("Extra bytes at the end of the class file"));
+    }
+
     Ok(ClassFile {
         minor_version,
         major_version,
@@ -0,0 +1,440 @@
+//! The run-time model of classes (JVMS chapter 5).
+//!
+//! A `ClassLoader` loads classes by their binary name from a `ClassPath` and links them:
+//! the super class and the interfaces are loaded first, then the class gets the tables
+//! the JVM dispatches calls with. The vtable has an entry for every instance method that
+//! can be invoked on the class, methods of a sub class take the entry of the methods they
+//! override (JVMS 5.4.5), and an itable per implemented interface has the method selected
+//! for every method of the interface (JVMS 5.4.6).
+//!
+//! Symbolic references are resolved to methods and fields like JVMS 5.4.3 describes,
+//! looking in the super classes and super interfaces. Access control (JVMS 5.4.4) is not
+//! checked. Failures are `LinkageError`s, named like the errors the JVM throws.
+//!
+//! Loaded classes are numbered, methods and fields are identified by their class and
+//! their index in the `methods` and `fields` of the class file.
+
+mod resolution;
+mod tables;
+
+use super::{fields, methods, ClassFile, ClassPath, Method};
+use anyhow::Result;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+const OBJECT: &str = "java/lang/Object";
+
+/// A loaded class, the index in the loader
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ClassId(usize);
+
+/// A method of a loaded class, `index` is its index in the methods of the class file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MethodId {
+    pub class: ClassId,
+    pub index: usize,
+}
+
+/// A field of a loaded class, `index` is its index in the fields of the class file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FieldId {
+    pub class: ClassId,
+    pub index: usize,
+}
+
+/// The errors of loading, linking and resolution, thrown by the JVM as subclasses of
+/// `java.lang.LinkageError`
+#[derive(Debug, Clone, PartialEq)]
+pub enum LinkageError {
+    NoClassDefFound(String),
+    ClassCircularity(String),
+    IncompatibleClassChange(String),
+    NoSuchField(String),
+    NoSuchMethod(String),
+    AbstractMethod(String),
+}
+
+impl LinkageError {
+    /// Binary name of the error class, e.g. `java/lang/NoSuchMethodError`
+    pub fn class(&self) -> &'static str {
+        match self {
+            LinkageError::NoClassDefFound(_) => "java/lang/NoClassDefFoundError",
+            LinkageError::ClassCircularity(_) => "java/lang/ClassCircularityError",
+            LinkageError::IncompatibleClassChange(_) => "java/lang/IncompatibleClassChangeError",
+            LinkageError::NoSuchField(_) => "java/lang/NoSuchFieldError",
+            LinkageError::NoSuchMethod(_) => "java/lang/NoSuchMethodError",
+            LinkageError::AbstractMethod(_) => "java/lang/AbstractMethodError",
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            LinkageError::NoClassDefFound(m)
+            | LinkageError::ClassCircularity(m)
+            | LinkageError::IncompatibleClassChange(m)
+            | LinkageError::NoSuchField(m)
+            | LinkageError::NoSuchMethod(m)
+            | LinkageError::AbstractMethod(m) => m,
+        }
+    }
+}
+
+/// Formatted like the exception, e.g. `java.lang.NoSuchMethodError: A.m()V`
+impl fmt::Display for LinkageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.class().replace('/', "."), self.message())
+    }
+}
+
+impl std::error::Error for LinkageError {}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Access {
+    Public,
+    Protected,
+    Package,
+    Private,
+}
+
+/// Name, descriptor and flags of a method or field, read once from the constant pool
+#[derive(Debug)]
+struct Member {
+    name: String,
+    descriptor: String,
+    access: Access,
+    is_static: bool,
+    is_abstract: bool,
+}
+
+impl Member {
+    fn is(&self, name: &str, descriptor: &str) -> bool {
+        self.name == name && self.descriptor == descriptor
+    }
+
+    /// Instance methods other than constructors, the methods in vtables and itables
+    fn is_virtual(&self) -> bool {
+        !self.is_static && self.access != Access::Private && !self.name.starts_with('<')
+    }
+}
+
+/// A class or interface after loading and linking
+pub struct RuntimeClass {
+    pub id: ClassId,
+    /// Binary name, e.g. `java/lang/String`
+    pub name: String,
+    pub class_file: Rc<ClassFile>,
+    pub super_class: Option<ClassId>,
+    /// The direct super interfaces
+    pub interfaces: Vec<ClassId>,
+    /// Every super interface, direct or inherited, each one once
+    superinterfaces: Vec<ClassId>,
+    methods: Vec<Member>,
+    fields: Vec<Member>,
+    vtable: Vec<MethodId>,
+    itables: Vec<Itable>,
+    initialization_dependencies: Vec<ClassId>,
+}
+
+/// The methods selected for the methods of an interface, in the order of `itable_methods`
+/// of the interface. `None` if there is no method to select (abstract or conflicting
+/// default methods).
+#[derive(Debug)]
+struct Itable {
+    interface: ClassId,
+    methods: Vec<Option<MethodId>>,
+}
+
+impl RuntimeClass {
+    pub fn is_interface(&self) -> bool {
+        self.class_file.is_interface()
+    }
+
+    /// Every super interface of the class, including those of its super classes
+    pub fn superinterfaces(&self) -> &[ClassId] {
+        &self.superinterfaces
+    }
+
+    pub fn method(&self, index: usize) -> &Method {
+        &self.class_file.methods.methods[index]
+    }
+
+    pub fn method_name(&self, index: usize) -> &str {
+        &self.methods[index].name
+    }
+
+    pub fn method_descriptor(&self, index: usize) -> &str {
+        &self.methods[index].descriptor
+    }
+
+    pub fn field_name(&self, index: usize) -> &str {
+        &self.fields[index].name
+    }
+
+    pub fn field_descriptor(&self, index: usize) -> &str {
+        &self.fields[index].descriptor
+    }
+
+    pub fn is_static_field(&self, index: usize) -> bool {
+        self.fields[index].is_static
+    }
+
+    /// The declared method with the name and descriptor
+    pub fn find_method(&self, name: &str, descriptor: &str) -> Option<MethodId> {
+        self.methods
+            .iter()
+            .position(|m| m.is(name, descriptor))
+            .map(|index| self.method_id(index))
+    }
+
+    /// The instance methods that can be invoked on objects of a class, empty for interfaces
+    pub fn vtable(&self) -> &[MethodId] {
+        &self.vtable
+    }
+
+    /// The methods selected for the methods of the interface (see `itable_methods`),
+    /// `None` if the class doesn't implement the interface
+    pub fn itable(&self, interface: ClassId) -> Option<&[Option<MethodId>]> {
+        self.itables
+            .iter()
+            .find(|itable| itable.interface == interface)
+            .map(|itable| itable.methods.as_slice())
+    }
+
+    /// The methods of an interface that have an entry in the itables of its implementations
+    pub fn itable_methods(&self) -> Vec<MethodId> {
+        (0..self.methods.len())
+            .filter(|index| self.methods[*index].is_virtual())
+            .map(|index| self.method_id(index))
+            .collect()
+    }
+
+    /// The classes and interfaces to initialize before this one (JVMS 5.5): the super class
+    /// and, for classes, the super interfaces that declare default methods
+    pub fn initialization_dependencies(&self) -> &[ClassId] {
+        &self.initialization_dependencies
+    }
+
+    fn method_id(&self, index: usize) -> MethodId {
+        MethodId {
+            class: self.id,
+            index,
+        }
+    }
+
+    /// The run-time package, the binary name without the simple name
+    fn package(&self) -> &str {
+        self.name
+            .rsplit_once('/')
+            .map_or("", |(package, _)| package)
+    }
+}
+
+impl fmt::Debug for RuntimeClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RuntimeClass")
+            .field("id", &self.id)
+            .field("name", &self.name)
+            .field("super_class", &self.super_class)
+            .field("interfaces", &self.interfaces)
+            .finish()
+    }
+}
+
+/// Loads and links classes, each class is loaded once
+pub struct ClassLoader {
+    class_path: ClassPath,
+    classes: RefCell<Vec<Rc<RuntimeClass>>>,
+    ids: RefCell<HashMap<String, ClassId>>,
+    /// Classes whose super classes and interfaces are being loaded
+    loading: RefCell<Vec<String>>,
+}
+
+impl ClassLoader {
+    pub fn new(class_path: ClassPath) -> ClassLoader {
+        ClassLoader {
+            class_path,
+            classes: RefCell::new(Vec::new()),
+            ids: RefCell::new(HashMap::new()),
+            loading: RefCell::new(Vec::new()),
+        }
+    }
+
+    pub fn class_path(&self) -> &ClassPath {
+        &self.class_path
+    }
+
+    pub fn class(&self, id: ClassId) -> Rc<RuntimeClass> {
+        self.classes.borrow()[id.0].clone()
+    }
+
+    /// Loads and links the class with the binary name and its super classes and interfaces
+    pub fn load(&self, name: &str) -> Result<Rc<RuntimeClass>> {
+        if let Some(id) = self.ids.borrow().get(name) {
+            return Ok(self.class(*id));
+        }
+        let cf = self
+            .class_path
+            .load(name)
+            .map_err(|e| e.context(LinkageError::NoClassDefFound(name.to_string())))?;
+        if cf.class_name() != name {
+            let message = format!("{} (wrong name: {})", name, cf.class_name());
+            return Err(LinkageError::NoClassDefFound(message).into());
+        }
+        if self.loading.borrow().iter().any(|c| c == name) {
+            return Err(LinkageError::ClassCircularity(name.to_string()).into());
+        }
+
+        self.loading.borrow_mut().push(name.to_string());
+        let supers = self.load_supers(&cf);
+        self.loading.borrow_mut().pop();
+        let (super_class, interfaces) = supers?;
+
+        let id = ClassId(self.classes.borrow().len());
+        let cp = &cf.constant_pool;
+        let methods = cf
+            .methods
+            .methods
+            .iter()
+            .map(|m| {
+                let flags = m.access_flags.flag_vector();
+                Member {
+                    name: cp.get_to_string(m.name_index),
+                    descriptor: cp.get_to_string(m.descriptor_index),
+                    access: method_access(&flags),
+                    is_static: flags.contains(&methods::AccessFlag::Static),
+                    is_abstract: flags.contains(&methods::AccessFlag::Abstract),
+                }
+            })
+            .collect();
+        let fields = cf
+            .fields
+            .fields
+            .iter()
+            .map(|f| {
+                let flags = f.access_flags.flag_vector();
+                Member {
+                    name: cp.get_to_string(f.name_index),
+                    descriptor: cp.get_to_string(f.descriptor_index),
+                    access: field_access(&flags),
+                    is_static: flags.contains(&fields::AccessFlag::Static),
+                    is_abstract: false,
+                }
+            })
+            .collect();
+        let mut class = RuntimeClass {
+            id,
+            name: name.to_string(),
+            class_file: cf,
+            super_class,
+            interfaces,
+            superinterfaces: Vec::new(),
+            methods,
+            fields,
+            vtable: Vec::new(),
+            itables: Vec::new(),
+            initialization_dependencies: Vec::new(),
+        };
+        self.link(&mut class);
+
+        let class = Rc::new(class);
+        self.classes.borrow_mut().push(class.clone());
+        self.ids.borrow_mut().insert(name.to_string(), id);
+        Ok(class)
+    }
+
+    /// The classes to initialize when the class is initialized, in the order their static
+    /// initializers run (JVMS 5.5), the class last
+    pub fn initialization_order(&self, name: &str) -> Result<Vec<ClassId>> {
+        fn visit(loader: &ClassLoader, class: ClassId, order: &mut Vec<ClassId>) {
+            if order.contains(&class) {
+                return;
+            }
+            for dependency in loader.class(class).initialization_dependencies() {
+                visit(loader, *dependency, order);
+            }
+            order.push(class);
+        }
+        let mut order = Vec::new();
+        visit(self, self.load(name)?.id, &mut order);
+        Ok(order)
+    }
+
+    /// Whether the class is the other class or one of its sub classes or implementations
+    pub fn is_subclass_of(&self, class: ClassId, other: ClassId) -> bool {
+        let mut current = Some(class);
+        while let Some(c) = current {
+            if c == other {
+                return true;
+            }
+            current = self.class(c).super_class;
+        }
+        self.class(class).superinterfaces.contains(&other)
+    }
+
+    /// `Class.method(descriptor)`, e.g. `java/lang/Object.hashCode()I`
+    pub fn method_string(&self, method: MethodId) -> String {
+        let class = self.class(method.class);
+        format!(
+            "{}.{}{}",
+            class.name,
+            class.method_name(method.index),
+            class.method_descriptor(method.index)
+        )
+    }
+
+    fn load_supers(&self, cf: &ClassFile) -> Result<(Option<ClassId>, Vec<ClassId>)> {
+        let name = cf.class_name();
+        let super_class = match cf.super_class_name() {
+            Some(super_name) => {
+                let super_class = self.load(&super_name)?;
+                if super_class.is_interface() {
+                    let message =
+                        format!("class {} has interface {} as super class", name, super_name);
+                    return Err(LinkageError::IncompatibleClassChange(message).into());
+                }
+                Some(super_class.id)
+            }
+            None => None,
+        };
+        let mut interfaces = Vec::new();
+        for index in &cf.interfaces.interfaces {
+            let interface_name = cf.constant_pool.get_to_string(*index);
+            let interface = self.load(&interface_name)?;
+            if !interface.is_interface() {
+                let message = format!(
+                    "class {} can not implement {}, because it is not an interface",
+                    name, interface_name
+                );
+                return Err(LinkageError::IncompatibleClassChange(message).into());
+            }
+            interfaces.push(interface.id);
+        }
+        Ok((super_class, interfaces))
+    }
+}
+
+fn method_access(flags: &[methods::AccessFlag]) -> Access {
+    if flags.contains(&methods::AccessFlag::Public) {
+        Access::Public
+    } else if flags.contains(&methods::AccessFlag::Protected) {
+        Access::Protected
+    } else if flags.contains(&methods::AccessFlag::Private) {
+        Access::Private
+    } else {
+        Access::Package
+    }
+}
+
+fn field_access(flags: &[fields::AccessFlag]) -> Access {
+    if flags.contains(&fields::AccessFlag::Public) {
+        Access::Public
+    } else if flags.contains(&fields::AccessFlag::Protected) {
+        Access::Protected
+    } else if flags.contains(&fields::AccessFlag::Private) {
+        Access::Private
+    } else {
+        Access::Package
+    }
+}
@@ -0,0 +1,211 @@
+//! Resolution of symbolic references to fields and methods (JVMS 5.4.3) and selection of
+//! the method `invokevirtual` and `invokeinterface` run (JVMS 5.4.6)
+
+use super::{Access, ClassId, ClassLoader, FieldId, LinkageError, MethodId, RuntimeClass, OBJECT};
+use anyhow::Result;
+use std::rc::Rc;
+
+impl ClassLoader {
+    /// Resolves a `Methodref`: the method declared by the class or its super classes,
+    /// otherwise a maximally-specific method of its super interfaces (JVMS 5.4.3.3)
+    pub fn resolve_method(&self, class: &str, name: &str, descriptor: &str) -> Result<MethodId> {
+        let class = self.load_for_members(class)?;
+        if class.is_interface() {
+            let message = format!("Found interface {}, but class was expected", class.name);
+            return Err(LinkageError::IncompatibleClassChange(message).into());
+        }
+        let mut current = Some(class.clone());
+        while let Some(c) = current {
+            if let Some(method) = c.find_method(name, descriptor) {
+                return Ok(method);
+            }
+            current = c.super_class.map(|s| self.class(s));
+        }
+        self.resolve_in_superinterfaces(&class, name, descriptor)
+            .ok_or_else(|| no_such_method(&class, name, descriptor))
+    }
+
+    /// Resolves an `InterfaceMethodref`: the method declared by the interface, a public
+    /// method of `java.lang.Object` or a maximally-specific method of its super interfaces
+    /// (JVMS 5.4.3.4)
+    pub fn resolve_interface_method(
+        &self,
+        interface: &str,
+        name: &str,
+        descriptor: &str,
+    ) -> Result<MethodId> {
+        let interface = self.load(interface)?;
+        if !interface.is_interface() {
+            let message = format!("Found class {}, but interface was expected", interface.name);
+            return Err(LinkageError::IncompatibleClassChange(message).into());
+        }
+        if let Some(method) = interface.find_method(name, descriptor) {
+            return Ok(method);
+        }
+        let object = self.load(OBJECT)?;
+        let public_method = object.find_method(name, descriptor).filter(|m| {
+            let member = &object.methods[m.index];
+            member.access == Access::Public && !member.is_static
+        });
+        public_method
+            .or_else(|| self.resolve_in_superinterfaces(&interface, name, descriptor))
+            .ok_or_else(|| no_such_method(&interface, name, descriptor))
+    }
+
+    /// Resolves a `Fieldref`: the field declared by the class, its super interfaces or its
+    /// super classes, in this order (JVMS 5.4.3.2)
+    pub fn resolve_field(&self, class: &str, name: &str, descriptor: &str) -> Result<FieldId> {
+        let class = self.load(class)?;
+        self.find_field(&class, name, descriptor)
+            .ok_or_else(|| LinkageError::NoSuchField(name.to_string()).into())
+    }
+
+    /// The method `invokevirtual` and `invokeinterface` run for a resolved method on an
+    /// object of the class (JVMS 5.4.6): a private method itself, otherwise the entry of
+    /// the method in the vtable or the itable of the class
+    pub fn select_method(&self, class: &str, method: MethodId) -> Result<MethodId> {
+        let resolved = self.class(method.class);
+        let member = &resolved.methods[method.index];
+        if member.is_static {
+            let message = format!("Expected non-static method {}", self.method_string(method));
+            return Err(LinkageError::IncompatibleClassChange(message).into());
+        }
+        if member.access == Access::Private {
+            return Ok(method);
+        }
+
+        let class = self.load_for_members(class)?;
+        let not_implemented = || {
+            let message = format!(
+                "Class {} does not implement the requested {}",
+                class.name, resolved.name
+            );
+            LinkageError::IncompatibleClassChange(message)
+        };
+        let selected = if resolved.is_interface() {
+            let index = resolved
+                .itable_methods()
+                .iter()
+                .position(|m| *m == method)
+                .ok_or_else(not_implemented)?;
+            let itable = class.itable(resolved.id).ok_or_else(not_implemented)?;
+            match itable[index] {
+                Some(selected) => selected,
+                None => return Err(self.missing_implementation(&class, method).into()),
+            }
+        } else {
+            if !self.is_subclass_of(class.id, resolved.id) {
+                return Err(not_implemented().into());
+            }
+            let index = resolved
+                .vtable
+                .iter()
+                .position(|m| *m == method)
+                .ok_or_else(not_implemented)?;
+            class.vtable[index]
+        };
+        if self.is_abstract(selected) {
+            return Err(self.missing_implementation(&class, method).into());
+        }
+        Ok(selected)
+    }
+
+    /// The methods with the name and descriptor declared by the super interfaces that no
+    /// sub interface among them overrides, private and static methods excluded
+    pub fn maximally_specific_methods(
+        &self,
+        superinterfaces: &[ClassId],
+        name: &str,
+        descriptor: &str,
+    ) -> Vec<MethodId> {
+        let candidates = superinterfaces
+            .iter()
+            .filter_map(|interface| {
+                let interface = self.class(*interface);
+                interface.find_method(name, descriptor).filter(|m| {
+                    let member = &interface.methods[m.index];
+                    !member.is_static && member.access != Access::Private
+                })
+            })
+            .collect::<Vec<_>>();
+        candidates
+            .iter()
+            .filter(|method| {
+                !candidates.iter().any(|other| {
+                    other.class != method.class
+                        && self
+                            .class(other.class)
+                            .superinterfaces
+                            .contains(&method.class)
+                })
+            })
+            .copied()
+            .collect()
+    }
+
+    /// The only non-abstract maximally-specific method, otherwise any of them
+    fn resolve_in_superinterfaces(
+        &self,
+        class: &RuntimeClass,
+        name: &str,
+        descriptor: &str,
+    ) -> Option<MethodId> {
+        let methods = self.maximally_specific_methods(&class.superinterfaces, name, descriptor);
+        let mut defaults = methods.iter().filter(|m| !self.is_abstract(**m));
+        match (defaults.next(), defaults.next()) {
+            (Some(method), None) => Some(*method),
+            _ => methods.first().copied(),
+        }
+    }
+
+    fn find_field(&self, class: &RuntimeClass, name: &str, descriptor: &str) -> Option<FieldId> {
+        let declared = class.fields.iter().position(|f| f.is(name, descriptor));
+        if let Some(index) = declared {
+            return Some(FieldId {
+                class: class.id,
+                index,
+            });
+        }
+        class
+            .interfaces
+            .iter()
+            .chain(class.super_class.iter())
+            .find_map(|c| self.find_field(&self.class(*c), name, descriptor))
+    }
+
+    /// The error of selecting an interface method without implementation: several
+    /// maximally-specific default methods or none at all
+    fn missing_implementation(&self, class: &RuntimeClass, method: MethodId) -> LinkageError {
+        let resolved = self.class(method.class);
+        let name = resolved.method_name(method.index);
+        let descriptor = resolved.method_descriptor(method.index);
+        let defaults = self
+            .maximally_specific_methods(&class.superinterfaces, name, descriptor)
+            .into_iter()
+            .filter(|m| !self.is_abstract(*m))
+            .map(|m| self.method_string(m))
+            .collect::<Vec<_>>();
+        if defaults.len() > 1 {
+            let message = format!("Conflicting default methods: {}", defaults.join(" "));
+            return LinkageError::IncompatibleClassChange(message);
+        }
+        let message = format!(
+            "Receiver class {} does not define or inherit an implementation of the resolved method {}",
+            class.name,
+            self.method_string(method)
+        );
+        LinkageError::AbstractMethod(message)
+    }
+
+    /// Arrays have the members of `java.lang.Object`
+    fn load_for_members(&self, class: &str) -> Result<Rc<RuntimeClass>> {
+        match class.starts_with('[') {
+            true => self.load(OBJECT),
+            false => self.load(class),
+        }
+    }
+}
+
+fn no_such_method(class: &RuntimeClass, name: &str, descriptor: &str) -> anyhow::Error {
+    LinkageError::NoSuchMethod(format!("{}.{}{}", class.name, name, descriptor)).into()
+}
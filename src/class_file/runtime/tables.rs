@@ -0,0 +1,163 @@
+//! Linking a loaded class: its super interfaces, vtable, itables and the classes to
+//! initialize before it
+
+use super::{Access, ClassId, ClassLoader, Itable, Member, MethodId, RuntimeClass};
+
+impl ClassLoader {
+    /// Fills the tables of a class whose super classes and interfaces are loaded
+    pub(super) fn link(&self, class: &mut RuntimeClass) {
+        class.superinterfaces = self.collect_superinterfaces(class);
+        class.initialization_dependencies = self.collect_initialization_dependencies(class);
+        if class.is_interface() {
+            return;
+        }
+        class.vtable = self.build_vtable(class);
+        class.itables = class
+            .superinterfaces
+            .iter()
+            .map(|interface| Itable {
+                interface: *interface,
+                methods: self
+                    .class(*interface)
+                    .itable_methods()
+                    .into_iter()
+                    .map(|method| self.select_interface_method(class, method))
+                    .collect(),
+            })
+            .collect();
+    }
+
+    /// The direct super interfaces with their super interfaces, then those of the super class
+    fn collect_superinterfaces(&self, class: &RuntimeClass) -> Vec<ClassId> {
+        let mut superinterfaces = Vec::new();
+        let mut add = |interface: ClassId| {
+            if !superinterfaces.contains(&interface) {
+                superinterfaces.push(interface);
+            }
+        };
+        for interface in &class.interfaces {
+            add(*interface);
+            self.class(*interface)
+                .superinterfaces
+                .iter()
+                .for_each(|i| add(*i));
+        }
+        if let Some(super_class) = class.super_class {
+            self.class(super_class)
+                .superinterfaces
+                .iter()
+                .for_each(|i| add(*i));
+        }
+        superinterfaces
+    }
+
+    /// JVMS 5.5 step 7: the super class of a class, then its super interfaces that declare
+    /// a non-abstract instance method, each after its own super interfaces in the order of
+    /// the `interfaces` of the class. Interfaces don't initialize their super interfaces.
+    fn collect_initialization_dependencies(&self, class: &RuntimeClass) -> Vec<ClassId> {
+        fn visit(loader: &ClassLoader, interface: ClassId, dependencies: &mut Vec<ClassId>) {
+            let interface = loader.class(interface);
+            for superinterface in &interface.interfaces {
+                visit(loader, *superinterface, dependencies);
+            }
+            let has_default = interface
+                .methods
+                .iter()
+                .any(|m| !m.is_abstract && !m.is_static);
+            if has_default && !dependencies.contains(&interface.id) {
+                dependencies.push(interface.id);
+            }
+        }
+
+        if class.is_interface() {
+            return Vec::new();
+        }
+        let mut dependencies = Vec::from_iter(class.super_class);
+        for interface in &class.interfaces {
+            visit(self, *interface, &mut dependencies);
+        }
+        dependencies
+    }
+
+    /// The vtable of the super class with the methods the class overrides replaced and its
+    /// other instance methods appended
+    fn build_vtable(&self, class: &RuntimeClass) -> Vec<MethodId> {
+        let mut vtable = match class.super_class {
+            Some(super_class) => self.class(super_class).vtable.clone(),
+            None => Vec::new(),
+        };
+        for (index, method) in class.methods.iter().enumerate() {
+            if !method.is_virtual() {
+                continue;
+            }
+            let mut overrides = false;
+            // the methods of the class don't override each other
+            for entry in vtable.iter_mut().filter(|entry| entry.class != class.id) {
+                let declaring = self.class(entry.class);
+                if can_override(class, method, &declaring, &declaring.methods[entry.index]) {
+                    *entry = class.method_id(index);
+                    overrides = true;
+                }
+            }
+            if !overrides {
+                vtable.push(class.method_id(index));
+            }
+        }
+        vtable
+    }
+
+    /// JVMS 5.4.6 for an interface method: the method of the class or its super classes
+    /// that overrides it, otherwise the only maximally-specific default method
+    fn select_interface_method(&self, class: &RuntimeClass, method: MethodId) -> Option<MethodId> {
+        let interface = self.class(method.class);
+        let name = interface.method_name(method.index);
+        let descriptor = interface.method_descriptor(method.index);
+        self.find_instance_method(class, name, descriptor)
+            .or_else(|| {
+                match self.maximally_specific_methods(&class.superinterfaces, name, descriptor)[..]
+                {
+                    [method] if !self.is_abstract(method) => Some(method),
+                    _ => None,
+                }
+            })
+    }
+
+    /// The first non-private instance method with the name and descriptor declared by the
+    /// class or a super class
+    pub(super) fn find_instance_method(
+        &self,
+        class: &RuntimeClass,
+        name: &str,
+        descriptor: &str,
+    ) -> Option<MethodId> {
+        let declared = class
+            .methods
+            .iter()
+            .position(|m| m.is(name, descriptor) && m.is_virtual());
+        if let Some(index) = declared {
+            return Some(class.method_id(index));
+        }
+        let super_class = self.class(class.super_class?);
+        self.find_instance_method(&super_class, name, descriptor)
+    }
+
+    pub(super) fn is_abstract(&self, method: MethodId) -> bool {
+        self.class(method.class).methods[method.index].is_abstract
+    }
+}
+
+/// JVMS 5.4.5: a method overrides a method with the same name and descriptor which is
+/// public, protected or package private in the same run-time package
+fn can_override(
+    class: &RuntimeClass,
+    method: &Member,
+    declaring: &RuntimeClass,
+    overridden: &Member,
+) -> bool {
+    overridden.is(&method.name, &method.descriptor)
+        && match overridden.access {
+            Access::Public | Access::Protected => true,
+            Access::Package => class.package() == declaring.package(),
+            Access::Private => false,
+        }
+}
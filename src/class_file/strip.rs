@@ -0,0 +1,96 @@
+//! Removes attributes from class files and drops the constant pool entries only they used
+
+use crate::class_file::attributes::{Attribute, Attributes};
+use crate::class_file::constant_pool::ConstantPool;
+use crate::class_file::ClassFile;
+use anyhow::{anyhow, Result};
+
+/// The attributes `jaust strip` removes by default: debug information and the annotations
+/// the JVM doesn't keep at run time
+pub const DEBUG_ATTRIBUTES: &[&str] = &[
+    "LineNumberTable",
+    "LocalVariableTable",
+    "LocalVariableTypeTable",
+    "SourceFile",
+    "SourceDebugExtension",
+    "RuntimeInvisibleAnnotations",
+    "RuntimeInvisibleParameterAnnotations",
+    "RuntimeInvisibleTypeAnnotations",
+];
+
+/// Attributes the JVM needs to load, link or verify a class, `strip` refuses to remove them
+pub const REQUIRED_ATTRIBUTES: &[&str] = &[
+    "Code",
+    "StackMapTable",
+    "BootstrapMethods",
+    "ConstantValue",
+    "NestHost",
+    "NestMembers",
+    "PermittedSubclasses",
+    "Module",
+];
+
+/// Removes the attributes named `names` from the class, its fields, methods, code and
+/// record components, then drops the constant pool entries nothing refers to anymore and
+/// renumbers the rest. Fails for the attributes the JVM needs and for classes with
+/// attributes whose constant pool references are unknown.
+pub fn strip(class: &mut ClassFile, names: &[&str]) -> Result<()> {
+    if let Some(name) = names.iter().find(|n| REQUIRED_ATTRIBUTES.contains(n)) {
+        return Err(anyhow!("{} attributes can't be stripped", name));
+    }
+
+    let cp = class.constant_pool.clone();
+    let mut keep = |att: &Attribute| Ok(!names.contains(&att.try_name(&cp)?));
+    remove_attributes(&mut class.attributes, &mut keep)?;
+    for field in &mut class.fields.fields {
+        remove_attributes(&mut field.attributes, &mut keep)?;
+    }
+    for method in &mut class.methods.methods {
+        remove_attributes(&mut method.attributes, &mut keep)?;
+    }
+
+    let mut used = Vec::new();
+    constant_indices(class, &cp, &mut |index| used.push(*index))?;
+    let (compacted, map) = cp.compact(used)?;
+    constant_indices(class, &cp, &mut |index| *index = map[*index as usize])?;
+    class.constant_pool = compacted;
+    Ok(())
+}
+
+fn remove_attributes(
+    attributes: &mut Attributes,
+    keep: &mut dyn FnMut(&Attribute) -> Result<bool>,
+) -> Result<()> {
+    attributes.retain(keep)?;
+    for att in attributes.attributes_mut()? {
+        match att {
+            Attribute::Code(code) => code.retain_attributes(keep)?,
+            Attribute::RecordAttribute(record) => record.retain_attributes(keep)?,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Passes every constant pool index of the class outside the pool itself to `f`
+fn constant_indices(
+    class: &mut ClassFile,
+    cp: &ConstantPool,
+    f: &mut dyn FnMut(&mut u16),
+) -> Result<()> {
+    f(&mut class.this_class);
+    // 0 for java/lang/Object and module-info, which compact and the map both keep at 0
+    f(&mut class.super_class);
+    class.interfaces.interfaces.iter_mut().for_each(&mut *f);
+    for field in &mut class.fields.fields {
+        f(&mut field.name_index);
+        f(&mut field.descriptor_index);
+        field.attributes.constant_indices(cp, f)?;
+    }
+    for method in &mut class.methods.methods {
+        f(&mut method.name_index);
+        f(&mut method.descriptor_index);
+        method.attributes.constant_indices(cp, f)?;
+    }
+    class.attributes.constant_indices(cp, f)
+}
@@ -0,0 +1,505 @@
+//! Rewrites class files with visitors, in the spirit of ASM's `ClassVisitor` and
+//! `MethodVisitor`.
+//!
+//! ```
+//! use jaust::class_file::builder::{ClassBuilder, CodeBuilder, Constant, ACC_PUBLIC, ACC_STATIC};
+//! use jaust::class_file::transform::{self, ClassVisitor, Instruction, Member};
+//! use jaust::class_file::transform::{MethodVisit, MethodVisitor};
+//! use jaust::class_file::{ClassHierarchyMap, ConstantPool};
+//!
+//! /// Removes the calls to `println(String)`, their receiver and argument are popped
+//! struct NoPrintln;
+//!
+//! impl ClassVisitor for NoPrintln {
+//!     fn visit_method(&mut self, _method: &mut Member) -> MethodVisit<'_> {
+//!         MethodVisit::Visit(Box::new(NoPrintln))
+//!     }
+//! }
+//!
+//! impl MethodVisitor for NoPrintln {
+//!     fn visit_instruction(
+//!         &mut self,
+//!         code: &mut CodeBuilder,
+//!         instruction: Instruction,
+//!         cp: &ConstantPool,
+//!     ) {
+//!         match instruction.method_ref(cp) {
+//!             Some(m) if m.name == "println" && m.descriptor == "(Ljava/lang/String;)V" => {
+//!                 code.pop().pop();
+//!             }
+//!             _ => {
+//!                 code.emit(instruction);
+//!             }
+//!         }
+//!     }
+//! }
+//!
+//! let mut main = CodeBuilder::new();
+//! main.getstatic("java/lang/System", "out", "Ljava/io/PrintStream;")
+//!     .ldc(Constant::String("Hello".to_string()))
+//!     .invokevirtual("java/io/PrintStream", "println", "(Ljava/lang/String;)V")
+//!     .return_();
+//! let hierarchy = ClassHierarchyMap::new();
+//! let class = ClassBuilder::new("Hello")
+//!     .method(ACC_PUBLIC | ACC_STATIC, "main", "([Ljava/lang/String;)V", main)
+//!     .build(&hierarchy)
+//!     .unwrap();
+//!
+//! // getstatic, ldc, pop, pop, return
+//! let quiet = transform::transform(class, &mut NoPrintln, &hierarchy).unwrap();
+//! let code = quiet.methods.methods[0].get_code().unwrap();
+//! assert_eq!(code.code().len(), 5);
+//! ```
+//!
+//! The class is taken apart into events: its header, each field, each method with its
+//! instructions, and the attributes. The visitor changes what it is given, removes members
+//! and emits other instructions instead of the ones it visits. The class is then put
+//! together with the `builder`: branches, the exception table, the LineNumberTable and the
+//! LocalVariableTable follow the instructions they refer to, and `max_stack`, `max_locals`
+//! and the StackMapTable are computed again. The constant pool of the class is kept and new
+//! entries are added after it, so the indices in the instructions stay valid.
+
+use super::attributes::{Attribute, Attributes, CodeAttribute};
+use super::builder::{ClassBuilder, CodeBuilder, Constants, Label};
+use super::bytecode::ByteCode;
+use super::constant_pool::{ConstantPool, FieldRef, MethodRef};
+use super::{ClassFile, ClassHierarchy};
+use anyhow::Result;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// The class as `ClassVisitor::visit` sees it, the changes are kept
+#[derive(Debug, Clone)]
+pub struct ClassHeader {
+    pub major_version: u16,
+    pub minor_version: u16,
+    pub access_flags: u16,
+    /// Binary name, e.g. `com/foo/Bar`
+    pub name: String,
+    /// `None` for `java/lang/Object`
+    pub super_class: Option<String>,
+    pub interfaces: Vec<String>,
+}
+
+/// A field or a method, the changes are kept
+#[derive(Debug, Clone)]
+pub struct Member {
+    pub access_flags: u16,
+    pub name: String,
+    pub descriptor: String,
+}
+
+/// What happens to a method
+pub enum MethodVisit<'a> {
+    /// It is copied as it is
+    Keep,
+    Remove,
+    /// Its code goes through the visitor
+    Visit(Box<dyn MethodVisitor + 'a>),
+}
+
+/// A local variable of the LocalVariableTable, with its signature from the
+/// LocalVariableTypeTable
+#[derive(Debug, Clone)]
+pub struct Variable {
+    pub index: u16,
+    pub name: String,
+    pub descriptor: String,
+    pub signature: Option<String>,
+    pub start: Label,
+    pub end: Label,
+}
+
+/// An instruction of the code being transformed, its constant pool indices are those of
+/// the class
+#[derive(Debug, Clone, PartialEq)]
+pub enum Instruction {
+    /// An instruction that doesn't branch
+    Plain(ByteCode),
+    /// `goto`, `jsr` or an `if`, the offset of the `ByteCode` is replaced by the one of
+    /// the label
+    Jump(ByteCode, Label),
+    TableSwitch {
+        low: i32,
+        targets: Vec<Label>,
+        default: Label,
+    },
+    LookupSwitch {
+        pairs: Vec<(i32, Label)>,
+        default: Label,
+    },
+}
+
+impl Instruction {
+    /// The method an `invoke` instruction calls (not `invokedynamic`)
+    pub fn method_ref(&self, cp: &ConstantPool) -> Option<MethodRef> {
+        match self {
+            Instruction::Plain(
+                ByteCode::InvokeVirtual(index)
+                | ByteCode::InvokeSpecial(index)
+                | ByteCode::InvokeStatic(index)
+                | ByteCode::InvokeInterface(index, _),
+            ) => cp.method_ref(*index).ok(),
+            _ => None,
+        }
+    }
+
+    /// The field a `get` or `put` instruction accesses
+    pub fn field_ref(&self, cp: &ConstantPool) -> Option<FieldRef> {
+        match self {
+            Instruction::Plain(
+                ByteCode::GetStatic(index)
+                | ByteCode::PutStatic(index)
+                | ByteCode::GetField(index)
+                | ByteCode::PutField(index),
+            ) => cp.field_ref(*index).ok(),
+            _ => None,
+        }
+    }
+}
+
+/// Visits a class, in this order: `visit`, `visit_field` and `visit_method` for each member,
+/// `visit_attribute` for the attributes of each member and of the class, then `visit_end`.
+/// The default methods keep everything as it is.
+pub trait ClassVisitor {
+    fn visit(&mut self, _class: &mut ClassHeader) {}
+
+    /// `false` removes the field
+    fn visit_field(&mut self, _field: &mut Member) -> bool {
+        true
+    }
+
+    fn visit_method(&mut self, _method: &mut Member) -> MethodVisit<'_> {
+        MethodVisit::Keep
+    }
+
+    /// An attribute of a member or of the class, `false` removes it. The Code attribute
+    /// and the BootstrapMethods attribute are not visited, the attributes of the code are
+    /// visited by the `MethodVisitor`.
+    fn visit_attribute(&mut self, _name: &str) -> bool {
+        true
+    }
+
+    /// The class with the visited members, to add fields and methods
+    fn visit_end(&mut self, class: ClassBuilder) -> ClassBuilder {
+        class
+    }
+}
+
+/// Visits the code of a method, in this order: `visit_try_catch` for each entry of the
+/// exception table, `visit_code`, then the instructions with the labels and line numbers
+/// before them, `visit_local_variable` and `visit_end`. The default methods emit what they
+/// are given into `code`, a visitor emits other instructions instead or nothing.
+pub trait MethodVisitor {
+    fn visit_try_catch(
+        &mut self,
+        code: &mut CodeBuilder,
+        start: Label,
+        end: Label,
+        handler: Label,
+        catch_type: Option<&str>,
+    ) {
+        code.try_catch(start, end, handler, catch_type);
+    }
+
+    /// Before the first instruction, the locals from `max_locals` on are free
+    fn visit_code(&mut self, _code: &mut CodeBuilder, _max_locals: u16) {}
+
+    fn visit_label(&mut self, code: &mut CodeBuilder, label: Label) {
+        code.place(label);
+    }
+
+    fn visit_line_number(&mut self, code: &mut CodeBuilder, line: u16) {
+        code.line_number(line);
+    }
+
+    fn visit_instruction(
+        &mut self,
+        code: &mut CodeBuilder,
+        instruction: Instruction,
+        _cp: &ConstantPool,
+    ) {
+        code.emit(instruction);
+    }
+
+    fn visit_local_variable(&mut self, code: &mut CodeBuilder, variable: Variable) {
+        code.local_variable(
+            variable.index,
+            &variable.name,
+            &variable.descriptor,
+            variable.signature.as_deref(),
+            variable.start,
+            variable.end,
+        );
+    }
+
+    fn visit_end(&mut self, _code: &mut CodeBuilder) {}
+}
+
+/// `class` rewritten by `visitor`. `hierarchy` gives the super classes to compute the
+/// frames of the methods whose code was visited. The attributes of visited code other than
+/// the LineNumberTable and the local variable tables are dropped.
+pub fn transform(
+    class: ClassFile,
+    visitor: &mut dyn ClassVisitor,
+    hierarchy: &dyn ClassHierarchy,
+) -> Result<ClassFile> {
+    let ClassFile {
+        minor_version,
+        major_version,
+        constant_pool: cp,
+        access_flags,
+        this_class,
+        super_class,
+        interfaces,
+        mut fields,
+        mut methods,
+        mut attributes,
+    } = class;
+
+    let mut header = ClassHeader {
+        major_version,
+        minor_version,
+        access_flags: access_flags.bits(),
+        name: cp.class_name(this_class)?.to_string(),
+        super_class: match super_class {
+            0 => None,
+            index => Some(cp.class_name(index)?.to_string()),
+        },
+        interfaces: interfaces
+            .interfaces
+            .iter()
+            .map(|index| Ok(cp.class_name(*index)?.to_string()))
+            .collect::<Result<_>>()?,
+    };
+    visitor.visit(&mut header);
+
+    let mut visited_fields = Vec::new();
+    for field in &mut fields.fields {
+        let mut member = Member {
+            access_flags: field.access_flags.bits(),
+            name: cp.utf8(field.name_index)?.to_string(),
+            descriptor: cp.utf8(field.descriptor_index)?.to_string(),
+        };
+        if visitor.visit_field(&mut member) {
+            let attributes = visit_attributes(&mut field.attributes, visitor, &cp)?;
+            visited_fields.push((member, attributes));
+        }
+    }
+
+    let mut visited_methods = Vec::new();
+    for method in &mut methods.methods {
+        let mut member = Member {
+            access_flags: method.access_flags.bits(),
+            name: cp.utf8(method.name_index)?.to_string(),
+            descriptor: cp.utf8(method.descriptor_index)?.to_string(),
+        };
+        let code = match (visitor.visit_method(&mut member), method.get_code()) {
+            (MethodVisit::Remove, _) => continue,
+            (MethodVisit::Visit(mut method_visitor), Some(code)) => {
+                Some(visit_code(code, method_visitor.as_mut(), &cp))
+            }
+            (_, _) => None,
+        };
+        let attributes = visit_attributes(&mut method.attributes, visitor, &cp)?;
+        // the Code attribute is kept unless the code was visited
+        let attributes = attributes
+            .into_iter()
+            .filter(|a| code.is_none() || !matches!(a, Attribute::Code(_)))
+            .collect();
+        visited_methods.push((member, code.unwrap_or_default(), attributes));
+    }
+
+    let mut bootstrap_methods = Vec::new();
+    if let Some(table) = attributes.attributes().iter().find_map(|a| match a {
+        Attribute::BootstrapMethodsAttribute(table) => Some(table),
+        _ => None,
+    }) {
+        for index in 0..table.num_bootstrap_methods() {
+            let method = table.get(index as u16).unwrap();
+            bootstrap_methods.push((method.method_ref(), method.arguments().to_vec()));
+        }
+    }
+    // the builder writes the BootstrapMethods attribute again, with the new entries
+    let attributes = visit_attributes(&mut attributes, visitor, &cp)?
+        .into_iter()
+        .filter(|a| !matches!(a, Attribute::BootstrapMethodsAttribute(_)));
+
+    let constants = Constants {
+        cp,
+        bootstrap_methods,
+    };
+    let mut builder = ClassBuilder::with_constants(&header.name, constants)
+        .version(header.major_version, header.minor_version)
+        .access_flags(header.access_flags);
+    if let Some(super_class) = &header.super_class {
+        builder = builder.super_class(super_class);
+    }
+    for interface in &header.interfaces {
+        builder = builder.interface(interface);
+    }
+    for (field, attributes) in visited_fields {
+        builder = builder.field_with(
+            field.access_flags,
+            &field.name,
+            &field.descriptor,
+            None,
+            attributes,
+        );
+    }
+    for (method, code, attributes) in visited_methods {
+        builder = builder.method_with(
+            method.access_flags,
+            &method.name,
+            &method.descriptor,
+            code,
+            attributes,
+        );
+    }
+    for attribute in attributes {
+        builder = builder.attribute(attribute);
+    }
+    visitor.visit_end(builder).build(hierarchy)
+}
+
+/// The attributes the visitor keeps, Code and BootstrapMethods are always kept
+fn visit_attributes(
+    attributes: &mut Attributes,
+    visitor: &mut dyn ClassVisitor,
+    cp: &ConstantPool,
+) -> Result<Vec<Attribute>> {
+    let mut kept = Vec::new();
+    for attribute in std::mem::take(attributes.attributes_mut()?) {
+        let always = matches!(
+            attribute,
+            Attribute::Code(_) | Attribute::BootstrapMethodsAttribute(_)
+        );
+        if always || visitor.visit_attribute(&attribute.name(cp)) {
+            kept.push(attribute);
+        }
+    }
+    Ok(kept)
+}
+
+/// Replays `code` into `visitor`, with labels for the pcs the branches, the exception
+/// table and the local variables refer to
+fn visit_code(
+    code: &CodeAttribute,
+    visitor: &mut dyn MethodVisitor,
+    cp: &ConstantPool,
+) -> CodeBuilder {
+    let mut lines: BTreeMap<u32, Vec<u16>> = BTreeMap::new();
+    let mut variables = Vec::new();
+    let mut signatures = Vec::new();
+    for attribute in code.attributes().attributes() {
+        match attribute {
+            Attribute::LineNumberTable(table) => {
+                for line in table.line_numbers() {
+                    let pc = line.start_pc as u32;
+                    lines.entry(pc).or_default().push(line.line_number);
+                }
+            }
+            Attribute::LocalVariableTable(table) => variables.extend(&table.local_variables),
+            Attribute::LocalVariableTypeTable(table) => signatures.extend(&table.local_variables),
+            _ => (),
+        }
+    }
+
+    let mut pcs = BTreeSet::new();
+    for (pc, instruction) in code.instructions() {
+        pcs.extend(instruction.branch_targets(pc));
+    }
+    for entry in code.exception_table() {
+        pcs.extend([entry.start_pc, entry.end_pc, entry.handler_pc].map(u32::from));
+    }
+    for variable in &variables {
+        pcs.insert(variable.start_pc as u32);
+        pcs.insert(variable.start_pc as u32 + variable.length as u32);
+    }
+    let mut builder = CodeBuilder::new();
+    let labels: BTreeMap<u32, Label> = pcs
+        .into_iter()
+        .map(|pc| (pc, builder.new_label()))
+        .collect();
+
+    for entry in code.exception_table() {
+        let catch_type = match entry.catch_type {
+            0 => None,
+            index => cp.class_name(index).ok(),
+        };
+        visitor.visit_try_catch(
+            &mut builder,
+            labels[&(entry.start_pc as u32)],
+            labels[&(entry.end_pc as u32)],
+            labels[&(entry.handler_pc as u32)],
+            catch_type,
+        );
+    }
+    visitor.visit_code(&mut builder, code.max_locals());
+    for (pc, instruction) in code.instructions() {
+        if let Some(label) = labels.get(&pc) {
+            visitor.visit_label(&mut builder, *label);
+        }
+        for line in lines.get(&pc).into_iter().flatten() {
+            visitor.visit_line_number(&mut builder, *line);
+        }
+        let target = |offset: i32| labels[&((pc as i64 + offset as i64) as u32)];
+        let instruction = match instruction {
+            ByteCode::TableSwitch(switch) => Instruction::TableSwitch {
+                low: switch.low,
+                targets: switch
+                    .offsets
+                    .iter()
+                    .map(|offset| target(*offset))
+                    .collect(),
+                default: target(switch.default),
+            },
+            ByteCode::LookupSwitch(switch) => Instruction::LookupSwitch {
+                pairs: switch
+                    .pairs
+                    .iter()
+                    .map(|(key, offset)| (*key, target(*offset)))
+                    .collect(),
+                default: target(switch.default),
+            },
+            instruction => match instruction.branch_targets(pc).first() {
+                Some(target) => Instruction::Jump(instruction.clone(), labels[target]),
+                None => Instruction::Plain(instruction.clone()),
+            },
+        };
+        visitor.visit_instruction(&mut builder, instruction, cp);
+    }
+    if let Some(label) = labels.get(&code.code_length()) {
+        visitor.visit_label(&mut builder, *label);
+    }
+
+    for variable in variables {
+        let name = cp.utf8(variable.name_index).unwrap_or_default();
+        let descriptor = cp.utf8(variable.descriptor_index).unwrap_or_default();
+        let signature = signatures
+            .iter()
+            .find(|s| {
+                (s.index, s.start_pc, s.length, s.name_index)
+                    == (
+                        variable.index,
+                        variable.start_pc,
+                        variable.length,
+                        variable.name_index,
+                    )
+            })
+            .and_then(|s| cp.utf8(s.descriptor_index).ok());
+        let start = variable.start_pc as u32;
+        visitor.visit_local_variable(
+            &mut builder,
+            Variable {
+                index: variable.index,
+                name: name.to_string(),
+                descriptor: descriptor.to_string(),
+                signature: signature.map(str::to_string),
+                start: labels[&start],
+                end: labels[&(start + variable.length as u32)],
+            },
+        );
+    }
+    visitor.visit_end(&mut builder);
+    builder
+}
@@ -0,0 +1,855 @@
+//! Verification by type checking (JVMS 4.10.1), what the JVM does for class files of
+//! version 50 and above when a class is loaded.
+//!
+//! Every method is checked in a single pass over its instructions. The types of the
+//! locals and the stack are simulated with `FrameAnalysis`, at each StackMapTable frame
+//! the simulated types must be assignable to the frame which then replaces them, and every
+//! jump and exception handler must target a frame the types are assignable to. Class
+//! types are resolved through a `ClassPath` to check assignability.
+//!
+//! Not checked are the access rules for `protected` members and linking errors like
+//! missing fields or methods, those are reported by the JVM at run time.
+
+use super::attributes::VerificationType;
+use super::bytecode::ByteCode;
+use super::constant_pool::ConstantPool;
+use super::dataflow::{Analysis, FrameAnalysis, Interpreter, State};
+use super::methods::{AccessFlag, Method};
+use super::{ClassFile, ClassPath, MethodDescriptor};
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+use std::fmt;
+
+const OBJECT: &str = "java/lang/Object";
+const THROWABLE: &str = "java/lang/Throwable";
+
+/// A reason the JVM would throw a `VerifyError` (or a related linkage error) for the class
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerifyError {
+    pub class_name: String,
+    /// Name and descriptor, e.g. `sum([I)I`, `None` for errors of the class itself
+    pub method: Option<String>,
+    pub pc: Option<u32>,
+    pub message: String,
+    pub expected: Option<String>,
+    pub actual: Option<String>,
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.class_name.replace('/', "."))?;
+        if let Some(method) = &self.method {
+            write!(f, ".{}", method)?;
+        }
+        if let Some(pc) = self.pc {
+            write!(f, " at pc {}", pc)?;
+        }
+        write!(f, ": {}", self.message)?;
+        if let (Some(expected), Some(actual)) = (&self.expected, &self.actual) {
+            write!(f, " (expected {}, found {})", expected, actual)?;
+        }
+        Ok(())
+    }
+}
+
+/// A value that is not of the type an instruction or frame requires
+#[derive(Debug)]
+struct Mismatch {
+    message: &'static str,
+    expected: String,
+    actual: VerificationType,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for Mismatch {}
+
+/// Verifies every method of the class, returns nothing if the class is valid.
+/// Each method reports at most one error, like the JVM stops at the first one.
+pub fn verify(cf: &ClassFile, class_path: &ClassPath) -> Vec<VerifyError> {
+    let class_name = cf.class_name();
+    let error = |method: Option<String>, message: String| VerifyError {
+        class_name: class_name.clone(),
+        method,
+        pc: None,
+        message,
+        expected: None,
+        actual: None,
+    };
+
+    if cf.major_version < 50 {
+        let message = format!(
+            "Class file version {} has no StackMapTable, verification by type inference is not supported",
+            cf.major_version
+        );
+        return vec![error(None, message)];
+    }
+    if let Err(e) = cf.decode_attributes() {
+        return vec![error(None, e.to_string())];
+    }
+
+    let mut errors = Vec::new();
+    if let Some(super_class) = cf.super_class_name() {
+        match class_path.load(&super_class) {
+            Ok(s) if s.is_final() => errors.push(error(
+                None,
+                format!("Subclass of final class {}", super_class),
+            )),
+            Ok(s) if s.is_interface() => errors.push(error(
+                None,
+                format!("Super class {} is an interface", super_class),
+            )),
+            Ok(_) => {}
+            Err(e) => errors.push(error(None, e.to_string())),
+        }
+    }
+
+    let super_class = cf.super_class_name();
+    let cp = &cf.constant_pool;
+    for method in &cf.methods.methods {
+        let name = cp.get_to_string(method.name_index);
+        let descriptor = cp.get_to_string(method.descriptor_index);
+        let method_name = format!("{}{}", name, descriptor);
+        if let Some(message) = overridden_final(cf, method, class_path) {
+            errors.push(error(Some(method_name.clone()), message));
+            continue;
+        }
+        let flags = method.access_flags.flag_vector();
+        let is_abstract =
+            flags.contains(&AccessFlag::Abstract) || flags.contains(&AccessFlag::Native);
+        match (method.get_code(), is_abstract) {
+            (Some(_), true) => {
+                let message = "Abstract or native method with code".to_string();
+                errors.push(error(Some(method_name), message));
+            }
+            (None, false) => {
+                let message = "Missing Code attribute".to_string();
+                errors.push(error(Some(method_name), message));
+            }
+            (None, true) => {}
+            (Some(_), false) => {
+                let verifier = MethodVerifier {
+                    class_name: &class_name,
+                    super_class: super_class.as_deref(),
+                    is_interface: cf.is_interface(),
+                    method,
+                    name: &name,
+                    descriptor: &descriptor,
+                    cp,
+                    class_path,
+                };
+                if let Err(e) = verifier.verify() {
+                    let mut e = *e;
+                    e.class_name = class_name.clone();
+                    e.method = Some(method_name);
+                    errors.push(e);
+                }
+            }
+        }
+    }
+    errors
+}
+
+/// The error if the method overrides a final method of a super class
+fn overridden_final(cf: &ClassFile, method: &Method, class_path: &ClassPath) -> Option<String> {
+    let cp = &cf.constant_pool;
+    let name = cp.get_to_string(method.name_index);
+    let descriptor = cp.get_to_string(method.descriptor_index);
+    let flags = method.access_flags.flag_vector();
+    if name.starts_with('<')
+        || flags.contains(&AccessFlag::Static)
+        || flags.contains(&AccessFlag::Private)
+    {
+        return None;
+    }
+    let mut super_class = cf.super_class_name();
+    while let Some(class) = super_class {
+        let s = class_path.load(&class).ok()?;
+        let overridden = s.methods.methods.iter().find(|m| {
+            s.constant_pool.get_to_string(m.name_index) == name
+                && s.constant_pool.get_to_string(m.descriptor_index) == descriptor
+        });
+        if let Some(m) = overridden {
+            let flags = m.access_flags.flag_vector();
+            if flags.contains(&AccessFlag::Final) && !flags.contains(&AccessFlag::Private) {
+                return Some(format!(
+                    "Overrides final method {}.{}{}",
+                    class, name, descriptor
+                ));
+            }
+        }
+        super_class = s.super_class_name();
+    }
+    None
+}
+
+struct MethodVerifier<'a> {
+    class_name: &'a str,
+    super_class: Option<&'a str>,
+    is_interface: bool,
+    method: &'a Method,
+    name: &'a str,
+    descriptor: &'a str,
+    cp: &'a ConstantPool,
+    class_path: &'a ClassPath,
+}
+
+impl MethodVerifier<'_> {
+    fn verify(&self) -> Result<(), Box<VerifyError>> {
+        let fail = |pc: Option<u32>, message: String| {
+            Box::new(VerifyError {
+                class_name: String::new(),
+                method: None,
+                pc,
+                message,
+                expected: None,
+                actual: None,
+            })
+        };
+        let code = self.method.get_code().unwrap();
+        let instructions = code.instructions().collect::<Vec<_>>();
+        if instructions.is_empty() {
+            return Err(fail(None, "Empty code".to_string()));
+        }
+
+        let mut frames = HashMap::new();
+        let stack_map = self
+            .method
+            .stack_map_frames(self.class_name, self.cp)
+            .map_err(|e| fail(None, format!("Invalid StackMapTable: {}", e)))?;
+        for frame in stack_map {
+            let pc = frame.pc as u32;
+            if !instructions.iter().any(|(p, _)| *p == pc) {
+                return Err(fail(
+                    Some(pc),
+                    "Stack map frame is not at an instruction".to_string(),
+                ));
+            }
+            let mut locals = Vec::new();
+            for t in frame.locals {
+                let size = t.size();
+                locals.push(t);
+                if size == 2 {
+                    locals.push(VerificationType::Top);
+                }
+            }
+            let state = State {
+                locals,
+                stack: frame.stack,
+            };
+            self.check_limits(pc, &state, code.max_stack(), code.max_locals())?;
+            frames.insert(pc, state);
+        }
+
+        let new_classes = instructions
+            .iter()
+            .filter_map(|(pc, instruction)| match instruction {
+                ByteCode::New(index) => Some((*pc as u16, self.cp.get_to_string(*index))),
+                _ => None,
+            })
+            .collect();
+        let checker = TypeChecker {
+            class_name: self.class_name,
+            super_class: self.super_class,
+            is_interface: self.is_interface,
+            cp: self.cp,
+            class_path: self.class_path,
+            return_type: return_type(self.descriptor).map_err(|e| fail(None, e.to_string()))?,
+            new_classes,
+        };
+        let analysis = FrameAnalysis::new(checker, self.method, self.class_name, self.cp)
+            .map_err(|e| fail(None, e.to_string()))?;
+        let checker = analysis.interpreter();
+
+        let mut state = analysis.boundary().map_err(|e| fail(None, e.to_string()))?;
+        let mut falls_through = true;
+        for (pc, instruction) in &instructions {
+            let pc = *pc;
+            let error = |e: anyhow::Error| match e.downcast_ref::<Mismatch>() {
+                Some(m) => Box::new(VerifyError {
+                    expected: Some(m.expected.clone()),
+                    actual: Some(m.actual.to_string()),
+                    ..*fail(Some(pc), m.message.to_string())
+                }),
+                None => fail(Some(pc), e.to_string()),
+            };
+
+            match frames.get(&pc) {
+                Some(frame) => {
+                    if falls_through {
+                        checker.check_frame(&state, frame).map_err(error)?;
+                    }
+                    state = frame.clone();
+                }
+                None if !falls_through => {
+                    let message = "Expecting a stack map frame after an unconditional jump";
+                    return Err(fail(Some(pc), message.to_string()));
+                }
+                None => {}
+            }
+
+            for entry in code.exception_table() {
+                if !(entry.start_pc as u32..entry.end_pc as u32).contains(&pc) {
+                    continue;
+                }
+                let catch_type = match entry.catch_type {
+                    0 => THROWABLE.to_string(),
+                    index => self
+                        .cp
+                        .class_name(index)
+                        .map_err(|e| fail(Some(pc), e.to_string()))?
+                        .to_string(),
+                };
+                let exception = VerificationType::Object(catch_type);
+                let throwable = VerificationType::Object(THROWABLE.to_string());
+                checker
+                    .check(
+                        &exception,
+                        &Expected::Type(throwable),
+                        "Catch type is not a Throwable",
+                    )
+                    .map_err(error)?;
+                let handler = State {
+                    locals: state.locals.clone(),
+                    stack: vec![exception],
+                };
+                let target = self.frame_at(&frames, entry.handler_pc as u32)?;
+                checker.check_frame(&handler, target).map_err(error)?;
+            }
+
+            if let ByteCode::Return = instruction {
+                if self.name == "<init>"
+                    && state.locals.contains(&VerificationType::UninitializedThis)
+                {
+                    let message = "Constructor returns before calling super() or this()";
+                    return Err(fail(Some(pc), message.to_string()));
+                }
+            }
+
+            let mut next = state.clone();
+            analysis
+                .transfer(pc, instruction, &mut next)
+                .map_err(error)?;
+            self.check_limits(pc, &next, code.max_stack(), code.max_locals())?;
+
+            for target in instruction.branch_targets(pc) {
+                let frame = self.frame_at(&frames, target)?;
+                checker.check_frame(&next, frame).map_err(error)?;
+            }
+            falls_through = instruction.falls_through();
+            state = next;
+        }
+        if falls_through {
+            let (pc, _) = instructions.last().unwrap();
+            return Err(fail(
+                Some(*pc),
+                "Falling off the end of the code".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn frame_at<'f>(
+        &self,
+        frames: &'f HashMap<u32, State<VerificationType>>,
+        pc: u32,
+    ) -> Result<&'f State<VerificationType>, Box<VerifyError>> {
+        frames.get(&pc).ok_or_else(|| {
+            Box::new(VerifyError {
+                class_name: String::new(),
+                method: None,
+                pc: Some(pc),
+                message: "Expecting a stack map frame at the jump target".to_string(),
+                expected: None,
+                actual: None,
+            })
+        })
+    }
+
+    fn check_limits(
+        &self,
+        pc: u32,
+        state: &State<VerificationType>,
+        max_stack: u16,
+        max_locals: u16,
+    ) -> Result<(), Box<VerifyError>> {
+        let message = if state.stack_size() > max_stack as usize {
+            "Operand stack overflow, max_stack exceeded"
+        } else if state.locals.len() > max_locals as usize {
+            "Local variable index exceeds max_locals"
+        } else {
+            return Ok(());
+        };
+        Err(Box::new(VerifyError {
+            class_name: String::new(),
+            method: None,
+            pc: Some(pc),
+            message: message.to_string(),
+            expected: None,
+            actual: None,
+        }))
+    }
+}
+
+/// What an instruction requires of an operand
+enum Expected {
+    /// a type the value must be assignable to
+    Type(VerificationType),
+    Reference,
+    /// an array with one of the component descriptors, `L` for any reference,
+    /// empty for any array
+    Array(&'static str),
+}
+
+impl fmt::Display for Expected {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expected::Type(t) => write!(f, "{}", t),
+            Expected::Reference => write!(f, "reference"),
+            Expected::Array("") => write!(f, "array"),
+            Expected::Array("L") => write!(f, "array of references"),
+            Expected::Array(components) => write!(f, "array of {}", components),
+        }
+    }
+}
+
+/// Checks the operands of the instructions while `FrameAnalysis` simulates them
+struct TypeChecker<'a> {
+    class_name: &'a str,
+    super_class: Option<&'a str>,
+    is_interface: bool,
+    cp: &'a ConstantPool,
+    class_path: &'a ClassPath,
+    /// `None` for void methods
+    return_type: Option<VerificationType>,
+    /// class of the `new` at each pc, to check the `<init>` of uninitialized objects
+    new_classes: HashMap<u16, String>,
+}
+
+impl TypeChecker<'_> {
+    fn check(
+        &self,
+        actual: &VerificationType,
+        expected: &Expected,
+        message: &'static str,
+    ) -> Result<()> {
+        use VerificationType::*;
+        let ok = match (expected, actual) {
+            (Expected::Type(t), _) => self.is_assignable(actual, t)?,
+            (Expected::Reference, Object(_) | Null | Uninitialized(_) | UninitializedThis) => true,
+            (Expected::Array(_), Null) => true,
+            (Expected::Array(components), Object(array)) => match array.strip_prefix('[') {
+                Some(component) => match *components {
+                    "" => true,
+                    "L" => component.starts_with('L') || component.starts_with('['),
+                    _ => components.contains(&component[..1]),
+                },
+                None => false,
+            },
+            _ => false,
+        };
+        if ok {
+            return Ok(());
+        }
+        Err(Mismatch {
+            message,
+            expected: expected.to_string(),
+            actual: actual.clone(),
+        }
+        .into())
+    }
+
+    fn is_assignable(&self, from: &VerificationType, to: &VerificationType) -> Result<bool> {
+        use VerificationType::*;
+        let assignable = match (from, to) {
+            _ if from == to => true,
+            (_, Top) => true,
+            (Null, Object(_)) => true,
+            (Object(from), Object(to)) => self.is_class_assignable(from, to)?,
+            _ => false,
+        };
+        Ok(assignable)
+    }
+
+    /// Assignability of class names and array descriptors. Like in the JVM every class
+    /// is assignable to an interface, the check is deferred to the invocation.
+    fn is_class_assignable(&self, from: &str, to: &str) -> Result<bool> {
+        if from == to || to == OBJECT {
+            return Ok(true);
+        }
+        match (from.strip_prefix('['), to.strip_prefix('[')) {
+            (Some(_), None) => Ok(to == "java/lang/Cloneable" || to == "java/io/Serializable"),
+            (None, Some(_)) => Ok(false),
+            (Some(from), Some(to)) => {
+                let reference = |c: &str| c.starts_with('L') || c.starts_with('[');
+                if reference(from) && reference(to) {
+                    let name = |c: &str| {
+                        c.strip_prefix('L')
+                            .unwrap_or(c)
+                            .trim_end_matches(';')
+                            .to_string()
+                    };
+                    self.is_class_assignable(&name(from), &name(to))
+                } else {
+                    Ok(from == to)
+                }
+            }
+            (None, None) => {
+                if self.lookup(to)?.1 {
+                    return Ok(true);
+                }
+                let mut class = from.to_string();
+                loop {
+                    match self.lookup(&class)?.0 {
+                        Some(s) if s == to => return Ok(true),
+                        Some(s) => class = s,
+                        None => return Ok(false),
+                    }
+                }
+            }
+        }
+    }
+
+    /// Super class and whether it is an interface, the verified class doesn't have to
+    /// be on the class path
+    fn lookup(&self, class: &str) -> Result<(Option<String>, bool)> {
+        if class == self.class_name {
+            return Ok((self.super_class.map(|s| s.to_string()), self.is_interface));
+        }
+        let cf = self.class_path.load(class)?;
+        Ok((cf.super_class_name(), cf.is_interface()))
+    }
+
+    /// The types of the current state must be assignable to the ones of the frame
+    fn check_frame(
+        &self,
+        state: &State<VerificationType>,
+        frame: &State<VerificationType>,
+    ) -> Result<()> {
+        if state.stack.len() != frame.stack.len() {
+            return Err(anyhow!(
+                "Inconsistent stack height {} != {} of the stack map frame",
+                state.stack.len(),
+                frame.stack.len()
+            ));
+        }
+        let message = "Current frame is not assignable to the stack map frame";
+        for (slot, expected) in frame.locals.iter().enumerate() {
+            let actual = state.locals.get(slot).unwrap_or(&VerificationType::Top);
+            self.check(actual, &Expected::Type(expected.clone()), message)?;
+        }
+        for (actual, expected) in state.stack.iter().zip(&frame.stack) {
+            self.check(actual, &Expected::Type(expected.clone()), message)?;
+        }
+        Ok(())
+    }
+
+    /// Requirements on the values an instruction pops, bottom of the stack first
+    fn expected_inputs(&self, instruction: &ByteCode) -> Result<Vec<Expected>> {
+        use Expected::*;
+        use VerificationType::{Double, Float, Integer, Long};
+        let object = |name: &str| Type(VerificationType::Object(name.to_string()));
+        let expected = match instruction {
+            ByteCode::IALoad => vec![Array("I"), Type(Integer)],
+            ByteCode::LALoad => vec![Array("J"), Type(Integer)],
+            ByteCode::FALoad => vec![Array("F"), Type(Integer)],
+            ByteCode::DALoad => vec![Array("D"), Type(Integer)],
+            ByteCode::AALoad => vec![Array("L"), Type(Integer)],
+            ByteCode::BALoad => vec![Array("BZ"), Type(Integer)],
+            ByteCode::CALoad => vec![Array("C"), Type(Integer)],
+            ByteCode::SALoad => vec![Array("S"), Type(Integer)],
+            ByteCode::IAStore => vec![Array("I"), Type(Integer), Type(Integer)],
+            ByteCode::LAStore => vec![Array("J"), Type(Integer), Type(Long)],
+            ByteCode::FAStore => vec![Array("F"), Type(Integer), Type(Float)],
+            ByteCode::DAStore => vec![Array("D"), Type(Integer), Type(Double)],
+            ByteCode::AAStore => vec![Array("L"), Type(Integer), Reference],
+            ByteCode::BAStore => vec![Array("BZ"), Type(Integer), Type(Integer)],
+            ByteCode::CAStore => vec![Array("C"), Type(Integer), Type(Integer)],
+            ByteCode::SAStore => vec![Array("S"), Type(Integer), Type(Integer)],
+            ByteCode::IAdd
+            | ByteCode::ISub
+            | ByteCode::IMul
+            | ByteCode::IDiv
+            | ByteCode::IRem
+            | ByteCode::IShl
+            | ByteCode::IShr
+            | ByteCode::IUShr
+            | ByteCode::IAnd
+            | ByteCode::IOr
+            | ByteCode::IXor
+            | ByteCode::IfICmpEq(_)
+            | ByteCode::IfICmpNe(_)
+            | ByteCode::IfICmpLt(_)
+            | ByteCode::IfICmpGe(_)
+            | ByteCode::IfICmpGt(_)
+            | ByteCode::IfICmpLe(_) => vec![Type(Integer), Type(Integer)],
+            ByteCode::LAdd
+            | ByteCode::LSub
+            | ByteCode::LMul
+            | ByteCode::LDiv
+            | ByteCode::LRem
+            | ByteCode::LAnd
+            | ByteCode::LOr
+            | ByteCode::LXor
+            | ByteCode::Lcmp => vec![Type(Long), Type(Long)],
+            ByteCode::LShl | ByteCode::LShr | ByteCode::LUShr => vec![Type(Long), Type(Integer)],
+            ByteCode::FAdd
+            | ByteCode::FSub
+            | ByteCode::FMul
+            | ByteCode::FDiv
+            | ByteCode::FRem
+            | ByteCode::FCmpl
+            | ByteCode::FCmpg => vec![Type(Float), Type(Float)],
+            ByteCode::DAdd
+            | ByteCode::DSub
+            | ByteCode::DMul
+            | ByteCode::DDiv
+            | ByteCode::DRem
+            | ByteCode::DCmpl
+            | ByteCode::DCmpg => vec![Type(Double), Type(Double)],
+            ByteCode::INeg
+            | ByteCode::I2L
+            | ByteCode::I2F
+            | ByteCode::I2D
+            | ByteCode::I2B
+            | ByteCode::I2C
+            | ByteCode::I2S
+            | ByteCode::Ifeq(_)
+            | ByteCode::Ifne(_)
+            | ByteCode::Iflt(_)
+            | ByteCode::Ifge(_)
+            | ByteCode::Ifgt(_)
+            | ByteCode::Ifle(_)
+            | ByteCode::TableSwitch(_)
+            | ByteCode::LookupSwitch(_)
+            | ByteCode::NewArray(_)
+            | ByteCode::ANewArray(_) => vec![Type(Integer)],
+            ByteCode::LNeg | ByteCode::L2i | ByteCode::L2F | ByteCode::L2D => vec![Type(Long)],
+            ByteCode::FNeg | ByteCode::F2I | ByteCode::F2L | ByteCode::F2D => vec![Type(Float)],
+            ByteCode::DNeg | ByteCode::D2I | ByteCode::D2L | ByteCode::D2F => vec![Type(Double)],
+            ByteCode::IfACmpEq(_) | ByteCode::IfACmpNe(_) => vec![Reference, Reference],
+            ByteCode::IfNull(_)
+            | ByteCode::IfNonNull(_)
+            | ByteCode::MonitorEnter
+            | ByteCode::MonitorExit
+            | ByteCode::CheckCast(_)
+            | ByteCode::InstanceOf(_) => vec![Reference],
+            ByteCode::ArrayLength => vec![Array("")],
+            ByteCode::Athrow => vec![object(THROWABLE)],
+            ByteCode::MultiANewArray(_, dimensions) => {
+                (0..*dimensions).map(|_| Type(Integer)).collect()
+            }
+            ByteCode::IReturn
+            | ByteCode::LReturn
+            | ByteCode::FReturn
+            | ByteCode::DReturn
+            | ByteCode::AReturn
+            | ByteCode::Return => {
+                let matches = matches!(
+                    (instruction, &self.return_type),
+                    (ByteCode::Return, None)
+                        | (ByteCode::IReturn, Some(Integer))
+                        | (ByteCode::LReturn, Some(Long))
+                        | (ByteCode::FReturn, Some(Float))
+                        | (ByteCode::DReturn, Some(Double))
+                        | (ByteCode::AReturn, Some(VerificationType::Object(_)))
+                );
+                if !matches {
+                    let return_type = match &self.return_type {
+                        Some(t) => t.to_string(),
+                        None => "void".to_string(),
+                    };
+                    return Err(anyhow!(
+                        "{} in a method returning {}",
+                        instruction.to_string(),
+                        return_type
+                    ));
+                }
+                self.return_type.iter().map(|t| Type(t.clone())).collect()
+            }
+            ByteCode::PutStatic(index) => {
+                let field = self.cp.field_ref(*index)?;
+                vec![Type(VerificationType::from_descriptor(&field.descriptor)?)]
+            }
+            ByteCode::GetField(index) => vec![object(&self.cp.field_ref(*index)?.owner)],
+            ByteCode::PutField(index) => {
+                let field = self.cp.field_ref(*index)?;
+                vec![
+                    object(&field.owner),
+                    Type(VerificationType::from_descriptor(&field.descriptor)?),
+                ]
+            }
+            ByteCode::InvokeVirtual(index)
+            | ByteCode::InvokeSpecial(index)
+            | ByteCode::InvokeInterface(index, _)
+            | ByteCode::InvokeStatic(index) => {
+                let method = self.cp.method_ref(*index)?;
+                let is_init = method.name == "<init>";
+                if method.name == "<clinit>"
+                    || (is_init && !matches!(instruction, ByteCode::InvokeSpecial(_)))
+                {
+                    return Err(anyhow!("Invalid call of {}", method.name));
+                }
+                let mut expected = match instruction {
+                    ByteCode::InvokeStatic(_) => vec![],
+                    // the receiver of `<init>` is checked by `initialize`
+                    ByteCode::InvokeSpecial(_) if is_init => vec![Reference],
+                    ByteCode::InvokeSpecial(_) => vec![object(self.class_name)],
+                    _ => vec![object(&method.owner)],
+                };
+                for arg in MethodDescriptor::parse(&method.descriptor)?.parameters {
+                    expected.push(Type(VerificationType::from_field_type(&arg)));
+                }
+                expected
+            }
+            ByteCode::InvokeDynamic(index) => {
+                let call_site = self.cp.invoke_dynamic(*index)?;
+                MethodDescriptor::parse(&call_site.descriptor)?
+                    .parameters
+                    .iter()
+                    .map(|arg| Type(VerificationType::from_field_type(arg)))
+                    .collect()
+            }
+            _ => vec![],
+        };
+        Ok(expected)
+    }
+
+    fn check_inputs(&self, instruction: &ByteCode, inputs: &[VerificationType]) -> Result<()> {
+        let expected = self.expected_inputs(instruction)?;
+        for (i, (actual, expected)) in inputs.iter().zip(&expected).enumerate() {
+            // constructors can set the fields of their class before calling super()
+            if i == 0 && *actual == VerificationType::UninitializedThis {
+                if let ByteCode::PutField(index) = instruction {
+                    if self.cp.field_ref(*index)?.owner == self.class_name {
+                        continue;
+                    }
+                }
+            }
+            self.check(actual, expected, "Bad type on operand stack")?;
+        }
+        Ok(())
+    }
+}
+
+impl Interpreter for TypeChecker<'_> {
+    type Value = VerificationType;
+
+    fn empty(&self) -> VerificationType {
+        VerificationType::Top
+    }
+
+    fn entry(&self, _slot: u16, t: &VerificationType) -> VerificationType {
+        t.clone()
+    }
+
+    fn exception(&self, _handler_pc: u32, catch_type: &str) -> VerificationType {
+        VerificationType::Object(catch_type.to_string())
+    }
+
+    fn operation(
+        &self,
+        _pc: u32,
+        instruction: &ByteCode,
+        inputs: &[VerificationType],
+        result: &VerificationType,
+    ) -> Result<VerificationType> {
+        self.check_inputs(instruction, inputs)?;
+        if let ByteCode::AALoad = instruction {
+            return match &inputs[0] {
+                VerificationType::Object(array) => VerificationType::from_descriptor(&array[1..]),
+                _ => Ok(VerificationType::Null),
+            };
+        }
+        Ok(result.clone())
+    }
+
+    fn consume(&self, _pc: u32, instruction: &ByteCode, inputs: &[VerificationType]) -> Result<()> {
+        self.check_inputs(instruction, inputs)
+    }
+
+    fn load(
+        &self,
+        _pc: u32,
+        instruction: &ByteCode,
+        value: &VerificationType,
+    ) -> Result<VerificationType> {
+        self.check(value, &local_type(instruction), "Bad local variable type")?;
+        Ok(value.clone())
+    }
+
+    fn store(
+        &self,
+        _pc: u32,
+        instruction: &ByteCode,
+        value: &VerificationType,
+    ) -> Result<VerificationType> {
+        let message = match instruction {
+            ByteCode::IInc(_, _) => "Bad local variable type",
+            _ => "Bad type on operand stack",
+        };
+        self.check(value, &local_type(instruction), message)?;
+        Ok(value.clone())
+    }
+
+    fn initialize(
+        &self,
+        _pc: u32,
+        receiver: &VerificationType,
+        owner: &str,
+    ) -> Result<Option<VerificationType>> {
+        match receiver {
+            VerificationType::UninitializedThis => {
+                if owner != self.class_name && Some(owner) != self.super_class {
+                    return Err(anyhow!(
+                        "Call to {}.<init> does not initialize this or its super class",
+                        owner
+                    ));
+                }
+                Ok(Some(VerificationType::Object(self.class_name.to_string())))
+            }
+            VerificationType::Uninitialized(new_pc) => match self.new_classes.get(new_pc) {
+                Some(class) if class == owner => {
+                    Ok(Some(VerificationType::Object(owner.to_string())))
+                }
+                _ => Err(anyhow!(
+                    "Call to {}.<init> on an object created by another new",
+                    owner
+                )),
+            },
+            _ => Err(Mismatch {
+                message: "Bad type on operand stack",
+                expected: "uninitialized object".to_string(),
+                actual: receiver.clone(),
+            }
+            .into()),
+        }
+    }
+
+    /// Frames are never merged, they come from the StackMapTable
+    fn merge(&self, _a: &VerificationType, _b: &VerificationType) -> VerificationType {
+        VerificationType::Top
+    }
+}
+
+/// Type of the local a load, store or `iinc` accesses
+fn local_type(instruction: &ByteCode) -> Expected {
+    match instruction {
+        ByteCode::ILoad(_) | ByteCode::IStore(_) | ByteCode::IInc(_, _) => {
+            Expected::Type(VerificationType::Integer)
+        }
+        ByteCode::Lload(_) | ByteCode::LStore(_) => Expected::Type(VerificationType::Long),
+        ByteCode::FLoad(_) | ByteCode::FStore(_) => Expected::Type(VerificationType::Float),
+        ByteCode::DLoad(_) | ByteCode::DStore(_) => Expected::Type(VerificationType::Double),
+        _ => Expected::Reference,
+    }
+}
+
+fn return_type(descriptor: &str) -> Result<Option<VerificationType>> {
+    let return_type = MethodDescriptor::parse(descriptor)?.return_type;
+    Ok(return_type.as_ref().map(VerificationType::from_field_type))
+}
@@ -4,7 +4,7 @@ pub const DEBUG: bool = false;
 #[macro_export]
 macro_rules! print_debug {
     ($($arg:tt)*) => {{
-        if crate::debug_utils::DEBUG {
+        if $crate::debug_utils::DEBUG {
             println!($($arg)*);
         }
     }};
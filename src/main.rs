@@ -1 +1,298 @@
-fn main() {}
+use clap::{Parser, Subcommand};
+use jaust::class_file;
+use jaust::class_file::assembler;
+use jaust::class_file::decompiler;
+use jaust::class_file::interpreter::{JavaException, Vm};
+use std::io::{Cursor, Read, Write};
+use std::path::Path;
+use zip::write::SimpleFileOptions;
+
+#[derive(Parser)]
+struct Opts {
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Checks that class files are well formed (JVMS 4.8) and reports every violation
+    LintClass {
+        /// class files to check
+        #[clap(required = true)]
+        files: Vec<String>,
+    },
+    /// Runs the main method of a class with the bytecode interpreter
+    Run {
+        #[clap(
+            long = "class-path",
+            alias = "cp",
+            default_value = ".",
+            help = "Directories and jars to load classes from, separated by ':'"
+        )]
+        class_path: String,
+
+        /// class with the main method, e.g. `com.example.Main`
+        class: String,
+    },
+    /// Decompiles a class to Java source
+    Decompile {
+        #[clap(
+            long = "class-path",
+            alias = "cp",
+            default_value = ".",
+            help = "Directories and jars to load classes from, separated by ':'"
+        )]
+        class_path: String,
+
+        /// class to decompile, e.g. `com.example.Main`
+        class: String,
+    },
+    /// Assembles class files from a Jasmin-like textual format
+    Assemble {
+        /// directory to write the class files to, in directories of their packages
+        #[clap(short = 'd', default_value = ".")]
+        directory: String,
+
+        #[clap(
+            long = "class-path",
+            alias = "cp",
+            default_value = ".",
+            help = "Directories and jars to load classes from, separated by ':'"
+        )]
+        class_path: String,
+
+        /// files to assemble
+        #[clap(required = true)]
+        files: Vec<String>,
+    },
+    /// Removes debug information and other attributes from class files and jars and drops
+    /// the constant pool entries they used
+    Strip {
+        /// directory to write the class files to, in directories of their packages, and the
+        /// jars to, under their file name
+        #[clap(short = 'd', default_value = ".")]
+        directory: String,
+
+        #[clap(
+            long = "attributes",
+            value_delimiter = ',',
+            help = "Attributes to remove, separated by ',' [default: LineNumberTable, \
+                    LocalVariableTable, LocalVariableTypeTable, SourceFile, \
+                    SourceDebugExtension and the Runtime*Invisible*Annotations]"
+        )]
+        attributes: Vec<String>,
+
+        /// class files and jars to strip
+        #[clap(required = true)]
+        files: Vec<String>,
+    },
+}
+
+fn lint_class(files: &[String]) -> bool {
+    let mut ok = true;
+    for file in files {
+        let cf = match class_file::read_class_file(file) {
+            Ok(cf) => cf,
+            Err(e) => {
+                println!("{}: {}", file, e);
+                ok = false;
+                continue;
+            }
+        };
+        for error in class_file::check_format(&cf) {
+            println!("{}: {}", file, error);
+            ok = false;
+        }
+    }
+    ok
+}
+
+fn run(class_path: &str, class: &str) -> bool {
+    let mut class_path = match class_file::ClassPath::from_class_path(class_path) {
+        Ok(class_path) => class_path,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return false;
+        }
+    };
+    // the JDK classes are needed for the hierarchy of the exceptions
+    if let Err(e) = class_path.add_jdk() {
+        eprintln!("Warning: {}", e);
+    }
+    let mut vm = Vm::new(class_path);
+    let result = vm.run_main(&class.replace('.', "/"));
+    print!("{}", vm.take_output());
+    match result {
+        Ok(()) => true,
+        Err(e) => {
+            match e.downcast_ref::<JavaException>() {
+                Some(exception) => eprintln!("Exception in thread \"main\" {}", exception),
+                None => eprintln!("Error: {}", e),
+            }
+            false
+        }
+    }
+}
+
+fn decompile(class_path: &str, class: &str) -> bool {
+    let mut class_path = match class_file::ClassPath::from_class_path(class_path) {
+        Ok(class_path) => class_path,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return false;
+        }
+    };
+    // the JDK classes are needed for the types of fields and methods they declare
+    if let Err(e) = class_path.add_jdk() {
+        eprintln!("Warning: {}", e);
+    }
+    match decompiler::decompile(&class_path, class) {
+        Ok(source) => {
+            print!("{}", source);
+            true
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            false
+        }
+    }
+}
+
+fn assemble(directory: &str, class_path: &str, files: &[String]) -> bool {
+    let mut class_path = match class_file::ClassPath::from_class_path(class_path) {
+        Ok(class_path) => class_path,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return false;
+        }
+    };
+    // the frames merge types with the classes they extend
+    if let Err(e) = class_path.add_jdk() {
+        eprintln!("Warning: {}", e);
+    }
+    let mut ok = true;
+    for file in files {
+        let written = std::fs::read_to_string(file)
+            .map_err(anyhow::Error::from)
+            .and_then(|source| assembler::assemble(&source, &class_path))
+            .and_then(|cf| {
+                let path =
+                    std::path::Path::new(directory).join(format!("{}.class", cf.class_name()));
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                class_file::write_class_file(&cf, &path.to_string_lossy())
+            });
+        if let Err(e) = written {
+            eprintln!("{}: {}", file, e);
+            ok = false;
+        }
+    }
+    ok
+}
+
+fn strip(directory: &str, attributes: &[String], files: &[String]) -> bool {
+    let names: Vec<&str> = match attributes.is_empty() {
+        true => class_file::DEBUG_ATTRIBUTES.to_vec(),
+        false => attributes.iter().map(|a| a.as_str()).collect(),
+    };
+    if let Some(name) = names
+        .iter()
+        .find(|n| class_file::REQUIRED_ATTRIBUTES.contains(n))
+    {
+        eprintln!("Error: {} attributes can't be stripped", name);
+        return false;
+    }
+    let mut ok = true;
+    for file in files {
+        // the size before, the output may replace the file
+        let stripped = std::fs::metadata(file)
+            .map_err(anyhow::Error::from)
+            .and_then(|before| match file.ends_with(".jar") {
+                true => Ok((before.len(), strip_jar(file, directory, &names)?)),
+                false => {
+                    let (class_name, bytes) = strip_class(std::fs::read(file)?, &names)?;
+                    let path = Path::new(directory).join(format!("{}.class", class_name));
+                    if let Some(parent) = path.parent() {
+                        std::fs::create_dir_all(parent)?;
+                    }
+                    std::fs::write(path, &bytes)?;
+                    Ok((before.len(), bytes.len()))
+                }
+            });
+        match stripped {
+            Ok((before, after)) => println!("{}: {} -> {} bytes", file, before, after),
+            Err(e) => {
+                eprintln!("{}: {}", file, e);
+                ok = false;
+            }
+        }
+    }
+    ok
+}
+
+/// The name of the class and its bytes without the attributes `names`
+fn strip_class(bytes: Vec<u8>, names: &[&str]) -> anyhow::Result<(String, Vec<u8>)> {
+    let mut cf = class_file::read_class_bytes(bytes)?;
+    class_file::strip(&mut cf, names)?;
+    Ok((cf.class_name(), cf.to_bytes()?))
+}
+
+/// Writes the jar with its classes stripped to `directory`, returns its size. A class that
+/// can't be stripped, e.g. `module-info`, is copied as it is.
+fn strip_jar(jar: &str, directory: &str, names: &[&str]) -> anyhow::Result<usize> {
+    let mut archive = zip::ZipArchive::new(std::fs::File::open(jar)?)?;
+    // written to memory first, the output may replace the jar being read
+    let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        if !entry.is_file() || !entry.name().ends_with(".class") {
+            writer.raw_copy_file(entry)?;
+            continue;
+        }
+        let mut bytes = Vec::new();
+        entry.read_to_end(&mut bytes)?;
+        let bytes = match strip_class(bytes.clone(), names) {
+            Ok((_, stripped)) => stripped,
+            Err(e) => {
+                eprintln!("Warning: {}!{}: {}, copied unchanged", jar, entry.name(), e);
+                bytes
+            }
+        };
+        let mut options = SimpleFileOptions::default().compression_method(entry.compression());
+        if let Some(time) = entry.last_modified() {
+            options = options.last_modified_time(time);
+        }
+        writer.start_file(entry.name(), options)?;
+        writer.write_all(&bytes)?;
+    }
+    let bytes = writer.finish()?.into_inner();
+    let name = Path::new(jar)
+        .file_name()
+        .ok_or_else(|| anyhow::anyhow!("{} is not a file", jar))?;
+    std::fs::create_dir_all(directory)?;
+    std::fs::write(Path::new(directory).join(name), &bytes)?;
+    Ok(bytes.len())
+}
+
+fn main() {
+    let opts = Opts::parse();
+    let ok = match opts.command {
+        Command::LintClass { files } => lint_class(&files),
+        Command::Run { class_path, class } => run(&class_path, &class),
+        Command::Decompile { class_path, class } => decompile(&class_path, &class),
+        Command::Assemble {
+            directory,
+            class_path,
+            files,
+        } => assemble(&directory, &class_path, &files),
+        Command::Strip {
+            directory,
+            attributes,
+            files,
+        } => strip(&directory, &attributes, &files),
+    };
+    if !ok {
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,23 @@
+public class ConstantsTest {
+	public static final int MAX = 10;
+	public static final int NEG = -5;
+	public static final long BIG = 12345678901L;
+	public static final float F = 1.1f;
+	public static final float LARGE_F = 1.6777216e7f;
+	public static final float NAN_F = Float.NaN;
+	public static final double D = 2.25;
+	public static final double SMALL_D = 1.0e-5;
+	public static final double NEG_INF_D = Double.NEGATIVE_INFINITY;
+	public static final char C = 'A';
+	public static final char QUOTE = '\'';
+	public static final char NL = '\n';
+	public static final boolean B = true;
+	public static final byte BY = 3;
+	public static final short SH = -4;
+	public static final String S = "hello \"world\"\n\ttab 'single'";
+	public static final String U = "caf\u00e9";
+	@Deprecated
+	public static final int OLD = 1;
+	final int instance = 3;
+	static int notConstant = 4;
+}
@@ -0,0 +1,286 @@
+import java.time.DayOfWeek;
+import java.util.ArrayList;
+import java.util.Iterator;
+import java.util.List;
+import java.util.Map;
+import java.util.TreeMap;
+import java.util.function.Function;
+import java.util.function.IntBinaryOperator;
+import java.util.function.Supplier;
+
+// Decompiled by jaust, recompiled and run, its output is compared with the output of the original
+public class DecompileTest {
+    private final List<String> names = new ArrayList<>();
+    private int count;
+    private static final Object LOCK = new Object();
+    static long total;
+
+    DecompileTest(String... names) {
+        for (String name : names) {
+            this.names.add(name);
+        }
+    }
+
+    int countdown(int n) {
+        int steps = 0;
+        while (n > 0) {
+            if (n % 2 == 0) {
+                n /= 2;
+            } else {
+                n--;
+            }
+            steps++;
+        }
+        return steps;
+    }
+
+    static int firstNegative(int[] values) {
+        int i = 0;
+        do {
+            if (values[i] < 0) {
+                break;
+            }
+            i++;
+        } while (i < values.length);
+        return i;
+    }
+
+    static String grid(int rows, int columns) {
+        StringBuilder sb = new StringBuilder();
+        outer:
+        for (int r = 0; r < rows; r++) {
+            for (int c = 0; c < columns; c++) {
+                if (c > r) {
+                    continue outer;
+                }
+                if (r * c > 6) {
+                    break outer;
+                }
+                sb.append(r * c).append(',');
+            }
+            sb.append(';');
+        }
+        return sb.toString();
+    }
+
+    static boolean inRange(int x, int low, int high) {
+        return x >= low && x <= high || x == -1;
+    }
+
+    static String sign(long value) {
+        return value > 0L ? "positive" : value < 0L ? "negative" : "zero";
+    }
+
+    static int sumOdd(List<Integer> values) {
+        int sum = 0;
+        for (Integer value : values) {
+            if (value % 2 == 0) {
+                continue;
+            }
+            sum += value;
+        }
+        return sum;
+    }
+
+    static String joinAll(Iterable<String> parts, char separator) {
+        StringBuilder sb = new StringBuilder();
+        Iterator<String> it = parts.iterator();
+        while (it.hasNext()) {
+            sb.append(it.next());
+            if (it.hasNext()) {
+                sb.append(separator);
+            }
+        }
+        return sb.toString();
+    }
+
+    static int daysIn(String month) {
+        switch (month) {
+            case "feb":
+                return 28;
+            case "apr":
+            case "jun":
+                return 30;
+            default:
+                return 31;
+        }
+    }
+
+    static String weekday(DayOfWeek day) {
+        switch (day) {
+            case SATURDAY:
+            case SUNDAY:
+                return "weekend";
+            default:
+                return "weekday";
+        }
+    }
+
+    static int fallThrough(int n) {
+        int result = 0;
+        switch (n) {
+            case 1:
+                result += 1;
+            case 2:
+                result += 10;
+                break;
+            case 3:
+                result = -1;
+                break;
+        }
+        return result;
+    }
+
+    synchronized int increment() {
+        return ++count;
+    }
+
+    static void add(long amount) {
+        synchronized (LOCK) {
+            total += amount;
+        }
+    }
+
+    static String tryFinally(String s) {
+        StringBuilder log = new StringBuilder();
+        try {
+            log.append(Integer.parseInt(s));
+        } catch (NumberFormatException e) {
+            log.append("bad");
+        } finally {
+            log.append("!");
+        }
+        return log.toString();
+    }
+
+    static int multiCatch(Object o) {
+        try {
+            return ((String) o).length();
+        } catch (ClassCastException | NullPointerException e) {
+            return -1;
+        }
+    }
+
+    List<String> upper(String suffix) {
+        List<String> out = new ArrayList<>();
+        names.forEach(name -> out.add(name.toUpperCase() + suffix));
+        return out;
+    }
+
+    static int apply(IntBinaryOperator op, int a, int b) {
+        return op.applyAsInt(a, b);
+    }
+
+    static Map<String, Integer> lengths(List<String> words) {
+        TreeMap<String, Integer> map = new TreeMap<>();
+        Function<String, Integer> length = String::length;
+        for (String word : words) {
+            map.put(word, length.apply(word));
+        }
+        return map;
+    }
+
+    static String chars(String s) {
+        char[] chars = s.toCharArray();
+        int[] counts = new int[26];
+        for (char c : chars) {
+            if (c >= 'a' && c <= 'z') {
+                counts[c - 'a']++;
+            }
+        }
+        StringBuilder sb = new StringBuilder();
+        for (int i = 0; i < counts.length; i++) {
+            if (counts[i] > 0) {
+                sb.append((char) ('a' + i)).append(counts[i]);
+            }
+        }
+        return sb.toString();
+    }
+
+    static int bits(int x) {
+        int n = 0;
+        while (x != 0) {
+            x &= x - 1;
+            n++;
+        }
+        return n << 2 | n >>> 1 ^ ~n;
+    }
+
+    static boolean flags(boolean a, boolean b) {
+        boolean both = a & b;
+        boolean either = a | b;
+        return both != either ^ !a;
+    }
+
+    static double mix(int i, long l, float f, double d, byte b, short s) {
+        return i * l + f / d - b % s;
+    }
+
+    // a local class capturing a variable and the outer instance
+    Iterator<String> prefixed(String prefix) {
+        class Prefixer implements Iterator<String> {
+            private int next;
+
+            public boolean hasNext() {
+                return next < names.size();
+            }
+
+            public String next() {
+                return prefix + names.get(next++);
+            }
+        }
+        return new Prefixer();
+    }
+
+    // an anonymous class in an anonymous class, using the fields of the outer instance
+    Supplier<Supplier<String>> nested() {
+        return new Supplier<Supplier<String>>() {
+            public Supplier<String> get() {
+                return new Supplier<String>() {
+                    public String get() {
+                        return names.get(0) + count;
+                    }
+                };
+            }
+        };
+    }
+
+    public static void main(String[] args) {
+        DecompileTest test = new DecompileTest("ada", "grace", "alan");
+        System.out.println(test.countdown(37) + " " + firstNegative(new int[] {3, 1, -4, 1}));
+        System.out.println(grid(4, 4) + " " + inRange(3, 1, 5) + " " + inRange(-1, 1, 5) + " " + inRange(9, 1, 5));
+        System.out.println(sign(5) + " " + sign(-5) + " " + sign(0));
+        ArrayList<Integer> values = new ArrayList<>();
+        for (int i = 0; i < 10; i++) {
+            values.add(i * i);
+        }
+        System.out.println(sumOdd(values) + " " + joinAll(test.names, '/'));
+        System.out.println(daysIn("feb") + " " + daysIn("jun") + " " + daysIn("dec"));
+        System.out.println(weekday(DayOfWeek.SUNDAY) + " " + weekday(DayOfWeek.MONDAY));
+        System.out.println(fallThrough(1) + " " + fallThrough(2) + " " + fallThrough(3) + " " + fallThrough(4));
+        System.out.println(test.increment() + test.increment());
+        add(5);
+        add(7);
+        System.out.println(total);
+        System.out.println(tryFinally("42") + tryFinally("x"));
+        System.out.println(multiCatch("four") + " " + multiCatch(4) + " " + multiCatch(null));
+        System.out.println(test.upper("?"));
+        int base = 3;
+        System.out.println(apply((a, b) -> a * b + base, 4, 5) + " " + apply(Math::max, 4, 5));
+        System.out.println(lengths(List.of("one", "three", "seven")));
+        Supplier<String> supplier = () -> "supplied";
+        char letter = (char) ('a' + args.length);
+        System.out.println(supplier.get() + " " + chars("hello world") + " " + letter + (int) letter);
+        System.out.println(bits(255) + " " + flags(true, false) + " " + flags(true, true));
+        System.out.println(mix(1, 2L, 3.5f, 0.5, (byte) 7, (short) 4));
+        int x = 5;
+        int y = x++ + ++x;
+        int[] a = {1, 2, 3};
+        a[x - 6] += y;
+        System.out.println(x + " " + y + " " + a[1] + " " + (x > 6 ? a[0]-- : a[2]++) + " " + a[0]);
+        for (Iterator<String> it = test.prefixed("-"); it.hasNext(); ) {
+            System.out.print(it.next());
+        }
+        System.out.println(" " + test.nested().get().get());
+    }
+}
@@ -0,0 +1,46 @@
+import java.util.List;
+import java.util.Map;
+import java.util.function.Function;
+
+public class GenericTest<T extends Comparable<? super T>, U> extends java.util.AbstractList<T> implements Function<List<? extends U>, Map<String, T[]>> {
+	public List<?> wild;
+	public Map<String, List<int[]>> nested;
+	protected T value;
+	public Inner<String>.Deep<T> deep;
+
+	public class Inner<V> {
+		public class Deep<W> {
+		}
+	}
+
+	public <R extends Number & Runnable> R pick(List<? super R> from, T[]... rest) throws IllegalStateException {
+		return null;
+	}
+
+	public static <E extends Exception> void fail() throws E {
+	}
+
+	public static <M extends Object & Comparable<? super M>> M least(List<M> from) {
+		return null;
+	}
+
+	public T get(int i) { return value; }
+	public int size() { return 0; }
+	public Map<String, T[]> apply(List<? extends U> l) { return null; }
+}
+
+@interface GenericMarker {
+	String value() default "x";
+	int[] counts() default {};
+}
+
+interface GenericShapes {
+	static int zero() { return 0; }
+	default int next(int n) { return n + 1; }
+	private int hidden() { return 1; }
+	int abs();
+}
+
+record GenericPoint<T>(int x, String y, List<T> z) implements Comparable<GenericPoint<T>> {
+	public int compareTo(GenericPoint<T> o) { return 0; }
+}
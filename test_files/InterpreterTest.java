@@ -0,0 +1,230 @@
+// Run by the jaust interpreter, its output is compared with the output of java
+public class InterpreterTest {
+    static final int LIMIT = 10;
+    static final String GREETING = "hello";
+    static int counter;
+    static long big = 1L << 40;
+    static int[] squares = new int[LIMIT];
+
+    static {
+        for (int i = 0; i < LIMIT; i++) {
+            squares[i] = i * i;
+        }
+        System.out.println("InterpreterTest initialized");
+    }
+
+    static class Lazy {
+        static int value = 42;
+
+        static {
+            System.out.println("Lazy initialized");
+        }
+    }
+
+    static class Point {
+        int x;
+        int y;
+
+        Point(int x, int y) {
+            this.x = x;
+            this.y = y;
+        }
+
+        int norm() {
+            return x * x + y * y;
+        }
+    }
+
+    static class Point3 extends Point {
+        int z;
+
+        Point3(int x, int y, int z) {
+            super(x, y);
+            this.z = z;
+        }
+
+        @Override
+        int norm() {
+            return super.norm() + z * z;
+        }
+    }
+
+    static class CustomException extends RuntimeException {
+        final int code;
+
+        CustomException(String message, int code) {
+            super(message);
+            this.code = code;
+        }
+    }
+
+    static int fib(int n) {
+        return n < 2 ? n : fib(n - 1) + fib(n - 2);
+    }
+
+    static long factorial(int n) {
+        long result = 1;
+        for (int i = 2; i <= n; i++) {
+            result *= i;
+        }
+        return result;
+    }
+
+    static double harmonic(int n) {
+        double sum = 0;
+        for (int i = 1; i <= n; i++) {
+            sum += 1.0 / i;
+        }
+        return sum;
+    }
+
+    static float average(int[] values) {
+        float sum = 0;
+        for (int value : values) {
+            sum += value;
+        }
+        return sum / values.length;
+    }
+
+    static int divide(int a, int b) {
+        try {
+            return a / b;
+        } catch (ArithmeticException e) {
+            System.out.println("caught " + e.getMessage());
+            return -1;
+        }
+    }
+
+    static String classify(int n) {
+        switch (n) {
+            case 0:
+                return "zero";
+            case 1:
+                return "one";
+            case 2:
+                return "two";
+            default:
+                return "many";
+        }
+    }
+
+    static int lookup(int n) {
+        switch (n) {
+            case -100:
+                return 1;
+            case 7:
+                return 2;
+            case 100000:
+                return 3;
+            default:
+                return 0;
+        }
+    }
+
+    static int sumMatrix(int[][] matrix) {
+        int sum = 0;
+        for (int[] row : matrix) {
+            for (int value : row) {
+                sum += value;
+            }
+        }
+        return sum;
+    }
+
+    static int check(int n) {
+        try {
+            if (n > 5) {
+                throw new CustomException("too big: " + n, n * 10);
+            }
+            return n;
+        } catch (CustomException e) {
+            System.out.println(e.getMessage());
+            return e.code;
+        } finally {
+            counter++;
+        }
+    }
+
+    static int outOfBounds(int[] values, int index) {
+        try {
+            return values[index];
+        } catch (ArrayIndexOutOfBoundsException e) {
+            return -2;
+        }
+    }
+
+    static int nullField(Point point) {
+        try {
+            return point.x;
+        } catch (NullPointerException e) {
+            return -3;
+        }
+    }
+
+    static String describe(Object o) {
+        if (o instanceof Point3) {
+            return "point3";
+        } else if (o instanceof Point) {
+            return "point";
+        } else if (o instanceof int[]) {
+            return "int[]";
+        }
+        return "other";
+    }
+
+    static int norm(Point point) {
+        return point.norm();
+    }
+
+    static int fail(int n) {
+        return 100 / (n - n);
+    }
+
+    public static void main(String[] args) {
+        System.out.println("fib(20) = " + fib(20));
+        System.out.println("20! = " + factorial(20));
+        System.out.println("H(10) = " + harmonic(10));
+        System.out.println("average = " + average(new int[] {1, 2, 3, 4}));
+        System.out.println(divide(7, 2));
+        System.out.println(divide(7, 0));
+        System.out.println(-7 / 2 + " " + -7 % 2 + " " + 7.5 % 2 + " " + Integer.MIN_VALUE / -1);
+        for (int i = 0; i < 4; i++) {
+            System.out.println(classify(i) + " " + lookup(i == 3 ? 100000 : i * 7 - 7));
+        }
+        System.out.println(sumMatrix(new int[][] {{1, 2}, {3, 4, 5}, {}}));
+        int[][][] cube = new int[2][3][4];
+        cube[1][2][3] = 9;
+        System.out.println(cube.length + " " + cube[1].length + " " + cube[1][2].length + " " + cube[1][2][3]);
+        System.out.println(check(3) + " " + check(8) + " counter=" + counter);
+        System.out.println(outOfBounds(squares, 3) + " " + outOfBounds(squares, LIMIT));
+        System.out.println(nullField(null) + " " + nullField(new Point(1, 2)));
+        System.out.println("before Lazy");
+        System.out.println(Lazy.value);
+
+        Point3 p = new Point3(1, 2, 3);
+        System.out.println(norm(p) + " " + describe(p) + " " + describe(new Point(0, 0)) + " "
+                + describe(squares) + " " + describe(GREETING));
+
+        byte b = (byte) 200;
+        short s = (short) 70000;
+        char c = (char) ('a' + 2);
+        System.out.println(b + " " + s + " " + c + " " + (int) c + " " + (b < 0));
+        System.out.println((int) 3.99 + " " + (int) -2.5f + " " + (long) 1e20 + " " + (int) Double.NaN);
+        System.out.println((5 << 3) + " " + (-17 >> 2) + " " + (-17 >>> 28) + " " + (big >>> 3) + " " + (big ^ 0xFFL));
+        System.out.println(1.0f / 3 + " " + 2.0 / 3 + " " + 1e-5 + " " + 1e21 + " " + 100.0 + " " + -0.0);
+        System.out.println(Long.MAX_VALUE + 1);
+        System.out.println(GREETING.length() + " " + GREETING.charAt(1) + " " + GREETING.equals("hel" + "lo"));
+
+        boolean[] flags = new boolean[3];
+        flags[1] = true;
+        char[] chars = {'j', 'v', 'm'};
+        long[] longs = new long[2];
+        longs[1] = big;
+        double[] doubles = {0.5, 0.25};
+        System.out.println(flags[0] + " " + flags[1] + " " + chars[1] + " " + longs[1] + " " + (doubles[0] + doubles[1]));
+
+        String text = null;
+        System.out.println("text = " + text);
+        System.out.println(fail(1));
+    }
+}
@@ -0,0 +1,167 @@
+// Run by the jaust interpreter, its output is compared with the output of java
+public class LinkingTest {
+    static int trace(String name) {
+        System.out.println("initialize " + name);
+        return name.length();
+    }
+
+    interface Named {
+        int NAMED = trace("Named");
+
+        String name();
+
+        default String greet() {
+            return "hello " + name();
+        }
+    }
+
+    interface Loud extends Named {
+        int LOUD = trace("Loud");
+
+        default String greet() {
+            return Named.super.greet() + "!";
+        }
+    }
+
+    // without default methods, initializing an implementation doesn't initialize it
+    interface Titled extends Named {
+        int TITLED = trace("Titled");
+
+        String title();
+    }
+
+    interface Counter {
+        int START = trace("Counter");
+
+        static int zero() {
+            return 0;
+        }
+
+        default int next(int n) {
+            return n + 1;
+        }
+    }
+
+    static class Base implements Counter {
+        static String label = "base";
+
+        static {
+            trace("Base");
+        }
+
+        String kind() {
+            return "base";
+        }
+
+        @Override
+        public String toString() {
+            return kind();
+        }
+    }
+
+    static class Person extends Base implements Loud, Titled {
+        static String label = "person";
+
+        static {
+            trace("Person");
+        }
+
+        @Override
+        public String name() {
+            return "Ada";
+        }
+
+        @Override
+        public String title() {
+            return "Dr";
+        }
+
+        @Override
+        String kind() {
+            return "person";
+        }
+    }
+
+    static class Robot implements Counter {
+        @Override
+        public int next(int n) {
+            return Counter.super.next(n) * 2;
+        }
+    }
+
+    interface Left {
+        default String side() {
+            return "left";
+        }
+    }
+
+    interface Right {
+        default String side() {
+            return "right";
+        }
+    }
+
+    static class Both implements Left, Right {
+        @Override
+        public String side() {
+            return Left.super.side() + "+" + Right.super.side();
+        }
+    }
+
+    static abstract class Shape {
+        abstract int area();
+
+        int doubled() {
+            return 2 * area();
+        }
+    }
+
+    static class Square extends Shape {
+        int side = 3;
+
+        @Override
+        int area() {
+            return side * side;
+        }
+    }
+
+    static class Secret {
+        private String hidden() {
+            return "secret";
+        }
+
+        String reveal() {
+            return hidden();
+        }
+    }
+
+    static class MoreSecret extends Secret {
+        private String hidden() {
+            return "more secret";
+        }
+
+        String both() {
+            return hidden() + " " + reveal();
+        }
+    }
+
+    public static void main(String[] args) {
+        System.out.println("Person.NAMED = " + Person.NAMED);
+        Person person = new Person();
+        Named named = person;
+        Titled titled = person;
+        System.out.println(person.greet() + " " + named.greet() + " " + named.name() + " " + titled.title());
+        System.out.println(named + " " + named.hashCode() * 0 + " " + person.next(1) + " " + new Robot().next(1));
+        System.out.println("Titled.TITLED = " + Titled.TITLED);
+        System.out.println(Base.label + " " + Person.label + " " + Counter.zero() + " " + Counter.START);
+        Left left = new Both();
+        System.out.println(left.side() + " " + ((Right) left).side());
+        Shape shape = new Square();
+        System.out.println(shape.area() + " " + shape.doubled());
+        System.out.println(new MoreSecret().both() + ", " + new Secret().reveal());
+        int[] values = {1, 2, 3};
+        int[] copy = values.clone();
+        copy[0] = 9;
+        System.out.println(values[0] + " " + copy[0] + " " + copy.length);
+    }
+}
@@ -0,0 +1,17 @@
+class NestedTest {
+	private static class PrivateStatic {
+	}
+
+	protected class Inner {
+		public class Deep {
+		}
+	}
+
+	interface Iface {
+	}
+
+	Object anonymous() {
+		return new Object() {
+		};
+	}
+}
@@ -0,0 +1,10 @@
+// compiled to a sealed class permitting its constant bodies
+enum SealedOperation {
+	PLUS {
+		int apply(int a, int b) {
+			return a + b;
+		}
+	};
+
+	abstract int apply(int a, int b);
+}
@@ -0,0 +1,19 @@
+public sealed interface SealedTest permits SealedCircle, SealedSquare, SealedShape {
+	default Runnable runner() {
+		return new Runnable() {
+			public void run() {
+			}
+		};
+	}
+}
+
+final class SealedCircle implements SealedTest {
+}
+
+non-sealed class SealedSquare implements SealedTest {
+}
+
+abstract sealed class SealedShape implements SealedTest permits SealedShape.Triangle {
+	static final class Triangle extends SealedShape {
+	}
+}
@@ -0,0 +1,54 @@
+public class StackMapTest {
+	private final String name;
+
+	public StackMapTest(String name, boolean upper) {
+		this.name = upper ? name.toUpperCase() : name;
+	}
+
+	public static int sum(int[] values) {
+		int total = 0;
+		for (int i = 0; i < values.length; i++) {
+			total += values[i];
+		}
+		return total;
+	}
+
+	public long scan(long start, double factor, String label) {
+		long result = start;
+		if (label == null) {
+			String fallback = "none";
+			result += fallback.length();
+		}
+		while (factor > 1.0) {
+			factor /= 2;
+			result++;
+		}
+		return result;
+	}
+
+	public static Number pick(boolean small) {
+		Number n = small ? (Number) Integer.valueOf(1) : Long.valueOf(2);
+		return n;
+	}
+
+	public static int parse(String s) {
+		try {
+			return Integer.parseInt(s);
+		} catch (NumberFormatException e) {
+			return -1;
+		}
+	}
+
+	public static String describe(int n) {
+		switch (n) {
+			case 0:
+				return "zero";
+			case 1:
+				return "one";
+			case 10:
+				return "ten";
+			default:
+				return new StringBuilder(n > 0 ? "positive" : "negative").toString();
+		}
+	}
+}
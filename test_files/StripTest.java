@@ -0,0 +1,43 @@
+import java.lang.annotation.ElementType;
+import java.lang.annotation.Retention;
+import java.lang.annotation.RetentionPolicy;
+import java.lang.annotation.Target;
+import java.util.ArrayList;
+import java.util.List;
+
+@StripTest.Internal("class")
+public class StripTest {
+    // kept in the class file but not at run time
+    @interface Internal {
+        String value() default "none";
+    }
+
+    @Retention(RetentionPolicy.RUNTIME)
+    @interface Visible {
+        Class<?> type() default Object.class;
+
+        ElementType[] targets() default {ElementType.PARAMETER};
+    }
+
+    @Target(ElementType.TYPE_USE)
+    @interface NonNull {}
+
+    private final List<@NonNull String> names = new ArrayList<@NonNull String>();
+
+    @Internal
+    void add(@Internal @Visible(type = String.class) String name) {
+        @NonNull String copy = name;
+        names.add(copy);
+    }
+
+    public static void main(String[] args) throws Exception {
+        StripTest test = new StripTest();
+        test.add("a");
+        test.add("b");
+        System.out.println(String.join(",", test.names));
+        Visible visible = (Visible) StripTest.class
+                .getDeclaredMethod("add", String.class)
+                .getParameterAnnotations()[0][0];
+        System.out.println(visible.type().getSimpleName() + " " + visible.targets()[0]);
+    }
+}
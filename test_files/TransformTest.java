@@ -0,0 +1,41 @@
+public class TransformTest {
+    static void log(String message) {
+        System.out.println("log: " + message);
+    }
+
+    static int sum(int n) {
+        log("sum " + n);
+        int sum = 0;
+        for (int i = 1; i <= n; i++) {
+            sum += i;
+        }
+        return sum;
+    }
+
+    static int divide(int a, int b) {
+        try {
+            return a / b;
+        } catch (ArithmeticException e) {
+            log("division by zero");
+            return -1;
+        }
+    }
+
+    static String describe(int i) {
+        switch (i) {
+            case 0:
+                return "zero";
+            case 1:
+                return "one";
+            default:
+                log("other " + i);
+                return "other";
+        }
+    }
+
+    public static void main(String[] args) {
+        System.out.println(sum(10));
+        System.out.println(divide(7, 0));
+        System.out.println(describe(1) + " " + describe(5));
+    }
+}
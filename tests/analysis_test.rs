@@ -0,0 +1,155 @@
+mod common;
+
+use common::initialize;
+use jaust::class_file;
+
+#[test]
+fn control_flow_graph_blocks_and_edges() {
+    use class_file::EdgeKind::*;
+
+    initialize();
+    let cf = class_file::read_class_file("./test_class_files/StackMapTest.class").unwrap();
+    let method = |name: &str| {
+        cf.methods
+            .methods
+            .iter()
+            .find(|m| cf.constant_pool.get_to_string(m.name_index) == name)
+            .unwrap()
+    };
+    let starts = |cfg: &class_file::ControlFlowGraph| {
+        cfg.blocks.iter().map(|b| b.start_pc).collect::<Vec<_>>()
+    };
+
+    // try { return Integer.parseInt(s); } catch (NumberFormatException e) { return -1; }
+    let cfg = method("parse")
+        .control_flow_graph(&cf.constant_pool)
+        .unwrap();
+    assert_eq!(starts(&cfg), vec![0, 4, 5]);
+    let catch = Exceptional(Some("java/lang/NumberFormatException".to_string()));
+    assert_eq!(
+        cfg.blocks[0]
+            .successors
+            .iter()
+            .map(|e| (e.block, e.kind.clone()))
+            .collect::<Vec<_>>(),
+        vec![(1, Normal), (2, catch.clone())]
+    );
+    assert_eq!(cfg.blocks[2].predecessors[0].kind, catch);
+    assert!(cfg.blocks[1].successors.is_empty());
+
+    // the switch jumps to every case, the conditional joins before the constructor call
+    let cfg = method("describe")
+        .control_flow_graph(&cf.constant_pool)
+        .unwrap();
+    assert_eq!(starts(&cfg), vec![0, 36, 39, 42, 45, 53, 58, 60]);
+    assert_eq!(cfg.blocks[0].successors.len(), 4);
+    let join = cfg.block_index(60).unwrap();
+    assert_eq!(
+        cfg.blocks[join]
+            .predecessors
+            .iter()
+            .map(|e| e.block)
+            .collect::<Vec<_>>(),
+        vec![5, 6]
+    );
+
+    let dot = class_file::jaustp_cfg(&cf, "parse").unwrap();
+    assert!(dot.starts_with("digraph \"StackMapTest.parse(Ljava/lang/String;)I\" {\n"));
+    assert!(dot.contains("  b0 -> b1;\n"));
+    assert!(dot.contains("  b0 -> b2 [style=dashed, label=\"java/lang/NumberFormatException\"];\n"));
+    assert!(class_file::jaustp_cfg(&cf, "missing").is_err());
+}
+
+#[test]
+fn dataflow_analyses() {
+    use class_file::dataflow::{self, Definition::*};
+    use class_file::VerificationType;
+    use std::collections::BTreeSet;
+
+    initialize();
+    let cf = class_file::read_class_file("./test_class_files/StackMapTest.class").unwrap();
+    let cp = &cf.constant_pool;
+    let method = |name: &str| {
+        cf.methods
+            .methods
+            .iter()
+            .find(|m| cp.get_to_string(m.name_index) == name)
+            .unwrap()
+    };
+    let definitions = |d: &dataflow::Definitions| d.definitions.iter().cloned().collect::<Vec<_>>();
+
+    // this.name = upper ? name.toUpperCase() : name;
+    let init = method("<init>");
+    let defs = dataflow::reaching_definitions(init, "StackMapTest", cp).unwrap();
+    let code = init.get_code().unwrap();
+    let (_, to_upper_case) = code.instructions().find(|(pc, _)| *pc == 10).unwrap();
+    let receiver = defs
+        .before(10)
+        .unwrap()
+        .invoke_arguments(to_upper_case, cp)
+        .unwrap();
+    assert_eq!(definitions(&receiver[0]), vec![Parameter(1)]);
+    let stack = &defs.before(17).unwrap().stack;
+    assert_eq!(definitions(&stack[0]), vec![Parameter(0)]);
+    assert_eq!(definitions(&stack[1]), vec![Parameter(1), Instruction(10)]);
+
+    // total is stored before the loop and in it
+    let defs = dataflow::reaching_definitions(method("sum"), "StackMapTest", cp).unwrap();
+    assert_eq!(
+        definitions(&defs.before(22).unwrap().locals[1]),
+        vec![Instruction(1), Instruction(15)]
+    );
+
+    let live = dataflow::liveness(method("sum"), cp).unwrap();
+    assert_eq!(live.before(0), Some(&BTreeSet::from([0])));
+    assert_eq!(live.before(4), Some(&BTreeSet::from([0, 1, 2])));
+    assert_eq!(live.before(22), Some(&BTreeSet::from([1])));
+
+    let hierarchy = class_file::ClassHierarchyMap::new();
+    let types = dataflow::type_states(method("pick"), "StackMapTest", cp, &hierarchy).unwrap();
+    assert_eq!(
+        types.after(5).unwrap().stack,
+        vec![VerificationType::Object("java/lang/Integer".to_string())]
+    );
+    assert_eq!(
+        types.before(17).unwrap().stack,
+        vec![VerificationType::Object("java/lang/Object".to_string())]
+    );
+}
+
+#[test]
+fn control_flow_graph_rejects_invalid_exception_tables() {
+    use class_file::assembler;
+
+    // sipush takes the pcs 0 to 2, the handler is at 5 and the code 6 bytes long
+    let source = ".class public A\n.super java/lang/Object\n\
+        .method public static m()V\n    .catch all from Start to End using Handler\n\
+        Start:\n    sipush 1000\n    pop\nEnd:\n    return\nHandler:\n    athrow\n.end method\n";
+    let cf = assembler::assemble(source, &class_file::ClassHierarchyMap::new()).unwrap();
+    assert!(cf.methods.methods[0]
+        .control_flow_graph(&cf.constant_pool)
+        .is_ok());
+    let bytes = cf.to_bytes().unwrap();
+    let code = [0x11, 0x03, 0xe8, 0x57, 0xb1, 0xbf, 0x00, 0x01];
+    let entry = bytes.windows(code.len()).position(|w| w == code).unwrap() + code.len();
+    assert_eq!(bytes[entry..entry + 6], [0, 0, 0, 4, 0, 5]);
+
+    // start_pc, end_pc and handler_pc
+    for (offset, pc, error) in [
+        (0, 1, "invalid start_pc 1"),
+        (0, 4, "invalid start_pc 4"),
+        (2, 2, "invalid end_pc 2"),
+        (4, 1, "invalid handler_pc 1"),
+        (4, 6, "invalid handler_pc 6"),
+    ] {
+        let mut bytes = bytes.clone();
+        bytes[entry + offset + 1] = pc;
+        let cf = class_file::read_class_bytes(bytes).unwrap();
+        let cfg = cf.methods.methods[0].control_flow_graph(&cf.constant_pool);
+        assert_eq!(
+            cfg.unwrap_err().to_string(),
+            format!("Exception table entry with {}", error)
+        );
+        assert!(class_file::jaustp_cfg(&cf, "m").is_err());
+    }
+}
@@ -0,0 +1,88 @@
+use jaust::class_file;
+
+use text_diff::assert_diff;
+
+use std::fs;
+use std::process::Command;
+
+#[test]
+fn assembled_class_runs() {
+    use class_file::assembler;
+
+    let mut class_path = class_file::ClassPath::new();
+    class_path.add_jdk().unwrap();
+    let source = fs::read_to_string("./test_files/AssemblerTest.j").unwrap();
+    let cf = assembler::assemble(&source, &class_path).unwrap();
+    let errors = class_file::verify(&cf, &class_path);
+    assert!(errors.is_empty(), "{:?}", errors);
+
+    let dir = std::env::temp_dir().join("jaust_assembled");
+    fs::remove_dir_all(&dir).unwrap_or(());
+    fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("AssemblerTest.class");
+    class_file::write_class_file(&cf, file.to_str().unwrap()).unwrap();
+    let read = class_file::read_class_file(file.to_str().unwrap()).unwrap();
+    assert_eq!(read.to_bytes().unwrap(), cf.to_bytes().unwrap());
+
+    let output = Command::new("java")
+        .arg("-cp")
+        .arg(&dir)
+        .arg("AssemblerTest")
+        .output()
+        .expect("failed to execute java");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8(output.stderr).unwrap()
+    );
+    let expected = "Hello, assembler\nzero\none\ntwo\nunknown\nthousand\nsum = 55\n-1\n-1000\n\
+                    10000000042\n2.5\n1.5\nclass AssemblerTest\n1\n";
+    assert_diff(expected, &String::from_utf8(output.stdout).unwrap(), "", 0);
+}
+
+#[test]
+fn assembler_errors_name_the_line() {
+    use class_file::assembler;
+
+    let hierarchy = class_file::ClassHierarchyMap::new();
+    let error = |source: &str| match assembler::assemble(source, &hierarchy) {
+        Ok(_) => panic!("{} assembled", source),
+        Err(e) => e.to_string(),
+    };
+    let method = |code: &str| {
+        format!(
+            ".class public A\n.method public static m()V\n{}\n.end method\n",
+            code
+        )
+    };
+
+    assert_eq!(
+        error(&method("    goto Nowhere\n    return")),
+        "line 3: Unknown label Nowhere"
+    );
+    assert_eq!(
+        error(&method("L:\nL:\n    return")),
+        "line 4: Label L is defined twice"
+    );
+    assert_eq!(
+        error(&method("    frobnicate\n    return")),
+        "line 3: Unknown instruction frobnicate"
+    );
+    assert_eq!(
+        error(&method("    ldc 10L\n    pop2\n    return")),
+        "line 3: Longs and doubles are loaded with ldc2_w"
+    );
+    assert_eq!(
+        error(&method("    iconst_0\n    iconst_1 2\n    return")),
+        "line 4: Unexpected 2"
+    );
+    assert_eq!(
+        error(".class public A\n.method public m()V\n    return\n"),
+        "line 2: Missing .end method"
+    );
+    assert_eq!(error(".super java/lang/Object\n"), "Missing .class");
+    assert_eq!(
+        error(".class A\n.field x I = \"unterminated\n"),
+        "line 2: Unterminated string"
+    );
+}
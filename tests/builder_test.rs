@@ -0,0 +1,138 @@
+use jaust::class_file;
+
+use text_diff::assert_diff;
+
+use std::fs;
+use std::process::Command;
+
+#[test]
+fn built_class_runs() {
+    use class_file::builder::*;
+    use class_file::{MemberRef, MethodHandle, MethodHandleReferenceKind, MethodRef};
+
+    let mut class_path = class_file::ClassPath::new();
+    class_path.add_jdk().unwrap();
+
+    let mut constructor = CodeBuilder::new();
+    constructor
+        .aload(0)
+        .invokespecial("java/lang/Object", "<init>", "()V")
+        .return_();
+
+    // the sum of 1 to n
+    let mut sum = CodeBuilder::new();
+    let (start, done) = (sum.new_label(), sum.new_label());
+    sum.iconst(0).istore(1).iconst(1).istore(2);
+    sum.place(start).iload(2).iload(0).if_icmpgt(done);
+    sum.iload(1)
+        .iload(2)
+        .iadd()
+        .istore(1)
+        .iinc(2, 1)
+        .goto(start);
+    sum.place(done).iload(1).ireturn();
+
+    let mut divide = CodeBuilder::new();
+    let (start, end, handler) = (divide.new_label(), divide.new_label(), divide.new_label());
+    divide
+        .place(start)
+        .iload(0)
+        .iload(1)
+        .idiv()
+        .place(end)
+        .ireturn();
+    divide.place(handler).pop().iconst(-1).ireturn();
+    divide.try_catch(start, end, handler, Some("java/lang/ArithmeticException"));
+
+    let concat = MethodHandle {
+        kind: MethodHandleReferenceKind::RefInvokeStatic,
+        reference: MemberRef::Method(MethodRef {
+            owner: "java/lang/invoke/StringConcatFactory".to_string(),
+            name: "makeConcatWithConstants".to_string(),
+            descriptor: "(Ljava/lang/invoke/MethodHandles$Lookup;Ljava/lang/String;\
+                         Ljava/lang/invoke/MethodType;Ljava/lang/String;[Ljava/lang/Object;)\
+                         Ljava/lang/invoke/CallSite;"
+                .to_string(),
+            is_interface: false,
+        }),
+    };
+    let out = ("java/lang/System", "out", "Ljava/io/PrintStream;");
+    let mut main = CodeBuilder::new();
+    main.getstatic(out.0, out.1, out.2)
+        .getstatic("com/foo/Built", "GREETING", "Ljava/lang/String;")
+        .invokevirtual("java/io/PrintStream", "println", "(Ljava/lang/String;)V")
+        .getstatic(out.0, out.1, out.2)
+        .iconst(100)
+        .invokestatic("com/foo/Built", "sum", "(I)I")
+        .invokedynamic(
+            "makeConcatWithConstants",
+            "(I)Ljava/lang/String;",
+            concat,
+            vec![Constant::String("sum = \u{1}".to_string())],
+        )
+        .invokevirtual("java/io/PrintStream", "println", "(Ljava/lang/String;)V")
+        .getstatic(out.0, out.1, out.2)
+        .iconst(7)
+        .iconst(0)
+        .invokestatic("com/foo/Built", "divide", "(II)I")
+        .invokevirtual("java/io/PrintStream", "println", "(I)V")
+        .getstatic(out.0, out.1, out.2)
+        .ldc(Constant::Long(10_000_000_000))
+        .invokevirtual("java/io/PrintStream", "println", "(J)V")
+        .return_();
+
+    let cf = ClassBuilder::new("com/foo/Built")
+        .version(61, 0)
+        .source_file("Built.java")
+        .constant_field(
+            ACC_PUBLIC | ACC_STATIC | ACC_FINAL,
+            "GREETING",
+            "Ljava/lang/String;",
+            Constant::String("Hello, builder".to_string()),
+        )
+        .method(ACC_PUBLIC, "<init>", "()V", constructor)
+        .method(ACC_PUBLIC | ACC_STATIC, "sum", "(I)I", sum)
+        .method(ACC_PUBLIC | ACC_STATIC, "divide", "(II)I", divide)
+        .method(
+            ACC_PUBLIC | ACC_STATIC,
+            "main",
+            "([Ljava/lang/String;)V",
+            main,
+        )
+        .build(&class_path)
+        .unwrap();
+    let errors = class_file::verify(&cf, &class_path);
+    assert!(errors.is_empty(), "{:?}", errors);
+
+    // each entry is added once, however often the instructions use it
+    let cp = &cf.constant_pool;
+    let println = (1..cp.len() as u16)
+        .filter_map(|i| cp.method_ref(i).ok())
+        .filter(|m| m.to_string() == "java/io/PrintStream.println:(Ljava/lang/String;)V")
+        .count();
+    assert_eq!(println, 1);
+    let fields = (1..cp.len() as u16)
+        .filter(|i| cp.field_ref(*i).is_ok_and(|f| f.name == "out"))
+        .count();
+    assert_eq!(fields, 1);
+
+    let dir = std::env::temp_dir().join("jaust_built");
+    fs::remove_dir_all(&dir).unwrap_or(());
+    fs::create_dir_all(dir.join("com/foo")).unwrap();
+    let file = dir.join("com/foo/Built.class");
+    class_file::write_class_file(&cf, file.to_str().unwrap()).unwrap();
+
+    let output = Command::new("java")
+        .arg("-cp")
+        .arg(&dir)
+        .arg("com.foo.Built")
+        .output()
+        .expect("failed to execute java");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8(output.stderr).unwrap()
+    );
+    let expected = "Hello, builder\nsum = 5050\n-1\n10000000000\n";
+    assert_diff(expected, &String::from_utf8(output.stdout).unwrap(), "", 0);
+}
@@ -0,0 +1,39 @@
+use std::fs;
+use std::process::Command;
+use std::sync::Once;
+
+static INIT: Once = Once::new();
+
+pub fn initialize() {
+    INIT.call_once(|| {
+        // clean class files
+        fs::remove_dir_all("./test_class_files").unwrap_or(());
+
+        // java files to compile (all in test_files recursively that end in .java)
+        let files = fs::read_dir("./test_files")
+            .unwrap()
+            .map(|f| f.unwrap().path())
+            .filter(|f| f.extension().unwrap() == "java");
+
+        // compile example classes
+        let out = Command::new("javac")
+            .arg("-d")
+            .arg("./test_class_files")
+            .args(files)
+            .output()
+            .expect("failed to execute javac");
+
+        if !out.status.success() {
+            let mut msg = format!("javac failed: {:?}", out.status);
+            msg.push_str(&format!(
+                "stdout:\n{}",
+                String::from_utf8(out.stdout).unwrap()
+            ));
+            msg.push_str(&format!(
+                "stderr:\n{}",
+                String::from_utf8(out.stderr).unwrap()
+            ));
+            panic!("{}", msg);
+        }
+    });
+}
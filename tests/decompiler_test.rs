@@ -0,0 +1,61 @@
+mod common;
+
+use common::initialize;
+use jaust::class_file;
+
+use text_diff::assert_diff;
+
+use std::fs;
+use std::process::Command;
+
+#[test]
+fn decompiled_classes_recompile_and_run() {
+    use class_file::decompiler;
+
+    initialize();
+    let mut class_path = class_file::ClassPath::new();
+    class_path
+        .add(std::path::Path::new("./test_class_files"))
+        .unwrap();
+    class_path.add_jdk().unwrap();
+
+    let dir = std::env::temp_dir().join("jaust_decompiled");
+    fs::remove_dir_all(&dir).unwrap_or(());
+    fs::create_dir_all(dir.join("classes")).unwrap();
+    let classes = ["DecompileTest", "Example", "LinkingTest", "InterpreterTest"];
+    // only recompiled, javac checks the sealed, non-sealed and permits clauses
+    let sealed = ["SealedTest", "SealedShape"];
+    let mut sources = Vec::new();
+    for class in classes.into_iter().chain(sealed) {
+        let source = decompiler::decompile(&class_path, class).unwrap();
+        assert!(!source.contains("Could not decompile"), "{}", source);
+        let file = dir.join(format!("{}.java", class));
+        fs::write(&file, source).unwrap();
+        sources.push(file);
+    }
+    // Example extends and uses classes that are not decompiled
+    let javac = Command::new("javac")
+        .args(["-cp", "./test_class_files", "-d"])
+        .arg(dir.join("classes"))
+        .args(&sources)
+        .output()
+        .expect("failed to execute javac");
+    assert!(
+        javac.status.success(),
+        "{}",
+        String::from_utf8(javac.stderr).unwrap()
+    );
+
+    // B, C and the other classes Example uses are not decompiled
+    let decompiled = format!("{}:./test_class_files", dir.join("classes").display());
+    for class in classes {
+        let run = |class_path: &str| {
+            let output = Command::new("java")
+                .args(["-cp", class_path, class])
+                .output()
+                .expect("failed to execute java");
+            String::from_utf8(output.stdout).unwrap()
+        };
+        assert_diff(&run("./test_class_files"), &run(&decompiled), "", 0);
+    }
+}
@@ -0,0 +1,293 @@
+mod common;
+
+use common::initialize;
+use jaust::class_file;
+
+use anyhow::Result;
+use std::fs;
+use std::process::Command;
+
+#[test]
+fn stack_map_frames_with_absolute_pcs() {
+    use class_file::VerificationType::*;
+
+    initialize();
+    let cf = class_file::read_class_file("./test_class_files/StackMapTest.class").unwrap();
+    let class_name = cf.class_name();
+    let method = |name: &str| {
+        cf.methods
+            .methods
+            .iter()
+            .find(|m| cf.constant_pool.get_to_string(m.name_index) == name)
+            .unwrap()
+    };
+    let object = |name: &str| Object(name.to_string());
+    let frame = |pc, locals, stack| class_file::Frame { pc, locals, stack };
+
+    let init = method("<init>");
+    assert_eq!(
+        init.initial_frame(&class_name, &cf.constant_pool).unwrap(),
+        frame(
+            0,
+            vec![UninitializedThis, object("java/lang/String"), Integer],
+            vec![]
+        )
+    );
+    let locals = vec![object("StackMapTest"), object("java/lang/String"), Integer];
+    assert_eq!(
+        init.stack_map_frames(&class_name, &cf.constant_pool)
+            .unwrap(),
+        vec![
+            frame(16, locals.clone(), vec![object("StackMapTest")]),
+            frame(
+                17,
+                locals,
+                vec![object("StackMapTest"), object("java/lang/String")]
+            ),
+        ]
+    );
+
+    // append then chop
+    assert_eq!(
+        method("sum")
+            .stack_map_frames(&class_name, &cf.constant_pool)
+            .unwrap(),
+        vec![
+            frame(4, vec![object("[I"), Integer, Integer], vec![]),
+            frame(22, vec![object("[I"), Integer], vec![]),
+        ]
+    );
+
+    // long and double arguments are single entries
+    let scan = method("scan")
+        .stack_map_frames(&class_name, &cf.constant_pool)
+        .unwrap();
+    let locals = vec![
+        object("StackMapTest"),
+        Long,
+        Double,
+        object("java/lang/String"),
+        Long,
+    ];
+    assert_eq!(
+        scan,
+        vec![frame(23, locals.clone(), vec![]), frame(44, locals, vec![])]
+    );
+}
+
+#[test]
+fn computed_frames_match_javac() {
+    initialize();
+    let mut hierarchy = class_file::ClassHierarchyMap::new();
+    hierarchy.add_class("java/lang/Integer", Some("java/lang/Number"), false);
+    hierarchy.add_class("java/lang/Long", Some("java/lang/Number"), false);
+    // the multi-catch in DecompileTest
+    for exception in [
+        "java/lang/ClassCastException",
+        "java/lang/NullPointerException",
+    ] {
+        hierarchy.add_class(exception, Some("java/lang/RuntimeException"), false);
+    }
+    hierarchy.add_class(
+        "java/lang/RuntimeException",
+        Some("java/lang/Exception"),
+        false,
+    );
+    hierarchy.add_class("java/lang/Exception", Some("java/lang/Throwable"), false);
+    hierarchy.add_class("java/lang/Throwable", Some("java/lang/Object"), false);
+
+    let mut checked = 0;
+    for entry in fs::read_dir("./test_class_files").unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().unwrap_or_default() != "class" {
+            continue;
+        }
+        let cf = class_file::read_class_file(path.to_str().unwrap()).unwrap();
+        let class_name = cf.class_name();
+        for method in &cf.methods.methods {
+            let Some(code) = method.get_code() else {
+                continue;
+            };
+            let name = format!(
+                "{}.{}",
+                class_name,
+                cf.constant_pool.get_to_string(method.name_index)
+            );
+            let computed = method
+                .compute_frames(&class_name, &cf.constant_pool, &hierarchy)
+                .unwrap_or_else(|e| panic!("{}: {}", name, e));
+            assert_eq!(computed.max_stack, code.max_stack(), "{} max_stack", name);
+            assert_eq!(
+                computed.max_locals,
+                code.max_locals(),
+                "{} max_locals",
+                name
+            );
+            let javac = method
+                .stack_map_frames(&class_name, &cf.constant_pool)
+                .unwrap();
+            // javac drops the locals that went out of scope, the inferred frames keep
+            // every local that is still assigned on all paths
+            assert_eq!(computed.frames.len(), javac.len(), "{} frames", name);
+            for (computed, javac) in computed.frames.iter().zip(&javac) {
+                assert_eq!(computed.pc, javac.pc, "{}", name);
+                assert_eq!(computed.stack, javac.stack, "{} at {}", name, javac.pc);
+                assert!(
+                    computed.locals.starts_with(&javac.locals),
+                    "{} at {}: {:?} vs {:?}",
+                    name,
+                    javac.pc,
+                    computed.locals,
+                    javac.locals
+                );
+            }
+            checked += 1;
+        }
+    }
+    assert!(checked > 20);
+}
+
+#[test]
+fn update_frames_rewrites_stack_map_table() {
+    initialize();
+    let mut cf = class_file::read_class_file("./test_class_files/StackMapTest.class").unwrap();
+    let hierarchy = class_file::ClassHierarchyMap::new();
+    let class_name = cf.class_name();
+    let expected = cf
+        .methods
+        .methods
+        .iter()
+        .map(|m| m.compute_frames(&class_name, &cf.constant_pool, &hierarchy))
+        .collect::<Result<Vec<_>>>()
+        .unwrap();
+
+    cf.update_frames(&hierarchy).unwrap();
+
+    for (method, expected) in cf.methods.methods.iter().zip(expected) {
+        let code = method.get_code().unwrap();
+        assert_eq!(code.max_stack(), expected.max_stack);
+        assert_eq!(code.max_locals(), expected.max_locals);
+        assert_eq!(
+            code.attributes().get_stack_map_table().is_some(),
+            !expected.frames.is_empty()
+        );
+        assert_eq!(
+            method
+                .stack_map_frames(&class_name, &cf.constant_pool)
+                .unwrap(),
+            expected.frames
+        );
+    }
+
+    // without the hierarchy Integer and Long merge to Object
+    let pick = cf
+        .methods
+        .methods
+        .iter()
+        .find(|m| cf.constant_pool.get_to_string(m.name_index) == "pick")
+        .unwrap();
+    let frames = pick
+        .stack_map_frames(&class_name, &cf.constant_pool)
+        .unwrap();
+    assert_eq!(
+        frames[1].stack,
+        vec![class_file::VerificationType::Object(
+            "java/lang/Object".to_string()
+        )]
+    );
+}
+
+#[test]
+fn dead_code_is_replaced_by_nops_and_athrow() {
+    use class_file::{assembler, ByteCode, VerificationType};
+
+    let mut class_path = class_file::ClassPath::new();
+    class_path.add_jdk().unwrap();
+    let source = "\
+.version 61
+.class public super DeadCode
+.super java/lang/Object
+
+.method public static main([Ljava/lang/String;)V
+    .noframes
+Start:
+    getstatic java/lang/System.out:Ljava/io/PrintStream;
+    ldc \"live\"
+    invokevirtual java/io/PrintStream.println:(Ljava/lang/String;)V
+    goto End
+Dead:
+    iload_1
+    ifeq Dead
+    lload_2
+    pop2
+End:
+    return
+Handler:
+    pop
+    return
+    .catch java/lang/RuntimeException from Start to End using Handler
+.end method
+";
+    let mut cf = assembler::assemble(source, &class_path).unwrap();
+    let class_name = cf.class_name();
+    let computed = cf.methods.methods[0]
+        .compute_frames(&class_name, &cf.constant_pool, &class_path)
+        .unwrap();
+    let (start, end) = (11, 17);
+    assert_eq!(computed.dead_code, vec![(start, end)]);
+    let throwable = VerificationType::Object("java/lang/Throwable".to_string());
+    assert!(computed.frames.contains(&class_file::Frame {
+        pc: start as u16,
+        locals: vec![],
+        stack: vec![throwable],
+    }));
+
+    cf.update_frames(&class_path).unwrap();
+    let code = cf.methods.methods[0].get_code().unwrap();
+    let dead = code
+        .instructions()
+        .filter(|(pc, _)| (start..end).contains(pc))
+        .map(|(_, instruction)| instruction.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(dead.len(), 6);
+    assert!(dead[..5].iter().all(|i| *i == ByteCode::Nop));
+    assert_eq!(dead[5], ByteCode::Athrow);
+    // the handler no longer covers the dead code
+    let entries = code.exception_table();
+    assert_eq!(entries.len(), 1);
+    assert_eq!((entries[0].start_pc, entries[0].end_pc), (0, start as u16));
+    let errors = class_file::verify(&cf, &class_path);
+    assert!(errors.is_empty(), "{:?}", errors);
+
+    let dir = std::env::temp_dir().join("jaust_dead_code");
+    fs::remove_dir_all(&dir).unwrap_or(());
+    fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("DeadCode.class");
+    class_file::write_class_file(&cf, file.to_str().unwrap()).unwrap();
+    let output = Command::new("java")
+        .arg("-cp")
+        .arg(&dir)
+        .arg("DeadCode")
+        .output()
+        .expect("failed to execute java");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8(output.stderr).unwrap()
+    );
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "live\n");
+}
+
+#[test]
+fn common_super_class_stops_at_hierarchy_cycles() {
+    use class_file::ClassHierarchy;
+
+    let mut hierarchy = class_file::ClassHierarchyMap::new();
+    hierarchy.add_class("A", Some("B"), false);
+    hierarchy.add_class("B", Some("A"), false);
+    hierarchy.add_class("C", Some("D"), false);
+    hierarchy.add_class("D", Some("C"), false);
+    assert_eq!(hierarchy.common_super_class("A", "B"), "B");
+    assert_eq!(hierarchy.common_super_class("A", "C"), "java/lang/Object");
+    assert_eq!(hierarchy.common_super_class("C", "A"), "java/lang/Object");
+}
@@ -0,0 +1,201 @@
+mod common;
+
+use common::initialize;
+use jaust::class_file;
+
+use text_diff::assert_diff;
+
+use std::process::Command;
+
+#[test]
+fn interpreter_matches_java() {
+    use class_file::interpreter::{JavaException, Value, Vm};
+
+    initialize();
+    let java = Command::new("java")
+        .args(["-cp", "./test_class_files", "InterpreterTest"])
+        .output()
+        .expect("failed to execute java");
+    let java_stdout = String::from_utf8(java.stdout).unwrap();
+    let java_stderr = String::from_utf8(java.stderr).unwrap();
+
+    let mut class_path = class_file::ClassPath::new();
+    class_path
+        .add(std::path::Path::new("./test_class_files"))
+        .unwrap();
+    class_path.add_jdk().unwrap();
+    let mut vm = Vm::new(class_path);
+
+    // main ends with an uncaught ArithmeticException
+    let error = vm.run_main("InterpreterTest").unwrap_err();
+    assert_diff(&java_stdout, vm.output(), "", 0);
+    let exception = error.downcast_ref::<JavaException>().unwrap();
+    assert_eq!(exception.class, "java/lang/ArithmeticException");
+    assert_eq!(exception.message.as_deref(), Some("/ by zero"));
+    let first_line = java_stderr.lines().next().unwrap();
+    assert_eq!(
+        first_line,
+        format!("Exception in thread \"main\" {}", exception)
+    );
+
+    let result = vm
+        .invoke_static("InterpreterTest", "fib", "(I)I", vec![Value::Int(10)])
+        .unwrap();
+    assert_eq!(result, Some(Value::Int(55)));
+    let result = vm
+        .invoke_static("InterpreterTest", "factorial", "(I)J", vec![Value::Int(5)])
+        .unwrap();
+    assert_eq!(result, Some(Value::Long(120)));
+    let result = vm
+        .invoke_static(
+            "InterpreterTest",
+            "classify",
+            "(I)Ljava/lang/String;",
+            vec![Value::Int(1)],
+        )
+        .unwrap();
+    assert_eq!(vm.string(&result.unwrap()), Some("one"));
+}
+
+#[test]
+fn class_loader_links_classes() {
+    use class_file::interpreter::Vm;
+    use class_file::runtime::{ClassLoader, LinkageError};
+
+    initialize();
+    let java = Command::new("java")
+        .args(["-cp", "./test_class_files", "LinkingTest"])
+        .output()
+        .expect("failed to execute java");
+    let java_stdout = String::from_utf8(java.stdout).unwrap();
+
+    let class_path = || {
+        let mut class_path = class_file::ClassPath::new();
+        class_path
+            .add(std::path::Path::new("./test_class_files"))
+            .unwrap();
+        class_path.add_jdk().unwrap();
+        class_path
+    };
+    let mut vm = Vm::new(class_path());
+    vm.run_main("LinkingTest").unwrap();
+    assert_diff(&java_stdout, vm.output(), "", 0);
+
+    let loader = ClassLoader::new(class_path());
+    let names = |ids: Vec<_>| {
+        ids.into_iter()
+            .map(|id| loader.class(id).name.clone())
+            .collect::<Vec<_>>()
+    };
+    // Titled has no default method so it is not initialized with its implementations
+    let order = loader.initialization_order("LinkingTest$Person").unwrap();
+    assert_eq!(
+        names(order),
+        [
+            "java/lang/Object",
+            "LinkingTest$Counter",
+            "LinkingTest$Base",
+            "LinkingTest$Named",
+            "LinkingTest$Loud",
+            "LinkingTest$Person"
+        ]
+    );
+
+    // the default method of the most specific interface
+    let greet = loader
+        .resolve_method("LinkingTest$Person", "greet", "()Ljava/lang/String;")
+        .unwrap();
+    assert_eq!(
+        loader.method_string(greet),
+        "LinkingTest$Loud.greet()Ljava/lang/String;"
+    );
+    let named_greet = loader
+        .resolve_interface_method("LinkingTest$Named", "greet", "()Ljava/lang/String;")
+        .unwrap();
+    let selected = loader
+        .select_method("LinkingTest$Person", named_greet)
+        .unwrap();
+    assert_eq!(selected, greet);
+
+    // overriding methods take the vtable entry of the method they override
+    let shape_area = loader
+        .resolve_method("LinkingTest$Shape", "area", "()I")
+        .unwrap();
+    let square_area = loader
+        .select_method("LinkingTest$Square", shape_area)
+        .unwrap();
+    assert_eq!(
+        loader.method_string(square_area),
+        "LinkingTest$Square.area()I"
+    );
+    let shape = loader.class(shape_area.class);
+    let square = loader.load("LinkingTest$Square").unwrap();
+    let index = shape
+        .vtable()
+        .iter()
+        .position(|m| *m == shape_area)
+        .unwrap();
+    assert_eq!(square.vtable()[index], square_area);
+    assert_eq!(square.vtable().len(), shape.vtable().len());
+
+    // private methods are not overridden
+    let hidden = loader
+        .resolve_method("LinkingTest$Secret", "hidden", "()Ljava/lang/String;")
+        .unwrap();
+    assert_eq!(
+        loader
+            .select_method("LinkingTest$MoreSecret", hidden)
+            .unwrap(),
+        hidden
+    );
+
+    // Both overrides the conflicting default methods of Left and Right
+    let both = loader.load("LinkingTest$Both").unwrap();
+    let defaults =
+        loader.maximally_specific_methods(both.superinterfaces(), "side", "()Ljava/lang/String;");
+    assert_eq!(defaults.len(), 2);
+    let left = loader.load("LinkingTest$Left").unwrap();
+    let itable = both.itable(left.id).unwrap();
+    assert_eq!(
+        loader.method_string(itable[0].unwrap()),
+        "LinkingTest$Both.side()Ljava/lang/String;"
+    );
+
+    // static fields are found through the super interfaces
+    let field = loader
+        .resolve_field("LinkingTest$Person", "NAMED", "I")
+        .unwrap();
+    assert_eq!(loader.class(field.class).name, "LinkingTest$Named");
+
+    let linkage_error = |error: anyhow::Error| error.downcast::<LinkageError>().unwrap();
+    assert_eq!(
+        linkage_error(
+            loader
+                .resolve_method("LinkingTest$Named", "name", "()Ljava/lang/String;")
+                .unwrap_err()
+        ),
+        LinkageError::IncompatibleClassChange(
+            "Found interface LinkingTest$Named, but class was expected".to_string()
+        )
+    );
+    assert_eq!(
+        linkage_error(
+            loader
+                .resolve_method("LinkingTest$Person", "missing", "()V")
+                .unwrap_err()
+        ),
+        LinkageError::NoSuchMethod("LinkingTest$Person.missing()V".to_string())
+    );
+    assert_eq!(
+        linkage_error(
+            loader
+                .resolve_field("LinkingTest$Person", "NAMED", "J")
+                .unwrap_err()
+        ),
+        LinkageError::NoSuchField("NAMED".to_string())
+    );
+    assert_eq!(
+        linkage_error(loader.load("LinkingTest$Missing").unwrap_err()),
+        LinkageError::NoClassDefFound("LinkingTest$Missing".to_string())
+    );
+}
@@ -1,3 +1,6 @@
+mod common;
+
+use common::initialize;
 use jaust::class_file;
 
 use text_diff::assert_diff;
@@ -5,47 +8,20 @@ use text_diff::assert_diff;
 use anyhow::Result;
 use std::fs;
 use std::process::Command;
-use std::sync::Once;
-
-static INIT: Once = Once::new();
 
-fn initialize() {
-    INIT.call_once(|| {
-        // clean class files
-        fs::remove_dir_all("./test_class_files").unwrap_or(());
+fn javap_summary(file: &str) -> Result<String> {
+    javap_summary_with(file, &[])
+}
 
-        // java files to compile (all in test_files recursively that end in .java)
-        let files = fs::read_dir("./test_files")
-            .unwrap()
-            .map(|f| f.unwrap().path())
-            .filter(|f| f.extension().unwrap() == "java");
-
-        // compile example classes
-        let out = Command::new("javac")
-            .arg("-d")
-            .arg("./test_class_files")
-            .args(files)
-            .output()
-            .expect("failed to execute javac");
-
-        if !out.status.success() {
-            let mut msg = format!("javac failed: {:?}", out.status);
-            msg.push_str(&format!(
-                "stdout:\n{}",
-                String::from_utf8(out.stdout).unwrap()
-            ));
-            msg.push_str(&format!(
-                "stderr:\n{}",
-                String::from_utf8(out.stderr).unwrap()
-            ));
-            panic!("{}", msg);
-        }
-    });
+fn javap_summary_with(file: &str, args: &[&str]) -> Result<String> {
+    let mut args = args.to_vec();
+    args.insert(0, "-private");
+    javap_output(file, &args)
 }
 
-fn javap_summary(file: &str) -> Result<String> {
+fn javap_output(file: &str, args: &[&str]) -> Result<String> {
     let javap_out = Command::new("javap")
-        .arg("-private")
+        .args(args)
         .arg(file)
         .output()
         .expect("failed to execute javap");
@@ -69,10 +45,11 @@ fn jaustp_test_template(file: &str) {
     initialize();
     let cf = class_file::read_class_file(file).unwrap();
     let ops = class_file::JavapOptions {
-        private: true,
+        visibility: class_file::Visibility::Private,
         code: false,
+        ..Default::default()
     };
-    let jaustp_out = class_file::jaustp_summary(&cf, &ops);
+    let jaustp_out = class_file::jaustp_summary(&cf, &ops).unwrap();
     let javap_out = javap_summary(file).unwrap();
     assert_diff(&jaustp_out, &javap_out, "\n", 0);
 }
@@ -95,5 +72,491 @@ javap_tests! {
     jaustp_c_test: "./test_class_files/C.class",
     jaustp_my_class1_test: "./test_class_files/MyClass1.class",
     jaustp_my_class2_test: "./test_class_files/MyClass2.class",
+    jaustp_generic_test: "./test_class_files/GenericTest.class",
+    jaustp_generic_deep_test: "./test_class_files/GenericTest$Inner$Deep.class",
+    jaustp_interface_methods_test: "./test_class_files/GenericShapes.class",
+    jaustp_interface_extends_test: "./test_class_files/LinkingTest$Loud.class",
+    jaustp_interfaces_test: "./test_class_files/LinkingTest$Person.class",
+}
+
+fn jaustp_descriptors_test_template(file: &str) {
+    initialize();
+    let cf = class_file::read_class_file(file).unwrap();
+    let ops = class_file::JavapOptions {
+        visibility: class_file::Visibility::Private,
+        descriptors: true,
+        ..Default::default()
+    };
+    let jaustp_out = class_file::jaustp_summary(&cf, &ops).unwrap();
+    let javap_out = javap_summary_with(file, &["-s"]).unwrap();
+    assert_diff(&jaustp_out, &javap_out, "\n", 0);
+}
+
+macro_rules! javap_descriptors_tests {
+    ($($name:ident: $value:expr,)*) => {
+    $(
+        #[test]
+        fn $name() {
+            jaustp_descriptors_test_template($value);
+        }
+    )*
+    }
+}
+
+javap_descriptors_tests! {
+    jaustp_example_descriptors_test: "./test_class_files/Example.class",
+    jaustp_constants_descriptors_test: "./test_class_files/ConstantsTest.class",
+    jaustp_c_descriptors_test: "./test_class_files/C.class",
+    jaustp_nested_descriptors_test: "./test_class_files/NestedTest.class",
+    jaustp_stack_map_descriptors_test: "./test_class_files/StackMapTest.class",
+}
+
+/// Diffs the members and the InnerClasses attribute (`-v`) printed at each visibility
+fn jaustp_visibility_test_template(file: &str) {
+    use class_file::Visibility;
+
+    initialize();
+    let cf = class_file::read_class_file(file).unwrap();
+    for (visibility, javap_option) in [
+        (Visibility::Public, "-public"),
+        (Visibility::Protected, "-protected"),
+        (Visibility::Package, "-package"),
+        (Visibility::Private, "-private"),
+    ] {
+        let mut ops = class_file::JavapOptions {
+            visibility,
+            ..Default::default()
+        };
+        let jaustp_out = class_file::jaustp_summary(&cf, &ops).unwrap();
+        let javap_out = javap_output(file, &[javap_option]).unwrap();
+        assert_diff(&jaustp_out, &javap_out, "\n", 0);
+
+        ops.verbose = true;
+        let jaustp_out = class_file::jaustp_summary(&cf, &ops).unwrap();
+        let javap_out = javap_output(file, &[javap_option, "-v"]).unwrap();
+        let javap_inner_classes = javap_out
+            .find("\nInnerClasses:\n")
+            .map_or("", |i| &javap_out[i + 1..]);
+        let jaustp_inner_classes = jaustp_out
+            .find("\nInnerClasses:\n")
+            .map_or("", |i| &jaustp_out[i + 1..]);
+        assert_diff(jaustp_inner_classes, javap_inner_classes, "\n", 0);
+    }
+}
+
+macro_rules! javap_visibility_tests {
+    ($($name:ident: $value:expr,)*) => {
+    $(
+        #[test]
+        fn $name() {
+            jaustp_visibility_test_template($value);
+        }
+    )*
+    }
+}
+
+javap_visibility_tests! {
+    jaustp_example_visibility_test: "./test_class_files/Example.class",
+    jaustp_base_visibility_test: "./test_class_files/LinkingTest$Base.class",
+    jaustp_nested_visibility_test: "./test_class_files/NestedTest.class",
+    jaustp_inner_visibility_test: "./test_class_files/NestedTest$Inner.class",
+}
+
+#[test]
+fn jaustp_sysinfo_test() {
+    initialize();
+    let file = "./test_class_files/Example.class";
+    // jaustp prints the modification date in UTC
+    let javap_out = Command::new("javap")
+        .env("TZ", "UTC")
+        .args(["-sysinfo", "-s", file])
+        .output()
+        .unwrap();
+    let jaustp_out = Command::new(env!("CARGO_BIN_EXE_jaustp"))
+        .args(["--sysinfo", "-s", file])
+        .output()
+        .unwrap();
+    assert!(jaustp_out.status.success());
+    assert_diff(
+        &String::from_utf8(jaustp_out.stdout).unwrap(),
+        &String::from_utf8(javap_out.stdout).unwrap(),
+        "\n",
+        0,
+    );
+}
+
+#[test]
+fn jaustp_constants_test() {
+    initialize();
+    let file = "./test_class_files/ConstantsTest.class";
+    let cf = class_file::read_class_file(file).unwrap();
+    let ops = class_file::JavapOptions {
+        visibility: class_file::Visibility::Private,
+        constants: true,
+        ..Default::default()
+    };
+    let jaustp_out = class_file::jaustp_summary(&cf, &ops).unwrap();
+    let javap_out = javap_summary_with(file, &["-constants"]).unwrap();
+    assert_diff(&jaustp_out, &javap_out, "\n", 0);
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn jaustp_json_output() {
+    initialize();
+    let jaustp_json = |args: &[&str]| -> serde_json::Value {
+        let out = Command::new(env!("CARGO_BIN_EXE_jaustp"))
+            .args(["--format", "json"])
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(out.status.success());
+        serde_json::from_slice(&out.stdout).unwrap()
+    };
+
+    let json = jaustp_json(&["./test_class_files/Example.class"]);
+    assert_eq!(json["name"], "Example");
+    assert_eq!(json["super_class"], "B");
+    assert_eq!(json["interfaces"], serde_json::json!(["C"]));
+    assert_eq!(json["flags"], serde_json::json!(["public", "super"]));
+    let methods = json["methods"].as_array().unwrap();
+    let method = |name: &str| methods.iter().find(|m| m["name"] == name).unwrap();
+    assert_eq!(method("add2")["descriptor"], "(JJ)J");
+    assert_eq!(
+        method("add2")["exceptions"],
+        serde_json::json!(["java/lang/Exception"])
+    );
+    assert_eq!(
+        method("weird")["annotations"],
+        serde_json::json!([{ "type": "Ljava/lang/Deprecated;", "elements": {} }])
+    );
+    assert!(method("add").get("code").is_none());
+    // private members only with -p, like the text output
+    assert!(json["fields"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .all(|f| f["name"] != "my_double"));
+
+    let json = jaustp_json(&["-p", "-c", "./test_class_files/ConstantsTest.class"]);
+    let fields = json["fields"].as_array().unwrap();
+    let field = |name: &str| fields.iter().find(|f| f["name"] == name).unwrap();
+    assert_eq!(field("BIG")["constant_value"], 12345678901i64);
+    assert_eq!(
+        field("notConstant")["constant_value"],
+        serde_json::Value::Null
+    );
+    let code = &json["methods"][0]["code"];
+    assert_eq!(code["instructions"][0]["pc"], 0);
+    assert!(code["max_stack"].as_u64().is_some());
+
+    let json = jaustp_json(&["-p", "-c", "./test_class_files/StackMapTest.class"]);
+    let methods = json["methods"].as_array().unwrap();
+    let code = |name: &str| &methods.iter().find(|m| m["name"] == name).unwrap()["code"];
+    let instructions = |name: &str| {
+        code(name)["instructions"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|i| i["instruction"].as_str().unwrap().to_string())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        instructions("parse"),
+        [
+            "aload 0",
+            "invokestatic java/lang/Integer.parseInt:(Ljava/lang/String;)I",
+            "ireturn",
+            "astore 1",
+            "iconst_m1",
+            "ireturn"
+        ]
+    );
+    assert_eq!(
+        code("parse")["exception_table"],
+        serde_json::json!([{
+            "start_pc": 0,
+            "end_pc": 4,
+            "handler_pc": 5,
+            "catch_type": "java/lang/NumberFormatException"
+        }])
+    );
+    let describe = instructions("describe");
+    assert_eq!(
+        describe[1],
+        "lookupswitch { 0: 36, 1: 39, 10: 42, default: 45 }"
+    );
+    assert!(describe.contains(&"ldc \"zero\"".to_string()));
+    assert!(describe.contains(&"ifle 58".to_string()));
+
+    // finally catches everything
+    let json = jaustp_json(&["-p", "-c", "./test_class_files/DecompileTest.class"]);
+    let catch_types = json["methods"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .flat_map(|m| {
+            m["code"]["exception_table"]
+                .as_array()
+                .cloned()
+                .unwrap_or_default()
+        })
+        .map(|e| e["catch_type"].clone())
+        .collect::<Vec<_>>();
+    assert!(catch_types.contains(&serde_json::Value::Null));
+}
+
+#[test]
+fn deprecated_and_constant_fields() {
+    initialize();
+    let cf = class_file::read_class_file("./test_class_files/ConstantsTest.class").unwrap();
+    let field = |name: &str| {
+        cf.fields
+            .fields
+            .iter()
+            .find(|f| cf.constant_pool.get_to_string(f.name_index) == name)
+            .unwrap()
+    };
+    assert!(field("OLD").is_deprecated());
+    assert!(!field("MAX").is_deprecated());
+    assert_eq!(
+        field("BIG")
+            .constant_value(&cf.constant_pool)
+            .unwrap()
+            .unwrap(),
+        class_file::ConstantValue::Long(12345678901)
+    );
+    assert_eq!(
+        field("S")
+            .constant_value(&cf.constant_pool)
+            .unwrap()
+            .unwrap(),
+        class_file::ConstantValue::String("hello \"world\"\n\ttab 'single'".to_string())
+    );
+    assert!(field("notConstant")
+        .constant_value(&cf.constant_pool)
+        .is_none());
+}
+
+#[test]
+fn invalid_constant_values_are_errors() {
+    use class_file::assembler;
+
+    let source = ".class public A\n.super java/lang/Object\n.field public static final X I = 5\n";
+    let cf = assembler::assemble(source, &class_file::ClassHierarchyMap::new()).unwrap();
+    let name = (1..=cf.constant_pool.len() as u16)
+        .find(|i| cf.constant_pool.utf8(*i).ok() == Some("ConstantValue"))
+        .unwrap();
+    // the attribute name, a length of 2 and the index of the value
+    let mut bytes = cf.to_bytes().unwrap();
+    let attribute = [(name >> 8) as u8, name as u8, 0, 0, 0, 2];
+    let value = bytes.windows(6).position(|w| w == attribute).unwrap() + 6;
+    bytes[value..value + 2].copy_from_slice(&[0xff, 0xff]);
+
+    let cf = class_file::read_class_bytes(bytes).unwrap();
+    let error = cf.fields.fields[0]
+        .constant_value(&cf.constant_pool)
+        .unwrap()
+        .unwrap_err();
+    assert_eq!(error.to_string(), "Invalid ConstantValue index #65535");
+    #[cfg(feature = "json")]
+    assert!(class_file::jaustp_json(&cf, &class_file::JavapOptions::default()).is_err());
+}
+
+// javap (17) does not print sealed hierarchies, record headers or `@interface`, so these compare
+// against the expected class lines
+fn jaustp_class_line(file: &str) -> String {
+    initialize();
+    let cf = class_file::read_class_file(file).unwrap();
+    let ops = class_file::JavapOptions {
+        visibility: class_file::Visibility::Private,
+        class_path: vec!["./test_class_files".to_string()],
+        ..Default::default()
+    };
+    let out = class_file::jaustp_summary(&cf, &ops).unwrap();
+    out.lines().nth(1).unwrap().to_string()
+}
+
+#[test]
+fn jaustp_sealed_class_lines() {
+    let cases = vec![
+        (
+            "./test_class_files/SealedTest.class",
+            "public sealed interface SealedTest permits SealedCircle, SealedSquare, SealedShape {",
+        ),
+        (
+            "./test_class_files/SealedCircle.class",
+            "final class SealedCircle implements SealedTest {",
+        ),
+        (
+            "./test_class_files/SealedSquare.class",
+            "non-sealed class SealedSquare implements SealedTest {",
+        ),
+        (
+            "./test_class_files/SealedShape.class",
+            "abstract sealed class SealedShape implements SealedTest permits SealedShape$Triangle {",
+        ),
+        (
+            "./test_class_files/SealedOperation.class",
+            "abstract class SealedOperation extends java.lang.Enum<SealedOperation> {",
+        ),
+        (
+            "./test_class_files/SealedOperation$1.class",
+            "final class SealedOperation$1 extends SealedOperation {",
+        ),
+    ];
+    for (file, expected) in cases {
+        assert_eq!(jaustp_class_line(file), expected);
+    }
+}
+
+fn jaustp_members_test_template(file: &str) {
+    initialize();
+    let cf = class_file::read_class_file(file).unwrap();
+    let ops = class_file::JavapOptions {
+        visibility: class_file::Visibility::Private,
+        ..Default::default()
+    };
+    let members = |out: &str| out.lines().skip(2).collect::<Vec<_>>().join("\n");
+    let jaustp_out = class_file::jaustp_summary(&cf, &ops).unwrap();
+    let javap_out = javap_summary(file).unwrap();
+    assert_diff(&members(&jaustp_out), &members(&javap_out), "\n", 0);
+}
+
+// the members after the class line are printed like javap does
+macro_rules! javap_members_tests {
+    ($($name:ident: $value:expr,)*) => {
+    $(
+        #[test]
+        fn $name() {
+            jaustp_members_test_template($value);
+        }
+    )*
+    }
+}
+
+javap_members_tests! {
     jaustp_com_example_record_test: "./test_class_files/com/example/RecordTest.class",
+    jaustp_generic_point_test: "./test_class_files/GenericPoint.class",
+    jaustp_generic_marker_test: "./test_class_files/GenericMarker.class",
+}
+
+#[test]
+fn jaustp_record_and_annotation_class_lines() {
+    let cases = vec![
+        (
+            "./test_class_files/com/example/RecordTest.class",
+            "public record com.example.RecordTest(int a, long b) {",
+        ),
+        (
+            "./test_class_files/GenericPoint.class",
+            "record GenericPoint<T>(int x, java.lang.String y, java.util.List<T> z) implements java.lang.Comparable<GenericPoint<T>> {",
+        ),
+        (
+            "./test_class_files/GenericMarker.class",
+            "@interface GenericMarker {",
+        ),
+    ];
+    for (file, expected) in cases {
+        assert_eq!(jaustp_class_line(file), expected);
+    }
+}
+
+#[test]
+fn enclosing_method_and_nest_attributes() {
+    initialize();
+    let cf = class_file::read_class_file("./test_class_files/SealedTest$1.class").unwrap();
+    let enclosing = cf.enclosing_method().unwrap().unwrap();
+    assert_eq!(
+        enclosing,
+        class_file::EnclosingMethod {
+            class: "SealedTest".to_string(),
+            method_name: Some("runner".to_string()),
+            method_descriptor: Some("()Ljava/lang/Runnable;".to_string()),
+        }
+    );
+    assert_eq!(cf.nest_host(), Some("SealedTest".to_string()));
+
+    let host = class_file::read_class_file("./test_class_files/SealedTest.class").unwrap();
+    assert_eq!(host.nest_members(), vec!["SealedTest$1".to_string()]);
+    assert!(host.enclosing_method().is_none());
+
+    // the attribute name, a length of 4, the class and the method
+    let name = (1..=cf.constant_pool.len() as u16)
+        .find(|i| cf.constant_pool.utf8(*i).ok() == Some("EnclosingMethod"))
+        .unwrap();
+    let mut bytes = fs::read("./test_class_files/SealedTest$1.class").unwrap();
+    let attribute = [(name >> 8) as u8, name as u8, 0, 0, 0, 4];
+    let class = bytes.windows(6).position(|w| w == attribute).unwrap() + 6;
+    bytes[class..class + 2].copy_from_slice(&[0xff, 0xff]);
+    let cf = class_file::read_class_bytes(bytes).unwrap();
+    assert!(cf.enclosing_method().unwrap().is_err());
+}
+
+fn javap_inner_classes(file: &str) -> String {
+    let javap_out = Command::new("javap")
+        .arg("-v")
+        .arg("-private")
+        .arg(file)
+        .output()
+        .expect("failed to execute javap");
+    let out = String::from_utf8(javap_out.stdout).unwrap();
+    let mut block = String::new();
+    for line in out.lines().skip_while(|l| !l.starts_with("InnerClasses:")) {
+        if !block.is_empty() && !line.starts_with(' ') {
+            break;
+        }
+        block.push_str(line);
+        block.push('\n');
+    }
+    block
+}
+
+#[test]
+fn jaustp_inner_classes_match_javap() {
+    initialize();
+    let files = vec![
+        "./test_class_files/EnumTest.class",
+        "./test_class_files/NestedTest.class",
+        "./test_class_files/NestedTest$1.class",
+        "./test_class_files/NestedTest$Inner$Deep.class",
+        "./test_class_files/SealedShape.class",
+    ];
+    for file in files {
+        let cf = class_file::read_class_file(file).unwrap();
+        let ops = class_file::JavapOptions {
+            visibility: class_file::Visibility::Private,
+            verbose: true,
+            ..Default::default()
+        };
+        let out = class_file::jaustp_summary(&cf, &ops).unwrap();
+        let jaustp_block = out.split_once("}\n").unwrap().1;
+        assert_diff(jaustp_block, &javap_inner_classes(file), "\n", 0);
+    }
+}
+
+#[test]
+fn inner_class_source_names() {
+    initialize();
+    let cf = class_file::read_class_file("./test_class_files/NestedTest$Inner$Deep.class").unwrap();
+    assert_eq!(cf.source_name(&cf.class_name()), "NestedTest.Inner.Deep");
+    assert_eq!(cf.source_name("NestedTest$Inner"), "NestedTest.Inner");
+    assert_eq!(cf.source_name("java/lang/Object"), "java.lang.Object");
+
+    let cf = class_file::read_class_file("./test_class_files/NestedTest.class").unwrap();
+    let anonymous = cf
+        .inner_classes()
+        .into_iter()
+        .find(|c| c.is_anonymous())
+        .unwrap();
+    assert_eq!(
+        anonymous.inner_class_name(&cf.constant_pool),
+        "NestedTest$1"
+    );
+    assert_eq!(anonymous.outer_class_name(&cf.constant_pool), None);
+    assert_eq!(cf.source_name("NestedTest$1"), "NestedTest$1");
+    assert_eq!(
+        cf.source_name("NestedTest$PrivateStatic"),
+        "NestedTest.PrivateStatic"
+    );
 }
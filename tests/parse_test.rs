@@ -0,0 +1,298 @@
+mod common;
+
+use common::initialize;
+use jaust::class_file;
+
+use std::fs;
+use std::process::Command;
+
+#[test]
+fn typed_constant_pool_references() {
+    use class_file::{MemberRef, MethodHandleReferenceKind};
+
+    initialize();
+    let cf = class_file::read_class_file("./test_class_files/LinkingTest$Loud.class").unwrap();
+    let cp = &cf.constant_pool;
+
+    // Named.super.greet()
+    let greet = cp.method_ref(1).unwrap();
+    assert_eq!(
+        (
+            greet.owner.as_str(),
+            greet.name.as_str(),
+            greet.is_interface
+        ),
+        ("LinkingTest$Named", "greet", true)
+    );
+    assert_eq!(greet.descriptor, "()Ljava/lang/String;");
+    let trace = cp.method_ref(13).unwrap();
+    assert!(!trace.is_interface);
+    assert_eq!(trace.to_string(), "LinkingTest.trace:(Ljava/lang/String;)I");
+
+    let field = cp.field_ref(19).unwrap();
+    assert_eq!(field.to_string(), "LinkingTest$Loud.LOUD:I");
+    assert_eq!(cp.class_name(25).unwrap(), "java/lang/Object");
+    assert_eq!(cp.string(11).unwrap(), "Loud");
+    assert_eq!(cp.utf8(29).unwrap(), "<clinit>");
+    assert_eq!(
+        cp.name_and_type(3).unwrap(),
+        ("greet", "()Ljava/lang/String;")
+    );
+
+    let call_site = cp.invoke_dynamic(7).unwrap();
+    assert_eq!(call_site.bootstrap_method_attr_index, 0);
+    assert_eq!(call_site.name, "makeConcatWithConstants");
+    let handle = cp.method_handle(35).unwrap();
+    assert_eq!(handle.kind, MethodHandleReferenceKind::RefInvokeStatic);
+    match handle.reference {
+        MemberRef::Method(method) => {
+            assert_eq!(method.owner, "java/lang/invoke/StringConcatFactory");
+            assert_eq!(method.name, "makeConcatWithConstants");
+        }
+        MemberRef::Field(field) => panic!("Unexpected field {}", field),
+    }
+
+    let error = |result: anyhow::Result<String>| result.unwrap_err().to_string();
+    assert_eq!(
+        error(cp.field_ref(13).map(|f| f.to_string())),
+        "#13 must be a Fieldref entry, not Methodref"
+    );
+    assert_eq!(
+        error(cp.class_name(4).map(str::to_string)),
+        "#4 must be a Class entry, not Utf8"
+    );
+    assert_eq!(
+        error(cp.string(0).map(str::to_string)),
+        "#0 must be a String entry, not missing"
+    );
+}
+
+#[test]
+fn lazy_parsing_decodes_attributes_on_access() {
+    initialize();
+    let file = "./test_class_files/StackMapTest.class";
+    let eager = class_file::read_class_file(file).unwrap();
+    let lazy = class_file::read_class_file_with(file, class_file::Parsing::Lazy).unwrap();
+    let decoded = |cf: &class_file::ClassFile| {
+        cf.methods
+            .methods
+            .iter()
+            .filter(|m| m.attributes.is_decoded())
+            .count()
+    };
+
+    assert_eq!(lazy.class_name(), eager.class_name());
+    assert_eq!(decoded(&lazy), 0);
+    assert!(!lazy.attributes.is_decoded());
+    let method = lazy.find_method("parse", "(Ljava/lang/String;)I").unwrap();
+    assert!(method.get_code().is_some());
+    assert!(method.attributes.decode().is_ok());
+    assert_eq!(decoded(&lazy), 1);
+
+    let ops = class_file::JavapOptions {
+        visibility: class_file::Visibility::Private,
+        code: true,
+        ..Default::default()
+    };
+    assert_eq!(
+        class_file::jaustp_summary(&lazy, &ops).unwrap(),
+        class_file::jaustp_summary(&eager, &ops).unwrap()
+    );
+    assert_eq!(decoded(&lazy), lazy.methods.methods.len());
+    assert!(class_file::check_format(&lazy).is_empty());
+}
+
+#[test]
+fn lazy_parsing_reports_corrupt_attribute_tables() {
+    use class_file::assembler;
+
+    // the Exceptions attribute is 2 bytes long, as its length says, but lists 5 classes
+    let source = ".class public A\n.super java/lang/Object\n\
+        .method public static m()V\n    .attribute Exceptions 0005\n    return\n.end method\n";
+    let cf = assembler::assemble(source, &class_file::ClassHierarchyMap::new()).unwrap();
+    let bytes = cf.to_bytes().unwrap();
+    assert!(class_file::read_class_bytes(bytes.clone()).is_err());
+
+    let mut lazy =
+        class_file::read_class_bytes_with(bytes.clone(), class_file::Parsing::Lazy).unwrap();
+    let method = lazy.find_method("m", "()V").unwrap();
+    assert!(method.attributes.decode().is_err());
+    let error = lazy.decode_attributes().unwrap_err().to_string();
+    let ops = class_file::JavapOptions::default();
+    let summary = class_file::jaustp_summary(&lazy, &ops).unwrap_err();
+    assert_eq!(summary.to_string(), error);
+    #[cfg(feature = "json")]
+    assert!(class_file::jaustp_json(&lazy, &ops).is_err());
+
+    // the method has code, it just can't be decoded
+    let hierarchy = class_file::ClassHierarchyMap::new();
+    assert_eq!(method.code().unwrap_err().to_string(), error);
+    assert!(method
+        .compute_frames("A", &lazy.constant_pool, &hierarchy)
+        .is_err());
+    let errors = class_file::verify(&lazy, &class_file::ClassPath::new());
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].to_string().ends_with(&error), "{}", errors[0]);
+
+    // nothing is lost when the class is written after a failed change
+    assert!(lazy.update_frames(&hierarchy).is_err());
+    let method = &mut lazy.methods.methods[0];
+    assert!(method.attributes.attributes_mut().is_err());
+    assert!(lazy.to_bytes().unwrap() == bytes);
+}
+
+#[test]
+fn borrowed_parsing_slices_the_input() {
+    initialize();
+    let bytes = fs::read("./test_class_files/StackMapTest.class").unwrap();
+    let cf = class_file::borrowed::ClassFile::parse(&bytes).unwrap();
+    let owned = cf.decode().unwrap();
+    let cp = &cf.constant_pool;
+
+    assert_eq!(cf.class_name().unwrap(), owned.class_name());
+    assert_eq!(cp.len(), owned.constant_pool.len());
+    assert_eq!(cf.methods.len(), owned.methods.methods.len());
+    let input = bytes.as_ptr_range();
+    assert!(input.contains(&cp.class_name(cf.this_class).unwrap().as_ptr()));
+    assert!(cp
+        .utf8(cf.this_class)
+        .unwrap_err()
+        .to_string()
+        .starts_with(&format!(
+            "#{} must be a Utf8 entry, not Class",
+            cf.this_class
+        )));
+
+    let method = cf.find_method("parse", "(Ljava/lang/String;)I").unwrap();
+    let code_attribute = method.attribute(cp, "Code").unwrap();
+    assert!(input.contains(&code_attribute.info.as_ptr()));
+    let code = code_attribute.code().unwrap();
+    let expected = owned
+        .find_method("parse", "(Ljava/lang/String;)I")
+        .unwrap()
+        .get_code()
+        .unwrap();
+    assert_eq!(code.max_stack, expected.max_stack());
+    assert_eq!(code.code.len() as u32, expected.code_length());
+    assert_eq!(code.exception_table.len(), expected.exception_table().len());
+    assert_eq!(code.instructions().unwrap().len(), expected.code().len());
+    assert!(code.attribute(cp, "StackMapTable").is_some());
+
+    assert!(class_file::borrowed::ClassFile::parse(&bytes[..bytes.len() - 1]).is_err());
+}
+
+#[test]
+fn borrowed_parsing_decodes_modified_utf8() {
+    use class_file::builder::{ClassBuilder, Constant, ACC_FINAL, ACC_STATIC};
+
+    // NUL and the emoji are written as C0 80 and a surrogate pair, not as UTF-8
+    let value = "nul \0 and \u{1f600}";
+    let bytes = ClassBuilder::new("Utf8Test")
+        .constant_field(
+            ACC_STATIC | ACC_FINAL,
+            "VALUE",
+            "Ljava/lang/String;",
+            Constant::String(value.to_string()),
+        )
+        .build(&class_file::ClassHierarchyMap::new())
+        .unwrap()
+        .to_bytes()
+        .unwrap();
+    let cf = class_file::borrowed::ClassFile::parse(&bytes).unwrap();
+    let owned = cf.decode().unwrap();
+    let input = bytes.as_ptr_range();
+    let mut decoded = 0;
+    for index in 1..=cf.constant_pool.len() as u16 {
+        let Ok(utf8) = cf.constant_pool.utf8(index) else {
+            continue;
+        };
+        assert_eq!(utf8, owned.constant_pool.utf8(index).unwrap());
+        if utf8 == value {
+            assert!(!input.contains(&utf8.as_ptr()));
+            decoded += 1;
+        } else {
+            assert!(input.contains(&utf8.as_ptr()), "{}", utf8);
+        }
+    }
+    assert_eq!(decoded, 1);
+}
+
+#[test]
+fn parse_all_keeps_input_order_and_errors() {
+    initialize();
+    let jar_path = std::env::temp_dir().join("jaust_parse_all.jar");
+    let out = Command::new("jar")
+        .args([
+            "cf",
+            jar_path.to_str().unwrap(),
+            "-C",
+            "./test_class_files",
+            ".",
+        ])
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+
+    let inputs = [
+        "./test_class_files/StackMapTest.class".to_string(),
+        "./missing.class".to_string(),
+        "./test_class_files".to_string(),
+        jar_path.to_str().unwrap().to_string(),
+    ];
+    let parsed: Vec<_> = class_file::parse_all(&inputs, class_file::Parsing::Lazy).collect();
+    assert_eq!(
+        parsed[0].class_file.as_ref().unwrap().class_name(),
+        "StackMapTest"
+    );
+    assert_eq!(parsed[1].source.to_string(), "./missing.class");
+    assert!(parsed[1].class_file.is_err());
+    assert_eq!(parsed.iter().filter(|p| p.class_file.is_err()).count(), 1);
+
+    // the class files of the directory and the jar are sorted by path
+    let class_count = (parsed.len() - 2) / 2;
+    let (in_dir, in_jar) = parsed[2..].split_at(class_count);
+    let sources: Vec<String> = in_dir.iter().map(|p| p.source.to_string()).collect();
+    let mut sorted = sources.clone();
+    sorted.sort();
+    assert_eq!(sources, sorted);
+    assert!(sources.contains(&"./test_class_files/com/example/RecordTest.class".to_string()));
+    for (dir, jar) in in_dir.iter().zip(in_jar) {
+        let name = dir.class_file.as_ref().unwrap().class_name();
+        assert_eq!(jar.class_file.as_ref().unwrap().class_name(), name);
+        assert_eq!(
+            jar.source.to_string(),
+            format!("{}!/{}.class", jar_path.display(), name)
+        );
+    }
+
+    // more class files than are parsed at a time still come out in the order of the inputs
+    let inputs = vec!["./test_class_files"; 40];
+    let sources: Vec<String> = class_file::parse_all(&inputs, class_file::Parsing::Lazy)
+        .map(|p| p.source.to_string())
+        .collect();
+    assert_eq!(sources.len(), class_count * 40);
+    for chunk in sources.chunks(class_count) {
+        assert_eq!(chunk, &sources[..class_count]);
+    }
+}
+
+#[test]
+fn written_class_files_match_the_originals() {
+    initialize();
+    for entry in fs::read_dir("./test_class_files").unwrap() {
+        let file = entry.unwrap().path();
+        if file.extension().is_none_or(|e| e != "class") {
+            continue;
+        }
+        let bytes = fs::read(&file).unwrap();
+        for parsing in [class_file::Parsing::Eager, class_file::Parsing::Lazy] {
+            let cf = class_file::read_class_bytes_with(bytes.clone(), parsing).unwrap();
+            assert!(
+                cf.to_bytes().unwrap() == bytes,
+                "{} ({:?})",
+                file.display(),
+                parsing
+            );
+        }
+    }
+}
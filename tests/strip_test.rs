@@ -0,0 +1,158 @@
+mod common;
+
+use common::initialize;
+use jaust::class_file;
+
+use text_diff::assert_diff;
+
+use std::fs;
+use std::process::Command;
+
+#[test]
+fn stripped_classes_verify_and_run() {
+    initialize();
+    let mut class_path = class_file::ClassPath::new();
+    class_path
+        .add(std::path::Path::new("./test_class_files"))
+        .unwrap();
+    class_path.add_jdk().unwrap();
+
+    let dir = std::env::temp_dir().join("jaust_stripped");
+    fs::remove_dir_all(&dir).unwrap_or(());
+    fs::create_dir_all(&dir).unwrap();
+    for entry in fs::read_dir("./test_class_files").unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_none_or(|e| e != "class") {
+            continue;
+        }
+        let before = fs::metadata(&path).unwrap().len() as usize;
+        let mut cf = class_file::read_class_file(path.to_str().unwrap()).unwrap();
+        class_file::strip(&mut cf, class_file::DEBUG_ATTRIBUTES).unwrap();
+        let errors = class_file::verify(&cf, &class_path);
+        assert!(errors.is_empty(), "{}: {:?}", path.display(), errors);
+        assert!(class_file::check_format(&cf).is_empty());
+        // the names of the removed attributes went with them
+        for index in 1..=cf.constant_pool.len() as u16 {
+            assert_ne!(cf.constant_pool.utf8(index).ok(), Some("LineNumberTable"));
+            assert_ne!(cf.constant_pool.utf8(index).ok(), Some("SourceFile"));
+        }
+        let bytes = cf.to_bytes().unwrap();
+        assert!(bytes.len() < before, "{}", path.display());
+        fs::write(dir.join(path.file_name().unwrap()), bytes).unwrap();
+    }
+
+    // the visible parameter annotations and annotation defaults point into the new pool
+    let output = Command::new("java")
+        .arg("-cp")
+        .arg(&dir)
+        .arg("StripTest")
+        .output()
+        .expect("failed to execute java");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8(output.stderr).unwrap()
+    );
+    assert_diff(
+        "a,b\nString PARAMETER\n",
+        &String::from_utf8(output.stdout).unwrap(),
+        "",
+        0,
+    );
+
+    // the invisible annotations that are kept are renumbered too
+    let mut cf = class_file::read_class_file("./test_class_files/StripTest.class").unwrap();
+    class_file::strip(&mut cf, &["LineNumberTable", "SourceFile"]).unwrap();
+    let stripped = dir.join("StripTest.class");
+    class_file::write_class_file(&cf, stripped.to_str().unwrap()).unwrap();
+    let javap = |file: &str| {
+        let output = Command::new("javap")
+            .args(["-v", "-p", file])
+            .output()
+            .unwrap();
+        let out = String::from_utf8(output.stdout).unwrap();
+        // the type annotation targets and the annotations as javap resolves them,
+        // without the pool indices
+        out.lines()
+            .filter_map(|l| match l.split_once("(): ") {
+                Some((_, target)) => Some(target.to_string()),
+                None if l.contains("StripTest$") && !l.contains('#') => Some(l.trim().to_string()),
+                None => None,
+            })
+            .collect::<Vec<_>>()
+    };
+    let expected = javap("./test_class_files/StripTest.class");
+    assert!(expected.iter().any(|l| l.contains("LOCAL_VARIABLE")));
+    assert_eq!(javap(stripped.to_str().unwrap()), expected);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_jaust"))
+        .args([
+            "strip",
+            "--attributes",
+            "Code",
+            "./test_class_files/C.class",
+        ])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+}
+
+#[test]
+fn corrupt_classes_are_copied_unchanged() {
+    use std::io::{Read, Write};
+
+    initialize();
+    let class = fs::read("./test_class_files/C.class").unwrap();
+    // #1, the this_class, names the Utf8 entry at bytes 11 and 12
+    let mut bad_class_name = class.clone();
+    bad_class_name[11..13].copy_from_slice(&[0xff, 0xf0]);
+    // the name index of the SourceFile attribute the class ends with
+    let mut bad_attribute_name = class.clone();
+    let end = class.len();
+    bad_attribute_name[end - 8..end - 6].copy_from_slice(&[0xff, 0xff]);
+    for bytes in [&bad_class_name, &bad_attribute_name] {
+        let mut cf = class_file::read_class_bytes(bytes.clone()).unwrap();
+        assert!(class_file::strip(&mut cf, class_file::DEBUG_ATTRIBUTES).is_err());
+    }
+
+    let dir = std::env::temp_dir().join("jaust_stripped_jar");
+    fs::remove_dir_all(&dir).unwrap_or(());
+    fs::create_dir_all(dir.join("out")).unwrap();
+    let jar = dir.join("corrupt.jar");
+    let mut writer = zip::ZipWriter::new(fs::File::create(&jar).unwrap());
+    let entries = [
+        ("C.class", &class),
+        ("BadClassName.class", &bad_class_name),
+        ("BadAttributeName.class", &bad_attribute_name),
+    ];
+    for (name, bytes) in entries {
+        writer
+            .start_file(name, zip::write::SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(bytes).unwrap();
+    }
+    writer.finish().unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_jaust"))
+        .arg("strip")
+        .arg("-d")
+        .arg(dir.join("out"))
+        .arg(&jar)
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(output.status.success(), "{}", stderr);
+    assert_eq!(stderr.matches("copied unchanged").count(), 2, "{}", stderr);
+
+    let mut stripped =
+        zip::ZipArchive::new(fs::File::open(dir.join("out/corrupt.jar")).unwrap()).unwrap();
+    for (name, bytes) in entries {
+        let mut entry = stripped.by_name(name).unwrap();
+        let mut content = Vec::new();
+        entry.read_to_end(&mut content).unwrap();
+        match name {
+            "C.class" => assert!(content.len() < bytes.len()),
+            _ => assert_eq!(&content, bytes, "{}", name),
+        }
+    }
+}
@@ -0,0 +1,138 @@
+mod common;
+
+use common::initialize;
+use jaust::class_file;
+
+use text_diff::assert_diff;
+
+use std::fs;
+use std::process::Command;
+
+#[test]
+fn transformed_classes_verify_and_run() {
+    use class_file::builder::{ClassBuilder, CodeBuilder, Constant, ACC_PRIVATE, ACC_STATIC};
+    use class_file::transform::{
+        self, ClassVisitor, Instruction, Member, MethodVisit, MethodVisitor,
+    };
+    use class_file::{ByteCode, ConstantPool};
+
+    initialize();
+    let mut class_path = class_file::ClassPath::new();
+    class_path
+        .add(std::path::Path::new("./test_class_files"))
+        .unwrap();
+    class_path.add_jdk().unwrap();
+
+    // code that goes through the visitors unchanged is laid out again and still verifies
+    struct Identity;
+    impl ClassVisitor for Identity {
+        fn visit_method(&mut self, _method: &mut Member) -> MethodVisit<'_> {
+            MethodVisit::Visit(Box::new(Identity))
+        }
+    }
+    impl MethodVisitor for Identity {}
+    for entry in fs::read_dir("./test_class_files").unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_none_or(|e| e != "class") {
+            continue;
+        }
+        let cf = class_file::read_class_file(path.to_str().unwrap()).unwrap();
+        let cf = transform::transform(cf, &mut Identity, &class_path).unwrap();
+        let errors = class_file::verify(&cf, &class_path);
+        assert!(errors.is_empty(), "{}: {:?}", path.display(), errors);
+    }
+
+    // removes the calls to log and times the other methods with probe
+    struct Probes;
+    struct Probe {
+        name: String,
+        start: u16,
+    }
+    impl ClassVisitor for Probes {
+        fn visit_method(&mut self, method: &mut Member) -> MethodVisit<'_> {
+            match method.name.as_str() {
+                "log" => MethodVisit::Remove,
+                "<init>" | "main" => MethodVisit::Keep,
+                name => MethodVisit::Visit(Box::new(Probe {
+                    name: name.to_string(),
+                    start: 0,
+                })),
+            }
+        }
+
+        fn visit_end(&mut self, class: ClassBuilder) -> ClassBuilder {
+            let mut probe = CodeBuilder::new();
+            let done = probe.new_label();
+            probe
+                .getstatic("java/lang/System", "out", "Ljava/io/PrintStream;")
+                .aload(0)
+                .invokevirtual("java/io/PrintStream", "println", "(Ljava/lang/String;)V")
+                .invokestatic("java/lang/System", "nanoTime", "()J")
+                .lload(1)
+                .lcmp()
+                .ifge(done)
+                .new_("java/lang/AssertionError")
+                .dup()
+                .invokespecial("java/lang/AssertionError", "<init>", "()V")
+                .athrow()
+                .place(done)
+                .return_();
+            class.method(
+                ACC_PRIVATE | ACC_STATIC,
+                "probe",
+                "(Ljava/lang/String;J)V",
+                probe,
+            )
+        }
+    }
+    impl MethodVisitor for Probe {
+        fn visit_code(&mut self, code: &mut CodeBuilder, max_locals: u16) {
+            self.start = max_locals;
+            code.invokestatic("java/lang/System", "nanoTime", "()J")
+                .lstore(max_locals);
+        }
+
+        fn visit_instruction(
+            &mut self,
+            code: &mut CodeBuilder,
+            instruction: Instruction,
+            cp: &ConstantPool,
+        ) {
+            // log takes a String, it is popped with the call
+            if instruction.method_ref(cp).is_some_and(|m| m.name == "log") {
+                code.pop();
+                return;
+            }
+            if let Instruction::Plain(ByteCode::IReturn | ByteCode::AReturn) = instruction {
+                code.ldc(Constant::String(self.name.clone()))
+                    .lload(self.start)
+                    .invokestatic("TransformTest", "probe", "(Ljava/lang/String;J)V");
+            }
+            code.emit(instruction);
+        }
+    }
+
+    let cf = class_file::read_class_file("./test_class_files/TransformTest.class").unwrap();
+    let cf = transform::transform(cf, &mut Probes, &class_path).unwrap();
+    let errors = class_file::verify(&cf, &class_path);
+    assert!(errors.is_empty(), "{:?}", errors);
+
+    let dir = std::env::temp_dir().join("jaust_transformed");
+    fs::remove_dir_all(&dir).unwrap_or(());
+    fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("TransformTest.class");
+    class_file::write_class_file(&cf, file.to_str().unwrap()).unwrap();
+    let output = Command::new("java")
+        .arg("-cp")
+        .arg(&dir)
+        .arg("TransformTest")
+        .output()
+        .expect("failed to execute java");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8(output.stderr).unwrap()
+    );
+    let expected = "sum\n55\ndivide\n-1\ndescribe\ndescribe\none other\n";
+    assert_diff(expected, &String::from_utf8(output.stdout).unwrap(), "", 0);
+}
@@ -0,0 +1,121 @@
+mod common;
+
+use common::initialize;
+use jaust::class_file;
+
+use std::fs;
+use std::process::Command;
+
+#[test]
+fn verify_class_files() {
+    initialize();
+    let mut class_path = class_file::ClassPath::new();
+    class_path
+        .add(std::path::Path::new("./test_class_files"))
+        .unwrap();
+    class_path.add_jdk().unwrap();
+
+    for entry in fs::read_dir("./test_class_files").unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_none_or(|e| e != "class") {
+            continue;
+        }
+        let cf = class_file::read_class_file(path.to_str().unwrap()).unwrap();
+        let errors = class_file::verify(&cf, &class_path);
+        assert!(errors.is_empty(), "{}: {:?}", path.display(), errors);
+    }
+
+    // without the hierarchy the frame merges Integer and Long to Object, not Number
+    let mut cf = class_file::read_class_file("./test_class_files/StackMapTest.class").unwrap();
+    cf.update_frames(&class_file::ClassHierarchyMap::new())
+        .unwrap();
+    let errors = class_file::verify(&cf, &class_path);
+    assert_eq!(errors.len(), 1);
+    let error = &errors[0];
+    assert_eq!(error.method.as_deref(), Some("pick(Z)Ljava/lang/Number;"));
+    assert_eq!(error.pc, Some(19));
+    assert_eq!(error.expected.as_deref(), Some("class java/lang/Number"));
+    assert_eq!(error.actual.as_deref(), Some("class java/lang/Object"));
+    assert_eq!(
+        error.to_string(),
+        "StackMapTest.pick(Z)Ljava/lang/Number; at pc 19: Bad type on operand stack \
+         (expected class java/lang/Number, found class java/lang/Object)"
+    );
+
+    // with the JDK on the class path the frames are computed like javac does
+    let mut cf = class_file::read_class_file("./test_class_files/StackMapTest.class").unwrap();
+    cf.update_frames(&class_path).unwrap();
+    assert!(class_file::verify(&cf, &class_path).is_empty());
+}
+
+#[test]
+fn check_format_reports_all_violations() {
+    initialize();
+    for entry in fs::read_dir("./test_class_files").unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_none_or(|e| e != "class") {
+            continue;
+        }
+        let cf = class_file::read_class_file(path.to_str().unwrap()).unwrap();
+        let errors = class_file::check_format(&cf);
+        assert!(errors.is_empty(), "{}: {:?}", path.display(), errors);
+    }
+
+    // C.class ends with its only class attribute: SourceFile, u4 length 2, u2 sourcefile_index
+    let mut bytes = fs::read("./test_class_files/C.class").unwrap();
+    let end = bytes.len();
+    bytes[end - 3] = 1; // attribute_length
+    bytes[end - 1] = 1; // sourcefile_index, #1 is a Class entry
+    let cf = class_file::read_class_bytes(bytes.clone()).unwrap();
+    let errors = class_file::check_format(&cf)
+        .iter()
+        .map(|e| e.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        errors,
+        vec![
+            "class: attribute_length of SourceFile is 1 but its content is 2 bytes",
+            "class, attribute SourceFile: #1 must be a Utf8 entry, not Class",
+        ]
+    );
+
+    let mut extra = fs::read("./test_class_files/C.class").unwrap();
+    extra.push(0);
+    assert!(class_file::read_class_bytes(extra).is_err());
+
+    // an invalid super class of an interface is reported, not followed
+    let mut cf = class_file::read_class_file("./test_class_files/C.class").unwrap();
+    cf.super_class = cf.constant_pool.len() as u16 + 1;
+    let super_errors = class_file::check_format(&cf);
+    assert_eq!(super_errors.len(), 1);
+    assert!(super_errors[0].to_string().starts_with("super_class: "));
+
+    // corrupt class files are errors, not panics, e.g. a StackMapTable frame type of 200
+    for class in ["C", "StackMapTest"] {
+        let bytes = fs::read(format!("./test_class_files/{}.class", class)).unwrap();
+        for i in 8..bytes.len() {
+            for value in [0, 200, 0xff] {
+                let mut bytes = bytes.clone();
+                bytes[i] = value;
+                if let Ok(cf) = class_file::read_class_bytes(bytes) {
+                    class_file::check_format(&cf);
+                }
+            }
+        }
+    }
+
+    let file = std::env::temp_dir().join("jaust_lint_class_C.class");
+    fs::write(&file, bytes).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_jaust"))
+        .args([
+            "lint-class",
+            "./test_class_files/C.class",
+            file.to_str().unwrap(),
+        ])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout.lines().count(), errors.len());
+    assert!(stdout.starts_with(&format!("{}: class: ", file.display())));
+}